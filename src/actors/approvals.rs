@@ -0,0 +1,242 @@
+//! Approvals worker: polls pending requests and requests awaiting approval.
+//!
+//! The user's own requests also give their scheduled activations.
+//!
+//! Runs apart from the PIM actor's role refresh, so it can poll often with
+//! a few cheap list calls and never delays activations.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use super::pim::PimMessage;
+use super::Envelope;
+use crate::auth::oauth::OAuth2Client;
+use crate::auth::token_cache::{AccountTokenCache, TokenOwner};
+use crate::error::PimError;
+use crate::keychain;
+use crate::menubar::state::{get_app_state, AuthState};
+use crate::menubar::updates;
+use crate::notifications;
+use crate::pim::{ApprovalCounts, MyRequests, PimClient, Scope};
+
+/// Interval between polls.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Fetch a new Management API token this long before the cached one expires.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::minutes(5);
+
+/// Keeps the approval counts in `PimState` current while signed in.
+pub struct ApprovalsWorker {
+    oauth_client: Arc<OAuth2Client>,
+    pim_client: Arc<PimClient>,
+    pim_tx: mpsc::Sender<Envelope<PimMessage>>,
+
+    /// Cached Management API token, bound to the account it was issued to.
+    token: AccountTokenCache,
+    /// Object ID of the account the last counts belong to.
+    account_id: Option<String>,
+    /// Counts from the last successful poll.
+    last: Option<ApprovalCounts>,
+}
+
+impl ApprovalsWorker {
+    /// Create the worker.
+    pub fn new(
+        oauth_client: Arc<OAuth2Client>,
+        pim_client: Arc<PimClient>,
+        pim_tx: mpsc::Sender<Envelope<PimMessage>>,
+    ) -> Self {
+        Self {
+            oauth_client,
+            pim_client,
+            pim_tx,
+            token: AccountTokenCache::default(),
+            account_id: None,
+            last: None,
+        }
+    }
+
+    /// Poll until the runtime shuts down.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.poll().await;
+        }
+    }
+
+    async fn poll(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let auth_state = state.get_auth_state();
+        if auth_state == AuthState::Offline {
+            // Keep the last counts until Azure can be reached again
+            return;
+        }
+        if !auth_state.is_signed_in() {
+            // Counts and token belong to the signed-in user
+            self.token.clear();
+            self.account_id = None;
+            if self.last.take().is_some() {
+                updates::update_pim_approvals(ApprovalCounts::default());
+                updates::update_pim_pending_requests(Vec::new());
+                updates::update_pim_approval_requests(Vec::new());
+                updates::update_pim_scheduled_activations(Vec::new());
+            }
+            return;
+        }
+
+        let Some(account_id) = state.get_user_info().map(|u| u.user_id) else {
+            return;
+        };
+        if self.account_id.as_deref() != Some(account_id.as_str()) {
+            // Another account signed in between polls; its counts start over
+            self.last = None;
+            self.account_id = Some(account_id.clone());
+        }
+        let owner = TokenOwner {
+            account_id,
+            generation: self.oauth_client.token_generation(),
+        };
+        let token = match self.management_token(owner).await {
+            Ok(token) => token,
+            Err(e) => {
+                debug!("Skipping approvals poll, no Management API token: {}", e);
+                return;
+            }
+        };
+
+        // Schedule requests can only be listed per scope
+        let pim_state = state.get_pim_state();
+        let mut scopes: Vec<Scope> = pim_state
+            .eligible_roles
+            .into_iter()
+            .map(|role| role.scope)
+            .collect();
+        scopes.sort();
+        scopes.dedup();
+
+        let MyRequests { pending, scheduled } =
+            match self.pim_client.list_my_requests(&token, &scopes).await {
+                Ok(mine) => mine,
+                Err(e) => return self.poll_failed(e),
+            };
+        // Requests to review are for other users' roles, so look in every subscription
+        let subscription_scopes: Vec<Scope> = pim_state
+            .subscription_names
+            .sorted()
+            .into_iter()
+            .filter_map(|(id, _)| format!("/subscriptions/{}", id).parse().ok())
+            .collect();
+        let to_review = match self
+            .pim_client
+            .list_requests_awaiting_my_approval(&token, &subscription_scopes)
+            .await
+        {
+            Ok(requests) => requests,
+            Err(e) => return self.poll_failed(e),
+        };
+        let awaiting_my_approval = match self.pim_client.count_awaiting_my_approval(&token).await {
+            Ok(count) => count,
+            // Users who approve nothing may not be allowed to list approvals
+            Err(PimError::Forbidden) => 0,
+            Err(e) => return self.poll_failed(e),
+        };
+
+        let counts = ApprovalCounts {
+            my_pending: pending.len(),
+            awaiting_my_approval,
+        };
+        // Also drops requests the PIM actor added that were resolved since
+        if state.get_pim_state().pending_requests != pending {
+            updates::update_pim_pending_requests(pending);
+        }
+        if state.get_pim_state().approval_requests != to_review {
+            updates::update_pim_approval_requests(to_review);
+        }
+        let previous = state.get_pim_state().scheduled_activations;
+        if previous != scheduled {
+            // A scheduled activation that started shows up as an active assignment
+            let started = previous
+                .iter()
+                .any(|p| p.start_time <= Utc::now() && !scheduled.iter().any(|s| s.is(&p.id)));
+            updates::update_pim_scheduled_activations(scheduled);
+            if started {
+                let _ = self
+                    .pim_tx
+                    .try_send(Envelope::new(PimMessage::ReconcileAssignments));
+            }
+        }
+        if self.last != Some(counts) {
+            info!("Approval counts changed: {:?}", counts);
+            self.notify_changes(counts);
+            updates::update_pim_approvals(counts);
+        }
+        self.last = Some(counts);
+    }
+
+    fn poll_failed(&mut self, e: PimError) {
+        if matches!(e, PimError::Unauthorized) {
+            self.token.clear();
+        }
+        warn!("Approvals poll failed: {}", e);
+    }
+
+    /// Notify about new approvals and resolved requests.
+    fn notify_changes(&self, counts: ApprovalCounts) {
+        let previous = self.last.unwrap_or_default();
+
+        if counts.awaiting_my_approval > previous.awaiting_my_approval {
+            notifications::notify(
+                "PIM approval needed",
+                &awaiting_approval_text(counts.awaiting_my_approval),
+            );
+        }
+
+        if self.last.is_some() && counts.my_pending < previous.my_pending {
+            notifications::notify(
+                "PIM request resolved",
+                "A pending activation request was approved or denied.",
+            );
+            // An approved request shows up as an active assignment
+            let _ = self
+                .pim_tx
+                .try_send(Envelope::new(PimMessage::ReconcileAssignments));
+        }
+    }
+
+    /// Get a Management API token for `owner`, reusing the cached one until
+    /// shortly before it expires.
+    async fn management_token(&mut self, owner: TokenOwner) -> Result<String> {
+        if let Some(token) = self.token.get(&owner, Utc::now() + TOKEN_REFRESH_MARGIN) {
+            return Ok(token.to_string());
+        }
+
+        let refresh_token = keychain::get_refresh_token()?;
+        let response = self
+            .oauth_client
+            .get_management_token(&refresh_token)
+            .await?;
+        let expires_at = Utc::now() + Duration::seconds(response.expires_in as i64);
+        self.token
+            .store(owner, response.access_token.clone(), expires_at);
+        Ok(response.access_token)
+    }
+}
+
+/// Notification body for requests awaiting the user's approval.
+fn awaiting_approval_text(count: usize) -> String {
+    if count == 1 {
+        "1 request is waiting for your approval.".to_string()
+    } else {
+        format!("{} requests are waiting for your approval.", count)
+    }
+}
@@ -0,0 +1,1289 @@
+//! Authentication actor: sign-in flow, token refresh, and session restore.
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use objc2_foundation::MainThreadMarker;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
+
+use super::pim::PimMessage;
+use super::settings::update_settings;
+use super::{respond, Envelope};
+use crate::api::{BoxFuture, GraphApi, OAuthApi};
+use crate::auth::callback_server::{self, CallbackResult};
+use crate::auth::challenge::StepUp;
+use crate::auth::graph::UserInfo;
+use crate::auth::oauth::{parse_callback_url, scope_display_name, PkceChallenge, TokenResponse};
+use crate::auth::token_format::TokenFormat;
+use crate::auth::token_manager::{ErrorRetry, RefreshFailures, RETRY_COUNTDOWN_STEP_SECS};
+use crate::error::{AuthError, ErrorClass, KeychainError};
+use crate::health;
+use crate::keychain;
+use crate::menubar::delegate::{self, ActionOutcome};
+use crate::menubar::state::{get_app_state, AuthState, Settings};
+use crate::menubar::updates;
+use crate::notifications;
+use crate::operation::{Operation, OperationKind, Outcome};
+use crate::pim::{ActivationOptions, CacheOwner, ExpiryUrgency};
+use crate::settings::{self, DataCategory};
+
+/// Messages handled by the auth actor.
+#[derive(Debug)]
+pub enum AuthMessage {
+    /// Restore the previous session from the Keychain (sent at launch).
+    RestoreSession,
+    SignIn,
+    CancelSignIn,
+    SignOut,
+    RefreshToken,
+    /// Copy the access token to the clipboard in the given format.
+    CopyToken(TokenFormat),
+    /// Remove the stored data of the chosen categories.
+    ClearData(Vec<DataCategory>),
+    /// Consent to additional scopes without signing in again.
+    GrantConsent(Vec<String>),
+    /// Sign in with the verification an activation needs, then retry it.
+    StepUp {
+        step_up: StepUp,
+        activation: PendingActivation,
+    },
+    /// Check the health of each integration for the Status submenu.
+    CheckHealth,
+    /// Refresh the token after background refresh paused.
+    RetryRefresh,
+    /// Stop the callback server before the app quits.
+    Shutdown,
+}
+
+/// An activation to retry once a step-up sign-in completes.
+#[derive(Debug)]
+pub struct PendingActivation {
+    pub role_key: String,
+    pub justification: String,
+    pub options: ActivationOptions,
+}
+
+/// Label for reporting the outcome of a consent flow.
+const CONSENT_LABEL: &str = "Grant permissions";
+
+/// Label for reporting the outcome of a step-up flow.
+const STEP_UP_LABEL: &str = "Verify identity";
+
+/// Interval between token expiry checks.
+const TOKEN_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Interval between reconnect attempts while offline.
+const OFFLINE_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Interval between background refreshes of the user's profile.
+const PROFILE_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Interval between checks for a due error retry, also updating its countdown.
+const ERROR_RETRY_CHECK_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(RETRY_COUNTDOWN_STEP_SECS as u64);
+
+/// Where the user signs in: opens the authorization URL and receives the redirect.
+pub trait Browser: Send + Sync {
+    /// Wait for the redirect until `cancel` fires.
+    fn wait_for_callback(&self, cancel: CancellationToken) -> BoxFuture<'static, CallbackResult>;
+    /// Open the authorization URL.
+    fn open(&self, url: &str) -> std::io::Result<()>;
+}
+
+/// The default browser, redirecting to the local callback server.
+pub struct SystemBrowser;
+
+impl Browser for SystemBrowser {
+    fn wait_for_callback(&self, cancel: CancellationToken) -> BoxFuture<'static, CallbackResult> {
+        Box::pin(callback_server::run_callback_server(cancel))
+    }
+
+    fn open(&self, url: &str) -> std::io::Result<()> {
+        open::that(url)
+    }
+}
+
+/// A running OAuth callback server.
+struct CallbackServer {
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+/// Owns the in-progress OAuth flow and callback server.
+pub struct AuthActor {
+    oauth_client: Arc<dyn OAuthApi>,
+    graph_client: Arc<dyn GraphApi>,
+    browser: Arc<dyn Browser>,
+    pim_tx: mpsc::Sender<Envelope<PimMessage>>,
+    rx: mpsc::Receiver<Envelope<AuthMessage>>,
+
+    /// Channel to receive callback results from the HTTP server.
+    callback_tx: mpsc::Sender<CallbackResult>,
+    callback_rx: mpsc::Receiver<CallbackResult>,
+    /// The running callback server, if a sign-in is in progress.
+    server: Option<CallbackServer>,
+
+    /// State for in-progress OAuth flow.
+    pending_pkce: Option<PkceChallenge>,
+    pending_state: Option<String>,
+    /// Scopes being consented to, if the flow is an incremental consent.
+    pending_consent: Option<Vec<String>>,
+    /// Verification being completed and the activation waiting on it, if the
+    /// flow is a step-up.
+    pending_step_up: Option<(StepUp, PendingActivation)>,
+    /// The sign-in in progress, from opening the browser to the callback.
+    sign_in: Option<Operation>,
+    /// Whether missing scopes were already offered this session.
+    consent_offered: bool,
+    /// Consecutive background refresh failures.
+    refresh_failures: RefreshFailures,
+    /// Backoff for silently retrying a recoverable refresh error.
+    error_retry: ErrorRetry,
+}
+
+impl AuthActor {
+    /// Create the actor.
+    pub fn new(
+        oauth_client: Arc<dyn OAuthApi>,
+        graph_client: Arc<dyn GraphApi>,
+        browser: Arc<dyn Browser>,
+        pim_tx: mpsc::Sender<Envelope<PimMessage>>,
+        rx: mpsc::Receiver<Envelope<AuthMessage>>,
+    ) -> Self {
+        let (callback_tx, callback_rx) = mpsc::channel(1);
+        Self {
+            oauth_client,
+            graph_client,
+            browser,
+            pim_tx,
+            rx,
+            callback_tx,
+            callback_rx,
+            server: None,
+            pending_pkce: None,
+            pending_state: None,
+            pending_consent: None,
+            pending_step_up: None,
+            sign_in: None,
+            consent_offered: false,
+            refresh_failures: RefreshFailures::default(),
+            error_retry: ErrorRetry::default(),
+        }
+    }
+
+    /// Process messages until the channel closes.
+    pub async fn run(mut self) {
+        let mut offline_retry = tokio::time::interval(OFFLINE_RETRY_INTERVAL);
+        offline_retry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut token_check = tokio::time::interval(TOKEN_CHECK_INTERVAL);
+        token_check.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut error_retry = tokio::time::interval(ERROR_RETRY_CHECK_INTERVAL);
+        error_retry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The profile was just fetched by sign-in or restore, so skip the first tick
+        let mut profile_refresh = tokio::time::interval_at(
+            tokio::time::Instant::now() + PROFILE_REFRESH_INTERVAL,
+            PROFILE_REFRESH_INTERVAL,
+        );
+        profile_refresh.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                msg = self.rx.recv() => match msg {
+                    Some(Envelope { msg, reply }) => {
+                        let outcome = self.handle(msg).await;
+                        respond(reply, outcome);
+                    }
+                    None => break,
+                },
+                // Handle OAuth callbacks from the HTTP server
+                Some(result) = self.callback_rx.recv() => {
+                    self.handle_callback_result(result).await;
+                }
+                _ = offline_retry.tick() => self.retry_offline().await,
+                _ = token_check.tick() => self.check_token_expiry().await,
+                _ = error_retry.tick() => self.check_error_retry().await,
+                _ = profile_refresh.tick() => self.refresh_profile().await,
+            }
+        }
+        self.stop_callback_server().await;
+    }
+
+    /// Handle a message. Failures shown elsewhere in the menu report `Done`.
+    pub(super) async fn handle(&mut self, msg: AuthMessage) -> ActionOutcome {
+        match msg {
+            AuthMessage::RestoreSession => self.restore_session().await,
+            AuthMessage::SignIn => self.start_sign_in().await,
+            AuthMessage::CancelSignIn => {
+                info!("Sign-in cancelled");
+                self.reset_flow().await;
+                updates::update_signed_out();
+            }
+            AuthMessage::SignOut => {
+                info!("Signing out");
+                return self.sign_out().await;
+            }
+            AuthMessage::RefreshToken => {
+                info!("Manual token refresh requested");
+                self.refresh_now().await;
+            }
+            AuthMessage::RetryRefresh => return self.retry_refresh().await,
+            AuthMessage::GrantConsent(scopes) => return self.start_consent(scopes).await,
+            AuthMessage::StepUp {
+                step_up,
+                activation,
+            } => return self.start_step_up(step_up, activation).await,
+            AuthMessage::CopyToken(format) => return copy_token(format).await,
+            AuthMessage::CheckHealth => {
+                let token_expiry = get_app_state().and_then(|s| s.get_token_expiry());
+                let report = health::run_checks(token_expiry, self.server.is_some()).await;
+                updates::update_health(report);
+            }
+            AuthMessage::Shutdown => {
+                info!("Stopping callback server for shutdown");
+                self.stop_callback_server().await;
+            }
+            AuthMessage::ClearData(categories) => return self.clear_data(categories).await,
+        }
+        ActionOutcome::Done
+    }
+
+    /// End the session, removing tokens and user info from the Keychain.
+    ///
+    /// Favorites, settings and cached roles are kept for the next sign-in.
+    async fn sign_out(&mut self) -> ActionOutcome {
+        self.reset_flow().await;
+        self.consent_offered = false;
+        self.clear_refresh_failures();
+        self.error_retry.reset();
+        self.oauth_client.invalidate_cached_tokens().await;
+        let outcome = clear_credentials();
+        updates::update_signed_out();
+        outcome
+    }
+
+    /// Remove the stored data of the chosen categories.
+    ///
+    /// The PIM actor clears favorites, cached roles and session notes, since
+    /// it keeps its own copies of them.
+    async fn clear_data(&mut self, categories: Vec<DataCategory>) -> ActionOutcome {
+        info!("Clearing data: {:?}", categories);
+        let mut outcomes = Vec::new();
+        if categories.contains(&DataCategory::Credentials) {
+            outcomes.push(self.sign_out().await);
+        }
+        if categories.contains(&DataCategory::Settings) {
+            outcomes.push(update_settings(|s| *s = Settings::default()));
+        }
+        if categories.contains(&DataCategory::Logs) {
+            if let Err(e) = settings::clear_logs() {
+                error!("Failed to clear logs: {}", e);
+                outcomes.push(ActionOutcome::Failed(format!(
+                    "Logs could not be removed: {}",
+                    e
+                )));
+            }
+        }
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let envelope = Envelope {
+            msg: PimMessage::ClearData(categories),
+            reply: Some(reply_tx),
+        };
+        if self.pim_tx.send(envelope).await.is_ok() {
+            if let Ok(outcome) = reply_rx.await {
+                outcomes.push(outcome);
+            }
+        }
+
+        let failures: Vec<String> = outcomes
+            .into_iter()
+            .filter_map(|outcome| match outcome {
+                ActionOutcome::Failed(reason) => Some(reason),
+                _ => None,
+            })
+            .collect();
+        if failures.is_empty() {
+            ActionOutcome::Done
+        } else {
+            ActionOutcome::Failed(failures.join("; "))
+        }
+    }
+
+    /// Have the PIM actor take over its caches for this account, dropping
+    /// those of another account that signed in before.
+    async fn claim_caches(&self, user_info: &UserInfo) {
+        let owner = CacheOwner {
+            user_id: user_info.user_id.clone(),
+            tenant_id: user_info.tenant_id.clone(),
+        };
+        let _ = self
+            .pim_tx
+            .send(Envelope::new(PimMessage::ClaimCaches(owner)))
+            .await;
+    }
+
+    /// Send a message to the PIM actor once the signed-in state has been applied.
+    fn send_after_sign_in(&self, msg: PimMessage) {
+        // Queued behind update_signed_in so the PIM actor sees the new user info
+        let pim_tx = self.pim_tx.clone();
+        dispatch::Queue::main().exec_async(move || {
+            let _ = pim_tx.try_send(Envelope::new(msg));
+        });
+    }
+
+    /// Restore the previous session and apply the startup settings.
+    async fn restore_session(&mut self) {
+        let settings = get_app_state()
+            .map(|s| s.get_settings())
+            .unwrap_or_default();
+
+        let operation = Operation::start(OperationKind::Restore);
+        let result = try_restore_session(self.oauth_client.as_ref(), self.graph_client.as_ref())
+            .instrument(operation.span())
+            .await;
+        if let Ok((user_info, _)) = &result {
+            operation.record_tenant(&user_info.tenant_id);
+        }
+        operation.finish(outcome_of(&result));
+
+        match result {
+            Ok((user_info, granted)) => {
+                self.clear_refresh_failures();
+                self.claim_caches(&user_info).await;
+                self.check_granted_scopes(&granted);
+                if settings.notify_session_restored {
+                    notifications::notify(
+                        "Session restored",
+                        &format!("Signed in as {}", user_info.display_name),
+                    );
+                }
+                let has_restored_assignments = get_app_state()
+                    .is_some_and(|s| !s.get_pim_state().active_assignments.is_empty());
+                if settings.auto_load_roles {
+                    self.send_after_sign_in(PimMessage::LoadRoles);
+                } else if has_restored_assignments {
+                    self.send_after_sign_in(PimMessage::ReconcileAssignments);
+                }
+            }
+            Err(e) => match e.downcast_ref::<KeychainError>() {
+                Some(keychain_err) if keychain_err.is_retryable() => {
+                    // Tokens are still stored; the user can allow access and retry
+                    warn!(
+                        "Keychain access unavailable during restore: {}",
+                        keychain_err
+                    );
+                    updates::update_error(
+                        keychain_err.user_message().to_string(),
+                        ErrorClass::NeedsReauth,
+                    );
+                }
+                _ if is_offline(&e) => match stored_user_info() {
+                    Some(user_info) => {
+                        // Show the cached roles until Azure AD can be reached
+                        warn!("Restoring session offline: {}", e);
+                        updates::update_offline(Some(user_info));
+                    }
+                    None => {
+                        info!("Offline with no stored user info: {}", e);
+                        updates::update_signed_out();
+                    }
+                },
+                _ => {
+                    info!("No existing session to restore: {}", e);
+                    // Revert UI to signed-out state if restore fails
+                    updates::update_signed_out();
+                }
+            },
+        }
+    }
+
+    /// Refresh the access token, showing failures in the menu.
+    ///
+    /// Returns whether the refresh succeeded.
+    async fn refresh_now(&mut self) -> bool {
+        match refresh_token(self.oauth_client.as_ref()).await {
+            Ok(granted) => {
+                self.clear_refresh_failures();
+                self.check_granted_scopes(&granted);
+                true
+            }
+            Err(e) if is_offline(&e) => {
+                warn!("Token refresh failed, Azure AD unreachable: {}", e);
+                updates::update_offline(None);
+                false
+            }
+            Err(e) => {
+                error!("Token refresh failed: {}", e);
+                self.refresh_error(e);
+                false
+            }
+        }
+    }
+
+    /// Show a refresh error, retrying silently with backoff if it is recoverable.
+    fn refresh_error(&mut self, e: anyhow::Error) {
+        if is_recoverable(&e) {
+            let retry_at = self.error_retry.schedule(Utc::now());
+            info!("Recoverable refresh error, retrying at {}", retry_at);
+            updates::update_recoverable_error(e.to_string(), retry_at);
+        } else {
+            self.error_retry.reset();
+            updates::update_error(e.to_string(), ErrorClass::of(&e));
+        }
+    }
+
+    /// Retry a recoverable refresh error once its backoff has elapsed.
+    async fn check_error_retry(&mut self) {
+        if self.error_retry.next_at().is_none() {
+            return;
+        }
+        if !self.error_retry.is_due(Utc::now()) {
+            // Keep the countdown current
+            updates::rebuild_menu();
+            return;
+        }
+
+        info!("Retrying token refresh after a recoverable error");
+        match refresh_token(self.oauth_client.as_ref()).await {
+            Ok(granted) => self.error_recovered(&granted),
+            Err(e) if is_offline(&e) => {
+                warn!("Azure AD unreachable while retrying: {}", e);
+                self.error_retry.reset();
+                updates::update_offline(None);
+            }
+            Err(e) => {
+                warn!("Retrying token refresh failed: {}", e);
+                self.refresh_error(e);
+            }
+        }
+    }
+
+    /// Leave the error state after a refresh succeeded again.
+    fn error_recovered(&mut self, granted: &str) {
+        info!("Recovered from refresh error");
+        self.error_retry.reset();
+        self.clear_refresh_failures();
+        updates::update_error_recovered();
+        self.check_granted_scopes(granted);
+        self.send_after_sign_in(PimMessage::RefreshRoles);
+    }
+
+    /// Count a failed background refresh, and tell the user once if refresh pauses.
+    fn background_refresh_failed(&mut self) {
+        if self.refresh_failures.record_failure() {
+            warn!(
+                "Background token refresh paused after {} consecutive failures",
+                self.refresh_failures.count()
+            );
+            notifications::notify(
+                "Token refresh paused",
+                "Refreshing failed repeatedly. Choose Try Again Now in the menu to retry.",
+            );
+        }
+        updates::update_refresh_failures(self.refresh_failures.count());
+    }
+
+    /// Resume background refresh after a success or a new session.
+    fn clear_refresh_failures(&mut self) {
+        if self.refresh_failures.reset() {
+            info!("Background token refresh resumed");
+            updates::update_refresh_failures(0);
+        }
+    }
+
+    /// Retry a paused background refresh or a recoverable error on request.
+    async fn retry_refresh(&mut self) -> ActionOutcome {
+        info!("Retrying token refresh");
+        if self.error_retry.next_at().is_some() {
+            return match refresh_token(self.oauth_client.as_ref()).await {
+                Ok(granted) => {
+                    self.error_recovered(&granted);
+                    ActionOutcome::Succeeded("Token refreshed.".to_string())
+                }
+                Err(e) => {
+                    warn!("Retrying token refresh failed: {}", e);
+                    self.refresh_error(e);
+                    ActionOutcome::Done
+                }
+            };
+        }
+        let offline = get_app_state().is_some_and(|s| s.get_auth_state() == AuthState::Offline);
+        if !self.refresh_now().await {
+            return ActionOutcome::Failed(
+                "The token still could not be refreshed. Background refresh stays paused."
+                    .to_string(),
+            );
+        }
+        if offline {
+            info!("Back online");
+            updates::update_back_online();
+            self.send_after_sign_in(PimMessage::RefreshRoles);
+        }
+        ActionOutcome::Succeeded("Token refreshed. Background refresh resumed.".to_string())
+    }
+
+    /// Keep the token countdown current and refresh at the warning threshold if enabled.
+    async fn check_token_expiry(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        if !state.get_auth_state().is_signed_in() {
+            return;
+        }
+        let Some(expiry) = state.get_token_expiry() else {
+            return;
+        };
+
+        let settings = state.get_settings();
+        let urgency = settings.token_urgency(expiry - Utc::now());
+        // While offline, retry_offline reconnects instead
+        let refresh = settings.refresh_at_token_warning
+            && urgency != ExpiryUrgency::Normal
+            && state.get_auth_state() != AuthState::Offline
+            && !self.refresh_failures.is_degraded();
+        if refresh {
+            info!("Token reached the warning threshold, refreshing");
+            if !self.refresh_now().await {
+                self.background_refresh_failed();
+            }
+        } else {
+            // Countdowns are shown in whole minutes
+            updates::rebuild_menu();
+        }
+    }
+
+    /// Try to reconnect while offline, then refresh the roles.
+    async fn retry_offline(&mut self) {
+        let offline = get_app_state().is_some_and(|s| s.get_auth_state() == AuthState::Offline);
+        if !offline || self.refresh_failures.is_degraded() {
+            return;
+        }
+
+        match refresh_token(self.oauth_client.as_ref()).await {
+            Ok(granted) => {
+                info!("Back online");
+                self.clear_refresh_failures();
+                updates::update_back_online();
+                self.check_granted_scopes(&granted);
+                self.send_after_sign_in(PimMessage::RefreshRoles);
+            }
+            Err(e) if is_offline(&e) => {
+                debug!("Still offline: {}", e);
+                self.background_refresh_failed();
+            }
+            Err(e) => {
+                error!("Token refresh failed after reconnecting: {}", e);
+                self.background_refresh_failed();
+                self.refresh_error(e);
+            }
+        }
+    }
+
+    /// Fetch the user's profile again and apply any name, email, or tenant change.
+    async fn refresh_profile(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        if state.get_auth_state() != AuthState::SignedIn {
+            return;
+        }
+        let Some(current) = state.get_user_info() else {
+            return;
+        };
+        let token_expired = state
+            .get_token_expiry()
+            .is_none_or(|expiry| expiry <= Utc::now());
+        if token_expired && !self.refresh_now().await {
+            return;
+        }
+
+        let user_info = match fetch_user_info(self.graph_client.as_ref()).await {
+            Ok(user_info) => user_info,
+            Err(e) => {
+                // Keep showing the profile we have; the next day tries again
+                warn!("Profile refresh failed: {}", e);
+                return;
+            }
+        };
+        if user_info == current {
+            debug!("Profile unchanged");
+            return;
+        }
+
+        info!("Profile changed: {:?}", current.changed_fields(&user_info));
+        match user_info.to_json() {
+            Ok(json) => {
+                if let Err(e) = keychain::store_user_info(&json) {
+                    warn!("Failed to store refreshed user info: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to serialize refreshed user info: {}", e),
+        }
+        updates::update_user_info(user_info);
+    }
+
+    /// Start the browser sign-in flow and the local callback server.
+    async fn start_sign_in(&mut self) {
+        info!("Starting sign-in flow");
+
+        // Stop any existing callback server first so the port is free
+        self.stop_callback_server().await;
+        self.error_retry.reset();
+        self.finish_sign_in(Outcome::Cancelled);
+        self.sign_in = Some(Operation::start(OperationKind::SignIn));
+
+        updates::update_authenticating();
+
+        // Generate PKCE
+        let pkce = PkceChallenge::new();
+        let (auth_url, state) = self.oauth_client.generate_auth_url(&pkce);
+
+        // Store for callback verification
+        self.pending_pkce = Some(pkce);
+        self.pending_state = Some(state);
+
+        if let Err(e) = self.open_browser_flow(auth_url.as_str()) {
+            error!("Failed to open browser: {}", e);
+            self.stop_callback_server().await;
+            self.finish_sign_in(Outcome::Failed);
+            updates::update_error(
+                "Failed to open browser".to_string(),
+                ErrorClass::NeedsReauth,
+            );
+        }
+    }
+
+    /// Offer consent for configured scopes missing from the granted ones, once per session.
+    ///
+    /// Features the profile enables but the grant doesn't cover are logged.
+    fn check_granted_scopes(&mut self, granted: &str) {
+        // An empty list means Azure AD didn't report scopes, not that none were granted
+        if self.consent_offered || granted.trim().is_empty() {
+            return;
+        }
+        for feature in self.oauth_client.unmet_features(granted) {
+            warn!(
+                "{} unavailable: granted scopes lack {}",
+                feature.name(),
+                feature.needs_text()
+            );
+        }
+        let missing = self.oauth_client.missing_scopes(granted);
+        if !missing.is_empty() {
+            info!("Granted scopes are missing {:?}", missing);
+            self.consent_offered = true;
+            updates::offer_consent(missing);
+        }
+    }
+
+    /// Start a browser flow that consents to `scopes` only, keeping the session.
+    async fn start_consent(&mut self, scopes: Vec<String>) -> ActionOutcome {
+        info!("Starting consent flow for {:?}", scopes);
+
+        // Stop any existing callback server first so the port is free
+        self.stop_callback_server().await;
+
+        let login_hint = get_app_state()
+            .and_then(|s| s.get_user_info())
+            .map(|user| user.email);
+        let pkce = PkceChallenge::new();
+        let (auth_url, state) =
+            self.oauth_client
+                .generate_consent_url(&pkce, &scopes, login_hint.as_deref());
+
+        self.pending_pkce = Some(pkce);
+        self.pending_state = Some(state);
+        self.pending_consent = Some(scopes);
+
+        if let Err(e) = self.open_browser_flow(auth_url.as_str()) {
+            error!("Failed to open browser: {}", e);
+            self.reset_flow().await;
+            return ActionOutcome::Failed("Failed to open browser".to_string());
+        }
+        ActionOutcome::Done
+    }
+
+    /// Start a browser sign-in that satisfies `step_up`, keeping the session,
+    /// and retry `activation` once it completes.
+    async fn start_step_up(
+        &mut self,
+        step_up: StepUp,
+        activation: PendingActivation,
+    ) -> ActionOutcome {
+        info!("Starting {} sign-in for {}", step_up, activation.role_key);
+
+        // Stop any existing callback server first so the port is free
+        self.stop_callback_server().await;
+
+        let login_hint = get_app_state()
+            .and_then(|s| s.get_user_info())
+            .map(|user| user.email);
+        let pkce = PkceChallenge::new();
+        let (auth_url, state) =
+            self.oauth_client
+                .generate_step_up_url(&pkce, &step_up, login_hint.as_deref());
+
+        self.pending_pkce = Some(pkce);
+        self.pending_state = Some(state);
+        self.pending_step_up = Some((step_up, activation));
+
+        if let Err(e) = self.open_browser_flow(auth_url.as_str()) {
+            error!("Failed to open browser: {}", e);
+            self.reset_flow().await;
+            return ActionOutcome::Failed("Failed to open browser".to_string());
+        }
+        ActionOutcome::Done
+    }
+
+    /// Start the callback server and open the authorization URL in the browser.
+    fn open_browser_flow(&mut self, auth_url: &str) -> std::io::Result<()> {
+        // Start callback server
+        let cancel = CancellationToken::new();
+        let tx = self.callback_tx.clone();
+        let task = tokio::spawn({
+            let callback = self.browser.wait_for_callback(cancel.clone());
+            async move {
+                let result = callback.await;
+                // Cancellation comes from this actor, which has already reset the flow
+                if !matches!(result, CallbackResult::Cancelled) {
+                    let _ = tx.send(result).await;
+                }
+            }
+        });
+        self.server = Some(CallbackServer { cancel, task });
+
+        self.browser.open(auth_url)
+    }
+
+    pub(super) async fn handle_callback_result(&mut self, result: CallbackResult) {
+        self.server = None; // Server is done
+
+        // The user is still signed in during a step-up flow; retry the activation
+        if let Some((step_up, activation)) = self.pending_step_up.take() {
+            let outcome = match result {
+                CallbackResult::Success(url_string) => {
+                    self.finish_step_up(&url_string, step_up, activation).await
+                }
+                CallbackResult::Cancelled => return,
+                CallbackResult::Error(e) => {
+                    self.pending_pkce = None;
+                    self.pending_state = None;
+                    ActionOutcome::Failed(format!("Authentication error: {}", e))
+                }
+            };
+            if let ActionOutcome::Failed(reason) = &outcome {
+                error!("Step-up sign-in failed: {}", reason);
+                updates::show_action_outcome(STEP_UP_LABEL, &outcome);
+            }
+            return;
+        }
+
+        // The user is still signed in during a consent flow; report it separately
+        if let Some(scopes) = self.pending_consent.take() {
+            let outcome = match result {
+                CallbackResult::Success(url_string) => {
+                    self.finish_consent(&url_string, &scopes).await
+                }
+                CallbackResult::Cancelled => return,
+                CallbackResult::Error(e) => {
+                    self.pending_pkce = None;
+                    self.pending_state = None;
+                    ActionOutcome::Failed(format!("Authentication error: {}", e))
+                }
+            };
+            if let ActionOutcome::Failed(reason) = &outcome {
+                error!("Consent failed: {}", reason);
+            }
+            updates::show_action_outcome(CONSENT_LABEL, &outcome);
+            return;
+        }
+
+        match result {
+            CallbackResult::Success(url_string) => {
+                info!("Received OAuth callback from server");
+
+                let operation = self
+                    .sign_in
+                    .take()
+                    .unwrap_or_else(|| Operation::start(OperationKind::SignIn));
+                let result = handle_oauth_callback(
+                    &url_string,
+                    self.pending_pkce.take(),
+                    self.pending_state.take(),
+                    self.oauth_client.as_ref(),
+                    self.graph_client.as_ref(),
+                )
+                .instrument(operation.span())
+                .await;
+                if let Ok((user_info, _, _)) = &result {
+                    operation.record_tenant(&user_info.tenant_id);
+                }
+                operation.finish(outcome_of(&result));
+
+                match result {
+                    Ok((user_info, expires_at, granted)) => {
+                        self.clear_refresh_failures();
+                        // Tokens cached for whoever was signed in before are not reused
+                        self.oauth_client.invalidate_cached_tokens().await;
+                        // Roles cached for another account must not show up for this one
+                        self.claim_caches(&user_info).await;
+                        updates::update_signed_in(user_info, expires_at);
+                        self.check_granted_scopes(&granted);
+                        let auto_load = get_app_state()
+                            .map(|s| s.get_settings().auto_load_roles)
+                            .unwrap_or_default();
+                        if auto_load {
+                            self.send_after_sign_in(PimMessage::LoadRoles);
+                        }
+                    }
+                    Err(e) => {
+                        error!("OAuth callback error: {}", e);
+                        if let Some(AuthError::RedirectUriMismatch(_)) = e.downcast_ref() {
+                            updates::update_error(
+                                "Redirect URI not registered".to_string(),
+                                ErrorClass::of(&e),
+                            );
+                            updates::show_redirect_uri_help(
+                                self.oauth_client.redirect_uri().to_string(),
+                            );
+                        } else {
+                            updates::update_error(e.to_string(), ErrorClass::of(&e));
+                        }
+                    }
+                }
+            }
+            // Not forwarded by the server task; see start_sign_in
+            CallbackResult::Cancelled => {}
+            CallbackResult::Error(e) => {
+                error!("Callback server error: {}", e);
+                self.finish_sign_in(Outcome::Failed);
+                self.pending_pkce = None;
+                self.pending_state = None;
+                updates::update_error(
+                    format!("Authentication error: {}", e),
+                    ErrorClass::NeedsReauth,
+                );
+            }
+        }
+    }
+
+    /// Merge the grant from a consent callback into the session.
+    async fn finish_consent(&mut self, url_string: &str, scopes: &[String]) -> ActionOutcome {
+        let Some(user_info) = get_app_state().and_then(|s| s.get_user_info()) else {
+            self.pending_pkce = None;
+            self.pending_state = None;
+            return ActionOutcome::Failed("Not signed in".to_string());
+        };
+        let result = complete_consent(
+            url_string,
+            self.pending_pkce.take(),
+            self.pending_state.take(),
+            scopes,
+            self.oauth_client.as_ref(),
+            &user_info,
+        )
+        .await;
+
+        match result {
+            Ok(granted) => {
+                let missing = self.oauth_client.missing_scopes(&granted);
+                if missing.is_empty() || granted.trim().is_empty() {
+                    info!("Consent granted for {:?}", scopes);
+                    ActionOutcome::Succeeded("Additional permissions granted.".to_string())
+                } else {
+                    let names: Vec<_> = missing
+                        .iter()
+                        .map(|scope| scope_display_name(scope))
+                        .collect();
+                    ActionOutcome::Failed(format!("Not granted: {}", names.join(", ")))
+                }
+            }
+            Err(e) => ActionOutcome::Failed(e.to_string()),
+        }
+    }
+
+    /// Keep the session from a step-up callback and retry the activation
+    /// waiting on it.
+    async fn finish_step_up(
+        &mut self,
+        url_string: &str,
+        step_up: StepUp,
+        activation: PendingActivation,
+    ) -> ActionOutcome {
+        let Some(user_info) = get_app_state().and_then(|s| s.get_user_info()) else {
+            self.pending_pkce = None;
+            self.pending_state = None;
+            return ActionOutcome::Failed("Not signed in".to_string());
+        };
+        let result = complete_step_up(
+            url_string,
+            self.pending_pkce.take(),
+            self.pending_state.take(),
+            self.oauth_client.as_ref(),
+            &user_info,
+        )
+        .await;
+        if let Err(e) = result {
+            return ActionOutcome::Failed(e.to_string());
+        }
+
+        info!("Completed {}, retrying {}", step_up, activation.role_key);
+        let PendingActivation {
+            role_key,
+            justification,
+            mut options,
+        } = activation;
+        options.step_up = Some(step_up);
+        delegate::send_activate_role(role_key, justification, options);
+        ActionOutcome::Done
+    }
+
+    /// Stop the callback server and forget the pending flow.
+    async fn reset_flow(&mut self) {
+        self.stop_callback_server().await;
+        self.pending_pkce = None;
+        self.pending_state = None;
+        self.pending_consent = None;
+        self.pending_step_up = None;
+        self.finish_sign_in(Outcome::Cancelled);
+    }
+
+    /// End the sign-in in progress, if any.
+    fn finish_sign_in(&mut self, outcome: Outcome) {
+        if let Some(operation) = self.sign_in.take() {
+            operation.finish(outcome);
+        }
+    }
+
+    /// Stop the callback server, if running, and wait until its port is released.
+    async fn stop_callback_server(&mut self) {
+        if let Some(server) = self.server.take() {
+            server.cancel.cancel();
+            if let Err(e) = server.task.await {
+                error!("Callback server task failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Remove tokens and user info from the Keychain.
+fn clear_credentials() -> ActionOutcome {
+    match keychain::delete_all() {
+        Ok(()) => ActionOutcome::Done,
+        Err(e) => {
+            error!("Failed to clear keychain: {}", e);
+            ActionOutcome::Failed(e.user_message().to_string())
+        }
+    }
+}
+
+/// Copy the access token to the clipboard on the main thread.
+async fn copy_token(format: TokenFormat) -> ActionOutcome {
+    let (tx, rx) = oneshot::channel();
+    dispatch::Queue::main().exec_async(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            let _ = tx.send(delegate::copy_token_to_clipboard(mtm, &format));
+        }
+    });
+
+    match rx.await {
+        Ok(Ok(())) => {
+            delegate::schedule_clipboard_clear();
+            ActionOutcome::Succeeded("Copied. The clipboard is cleared in 2 minutes.".to_string())
+        }
+        Ok(Err(e)) => {
+            error!("Failed to get access token: {}", e);
+            let reason = match &e {
+                KeychainError::NotFound => "No access token available. Sign in again.",
+                e => e.user_message(),
+            };
+            ActionOutcome::Failed(reason.to_string())
+        }
+        Err(_) => ActionOutcome::Failed("The clipboard could not be updated.".to_string()),
+    }
+}
+
+/// Whether an error means Azure AD could not be reached.
+fn is_offline(e: &anyhow::Error) -> bool {
+    e.chain()
+        .any(|cause| matches!(cause.downcast_ref(), Some(AuthError::Network(_))))
+}
+
+/// The outcome logged for an operation's result.
+fn outcome_of<T>(result: &Result<T>) -> Outcome {
+    match result {
+        Ok(_) => Outcome::Succeeded,
+        Err(e) if is_offline(e) => Outcome::Offline,
+        Err(_) => Outcome::Failed,
+    }
+}
+
+/// Whether an error passes on its own, so retrying beats an interactive sign-in.
+fn is_recoverable(e: &anyhow::Error) -> bool {
+    ErrorClass::of(e) == ErrorClass::Transient
+}
+
+/// User info stored by the last successful sign-in.
+fn stored_user_info() -> Option<UserInfo> {
+    let json = keychain::get_user_info().ok()?;
+    UserInfo::from_json(&json).ok()
+}
+
+/// Fetch the user's profile and organization with the stored access token.
+async fn fetch_user_info(graph_client: &dyn GraphApi) -> Result<UserInfo> {
+    let access_token = keychain::get_access_token()?;
+    let user_profile = graph_client
+        .get_user_profile(&access_token)
+        .await
+        .context("Failed to fetch user profile")?;
+    let organization = graph_client
+        .get_organization(&access_token)
+        .await
+        .context("Failed to fetch organization")?;
+    Ok(UserInfo::from_profile_and_org(user_profile, organization))
+}
+
+/// Try to restore a previous session from the Keychain.
+///
+/// Returns the restored user info and the granted scopes on success.
+async fn try_restore_session(
+    oauth_client: &dyn OAuthApi,
+    graph_client: &dyn GraphApi,
+) -> Result<(UserInfo, String)> {
+    info!("Attempting to restore previous session");
+
+    // Check for existing refresh token BEFORE updating UI
+    let refresh_token = keychain::get_refresh_token().context("No refresh token found")?;
+
+    // Only show authenticating state after confirming we have a token to restore
+    updates::update_authenticating();
+
+    // Try to refresh the access token
+    let token_response = oauth_client
+        .refresh_token(&refresh_token)
+        .await
+        .context("Failed to refresh token")?;
+
+    // Store new tokens
+    keychain::store_access_token(&token_response.access_token)?;
+    if let Some(new_refresh) = &token_response.refresh_token {
+        keychain::store_refresh_token(new_refresh)?;
+    }
+
+    // Calculate expiry
+    let expires_at = Utc::now() + Duration::seconds(token_response.expires_in as i64);
+    keychain::store_token_expiry(&expires_at.to_rfc3339())?;
+
+    // Fetch user info
+    let user_profile = graph_client
+        .get_user_profile(&token_response.access_token)
+        .await
+        .context("Failed to fetch user profile")?;
+
+    let organization = graph_client
+        .get_organization(&token_response.access_token)
+        .await
+        .context("Failed to fetch organization")?;
+
+    let user_info = UserInfo::from_profile_and_org(user_profile, organization);
+
+    // Store user info
+    keychain::store_user_info(&user_info.to_json()?)?;
+
+    // Update UI
+    updates::update_signed_in(user_info.clone(), expires_at);
+
+    info!("Session restored successfully");
+    Ok((user_info, token_response.granted_scopes()))
+}
+
+/// Handle an OAuth callback URL.
+async fn handle_oauth_callback(
+    url_string: &str,
+    pkce: Option<PkceChallenge>,
+    expected_state: Option<String>,
+    oauth_client: &dyn OAuthApi,
+    graph_client: &dyn GraphApi,
+) -> Result<(UserInfo, DateTime<Utc>, String)> {
+    // Parse the callback URL
+    let (code, state) = parse_callback_url(url_string)?;
+
+    // Verify state
+    if expected_state.as_ref() != Some(&state) {
+        anyhow::bail!("State mismatch - possible CSRF attack");
+    }
+
+    // Get PKCE verifier
+    let pkce = pkce.ok_or_else(|| anyhow::anyhow!("No pending PKCE challenge"))?;
+
+    // Exchange code for tokens
+    let token_response = oauth_client
+        .exchange_code(&code, &pkce.verifier)
+        .await
+        .context("Failed to exchange authorization code")?;
+
+    // Store tokens
+    keychain::store_access_token(&token_response.access_token)?;
+    if let Some(refresh_token) = &token_response.refresh_token {
+        keychain::store_refresh_token(refresh_token)?;
+    }
+
+    // Calculate expiry
+    let expires_at = Utc::now() + Duration::seconds(token_response.expires_in as i64);
+    keychain::store_token_expiry(&expires_at.to_rfc3339())?;
+
+    // Fetch user info
+    let user_profile = graph_client
+        .get_user_profile(&token_response.access_token)
+        .await
+        .context("Failed to fetch user profile")?;
+
+    let organization = graph_client
+        .get_organization(&token_response.access_token)
+        .await
+        .context("Failed to fetch organization")?;
+
+    let user_info = UserInfo::from_profile_and_org(user_profile, organization);
+
+    // Store user info
+    keychain::store_user_info(&user_info.to_json()?)?;
+
+    info!("Sign-in successful: {}", user_info.display_name);
+
+    Ok((user_info, expires_at, token_response.granted_scopes()))
+}
+
+/// Complete an incremental consent flow and merge the new grant.
+///
+/// Returns the scopes granted after the merge.
+async fn complete_consent(
+    url_string: &str,
+    pkce: Option<PkceChallenge>,
+    expected_state: Option<String>,
+    scopes: &[String],
+    oauth_client: &dyn OAuthApi,
+    user_info: &UserInfo,
+) -> Result<String> {
+    let (code, state) = parse_callback_url(url_string)?;
+
+    // Verify state
+    if expected_state.as_ref() != Some(&state) {
+        anyhow::bail!("State mismatch - possible CSRF attack");
+    }
+
+    let pkce = pkce.ok_or_else(|| anyhow::anyhow!("No pending PKCE challenge"))?;
+
+    let token_response = oauth_client
+        .exchange_consent_code(&code, &pkce.verifier, scopes)
+        .await
+        .context("Failed to exchange authorization code")?;
+
+    // A refresh token of another account must not replace the signed-in one's
+    match same_account(&token_response, user_info) {
+        Some(true) => {}
+        Some(false) => anyhow::bail!(
+            "Consented with another account; sign in as {} to grant the permissions",
+            user_info.email
+        ),
+        None => anyhow::bail!("Could not tell which account consented; nothing was granted"),
+    }
+
+    // The new refresh token carries the existing grant plus the new scopes
+    if let Some(refresh_token) = &token_response.refresh_token {
+        keychain::store_refresh_token(refresh_token)?;
+    }
+
+    // Refresh to get an access token with the merged grant
+    refresh_token(oauth_client).await
+}
+
+/// Complete a step-up flow: verify the callback and keep its refresh token,
+/// whose session now carries the verification.
+async fn complete_step_up(
+    url_string: &str,
+    pkce: Option<PkceChallenge>,
+    expected_state: Option<String>,
+    oauth_client: &dyn OAuthApi,
+    user_info: &UserInfo,
+) -> Result<()> {
+    let (code, state) = parse_callback_url(url_string)?;
+
+    // Verify state
+    if expected_state.as_ref() != Some(&state) {
+        anyhow::bail!("State mismatch - possible CSRF attack");
+    }
+
+    let pkce = pkce.ok_or_else(|| anyhow::anyhow!("No pending PKCE challenge"))?;
+
+    let token_response = oauth_client
+        .exchange_step_up_code(&code, &pkce.verifier)
+        .await
+        .context("Failed to exchange authorization code")?;
+
+    // A refresh token of another account must not replace the signed-in one's
+    match same_account(&token_response, user_info) {
+        Some(true) => {}
+        Some(false) => anyhow::bail!(
+            "Verified with another account; sign in as {} to activate the role",
+            user_info.email
+        ),
+        None => anyhow::bail!("Could not tell which account verified; the role was not activated"),
+    }
+
+    if let Some(refresh_token) = &token_response.refresh_token {
+        keychain::store_refresh_token(refresh_token)?;
+    }
+    Ok(())
+}
+
+/// Whether tokens from a browser flow were issued to the signed-in account.
+///
+/// `None` if the response doesn't tell which account it belongs to.
+fn same_account(token_response: &TokenResponse, user_info: &UserInfo) -> Option<bool> {
+    let (user_id, tenant_id) = token_response.account()?;
+    Some(user_id == user_info.user_id && tenant_id.eq_ignore_ascii_case(&user_info.tenant_id))
+}
+
+/// Refresh the access token in a `refresh` operation span.
+///
+/// Returns the granted scopes.
+async fn refresh_token(oauth_client: &dyn OAuthApi) -> Result<String> {
+    let operation = Operation::start(OperationKind::Refresh);
+    let result = redeem_refresh_token(oauth_client)
+        .instrument(operation.span())
+        .await;
+    operation.finish(outcome_of(&result));
+    result
+}
+
+/// Redeem the stored refresh token and store the new tokens.
+///
+/// Returns the granted scopes.
+async fn redeem_refresh_token(oauth_client: &dyn OAuthApi) -> Result<String> {
+    let refresh_token = keychain::get_refresh_token()?;
+
+    let token_response = oauth_client
+        .refresh_token(&refresh_token)
+        .await
+        .context("Token refresh failed")?;
+
+    // Store new tokens
+    keychain::store_access_token(&token_response.access_token)?;
+    if let Some(new_refresh) = &token_response.refresh_token {
+        keychain::store_refresh_token(new_refresh)?;
+    }
+
+    // Calculate and store expiry
+    let expires_at = Utc::now() + Duration::seconds(token_response.expires_in as i64);
+    keychain::store_token_expiry(&expires_at.to_rfc3339())?;
+
+    // Update UI
+    updates::update_token_expiry(expires_at);
+
+    info!("Token refreshed, expires at {}", expires_at);
+    Ok(token_response.granted_scopes())
+}
@@ -0,0 +1,229 @@
+//! Heartbeat worker: reports that the app is running for fleet monitoring.
+//!
+//! Organizations deploying the app can set `HeartbeatFile` and/or
+//! `HeartbeatURL` in a configuration profile. The worker then periodically
+//! writes a small JSON status to the file or POSTs it to the URL. The status
+//! holds the app version and sign-in state only, never tokens or user
+//! details. Monitors can treat a heartbeat older than a few intervals as the
+//! app not running.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::menubar::state::{get_app_state, AuthState};
+use crate::settings;
+
+/// Interval between heartbeats unless the profile sets one.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Shortest interval accepted from the profile.
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout for posting a heartbeat.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and how often heartbeats are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    pub file: Option<PathBuf>,
+    pub url: Option<String>,
+    pub interval: Duration,
+}
+
+impl HeartbeatConfig {
+    /// The heartbeat configured by the organization's profile, if any.
+    pub fn managed() -> Option<Self> {
+        let (file, url, interval_secs) = settings::managed_heartbeat()?;
+        Some(Self::new(file, url, interval_secs))
+    }
+
+    /// A configuration with the interval clamped to [`MIN_INTERVAL`].
+    fn new(file: Option<PathBuf>, url: Option<String>, interval_secs: Option<u64>) -> Self {
+        let interval = interval_secs
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_INTERVAL)
+            .max(MIN_INTERVAL);
+        Self {
+            file,
+            url,
+            interval,
+        }
+    }
+}
+
+/// The status written with each heartbeat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heartbeat {
+    pub version: &'static str,
+    /// "signed_in", "signed_out", "authenticating", "offline" or "error".
+    pub state: &'static str,
+    pub signed_in: bool,
+    pub active_roles: usize,
+    pub written_at: DateTime<Utc>,
+    /// Seconds until the next heartbeat, so monitors know when one is late.
+    pub interval_secs: u64,
+}
+
+impl Heartbeat {
+    /// The status for `auth_state`.
+    pub fn new(
+        auth_state: &AuthState,
+        active_roles: usize,
+        interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let state = match auth_state {
+            AuthState::SignedOut => "signed_out",
+            AuthState::Authenticating => "authenticating",
+            AuthState::SignedIn => "signed_in",
+            // Error messages can name the user or tenant, so only the kind is sent
+            AuthState::Error { .. } => "error",
+            AuthState::Offline => "offline",
+        };
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            state,
+            signed_in: auth_state.is_signed_in(),
+            active_roles,
+            written_at: now,
+            interval_secs: interval.as_secs(),
+        }
+    }
+}
+
+/// Sends heartbeats until the runtime shuts down.
+pub struct HeartbeatWorker {
+    config: HeartbeatConfig,
+    http_client: reqwest::Client,
+}
+
+impl HeartbeatWorker {
+    /// Create the worker.
+    pub fn new(config: HeartbeatConfig) -> Result<Self> {
+        let http_client = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Self {
+            config,
+            http_client,
+        })
+    }
+
+    /// Send a heartbeat every interval.
+    pub async fn run(self) {
+        info!(
+            "Heartbeat enabled every {:?} (file: {:?}, URL: {:?})",
+            self.config.interval, self.config.file, self.config.url
+        );
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            self.beat().await;
+        }
+    }
+
+    async fn beat(&self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let heartbeat = Heartbeat::new(
+            &state.get_auth_state(),
+            state.get_active_role_count(),
+            self.config.interval,
+            Utc::now(),
+        );
+
+        if let Some(path) = &self.config.file {
+            if let Err(e) = write_file(path, &heartbeat) {
+                warn!("Failed to write heartbeat file: {:#}", e);
+            }
+        }
+        if let Some(url) = &self.config.url {
+            if let Err(e) = self.post(url, &heartbeat).await {
+                warn!("Failed to post heartbeat: {:#}", e);
+            }
+        }
+    }
+
+    async fn post(&self, url: &str, heartbeat: &Heartbeat) -> Result<()> {
+        self.http_client
+            .post(url)
+            .json(heartbeat)
+            .send()
+            .await
+            .context("Request failed")?
+            .error_for_status()
+            .context("Endpoint returned an error")?;
+        Ok(())
+    }
+}
+
+/// Write `heartbeat` to `path`, replacing the file at once so readers never see half of it.
+fn write_file(path: &Path, heartbeat: &Heartbeat) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create directory")?;
+    }
+    let json = serde_json::to_string_pretty(heartbeat)?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json).context("Failed to write file")?;
+    std::fs::rename(&tmp, path).context("Failed to replace file")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_config_interval() {
+        let config = HeartbeatConfig::new(None, Some("https://example.com".into()), None);
+        assert_eq!(config.interval, DEFAULT_INTERVAL);
+        let config = HeartbeatConfig::new(None, None, Some(5));
+        assert_eq!(config.interval, MIN_INTERVAL);
+        let config = HeartbeatConfig::new(None, None, Some(600));
+        assert_eq!(config.interval, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_heartbeat_carries_no_details() {
+        let now = Utc::now();
+        let error = AuthState::Error {
+            message: "AADSTS50076 for john@example.com".into(),
+            class: crate::error::ErrorClass::NeedsReauth,
+        };
+        let heartbeat = Heartbeat::new(&error, 0, DEFAULT_INTERVAL, now);
+        assert_eq!(heartbeat.state, "error");
+        assert!(!heartbeat.signed_in);
+        let json = serde_json::to_string(&heartbeat).unwrap();
+        assert!(!json.contains("john"));
+
+        let heartbeat = Heartbeat::new(&AuthState::SignedIn, 2, DEFAULT_INTERVAL, now);
+        assert_eq!(heartbeat.state, "signed_in");
+        assert!(heartbeat.signed_in);
+        assert_eq!(heartbeat.active_roles, 2);
+        assert_eq!(heartbeat.interval_secs, 300);
+    }
+
+    #[test]
+    fn test_heartbeat_file_is_replaced() {
+        let dir = std::env::temp_dir().join(format!("azurepim-heartbeat-{}", std::process::id()));
+        let path = dir.join("status.json");
+        let heartbeat = Heartbeat::new(&AuthState::SignedOut, 0, DEFAULT_INTERVAL, Utc::now());
+        write_file(&path, &heartbeat).unwrap();
+        write_file(&path, &heartbeat).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains("\"state\": \"signed_out\""));
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
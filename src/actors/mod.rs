@@ -0,0 +1,976 @@
+//! Background actors running on the Tokio runtime.
+//!
+//! Each actor owns its state and processes typed messages from its own
+//! channel, so a slow PIM refresh never delays sign-in or settings changes.
+//! The [`Supervisor`] routes menu actions to the actors and reports any
+//! actor that stops unexpectedly.
+
+pub mod approvals;
+pub mod auth;
+pub mod heartbeat;
+pub mod pim;
+pub mod settings;
+
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn};
+
+use crate::app::shutdown::ShutdownRequest;
+use crate::auth::graph::GraphClient;
+use crate::auth::oauth::OAuth2Client;
+use crate::error::ErrorClass;
+use crate::menubar::delegate::{ActionOutcome, ActionRequest, MenuAction};
+use crate::menubar::updates;
+use crate::pim::PimClient;
+
+pub use approvals::ApprovalsWorker;
+pub use auth::{AuthActor, AuthMessage, PendingActivation, SystemBrowser};
+pub use heartbeat::{HeartbeatConfig, HeartbeatWorker};
+pub use pim::{PimActor, PimMessage};
+pub use settings::{SettingsActor, SettingsMessage};
+
+/// Capacity of each actor's message channel.
+const CHANNEL_CAPACITY: usize = 10;
+
+/// Longest a quit waits for the actors to finish shutting down.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// API clients shared by the actors.
+pub struct Clients {
+    pub oauth: Arc<OAuth2Client>,
+    pub graph: Arc<GraphClient>,
+    pub pim: Arc<PimClient>,
+}
+
+/// An actor message with an optional channel for reporting its outcome.
+#[derive(Debug)]
+pub struct Envelope<M> {
+    pub msg: M,
+    pub reply: Option<oneshot::Sender<ActionOutcome>>,
+}
+
+impl<M> Envelope<M> {
+    /// Wrap a message whose outcome nobody waits for.
+    pub fn new(msg: M) -> Self {
+        Self { msg, reply: None }
+    }
+}
+
+/// Send an outcome to the requester, if there is one.
+fn respond(reply: Option<oneshot::Sender<ActionOutcome>>, outcome: ActionOutcome) {
+    if let Some(reply) = reply {
+        // The requester may have stopped waiting
+        let _ = reply.send(outcome);
+    }
+}
+
+/// Wait for the outcome of a menu action and show it to the user.
+async fn report_outcome(id: u64, label: &'static str, rx: oneshot::Receiver<ActionOutcome>) {
+    match rx.await {
+        Ok(outcome) => {
+            if let ActionOutcome::Failed(reason) = &outcome {
+                warn!("Action #{} ({}) failed: {}", id, label, reason);
+            }
+            updates::show_action_outcome(label, &outcome);
+        }
+        Err(_) => warn!("Action #{} ({}) finished without a result", id, label),
+    }
+}
+
+/// A menu action translated into a message for a specific actor.
+#[derive(Debug)]
+enum Routed {
+    Auth(AuthMessage),
+    Pim(PimMessage),
+    Settings(SettingsMessage),
+}
+
+impl From<MenuAction> for Routed {
+    fn from(action: MenuAction) -> Self {
+        match action {
+            MenuAction::SignIn => Self::Auth(AuthMessage::SignIn),
+            MenuAction::SignOut => Self::Auth(AuthMessage::SignOut),
+            MenuAction::RefreshToken => Self::Auth(AuthMessage::RefreshToken),
+            MenuAction::CopyToken(format) => Self::Auth(AuthMessage::CopyToken(format)),
+            MenuAction::CheckHealth => Self::Auth(AuthMessage::CheckHealth),
+            MenuAction::RetryRefresh => Self::Auth(AuthMessage::RetryRefresh),
+            MenuAction::ClearData(categories) => Self::Auth(AuthMessage::ClearData(categories)),
+            MenuAction::CancelSignIn => Self::Auth(AuthMessage::CancelSignIn),
+            MenuAction::GrantConsent { scopes } => Self::Auth(AuthMessage::GrantConsent(scopes)),
+            MenuAction::StepUpActivation {
+                step_up,
+                role_key,
+                justification,
+                options,
+            } => Self::Auth(AuthMessage::StepUp {
+                step_up,
+                activation: PendingActivation {
+                    role_key,
+                    justification,
+                    options,
+                },
+            }),
+            MenuAction::ToggleAutoLaunch(enabled) => {
+                Self::Settings(SettingsMessage::AutoLaunch(enabled))
+            }
+            MenuAction::ToggleShowExpiry(enabled) => {
+                Self::Settings(SettingsMessage::ShowExpiry(enabled))
+            }
+            MenuAction::ToggleProfileDetails(enabled) => {
+                Self::Settings(SettingsMessage::ProfileDetails(enabled))
+            }
+            MenuAction::ToggleRestoreSession(enabled) => {
+                Self::Settings(SettingsMessage::RestoreSession(enabled))
+            }
+            MenuAction::ToggleAutoLoadRoles(enabled) => {
+                Self::Settings(SettingsMessage::AutoLoadRoles(enabled))
+            }
+            MenuAction::ToggleRestoreNotification(enabled) => {
+                Self::Settings(SettingsMessage::RestoreNotification(enabled))
+            }
+            MenuAction::SetNotificationSound { event, sound } => {
+                Self::Settings(SettingsMessage::NotificationSound { event, sound })
+            }
+            MenuAction::ToggleSuccessAnimation(enabled) => {
+                Self::Settings(SettingsMessage::SuccessAnimation(enabled))
+            }
+            MenuAction::ToggleQuitWarning(enabled) => {
+                Self::Settings(SettingsMessage::QuitWarning(enabled))
+            }
+            MenuAction::ToggleCriticalFlash(enabled) => {
+                Self::Settings(SettingsMessage::CriticalFlash(enabled))
+            }
+            MenuAction::ToggleFocusBreakthrough(enabled) => {
+                Self::Settings(SettingsMessage::FocusBreakthrough(enabled))
+            }
+            MenuAction::ToggleRoleStatusItems(enabled) => {
+                Self::Settings(SettingsMessage::RoleStatusItems(enabled))
+            }
+            MenuAction::ToggleKeyVaultToken(enabled) => {
+                Self::Settings(SettingsMessage::KeyVaultToken(enabled))
+            }
+            MenuAction::ToggleExtensionAtExpiry(enabled) => {
+                Self::Settings(SettingsMessage::ExtensionAtExpiry(enabled))
+            }
+            MenuAction::SetEndOfDaySummary(time) => {
+                Self::Settings(SettingsMessage::EndOfDaySummary(time))
+            }
+            MenuAction::SetTokenWarningMinutes(minutes) => {
+                Self::Settings(SettingsMessage::TokenWarningMinutes(minutes))
+            }
+            MenuAction::SetTokenCriticalMinutes(minutes) => {
+                Self::Settings(SettingsMessage::TokenCriticalMinutes(minutes))
+            }
+            MenuAction::ToggleMenuBarCountdown(enabled) => {
+                Self::Settings(SettingsMessage::MenuBarCountdown(enabled))
+            }
+            MenuAction::ToggleRefreshAtWarning(enabled) => {
+                Self::Settings(SettingsMessage::RefreshAtWarning(enabled))
+            }
+            MenuAction::ActivateRole {
+                role_key,
+                justification,
+                options,
+            } => Self::Pim(PimMessage::ActivateRole {
+                role_key,
+                justification,
+                options,
+            }),
+            MenuAction::CheckActivation { role_key } => {
+                Self::Pim(PimMessage::CheckActivation { role_key })
+            }
+            MenuAction::ToggleFavorite { role_key } => {
+                Self::Pim(PimMessage::ToggleFavorite { role_key })
+            }
+            MenuAction::RefreshPimRoles => Self::Pim(PimMessage::RefreshRoles),
+            MenuAction::ActivatePlan { name } => Self::Pim(PimMessage::ActivatePlan { name }),
+            MenuAction::SavePlan { draft } => Self::Pim(PimMessage::SavePlan { draft }),
+            MenuAction::DeactivateRole { role_key } => {
+                Self::Pim(PimMessage::DeactivateRole { role_key })
+            }
+            MenuAction::DeactivateAll => Self::Pim(PimMessage::DeactivateAll),
+            MenuAction::ExtendRole { role_key, minutes } => {
+                Self::Pim(PimMessage::ExtendRole { role_key, minutes })
+            }
+            MenuAction::AddSessionNote {
+                assignment_id,
+                text,
+            } => Self::Pim(PimMessage::AddSessionNote {
+                assignment_id,
+                text,
+            }),
+            MenuAction::ScheduleDeactivation { role_key, at } => {
+                Self::Pim(PimMessage::ScheduleDeactivation { role_key, at })
+            }
+            MenuAction::SetDeactivationReminder { role_key, minutes } => {
+                Self::Pim(PimMessage::SetDeactivationReminder { role_key, minutes })
+            }
+            MenuAction::CancelScheduledActivation { request_id } => {
+                Self::Pim(PimMessage::CancelScheduledActivation { request_id })
+            }
+            MenuAction::SetFavoritesFile { path } => {
+                Self::Pim(PimMessage::SetFavoritesFile { path })
+            }
+            MenuAction::ToggleSkipGroupLookup(skip) => Self::Pim(PimMessage::SkipGroupLookup(skip)),
+            MenuAction::ReviewApproval {
+                request_id,
+                result,
+                justification,
+            } => Self::Pim(PimMessage::ReviewApproval {
+                request_id,
+                result,
+                justification,
+            }),
+            MenuAction::ToggleAdminView(enabled) => Self::Pim(PimMessage::SetAdminView { enabled }),
+            MenuAction::ToggleAdminViewSubscription { subscription_id } => {
+                Self::Pim(PimMessage::ToggleAdminViewSubscription { subscription_id })
+            }
+            MenuAction::SetSkipDeniedSubscriptionsHours(hours) => {
+                Self::Pim(PimMessage::SkipDeniedSubscriptions { hours })
+            }
+            MenuAction::RecheckSkippedSubscriptions => Self::Pim(PimMessage::RecheckSubscriptions),
+            MenuAction::SetRolePageSize(size) => {
+                Self::Settings(SettingsMessage::RolePageSize(size))
+            }
+            MenuAction::SkipActivationConfirmation { role_key } => {
+                Self::Pim(PimMessage::SkipActivationConfirmation { role_key })
+            }
+            MenuAction::SetRoleLabel { role_key, label } => {
+                Self::Pim(PimMessage::SetRoleLabel { role_key, label })
+            }
+            MenuAction::SetRoleDuration { role_key, minutes } => {
+                Self::Pim(PimMessage::SetRoleDuration { role_key, minutes })
+            }
+        }
+    }
+}
+
+/// Starts the actors and routes menu actions to them.
+pub struct Supervisor {
+    auth_tx: mpsc::Sender<Envelope<AuthMessage>>,
+    pim_tx: mpsc::Sender<Envelope<PimMessage>>,
+    settings_tx: mpsc::Sender<Envelope<SettingsMessage>>,
+    actors: JoinSet<&'static str>,
+}
+
+impl Supervisor {
+    /// Spawn all actors. Must be called from within the Tokio runtime.
+    pub fn start(clients: Clients) -> Self {
+        let (auth_tx, auth_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (pim_tx, pim_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (settings_tx, settings_rx) = mpsc::channel(CHANNEL_CAPACITY);
+
+        let auth = AuthActor::new(
+            clients.oauth.clone(),
+            clients.graph.clone(),
+            Arc::new(SystemBrowser),
+            pim_tx.clone(),
+            auth_rx,
+        );
+        let approvals = ApprovalsWorker::new(
+            Arc::clone(&clients.oauth),
+            Arc::clone(&clients.pim),
+            pim_tx.clone(),
+        );
+        let pim = PimActor::new(clients.oauth, clients.graph, clients.pim, pim_rx);
+        let settings = SettingsActor::new(settings_rx);
+
+        let mut actors = JoinSet::new();
+        actors.spawn(async move {
+            auth.run().await;
+            "auth"
+        });
+        actors.spawn(async move {
+            pim.run().await;
+            "PIM"
+        });
+        actors.spawn(async move {
+            settings.run().await;
+            "settings"
+        });
+        actors.spawn(async move {
+            approvals.run().await;
+            "approvals"
+        });
+        // Only spawned when configured; it never returns either
+        if let Some(config) = HeartbeatConfig::managed() {
+            match HeartbeatWorker::new(config) {
+                Ok(heartbeat) => {
+                    actors.spawn(async move {
+                        heartbeat.run().await;
+                        "heartbeat"
+                    });
+                }
+                Err(e) => error!("Heartbeat disabled: {:#}", e),
+            }
+        }
+
+        Self {
+            auth_tx,
+            pim_tx,
+            settings_tx,
+            actors,
+        }
+    }
+
+    /// Route menu actions to the actors until the action channel closes or
+    /// the app quits.
+    ///
+    /// If `restore_session` is set, the auth actor restores the previous
+    /// session before handling any menu action.
+    pub async fn run(
+        mut self,
+        mut action_rx: mpsc::Receiver<ActionRequest>,
+        mut shutdown_rx: mpsc::Receiver<ShutdownRequest>,
+        restore_session: bool,
+    ) {
+        if restore_session {
+            self.send(Routed::Auth(AuthMessage::RestoreSession), None)
+                .await;
+        } else {
+            info!("Session restore at launch disabled in settings");
+        }
+
+        loop {
+            tokio::select! {
+                request = action_rx.recv() => match request {
+                    Some(request) => self.dispatch(request).await,
+                    None => break,
+                },
+                Some(request) = shutdown_rx.recv() => {
+                    self.shutdown(request.deactivate_roles).await;
+                    updates::finish_termination();
+                    return;
+                }
+                Some(result) = self.actors.join_next() => {
+                    // Actors only return when their channel closes, which can't
+                    // happen while the supervisor holds the senders
+                    let message = match result {
+                        Ok(name) => format!("The {} task stopped unexpectedly", name),
+                        Err(e) => format!("A background task failed: {}", e),
+                    };
+                    error!("{}", message);
+                    updates::update_error(message, ErrorClass::Bug { code: "APP-TASK" });
+                }
+            }
+        }
+
+        info!("Action channel closed, stopping actors");
+    }
+
+    /// Let the actors finish their part of a quit, bounded by [`SHUTDOWN_TIMEOUT`].
+    async fn shutdown(&self, deactivate_roles: bool) {
+        info!("Shutting down actors");
+        let work = async {
+            if deactivate_roles {
+                if let Some(ActionOutcome::Failed(reason)) =
+                    self.request(Routed::Pim(PimMessage::DeactivateAll)).await
+                {
+                    warn!("Some roles could not be deactivated: {}", reason);
+                }
+            }
+            self.request(Routed::Auth(AuthMessage::Shutdown)).await;
+        };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, work).await.is_err() {
+            warn!("Shutdown timed out after {:?}", SHUTDOWN_TIMEOUT);
+        }
+    }
+
+    /// Send a message and wait for its outcome.
+    async fn request(&self, routed: Routed) -> Option<ActionOutcome> {
+        let (tx, rx) = oneshot::channel();
+        self.send(routed, Some(tx)).await;
+        rx.await.ok()
+    }
+
+    /// Route an action to its actor, reporting the outcome unless the sender waits for it.
+    async fn dispatch(&self, request: ActionRequest) {
+        let ActionRequest { id, action, reply } = request;
+        let label = action.label();
+        debug!("Action #{}: {}", id, label);
+
+        let reply = reply.unwrap_or_else(|| {
+            let (tx, rx) = oneshot::channel();
+            tokio::spawn(report_outcome(id, label, rx));
+            tx
+        });
+        self.send(action.into(), Some(reply)).await;
+    }
+
+    async fn send(&self, routed: Routed, reply: Option<oneshot::Sender<ActionOutcome>>) {
+        let result = match routed {
+            Routed::Auth(msg) => self
+                .auth_tx
+                .send(Envelope { msg, reply })
+                .await
+                .map_err(|e| format!("{:?}", e.0.msg)),
+            Routed::Pim(msg) => self
+                .pim_tx
+                .send(Envelope { msg, reply })
+                .await
+                .map_err(|e| format!("{:?}", e.0.msg)),
+            Routed::Settings(msg) => self
+                .settings_tx
+                .send(Envelope { msg, reply })
+                .await
+                .map_err(|e| format!("{:?}", e.0.msg)),
+        };
+        if let Err(msg) = result {
+            warn!("Dropped message for stopped actor: {}", msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::auth::Browser;
+    use crate::api::{AssignmentsBySubscription, BoxFuture, GraphApi, OAuthApi, PimApi};
+    use crate::auth::callback_server::CallbackResult;
+    use crate::auth::challenge::StepUp;
+    use crate::auth::features::Feature;
+    use crate::auth::graph::{GroupMembership, Organization, UserInfo, UserProfile};
+    use crate::auth::oauth::{PkceChallenge, TokenResponse};
+    use crate::error::{ApiError, AuthError, PimError};
+    use crate::keychain::{self, MemoryStore};
+    use crate::menubar::state::{AppState, AuthEvent, APP_STATE};
+    use crate::notifications::{self, NoopBackend};
+    use crate::pim::client::EligibilityScan;
+    use crate::pim::compat::ApiVersionProblem;
+    use crate::pim::policy::{ActivationCheck, RolePolicy};
+    use crate::pim::{
+        self, ActivationOptions, ActivationRequest, ActiveAssignment, ApprovalRequest,
+        EligibleRole, GroupNames, ReviewResult, Scope, SubmittedActivation, Subscription,
+        TenantAssignment,
+    };
+    use chrono::{DateTime, Duration, Utc};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
+    use url::Url;
+
+    /// API requests the mock clients received, with their arguments.
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    const ROLE_KEY: &str = "/subscriptions/sub-1|acdd72a7-3385-48ef-bd42-f606fba81ae7";
+
+    fn reader_role() -> EligibleRole {
+        EligibleRole {
+            id: "eligibility-1".to_string(),
+            role_definition_id: "/subscriptions/sub-1/providers/Microsoft.Authorization/roleDefinitions/acdd72a7-3385-48ef-bd42-f606fba81ae7".to_string(),
+            role_name: "Reader".to_string(),
+            subscription_id: "sub-1".to_string(),
+            subscription_name: "Production".to_string(),
+            scope: Scope::Subscription {
+                subscription_id: "sub-1".to_string(),
+            },
+            principal_id: "group-1".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        }
+    }
+
+    fn tokens(access_token: &str, refresh_token: Option<&str>) -> TokenResponse {
+        TokenResponse {
+            access_token: access_token.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: 3600,
+            refresh_token: refresh_token.map(str::to_string),
+            scope: String::new(),
+            id_token: None,
+        }
+    }
+
+    /// Keep the Keychain items, app state and data files of the test process
+    /// away from the user's.
+    fn init_test_app() -> Arc<AppState> {
+        keychain::set_store(Box::new(MemoryStore::default()));
+        notifications::set_backend(Box::new(NoopBackend));
+        let dir = std::env::temp_dir().join(format!("azurepim-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        pim::set_data_dir(dir);
+        Arc::clone(APP_STATE.get_or_init(|| Arc::new(AppState::new())))
+    }
+
+    #[derive(Default)]
+    struct MockOAuth {
+        calls: Calls,
+        /// PKCE verifier of the last authorization URL.
+        verifier: Mutex<Option<String>>,
+    }
+
+    impl OAuthApi for MockOAuth {
+        fn redirect_uri(&self) -> &str {
+            "http://localhost:28491/callback"
+        }
+
+        fn generate_auth_url(&self, pkce: &PkceChallenge) -> (Url, String) {
+            *self.verifier.lock().unwrap() = Some(pkce.verifier.clone());
+            let url = Url::parse("https://login.example/authorize?state=state-1").unwrap();
+            (url, "state-1".to_string())
+        }
+
+        fn generate_consent_url(
+            &self,
+            _pkce: &PkceChallenge,
+            _scopes: &[String],
+            _login_hint: Option<&str>,
+        ) -> (Url, String) {
+            unreachable!()
+        }
+
+        fn generate_step_up_url(
+            &self,
+            _pkce: &PkceChallenge,
+            _step_up: &StepUp,
+            _login_hint: Option<&str>,
+        ) -> (Url, String) {
+            unreachable!()
+        }
+
+        fn missing_scopes(&self, _granted: &str) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn has_feature(&self, _feature: Feature) -> bool {
+            true
+        }
+
+        fn unmet_features(&self, _granted: &str) -> Vec<Feature> {
+            Vec::new()
+        }
+
+        fn arm_admin_consent_url(&self) -> Url {
+            unreachable!()
+        }
+
+        fn invalidate_cached_tokens(&self) -> BoxFuture<'_, ()> {
+            Box::pin(async {})
+        }
+
+        fn exchange_code<'a>(
+            &'a self,
+            code: &'a str,
+            pkce_verifier: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            let call = format!("exchange_code {} {}", code, pkce_verifier);
+            self.calls.lock().unwrap().push(call);
+            Box::pin(async { Ok(tokens("access-1", Some("refresh-1"))) })
+        }
+
+        fn exchange_consent_code<'a>(
+            &'a self,
+            _code: &'a str,
+            _pkce_verifier: &'a str,
+            _scopes: &'a [String],
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            unreachable!()
+        }
+
+        fn exchange_step_up_code<'a>(
+            &'a self,
+            _code: &'a str,
+            _pkce_verifier: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            unreachable!()
+        }
+
+        fn refresh_token<'a>(
+            &'a self,
+            refresh_token: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            assert_eq!(refresh_token, "refresh-1");
+            Box::pin(async { Ok(tokens("graph-token", None)) })
+        }
+
+        fn get_management_token<'a>(
+            &'a self,
+            refresh_token: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            assert_eq!(refresh_token, "refresh-1");
+            Box::pin(async { Ok(tokens("mgmt-token", None)) })
+        }
+
+        fn get_management_token_with_claims<'a>(
+            &'a self,
+            _refresh_token: &'a str,
+            _claims: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            unreachable!()
+        }
+
+        fn get_key_vault_token<'a>(
+            &'a self,
+            _refresh_token: &'a str,
+        ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+            unreachable!()
+        }
+    }
+
+    struct MockGraph;
+
+    impl GraphApi for MockGraph {
+        fn get_user_profile<'a>(
+            &'a self,
+            access_token: &'a str,
+        ) -> BoxFuture<'a, Result<UserProfile, ApiError>> {
+            assert_eq!(access_token, "access-1");
+            Box::pin(async {
+                Ok(UserProfile {
+                    id: "user-1".to_string(),
+                    display_name: Some("Ada Admin".to_string()),
+                    given_name: None,
+                    surname: None,
+                    mail: Some("ada@contoso.example".to_string()),
+                    user_principal_name: None,
+                    job_title: None,
+                    office_location: None,
+                })
+            })
+        }
+
+        fn get_organization<'a>(
+            &'a self,
+            access_token: &'a str,
+        ) -> BoxFuture<'a, Result<Organization, ApiError>> {
+            assert_eq!(access_token, "access-1");
+            Box::pin(async {
+                Ok(Organization {
+                    id: "tenant-1".to_string(),
+                    display_name: Some("Contoso".to_string()),
+                    verified_domains: Vec::new(),
+                })
+            })
+        }
+
+        fn get_user_groups<'a>(
+            &'a self,
+            access_token: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<GroupMembership>, ApiError>> {
+            assert_eq!(access_token, "graph-token");
+            Box::pin(async {
+                Ok(vec![GroupMembership {
+                    id: "group-1".to_string(),
+                    display_name: Some("Platform Admins".to_string()),
+                }])
+            })
+        }
+    }
+
+    #[derive(Default)]
+    struct MockPim {
+        calls: Calls,
+    }
+
+    impl PimApi for MockPim {
+        fn list_subscriptions<'a>(
+            &'a self,
+            _access_token: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<Subscription>, PimError>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn check_api_versions<'a>(
+            &'a self,
+            _access_token: &'a str,
+        ) -> BoxFuture<'a, Result<Vec<ApiVersionProblem>, PimError>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_all_eligible_roles<'a>(
+            &'a self,
+            access_token: &'a str,
+            principal_ids: &'a [String],
+            _skip: &'a [String],
+        ) -> BoxFuture<'a, Result<EligibilityScan, PimError>> {
+            let call = format!(
+                "get_all_eligible_roles {} {}",
+                access_token,
+                principal_ids.join(",")
+            );
+            self.calls.lock().unwrap().push(call);
+            Box::pin(async {
+                Ok(EligibilityScan {
+                    roles: vec![reader_role()],
+                    scanned: vec!["sub-1".to_string()],
+                    denied: Vec::new(),
+                })
+            })
+        }
+
+        fn get_role_policies<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _roles: &'a [EligibleRole],
+        ) -> BoxFuture<'a, Result<HashMap<String, RolePolicy>, PimError>> {
+            Box::pin(async { Ok(HashMap::new()) })
+        }
+
+        fn get_active_assignments<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _principal_ids: &'a [String],
+        ) -> BoxFuture<'a, Result<Vec<ActiveAssignment>, PimError>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_active_assignments_in<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _subscriptions: &'a [Subscription],
+            _principal_ids: &'a [String],
+        ) -> BoxFuture<'a, Result<AssignmentsBySubscription, PimError>> {
+            unreachable!()
+        }
+
+        fn get_tenant_assignments<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _subscriptions: &'a [Subscription],
+        ) -> BoxFuture<'a, Result<Vec<TenantAssignment>, PimError>> {
+            unreachable!()
+        }
+
+        fn get_max_activation_minutes<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _scope: &'a Scope,
+            _role_definition_id: &'a str,
+        ) -> BoxFuture<'a, Result<Option<u32>, PimError>> {
+            unreachable!()
+        }
+
+        fn validate_activation<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _request: &'a ActivationRequest,
+        ) -> BoxFuture<'a, Result<ActivationCheck, PimError>> {
+            unreachable!()
+        }
+
+        fn submit_activation<'a>(
+            &'a self,
+            access_token: &'a str,
+            request: ActivationRequest,
+        ) -> BoxFuture<'a, Result<SubmittedActivation, PimError>> {
+            let call = format!(
+                "submit_activation {} {} {}",
+                access_token,
+                request.eligible_role.favorites_key(),
+                request.justification
+            );
+            self.calls.lock().unwrap().push(call);
+            let role = request.eligible_role;
+            let now = Utc::now();
+            Box::pin(async move {
+                Ok(SubmittedActivation::Pending(ActiveAssignment {
+                    id: "assignment-1".to_string(),
+                    role_definition_id: role.role_definition_id,
+                    role_name: role.role_name,
+                    subscription_id: role.subscription_id,
+                    subscription_name: role.subscription_name,
+                    scope: role.scope,
+                    start_time: now,
+                    end_time: now + Duration::minutes(request.duration_minutes as i64),
+                    justification: request.justification,
+                    assignment_request_id: Some("request-1".to_string()),
+                }))
+            })
+        }
+
+        fn activate_role<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _request: ActivationRequest,
+        ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>> {
+            unreachable!()
+        }
+
+        fn extend_role<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _request: ActivationRequest,
+        ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>> {
+            unreachable!()
+        }
+
+        fn wait_for_provisioning<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _assignment: &'a ActiveAssignment,
+        ) -> BoxFuture<'a, Result<(), PimError>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn deactivate_role<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _assignment: &'a ActiveAssignment,
+            _principal_id: &'a str,
+        ) -> BoxFuture<'a, Result<(), PimError>> {
+            unreachable!()
+        }
+
+        fn cancel_schedule_request<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _request_id: &'a str,
+        ) -> BoxFuture<'a, Result<(), PimError>> {
+            unreachable!()
+        }
+
+        fn review_approval<'a>(
+            &'a self,
+            _access_token: &'a str,
+            _request: &'a ApprovalRequest,
+            _result: ReviewResult,
+            _justification: &'a str,
+        ) -> BoxFuture<'a, Result<(), PimError>> {
+            unreachable!()
+        }
+    }
+
+    /// Records the sign-in page and waits for a redirect that never comes;
+    /// the test delivers it.
+    #[derive(Default)]
+    struct MockBrowser {
+        opened: Mutex<Option<String>>,
+    }
+
+    impl Browser for MockBrowser {
+        fn wait_for_callback(
+            &self,
+            cancel: CancellationToken,
+        ) -> BoxFuture<'static, CallbackResult> {
+            Box::pin(async move {
+                cancel.cancelled().await;
+                CallbackResult::Cancelled
+            })
+        }
+
+        fn open(&self, url: &str) -> std::io::Result<()> {
+            *self.opened.lock().unwrap() = Some(url.to_string());
+            Ok(())
+        }
+    }
+
+    fn auth_message(action: MenuAction) -> AuthMessage {
+        match Routed::from(action) {
+            Routed::Auth(msg) => msg,
+            routed => panic!("Routed to the wrong actor: {:?}", routed),
+        }
+    }
+
+    fn pim_message(action: MenuAction) -> PimMessage {
+        match Routed::from(action) {
+            Routed::Pim(msg) => msg,
+            routed => panic!("Routed to the wrong actor: {:?}", routed),
+        }
+    }
+
+    #[test]
+    fn test_actions_route_to_owning_actor() {
+        assert!(matches!(
+            Routed::from(MenuAction::SignIn),
+            Routed::Auth(AuthMessage::SignIn)
+        ));
+        assert!(matches!(
+            Routed::from(MenuAction::RefreshPimRoles),
+            Routed::Pim(PimMessage::RefreshRoles)
+        ));
+        assert!(matches!(
+            Routed::from(MenuAction::ToggleShowExpiry(true)),
+            Routed::Settings(SettingsMessage::ShowExpiry(true))
+        ));
+        // Skipping group lookup refreshes roles, so the PIM actor owns it
+        assert!(matches!(
+            Routed::from(MenuAction::ToggleSkipGroupLookup(true)),
+            Routed::Pim(PimMessage::SkipGroupLookup(true))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_refresh_and_activate_against_mock_clients() {
+        let state = init_test_app();
+        let calls = Calls::default();
+        let oauth = Arc::new(MockOAuth {
+            calls: Arc::clone(&calls),
+            ..Default::default()
+        });
+        let pim_client = Arc::new(MockPim {
+            calls: Arc::clone(&calls),
+        });
+        let browser = Arc::new(MockBrowser::default());
+        let (pim_tx, mut pim_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (_auth_tx, auth_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let (_pim_tx, actor_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let mut auth = AuthActor::new(
+            oauth.clone(),
+            Arc::new(MockGraph),
+            browser.clone(),
+            pim_tx,
+            auth_rx,
+        );
+        let mut pim = PimActor::new(oauth.clone(), Arc::new(MockGraph), pim_client, actor_rx);
+
+        // Sign in: the browser opens, then the redirect carries the code
+        let outcome = auth.handle(auth_message(MenuAction::SignIn)).await;
+        assert_eq!(outcome, ActionOutcome::Done);
+        let opened = browser.opened.lock().unwrap().clone().unwrap();
+        let state_param = Url::parse(&opened)
+            .unwrap()
+            .query_pairs()
+            .find(|(name, _)| name == "state")
+            .map(|(_, value)| value.to_string())
+            .unwrap();
+        let callback = format!(
+            "http://localhost:28491/callback?code=code-1&state={}",
+            state_param
+        );
+        auth.handle_callback_result(CallbackResult::Success(callback))
+            .await;
+        assert_eq!(&*keychain::get_refresh_token().unwrap(), "refresh-1");
+
+        // The auth actor hands the caches to the new account
+        let claim = pim_rx.recv().await.unwrap();
+        assert!(matches!(&claim.msg, PimMessage::ClaimCaches(owner) if owner.user_id == "user-1"));
+        assert_eq!(pim.handle(claim.msg).await, ActionOutcome::Done);
+
+        // Stands in for the main thread, which applies the signed-in state
+        let user_info = UserInfo::from_json(&keychain::get_user_info().unwrap()).unwrap();
+        let expires_at: DateTime<Utc> = keychain::get_token_expiry().unwrap().parse().unwrap();
+        state.transition(AuthEvent::Authenticating).unwrap();
+        state
+            .transition(AuthEvent::SignedIn {
+                user_info,
+                expires_at,
+            })
+            .unwrap();
+
+        let outcome = pim.handle(pim_message(MenuAction::RefreshPimRoles)).await;
+        assert_eq!(outcome, ActionOutcome::Done);
+
+        // Stands in for the main thread again, which shows the roles found
+        state.set_pim_eligible_roles(vec![reader_role()], GroupNames::new());
+        let activate = MenuAction::ActivateRole {
+            role_key: ROLE_KEY.to_string(),
+            justification: "INC-4711".to_string(),
+            options: ActivationOptions::default(),
+        };
+        // Done once submitted; provisioning is reported when it finishes
+        assert_eq!(pim.handle(pim_message(activate)).await, ActionOutcome::Done);
+
+        let verifier = oauth.verifier.lock().unwrap().clone().unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("exchange_code code-1 {}", verifier),
+                "get_all_eligible_roles mgmt-token user-1,group-1".to_string(),
+                format!("submit_activation mgmt-token {} INC-4711", ROLE_KEY),
+            ]
+        );
+    }
+}
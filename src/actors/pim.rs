@@ -0,0 +1,2379 @@
+//! PIM actor: role discovery, favorites, activation, and expiry warnings.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn, Instrument};
+
+use super::settings::update_settings;
+use super::{respond, Envelope};
+use crate::api::{GraphApi, OAuthApi, PimApi};
+use crate::auth::challenge::StepUp;
+use crate::auth::features::Feature;
+use crate::auth::graph::GroupMembership;
+use crate::clock::SystemClock;
+use crate::duration::{self, Style};
+use crate::error::{ApiError, AuthError, PimError};
+use crate::keychain;
+use crate::menubar::delegate::{self, ActionOutcome};
+use crate::menubar::state::{get_app_state, AuthState, PimState};
+use crate::menubar::updates;
+use crate::notifications::{self, NotificationEvent};
+use crate::operation::{Operation, OperationKind, Outcome};
+use crate::pim::builtin_roles::is_key_vault_data_role;
+use crate::pim::concurrency;
+use crate::pim::policy::{
+    ActivationCheck, JustificationStandard, PolicyRemedy, PolicyRule, RolePolicy,
+};
+use crate::pim::schedule;
+use crate::pim::settings::SettingsWatcher;
+use crate::pim::{
+    self, eligibility_report, label_group_grants, ActivationOptions, ActivationRequest,
+    ActiveAssignment, CacheOwner, DeactivationReminders, EligibleRole, GroupLookup, GroupNames,
+    NotesStore, PendingRequest, PimAvailability, PimSettings, PlanDraft, PlanProgress,
+    ReviewResult, ScheduledActivation, ScheduledDeactivations, SubmittedActivation, Subscription,
+};
+use crate::settings::{self, DataCategory};
+
+/// Justification sent when only validating an activation.
+const CHECK_JUSTIFICATION: &str = "Activation check";
+
+/// Interval between checks for expiring role assignments.
+const EXPIRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Interval between polls of the subscriptions with active assignments.
+const ASSIGNMENT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3 * 60);
+
+/// Every how many polls the subscriptions of eligible roles are polled too,
+/// to find roles activated outside this app.
+const WIDE_POLL_EVERY: u32 = 5;
+
+/// Interval between checks for favorites changed on another machine.
+const FAVORITES_SYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Messages handled by the PIM actor.
+#[derive(Debug)]
+pub enum PimMessage {
+    /// Activate a role with justification.
+    ActivateRole {
+        role_key: String,
+        justification: String,
+        options: ActivationOptions,
+    },
+    /// Check whether a role could be activated, without activating it.
+    CheckActivation { role_key: String },
+    /// Toggle favorite status for a role.
+    ToggleFavorite { role_key: String },
+    /// Refresh eligible roles and active assignments from Azure.
+    RefreshRoles,
+    /// Load roles after sign-in, reusing eligible roles fetched within the cache TTL.
+    LoadRoles,
+    /// Confirm active assignments restored from disk against Azure.
+    ReconcileAssignments,
+    /// Deactivate all active assignments (from the menu or before quitting).
+    DeactivateAll,
+    /// Store favorites and presets in a custom file, or the default location for `None`.
+    SetFavoritesFile { path: Option<PathBuf> },
+    /// Activate every role of an elevation plan.
+    ActivatePlan { name: String },
+    /// Save the active roles as an elevation plan.
+    SavePlan { draft: PlanDraft },
+    /// Deactivate a single active role.
+    DeactivateRole { role_key: String },
+    /// Keep an active role for `minutes` past its current end.
+    ExtendRole { role_key: String, minutes: u32 },
+    /// Remind to deactivate a role in `minutes`, or cancel the reminder for `None`.
+    SetDeactivationReminder {
+        role_key: String,
+        minutes: Option<u32>,
+    },
+    /// Deactivate a role at `at`, or cancel its scheduled deactivation for `None`.
+    ScheduleDeactivation {
+        role_key: String,
+        at: Option<DateTime<Utc>>,
+    },
+    /// Add a note to the elevation of an active assignment.
+    AddSessionNote { assignment_id: String, text: String },
+    /// Stop or resume reading group memberships, then refresh roles.
+    SkipGroupLookup(bool),
+    /// Skip subscriptions without PIM access for this many hours; 0 never skips.
+    SkipDeniedSubscriptions { hours: u32 },
+    /// Forget which subscriptions lack PIM access, then refresh roles.
+    RecheckSubscriptions,
+    /// Turn the administrator view of everyone's active roles on or off.
+    SetAdminView { enabled: bool },
+    /// Add a subscription to the administrator view, or remove it if listed.
+    ToggleAdminViewSubscription { subscription_id: String },
+    /// Approve or deny another user's request with a justification.
+    ReviewApproval {
+        request_id: String,
+        result: ReviewResult,
+        justification: String,
+    },
+    /// Cancel one of the user's activations scheduled to start later.
+    CancelScheduledActivation { request_id: String },
+    /// Activate a role without confirmation from now on.
+    SkipActivationConfirmation { role_key: String },
+    /// Show `label` next to a role, or remove its label if blank.
+    SetRoleLabel { role_key: String, label: String },
+    /// Activate a role for `minutes` from now on.
+    SetRoleDuration { role_key: String, minutes: u32 },
+    /// Remove the PIM data of the chosen categories (settings, caches, session notes).
+    ClearData(Vec<DataCategory>),
+    /// A sign-in completed for this account; forget the cached roles,
+    /// assignments and schedules if they were saved for another one.
+    ClaimCaches(CacheOwner),
+}
+
+/// Access needed to query PIM for the signed-in user.
+struct PimAccess {
+    mgmt_token: String,
+    principal_ids: Vec<String>,
+    /// Display names of the groups among `principal_ids`.
+    group_names: GroupNames,
+    /// Whether groups were part of `principal_ids`.
+    group_lookup: GroupLookup,
+}
+
+/// Why PIM access could not be obtained, with the message to show.
+enum AccessError {
+    Failed(String),
+    PermissionDenied(String),
+    /// The app registration lacks the Azure Service Management permission.
+    ArmPermissionMissing {
+        consent_url: String,
+    },
+    /// Azure AD could not be reached.
+    Offline,
+}
+
+impl AccessError {
+    fn into_message(self) -> String {
+        match self {
+            Self::Failed(message) | Self::PermissionDenied(message) => message,
+            Self::ArmPermissionMissing { .. } => {
+                "The app registration lacks the Azure Service Management permission.".to_string()
+            }
+            Self::Offline => "Azure is unreachable. Check your connection.".to_string(),
+        }
+    }
+}
+
+/// Why an activation failed.
+enum ActivationError {
+    /// Failed with a message to show.
+    Failed(String),
+    /// Broke the role's policy; the user may retry with a fix.
+    Policy {
+        role_name: String,
+        remedy: PolicyRemedy,
+    },
+    /// Sent to an approver; the schedule request with this ID is pending.
+    AwaitingApproval(String),
+    /// Azure asked for more verification; retried after signing in with it.
+    StepUp(StepUp),
+}
+
+impl ActivationError {
+    /// A failed request, with Azure's reason if it was not provisioned.
+    fn failed(e: &PimError) -> Self {
+        match e {
+            PimError::ProvisioningFailed(reason) => {
+                Self::Failed(format!("Azure did not activate the role: {}.", reason))
+            }
+            e => Self::Failed(e.user_message().to_string()),
+        }
+    }
+}
+
+/// An activation Azure is still provisioning, watched in the background.
+struct PendingProvisioning {
+    role_key: String,
+    justification: String,
+    options: ActivationOptions,
+    assignment: ActiveAssignment,
+    operation: Operation,
+}
+
+/// Build the activation request for an eligible role, checking the app's
+/// justification standard before Azure sees the request.
+fn prepare_activation(
+    role_key: &str,
+    justification: String,
+    options: &ActivationOptions,
+    pim_state: &PimState,
+) -> Result<ActivationRequest, ActivationError> {
+    let eligible_role = pim_state
+        .eligible_roles
+        .iter()
+        .find(|r| r.favorites_key() == role_key)
+        .cloned()
+        .ok_or_else(|| {
+            ActivationError::Failed(
+                PimError::RoleNotFound(role_key.to_string())
+                    .user_message()
+                    .to_string(),
+            )
+        })?;
+
+    let standard = JustificationStandard::effective(
+        get_app_state().and_then(|s| s.get_settings().justification_min_length),
+        settings::managed_justification_min_length(),
+    );
+    if let Some(standard) = standard {
+        if !standard.accepts(&justification, &pim_state.settings.all_presets()) {
+            warn!(
+                "Justification for {} does not meet the {} character standard",
+                role_key, standard.min_length
+            );
+            return Err(ActivationError::Policy {
+                role_name: eligible_role.role_name,
+                remedy: PolicyRemedy::EnterDetailedJustification(standard.min_length),
+            });
+        }
+    }
+
+    // A longer request than the policy allows fails with an opaque 400
+    let requested = options
+        .duration_minutes
+        .unwrap_or(pim_state.settings.duration_for(role_key));
+    let duration_minutes = pim_state.clamp_duration(role_key, requested);
+    if duration_minutes < requested {
+        info!(
+            "Activating {} for {} minutes, the policy maximum, instead of {}",
+            role_key, duration_minutes, requested
+        );
+    }
+    Ok(ActivationRequest {
+        eligible_role,
+        justification,
+        duration_minutes,
+        ticket_number: options.ticket_number.clone(),
+        start_time: options.start_at,
+    })
+}
+
+/// Show a request just sent to an approver in the "Pending Approval" section.
+fn record_pending_request(request_id: String, role_key: String) {
+    updates::add_pim_pending_request(PendingRequest {
+        id: request_id,
+        role_key,
+    });
+}
+
+/// Summary text for a role of an elevation plan that failed to activate.
+fn plan_failure_text(role_name: &str, error: ActivationError) -> String {
+    match error {
+        ActivationError::Failed(message) => format!("{}: {}", role_name, message),
+        ActivationError::Policy {
+            remedy: PolicyRemedy::EnterDetailedJustification(min_length),
+            ..
+        } => format!(
+            "{}: justification needs at least {} characters",
+            role_name, min_length
+        ),
+        ActivationError::Policy { .. } => format!("{}: blocked by role policy", role_name),
+        ActivationError::AwaitingApproval(_) => format!("{}: waiting for approval", role_name),
+        ActivationError::StepUp(step_up) => {
+            format!("{}: activate it alone to complete {}", role_name, step_up)
+        }
+    }
+}
+
+/// How long subscriptions without PIM access are left out of scans.
+fn skip_period() -> chrono::Duration {
+    let hours = get_app_state()
+        .map(|s| s.get_settings().skip_denied_subscriptions_hours)
+        .unwrap_or_default();
+    chrono::Duration::hours(hours as i64)
+}
+
+/// Whether nobody is signed in, or a sign-in has not completed.
+fn is_signed_out() -> bool {
+    get_app_state().is_none_or(|s| {
+        matches!(
+            s.get_auth_state(),
+            AuthState::SignedOut | AuthState::Authenticating
+        )
+    })
+}
+
+/// Show and persist the current active assignments.
+fn set_active_assignments(assignments: Vec<ActiveAssignment>) {
+    if let Err(e) = pim::save_active_assignments(&assignments) {
+        error!("Failed to save active assignments: {}", e);
+    }
+    updates::update_pim_active_assignments(assignments);
+}
+
+/// Owns PIM API access and the expiry monitor.
+pub struct PimActor {
+    oauth_client: Arc<dyn OAuthApi>,
+    graph_client: Arc<dyn GraphApi>,
+    pim_client: Arc<dyn PimApi>,
+    rx: mpsc::Receiver<Envelope<PimMessage>>,
+    /// Activations whose provisioning finished, reported by their watchers.
+    provisioned_tx: mpsc::Sender<(PendingProvisioning, Result<(), PimError>)>,
+    provisioned_rx: mpsc::Receiver<(PendingProvisioning, Result<(), PimError>)>,
+
+    /// Assignments already warned about, so each one notifies only once.
+    warned: HashSet<String>,
+    /// Detects favorites synced in from other machines.
+    settings_watcher: SettingsWatcher,
+    /// Reminders to deactivate roles the user is done with.
+    reminders: DeactivationReminders,
+    /// Roles to deactivate at a set time, saved across restarts.
+    scheduled: ScheduledDeactivations,
+    /// Notes taken during elevations, saved for incident writeups.
+    notes: NotesStore,
+    /// Subscriptions that refused role scans, saved across restarts.
+    availability: PimAvailability,
+    /// Principal IDs (user + groups) from the last full refresh, reused by the assignment poll.
+    principal_ids: Vec<String>,
+    /// Assignment polls since the last one covering eligible roles' subscriptions.
+    polls_since_wide: u32,
+    /// Don't read group memberships. Kept here since settings reach the app state asynchronously.
+    skip_group_lookup: bool,
+    /// Day the end-of-day summary was last sent, so it is sent once a day.
+    summary_sent_on: Option<NaiveDate>,
+    /// Whether the API versions were checked, once per launch.
+    api_versions_checked: bool,
+    /// Activation rules of each role's policy, by role key, once read.
+    role_policies: HashMap<String, RolePolicy>,
+}
+
+impl PimActor {
+    /// Create the actor.
+    pub fn new(
+        oauth_client: Arc<dyn OAuthApi>,
+        graph_client: Arc<dyn GraphApi>,
+        pim_client: Arc<dyn PimApi>,
+        rx: mpsc::Receiver<Envelope<PimMessage>>,
+    ) -> Self {
+        let (provisioned_tx, provisioned_rx) = mpsc::channel(8);
+        Self {
+            oauth_client,
+            graph_client,
+            pim_client,
+            rx,
+            provisioned_tx,
+            provisioned_rx,
+            warned: HashSet::new(),
+            settings_watcher: SettingsWatcher::default(),
+            reminders: DeactivationReminders::default(),
+            scheduled: pim::load_scheduled_deactivations(),
+            notes: pim::load_session_notes(),
+            availability: pim::load_pim_availability(),
+            principal_ids: Vec::new(),
+            polls_since_wide: 0,
+            skip_group_lookup: get_app_state().is_some_and(|s| s.get_settings().skip_group_lookup),
+            summary_sent_on: None,
+            api_versions_checked: false,
+            role_policies: HashMap::new(),
+        }
+    }
+
+    /// Process messages until the channel closes.
+    pub async fn run(mut self) {
+        let mut expiry_interval = tokio::time::interval(EXPIRY_CHECK_INTERVAL);
+        let mut sync_interval = tokio::time::interval(FAVORITES_SYNC_INTERVAL);
+        let mut assignment_poll = tokio::time::interval(ASSIGNMENT_POLL_INTERVAL);
+        assignment_poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        updates::update_pim_scheduled_deactivations(self.scheduled.clone());
+        self.publish_skipped_subscriptions();
+        if self.notes.prune(Utc::now()) {
+            self.save_session_notes();
+        } else {
+            updates::update_pim_session_notes(self.notes.clone());
+        }
+
+        loop {
+            tokio::select! {
+                msg = self.rx.recv() => match msg {
+                    Some(Envelope { msg, reply }) => {
+                        let outcome = self.handle(msg).await;
+                        respond(reply, outcome);
+                    }
+                    None => break,
+                },
+                Some((activation, result)) = self.provisioned_rx.recv() => {
+                    let outcome = self.finish_provisioning(activation, result);
+                    updates::show_action_outcome("Activate Role", &outcome);
+                }
+                // Drop expired assignments and warn about ones about to expire
+                _ = expiry_interval.tick() => {
+                    self.prune_expired_assignments();
+                    // Reminders and schedules act for the signed-in account only
+                    if !is_signed_out() {
+                        self.check_expiring_assignments();
+                        self.check_deactivation_reminders();
+                        self.run_scheduled_deactivations().await;
+                        self.check_end_of_day_summary();
+                    }
+                }
+                _ = sync_interval.tick() => self.reload_changed_settings(),
+                _ = assignment_poll.tick() => {
+                    self.poll_active_assignments().await;
+                    self.refresh_tenant_assignments().await;
+                }
+            }
+        }
+    }
+
+    pub(super) async fn handle(&mut self, msg: PimMessage) -> ActionOutcome {
+        match msg {
+            PimMessage::ActivateRole {
+                role_key,
+                justification,
+                options,
+            } => {
+                info!(
+                    "Activating role {} with justification: {}",
+                    role_key, justification
+                );
+                updates::update_pim_activating(role_key.clone(), true);
+                let operation = Operation::start(OperationKind::Activation);
+                let result = match self
+                    .activate_role(&role_key, justification.clone(), &options)
+                    .instrument(operation.span())
+                    .await
+                {
+                    Ok((SubmittedActivation::Pending(assignment), mgmt_token)) => {
+                        // Stays "activating" until the watcher reports back
+                        self.watch_provisioning(
+                            PendingProvisioning {
+                                role_key,
+                                justification,
+                                options,
+                                assignment,
+                                operation,
+                            },
+                            mgmt_token,
+                        );
+                        return ActionOutcome::Done;
+                    }
+                    Ok((SubmittedActivation::Provisioned(assignment), _)) => Ok(assignment),
+                    Err(e) => Err(e),
+                };
+                self.finish_activation(operation, role_key, justification, options, result)
+            }
+            PimMessage::CheckActivation { role_key } => self.check_activation(&role_key).await,
+            PimMessage::ToggleFavorite { role_key } => {
+                info!("Toggling favorite for role: {}", role_key);
+                let Some(state) = get_app_state() else {
+                    return ActionOutcome::Done;
+                };
+                let mut pim_state = state.get_pim_state();
+                pim_state.toggle_favorite(&role_key);
+                state.set_pim_state(pim_state.clone());
+                updates::rebuild_menu();
+
+                // Save to disk
+                match pim::save_pim_settings(&pim_state.settings) {
+                    Ok(()) => {
+                        // Our own write is not a sync from elsewhere
+                        self.mark_settings_seen();
+                        ActionOutcome::Done
+                    }
+                    Err(e) => {
+                        error!("Failed to save PIM settings: {}", e);
+                        ActionOutcome::Failed(e.user_message().to_string())
+                    }
+                }
+            }
+            PimMessage::RefreshRoles => {
+                // Errors are shown in the PIM section of the menu
+                self.refresh_roles().await;
+                ActionOutcome::Done
+            }
+            PimMessage::LoadRoles => {
+                let cached = get_app_state().is_some_and(|s| s.get_pim_state().is_cache_valid());
+                if cached {
+                    info!("Eligible roles still cached, only refreshing active assignments");
+                    self.reconcile_assignments().await;
+                } else {
+                    self.refresh_roles().await;
+                }
+                ActionOutcome::Done
+            }
+            PimMessage::ReconcileAssignments => {
+                self.reconcile_assignments().await;
+                ActionOutcome::Done
+            }
+            PimMessage::DeactivateAll => self.deactivate_all().await,
+            PimMessage::SetFavoritesFile { path } => self.set_favorites_file(path),
+            PimMessage::ActivatePlan { name } => self.activate_plan(&name).await,
+            PimMessage::SavePlan { draft } => self.save_plan(draft),
+            PimMessage::DeactivateRole { role_key } => self.deactivate_role(&role_key).await,
+            PimMessage::ExtendRole { role_key, minutes } => {
+                self.extend_role(&role_key, minutes).await
+            }
+            PimMessage::SetDeactivationReminder { role_key, minutes } => {
+                self.set_deactivation_reminder(&role_key, minutes)
+            }
+            PimMessage::AddSessionNote {
+                assignment_id,
+                text,
+            } => self.add_session_note(&assignment_id, &text),
+            PimMessage::ScheduleDeactivation { role_key, at } => {
+                self.schedule_deactivation(&role_key, at)
+            }
+            PimMessage::SkipActivationConfirmation { role_key } => {
+                self.skip_activation_confirmation(&role_key)
+            }
+            PimMessage::SetRoleLabel { role_key, label } => self.set_role_label(&role_key, &label),
+            PimMessage::SetRoleDuration { role_key, minutes } => {
+                self.set_role_duration(&role_key, minutes)
+            }
+            PimMessage::ClearData(categories) => self.clear_data(&categories),
+            PimMessage::ClaimCaches(owner) => self.claim_caches(owner),
+            PimMessage::SkipGroupLookup(skip) => {
+                self.skip_group_lookup = skip;
+                let outcome = update_settings(|s| s.skip_group_lookup = skip);
+                self.refresh_roles().await;
+                outcome
+            }
+            PimMessage::SkipDeniedSubscriptions { hours } => {
+                let outcome = update_settings(|s| s.skip_denied_subscriptions_hours = hours);
+                self.publish_skipped_subscriptions();
+                outcome
+            }
+            PimMessage::RecheckSubscriptions => {
+                info!("Rechecking subscriptions skipped for lack of PIM access");
+                if self.availability.clear() {
+                    self.save_availability();
+                }
+                self.refresh_roles().await;
+                ActionOutcome::Done
+            }
+            PimMessage::ReviewApproval {
+                request_id,
+                result,
+                justification,
+            } => {
+                self.review_approval(&request_id, result, &justification)
+                    .await
+            }
+            PimMessage::CancelScheduledActivation { request_id } => {
+                self.cancel_scheduled_activation(&request_id).await
+            }
+            PimMessage::SetAdminView { enabled } => {
+                let outcome = update_settings(|s| s.admin_view = enabled);
+                updates::update_pim_tenant_assignments(None);
+                self.refresh_tenant_assignments().await;
+                outcome
+            }
+            PimMessage::ToggleAdminViewSubscription { subscription_id } => {
+                let outcome = update_settings(|s| {
+                    let chosen = &mut s.admin_view_subscriptions;
+                    if let Some(index) = chosen.iter().position(|id| *id == subscription_id) {
+                        chosen.remove(index);
+                    } else {
+                        chosen.push(subscription_id);
+                    }
+                });
+                updates::update_pim_tenant_assignments(None);
+                self.refresh_tenant_assignments().await;
+                outcome
+            }
+        }
+    }
+
+    /// Activate the roles of an elevation plan in an `activation` operation span.
+    async fn activate_plan(&self, name: &str) -> ActionOutcome {
+        let operation = Operation::start(OperationKind::Activation);
+        let outcome = self.run_plan(name).instrument(operation.span()).await;
+        operation.finish(match outcome {
+            ActionOutcome::Failed(_) => Outcome::Failed,
+            _ => Outcome::Succeeded,
+        });
+        outcome
+    }
+
+    /// Activate the roles of an elevation plan, showing progress in the menu.
+    ///
+    /// Roles at different scopes are activated concurrently, up to
+    /// `MAX_CONCURRENT_ACTIVATIONS`; roles at the same scope one after another.
+    /// Roles that are already active count as activated. Failures are
+    /// reported in the summary in plan order rather than offering a retry
+    /// for each role.
+    async fn run_plan(&self, name: &str) -> ActionOutcome {
+        let pim_state = get_app_state()
+            .map(|s| s.get_pim_state())
+            .unwrap_or_default();
+        let Some(plan) = pim_state.settings.plan(name).cloned() else {
+            return ActionOutcome::Failed(format!("Elevation plan \"{}\" not found", name));
+        };
+        info!(
+            "Activating elevation plan {} ({} roles)",
+            plan.name,
+            plan.role_keys.len()
+        );
+
+        let options = plan.activation_options();
+        let mut progress = PlanProgress {
+            name: plan.name.clone(),
+            done: 0,
+            total: plan.role_keys.len(),
+        };
+        updates::update_pim_plan_progress(Some(progress.clone()));
+
+        // Tracked here since the app state is updated asynchronously
+        let mut assignments = pim_state.active_assignments.clone();
+        // Failures by plan index, so the summary follows the plan's order
+        let mut failures: Vec<(usize, String)> = Vec::new();
+        let mut pending: Vec<(usize, String, String)> = Vec::new();
+        let mut requests = Vec::new();
+        for (index, role_key) in plan.role_keys.iter().enumerate() {
+            let role_name = pim_state
+                .eligible_roles
+                .iter()
+                .find(|r| r.favorites_key() == *role_key)
+                .map_or_else(|| role_key.clone(), |r| r.role_name.clone());
+
+            if assignments.iter().any(|a| a.role_key() == *role_key) {
+                info!("{} is already active", role_name);
+                progress.done += 1;
+                continue;
+            }
+            let justification = plan.justification_for(&role_name);
+            match prepare_activation(role_key, justification, &options, &pim_state) {
+                Ok(request) => {
+                    pending.push((index, role_key.clone(), role_name));
+                    requests.push(request);
+                }
+                Err(e) => {
+                    failures.push((index, plan_failure_text(&role_name, e)));
+                    progress.done += 1;
+                }
+            }
+        }
+        updates::update_pim_plan_progress(Some(progress.clone()));
+
+        if !pending.is_empty() {
+            match self.pim_access().await {
+                Ok(access) => {
+                    for (_, role_key, _) in &pending {
+                        updates::update_pim_activating(role_key.clone(), true);
+                    }
+                    let mut results = concurrency::activate_all(
+                        Arc::clone(&self.pim_client),
+                        access.mgmt_token,
+                        requests,
+                    );
+                    while let Some((request_index, result)) = results.recv().await {
+                        let (index, role_key, role_name) = &pending[request_index];
+                        updates::update_pim_activating(role_key.clone(), false);
+                        match result {
+                            Ok(assignment) => {
+                                assignments.push(assignment.clone());
+                                if let Err(e) = pim::save_active_assignments(&assignments) {
+                                    error!("Failed to save active assignments: {}", e);
+                                }
+                                updates::update_pim_role_activated(assignment);
+                            }
+                            Err(PimError::ApprovalRequired(request_id)) => {
+                                record_pending_request(request_id.clone(), role_key.clone());
+                                let error = ActivationError::AwaitingApproval(request_id);
+                                failures.push((*index, plan_failure_text(role_name, error)));
+                            }
+                            Err(e) => {
+                                error!("Failed to activate role {}: {}", role_key, e);
+                                let error = match e {
+                                    PimError::PolicyViolation(rule) => ActivationError::Policy {
+                                        role_name: role_name.clone(),
+                                        remedy: PolicyRemedy::Explain(rule),
+                                    },
+                                    PimError::StepUpRequired(step_up) => {
+                                        ActivationError::StepUp(step_up)
+                                    }
+                                    e => ActivationError::failed(&e),
+                                };
+                                failures.push((*index, plan_failure_text(role_name, error)));
+                            }
+                        }
+                        progress.done += 1;
+                        updates::update_pim_plan_progress(Some(progress.clone()));
+                    }
+                }
+                Err(e) => {
+                    let message = e.into_message();
+                    for (index, _, role_name) in &pending {
+                        failures.push((*index, format!("{}: {}", role_name, message)));
+                    }
+                }
+            }
+        }
+        updates::update_pim_plan_progress(None);
+
+        failures.sort_by_key(|(index, _)| *index);
+        let failures: Vec<String> = failures.into_iter().map(|(_, text)| text).collect();
+        let activated = progress.total - failures.len();
+        if failures.is_empty() {
+            ActionOutcome::Succeeded(format!("{}: all roles active", plan.name))
+        } else {
+            updates::request_attention();
+            ActionOutcome::Failed(format!(
+                "{}: {} of {} roles active. {}",
+                plan.name,
+                activated,
+                progress.total,
+                failures.join("; ")
+            ))
+        }
+    }
+
+    /// Save the currently active roles as an elevation plan.
+    fn save_plan(&mut self, draft: PlanDraft) -> ActionOutcome {
+        let role_keys: Vec<String> = get_app_state()
+            .map(|s| s.get_pim_state().active_assignments)
+            .unwrap_or_default()
+            .iter()
+            .map(|a| a.role_key())
+            .collect();
+        if role_keys.is_empty() {
+            return ActionOutcome::Failed("Activate the roles for the plan first.".to_string());
+        }
+
+        let count = role_keys.len();
+        let name = draft.name.clone();
+        match self.persist_pim_settings(|settings| settings.save_plan(draft.into_plan(role_keys))) {
+            ActionOutcome::Done => {
+                info!("Saved elevation plan {} with {} roles", name, count);
+                ActionOutcome::Succeeded(format!("Saved plan \"{}\" with {} roles", name, count))
+            }
+            outcome => outcome,
+        }
+    }
+
+    /// Stop confirming activations of a role and persist the choice.
+    fn skip_activation_confirmation(&mut self, role_key: &str) -> ActionOutcome {
+        info!("Activations of {} no longer need confirmation", role_key);
+        self.persist_pim_settings(|settings| settings.skip_activation_confirmation(role_key))
+    }
+
+    /// Label a role in the menu and palette.
+    fn set_role_label(&mut self, role_key: &str, label: &str) -> ActionOutcome {
+        self.persist_pim_settings(|settings| {
+            settings.set_label(role_key, label);
+            info!(
+                "Label of {} set to {:?}",
+                role_key,
+                settings.label_for(role_key)
+            );
+        })
+    }
+
+    /// Remember how long to activate the role for.
+    fn set_role_duration(&mut self, role_key: &str, minutes: u32) -> ActionOutcome {
+        info!("Activations of {} now last {} minutes", role_key, minutes);
+        self.persist_pim_settings(|settings| settings.set_duration(role_key, minutes))
+    }
+
+    /// Apply a change to the PIM settings, show it and save it.
+    ///
+    /// The app state is updated right away, as for favorites, so the next
+    /// message sees the change.
+    fn persist_pim_settings(&mut self, change: impl FnOnce(&mut PimSettings)) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let mut settings = state.get_pim_settings();
+        change(&mut settings);
+        state.set_pim_settings(settings.clone());
+        updates::rebuild_menu();
+
+        match pim::save_pim_settings(&settings) {
+            Ok(()) => {
+                // Our own write is not a sync from elsewhere
+                self.mark_settings_seen();
+                ActionOutcome::Done
+            }
+            Err(e) => {
+                error!("Failed to save PIM settings: {}", e);
+                ActionOutcome::Failed(e.user_message().to_string())
+            }
+        }
+    }
+
+    /// Record the settings file's current state without reloading it.
+    fn mark_settings_seen(&mut self) {
+        if let Some(path) = pim::settings::get_settings_path() {
+            self.settings_watcher.poll(&path);
+        }
+    }
+
+    /// Reload favorites and presets if the file changed outside this app.
+    fn reload_changed_settings(&mut self) {
+        let Some(path) = pim::settings::get_settings_path() else {
+            return;
+        };
+        if self.settings_watcher.poll(&path) {
+            info!("PIM settings changed on disk, reloading from {:?}", path);
+            updates::update_pim_settings(pim::load_pim_settings());
+        }
+    }
+
+    /// Move favorites and presets to another file.
+    ///
+    /// An existing file is adopted, e.g. one synced from another machine.
+    /// Otherwise the current settings are written to the new location.
+    fn set_favorites_file(&mut self, path: Option<PathBuf>) -> ActionOutcome {
+        let previous = pim::settings::get_custom_settings_path();
+        pim::settings::set_custom_settings_path(path.clone());
+        let Some(file) = pim::settings::get_settings_path() else {
+            pim::settings::set_custom_settings_path(previous);
+            return ActionOutcome::Failed("Could not determine config directory".to_string());
+        };
+
+        if file.exists() {
+            info!("Using existing PIM settings at {:?}", file);
+            updates::update_pim_settings(pim::load_pim_settings());
+        } else {
+            let current = get_app_state()
+                .map(|s| s.get_pim_state().settings)
+                .unwrap_or_default();
+            if let Err(e) = pim::save_pim_settings(&current) {
+                error!("Failed to write PIM settings to {:?}: {}", file, e);
+                pim::settings::set_custom_settings_path(previous);
+                return ActionOutcome::Failed(e.user_message().to_string());
+            }
+            info!("Moved PIM settings to {:?}", file);
+        }
+        self.settings_watcher.reset();
+        self.mark_settings_seen();
+
+        match update_settings(|s| s.favorites_file = path) {
+            ActionOutcome::Done => {
+                ActionOutcome::Succeeded(format!("Favorites are now stored in {}", file.display()))
+            }
+            outcome => outcome,
+        }
+    }
+
+    /// Remove stored PIM data of the chosen categories and forget it for this session.
+    fn clear_data(&mut self, categories: &[DataCategory]) -> ActionOutcome {
+        let mut failed = Vec::new();
+        let mut remove = |what: &'static str, result: Result<(), PimError>| {
+            if let Err(e) = result {
+                error!("Failed to delete {}: {}", what, e);
+                failed.push(what);
+            }
+        };
+
+        if categories.contains(&DataCategory::Settings) {
+            info!("Clearing favorites, presets and plans");
+            remove("PIM settings", pim::delete_pim_settings());
+            self.settings_watcher.reset();
+            self.skip_group_lookup = false;
+            updates::update_pim_settings(PimSettings::default());
+        }
+        if categories.contains(&DataCategory::Caches) {
+            self.clear_account_data(&mut remove);
+        }
+        if categories.contains(&DataCategory::SessionNotes) {
+            info!("Clearing session notes");
+            remove("session notes", pim::delete_session_notes());
+            self.notes = NotesStore::default();
+            updates::update_pim_session_notes(self.notes.clone());
+        }
+
+        if failed.is_empty() {
+            ActionOutcome::Done
+        } else {
+            ActionOutcome::Failed(format!("Could not delete {}", failed.join(", ")))
+        }
+    }
+
+    /// Forget the signed-in account's cached roles, assignments and schedules,
+    /// on disk and in memory. Favorites, settings and session notes are kept.
+    fn clear_account_data(&mut self, remove: &mut impl FnMut(&'static str, Result<(), PimError>)) {
+        info!("Clearing cached roles and assignments");
+        remove("saved active assignments", pim::delete_active_assignments());
+        remove("cached roles", pim::delete_roles_cache());
+        remove("saved subscription names", pim::delete_subscription_names());
+        remove(
+            "scheduled deactivations",
+            pim::delete_scheduled_deactivations(),
+        );
+        remove(
+            "subscriptions without PIM access",
+            pim::delete_pim_availability(),
+        );
+        remove("cache owner", pim::delete_cache_owner());
+        self.scheduled = ScheduledDeactivations::default();
+        self.availability = PimAvailability::default();
+        self.reminders = DeactivationReminders::default();
+        self.warned.clear();
+        self.principal_ids.clear();
+        if let Some(state) = get_app_state() {
+            let pim_state = state.get_pim_state();
+            state.set_pim_state(PimState {
+                settings: pim_state.settings,
+                session_notes: pim_state.session_notes,
+                ..PimState::default()
+            });
+        }
+        updates::rebuild_menu();
+    }
+
+    /// Take over the caches for the account that signed in.
+    ///
+    /// Caches saved for another account, or for no recorded one, are
+    /// forgotten first.
+    fn claim_caches(&mut self, owner: CacheOwner) -> ActionOutcome {
+        let recorded = pim::load_cache_owner();
+        let outcome = if owner.keeps_caches_of(recorded.as_ref(), pim::account_caches_exist()) {
+            ActionOutcome::Done
+        } else if recorded.is_none() {
+            info!("Cached roles have no recorded account, forgetting them");
+            self.forget_account()
+        } else {
+            info!("Signed in as another account, forgetting the previous one's cached roles");
+            self.forget_account()
+        };
+        if let Err(e) = pim::save_cache_owner(&owner) {
+            error!("Failed to save cache owner: {}", e);
+        }
+        outcome
+    }
+
+    /// Forget the previous account, so the one signing in starts without its roles.
+    fn forget_account(&mut self) -> ActionOutcome {
+        let mut failed = Vec::new();
+        self.clear_account_data(&mut |what, result| {
+            if let Err(e) = result {
+                error!("Failed to delete {}: {}", what, e);
+                failed.push(what);
+            }
+        });
+        if failed.is_empty() {
+            ActionOutcome::Done
+        } else {
+            ActionOutcome::Failed(format!("Could not delete {}", failed.join(", ")))
+        }
+    }
+
+    /// Report configured API versions that Azure has retired.
+    async fn check_api_versions(&self, mgmt_token: &str) {
+        let problems = match self.pim_client.check_api_versions(mgmt_token).await {
+            Ok(problems) => problems,
+            Err(e) => {
+                warn!("Failed to check API versions: {}", e);
+                return;
+            }
+        };
+        for problem in &problems {
+            warn!("{}", problem.advice());
+        }
+        if let Some(problem) = problems.first() {
+            notifications::notify("Azure API version retired", &problem.advice());
+        }
+    }
+
+    /// Publish the activation rules of the eligible roles, reading the
+    /// policies of roles not read before; a policy is unlikely to change
+    /// within a session.
+    async fn refresh_role_policies(&mut self, mgmt_token: &str, roles: &[EligibleRole]) {
+        let unread: Vec<EligibleRole> = roles
+            .iter()
+            .filter(|r| !self.role_policies.contains_key(&r.favorites_key()))
+            .cloned()
+            .collect();
+        if !unread.is_empty() {
+            match self.pim_client.get_role_policies(mgmt_token, &unread).await {
+                Ok(found) => self.role_policies.extend(found),
+                Err(e) => {
+                    warn!("Failed to read role policies: {}", e);
+                    return;
+                }
+            }
+        }
+        let policies: HashMap<String, RolePolicy> = roles
+            .iter()
+            .filter_map(|r| {
+                let key = r.favorites_key();
+                let policy = *self.role_policies.get(&key)?;
+                Some((key, policy))
+            })
+            .collect();
+        let needing_approval = policies.values().filter(|p| p.approval_required).count();
+        info!(
+            "Read policies of {} eligible roles, {} need approval",
+            policies.len(),
+            needing_approval
+        );
+        updates::update_pim_role_policies(policies);
+    }
+
+    /// Fetch eligible roles and active assignments in a `pim-scan` operation span.
+    async fn refresh_roles(&mut self) {
+        let operation = Operation::start(OperationKind::PimScan);
+        let outcome = self.scan_roles().instrument(operation.span()).await;
+        operation.finish(outcome);
+        self.refresh_tenant_assignments().await;
+    }
+
+    /// Fetch eligible roles and active assignments for the user and their groups.
+    ///
+    /// The outcome is that of the eligible roles; the assignments are best effort.
+    async fn scan_roles(&mut self) -> Outcome {
+        info!("Refreshing PIM roles");
+        updates::update_pim_loading();
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(AccessError::Failed(message)) => {
+                updates::update_pim_error(message);
+                return Outcome::Failed;
+            }
+            Err(AccessError::PermissionDenied(message)) => {
+                updates::update_pim_permission_denied(message);
+                return Outcome::Failed;
+            }
+            Err(AccessError::ArmPermissionMissing { consent_url }) => {
+                updates::update_pim_arm_permission_missing(consent_url);
+                return Outcome::Failed;
+            }
+            Err(AccessError::Offline) => {
+                // Keep showing the cached roles; the auth actor retries
+                warn!("Azure unreachable, showing cached PIM roles");
+                updates::update_offline(None);
+                return Outcome::Offline;
+            }
+        };
+        self.principal_ids = access.principal_ids.clone();
+        updates::update_pim_group_lookup(access.group_lookup);
+        if !self.api_versions_checked {
+            self.api_versions_checked = true;
+            self.check_api_versions(&access.mgmt_token).await;
+        }
+        self.refresh_subscription_names(&access.mgmt_token).await;
+
+        // Fetch eligible roles for user and all groups
+        let now = Utc::now();
+        let period = skip_period();
+        let skipped = self.availability.skipped_names(now, period);
+        if !skipped.is_empty() {
+            info!(
+                "Skipping {} subscriptions without PIM access: {}",
+                skipped.len(),
+                skipped.join(", ")
+            );
+        }
+        let outcome = match self
+            .pim_client
+            .get_all_eligible_roles(
+                &access.mgmt_token,
+                &access.principal_ids,
+                &self.availability.skipped_ids(now, period),
+            )
+            .await
+        {
+            Ok(scan) => {
+                let mut roles = scan.roles;
+                label_group_grants(&mut roles, &access.group_names);
+                info!("Found {}", eligibility_report(&roles));
+                self.record_availability(&scan.scanned, &scan.denied, now);
+                updates::update_pim_eligible_roles(roles.clone(), access.group_names.clone());
+                self.refresh_role_policies(&access.mgmt_token, &roles).await;
+                Outcome::Succeeded
+            }
+            Err(e) if e.is_offline() => {
+                warn!("Azure unreachable, showing cached PIM roles: {}", e);
+                updates::update_offline(None);
+                return Outcome::Offline;
+            }
+            Err(e) => {
+                error!("Failed to fetch PIM roles: {}", e);
+                updates::update_pim_error(format!("Failed to fetch roles: {}", e));
+                Outcome::Failed
+            }
+        };
+
+        // Also fetch active assignments for user and all groups
+        match self
+            .pim_client
+            .get_active_assignments(&access.mgmt_token, &access.principal_ids)
+            .await
+        {
+            Ok(assignments) => {
+                info!("Found {} active PIM assignments", assignments.len());
+                set_active_assignments(assignments);
+            }
+            Err(e) => {
+                error!("Failed to fetch active assignments: {}", e);
+                // Don't update error - roles may still be available
+            }
+        }
+        outcome
+    }
+
+    /// Replace assignments restored from disk with the current ones from Azure.
+    ///
+    /// Runs in the background, so failures are only logged and the restored
+    /// assignments stay visible.
+    async fn reconcile_assignments(&mut self) {
+        info!("Reconciling restored active assignments");
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => {
+                warn!(
+                    "Could not reconcile active assignments: {}",
+                    e.into_message()
+                );
+                return;
+            }
+        };
+        self.principal_ids = access.principal_ids.clone();
+
+        match self
+            .pim_client
+            .get_active_assignments(&access.mgmt_token, &access.principal_ids)
+            .await
+        {
+            Ok(assignments) => {
+                info!("Reconciled {} active PIM assignments", assignments.len());
+                set_active_assignments(assignments);
+            }
+            Err(e) => warn!("Could not reconcile active assignments: {}", e),
+        }
+    }
+
+    /// Reconcile active assignments by querying only the subscriptions with active roles.
+    ///
+    /// Much cheaper than a full refresh, which scans every subscription for
+    /// eligibility too. Every `WIDE_POLL_EVERY` polls the subscriptions of
+    /// eligible roles are included, to pick up roles activated in the portal.
+    /// Roles revoked outside the app are dropped with a notification.
+    /// Failures are left to the next poll.
+    async fn poll_active_assignments(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let pim_state = state.get_pim_state();
+        let mut subscriptions = pim::active_subscriptions(&pim_state.active_assignments);
+        self.polls_since_wide += 1;
+        if self.polls_since_wide >= WIDE_POLL_EVERY {
+            self.polls_since_wide = 0;
+            for subscription in pim::eligible_subscriptions(&pim_state.eligible_roles) {
+                if !subscriptions
+                    .iter()
+                    .any(|s| s.subscription_id == subscription.subscription_id)
+                {
+                    subscriptions.push(subscription);
+                }
+            }
+        }
+        if state.get_auth_state() != AuthState::SignedIn
+            || subscriptions.is_empty()
+            || self.principal_ids.is_empty()
+        {
+            return;
+        }
+
+        let mgmt_token = match keychain::get_refresh_token() {
+            Ok(refresh_token) => self.management_token(&refresh_token).await,
+            Err(e) => Err(AccessError::Failed(e.user_message().to_string())),
+        };
+        let mgmt_token = match mgmt_token {
+            Ok(token) => token,
+            Err(e) => {
+                debug!("Skipping active assignment poll: {}", e.into_message());
+                return;
+            }
+        };
+        self.refresh_subscription_names(&mgmt_token).await;
+
+        debug!(
+            "Polling active assignments in {} subscriptions",
+            subscriptions.len()
+        );
+        let polled = match self
+            .pim_client
+            .get_active_assignments_in(&mgmt_token, &subscriptions, &self.principal_ids)
+            .await
+        {
+            Ok(polled) => polled,
+            Err(e) => {
+                debug!("Active assignment poll failed: {}", e);
+                return;
+            }
+        };
+
+        // Re-read, since an activation may have landed while polling
+        let current = state.get_pim_state().active_assignments;
+        let reconciled = pim::reconcile_polled_assignments(current.clone(), polled, Utc::now());
+        for assignment in &reconciled.added {
+            info!(
+                "{} on {} was activated outside the app",
+                assignment.role_name, assignment.subscription_name
+            );
+        }
+        for assignment in &reconciled.revoked {
+            let role_text = format!(
+                "{} on {}",
+                assignment.role_name, assignment.subscription_name
+            );
+            warn!("{} was revoked or deactivated outside the app", role_text);
+            notifications::notify(
+                "PIM role revoked",
+                &format!(
+                    "{} is no longer active; it was revoked or deactivated outside the app",
+                    role_text
+                ),
+            );
+        }
+        if reconciled.assignments != current {
+            info!("Active assignments changed since the last poll");
+            set_active_assignments(reconciled.assignments);
+        }
+    }
+
+    /// List everyone's active roles in the subscriptions chosen for the
+    /// administrator view, if it's on.
+    ///
+    /// Failures keep the last list until the next poll.
+    async fn refresh_tenant_assignments(&self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let settings = state.get_settings();
+        if !settings.admin_view || state.get_auth_state() != AuthState::SignedIn {
+            return;
+        }
+        let names = state.get_pim_state().subscription_names;
+        let subscriptions: Vec<Subscription> = settings
+            .admin_view_subscriptions
+            .iter()
+            .map(|id| Subscription {
+                subscription_id: id.clone(),
+                display_name: names.get(id).unwrap_or(id).to_string(),
+                state: "Enabled".to_string(),
+            })
+            .collect();
+        if subscriptions.is_empty() {
+            updates::update_pim_tenant_assignments(Some(Vec::new()));
+            return;
+        }
+
+        let mgmt_token = match keychain::get_refresh_token() {
+            Ok(refresh_token) => self.management_token(&refresh_token).await,
+            Err(e) => Err(AccessError::Failed(e.user_message().to_string())),
+        };
+        let mgmt_token = match mgmt_token {
+            Ok(token) => token,
+            Err(e) => {
+                debug!("Skipping administrator view refresh: {}", e.into_message());
+                return;
+            }
+        };
+        match self
+            .pim_client
+            .get_tenant_assignments(&mgmt_token, &subscriptions)
+            .await
+        {
+            Ok(assignments) => {
+                debug!(
+                    "{} active roles in {} subscriptions",
+                    assignments.len(),
+                    subscriptions.len()
+                );
+                updates::update_pim_tenant_assignments(Some(assignments));
+            }
+            Err(e) => warn!("Failed to list everyone's active roles: {}", e),
+        }
+    }
+
+    /// Fetch subscription names if the known ones are over an hour old.
+    ///
+    /// Renamed subscriptions then show their new name on cached roles and
+    /// assignments. Failures keep the old names until the next attempt.
+    async fn refresh_subscription_names(&self, mgmt_token: &str) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let mut names = state.get_pim_state().subscription_names;
+        let now = Utc::now();
+        if !names.is_stale(now) {
+            return;
+        }
+
+        match self.pim_client.list_subscriptions(mgmt_token).await {
+            Ok(subscriptions) => {
+                if names.update(&subscriptions, now) {
+                    info!("Subscription names changed");
+                }
+                if let Err(e) = pim::save_subscription_names(&names) {
+                    error!("Failed to save subscription names: {}", e);
+                }
+                updates::update_subscription_names(names);
+            }
+            Err(e) => debug!("Could not refresh subscription names: {}", e),
+        }
+    }
+
+    /// Request activation of an eligible role, for the default duration unless overridden.
+    ///
+    /// Returns the accepted request and the Management API token it was sent
+    /// with, to watch its provisioning.
+    async fn activate_role(
+        &self,
+        role_key: &str,
+        justification: String,
+        options: &ActivationOptions,
+    ) -> Result<(SubmittedActivation, String), ActivationError> {
+        let pim_state = get_app_state()
+            .map(|s| s.get_pim_state())
+            .unwrap_or_default();
+        let request = prepare_activation(role_key, justification, options, &pim_state)?;
+
+        let mut access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return Err(ActivationError::Failed(e.into_message())),
+        };
+        if let Some(claims) = options.step_up.as_ref().and_then(StepUp::claims) {
+            access.mgmt_token = self.step_up_token(claims).await?;
+        }
+
+        let eligible_role = request.eligible_role.clone();
+        let duration_minutes = request.duration_minutes;
+        match self
+            .pim_client
+            .submit_activation(&access.mgmt_token, request)
+            .await
+        {
+            Ok(submitted) => Ok((submitted, access.mgmt_token)),
+            Err(PimError::StepUpRequired(step_up)) if options.step_up.is_none() => {
+                info!("Activation of {} requires {}", role_key, step_up);
+                Err(ActivationError::StepUp(step_up))
+            }
+            Err(PimError::StepUpRequired(step_up)) => {
+                warn!("Activation of {} still requires {}", role_key, step_up);
+                Err(ActivationError::Failed(format!(
+                    "Azure still requires {} after signing in again.",
+                    step_up
+                )))
+            }
+            Err(PimError::PolicyViolation(rule)) => {
+                warn!("Activation of {} failed policy rule: {}", role_key, rule);
+                let remedy = match rule {
+                    PolicyRule::Expiration => {
+                        match self
+                            .pim_client
+                            .get_max_activation_minutes(
+                                &access.mgmt_token,
+                                &eligible_role.scope,
+                                &eligible_role.role_definition_id,
+                            )
+                            .await
+                        {
+                            Ok(Some(max)) if max < duration_minutes => {
+                                PolicyRemedy::MaxDuration(max)
+                            }
+                            Ok(_) => PolicyRemedy::Explain(rule),
+                            Err(e) => {
+                                warn!("Failed to fetch role policy: {}", e);
+                                PolicyRemedy::Explain(rule)
+                            }
+                        }
+                    }
+                    PolicyRule::Justification => PolicyRemedy::EnterJustification,
+                    PolicyRule::Ticketing => PolicyRemedy::EnterTicketNumber,
+                    rule => PolicyRemedy::Explain(rule),
+                };
+                Err(ActivationError::Policy {
+                    role_name: eligible_role.role_name,
+                    remedy,
+                })
+            }
+            Err(PimError::ApprovalRequired(request_id)) => {
+                info!("Activation of {} is waiting for approval", role_key);
+                Err(ActivationError::AwaitingApproval(request_id))
+            }
+            Err(e) => {
+                error!("Failed to activate role {}: {}", role_key, e);
+                Err(ActivationError::failed(&e))
+            }
+        }
+    }
+
+    /// Report the result of an activation and update the menu with it.
+    fn finish_activation(
+        &mut self,
+        operation: Operation,
+        role_key: String,
+        justification: String,
+        options: ActivationOptions,
+        result: Result<ActiveAssignment, ActivationError>,
+    ) -> ActionOutcome {
+        operation.finish(
+            if matches!(result, Ok(_) | Err(ActivationError::AwaitingApproval(_))) {
+                Outcome::Succeeded
+            } else {
+                Outcome::Failed
+            },
+        );
+        updates::update_pim_activating(role_key.clone(), false);
+
+        match result {
+            Ok(assignment) if assignment.start_time > Utc::now() => {
+                let text = format!(
+                    "{} - {}: {}",
+                    assignment.role_name,
+                    assignment.subscription_name,
+                    schedule::scheduled_activation_text(assignment.start_time)
+                );
+                updates::add_pim_scheduled_activation(ScheduledActivation {
+                    id: assignment.id,
+                    role_key,
+                    start_time: assignment.start_time,
+                });
+                ActionOutcome::Succeeded(text)
+            }
+            Ok(assignment) => {
+                let mut assignments = get_app_state()
+                    .map(|s| s.get_pim_state().active_assignments)
+                    .unwrap_or_default();
+                // The assignment poll may have found it while it was provisioning
+                assignments.retain(|a| a.role_key() != assignment.role_key());
+                assignments.push(assignment.clone());
+                if let Err(e) = pim::save_active_assignments(&assignments) {
+                    error!("Failed to save active assignments: {}", e);
+                }
+                if let Some(at) = options.deactivate_at {
+                    if at < assignment.end_time {
+                        self.set_scheduled_deactivation(&role_key, Some(at));
+                    } else {
+                        info!("{} expires before its scheduled deactivation", role_key);
+                    }
+                }
+                self.offer_key_vault_token(&assignment);
+                notifications::notify_event(
+                    NotificationEvent::for_activation(true),
+                    "PIM role activated",
+                    &format!(
+                        "{} on {}",
+                        assignment.role_name, assignment.subscription_name
+                    ),
+                );
+                updates::update_pim_role_activated(assignment);
+                ActionOutcome::Done
+            }
+            Err(ActivationError::Policy { role_name, remedy }) => {
+                // The dialog explains the failure, so no notification
+                updates::offer_policy_retry(role_key, role_name, justification, options, remedy);
+                ActionOutcome::Done
+            }
+            Err(ActivationError::Failed(message)) => {
+                // Notified here with the configured sound rather than as a passive outcome
+                error!("Failed to activate {}: {}", role_key, message);
+                notifications::notify_event(
+                    NotificationEvent::for_activation(false),
+                    "PIM role activation failed",
+                    &message,
+                );
+                updates::request_attention();
+                ActionOutcome::Done
+            }
+            Err(ActivationError::AwaitingApproval(request_id)) => {
+                record_pending_request(request_id, role_key);
+                ActionOutcome::Succeeded(
+                    "Sent for approval. The role activates once an approver signs off.".to_string(),
+                )
+            }
+            Err(ActivationError::StepUp(step_up)) => {
+                let text = format!(
+                    "Complete {} in your browser. The role activates afterwards.",
+                    step_up
+                );
+                delegate::send_step_up_activation(step_up, role_key, justification, options);
+                ActionOutcome::Succeeded(text)
+            }
+        }
+    }
+
+    /// Poll a pending activation in the background, so the actor keeps
+    /// handling messages while Azure provisions it.
+    fn watch_provisioning(&self, activation: PendingProvisioning, mgmt_token: String) {
+        let pim_client = Arc::clone(&self.pim_client);
+        let tx = self.provisioned_tx.clone();
+        tokio::spawn(async move {
+            let result = pim_client
+                .wait_for_provisioning(&mgmt_token, &activation.assignment)
+                .instrument(activation.operation.span())
+                .await;
+            let _ = tx.send((activation, result)).await;
+        });
+    }
+
+    /// Report an activation once Azure has provisioned it, or refused to.
+    fn finish_provisioning(
+        &mut self,
+        activation: PendingProvisioning,
+        result: Result<(), PimError>,
+    ) -> ActionOutcome {
+        let PendingProvisioning {
+            role_key,
+            justification,
+            options,
+            assignment,
+            operation,
+        } = activation;
+        if is_signed_out() {
+            info!("Signed out while {} was provisioning", role_key);
+            operation.finish(Outcome::Cancelled);
+            updates::update_pim_activating(role_key, false);
+            return ActionOutcome::Done;
+        }
+        let result = match result {
+            Ok(()) => Ok(assignment),
+            Err(PimError::ApprovalRequired(request_id)) => {
+                info!("Activation of {} is waiting for approval", role_key);
+                Err(ActivationError::AwaitingApproval(request_id))
+            }
+            Err(e) => {
+                error!("Failed to activate role {}: {}", role_key, e);
+                Err(ActivationError::failed(&e))
+            }
+        };
+        self.finish_activation(operation, role_key, justification, options, result)
+    }
+
+    /// Get a Management API token with the claims a step-up sign-in satisfied.
+    async fn step_up_token(&self, claims: &str) -> Result<String, ActivationError> {
+        let refresh_token = keychain::get_refresh_token()
+            .map_err(|e| ActivationError::Failed(e.user_message().to_string()))?;
+        match self
+            .oauth_client
+            .get_management_token_with_claims(&refresh_token, claims)
+            .await
+        {
+            Ok(response) => Ok(response.access_token),
+            Err(e) => {
+                error!("Failed to get Management API token with claims: {}", e);
+                Err(ActivationError::Failed(
+                    "Failed to refresh token".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// After activating a Key Vault data role, get a Key Vault token and offer
+    /// to copy it, if enabled in settings.
+    ///
+    /// Runs in the background so the activation reports its result right away.
+    fn offer_key_vault_token(&self, assignment: &ActiveAssignment) {
+        let enabled = get_app_state().is_some_and(|s| s.get_settings().offer_key_vault_token);
+        if !enabled || !is_key_vault_data_role(&assignment.role_name) {
+            return;
+        }
+        let oauth_client = Arc::clone(&self.oauth_client);
+        let role_text = format!(
+            "{} - {}",
+            assignment.role_name, assignment.subscription_name
+        );
+        tokio::spawn(async move {
+            let token = match keychain::get_refresh_token() {
+                Ok(refresh_token) => oauth_client.get_key_vault_token(&refresh_token).await,
+                Err(e) => {
+                    warn!("No refresh token for a Key Vault token: {}", e);
+                    return;
+                }
+            };
+            match token {
+                Ok(response) => {
+                    if updates::offer_key_vault_token(role_text, response.access_token).await {
+                        delegate::schedule_clipboard_clear();
+                    }
+                }
+                Err(e) => warn!("Could not get a Key Vault token: {}", e),
+            }
+        });
+    }
+
+    /// Validate an activation of the role for the default duration and report the result.
+    ///
+    /// Nothing is activated, so this can verify access ahead of a change window.
+    async fn check_activation(&self, role_key: &str) -> ActionOutcome {
+        let pim_state = get_app_state()
+            .map(|s| s.get_pim_state())
+            .unwrap_or_default();
+        let Some(eligible_role) = pim_state
+            .eligible_roles
+            .iter()
+            .find(|r| r.favorites_key() == role_key)
+            .cloned()
+        else {
+            return ActionOutcome::Failed(
+                PimError::RoleNotFound(role_key.to_string())
+                    .user_message()
+                    .to_string(),
+            );
+        };
+        let duration_minutes = pim_state.duration_for(role_key);
+        let role_name = eligible_role.role_name.clone();
+
+        // Known conflicts don't need a round trip
+        if pim_state
+            .active_assignments
+            .iter()
+            .any(|a| a.role_key() == role_key)
+        {
+            updates::show_activation_check(
+                role_name,
+                duration_minutes,
+                ActivationCheck::AlreadyActive,
+            );
+            return ActionOutcome::Done;
+        }
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+        let request = ActivationRequest {
+            eligible_role,
+            justification: CHECK_JUSTIFICATION.to_string(),
+            duration_minutes,
+            ticket_number: None,
+            start_time: None,
+        };
+        match self
+            .pim_client
+            .validate_activation(&access.mgmt_token, &request)
+            .await
+        {
+            Ok(check) => {
+                info!("Activation check for {}: {:?}", role_key, check);
+                updates::show_activation_check(role_name, duration_minutes, check);
+                ActionOutcome::Done
+            }
+            Err(e) => {
+                error!("Failed to check activation of {}: {}", role_key, e);
+                ActionOutcome::Failed(e.user_message().to_string())
+            }
+        }
+    }
+
+    /// Deactivate every active assignment, keeping those that fail.
+    ///
+    /// Requests are sent concurrently like an elevation plan's. Failures are
+    /// reported per role in the summary, in the order of the Active Roles list.
+    async fn deactivate_all(&mut self) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let assignments = state.get_pim_state().active_assignments;
+        if assignments.is_empty() {
+            return ActionOutcome::Done;
+        }
+        let total = assignments.len();
+        info!("Deactivating {} active roles", total);
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+        // The signed-in user is always the first principal
+        let user_id = access.principal_ids[0].clone();
+
+        let mut results = concurrency::deactivate_all(
+            Arc::clone(&self.pim_client),
+            access.mgmt_token,
+            user_id,
+            assignments.clone(),
+        );
+        let mut failed: Vec<(usize, String)> = Vec::new();
+        let mut deactivated = Vec::new();
+        while let Some((index, result)) = results.recv().await {
+            let assignment = &assignments[index];
+            match result {
+                Ok(()) => deactivated.push(assignment.role_key()),
+                Err(e) => {
+                    warn!("Failed to deactivate {}: {}", assignment.role_name, e);
+                    failed.push((index, e.user_message().to_string()));
+                }
+            }
+        }
+        failed.sort_by_key(|(index, _)| *index);
+
+        let remaining: Vec<ActiveAssignment> = failed
+            .iter()
+            .map(|(index, _)| assignments[*index].clone())
+            .collect();
+        // Applied directly: the main thread is waiting for shutdown to finish
+        state.set_pim_active_assignments(remaining.clone());
+        if let Err(e) = pim::save_active_assignments(&remaining) {
+            error!("Failed to save active assignments: {}", e);
+        }
+        let (mut reminders_changed, mut schedule_changed) = (false, false);
+        for key in &deactivated {
+            reminders_changed |= self.reminders.cancel(key);
+            schedule_changed |= self.scheduled.cancel(key);
+        }
+        if reminders_changed {
+            updates::update_pim_deactivation_reminders(self.reminders.clone());
+        }
+        if schedule_changed {
+            self.save_scheduled_deactivations();
+        }
+
+        if failed.is_empty() {
+            return ActionOutcome::Succeeded(match total {
+                1 => "Active role deactivated".to_string(),
+                n => format!("All {} active roles deactivated", n),
+            });
+        }
+        let failures: Vec<String> = failed
+            .into_iter()
+            .map(|(index, message)| {
+                let assignment = &assignments[index];
+                format!(
+                    "{} on {}: {}",
+                    assignment.role_name, assignment.subscription_name, message
+                )
+            })
+            .collect();
+        updates::request_attention();
+        ActionOutcome::Failed(format!(
+            "{} of {} roles deactivated. {}",
+            total - failures.len(),
+            total,
+            failures.join("; ")
+        ))
+    }
+
+    /// Deactivate the active assignment of a single role.
+    async fn deactivate_role(&mut self, role_key: &str) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let mut assignments = state.get_pim_state().active_assignments;
+        let Some(idx) = assignments.iter().position(|a| a.role_key() == role_key) else {
+            info!("Role {} is no longer active", role_key);
+            return ActionOutcome::Done;
+        };
+        let assignment = assignments.remove(idx);
+        info!("Deactivating {}", assignment.role_name);
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+        // The signed-in user is always the first principal
+        let user_id = &access.principal_ids[0];
+        if let Err(e) = self
+            .pim_client
+            .deactivate_role(&access.mgmt_token, &assignment, user_id)
+            .await
+        {
+            warn!("Failed to deactivate {}: {}", assignment.role_name, e);
+            return ActionOutcome::Failed(e.user_message().to_string());
+        }
+
+        if self.reminders.cancel(role_key) {
+            updates::update_pim_deactivation_reminders(self.reminders.clone());
+        }
+        if self.scheduled.due_at(role_key).is_some() {
+            self.set_scheduled_deactivation(role_key, None);
+        }
+        set_active_assignments(assignments);
+        ActionOutcome::Succeeded(format!(
+            "{} on {} deactivated",
+            assignment.role_name, assignment.subscription_name
+        ))
+    }
+
+    /// Approve or deny another user's request, then drop it from the menu.
+    async fn review_approval(
+        &mut self,
+        request_id: &str,
+        result: ReviewResult,
+        justification: &str,
+    ) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let pim_state = state.get_pim_state();
+        let (reviewed, remaining): (Vec<_>, Vec<_>) = pim_state
+            .approval_requests
+            .into_iter()
+            .partition(|r| r.is(request_id));
+        let Some(request) = reviewed.into_iter().next() else {
+            return ActionOutcome::Failed(
+                "The request is no longer waiting for your review.".to_string(),
+            );
+        };
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+        if let Err(e) = self
+            .pim_client
+            .review_approval(&access.mgmt_token, &request, result, justification)
+            .await
+        {
+            warn!("Failed to review {}: {}", request.menu_title(), e);
+            return ActionOutcome::Failed(match e {
+                PimError::ReviewFailed(reason) => format!("Azure refused the review: {}.", reason),
+                e => e.user_message().to_string(),
+            });
+        }
+
+        // The approvals worker confirms on its next poll
+        let mut approvals = pim_state.approvals;
+        approvals.awaiting_my_approval = approvals.awaiting_my_approval.saturating_sub(1);
+        updates::update_pim_approvals(approvals);
+        updates::update_pim_approval_requests(remaining);
+        let verb = match result {
+            ReviewResult::Approve => "Approved",
+            ReviewResult::Deny => "Denied",
+        };
+        ActionOutcome::Succeeded(format!(
+            "{} {} for {}",
+            verb, request.role_name, request.principal_name
+        ))
+    }
+
+    /// Cancel one of the user's scheduled activations before it starts.
+    async fn cancel_scheduled_activation(&mut self, request_id: &str) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let pim_state = state.get_pim_state();
+        let (cancelled, remaining): (Vec<_>, Vec<_>) = pim_state
+            .scheduled_activations
+            .into_iter()
+            .partition(|s| s.is(request_id));
+        let Some(activation) = cancelled.into_iter().next() else {
+            return ActionOutcome::Failed("The activation is no longer scheduled.".to_string());
+        };
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+        if let Err(e) = self
+            .pim_client
+            .cancel_schedule_request(&access.mgmt_token, &activation.id)
+            .await
+        {
+            warn!(
+                "Failed to cancel scheduled activation of {}: {}",
+                activation.role_key, e
+            );
+            return ActionOutcome::Failed(e.user_message().to_string());
+        }
+
+        // The approvals worker confirms on its next poll
+        updates::update_pim_scheduled_activations(remaining);
+        let role_text = pim_state
+            .eligible_roles
+            .iter()
+            .find(|r| r.favorites_key() == activation.role_key)
+            .map_or_else(|| "the role".to_string(), |r| r.display_text());
+        ActionOutcome::Succeeded(format!("Cancelled scheduled activation of {}", role_text))
+    }
+
+    /// Keep an active role for `minutes` past its current end without a gap in access.
+    ///
+    /// Tries to extend the activation first. Most policies refuse that, so
+    /// the fallback schedules a new activation starting when the current one
+    /// ends. Either way the local assignment's end time moves out.
+    async fn extend_role(&self, role_key: &str, minutes: u32) -> ActionOutcome {
+        let Some(state) = get_app_state() else {
+            return ActionOutcome::Done;
+        };
+        let pim_state = state.get_pim_state();
+        let mut assignments = pim_state.active_assignments;
+        let Some(idx) = assignments.iter().position(|a| a.role_key() == role_key) else {
+            return ActionOutcome::Failed("The role is no longer active.".to_string());
+        };
+        let Some(eligible_role) = pim_state
+            .eligible_roles
+            .into_iter()
+            .find(|r| r.favorites_key() == role_key)
+        else {
+            return ActionOutcome::Failed(
+                PimError::RoleNotFound(role_key.to_string())
+                    .user_message()
+                    .to_string(),
+            );
+        };
+
+        let access = match self.pim_access().await {
+            Ok(access) => access,
+            Err(e) => return ActionOutcome::Failed(e.into_message()),
+        };
+
+        let current = &assignments[idx];
+        let extension = ActivationRequest {
+            eligible_role: eligible_role.clone(),
+            justification: current.justification.clone(),
+            duration_minutes: current.extended_duration_minutes(minutes),
+            ticket_number: None,
+            start_time: Some(current.start_time),
+        };
+        let (end_time, outcome) = match self
+            .pim_client
+            .extend_role(&access.mgmt_token, extension)
+            .await
+        {
+            Ok(extended) => {
+                let until = extended.end_time.with_timezone(&Local).format("%H:%M");
+                (
+                    extended.end_time,
+                    format!("{} extended until {}", current.role_name, until),
+                )
+            }
+            Err(e) if e.is_extension_refused() => {
+                info!(
+                    "Extending {} refused ({}), activating again from its end",
+                    role_key, e
+                );
+                let next = ActivationRequest {
+                    eligible_role,
+                    justification: current.justification.clone(),
+                    duration_minutes: minutes,
+                    ticket_number: None,
+                    start_time: Some(current.end_time),
+                };
+                match self
+                    .pim_client
+                    .activate_role(&access.mgmt_token, next)
+                    .await
+                {
+                    Ok(next) => {
+                        let from = current.end_time.with_timezone(&Local).format("%H:%M");
+                        let until = next.end_time.with_timezone(&Local).format("%H:%M");
+                        (
+                            next.end_time,
+                            format!(
+                                "{} activated again from {} until {}, with no gap",
+                                current.role_name, from, until
+                            ),
+                        )
+                    }
+                    Err(e) => {
+                        error!("Failed to activate {} again: {}", role_key, e);
+                        return ActionOutcome::Failed(e.user_message().to_string());
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to extend {}: {}", role_key, e);
+                return ActionOutcome::Failed(e.user_message().to_string());
+            }
+        };
+
+        assignments[idx].end_time = end_time;
+        set_active_assignments(assignments);
+        ActionOutcome::Succeeded(outcome)
+    }
+
+    /// Schedule or cancel a reminder to deactivate a role.
+    fn set_deactivation_reminder(&mut self, role_key: &str, minutes: Option<u32>) -> ActionOutcome {
+        match minutes {
+            Some(minutes) => {
+                info!("Reminding to deactivate {} in {} min", role_key, minutes);
+                self.reminders.set(role_key, minutes, Utc::now());
+            }
+            None => {
+                info!("Cancelling deactivation reminder for {}", role_key);
+                self.reminders.cancel(role_key);
+            }
+        }
+        updates::update_pim_deactivation_reminders(self.reminders.clone());
+        ActionOutcome::Done
+    }
+
+    /// Fire deactivation reminders that are due, offering to deactivate the role.
+    fn check_deactivation_reminders(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let assignments = state.get_pim_state().active_assignments;
+        let active_keys: Vec<String> = assignments.iter().map(|a| a.role_key()).collect();
+        let mut changed = self.reminders.retain_active(&active_keys);
+
+        for role_key in self.reminders.take_due(Utc::now()) {
+            changed = true;
+            let Some(assignment) = assignments.iter().find(|a| a.role_key() == role_key) else {
+                continue;
+            };
+            let role_text = format!(
+                "{} on {}",
+                assignment.role_name, assignment.subscription_name
+            );
+            info!("Deactivation reminder due for {}", role_text);
+            notifications::notify(
+                "Done with your elevated role?",
+                &format!("{} is still active", role_text),
+            );
+            updates::show_deactivation_reminder(role_key, role_text);
+        }
+
+        if changed {
+            updates::update_pim_deactivation_reminders(self.reminders.clone());
+        }
+    }
+
+    /// Schedule or cancel the deactivation of an active role at a time of day.
+    fn schedule_deactivation(
+        &mut self,
+        role_key: &str,
+        at: Option<DateTime<Utc>>,
+    ) -> ActionOutcome {
+        if let Some(at) = at {
+            let assignment = get_app_state().and_then(|s| {
+                s.get_pim_state()
+                    .active_assignments
+                    .into_iter()
+                    .find(|a| a.role_key() == role_key)
+            });
+            let Some(assignment) = assignment else {
+                return ActionOutcome::Failed("The role is no longer active.".to_string());
+            };
+            if at >= assignment.end_time {
+                return ActionOutcome::Failed(format!(
+                    "{} expires at {}, before then",
+                    assignment.role_name,
+                    assignment.end_time.with_timezone(&Local).format("%H:%M")
+                ));
+            }
+        }
+        self.set_scheduled_deactivation(role_key, at);
+        ActionOutcome::Done
+    }
+
+    /// Store a scheduled deactivation (or cancel it for `None`) and show it in the menu.
+    fn set_scheduled_deactivation(&mut self, role_key: &str, at: Option<DateTime<Utc>>) {
+        match at {
+            Some(at) => {
+                info!("Deactivating {} at {}", role_key, at);
+                self.scheduled.schedule(role_key, at);
+            }
+            None => {
+                info!("Cancelling scheduled deactivation of {}", role_key);
+                self.scheduled.cancel(role_key);
+            }
+        }
+        self.save_scheduled_deactivations();
+    }
+
+    fn save_scheduled_deactivations(&self) {
+        if let Err(e) = pim::save_scheduled_deactivations(&self.scheduled) {
+            error!("Failed to save scheduled deactivations: {}", e);
+        }
+        updates::update_pim_scheduled_deactivations(self.scheduled.clone());
+    }
+
+    /// Add a note to the elevation of an active assignment.
+    fn add_session_note(&mut self, assignment_id: &str, text: &str) -> ActionOutcome {
+        let assignment = get_app_state().and_then(|s| {
+            s.get_pim_state()
+                .active_assignments
+                .into_iter()
+                .find(|a| a.id == assignment_id)
+        });
+        let Some(assignment) = assignment else {
+            return ActionOutcome::Failed("The role is no longer active.".to_string());
+        };
+        if self.notes.add(&assignment, text, Utc::now()) {
+            info!("Added a note to the elevation of {}", assignment.role_name);
+            self.save_session_notes();
+        }
+        ActionOutcome::Done
+    }
+
+    fn save_session_notes(&self) {
+        if let Err(e) = pim::save_session_notes(&self.notes) {
+            error!("Failed to save session notes: {}", e);
+        }
+        updates::update_pim_session_notes(self.notes.clone());
+    }
+
+    /// Remember which subscriptions refused a scan, saving any change.
+    fn record_availability(
+        &mut self,
+        scanned: &[String],
+        denied: &[Subscription],
+        now: DateTime<Utc>,
+    ) {
+        if self.availability.record_scan(scanned, denied, now) {
+            self.save_availability();
+        } else {
+            // Skip periods may have run out since the last scan
+            self.publish_skipped_subscriptions();
+        }
+    }
+
+    /// Save the subscriptions that refused scans and show the skipped ones.
+    fn save_availability(&self) {
+        if let Err(e) = pim::save_pim_availability(&self.availability) {
+            error!("Failed to save subscriptions without PIM access: {}", e);
+        }
+        self.publish_skipped_subscriptions();
+    }
+
+    /// Show the subscriptions currently left out of scans in the menu.
+    fn publish_skipped_subscriptions(&self) {
+        let names = self.availability.skipped_names(Utc::now(), skip_period());
+        updates::update_pim_skipped_subscriptions(names);
+    }
+
+    /// Deactivate roles whose scheduled time has come, notifying the result.
+    async fn run_scheduled_deactivations(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let assignments = state.get_pim_state().active_assignments;
+        let active_keys: Vec<String> = assignments.iter().map(|a| a.role_key()).collect();
+        let mut changed = self.scheduled.retain_active(&active_keys);
+
+        for role_key in self.scheduled.take_due(Utc::now()) {
+            changed = true;
+            let Some(assignment) = assignments.iter().find(|a| a.role_key() == role_key) else {
+                continue;
+            };
+            let role_text = format!(
+                "{} on {}",
+                assignment.role_name, assignment.subscription_name
+            );
+            info!("Scheduled deactivation due for {}", role_text);
+            match self.deactivate_role(&role_key).await {
+                ActionOutcome::Failed(message) => notifications::notify(
+                    "Scheduled deactivation failed",
+                    &format!("{} is still active: {}", role_text, message),
+                ),
+                _ => notifications::notify(
+                    "Role deactivated as scheduled",
+                    &format!("{} is no longer active", role_text),
+                ),
+            }
+        }
+
+        if changed {
+            self.save_scheduled_deactivations();
+        }
+    }
+
+    /// Get a Management API token and the principal IDs (user + groups) to query.
+    async fn pim_access(&self) -> Result<PimAccess, AccessError> {
+        // Get refresh token
+        let refresh_token = match keychain::get_refresh_token() {
+            Ok(token) => token,
+            Err(e) => {
+                error!("Failed to get refresh token for PIM: {}", e);
+                let message = if e.is_retryable() {
+                    e.user_message()
+                } else {
+                    "Sign in required"
+                };
+                return Err(AccessError::Failed(message.to_string()));
+            }
+        };
+
+        // Get user info for principal ID
+        let user_id = match get_app_state()
+            .and_then(|s| s.get_user_info())
+            .map(|u| u.user_id.clone())
+        {
+            Some(id) => id,
+            None => {
+                error!("No user info available for PIM");
+                return Err(AccessError::Failed("User info not available".to_string()));
+            }
+        };
+
+        let (groups, group_lookup) = self.user_groups(&refresh_token).await?;
+
+        // Build list of all principal IDs (user + groups)
+        let mut principal_ids = vec![user_id];
+        principal_ids.extend(groups.iter().map(|g| g.id.clone()));
+        let group_names = groups
+            .into_iter()
+            .filter_map(|g| Some((g.id, g.display_name?)))
+            .collect();
+        info!(
+            "Checking PIM roles for {} principal IDs",
+            principal_ids.len()
+        );
+
+        let mgmt_token = self.management_token(&refresh_token).await?;
+
+        Ok(PimAccess {
+            mgmt_token,
+            principal_ids,
+            group_names,
+            group_lookup,
+        })
+    }
+
+    /// Fetch the user's group memberships, unless turned off or not permitted.
+    ///
+    /// Without them only roles eligible to the user directly are found.
+    async fn user_groups(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(Vec<GroupMembership>, GroupLookup), AccessError> {
+        if self.skip_group_lookup {
+            info!("Group lookup turned off in settings");
+            return Ok((vec![], GroupLookup::Skipped));
+        }
+        if !self.oauth_client.has_feature(Feature::GroupRoles) {
+            info!("Group-based roles disabled by the scope profile");
+            return Ok((vec![], GroupLookup::Unavailable));
+        }
+
+        // Get Graph API token to fetch user's groups
+        let graph_token = match self.oauth_client.refresh_token(refresh_token).await {
+            Ok(response) => response.access_token,
+            Err(AuthError::Network(e)) => {
+                warn!("Azure AD unreachable: {}", e);
+                return Err(AccessError::Offline);
+            }
+            Err(e) => {
+                error!("Failed to get Graph API token: {}", e);
+                return Err(AccessError::Failed("Failed to refresh token".to_string()));
+            }
+        };
+
+        match self.graph_client.get_user_groups(&graph_token).await {
+            Ok(groups) => {
+                info!("User is member of {} groups", groups.len());
+                Ok((groups, GroupLookup::Complete))
+            }
+            Err(ApiError::Forbidden) => {
+                warn!("Not permitted to read group memberships - continuing with user ID only");
+                Ok((vec![], GroupLookup::Unavailable))
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to fetch user groups: {} - continuing with user ID only",
+                    e
+                );
+                Ok((vec![], GroupLookup::Complete))
+            }
+        }
+    }
+
+    /// Get a Management API token for PIM requests.
+    async fn management_token(&self, refresh_token: &str) -> Result<String, AccessError> {
+        match self.oauth_client.get_management_token(refresh_token).await {
+            Ok(response) => Ok(response.access_token),
+            Err(AuthError::Network(e)) => {
+                warn!("Azure AD unreachable: {}", e);
+                Err(AccessError::Offline)
+            }
+            Err(AuthError::ArmPermissionMissing(code)) => {
+                error!("App lacks Azure Service Management permission ({})", code);
+                Err(AccessError::ArmPermissionMissing {
+                    consent_url: self.oauth_client.arm_admin_consent_url().to_string(),
+                })
+            }
+            Err(e) => {
+                error!("Failed to get Management API token: {}", e);
+                Err(AccessError::PermissionDenied(
+                    "PIM access not available. Check Azure AD permissions.".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Drop assignments that have expired since they were fetched.
+    fn prune_expired_assignments(&self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let assignments = state.get_pim_state().active_assignments;
+        let count = assignments.len();
+        let live = pim::store::prune_expired(assignments, &SystemClock);
+        if live.len() != count {
+            info!("Removing {} expired assignments", count - live.len());
+            set_active_assignments(live);
+            updates::request_attention();
+        }
+    }
+
+    /// At the end-of-day time, list the roles still active and offer to deactivate them.
+    fn check_end_of_day_summary(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let Some(time) = state.get_settings().end_of_day_summary else {
+            return;
+        };
+        let now = Local::now();
+        if !schedule::summary_due(time, &now, self.summary_sent_on) {
+            return;
+        }
+        self.summary_sent_on = Some(now.date_naive());
+
+        let assignments = state.get_pim_state().active_assignments;
+        if assignments.is_empty() {
+            return;
+        }
+        let roles: Vec<String> = assignments
+            .iter()
+            .map(|a| {
+                format!(
+                    "{} on {} ({} left)",
+                    a.role_name,
+                    a.subscription_name,
+                    duration::format(a.time_remaining(&SystemClock), Style::Short)
+                )
+            })
+            .collect();
+        info!("End-of-day summary: {} roles still active", roles.len());
+        let title = match roles.len() {
+            1 => "A PIM role is still active".to_string(),
+            n => format!("{} PIM roles are still active", n),
+        };
+        notifications::notify(&title, &roles.join(", "));
+        updates::show_end_of_day_summary(roles);
+    }
+
+    /// Notify about active role assignments that are about to expire.
+    fn check_expiring_assignments(&mut self) {
+        let Some(state) = get_app_state() else {
+            return;
+        };
+        let pim_state = state.get_pim_state();
+        let threshold = pim_state.settings.expiry_warning_minutes as i64;
+
+        // Forget assignments that are gone so a re-activation warns again
+        self.warned
+            .retain(|id| pim_state.active_assignments.iter().any(|a| &a.id == id));
+
+        for assignment in &pim_state.active_assignments {
+            if assignment.is_expired(&SystemClock)
+                || !assignment.is_expiring_soon(threshold, &SystemClock)
+            {
+                continue;
+            }
+            if self.warned.insert(assignment.id.clone()) {
+                let role_text = format!(
+                    "{} on {}",
+                    assignment.role_name, assignment.subscription_name
+                );
+                let remaining =
+                    duration::format(assignment.time_remaining(&SystemClock), Style::Long);
+                notifications::notify_event(
+                    NotificationEvent::ExpiryWarning,
+                    "PIM role expiring soon",
+                    &format!("{} expires in {}", role_text, remaining),
+                );
+                if state.get_settings().offer_extension_at_expiry {
+                    updates::offer_extension(assignment.role_key(), role_text, remaining);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Settings actor: applies, persists, and publishes app settings changes.
+
+use chrono::NaiveTime;
+use tokio::sync::mpsc;
+use tracing::error;
+
+use super::{respond, Envelope};
+use crate::menubar::delegate::ActionOutcome;
+use crate::menubar::state::{get_app_state, Settings};
+use crate::menubar::updates;
+use crate::notifications::{NotificationEvent, NotificationSound};
+use crate::settings;
+
+/// Messages handled by the settings actor.
+#[derive(Debug)]
+pub enum SettingsMessage {
+    AutoLaunch(bool),
+    ShowExpiry(bool),
+    ProfileDetails(bool),
+    RestoreSession(bool),
+    AutoLoadRoles(bool),
+    RestoreNotification(bool),
+    NotificationSound {
+        event: NotificationEvent,
+        sound: NotificationSound,
+    },
+    SuccessAnimation(bool),
+    QuitWarning(bool),
+    CriticalFlash(bool),
+    FocusBreakthrough(bool),
+    RoleStatusItems(bool),
+    KeyVaultToken(bool),
+    ExtensionAtExpiry(bool),
+    EndOfDaySummary(Option<NaiveTime>),
+    TokenWarningMinutes(u32),
+    TokenCriticalMinutes(u32),
+    MenuBarCountdown(bool),
+    RefreshAtWarning(bool),
+    RolePageSize(u32),
+}
+
+/// Serializes settings changes so concurrent toggles can't overwrite each other.
+pub struct SettingsActor {
+    rx: mpsc::Receiver<Envelope<SettingsMessage>>,
+}
+
+impl SettingsActor {
+    /// Create the actor.
+    pub fn new(rx: mpsc::Receiver<Envelope<SettingsMessage>>) -> Self {
+        Self { rx }
+    }
+
+    /// Process messages until the channel closes.
+    pub async fn run(mut self) {
+        while let Some(Envelope { msg, reply }) = self.rx.recv().await {
+            let outcome = match msg {
+                SettingsMessage::AutoLaunch(enabled) => {
+                    if let Err(e) = settings::set_auto_launch(enabled) {
+                        error!("Failed to set auto-launch: {}", e);
+                        ActionOutcome::Failed(e.to_string())
+                    } else {
+                        update_settings(|s| s.auto_launch = enabled)
+                    }
+                }
+                SettingsMessage::ShowExpiry(enabled) => {
+                    update_settings(|s| s.show_expiry = enabled)
+                }
+                SettingsMessage::ProfileDetails(enabled) => {
+                    update_settings(|s| s.show_profile_details = enabled)
+                }
+                SettingsMessage::RestoreSession(enabled) => {
+                    update_settings(|s| s.restore_session_on_launch = enabled)
+                }
+                SettingsMessage::AutoLoadRoles(enabled) => {
+                    update_settings(|s| s.auto_load_roles = enabled)
+                }
+                SettingsMessage::RestoreNotification(enabled) => {
+                    update_settings(|s| s.notify_session_restored = enabled)
+                }
+                SettingsMessage::NotificationSound { event, sound } => {
+                    update_settings(|s| s.sounds.set_sound_for(event, sound))
+                }
+                SettingsMessage::SuccessAnimation(enabled) => {
+                    update_settings(|s| s.animate_on_success = enabled)
+                }
+                SettingsMessage::QuitWarning(enabled) => {
+                    update_settings(|s| s.warn_active_roles_on_quit = enabled)
+                }
+                SettingsMessage::CriticalFlash(enabled) => {
+                    update_settings(|s| s.flash_on_critical_events = enabled)
+                }
+                SettingsMessage::FocusBreakthrough(enabled) => {
+                    update_settings(|s| s.time_sensitive_during_focus = enabled)
+                }
+                SettingsMessage::RoleStatusItems(enabled) => {
+                    update_settings(|s| s.status_item_per_role = enabled)
+                }
+                SettingsMessage::KeyVaultToken(enabled) => {
+                    update_settings(|s| s.offer_key_vault_token = enabled)
+                }
+                SettingsMessage::ExtensionAtExpiry(enabled) => {
+                    update_settings(|s| s.offer_extension_at_expiry = enabled)
+                }
+                SettingsMessage::EndOfDaySummary(time) => {
+                    update_settings(|s| s.end_of_day_summary = time)
+                }
+                SettingsMessage::TokenWarningMinutes(minutes) => {
+                    update_settings(|s| s.token_warning_minutes = minutes)
+                }
+                SettingsMessage::TokenCriticalMinutes(minutes) => {
+                    update_settings(|s| s.token_critical_minutes = minutes)
+                }
+                SettingsMessage::MenuBarCountdown(enabled) => {
+                    update_settings(|s| s.show_menu_bar_countdown = enabled)
+                }
+                SettingsMessage::RefreshAtWarning(enabled) => {
+                    update_settings(|s| s.refresh_at_token_warning = enabled)
+                }
+                SettingsMessage::RolePageSize(size) => update_settings(|s| s.role_page_size = size),
+            };
+            respond(reply, outcome);
+        }
+    }
+}
+
+/// Apply a change to the application settings, persist them, and update the UI.
+pub fn update_settings(change: impl FnOnce(&mut Settings)) -> ActionOutcome {
+    let mut settings = get_app_state()
+        .map(|s| s.get_settings())
+        .unwrap_or_default();
+    change(&mut settings);
+
+    let outcome = match settings::save_settings(&settings) {
+        Ok(()) => ActionOutcome::Done,
+        Err(e) => {
+            error!("Failed to save settings: {}", e);
+            ActionOutcome::Failed(format!("Settings could not be saved: {}", e))
+        }
+    };
+
+    // Apply for this session even if saving failed
+    updates::update_settings(settings);
+    outcome
+}
@@ -0,0 +1,504 @@
+//! Object-safe interfaces of the API clients.
+//!
+//! The menu bar app's actors hold the clients as trait objects, so their
+//! message handling can run against mock clients. Each trait covers the
+//! calls the actors make and is implemented for the real client.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use url::Url;
+
+use crate::auth::challenge::StepUp;
+use crate::auth::features::Feature;
+use crate::auth::graph::{GraphClient, GroupMembership, Organization, UserProfile};
+use crate::auth::oauth::{OAuth2Client, PkceChallenge, TokenResponse};
+use crate::error::{ApiError, AuthError, PimError};
+use crate::pim::client::EligibilityScan;
+use crate::pim::compat::ApiVersionProblem;
+use crate::pim::policy::{ActivationCheck, RolePolicy};
+use crate::pim::{
+    ActivationRequest, ActiveAssignment, ApprovalRequest, EligibleRole, PimClient, ReviewResult,
+    Scope, SubmittedActivation, Subscription, TenantAssignment,
+};
+
+/// A boxed future, as returned by the trait methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Active assignments by subscription ID; see [`PimClient::get_active_assignments_in`].
+pub type AssignmentsBySubscription = Vec<(String, Vec<ActiveAssignment>)>;
+
+/// Azure AD sign-in and token requests; see [`OAuth2Client`].
+pub trait OAuthApi: Send + Sync {
+    fn redirect_uri(&self) -> &str;
+    fn generate_auth_url(&self, pkce: &PkceChallenge) -> (Url, String);
+    fn generate_consent_url(
+        &self,
+        pkce: &PkceChallenge,
+        scopes: &[String],
+        login_hint: Option<&str>,
+    ) -> (Url, String);
+    fn generate_step_up_url(
+        &self,
+        pkce: &PkceChallenge,
+        step_up: &StepUp,
+        login_hint: Option<&str>,
+    ) -> (Url, String);
+    fn missing_scopes(&self, granted: &str) -> Vec<String>;
+    fn has_feature(&self, feature: Feature) -> bool;
+    fn unmet_features(&self, granted: &str) -> Vec<Feature>;
+    fn arm_admin_consent_url(&self) -> Url;
+    fn invalidate_cached_tokens(&self) -> BoxFuture<'_, ()>;
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn exchange_consent_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+        scopes: &'a [String],
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn exchange_step_up_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn refresh_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn get_management_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn get_management_token_with_claims<'a>(
+        &'a self,
+        refresh_token: &'a str,
+        claims: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+    fn get_key_vault_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>>;
+}
+
+impl OAuthApi for OAuth2Client {
+    fn redirect_uri(&self) -> &str {
+        OAuth2Client::redirect_uri(self)
+    }
+
+    fn generate_auth_url(&self, pkce: &PkceChallenge) -> (Url, String) {
+        OAuth2Client::generate_auth_url(self, pkce)
+    }
+
+    fn generate_consent_url(
+        &self,
+        pkce: &PkceChallenge,
+        scopes: &[String],
+        login_hint: Option<&str>,
+    ) -> (Url, String) {
+        OAuth2Client::generate_consent_url(self, pkce, scopes, login_hint)
+    }
+
+    fn generate_step_up_url(
+        &self,
+        pkce: &PkceChallenge,
+        step_up: &StepUp,
+        login_hint: Option<&str>,
+    ) -> (Url, String) {
+        OAuth2Client::generate_step_up_url(self, pkce, step_up, login_hint)
+    }
+
+    fn missing_scopes(&self, granted: &str) -> Vec<String> {
+        OAuth2Client::missing_scopes(self, granted)
+    }
+
+    fn has_feature(&self, feature: Feature) -> bool {
+        OAuth2Client::has_feature(self, feature)
+    }
+
+    fn unmet_features(&self, granted: &str) -> Vec<Feature> {
+        OAuth2Client::unmet_features(self, granted)
+    }
+
+    fn arm_admin_consent_url(&self) -> Url {
+        OAuth2Client::arm_admin_consent_url(self)
+    }
+
+    fn invalidate_cached_tokens(&self) -> BoxFuture<'_, ()> {
+        Box::pin(OAuth2Client::invalidate_cached_tokens(self))
+    }
+
+    fn exchange_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::exchange_code(self, code, pkce_verifier))
+    }
+
+    fn exchange_consent_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+        scopes: &'a [String],
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::exchange_consent_code(
+            self,
+            code,
+            pkce_verifier,
+            scopes,
+        ))
+    }
+
+    fn exchange_step_up_code<'a>(
+        &'a self,
+        code: &'a str,
+        pkce_verifier: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::exchange_step_up_code(
+            self,
+            code,
+            pkce_verifier,
+        ))
+    }
+
+    fn refresh_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::refresh_token(self, refresh_token))
+    }
+
+    fn get_management_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::get_management_token(self, refresh_token))
+    }
+
+    fn get_management_token_with_claims<'a>(
+        &'a self,
+        refresh_token: &'a str,
+        claims: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::get_management_token_with_claims(
+            self,
+            refresh_token,
+            claims,
+        ))
+    }
+
+    fn get_key_vault_token<'a>(
+        &'a self,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, AuthError>> {
+        Box::pin(OAuth2Client::get_key_vault_token(self, refresh_token))
+    }
+}
+
+/// Microsoft Graph requests; see [`GraphClient`].
+pub trait GraphApi: Send + Sync {
+    fn get_user_profile<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<UserProfile, ApiError>>;
+    fn get_organization<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Organization, ApiError>>;
+    fn get_user_groups<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<GroupMembership>, ApiError>>;
+}
+
+impl GraphApi for GraphClient {
+    fn get_user_profile<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<UserProfile, ApiError>> {
+        Box::pin(GraphClient::get_user_profile(self, access_token))
+    }
+
+    fn get_organization<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Organization, ApiError>> {
+        Box::pin(GraphClient::get_organization(self, access_token))
+    }
+
+    fn get_user_groups<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<GroupMembership>, ApiError>> {
+        Box::pin(GraphClient::get_user_groups(self, access_token))
+    }
+}
+
+/// Azure PIM requests; see [`PimClient`].
+pub trait PimApi: Send + Sync {
+    fn list_subscriptions<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Subscription>, PimError>>;
+    fn check_api_versions<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<ApiVersionProblem>, PimError>>;
+    fn get_all_eligible_roles<'a>(
+        &'a self,
+        access_token: &'a str,
+        principal_ids: &'a [String],
+        skip: &'a [String],
+    ) -> BoxFuture<'a, Result<EligibilityScan, PimError>>;
+    fn get_role_policies<'a>(
+        &'a self,
+        access_token: &'a str,
+        roles: &'a [EligibleRole],
+    ) -> BoxFuture<'a, Result<HashMap<String, RolePolicy>, PimError>>;
+    fn get_active_assignments<'a>(
+        &'a self,
+        access_token: &'a str,
+        principal_ids: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<ActiveAssignment>, PimError>>;
+    fn get_active_assignments_in<'a>(
+        &'a self,
+        access_token: &'a str,
+        subscriptions: &'a [Subscription],
+        principal_ids: &'a [String],
+    ) -> BoxFuture<'a, Result<AssignmentsBySubscription, PimError>>;
+    fn get_tenant_assignments<'a>(
+        &'a self,
+        access_token: &'a str,
+        subscriptions: &'a [Subscription],
+    ) -> BoxFuture<'a, Result<Vec<TenantAssignment>, PimError>>;
+    fn get_max_activation_minutes<'a>(
+        &'a self,
+        access_token: &'a str,
+        scope: &'a Scope,
+        role_definition_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u32>, PimError>>;
+    fn validate_activation<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: &'a ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActivationCheck, PimError>>;
+    fn submit_activation<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<SubmittedActivation, PimError>>;
+    fn activate_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>>;
+    fn extend_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>>;
+    fn wait_for_provisioning<'a>(
+        &'a self,
+        access_token: &'a str,
+        assignment: &'a ActiveAssignment,
+    ) -> BoxFuture<'a, Result<(), PimError>>;
+    fn deactivate_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        assignment: &'a ActiveAssignment,
+        principal_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>>;
+    fn cancel_schedule_request<'a>(
+        &'a self,
+        access_token: &'a str,
+        request_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>>;
+    fn review_approval<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: &'a ApprovalRequest,
+        result: ReviewResult,
+        justification: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>>;
+}
+
+impl PimApi for PimClient {
+    fn list_subscriptions<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<Subscription>, PimError>> {
+        Box::pin(PimClient::list_subscriptions(self, access_token))
+    }
+
+    fn check_api_versions<'a>(
+        &'a self,
+        access_token: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<ApiVersionProblem>, PimError>> {
+        Box::pin(PimClient::check_api_versions(self, access_token))
+    }
+
+    fn get_all_eligible_roles<'a>(
+        &'a self,
+        access_token: &'a str,
+        principal_ids: &'a [String],
+        skip: &'a [String],
+    ) -> BoxFuture<'a, Result<EligibilityScan, PimError>> {
+        Box::pin(PimClient::get_all_eligible_roles(
+            self,
+            access_token,
+            principal_ids,
+            skip,
+        ))
+    }
+
+    fn get_role_policies<'a>(
+        &'a self,
+        access_token: &'a str,
+        roles: &'a [EligibleRole],
+    ) -> BoxFuture<'a, Result<HashMap<String, RolePolicy>, PimError>> {
+        Box::pin(PimClient::get_role_policies(self, access_token, roles))
+    }
+
+    fn get_active_assignments<'a>(
+        &'a self,
+        access_token: &'a str,
+        principal_ids: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<ActiveAssignment>, PimError>> {
+        Box::pin(PimClient::get_active_assignments(
+            self,
+            access_token,
+            principal_ids,
+        ))
+    }
+
+    fn get_active_assignments_in<'a>(
+        &'a self,
+        access_token: &'a str,
+        subscriptions: &'a [Subscription],
+        principal_ids: &'a [String],
+    ) -> BoxFuture<'a, Result<AssignmentsBySubscription, PimError>> {
+        Box::pin(PimClient::get_active_assignments_in(
+            self,
+            access_token,
+            subscriptions,
+            principal_ids,
+        ))
+    }
+
+    fn get_tenant_assignments<'a>(
+        &'a self,
+        access_token: &'a str,
+        subscriptions: &'a [Subscription],
+    ) -> BoxFuture<'a, Result<Vec<TenantAssignment>, PimError>> {
+        Box::pin(PimClient::get_tenant_assignments(
+            self,
+            access_token,
+            subscriptions,
+        ))
+    }
+
+    fn get_max_activation_minutes<'a>(
+        &'a self,
+        access_token: &'a str,
+        scope: &'a Scope,
+        role_definition_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u32>, PimError>> {
+        Box::pin(PimClient::get_max_activation_minutes(
+            self,
+            access_token,
+            scope,
+            role_definition_id,
+        ))
+    }
+
+    fn validate_activation<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: &'a ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActivationCheck, PimError>> {
+        Box::pin(PimClient::validate_activation(self, access_token, request))
+    }
+
+    fn submit_activation<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<SubmittedActivation, PimError>> {
+        Box::pin(PimClient::submit_activation(self, access_token, request))
+    }
+
+    fn activate_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>> {
+        Box::pin(PimClient::activate_role(self, access_token, request))
+    }
+
+    fn extend_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: ActivationRequest,
+    ) -> BoxFuture<'a, Result<ActiveAssignment, PimError>> {
+        Box::pin(PimClient::extend_role(self, access_token, request))
+    }
+
+    fn wait_for_provisioning<'a>(
+        &'a self,
+        access_token: &'a str,
+        assignment: &'a ActiveAssignment,
+    ) -> BoxFuture<'a, Result<(), PimError>> {
+        Box::pin(PimClient::wait_for_provisioning(
+            self,
+            access_token,
+            assignment,
+        ))
+    }
+
+    fn deactivate_role<'a>(
+        &'a self,
+        access_token: &'a str,
+        assignment: &'a ActiveAssignment,
+        principal_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>> {
+        Box::pin(PimClient::deactivate_role(
+            self,
+            access_token,
+            assignment,
+            principal_id,
+        ))
+    }
+
+    fn cancel_schedule_request<'a>(
+        &'a self,
+        access_token: &'a str,
+        request_id: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>> {
+        Box::pin(PimClient::cancel_schedule_request(
+            self,
+            access_token,
+            request_id,
+        ))
+    }
+
+    fn review_approval<'a>(
+        &'a self,
+        access_token: &'a str,
+        request: &'a ApprovalRequest,
+        result: ReviewResult,
+        justification: &'a str,
+    ) -> BoxFuture<'a, Result<(), PimError>> {
+        Box::pin(PimClient::review_approval(
+            self,
+            access_token,
+            request,
+            result,
+            justification,
+        ))
+    }
+}
@@ -3,10 +3,12 @@
 use objc2::mutability::MainThreadOnly;
 use objc2::rc::Retained;
 use objc2::{declare_class, msg_send_id, ClassType, DeclaredClass};
-use objc2_app_kit::NSApplicationDelegate;
+use objc2_app_kit::{NSApplication, NSApplicationDelegate, NSApplicationTerminateReply};
 use objc2_foundation::{MainThreadMarker, NSNotification, NSObject, NSObjectProtocol};
 use tracing::info;
 
+use super::shutdown;
+
 // Define the AppDelegate class
 declare_class!(
     pub struct AppDelegate;
@@ -27,9 +29,16 @@ declare_class!(
             info!("Application did finish launching");
         }
 
+        #[method(applicationShouldTerminate:)]
+        fn application_should_terminate(&self, _sender: &NSApplication) -> NSApplicationTerminateReply {
+            info!("Application should terminate");
+            shutdown::should_terminate(MainThreadMarker::from(self))
+        }
+
         #[method(applicationWillTerminate:)]
         fn application_will_terminate(&self, _notification: &NSNotification) {
             info!("Application will terminate");
+            shutdown::save_state();
         }
     }
 );
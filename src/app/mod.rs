@@ -1,3 +1,4 @@
 //! Application-level components including the NSApplicationDelegate.
 
 pub mod delegate;
+pub mod shutdown;
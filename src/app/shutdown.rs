@@ -0,0 +1,102 @@
+//! Graceful shutdown on quit.
+//!
+//! `applicationShouldTerminate:` defers the quit while the actors stop the
+//! callback server and, if the user asked for it, deactivate active roles.
+//! The supervisor replies to AppKit once they are done.
+
+use objc2_app_kit::NSApplicationTerminateReply;
+use objc2_foundation::MainThreadMarker;
+use once_cell::sync::OnceCell;
+use std::io::Write;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::menubar::dialogs::{self, QuitChoice};
+use crate::menubar::state::get_app_state;
+use crate::pim;
+use crate::settings;
+
+/// Global sender for shutdown requests.
+static SHUTDOWN_SENDER: OnceCell<mpsc::Sender<ShutdownRequest>> = OnceCell::new();
+
+/// A deferred quit for the supervisor to carry out.
+#[derive(Debug, Clone, Copy)]
+pub struct ShutdownRequest {
+    /// Deactivate active roles before quitting.
+    pub deactivate_roles: bool,
+}
+
+/// Initialize the shutdown channel and return the receiver.
+pub fn init_shutdown_channel() -> mpsc::Receiver<ShutdownRequest> {
+    let (tx, rx) = mpsc::channel(1);
+    SHUTDOWN_SENDER
+        .set(tx)
+        .expect("Shutdown channel already initialized");
+    rx
+}
+
+/// Ask the supervisor to shut down. Returns false if it cannot be reached.
+fn request_shutdown(deactivate_roles: bool) -> bool {
+    SHUTDOWN_SENDER
+        .get()
+        .is_some_and(|tx| tx.try_send(ShutdownRequest { deactivate_roles }).is_ok())
+}
+
+/// Decide whether the app may quit now, later, or not at all.
+pub fn should_terminate(mtm: MainThreadMarker) -> NSApplicationTerminateReply {
+    let active_roles = get_app_state()
+        .map(|state| {
+            let warn = state.get_settings().warn_active_roles_on_quit;
+            (warn, state.get_pim_state().active_assignments.len())
+        })
+        .filter(|(warn, count)| *warn && *count > 0)
+        .map(|(_, count)| count);
+
+    let deactivate_roles = match active_roles {
+        Some(count) => match dialogs::confirm_quit_with_active_roles(mtm, count) {
+            QuitChoice::DeactivateAndQuit => true,
+            QuitChoice::Quit => false,
+            QuitChoice::Cancel => {
+                info!("Quit cancelled");
+                return NSApplicationTerminateReply::NSTerminateCancel;
+            }
+        },
+        None => false,
+    };
+
+    if request_shutdown(deactivate_roles) {
+        NSApplicationTerminateReply::NSTerminateLater
+    } else {
+        warn!("Supervisor not reachable, quitting immediately");
+        NSApplicationTerminateReply::NSTerminateNow
+    }
+}
+
+/// Persist settings and the PIM cache, then flush the logs.
+pub fn save_state() {
+    if let Some(state) = get_app_state() {
+        if let Err(e) = settings::save_settings(&state.get_settings()) {
+            error!("Failed to save settings: {}", e);
+        }
+        if let Err(e) = pim::save_pim_settings(&state.get_pim_settings()) {
+            error!("Failed to save PIM settings: {}", e);
+        }
+
+        let pim_state = state.get_pim_state();
+        if let Err(e) = pim::save_active_assignments(&pim_state.active_assignments) {
+            error!("Failed to save active assignments: {}", e);
+        }
+        if let Some(cached_at) = pim_state.roles_cached_at {
+            if !pim_state.eligible_roles.is_empty() {
+                if let Err(e) = pim::save_roles_cache(&pim_state.eligible_roles, cached_at) {
+                    error!("Failed to save roles cache: {}", e);
+                }
+            }
+        }
+    }
+
+    info!("State saved");
+    // Logs go to stderr; make sure nothing is lost on exit
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+}
@@ -0,0 +1,177 @@
+//! Access token audience checks.
+//!
+//! Azure AD issues a separate token per resource. A Graph token sent to the
+//! Management API fails with an unhelpful 401, so the clients check the
+//! token's `aud` claim before sending it. The `scp` claim is read the same
+//! way to check which scopes were granted, and the `oid` and `tid` claims
+//! to check which account a token was issued to.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::error::AudienceError;
+
+/// The API an access token is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    /// Azure Resource Manager (management.azure.com).
+    Management,
+    /// Microsoft Graph.
+    Graph,
+}
+
+impl Audience {
+    /// Resource URI, used in error messages.
+    pub fn resource(self) -> &'static str {
+        match self {
+            Self::Management => "https://management.azure.com",
+            Self::Graph => "https://graph.microsoft.com",
+        }
+    }
+
+    /// `aud` values Azure AD uses for this API: resource URIs and the app ID.
+    fn accepted(self) -> &'static [&'static str] {
+        match self {
+            Self::Management => &[
+                "https://management.azure.com",
+                "https://management.core.windows.net",
+                "797f4846-ba00-4fd7-ba43-dac1f8f63013",
+            ],
+            Self::Graph => &[
+                "https://graph.microsoft.com",
+                "00000003-0000-0000-c000-000000000000",
+            ],
+        }
+    }
+
+    fn accepts(self, aud: &str) -> bool {
+        let aud = aud.trim_end_matches('/');
+        self.accepted().iter().any(|a| a.eq_ignore_ascii_case(aud))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    aud: AudClaim,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScopeClaims {
+    scp: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountClaims {
+    oid: String,
+    tid: String,
+}
+
+/// The `aud` claim is a string or, rarely, a list of strings.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AudClaim {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Check that `token` was issued for `expected`.
+///
+/// Tokens that aren't readable JWTs are let through; Azure AD may issue
+/// opaque tokens, and the API still rejects a token that is really wrong.
+pub fn check_audience(token: &str, expected: Audience) -> Result<(), AudienceError> {
+    let Some(audiences) = token_audiences(token) else {
+        debug!("Access token is not a readable JWT, skipping audience check");
+        return Ok(());
+    };
+
+    if audiences.iter().any(|aud| expected.accepts(aud)) {
+        Ok(())
+    } else {
+        Err(AudienceError {
+            expected: expected.resource(),
+            actual: audiences.join(", "),
+        })
+    }
+}
+
+/// Decode a JWT's claims without verifying its signature.
+fn token_claims<T: DeserializeOwned>(token: &str) -> Option<T> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Read the `aud` claim from a JWT without verifying its signature.
+fn token_audiences(token: &str) -> Option<Vec<String>> {
+    let claims: Claims = token_claims(token)?;
+    Some(match claims.aud {
+        AudClaim::One(aud) => vec![aud],
+        AudClaim::Many(auds) => auds,
+    })
+}
+
+/// Read the space-separated `scp` (delegated scopes) claim from a JWT.
+pub fn token_scopes(token: &str) -> Option<String> {
+    token_claims::<ScopeClaims>(token).map(|claims| claims.scp)
+}
+
+/// Read the `oid` (object ID) and `tid` (tenant ID) claims from a JWT.
+pub fn token_account(token: &str) -> Option<(String, String)> {
+    token_claims::<AccountClaims>(token).map(|claims| (claims.oid, claims.tid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_with_aud(aud: &str) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"aud":"{}","sub":"x"}}"#, aud));
+        format!("{}.{}.signature", header, payload)
+    }
+
+    #[test]
+    fn test_check_audience() {
+        let arm = token_with_aud("https://management.azure.com/");
+        assert!(check_audience(&arm, Audience::Management).is_ok());
+
+        let graph = token_with_aud("00000003-0000-0000-c000-000000000000");
+        assert!(check_audience(&graph, Audience::Graph).is_ok());
+
+        let err = check_audience(&graph, Audience::Management).unwrap_err();
+        assert_eq!(err.expected, "https://management.azure.com");
+        assert_eq!(err.actual, "00000003-0000-0000-c000-000000000000");
+    }
+
+    #[test]
+    fn test_opaque_token_is_not_rejected() {
+        assert!(check_audience("opaque-token", Audience::Management).is_ok());
+    }
+
+    #[test]
+    fn test_token_scopes() {
+        let payload =
+            URL_SAFE_NO_PAD.encode(r#"{"aud":"x","scp":"User.Read GroupMember.Read.All"}"#);
+        let token = format!("header.{}.signature", payload);
+        assert_eq!(
+            token_scopes(&token).as_deref(),
+            Some("User.Read GroupMember.Read.All")
+        );
+        assert_eq!(token_scopes(&token_with_aud("x")), None);
+        assert_eq!(token_scopes("opaque-token"), None);
+    }
+
+    #[test]
+    fn test_token_account() {
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"aud":"x","oid":"user-1","tid":"tenant-1"}"#);
+        let token = format!("header.{}.signature", payload);
+        assert_eq!(
+            token_account(&token),
+            Some(("user-1".to_string(), "tenant-1".to_string()))
+        );
+        assert_eq!(token_account(&token_with_aud("x")), None);
+        assert_eq!(token_account("opaque-token"), None);
+    }
+}
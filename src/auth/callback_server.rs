@@ -3,10 +3,10 @@
 //! Provides a temporary localhost server to receive OAuth callbacks,
 //! display a success page to the user, and pass the auth code to the app.
 
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::mpsc;
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
 /// The port used for the OAuth callback server.
@@ -28,14 +28,18 @@ pub enum CallbackResult {
     Error(String),
 }
 
-/// Start the callback server and wait for a single OAuth callback.
+/// Maximum time to wait for a browser to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Run the callback server until a single OAuth callback arrives or `cancel` fires.
 ///
 /// Returns the full callback URL (including query parameters) when received.
-/// The server automatically shuts down after receiving the callback.
-pub fn start_callback_server(cancel_rx: mpsc::Receiver<()>) -> CallbackResult {
+/// The listening socket is closed before this future completes, so a new
+/// server can bind the port as soon as it returns.
+pub async fn run_callback_server(cancel: CancellationToken) -> CallbackResult {
     let addr = format!("127.0.0.1:{}", CALLBACK_PORT);
 
-    let listener = match TcpListener::bind(&addr) {
+    let listener = match TcpListener::bind(&addr).await {
         Ok(l) => l,
         Err(e) => {
             error!("Failed to bind callback server to {}: {}", addr, e);
@@ -43,42 +47,25 @@ pub fn start_callback_server(cancel_rx: mpsc::Receiver<()>) -> CallbackResult {
         }
     };
 
-    // Set non-blocking so we can check for cancellation
-    if let Err(e) = listener.set_nonblocking(true) {
-        error!("Failed to set non-blocking mode: {}", e);
-        return CallbackResult::Error(format!("Server configuration error: {}", e));
-    }
-
     info!("OAuth callback server listening on {}", addr);
 
     loop {
-        // Check for cancellation
-        match cancel_rx.try_recv() {
-            Ok(()) | Err(mpsc::TryRecvError::Disconnected) => {
+        let accepted = tokio::select! {
+            _ = cancel.cancelled() => {
                 info!("Callback server cancelled");
                 return CallbackResult::Cancelled;
             }
-            Err(mpsc::TryRecvError::Empty) => {}
-        }
+            accepted = listener.accept() => accepted,
+        };
 
-        // Try to accept a connection
-        match listener.accept() {
+        match accepted {
             Ok((stream, peer_addr)) => {
                 debug!("Connection from {}", peer_addr);
-                match handle_connection(stream) {
-                    Some(url) => {
-                        info!("OAuth callback received");
-                        return CallbackResult::Success(url);
-                    }
-                    None => {
-                        // Not a valid callback request, continue listening
-                        continue;
-                    }
+                if let Some(url) = handle_connection(stream).await {
+                    info!("OAuth callback received");
+                    return CallbackResult::Success(url);
                 }
-            }
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                // No connection yet, sleep briefly and retry
-                std::thread::sleep(Duration::from_millis(100));
+                // Not a valid callback request, continue listening
             }
             Err(e) => {
                 error!("Error accepting connection: {}", e);
@@ -91,29 +78,42 @@ pub fn start_callback_server(cancel_rx: mpsc::Receiver<()>) -> CallbackResult {
 /// Handle an incoming HTTP connection.
 ///
 /// Returns Some(url) if this was a valid OAuth callback, None otherwise.
-fn handle_connection(mut stream: TcpStream) -> Option<String> {
-    // Set read timeout
-    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
-
+async fn handle_connection(mut stream: TcpStream) -> Option<String> {
     let mut buffer = [0; 4096];
-    let bytes_read = match stream.read(&mut buffer) {
-        Ok(n) => n,
-        Err(e) => {
+    let bytes_read = match tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buffer)).await {
+        Ok(Ok(n)) => n,
+        Ok(Err(e)) => {
             debug!("Failed to read request: {}", e);
             return None;
         }
+        Err(_) => {
+            debug!("Timed out reading request");
+            return None;
+        }
     };
 
     let request = String::from_utf8_lossy(&buffer[..bytes_read]);
     debug!("Received request: {}", request.lines().next().unwrap_or(""));
 
+    let (url, response) = route_request(&request);
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.flush().await;
+    url
+}
+
+/// Decide how to answer a raw HTTP request.
+///
+/// Returns the callback URL, if this was a valid OAuth callback, and the
+/// HTTP response to send.
+fn route_request(request: &str) -> (Option<String>, String) {
     // Parse the request line to get the path
-    let request_line = request.lines().next()?;
+    let Some(request_line) = request.lines().next() else {
+        return (None, error_response(400, "Bad Request"));
+    };
     let parts: Vec<&str> = request_line.split_whitespace().collect();
 
     if parts.len() < 2 {
-        send_error_response(&mut stream, 400, "Bad Request");
-        return None;
+        return (None, error_response(400, "Bad Request"));
     }
 
     let method = parts[0];
@@ -121,37 +121,31 @@ fn handle_connection(mut stream: TcpStream) -> Option<String> {
 
     // Only handle GET requests to /callback
     if method != "GET" {
-        send_error_response(&mut stream, 405, "Method Not Allowed");
-        return None;
+        return (None, error_response(405, "Method Not Allowed"));
     }
 
     if !path.starts_with("/callback") {
-        send_error_response(&mut stream, 404, "Not Found");
-        return None;
+        return (None, error_response(404, "Not Found"));
     }
 
+    let url = format!("http://localhost:{}{}", CALLBACK_PORT, path);
+
     // Check if this is an error callback
     if path.contains("error=") {
-        send_error_page(&mut stream, path);
         // Still return the URL so the app can handle the error
-        return Some(format!("http://localhost:{}{}", CALLBACK_PORT, path));
+        return (Some(url), error_page(path));
     }
 
     // Check if this has the code parameter
     if !path.contains("code=") {
-        send_error_response(&mut stream, 400, "Missing authorization code");
-        return None;
+        return (None, error_response(400, "Missing authorization code"));
     }
 
-    // Send success page
-    send_success_page(&mut stream);
-
-    // Return the full callback URL
-    Some(format!("http://localhost:{}{}", CALLBACK_PORT, path))
+    (Some(url), success_page())
 }
 
-/// Send a success HTML page.
-fn send_success_page(stream: &mut TcpStream) {
+/// Build the success HTML page response.
+fn success_page() -> String {
     let html = r#"<!DOCTYPE html>
 <html lang="en">
 <head>
@@ -222,14 +216,11 @@ fn send_success_page(stream: &mut TcpStream) {
 </body>
 </html>"#;
 
-    let response = format!(
+    format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         html.len(),
         html
-    );
-
-    let _ = stream.write_all(response.as_bytes());
-    let _ = stream.flush();
+    )
 }
 
 /// HTML-escape a string to prevent XSS attacks.
@@ -241,8 +232,8 @@ fn html_escape(s: &str) -> String {
         .replace('\'', "&#x27;")
 }
 
-/// Send an error HTML page.
-fn send_error_page(stream: &mut TcpStream, path: &str) {
+/// Build the error HTML page response.
+fn error_page(path: &str) -> String {
     // Extract error description if present
     let error_desc = if let Some(start) = path.find("error_description=") {
         let start = start + 18;
@@ -333,28 +324,22 @@ fn send_error_page(stream: &mut TcpStream, path: &str) {
         error_desc
     );
 
-    let response = format!(
+    format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         html.len(),
         html
-    );
-
-    let _ = stream.write_all(response.as_bytes());
-    let _ = stream.flush();
+    )
 }
 
-/// Send an error response.
-fn send_error_response(stream: &mut TcpStream, status: u16, message: &str) {
-    let response = format!(
+/// Build a plain-text error response.
+fn error_response(status: u16, message: &str) -> String {
+    format!(
         "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
         status,
         message,
         message.len(),
         message
-    );
-
-    let _ = stream.write_all(response.as_bytes());
-    let _ = stream.flush();
+    )
 }
 
 #[cfg(test)]
@@ -366,4 +351,29 @@ mod tests {
         let uri = get_redirect_uri();
         assert_eq!(uri, "http://localhost:28491/callback");
     }
+
+    #[test]
+    fn test_route_request() {
+        let (url, response) = route_request("GET /callback?code=abc&state=xyz HTTP/1.1\r\n");
+        assert_eq!(
+            url.as_deref(),
+            Some("http://localhost:28491/callback?code=abc&state=xyz")
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        let (url, response) = route_request("GET /favicon.ico HTTP/1.1\r\n");
+        assert!(url.is_none());
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_releases_port() {
+        let cancel = CancellationToken::new();
+        let server = tokio::spawn(run_callback_server(cancel.clone()));
+        tokio::task::yield_now().await;
+
+        cancel.cancel();
+        assert!(matches!(server.await.unwrap(), CallbackResult::Cancelled));
+        assert!(std::net::TcpListener::bind(("127.0.0.1", CALLBACK_PORT)).is_ok());
+    }
 }
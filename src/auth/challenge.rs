@@ -0,0 +1,145 @@
+//! Step-up challenges: extra verification Azure asks for before an activation.
+//!
+//! A role whose policy requires an Authentication Context fails activation
+//! with `RoleAssignmentRequestAcrsValidationFailed` and the claims to request
+//! in the message; Conditional Access can also answer 401 with an
+//! `insufficient_claims` `WWW-Authenticate` challenge. A role requiring MFA
+//! fails its `MfaRule`. Each is satisfied by an interactive sign-in for the
+//! Management API with the claims, or with MFA, before the activation is retried.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+
+/// Error code of an activation missing the role's Authentication Context.
+const ACRS_VALIDATION_FAILED: &str = "RoleAssignmentRequestAcrsValidationFailed";
+
+/// Verification to complete before retrying an activation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepUp {
+    /// Sign in again for a token with these claims, a JSON claims request.
+    Claims(String),
+    /// Sign in again with multi-factor authentication.
+    Mfa,
+}
+
+impl StepUp {
+    /// Extra parameters of the authorization request.
+    pub fn auth_params(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            Self::Claims(claims) => vec![("claims", claims.as_str())],
+            Self::Mfa => vec![("amr_values", "mfa"), ("prompt", "login")],
+        }
+    }
+
+    /// The claims to request the Management API token with, if any.
+    pub fn claims(&self) -> Option<&str> {
+        match self {
+            Self::Claims(claims) => Some(claims),
+            Self::Mfa => None,
+        }
+    }
+}
+
+impl std::fmt::Display for StepUp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Claims(_) => write!(f, "authentication context"),
+            Self::Mfa => write!(f, "multi-factor authentication"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Get the claims challenge from an ARM activation error body.
+///
+/// The message ends with `&claims=` and the URL-encoded claims JSON.
+pub fn claims_from_arm_error(body: &str) -> Option<String> {
+    let response: ErrorResponse = serde_json::from_str(body).ok()?;
+    if response.error.code != ACRS_VALIDATION_FAILED {
+        return None;
+    }
+    let message = &response.error.message;
+    let start = message.find("claims=")? + "claims=".len();
+    let encoded = message[start..]
+        .split_whitespace()
+        .next()?
+        .trim_end_matches('.');
+    let claims = urlencoding::decode(encoded).ok()?.into_owned();
+    valid_claims(claims)
+}
+
+/// Get the claims challenge from a `WWW-Authenticate` header, e.g.
+/// `Bearer error="insufficient_claims", claims="eyJhY2Nlc3NfdG9rZW4iOnsi..."`.
+pub fn claims_from_www_authenticate(header: &str) -> Option<String> {
+    if !header.contains("insufficient_claims") {
+        return None;
+    }
+    let start = header.find("claims=\"")? + "claims=\"".len();
+    let encoded = &header[start..];
+    let encoded = &encoded[..encoded.find('"')?];
+    let claims = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+    valid_claims(claims)
+}
+
+/// `claims` if it is a JSON object, as Azure AD expects.
+fn valid_claims(claims: String) -> Option<String> {
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&claims).ok()?;
+    Some(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLAIMS: &str = r#"{"access_token":{"acrs":{"essential":true,"value":"c1"}}}"#;
+
+    #[test]
+    fn test_claims_from_arm_error() {
+        let body = r#"{"error":{"code":"RoleAssignmentRequestAcrsValidationFailed","message":"The Role assignment request ACRS validation failed with claims: &claims=%7B%22access_token%22%3A%7B%22acrs%22%3A%7B%22essential%22%3Atrue%2C%22value%22%3A%22c1%22%7D%7D%7D."}}"#;
+        assert_eq!(claims_from_arm_error(body).as_deref(), Some(CLAIMS));
+
+        let other = r#"{"error":{"code":"RoleAssignmentRequestPolicyValidationFailed","message":"claims=%7B%7D"}}"#;
+        assert_eq!(claims_from_arm_error(other), None);
+        let not_json = r#"{"error":{"code":"RoleAssignmentRequestAcrsValidationFailed","message":"&claims=c1"}}"#;
+        assert_eq!(claims_from_arm_error(not_json), None);
+    }
+
+    #[test]
+    fn test_claims_from_www_authenticate() {
+        let header = format!(
+            r#"Bearer authorization_uri="https://login.microsoftonline.com/common/oauth2/authorize", error="insufficient_claims", claims="{}""#,
+            STANDARD.encode(CLAIMS)
+        );
+        assert_eq!(
+            claims_from_www_authenticate(&header).as_deref(),
+            Some(CLAIMS)
+        );
+        assert_eq!(
+            claims_from_www_authenticate(r#"Bearer error="invalid_token""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_auth_params() {
+        let step_up = StepUp::Claims(CLAIMS.to_string());
+        assert_eq!(step_up.auth_params(), [("claims", CLAIMS)]);
+        assert_eq!(step_up.claims(), Some(CLAIMS));
+        assert_eq!(
+            StepUp::Mfa.auth_params(),
+            [("amr_values", "mfa"), ("prompt", "login")]
+        );
+        assert_eq!(StepUp::Mfa.claims(), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Graph permissions needed by each feature.
+//!
+//! Scope lists are configurable per profile, so a tenant that won't consent
+//! to group scopes can drop them. Features are enabled by the scopes a profile
+//! requests, and checked against the scopes actually granted at sign-in.
+
+use super::oauth::{normalize_scope, scope_display_name};
+
+/// A feature that needs a Graph permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Signing in and showing the user's profile.
+    UserProfile,
+    /// Finding roles granted through group membership.
+    GroupRoles,
+}
+
+impl Feature {
+    /// All features.
+    pub const ALL: [Feature; 2] = [Feature::UserProfile, Feature::GroupRoles];
+
+    /// User-facing name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::UserProfile => "User profile",
+            Self::GroupRoles => "Group-based roles",
+        }
+    }
+
+    /// Scopes that each satisfy the feature on their own, narrowest first.
+    pub fn satisfied_by(self) -> &'static [&'static str] {
+        match self {
+            Self::UserProfile => &["User.Read", "User.Read.All", "Directory.Read.All"],
+            Self::GroupRoles => &[
+                "GroupMember.Read.All",
+                "Group.Read.All",
+                "Directory.Read.All",
+            ],
+        }
+    }
+
+    /// Whether sign-in can't work without this feature.
+    pub fn is_required(self) -> bool {
+        self == Self::UserProfile
+    }
+
+    /// Whether any of `scopes` satisfies the feature.
+    pub fn is_covered_by<S: AsRef<str>>(self, scopes: &[S]) -> bool {
+        let scopes: Vec<String> = scopes.iter().map(|s| normalize_scope(s.as_ref())).collect();
+        self.satisfied_by()
+            .iter()
+            .any(|needed| scopes.contains(&needed.to_ascii_lowercase()))
+    }
+
+    /// Short description of the scopes needed, e.g. "GroupMember.Read.All or Group.Read.All".
+    pub fn needs_text(self) -> String {
+        let names: Vec<&str> = self
+            .satisfied_by()
+            .iter()
+            .map(|scope| scope_display_name(scope))
+            .collect();
+        names.join(" or ")
+    }
+}
+
+/// Features enabled by a profile's requested scopes. Required features are always enabled.
+pub fn enabled_features(requested: &[String]) -> Vec<Feature> {
+    Feature::ALL
+        .into_iter()
+        .filter(|feature| feature.is_required() || feature.is_covered_by(requested))
+        .collect()
+}
+
+/// Enabled features that a space-separated granted scope list doesn't cover.
+pub fn unmet_features(requested: &[String], granted: &str) -> Vec<Feature> {
+    let granted: Vec<&str> = granted.split_whitespace().collect();
+    enabled_features(requested)
+        .into_iter()
+        .filter(|feature| !feature.is_covered_by(&granted))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_enabled_features() {
+        let full = scopes(&[
+            "https://graph.microsoft.com/User.Read",
+            "https://graph.microsoft.com/GroupMember.Read.All",
+            "openid",
+        ]);
+        assert_eq!(enabled_features(&full), Feature::ALL.to_vec());
+
+        // Dropping group scopes disables group-based roles
+        let no_groups = scopes(&["https://graph.microsoft.com/User.Read", "openid"]);
+        assert_eq!(enabled_features(&no_groups), vec![Feature::UserProfile]);
+
+        // A broader scope covers both
+        let directory = scopes(&["https://graph.microsoft.com/Directory.Read.All"]);
+        assert_eq!(enabled_features(&directory), Feature::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_unmet_features() {
+        let requested = scopes(&[
+            "https://graph.microsoft.com/User.Read",
+            "https://graph.microsoft.com/GroupMember.Read.All",
+        ]);
+        assert!(unmet_features(&requested, "User.Read GroupMember.Read.All openid").is_empty());
+        assert!(unmet_features(&requested, "user.read directory.read.all").is_empty());
+        assert_eq!(
+            unmet_features(&requested, "User.Read profile openid"),
+            vec![Feature::GroupRoles]
+        );
+        assert_eq!(
+            Feature::GroupRoles.needs_text(),
+            "GroupMember.Read.All or Group.Read.All or Directory.Read.All"
+        );
+    }
+}
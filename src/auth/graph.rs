@@ -1,12 +1,14 @@
 //! Microsoft Graph API client for fetching user profile and organization info.
 
+use crate::auth::audience::{check_audience, Audience};
+use crate::config::RuntimeConfig;
 use crate::error::ApiError;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// Base URL for Microsoft Graph API.
-const GRAPH_BASE_URL: &str = "https://graph.microsoft.com/v1.0";
+pub const GRAPH_BASE_URL: &str = "https://graph.microsoft.com/v1.0";
 
 /// HTTP request timeout.
 const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
@@ -16,23 +18,35 @@ const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 /// Microsoft Graph API client.
 pub struct GraphClient {
     http_client: reqwest::Client,
+    base_url: String,
 }
 
 impl GraphClient {
     /// Create a new Graph client.
-    pub fn new() -> Result<Self> {
+    pub fn new(runtime: &RuntimeConfig) -> Result<Self> {
         let http_client = reqwest::Client::builder()
             .timeout(HTTP_TIMEOUT)
             .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .pool_max_idle_per_host(runtime.pool_max_idle_per_host)
             .build()
             .context("Failed to create HTTP client")?;
 
-        Ok(Self { http_client })
+        Ok(Self {
+            http_client,
+            base_url: GRAPH_BASE_URL.to_string(),
+        })
+    }
+
+    /// Call Graph at `base_url`, e.g. for another Graph version.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
     }
 
     /// Fetch the current user's profile.
     pub async fn get_user_profile(&self, access_token: &str) -> Result<UserProfile, ApiError> {
-        let url = format!("{}/me", GRAPH_BASE_URL);
+        check_audience(access_token, Audience::Graph)?;
+        let url = format!("{}/me", self.base_url);
 
         let response = self
             .http_client
@@ -60,7 +74,8 @@ impl GraphClient {
 
     /// Fetch the user's organization info.
     pub async fn get_organization(&self, access_token: &str) -> Result<Organization, ApiError> {
-        let url = format!("{}/organization", GRAPH_BASE_URL);
+        check_audience(access_token, Audience::Graph)?;
+        let url = format!("{}/organization", self.base_url);
 
         let response = self
             .http_client
@@ -94,11 +109,12 @@ impl GraphClient {
     /// Fetch the current user's group memberships (security groups and Microsoft 365 groups).
     /// Returns a list of group IDs that the user is a member of.
     pub async fn get_user_groups(&self, access_token: &str) -> Result<Vec<GroupMembership>, ApiError> {
+        check_audience(access_token, Audience::Graph)?;
         let mut all_groups = Vec::new();
         let mut next_link: Option<String> = None;
         let initial_url = format!(
             "{}/me/memberOf?$select=id,displayName&$filter=isof('microsoft.graph.group')",
-            GRAPH_BASE_URL
+            self.base_url
         );
 
         loop {
@@ -149,7 +165,7 @@ impl GraphClient {
 
 impl Default for GraphClient {
     fn default() -> Self {
-        Self::new().expect("Failed to create GraphClient")
+        Self::new(&RuntimeConfig::default()).expect("Failed to create GraphClient")
     }
 }
 
@@ -266,7 +282,7 @@ pub struct GroupMembership {
 }
 
 /// Combined user info for UI display.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserInfo {
     /// User's unique object ID (principal ID for PIM).
     pub user_id: String,
@@ -274,6 +290,12 @@ pub struct UserInfo {
     pub email: String,
     pub tenant_id: String,
     pub tenant_name: String,
+    /// Job title from the Graph profile, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_title: Option<String>,
+    /// Office location from the Graph profile, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub office_location: Option<String>,
 }
 
 impl UserInfo {
@@ -285,9 +307,21 @@ impl UserInfo {
             email: profile.email(),
             tenant_id: org.id.clone(),
             tenant_name: org.name_or_id(),
+            job_title: non_blank(profile.job_title),
+            office_location: non_blank(profile.office_location),
         }
     }
 
+    /// Job title and office, e.g. "Platform Engineer · Oslo HQ", if either is set.
+    pub fn profile_details(&self) -> Option<String> {
+        let details: Vec<&str> = [&self.job_title, &self.office_location]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        (!details.is_empty()).then(|| details.join(" · "))
+    }
+
     /// Serialize to JSON for storage.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
@@ -298,11 +332,47 @@ impl UserInfo {
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(json)
     }
+
+    /// Names of the displayed fields that differ from `other`, for logging.
+    pub fn changed_fields(&self, other: &UserInfo) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.display_name != other.display_name {
+            changed.push("name");
+        }
+        if self.email != other.email {
+            changed.push("email");
+        }
+        if self.tenant_id != other.tenant_id || self.tenant_name != other.tenant_name {
+            changed.push("tenant");
+        }
+        if self.job_title != other.job_title || self.office_location != other.office_location {
+            changed.push("profile details");
+        }
+        changed
+    }
+}
+
+/// `value` unless it is missing or blank.
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::golden::assert_parses_to_golden;
+
+    #[test]
+    fn test_golden_graph_responses() {
+        assert_parses_to_golden::<UserProfile>("graph/me.json");
+        assert_parses_to_golden::<UserProfile>("graph/me_guest.json");
+        assert_parses_to_golden::<OrganizationResponse>("graph/organization.json");
+        // Pages link to the next one until the last
+        assert_parses_to_golden::<GroupMembershipResponse>("graph/member_of_page1.json");
+        assert_parses_to_golden::<GroupMembershipResponse>("graph/member_of_page2.json");
+    }
 
     #[test]
     fn test_user_profile_display_name() {
@@ -346,6 +416,8 @@ mod tests {
             email: "test@example.com".into(),
             tenant_id: "abc-123".into(),
             tenant_name: "Test Org".into(),
+            job_title: None,
+            office_location: None,
         };
 
         let json = info.to_json().unwrap();
@@ -355,4 +427,63 @@ mod tests {
         assert_eq!(restored.display_name, info.display_name);
         assert_eq!(restored.email, info.email);
     }
+
+    #[test]
+    fn test_user_info_changed_fields() {
+        let info = UserInfo {
+            user_id: "user-object-id".into(),
+            display_name: "Test User".into(),
+            email: "test@example.com".into(),
+            tenant_id: "abc-123".into(),
+            tenant_name: "Test Org".into(),
+            job_title: None,
+            office_location: None,
+        };
+        assert!(info.changed_fields(&info.clone()).is_empty());
+
+        let renamed = UserInfo {
+            display_name: "Test User-Smith".into(),
+            tenant_name: "Test Org AS".into(),
+            ..info.clone()
+        };
+        assert_eq!(info.changed_fields(&renamed), vec!["name", "tenant"]);
+    }
+
+    #[test]
+    fn test_user_info_profile_details() {
+        let profile = UserProfile {
+            id: "123".into(),
+            display_name: Some("John Doe".into()),
+            given_name: None,
+            surname: None,
+            mail: None,
+            user_principal_name: Some("john@example.com".into()),
+            job_title: Some("Platform Engineer".into()),
+            office_location: Some("  ".into()),
+        };
+        let org = Organization {
+            id: "abc-123".into(),
+            display_name: Some("Test Org".into()),
+            verified_domains: Vec::new(),
+        };
+        let info = UserInfo::from_profile_and_org(profile, org);
+        assert_eq!(info.office_location, None);
+        assert_eq!(info.profile_details().as_deref(), Some("Platform Engineer"));
+
+        let moved = UserInfo {
+            office_location: Some("Oslo HQ".into()),
+            ..info.clone()
+        };
+        assert_eq!(
+            moved.profile_details().as_deref(),
+            Some("Platform Engineer · Oslo HQ")
+        );
+        assert_eq!(info.changed_fields(&moved), vec!["profile details"]);
+
+        // User info stored before these fields existed still loads
+        let stored =
+            r#"{"user_id":"u","display_name":"n","email":"e","tenant_id":"t","tenant_name":"o"}"#;
+        let restored = UserInfo::from_json(stored).unwrap();
+        assert_eq!(restored.profile_details(), None);
+    }
 }
@@ -1,9 +1,18 @@
 //! Azure AD authentication module.
 //!
 //! Provides OAuth2 with PKCE authentication, Microsoft Graph API client,
-//! and automatic token refresh management.
+//! automatic token refresh management, and token providers for embedders.
 
+pub mod audience;
 pub mod callback_server;
+pub mod challenge;
+pub mod features;
 pub mod graph;
 pub mod oauth;
+pub mod refresh_flight;
+pub mod token_cache;
+pub mod token_format;
 pub mod token_manager;
+pub mod token_provider;
+
+pub use token_provider::{RefreshTokenProvider, StaticToken, TokenProvider};
@@ -1,5 +1,9 @@
 //! OAuth2 client with PKCE support for Azure AD authentication.
 
+use super::audience::{token_account, token_scopes};
+use super::challenge::StepUp;
+use super::features::{enabled_features, unmet_features, Feature};
+use super::refresh_flight::RefreshFlight;
 use crate::config::Config;
 use crate::error::AuthError;
 use anyhow::{Context, Result};
@@ -7,6 +11,7 @@ use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use rand::Rng;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 use url::Url;
 
@@ -14,6 +19,28 @@ use url::Url;
 const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 /// HTTP connection timeout.
 const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Azure AD error code for a redirect URI missing from the app registration.
+const AADSTS_REDIRECT_URI_MISMATCH: &str = "AADSTS50011";
+/// Azure AD error code for a permission the user or admin hasn't consented to.
+const AADSTS_CONSENT_REQUIRED: &str = "AADSTS65001";
+/// Azure AD error code for a resource missing from the app registration's permissions.
+const AADSTS_RESOURCE_NOT_REGISTERED: &str = "AADSTS650057";
+/// Delegated permission needed to call the Azure Resource Manager API.
+pub const ARM_USER_IMPERSONATION: &str = "https://management.azure.com/user_impersonation";
+/// Microsoft Graph resource prefix used in fully qualified scopes.
+const GRAPH_RESOURCE: &str = "https://graph.microsoft.com/";
+/// OpenID Connect scopes. Granted implicitly and not always echoed back.
+const OIDC_SCOPES: [&str; 4] = ["openid", "profile", "email", "offline_access"];
+/// Scope used when refreshing: every Graph permission granted so far.
+const REFRESH_SCOPE: &str =
+    "https://graph.microsoft.com/.default openid profile email offline_access";
+/// Scope requested for Azure Management API tokens.
+const MANAGEMENT_SCOPE: &str = "https://management.azure.com/.default offline_access";
+/// Scope requested in a step-up sign-in; `openid` adds an ID token that
+/// tells which account signed in.
+const STEP_UP_SCOPE: &str = "https://management.azure.com/.default openid offline_access";
+/// Scope requested for Key Vault data-plane tokens.
+const KEY_VAULT_SCOPE: &str = "https://vault.azure.net/.default offline_access";
 
 /// PKCE code verifier and challenge pair.
 #[derive(Debug)]
@@ -58,6 +85,10 @@ pub struct OAuth2Client {
     redirect_uri: String,
     scopes: Vec<String>,
     http_client: reqwest::Client,
+    /// Serializes refresh-token redemptions across actors.
+    refresh_flight: RefreshFlight,
+    /// Moves on when the signed-in account changes, so tokens cached before are dropped.
+    token_generation: AtomicU64,
 }
 
 impl OAuth2Client {
@@ -66,6 +97,7 @@ impl OAuth2Client {
         let http_client = reqwest::Client::builder()
             .timeout(HTTP_TIMEOUT)
             .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .pool_max_idle_per_host(config.runtime.pool_max_idle_per_host)
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .context("Failed to create HTTP client")?;
@@ -74,15 +106,105 @@ impl OAuth2Client {
             client_id: config.oauth.client_id.clone(),
             tenant: config.oauth.tenant.clone(),
             redirect_uri: config.oauth.redirect_uri.clone(),
-            scopes: config.oauth.scopes.scopes.clone(),
+            scopes: config.oauth.active_scopes().to_vec(),
             http_client,
+            refresh_flight: RefreshFlight::default(),
+            token_generation: AtomicU64::new(0),
         })
     }
 
+    /// Current token generation, to tag cached tokens with (see [`TokenOwner`]).
+    ///
+    /// [`TokenOwner`]: super::token_cache::TokenOwner
+    pub fn token_generation(&self) -> u64 {
+        self.token_generation.load(Ordering::SeqCst)
+    }
+
+    /// Drop the tokens cached for the current account, on sign-out or when
+    /// an account signs in.
+    pub async fn invalidate_cached_tokens(&self) {
+        self.token_generation.fetch_add(1, Ordering::SeqCst);
+        self.refresh_flight.clear().await;
+    }
+
+    /// The redirect URI registered for this client.
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
     /// Generate the authorization URL for browser-based sign-in.
     ///
     /// Returns the URL and a CSRF state token that must be verified in the callback.
     pub fn generate_auth_url(&self, pkce: &PkceChallenge) -> (Url, String) {
+        self.build_auth_url(pkce, &self.scopes.join(" "), &[])
+    }
+
+    /// Generate the authorization URL for consenting to additional scopes only.
+    ///
+    /// The existing grant is kept; Azure AD adds the new scopes to it.
+    pub fn generate_consent_url(
+        &self,
+        pkce: &PkceChallenge,
+        scopes: &[String],
+        login_hint: Option<&str>,
+    ) -> (Url, String) {
+        let scope = consent_scope(scopes);
+        let mut extra = vec![("prompt", "consent")];
+        if let Some(hint) = login_hint {
+            extra.push(("login_hint", hint));
+        }
+        self.build_auth_url(pkce, &scope, &extra)
+    }
+
+    /// Generate the authorization URL for a Management API sign-in that
+    /// satisfies `step_up`, such as MFA or an Authentication Context.
+    pub fn generate_step_up_url(
+        &self,
+        pkce: &PkceChallenge,
+        step_up: &StepUp,
+        login_hint: Option<&str>,
+    ) -> (Url, String) {
+        let mut extra = step_up.auth_params();
+        if let Some(hint) = login_hint {
+            extra.push(("login_hint", hint));
+        }
+        self.build_auth_url(pkce, STEP_UP_SCOPE, &extra)
+    }
+
+    /// Configured scopes that are missing from a granted scope list.
+    ///
+    /// OpenID Connect scopes are ignored since Azure AD doesn't always echo them.
+    pub fn missing_scopes(&self, granted: &str) -> Vec<String> {
+        missing_scopes(&self.scopes, granted)
+    }
+
+    /// Whether the configured scopes enable `feature`.
+    pub fn has_feature(&self, feature: Feature) -> bool {
+        enabled_features(&self.scopes).contains(&feature)
+    }
+
+    /// Enabled features that a granted scope list doesn't cover.
+    pub fn unmet_features(&self, granted: &str) -> Vec<Feature> {
+        unmet_features(&self.scopes, granted)
+    }
+
+    /// URL where an admin grants the Azure Service Management permission
+    /// for the whole tenant.
+    pub fn arm_admin_consent_url(&self) -> Url {
+        admin_consent_url(
+            &self.tenant,
+            &self.client_id,
+            &self.redirect_uri,
+            ARM_USER_IMPERSONATION,
+        )
+    }
+
+    fn build_auth_url(
+        &self,
+        pkce: &PkceChallenge,
+        scope: &str,
+        extra: &[(&str, &str)],
+    ) -> (Url, String) {
         // Generate random state for CSRF protection
         let mut rng = rand::thread_rng();
         let state_bytes: Vec<u8> = (0..16).map(|_| rng.gen()).collect();
@@ -100,10 +222,11 @@ impl OAuth2Client {
             .append_pair("response_type", "code")
             .append_pair("redirect_uri", &self.redirect_uri)
             .append_pair("response_mode", "query")
-            .append_pair("scope", &self.scopes.join(" "))
+            .append_pair("scope", scope)
             .append_pair("state", &state)
             .append_pair("code_challenge", &pkce.challenge)
-            .append_pair("code_challenge_method", "S256");
+            .append_pair("code_challenge_method", "S256")
+            .extend_pairs(extra);
 
         (url, state)
     }
@@ -113,6 +236,38 @@ impl OAuth2Client {
         &self,
         code: &str,
         pkce_verifier: &str,
+    ) -> Result<TokenResponse, AuthError> {
+        self.exchange_code_for(code, pkce_verifier, &self.scopes.join(" "))
+            .await
+    }
+
+    /// Exchange an authorization code from the consent flow for tokens.
+    pub async fn exchange_consent_code(
+        &self,
+        code: &str,
+        pkce_verifier: &str,
+        scopes: &[String],
+    ) -> Result<TokenResponse, AuthError> {
+        self.exchange_code_for(code, pkce_verifier, &consent_scope(scopes))
+            .await
+    }
+
+    /// Exchange an authorization code from the step-up flow for Management
+    /// API tokens.
+    pub async fn exchange_step_up_code(
+        &self,
+        code: &str,
+        pkce_verifier: &str,
+    ) -> Result<TokenResponse, AuthError> {
+        self.exchange_code_for(code, pkce_verifier, STEP_UP_SCOPE)
+            .await
+    }
+
+    async fn exchange_code_for(
+        &self,
+        code: &str,
+        pkce_verifier: &str,
+        scope: &str,
     ) -> Result<TokenResponse, AuthError> {
         let token_endpoint = format!(
             "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
@@ -125,7 +280,7 @@ impl OAuth2Client {
             ("code", code),
             ("redirect_uri", self.redirect_uri.as_str()),
             ("code_verifier", pkce_verifier),
-            ("scope", &self.scopes.join(" ")),
+            ("scope", scope),
         ];
 
         let response = self
@@ -156,7 +311,21 @@ impl OAuth2Client {
     }
 
     /// Refresh an access token using a refresh token.
+    ///
+    /// Requests Graph's `.default` scope so the token carries every permission
+    /// granted so far, including ones added later through incremental consent.
+    /// The response's `scope` lists them.
+    ///
+    /// Concurrent refreshes are serialized (see [`RefreshFlight`]).
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<TokenResponse, AuthError> {
+        self.refresh_flight
+            .run(REFRESH_SCOPE, refresh_token, || {
+                self.request_refresh_token(refresh_token)
+            })
+            .await
+    }
+
+    async fn request_refresh_token(&self, refresh_token: &str) -> Result<TokenResponse, AuthError> {
         let token_endpoint = format!(
             "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
             self.tenant
@@ -166,7 +335,7 @@ impl OAuth2Client {
             ("client_id", self.client_id.as_str()),
             ("grant_type", "refresh_token"),
             ("refresh_token", refresh_token),
-            ("scope", &self.scopes.join(" ")),
+            ("scope", REFRESH_SCOPE),
         ];
 
         let response = self
@@ -175,17 +344,14 @@ impl OAuth2Client {
             .form(&params)
             .send()
             .await
-            .map_err(|e| AuthError::TokenRefreshFailed(e.to_string()))?;
+            .map_err(refresh_send_error)?;
 
         if !response.status().is_success() {
             let status = response.status();
             // Log error details for debugging (doesn't expose to user)
             let error_body = response.text().await.unwrap_or_default();
             tracing::error!("Token refresh failed: HTTP {} - {}", status, error_body);
-            return Err(AuthError::TokenRefreshFailed(format!(
-                "HTTP {}",
-                status.as_u16()
-            )));
+            return Err(refresh_status_error(status));
         }
 
         let token_response: TokenResponse = response
@@ -203,23 +369,69 @@ impl OAuth2Client {
     pub async fn get_management_token(
         &self,
         refresh_token: &str,
+    ) -> Result<TokenResponse, AuthError> {
+        self.refresh_flight
+            .run(MANAGEMENT_SCOPE, refresh_token, || {
+                self.request_resource_token(refresh_token, MANAGEMENT_SCOPE, "Management API", None)
+            })
+            .await
+    }
+
+    /// Get a Management API token carrying `claims`, after a step-up sign-in
+    /// satisfied them.
+    ///
+    /// Not shared with concurrent refreshes, which don't ask for the claims.
+    pub async fn get_management_token_with_claims(
+        &self,
+        refresh_token: &str,
+        claims: &str,
+    ) -> Result<TokenResponse, AuthError> {
+        self.request_resource_token(
+            refresh_token,
+            MANAGEMENT_SCOPE,
+            "Management API",
+            Some(claims),
+        )
+        .await
+    }
+
+    /// Get an access token for Key Vault secrets, keys and certificates.
+    ///
+    /// Only the token is returned; it is never stored.
+    pub async fn get_key_vault_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<TokenResponse, AuthError> {
+        self.refresh_flight
+            .run(KEY_VAULT_SCOPE, refresh_token, || {
+                self.request_resource_token(refresh_token, KEY_VAULT_SCOPE, "Key Vault", None)
+            })
+            .await
+    }
+
+    /// Redeem the refresh token for an access token to another resource,
+    /// with `claims` if a resource asked for them.
+    async fn request_resource_token(
+        &self,
+        refresh_token: &str,
+        scope: &str,
+        resource: &str,
+        claims: Option<&str>,
     ) -> Result<TokenResponse, AuthError> {
         let token_endpoint = format!(
             "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
             self.tenant
         );
 
-        // Request token for Azure Management API resource
-        let scope = "https://management.azure.com/.default offline_access";
-
-        let params = [
+        let mut params = vec![
             ("client_id", self.client_id.as_str()),
             ("grant_type", "refresh_token"),
             ("refresh_token", refresh_token),
             ("scope", scope),
         ];
+        params.extend(claims.map(|claims| ("claims", claims)));
 
-        tracing::debug!("Requesting Management API token");
+        tracing::debug!("Requesting {} token", resource);
 
         let response = self
             .http_client
@@ -227,18 +439,32 @@ impl OAuth2Client {
             .form(&params)
             .send()
             .await
-            .map_err(|e| AuthError::TokenRefreshFailed(e.to_string()))?;
+            .map_err(refresh_send_error)?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_body = response.text().await.unwrap_or_default();
             tracing::error!(
-                "Management API token request failed: HTTP {} - {}",
+                "{} token request failed: HTTP {} - {}",
+                resource,
                 status,
                 error_body
             );
+            match missing_permission_code(&error_body) {
+                Some(code) if scope == MANAGEMENT_SCOPE => {
+                    return Err(AuthError::ArmPermissionMissing(code.to_string()));
+                }
+                Some(code) => {
+                    return Err(AuthError::TokenRefreshFailed(format!(
+                        "{} permission not granted ({})",
+                        resource, code
+                    )));
+                }
+                None => {}
+            }
             return Err(AuthError::TokenRefreshFailed(format!(
-                "Management API token: HTTP {}",
+                "{} token: HTTP {}",
+                resource,
                 status.as_u16()
             )));
         }
@@ -248,13 +474,13 @@ impl OAuth2Client {
             .await
             .map_err(|e| AuthError::TokenRefreshFailed(e.to_string()))?;
 
-        tracing::info!("Successfully acquired Management API token");
+        tracing::info!("Successfully acquired {} token", resource);
         Ok(token_response)
     }
 }
 
 /// Token response from Azure AD.
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 #[allow(dead_code)]
 pub struct TokenResponse {
     pub access_token: String,
@@ -263,6 +489,97 @@ pub struct TokenResponse {
     pub refresh_token: Option<String>,
     #[serde(default)]
     pub scope: String,
+    /// ID token, returned when `openid` was requested.
+    #[serde(default)]
+    pub id_token: Option<String>,
+}
+
+impl TokenResponse {
+    /// Granted scopes: the access token's `scp` claim, or the `scope` field
+    /// if the token isn't a readable JWT.
+    pub fn granted_scopes(&self) -> String {
+        token_scopes(&self.access_token).unwrap_or_else(|| self.scope.clone())
+    }
+
+    /// Object ID and tenant ID of the account the tokens were issued to,
+    /// read from the ID token or else the access token.
+    pub fn account(&self) -> Option<(String, String)> {
+        self.id_token
+            .as_deref()
+            .and_then(token_account)
+            .or_else(|| token_account(&self.access_token))
+    }
+}
+
+/// Map a failed token request; unreachable Azure AD means offline, not a bad token.
+fn refresh_send_error(e: reqwest::Error) -> AuthError {
+    if e.is_connect() || e.is_timeout() {
+        AuthError::Network(e.to_string())
+    } else {
+        AuthError::TokenRefreshFailed(e.to_string())
+    }
+}
+
+/// Map a token endpoint error status; throttling and server errors pass on their own.
+fn refresh_status_error(status: reqwest::StatusCode) -> AuthError {
+    let message = format!("HTTP {}", status.as_u16());
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        AuthError::TemporarilyUnavailable(message)
+    } else {
+        AuthError::TokenRefreshFailed(message)
+    }
+}
+
+/// Scope of a consent flow: the new scopes, plus `openid` for an ID token
+/// naming the account that consented.
+fn consent_scope(scopes: &[String]) -> String {
+    format!("{} openid offline_access", scopes.join(" "))
+}
+
+/// Build an admin consent URL for a permission.
+fn admin_consent_url(tenant: &str, client_id: &str, redirect_uri: &str, scope: &str) -> Url {
+    let consent_endpoint = format!(
+        "https://login.microsoftonline.com/{}/v2.0/adminconsent",
+        tenant
+    );
+
+    let mut url = Url::parse(&consent_endpoint).expect("Invalid admin consent endpoint");
+    url.query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("scope", scope)
+        .append_pair("redirect_uri", redirect_uri);
+    url
+}
+
+/// Azure AD error code in a token error body if the app lacks a permission.
+fn missing_permission_code(error_body: &str) -> Option<&'static str> {
+    [AADSTS_RESOURCE_NOT_REGISTERED, AADSTS_CONSENT_REQUIRED]
+        .into_iter()
+        .find(|code| error_body.contains(code))
+}
+
+/// Normalize a scope for comparison, e.g. `https://graph.microsoft.com/User.Read` → `user.read`.
+pub fn normalize_scope(scope: &str) -> String {
+    scope
+        .strip_prefix(GRAPH_RESOURCE)
+        .unwrap_or(scope)
+        .to_ascii_lowercase()
+}
+
+/// Required scopes that are missing from a space-separated granted scope list.
+fn missing_scopes(required: &[String], granted: &str) -> Vec<String> {
+    let granted: Vec<String> = granted.split_whitespace().map(normalize_scope).collect();
+    required
+        .iter()
+        .filter(|scope| !OIDC_SCOPES.contains(&scope.as_str()))
+        .filter(|scope| !granted.contains(&normalize_scope(scope)))
+        .cloned()
+        .collect()
+}
+
+/// Short display name for a scope, e.g. `https://graph.microsoft.com/User.Read` → `User.Read`.
+pub fn scope_display_name(scope: &str) -> &str {
+    scope.strip_prefix(GRAPH_RESOURCE).unwrap_or(scope)
 }
 
 /// Parse OAuth callback URL to extract code and state.
@@ -277,6 +594,9 @@ pub fn parse_callback_url(url_string: &str) -> Result<(String, String), AuthErro
             .get("error_description")
             .map(|s| s.to_string())
             .unwrap_or_else(|| error.to_string());
+        if description.contains(AADSTS_REDIRECT_URI_MISMATCH) {
+            return Err(AuthError::RedirectUriMismatch(description));
+        }
         return Err(AuthError::OAuthFailed(description));
     }
 
@@ -309,6 +629,84 @@ mod tests {
         assert_ne!(pkce.verifier, pkce.challenge);
     }
 
+    #[test]
+    fn test_missing_scopes() {
+        let required = vec![
+            "https://graph.microsoft.com/User.Read".to_string(),
+            "https://graph.microsoft.com/Directory.Read.All".to_string(),
+            "openid".to_string(),
+            "offline_access".to_string(),
+        ];
+
+        assert_eq!(
+            missing_scopes(&required, "User.Read profile openid email"),
+            vec!["https://graph.microsoft.com/Directory.Read.All".to_string()]
+        );
+        assert!(missing_scopes(&required, "user.read Directory.Read.All").is_empty());
+        assert_eq!(
+            scope_display_name("https://graph.microsoft.com/Directory.Read.All"),
+            "Directory.Read.All"
+        );
+    }
+
+    #[test]
+    fn test_missing_permission_code() {
+        let body = r#"{"error":"invalid_grant","error_description":"AADSTS65001: The user or administrator has not consented to use the application"}"#;
+        assert_eq!(missing_permission_code(body), Some("AADSTS65001"));
+        let body =
+            r#"{"error":"invalid_client","error_description":"AADSTS650057: Invalid resource."}"#;
+        assert_eq!(missing_permission_code(body), Some("AADSTS650057"));
+        assert_eq!(
+            missing_permission_code(
+                r#"{"error":"invalid_grant","error_description":"AADSTS70008"}"#
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_refresh_status_error() {
+        use reqwest::StatusCode;
+        for status in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(matches!(
+                refresh_status_error(status),
+                AuthError::TemporarilyUnavailable(_)
+            ));
+        }
+        assert!(matches!(
+            refresh_status_error(StatusCode::BAD_REQUEST),
+            AuthError::TokenRefreshFailed(_)
+        ));
+    }
+
+    #[test]
+    fn test_admin_consent_url() {
+        let url = admin_consent_url(
+            "tenant-id",
+            "client-id",
+            "http://localhost:28491/callback",
+            ARM_USER_IMPERSONATION,
+        );
+        assert_eq!(
+            url.as_str(),
+            "https://login.microsoftonline.com/tenant-id/v2.0/adminconsent?client_id=client-id\
+             &scope=https%3A%2F%2Fmanagement.azure.com%2Fuser_impersonation\
+             &redirect_uri=http%3A%2F%2Flocalhost%3A28491%2Fcallback"
+        );
+    }
+
+    #[test]
+    fn test_consent_scope() {
+        let scopes = vec!["https://graph.microsoft.com/GroupMember.Read.All".to_string()];
+        assert_eq!(
+            consent_scope(&scopes),
+            "https://graph.microsoft.com/GroupMember.Read.All openid offline_access"
+        );
+    }
+
     #[test]
     fn test_parse_callback_success() {
         let url = "http://localhost:28491/callback?code=abc123&state=xyz789";
@@ -324,6 +722,13 @@ mod tests {
         assert!(matches!(result, Err(AuthError::OAuthFailed(_))));
     }
 
+    #[test]
+    fn test_parse_callback_redirect_uri_mismatch() {
+        let url = "http://localhost:28491/callback?error=invalid_request&error_description=AADSTS50011%3A%20The%20redirect%20URI%20does%20not%20match";
+        let result = parse_callback_url(url);
+        assert!(matches!(result, Err(AuthError::RedirectUriMismatch(_))));
+    }
+
     #[test]
     fn test_parse_callback_missing_code() {
         let url = "http://localhost:28491/callback?state=xyz789";
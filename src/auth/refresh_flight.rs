@@ -0,0 +1,216 @@
+//! Single-flight token refreshes.
+//!
+//! Manual refresh, background refresh, the PIM actor, and the approvals
+//! worker all redeem the same refresh token. Run concurrently, Azure AD may
+//! rotate the token mid-race and reject the losers. Refreshes of the same
+//! token at the same scope are serialized here, and a caller that waited on
+//! one reuses its result instead of redeeming the token again. Refreshes at
+//! other scopes don't wait, so a stalled Key Vault refresh doesn't hold up
+//! the Management API token of the PIM poll.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use super::oauth::TokenResponse;
+use crate::error::AuthError;
+
+/// How long a completed refresh is reused by callers that waited on it.
+const REUSE_WINDOW: Duration = Duration::from_secs(10);
+
+/// A refresh that completed recently.
+struct Completed {
+    at: Instant,
+    response: TokenResponse,
+}
+
+/// The refreshes of one scope and refresh token, one at a time.
+type Flight = Arc<Mutex<Option<Completed>>>;
+
+/// Serializes refreshes of the same scope and refresh token.
+#[derive(Default)]
+pub struct RefreshFlight {
+    /// By hash of the scope and refresh token redeemed, so the token isn't kept.
+    flights: std::sync::Mutex<HashMap<[u8; 32], Flight>>,
+}
+
+impl RefreshFlight {
+    /// Run `refresh` for `refresh_token` at `scope`, unless an identical
+    /// refresh just completed, in which case its response is returned.
+    pub async fn run<F, Fut>(
+        &self,
+        scope: &str,
+        refresh_token: &str,
+        refresh: F,
+    ) -> Result<TokenResponse, AuthError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<TokenResponse, AuthError>>,
+    {
+        let flight = self.flight(flight_key(scope, refresh_token));
+        let mut last = flight.lock().await;
+        if let Some(completed) = last.as_ref() {
+            if completed.at.elapsed() < REUSE_WINDOW {
+                debug!("Reusing token refresh that just completed");
+                return Ok(completed.response.clone());
+            }
+        }
+
+        let response = refresh().await?;
+        *last = Some(Completed {
+            at: Instant::now(),
+            response: response.clone(),
+        });
+        Ok(response)
+    }
+
+    /// Forget the recent refreshes, so their responses aren't reused.
+    pub async fn clear(&self) {
+        self.flights.lock().unwrap().clear();
+    }
+
+    /// The flight for `key`, dropping idle ones whose response can't be
+    /// reused anymore, e.g. of refresh tokens that were rotated.
+    fn flight(&self, key: [u8; 32]) -> Flight {
+        let mut flights = self.flights.lock().unwrap();
+        flights.retain(|_, flight| {
+            Arc::strong_count(flight) > 1
+                || flight.try_lock().map_or(true, |last| {
+                    last.as_ref()
+                        .is_some_and(|completed| completed.at.elapsed() < REUSE_WINDOW)
+                })
+        });
+        flights.entry(key).or_default().clone()
+    }
+}
+
+fn flight_key(scope: &str, refresh_token: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_bytes());
+    hasher.update([0]);
+    hasher.update(refresh_token.as_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn response(access_token: &str) -> TokenResponse {
+        TokenResponse {
+            access_token: access_token.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: 3600,
+            refresh_token: None,
+            scope: String::new(),
+            id_token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refreshes_share_one_request() {
+        let flight = Arc::new(RefreshFlight::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let flight = flight.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    flight
+                        .run("graph", "rt-1", || async {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            Ok(response("at-1"))
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap().access_token, "at-1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A different scope or refresh token refreshes again
+        let other = flight
+            .run("arm", "rt-1", || async { Ok(response("at-2")) })
+            .await
+            .unwrap();
+        assert_eq!(other.access_token, "at-2");
+    }
+
+    #[tokio::test]
+    async fn test_stalled_refresh_does_not_block_other_scopes() {
+        let flight = Arc::new(RefreshFlight::default());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+
+        let stalled = {
+            let flight = flight.clone();
+            let release = release.clone();
+            tokio::spawn(async move {
+                flight
+                    .run("vault", "rt-1", || async move {
+                        let _ = started_tx.send(());
+                        release.notified().await;
+                        Ok(response("vault-1"))
+                    })
+                    .await
+            })
+        };
+        started_rx.await.unwrap();
+
+        let arm = tokio::time::timeout(
+            Duration::from_secs(1),
+            flight.run("arm", "rt-1", || async { Ok(response("arm-1")) }),
+        )
+        .await
+        .expect("refresh at another scope waited on the stalled one")
+        .unwrap();
+        assert_eq!(arm.access_token, "arm-1");
+
+        release.notify_one();
+        assert_eq!(stalled.await.unwrap().unwrap().access_token, "vault-1");
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_not_reused() {
+        let flight = RefreshFlight::default();
+        let failed = flight
+            .run("graph", "rt-1", || async {
+                Err(AuthError::TokenRefreshFailed("HTTP 400".to_string()))
+            })
+            .await;
+        assert!(failed.is_err());
+
+        let retried = flight
+            .run("graph", "rt-1", || async { Ok(response("at-1")) })
+            .await
+            .unwrap();
+        assert_eq!(retried.access_token, "at-1");
+    }
+
+    #[tokio::test]
+    async fn test_cleared_refresh_is_not_reused() {
+        let flight = RefreshFlight::default();
+        flight
+            .run("graph", "rt-1", || async { Ok(response("at-1")) })
+            .await
+            .unwrap();
+        flight.clear().await;
+
+        let again = flight
+            .run("graph", "rt-1", || async { Ok(response("at-2")) })
+            .await
+            .unwrap();
+        assert_eq!(again.access_token, "at-2");
+    }
+}
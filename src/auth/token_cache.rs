@@ -0,0 +1,93 @@
+//! Access tokens cached for the account that signed in.
+//!
+//! A worker that keeps a Management API token between polls may not notice
+//! a sign-out when another account signs in right after. Each cached token
+//! is tagged with the object ID of the account it was issued to and the
+//! [`OAuth2Client`](super::oauth::OAuth2Client) token generation, which
+//! moves on with every sign-out and sign-in, so a token is only handed out
+//! to the session it was issued in.
+
+use chrono::{DateTime, Utc};
+
+/// Who a token was issued to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenOwner {
+    /// Object ID of the signed-in account.
+    pub account_id: String,
+    /// Token generation of the OAuth client when the token was requested.
+    pub generation: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CachedToken {
+    owner: TokenOwner,
+    access_token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A single access token, reused until shortly before it expires.
+#[derive(Debug, Clone, Default)]
+pub struct AccountTokenCache {
+    cached: Option<CachedToken>,
+}
+
+impl AccountTokenCache {
+    /// The cached token, if it was issued to `owner` and is valid past `valid_until`.
+    pub fn get(&self, owner: &TokenOwner, valid_until: DateTime<Utc>) -> Option<&str> {
+        self.cached
+            .as_ref()
+            .filter(|cached| cached.owner == *owner && valid_until < cached.expires_at)
+            .map(|cached| cached.access_token.as_str())
+    }
+
+    /// Keep `access_token`, issued to `owner`, until `expires_at`.
+    pub fn store(&mut self, owner: TokenOwner, access_token: String, expires_at: DateTime<Utc>) {
+        self.cached = Some(CachedToken {
+            owner,
+            access_token,
+            expires_at,
+        });
+    }
+
+    /// Drop the cached token.
+    pub fn clear(&mut self) {
+        self.cached = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn owner(account_id: &str, generation: u64) -> TokenOwner {
+        TokenOwner {
+            account_id: account_id.to_string(),
+            generation,
+        }
+    }
+
+    #[test]
+    fn test_token_only_reused_by_its_session() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut cache = AccountTokenCache::default();
+        cache.store(
+            owner("alice", 1),
+            "at-1".to_string(),
+            now + Duration::hours(1),
+        );
+
+        assert_eq!(cache.get(&owner("alice", 1), now), Some("at-1"));
+        // Another account, or the same one after signing out and in again
+        assert_eq!(cache.get(&owner("bob", 1), now), None);
+        assert_eq!(cache.get(&owner("alice", 2), now), None);
+        // Too close to expiry
+        assert_eq!(
+            cache.get(&owner("alice", 1), now + Duration::hours(1)),
+            None
+        );
+
+        cache.clear();
+        assert_eq!(cache.get(&owner("alice", 1), now), None);
+    }
+}
@@ -0,0 +1,82 @@
+//! Ways to copy the access token.
+//!
+//! Besides the raw token, the token can be copied as an `Authorization`
+//! header or as a `curl` command, ready to paste when debugging an API. The
+//! copied token is for Microsoft Graph, so the offered endpoints are Graph
+//! endpoints; any other URL can be entered instead.
+
+use super::graph::GRAPH_BASE_URL;
+
+/// Graph endpoints offered for curl snippets, as (menu label, path).
+pub const CURL_ENDPOINTS: [(&str, &str); 3] = [
+    ("Graph /me", "/me"),
+    ("Graph /me/memberOf", "/me/memberOf"),
+    ("Graph /organization", "/organization"),
+];
+
+/// How to put the access token on the clipboard.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TokenFormat {
+    /// The token alone.
+    #[default]
+    Raw,
+    /// `Authorization: Bearer <token>`.
+    BearerHeader,
+    /// A `curl` command calling `url` with the token.
+    Curl { url: String },
+}
+
+impl TokenFormat {
+    /// A curl snippet against one of [`CURL_ENDPOINTS`].
+    pub fn curl_endpoint(index: usize) -> Option<Self> {
+        let (_, path) = CURL_ENDPOINTS.get(index)?;
+        Some(Self::Curl {
+            url: format!("{}{}", GRAPH_BASE_URL, path),
+        })
+    }
+
+    /// The text copied for `token`.
+    pub fn render(&self, token: &str) -> String {
+        match self {
+            Self::Raw => token.to_string(),
+            Self::BearerHeader => format!("Authorization: Bearer {}", token),
+            Self::Curl { url } => format!(
+                "curl -sS -H {} {}",
+                shell_quote(&format!("Authorization: Bearer {}", token)),
+                shell_quote(url)
+            ),
+        }
+    }
+}
+
+/// Quote `text` as a single shell word.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_formats() {
+        assert_eq!(TokenFormat::Raw.render("eyJ0"), "eyJ0");
+        assert_eq!(
+            TokenFormat::BearerHeader.render("eyJ0"),
+            "Authorization: Bearer eyJ0"
+        );
+        assert_eq!(
+            TokenFormat::curl_endpoint(0).unwrap().render("eyJ0"),
+            "curl -sS -H 'Authorization: Bearer eyJ0' 'https://graph.microsoft.com/v1.0/me'"
+        );
+        assert_eq!(TokenFormat::curl_endpoint(CURL_ENDPOINTS.len()), None);
+
+        // Quotes in a custom URL can't end the shell word early
+        let custom = TokenFormat::Curl {
+            url: "https://example.com/?q='x'".to_string(),
+        };
+        assert!(custom
+            .render("eyJ0")
+            .ends_with(r"'https://example.com/?q='\''x'\'''"));
+    }
+}
@@ -1,6 +1,7 @@
 //! Token management with automatic refresh.
 
 use crate::auth::oauth::OAuth2Client;
+use crate::clock::{Clock, SystemClock};
 use crate::error::{AppError, AuthError};
 use crate::keychain;
 use chrono::{DateTime, Duration, Utc};
@@ -26,16 +27,24 @@ pub struct TokenManager {
     command_tx: Option<mpsc::Sender<TokenMessage>>,
     /// Whether auto-refresh is currently active.
     is_running: Arc<Mutex<bool>>,
+    /// Time source for computing token expiry.
+    clock: Arc<dyn Clock>,
 }
 
 #[allow(dead_code)]
 impl TokenManager {
     /// Create a new token manager.
     pub fn new(oauth_client: Arc<OAuth2Client>) -> Self {
+        Self::with_clock(oauth_client, Arc::new(SystemClock))
+    }
+
+    /// Create a new token manager using the given time source.
+    pub fn with_clock(oauth_client: Arc<OAuth2Client>, clock: Arc<dyn Clock>) -> Self {
         Self {
             oauth_client,
             command_tx: None,
             is_running: Arc::new(Mutex::new(false)),
+            clock,
         }
     }
 
@@ -56,6 +65,7 @@ impl TokenManager {
 
         let oauth_client = Arc::clone(&self.oauth_client);
         let is_running = Arc::clone(&self.is_running);
+        let clock = Arc::clone(&self.clock);
         let on_refresh = Arc::new(on_refresh);
 
         // Calculate when to refresh
@@ -83,14 +93,14 @@ impl TokenManager {
                         }
 
                         info!("Auto-refreshing token");
-                        let result = refresh_token_internal(&oauth_client).await;
+                        let result = refresh_token_internal(&oauth_client, clock.as_ref()).await;
                         (on_refresh)(result);
                     }
                     Some(msg) = rx.recv() => {
                         match msg {
                             TokenMessage::RefreshNow => {
                                 info!("Manual token refresh requested");
-                                let result = refresh_token_internal(&oauth_client).await;
+                                let result = refresh_token_internal(&oauth_client, clock.as_ref()).await;
                                 (on_refresh)(result);
                             }
                             TokenMessage::Stop => {
@@ -123,7 +133,7 @@ impl TokenManager {
             Ok(())
         } else {
             // No background task running, refresh directly
-            refresh_token_internal(&self.oauth_client).await
+            refresh_token_internal(&self.oauth_client, self.clock.as_ref()).await
         }
     }
 
@@ -135,7 +145,10 @@ impl TokenManager {
 
 /// Internal function to perform token refresh.
 #[allow(dead_code)]
-async fn refresh_token_internal(oauth_client: &OAuth2Client) -> Result<(), AppError> {
+async fn refresh_token_internal(
+    oauth_client: &OAuth2Client,
+    clock: &dyn Clock,
+) -> Result<(), AppError> {
     // Get refresh token from keychain
     let refresh_token = keychain::get_refresh_token()?;
 
@@ -150,7 +163,7 @@ async fn refresh_token_internal(oauth_client: &OAuth2Client) -> Result<(), AppEr
     }
 
     // Store new expiry time
-    let expires_at = Utc::now() + Duration::seconds(token_response.expires_in as i64);
+    let expires_at = clock.now() + Duration::seconds(token_response.expires_in as i64);
     keychain::store_token_expiry(&expires_at.to_rfc3339())?;
 
     info!("Token refreshed successfully, expires at {}", expires_at);
@@ -158,11 +171,125 @@ async fn refresh_token_internal(oauth_client: &OAuth2Client) -> Result<(), AppEr
     Ok(())
 }
 
+/// Consecutive background refresh failures after which background refresh pauses.
+pub const MAX_BACKGROUND_REFRESH_FAILURES: u32 = 10;
+
+/// Counts consecutive background refresh failures.
+///
+/// Once [`MAX_BACKGROUND_REFRESH_FAILURES`] is reached, background refresh
+/// pauses until a refresh succeeds or the user retries from the menu.
+#[derive(Debug, Default)]
+pub struct RefreshFailures {
+    count: u32,
+}
+
+impl RefreshFailures {
+    /// Consecutive failures so far.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Whether background refresh is paused.
+    pub fn is_degraded(&self) -> bool {
+        self.count >= MAX_BACKGROUND_REFRESH_FAILURES
+    }
+
+    /// Record a failure. Returns true if this failure pauses background refresh.
+    pub fn record_failure(&mut self) -> bool {
+        self.count = self.count.saturating_add(1);
+        self.count == MAX_BACKGROUND_REFRESH_FAILURES
+    }
+
+    /// Forget past failures. Returns true if there were any.
+    pub fn reset(&mut self) -> bool {
+        std::mem::take(&mut self.count) > 0
+    }
+}
+
+/// Menu text for consecutive background refresh failures, if there are any.
+pub fn refresh_failures_text(count: u32) -> Option<String> {
+    match count {
+        0 => None,
+        count if count >= MAX_BACKGROUND_REFRESH_FAILURES => {
+            Some(format!("Token refresh paused after {} failures", count))
+        }
+        1 => Some("Token refresh failed, retrying".to_string()),
+        count => Some(format!("Token refresh failed {} times, retrying", count)),
+    }
+}
+
+/// Delay before the first silent retry after a recoverable error.
+const ERROR_RETRY_INITIAL_SECS: i64 = 30;
+
+/// Longest delay between silent retries after a recoverable error.
+const ERROR_RETRY_MAX_SECS: i64 = 10 * 60;
+
+/// Countdowns before a silent retry are shown in steps of this many seconds.
+pub const RETRY_COUNTDOWN_STEP_SECS: i64 = 5;
+
+/// Exponential backoff for silently retrying a recoverable error.
+///
+/// Recoverable errors (Azure AD briefly unavailable) retry the token
+/// refresh on their own instead of asking for an interactive sign-in.
+#[derive(Debug, Default)]
+pub struct ErrorRetry {
+    attempts: u32,
+    next_at: Option<DateTime<Utc>>,
+}
+
+impl ErrorRetry {
+    /// Schedule the next retry, waiting twice as long as the last one.
+    pub fn schedule(&mut self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let at = now + error_retry_delay(self.attempts);
+        self.attempts = self.attempts.saturating_add(1);
+        self.next_at = Some(at);
+        at
+    }
+
+    /// When the next retry is due, if one is scheduled.
+    pub fn next_at(&self) -> Option<DateTime<Utc>> {
+        self.next_at
+    }
+
+    /// Whether a scheduled retry is due.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_at.is_some_and(|at| at <= now)
+    }
+
+    /// Stop retrying and start the backoff over. Returns true if a retry was scheduled.
+    pub fn reset(&mut self) -> bool {
+        self.attempts = 0;
+        self.next_at.take().is_some()
+    }
+}
+
+/// Delay before silent retry number `attempt` (starting at 0).
+pub fn error_retry_delay(attempt: u32) -> Duration {
+    let secs = ERROR_RETRY_INITIAL_SECS.saturating_mul(1 << attempt.min(16));
+    Duration::seconds(secs.min(ERROR_RETRY_MAX_SECS))
+}
+
+/// Menu text counting down to a silent retry, e.g. "Retrying in 30s".
+pub fn retry_countdown_text(remaining: Duration) -> String {
+    let secs = remaining.num_seconds();
+    if secs <= 0 {
+        return "Retrying…".to_string();
+    }
+    // Round up so the countdown never shows 0 before the retry runs
+    let step = RETRY_COUNTDOWN_STEP_SECS;
+    let secs = (secs + step - 1) / step * step;
+    if secs < 60 {
+        format!("Retrying in {}s", secs)
+    } else {
+        format!("Retrying in {} min", (secs + 59) / 60)
+    }
+}
+
 /// Calculate the remaining time until token expiry.
 #[allow(dead_code)]
-pub fn time_until_expiry(expiry_str: &str) -> Option<Duration> {
+pub fn time_until_expiry(expiry_str: &str, clock: &dyn Clock) -> Option<Duration> {
     let expiry: DateTime<Utc> = expiry_str.parse().ok()?;
-    let now = Utc::now();
+    let now = clock.now();
 
     if expiry > now {
         Some(expiry - now)
@@ -171,48 +298,99 @@ pub fn time_until_expiry(expiry_str: &str) -> Option<Duration> {
     }
 }
 
-/// Format duration as human-readable string (e.g., "45 min", "1 hour").
-pub fn format_duration(duration: Duration) -> String {
-    let total_minutes = duration.num_minutes();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
 
-    if total_minutes < 1 {
-        "< 1 min".to_string()
-    } else if total_minutes < 60 {
-        format!("{} min", total_minutes)
-    } else {
-        let hours = total_minutes / 60;
-        let mins = total_minutes % 60;
-        if mins == 0 {
-            format!("{} hour{}", hours, if hours == 1 { "" } else { "s" })
-        } else {
-            format!("{}h {}m", hours, mins)
+    #[test]
+    fn test_refresh_failures() {
+        let mut failures = RefreshFailures::default();
+        assert_eq!(refresh_failures_text(failures.count()), None);
+
+        for _ in 1..MAX_BACKGROUND_REFRESH_FAILURES {
+            assert!(!failures.record_failure());
         }
+        assert!(!failures.is_degraded());
+        assert_eq!(
+            refresh_failures_text(failures.count()).unwrap(),
+            format!(
+                "Token refresh failed {} times, retrying",
+                MAX_BACKGROUND_REFRESH_FAILURES - 1
+            )
+        );
+
+        // Only the failure that crosses the threshold pauses refresh
+        assert!(failures.record_failure());
+        assert!(failures.is_degraded());
+        assert!(!failures.record_failure());
+        assert_eq!(
+            refresh_failures_text(MAX_BACKGROUND_REFRESH_FAILURES).unwrap(),
+            format!(
+                "Token refresh paused after {} failures",
+                MAX_BACKGROUND_REFRESH_FAILURES
+            )
+        );
+
+        assert!(failures.reset());
+        assert!(!failures.is_degraded());
+        assert!(!failures.reset());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_error_retry_backoff() {
+        assert_eq!(error_retry_delay(0), Duration::seconds(30));
+        assert_eq!(error_retry_delay(1), Duration::seconds(60));
+        assert_eq!(error_retry_delay(3), Duration::minutes(4));
+        assert_eq!(error_retry_delay(5), Duration::minutes(10));
+        assert_eq!(error_retry_delay(u32::MAX), Duration::minutes(10));
+
+        let now = Utc::now();
+        let mut retry = ErrorRetry::default();
+        assert!(!retry.is_due(now));
+        assert_eq!(retry.schedule(now), now + Duration::seconds(30));
+        assert!(!retry.is_due(now));
+        assert!(retry.is_due(now + Duration::seconds(30)));
+        assert_eq!(retry.schedule(now), now + Duration::seconds(60));
+
+        assert!(retry.reset());
+        assert_eq!(retry.next_at(), None);
+        assert_eq!(retry.schedule(now), now + Duration::seconds(30));
+    }
 
     #[test]
-    fn test_format_duration() {
-        assert_eq!(format_duration(Duration::seconds(30)), "< 1 min");
-        assert_eq!(format_duration(Duration::minutes(5)), "5 min");
-        assert_eq!(format_duration(Duration::minutes(45)), "45 min");
-        assert_eq!(format_duration(Duration::hours(1)), "1 hour");
-        assert_eq!(format_duration(Duration::hours(2)), "2 hours");
-        assert_eq!(format_duration(Duration::minutes(90)), "1h 30m");
+    fn test_retry_countdown_text() {
+        assert_eq!(
+            retry_countdown_text(Duration::seconds(30)),
+            "Retrying in 30s"
+        );
+        assert_eq!(
+            retry_countdown_text(Duration::seconds(27)),
+            "Retrying in 30s"
+        );
+        assert_eq!(retry_countdown_text(Duration::seconds(1)), "Retrying in 5s");
+        assert_eq!(
+            retry_countdown_text(Duration::seconds(90)),
+            "Retrying in 2 min"
+        );
+        assert_eq!(retry_countdown_text(Duration::zero()), "Retrying…");
     }
 
     #[test]
     fn test_time_until_expiry() {
-        let future = (Utc::now() + Duration::hours(1)).to_rfc3339();
-        let duration = time_until_expiry(&future);
-        assert!(duration.is_some());
-        assert!(duration.unwrap().num_minutes() > 55);
+        let now = Utc::now();
+        let clock = MockClock::new(now);
+
+        let future = (now + Duration::hours(1)).to_rfc3339();
+        let duration = time_until_expiry(&future, &clock);
+        assert_eq!(duration, Some(Duration::hours(1)));
 
-        let past = (Utc::now() - Duration::hours(1)).to_rfc3339();
-        let duration = time_until_expiry(&past);
+        let past = (now - Duration::hours(1)).to_rfc3339();
+        let duration = time_until_expiry(&past, &clock);
         assert!(duration.is_none());
+
+        // Expired exactly at the expiry time
+        clock.advance(Duration::hours(1));
+        assert!(time_until_expiry(&future, &clock).is_none());
     }
 }
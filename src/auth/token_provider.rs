@@ -0,0 +1,114 @@
+//! Access tokens for code embedding the crate.
+//!
+//! [`PimClient`](crate::pim::PimClient) takes an ARM access token for each
+//! call. A [`TokenProvider`] hands out a current one, so a CI job or a bot
+//! can get it wherever it signs in: by redeeming a refresh token, e.g. the
+//! one the menu bar app keeps in the Keychain, or with a token it already
+//! has, e.g. from `az account get-access-token`.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tracing::warn;
+
+use super::oauth::OAuth2Client;
+use crate::error::{AuthError, KeychainError};
+use crate::keychain::{self, secure::SecureString};
+
+/// A source of access tokens.
+pub trait TokenProvider: Send + Sync {
+    /// An access token for Azure Resource Manager, valid for at least a few minutes.
+    fn management_token(&self) -> impl Future<Output = Result<String, AuthError>> + Send;
+}
+
+/// A token obtained elsewhere, handed out as is.
+#[derive(Debug, Clone)]
+pub struct StaticToken(SecureString);
+
+impl StaticToken {
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self(SecureString::new(access_token.into()))
+    }
+}
+
+impl TokenProvider for StaticToken {
+    async fn management_token(&self) -> Result<String, AuthError> {
+        Ok(self.0.as_str().to_string())
+    }
+}
+
+/// Redeems a refresh token for each access token.
+///
+/// Azure AD may rotate the refresh token when it is redeemed, so the newest
+/// one a response carries is used for the next access token. Clones share it.
+#[derive(Clone)]
+pub struct RefreshTokenProvider {
+    oauth: Arc<OAuth2Client>,
+    refresh_token: Arc<Mutex<SecureString>>,
+    /// Write rotated refresh tokens back to the Keychain.
+    keychain: bool,
+}
+
+impl RefreshTokenProvider {
+    pub fn new(oauth: Arc<OAuth2Client>, refresh_token: impl Into<String>) -> Self {
+        Self {
+            oauth,
+            refresh_token: Arc::new(Mutex::new(SecureString::new(refresh_token.into()))),
+            keychain: false,
+        }
+    }
+
+    /// Use the refresh token the menu bar app stored in the Keychain when
+    /// the user signed in. Rotated tokens are stored back, so the app keeps
+    /// a current one.
+    pub fn from_keychain(oauth: Arc<OAuth2Client>) -> Result<Self, KeychainError> {
+        let refresh_token = keychain::get_refresh_token()?;
+        Ok(Self {
+            keychain: true,
+            ..Self::new(oauth, refresh_token.as_str())
+        })
+    }
+
+    fn current_refresh_token(&self) -> SecureString {
+        self.refresh_token.lock().unwrap().clone()
+    }
+
+    /// Keep a rotated refresh token for the next redemption.
+    fn replace_refresh_token(&self, refresh_token: &str) {
+        *self.refresh_token.lock().unwrap() = SecureString::new(refresh_token.to_string());
+        if self.keychain {
+            if let Err(e) = keychain::store_refresh_token(refresh_token) {
+                warn!("Could not store the rotated refresh token: {}", e);
+            }
+        }
+    }
+}
+
+impl TokenProvider for RefreshTokenProvider {
+    async fn management_token(&self) -> Result<String, AuthError> {
+        let refresh_token = self.current_refresh_token();
+        let response = self
+            .oauth
+            .get_management_token(refresh_token.as_str())
+            .await?;
+        if let Some(rotated) = response.refresh_token.as_deref() {
+            if rotated != refresh_token.as_str() {
+                self.replace_refresh_token(rotated);
+            }
+        }
+        Ok(response.access_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_token() {
+        let provider = StaticToken::new("token");
+        assert_eq!(provider.management_token().await.unwrap(), "token");
+        // Tokens stay out of logs
+        assert!(!format!("{:?}", provider).contains("token\""));
+    }
+}
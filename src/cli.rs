@@ -0,0 +1,363 @@
+//! Command-line flags for maintenance operations.
+//!
+//! Flags are handled before the AppKit event loop starts, so support can fix
+//! a broken install from Terminal without using the menu.
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use crate::auth::callback_server::CALLBACK_PORT;
+use crate::auth::graph::UserInfo;
+use crate::config::Config;
+use crate::error::{KeychainError, PimError};
+use crate::{keychain, notifications, pim, settings};
+
+/// Timeout for each endpoint check in `--diagnose`.
+const DIAGNOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Azure Management API endpoint checked by `--diagnose`.
+const MANAGEMENT_URL: &str = "https://management.azure.com";
+
+/// Deletes one of the PIM stores.
+type DeleteStore = fn() -> Result<(), PimError>;
+
+/// Usage text for `--help`.
+const USAGE: &str = "\
+Usage: azurepim [OPTION]
+
+Without options, starts the menu bar app.
+
+Options:
+  --version    Print the version and exit
+  --diagnose   Print configuration and check connectivity to Azure endpoints
+  --signout    Remove stored credentials from the Keychain
+  --reset      Remove stored credentials, settings, and saved role data
+  --help       Print this help";
+
+/// A maintenance command selected on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Version,
+    Diagnose,
+    SignOut,
+    Reset,
+    Help,
+}
+
+/// Parse command-line arguments (excluding the program name).
+///
+/// Returns `Ok(None)` when the app should start normally.
+pub fn parse_args<I, S>(args: I) -> Result<Option<Command>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    for arg in args {
+        let command = match arg.as_ref() {
+            "--version" | "-V" => Command::Version,
+            "--diagnose" => Command::Diagnose,
+            "--signout" => Command::SignOut,
+            "--reset" => Command::Reset,
+            "--help" | "-h" => Command::Help,
+            // Finder passes a process serial number on older macOS versions
+            a if a.starts_with("-psn_") => continue,
+            a => return Err(format!("Unknown option: {}", a)),
+        };
+        return Ok(Some(command));
+    }
+    Ok(None)
+}
+
+/// Handle command-line flags. Exits the process if a command was run.
+pub fn handle_args() {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(command)) => command,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // Commands print their results; nothing should pop up in Notification Center
+    notifications::set_backend(Box::new(notifications::NoopBackend));
+
+    let success = match command {
+        Command::Version => {
+            println!("azurepim {}", env!("CARGO_PKG_VERSION"));
+            true
+        }
+        Command::Help => {
+            println!("{}", USAGE);
+            true
+        }
+        Command::SignOut => sign_out(),
+        Command::Reset => reset(),
+        Command::Diagnose => diagnose(),
+    };
+
+    std::process::exit(if success { 0 } else { 1 });
+}
+
+/// Remove stored credentials.
+fn sign_out() -> bool {
+    match keychain::delete_all() {
+        Ok(()) => {
+            println!("Signed out: credentials removed from the Keychain.");
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to clear Keychain: {}", e);
+            false
+        }
+    }
+}
+
+/// Remove stored credentials and all settings files.
+fn reset() -> bool {
+    let mut success = sign_out();
+
+    let files = [
+        settings::get_settings_path(),
+        pim::settings::get_settings_path(),
+        pim::store::get_assignments_path(),
+        pim::store::get_roles_cache_path(),
+    ];
+    for path in files.into_iter().flatten() {
+        match std::fs::remove_file(&path) {
+            Ok(()) => println!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                eprintln!("Failed to remove {}: {}", path.display(), e);
+                success = false;
+            }
+        }
+    }
+
+    // Stores without a path of their own here
+    let stores: [(&str, DeleteStore); 5] = [
+        (
+            "scheduled deactivations",
+            pim::delete_scheduled_deactivations,
+        ),
+        ("session notes", pim::delete_session_notes),
+        (
+            "subscriptions without PIM access",
+            pim::delete_pim_availability,
+        ),
+        ("subscription names", pim::delete_subscription_names),
+        ("cache owner", pim::delete_cache_owner),
+    ];
+    for (what, delete) in stores {
+        if let Err(e) = delete() {
+            eprintln!("Failed to remove {}: {}", what, e);
+            success = false;
+        }
+    }
+
+    if success {
+        println!("Reset complete.");
+    }
+    success
+}
+
+/// Print configuration and check that required endpoints are reachable.
+fn diagnose() -> bool {
+    println!("azurepim {}\n", env!("CARGO_PKG_VERSION"));
+
+    let mut ok = true;
+    let config = match Config::load() {
+        Ok(config) => {
+            report("Configuration", true, "loaded");
+            println!("    client_id:    {}", config.oauth.client_id);
+            println!("    tenant:       {}", config.oauth.tenant);
+            println!("    redirect_uri: {}", config.oauth.redirect_uri);
+            Some(config)
+        }
+        Err(e) => {
+            report("Configuration", false, &e.to_string());
+            ok = false;
+            None
+        }
+    };
+
+    println!();
+    // Show the synced favorites file if the user chose one
+    pim::settings::set_custom_settings_path(settings::load_settings().favorites_file);
+    print_path("Settings", settings::get_settings_path());
+    print_path("PIM settings", pim::settings::get_settings_path());
+    print_path("Logs", Some(settings::log_directory()));
+    println!(
+        "{:<14} {}",
+        "Notifications:",
+        notifications::default_backend().name()
+    );
+    println!();
+
+    match keychain::get_refresh_token() {
+        Ok(_) => {
+            report("Keychain", true, "refresh token stored");
+            print_stored_user();
+        }
+        Err(KeychainError::NotFound) => report("Keychain", true, "no stored session"),
+        Err(e) => {
+            report("Keychain", false, &e.to_string());
+            ok = false;
+        }
+    }
+
+    match TcpListener::bind(("127.0.0.1", CALLBACK_PORT)) {
+        Ok(_) => report(
+            "Callback port",
+            true,
+            &format!("{} available", CALLBACK_PORT),
+        ),
+        Err(e) => {
+            report(
+                "Callback port",
+                false,
+                &format!("{} in use: {}", CALLBACK_PORT, e),
+            );
+            ok = false;
+        }
+    }
+
+    if let Some(config) = config {
+        let endpoints = [
+            (
+                "Azure AD",
+                format!(
+                    "https://login.microsoftonline.com/{}/v2.0/.well-known/openid-configuration",
+                    config.oauth.tenant
+                ),
+                true,
+            ),
+            ("Microsoft Graph", config.api.graph_base_url.clone(), false),
+            ("Azure Management", MANAGEMENT_URL.to_string(), false),
+        ];
+        ok &= check_endpoints(&endpoints);
+    }
+
+    println!();
+    println!(
+        "{}",
+        if ok {
+            "All checks passed."
+        } else {
+            "Some checks failed."
+        }
+    );
+    ok
+}
+
+/// Check that each endpoint responds.
+///
+/// Endpoints flagged `require_success` must return a 2xx status (e.g. to validate the
+/// tenant); the others only need to be reachable.
+fn check_endpoints(endpoints: &[(&str, String, bool)]) -> bool {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            report("Network", false, &format!("failed to start runtime: {}", e));
+            return false;
+        }
+    };
+
+    let client = match reqwest::Client::builder().timeout(DIAGNOSE_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            report(
+                "Network",
+                false,
+                &format!("failed to create HTTP client: {}", e),
+            );
+            return false;
+        }
+    };
+
+    let mut ok = true;
+    for (name, url, require_success) in endpoints {
+        match runtime.block_on(client.get(url).send()) {
+            Ok(response) if *require_success && !response.status().is_success() => {
+                report(
+                    name,
+                    false,
+                    &format!("HTTP {} (check tenant)", response.status()),
+                );
+                ok = false;
+            }
+            Ok(response) => report(
+                name,
+                true,
+                &format!("reachable (HTTP {})", response.status()),
+            ),
+            Err(e) => {
+                report(name, false, &e.to_string());
+                ok = false;
+            }
+        }
+    }
+    ok
+}
+
+/// Print a single check result.
+fn report(name: &str, ok: bool, detail: &str) {
+    println!(
+        "[{}] {:<18} {}",
+        if ok { " OK " } else { "FAIL" },
+        name,
+        detail
+    );
+}
+
+/// Print who the stored session belongs to, so audit screenshots identify the user.
+fn print_stored_user() {
+    let Some(user) = keychain::get_user_info()
+        .ok()
+        .and_then(|json| UserInfo::from_json(&json).ok())
+    else {
+        return;
+    };
+    println!("    user:         {} <{}>", user.display_name, user.email);
+    println!(
+        "    tenant:       {} ({})",
+        user.tenant_name, user.tenant_id
+    );
+    if let Some(job_title) = &user.job_title {
+        println!("    job title:    {}", job_title);
+    }
+    if let Some(office) = &user.office_location {
+        println!("    office:       {}", office);
+    }
+}
+
+/// Print a labelled file path.
+fn print_path(label: &str, path: Option<std::path::PathBuf>) {
+    match path {
+        Some(path) => println!("{:<14} {}", format!("{}:", label), path.display()),
+        None => println!("{:<14} (unavailable)", format!("{}:", label)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(Vec::<String>::new()), Ok(None));
+        assert_eq!(parse_args(["--version"]), Ok(Some(Command::Version)));
+        assert_eq!(parse_args(["--diagnose"]), Ok(Some(Command::Diagnose)));
+        assert_eq!(parse_args(["--signout"]), Ok(Some(Command::SignOut)));
+        assert_eq!(parse_args(["--reset"]), Ok(Some(Command::Reset)));
+        assert!(parse_args(["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_ignores_process_serial_number() {
+        assert_eq!(parse_args(["-psn_0_12345"]), Ok(None));
+    }
+}
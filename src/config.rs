@@ -4,8 +4,11 @@
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 
+use crate::auth::features::Feature;
+
 /// Embedded configuration file content.
 const CONFIG_TOML: &str = include_str!("../config.toml");
 
@@ -17,6 +20,8 @@ pub struct Config {
     pub oauth: OAuthConfig,
     pub api: ApiConfig,
     pub token: TokenConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
     pub logging: LoggingConfig,
 }
 
@@ -34,6 +39,24 @@ pub struct OAuthConfig {
     pub tenant: String,
     pub redirect_uri: String,
     pub scopes: ScopesConfig,
+    /// Profile whose scopes replace `scopes`, if set.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Named scope lists, e.g. for tenants that don't allow consent to group scopes.
+    #[serde(default)]
+    pub profiles: HashMap<String, ScopesConfig>,
+}
+
+impl OAuthConfig {
+    /// Scopes requested for the primary token: the active profile's, or the defaults.
+    pub fn active_scopes(&self) -> &[String] {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+            .unwrap_or(&self.scopes)
+            .scopes
+            .as_slice()
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -42,9 +65,83 @@ pub struct ScopesConfig {
 }
 
 #[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
 pub struct ApiConfig {
+    /// Microsoft Graph base URL; its last path segment is the Graph version.
     pub graph_base_url: String,
+    #[serde(default)]
+    pub versions: ApiVersions,
+}
+
+/// `api-version` of each Azure Management API called.
+///
+/// Azure retires API versions; a newer one can be set with
+/// `AZURE_PIM_API_VERSIONS`, e.g. "pim=2022-04-01-preview,batch=2022-12-01",
+/// without waiting for a release.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ApiVersions {
+    /// PIM schedules, schedule requests and role policies.
+    pub pim: String,
+    /// Role assignment approvals (preview only).
+    pub approvals: String,
+    /// Subscription listing.
+    pub subscriptions: String,
+    /// Role definitions.
+    pub role_definitions: String,
+    /// Batch requests.
+    pub batch: String,
+}
+
+impl Default for ApiVersions {
+    fn default() -> Self {
+        Self {
+            pim: "2020-10-01".to_string(),
+            approvals: "2021-01-01-preview".to_string(),
+            subscriptions: "2022-12-01".to_string(),
+            role_definitions: "2022-04-01".to_string(),
+            batch: "2020-06-01".to_string(),
+        }
+    }
+}
+
+impl ApiVersions {
+    /// Each version with its name, as in config.toml and `AZURE_PIM_API_VERSIONS`.
+    pub fn entries(&self) -> [(&'static str, &str); 5] {
+        [
+            ("pim", &self.pim),
+            ("approvals", &self.approvals),
+            ("subscriptions", &self.subscriptions),
+            ("role_definitions", &self.role_definitions),
+            ("batch", &self.batch),
+        ]
+    }
+
+    /// Apply overrides such as "pim=2022-04-01-preview,batch=2022-12-01".
+    pub fn apply_overrides(&mut self, overrides: &str) -> Result<()> {
+        for entry in overrides.split(',').filter(|e| !e.trim().is_empty()) {
+            let Some((name, version)) = entry.split_once('=') else {
+                anyhow::bail!(
+                    "Expected name=version in AZURE_PIM_API_VERSIONS, got {:?}",
+                    entry
+                );
+            };
+            let version = version.trim().to_string();
+            let field = match name.trim() {
+                "pim" => &mut self.pim,
+                "approvals" => &mut self.approvals,
+                "subscriptions" => &mut self.subscriptions,
+                "role_definitions" => &mut self.role_definitions,
+                "batch" => &mut self.batch,
+                other => anyhow::bail!(
+                    "Unknown API {:?} in AZURE_PIM_API_VERSIONS, expected one of \
+                     pim, approvals, subscriptions, role_definitions, batch",
+                    other
+                ),
+            };
+            *field = version;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -53,6 +150,28 @@ pub struct TokenConfig {
     pub refresh_before_expiry_seconds: u64,
 }
 
+/// Runtime and HTTP resource tuning for large tenants.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Tokio worker threads.
+    pub worker_threads: usize,
+    /// Maximum concurrent Management API requests when scanning subscriptions.
+    pub max_concurrent_requests: usize,
+    /// Maximum idle connections kept per host by each HTTP client.
+    pub pool_max_idle_per_host: usize,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            worker_threads: 2,
+            max_concurrent_requests: 4,
+            pool_max_idle_per_host: 8,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[allow(dead_code)]
 pub struct LoggingConfig {
@@ -80,6 +199,30 @@ impl Config {
             config.oauth.redirect_uri = redirect_uri;
         }
 
+        if let Ok(profile) = env::var("AZURE_PIM_PROFILE") {
+            config.oauth.profile = Some(profile);
+        }
+
+        if let Ok(graph_base_url) = env::var("AZURE_PIM_GRAPH_BASE_URL") {
+            config.api.graph_base_url = graph_base_url;
+        }
+
+        if let Ok(versions) = env::var("AZURE_PIM_API_VERSIONS") {
+            config.api.versions.apply_overrides(&versions)?;
+        }
+
+        if let Some(threads) = env_usize("AZURE_PIM_WORKER_THREADS")? {
+            config.runtime.worker_threads = threads;
+        }
+
+        if let Some(requests) = env_usize("AZURE_PIM_MAX_CONCURRENT_REQUESTS")? {
+            config.runtime.max_concurrent_requests = requests;
+        }
+
+        if let Some(pool) = env_usize("AZURE_PIM_POOL_MAX_IDLE_PER_HOST")? {
+            config.runtime.pool_max_idle_per_host = pool;
+        }
+
         if let Ok(log_level) = env::var("RUST_LOG") {
             config.logging.level = log_level;
         }
@@ -106,6 +249,30 @@ impl Config {
             );
         }
 
+        if let Some(profile) = &self.oauth.profile {
+            if !self.oauth.profiles.contains_key(profile) {
+                anyhow::bail!("Unknown scope profile {:?} in oauth.profile", profile);
+            }
+        }
+
+        for feature in Feature::ALL.into_iter().filter(|f| f.is_required()) {
+            if !feature.is_covered_by(self.oauth.active_scopes()) {
+                anyhow::bail!(
+                    "OAuth scopes must include {} ({})",
+                    feature.needs_text(),
+                    feature.name()
+                );
+            }
+        }
+
+        if self.runtime.worker_threads == 0 {
+            anyhow::bail!("runtime.worker_threads must be at least 1");
+        }
+
+        if self.runtime.max_concurrent_requests == 0 {
+            anyhow::bail!("runtime.max_concurrent_requests must be at least 1");
+        }
+
         Ok(())
     }
 
@@ -128,6 +295,18 @@ impl Config {
     }
 }
 
+/// Read a numeric environment variable override, if set.
+fn env_usize(name: &str) -> Result<Option<usize>> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .with_context(|| format!("{} must be a number, got {:?}", name, value)),
+        Err(_) => Ok(None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,9 +319,81 @@ mod tests {
         assert!(result.is_ok(), "Config parsing failed: {:?}", result.err());
     }
 
+    #[test]
+    fn test_runtime_config_validation() {
+        let mut config = test_config();
+        assert!(config.validate().is_ok());
+
+        config.runtime.worker_threads = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_scope_profiles() {
+        let mut config = test_config();
+        config.oauth.profiles.insert(
+            "restricted".into(),
+            ScopesConfig {
+                scopes: vec!["User.Read".into(), "Directory.Read.All".into()],
+            },
+        );
+        assert_eq!(config.oauth.active_scopes(), ["User.Read"]);
+
+        config.oauth.profile = Some("restricted".into());
+        assert_eq!(
+            config.oauth.active_scopes(),
+            ["User.Read", "Directory.Read.All"]
+        );
+        assert!(config.validate().is_ok());
+
+        config.oauth.profile = Some("missing".into());
+        assert!(config.validate().is_err());
+
+        // Sign-in needs a scope that can read the user profile
+        config.oauth.profiles.insert(
+            "broken".into(),
+            ScopesConfig {
+                scopes: vec!["GroupMember.Read.All".into()],
+            },
+        );
+        config.oauth.profile = Some("broken".into());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_api_version_overrides() {
+        let mut versions = ApiVersions::default();
+        versions
+            .apply_overrides("pim=2022-04-01-preview, batch=2022-12-01,")
+            .unwrap();
+        assert_eq!(versions.pim, "2022-04-01-preview");
+        assert_eq!(versions.batch, "2022-12-01");
+        assert_eq!(versions.approvals, ApiVersions::default().approvals);
+
+        assert!(versions.apply_overrides("graph=beta").is_err());
+        assert!(versions.apply_overrides("pim").is_err());
+
+        // The embedded config sets the defaults
+        let config: Config = toml::from_str(CONFIG_TOML).unwrap();
+        assert_eq!(config.api.versions, ApiVersions::default());
+    }
+
     #[test]
     fn test_urls() {
-        let config = Config {
+        let config = test_config();
+
+        assert_eq!(
+            config.auth_url(),
+            "https://login.microsoftonline.com/test-tenant/oauth2/v2.0/authorize"
+        );
+        assert_eq!(
+            config.token_url(),
+            "https://login.microsoftonline.com/test-tenant/oauth2/v2.0/token"
+        );
+    }
+
+    fn test_config() -> Config {
+        Config {
             app: AppConfig {
                 name: "test".into(),
                 version: "0.1.0".into(),
@@ -155,26 +406,21 @@ mod tests {
                 scopes: ScopesConfig {
                     scopes: vec!["User.Read".into()],
                 },
+                profile: None,
+                profiles: HashMap::new(),
             },
             api: ApiConfig {
                 graph_base_url: "https://graph.microsoft.com/v1.0".into(),
+                versions: ApiVersions::default(),
             },
             token: TokenConfig {
                 refresh_before_expiry_seconds: 300,
             },
+            runtime: RuntimeConfig::default(),
             logging: LoggingConfig {
                 level: "info".into(),
                 log_dir: "azurepim".into(),
             },
-        };
-
-        assert_eq!(
-            config.auth_url(),
-            "https://login.microsoftonline.com/test-tenant/oauth2/v2.0/authorize"
-        );
-        assert_eq!(
-            config.token_url(),
-            "https://login.microsoftonline.com/test-tenant/oauth2/v2.0/token"
-        );
+        }
     }
 }
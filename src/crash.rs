@@ -0,0 +1,193 @@
+//! Crash reporting for panics.
+//!
+//! A menu bar app that panics disappears without a trace, so a panic hook
+//! writes a report (version, backtrace, recent actions) to the log directory.
+//! Reports are offered to the user on the next launch.
+
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use tracing::{error, info};
+
+use crate::settings::log_directory;
+
+/// Number of recent actions kept for crash reports.
+const MAX_RECENT_ACTIONS: usize = 20;
+
+/// File name prefix for crash reports.
+const REPORT_PREFIX: &str = "crash-";
+
+/// Extension for reports not yet shown to the user.
+const PENDING_EXTENSION: &str = "txt";
+
+/// Extension for reports the user has already been offered.
+const SEEN_EXTENSION: &str = "seen";
+
+/// Recently performed actions, oldest first.
+static RECENT_ACTIONS: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_RECENT_ACTIONS)));
+
+/// Record an action so it appears in crash reports.
+pub fn record_action(description: impl Into<String>) {
+    if let Ok(mut actions) = RECENT_ACTIONS.lock() {
+        if actions.len() == MAX_RECENT_ACTIONS {
+            actions.pop_front();
+        }
+        actions.push_back(format!(
+            "{} {}",
+            Utc::now().format("%H:%M:%S"),
+            description.into()
+        ));
+    }
+}
+
+/// Install a panic hook that writes a crash report before the default hook runs.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |panic_info| {
+        let thread = std::thread::current();
+        let message = format!(
+            "Thread '{}' panicked: {}",
+            thread.name().unwrap_or("<unnamed>"),
+            panic_info
+        );
+
+        match write_report(&message, &Backtrace::force_capture()) {
+            Ok(path) => error!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write crash report: {}", e),
+        }
+
+        default_hook(panic_info);
+    }));
+}
+
+/// Write a crash report to the log directory.
+fn write_report(message: &str, backtrace: &Backtrace) -> std::io::Result<PathBuf> {
+    let dir = log_directory();
+    fs::create_dir_all(&dir)?;
+
+    // try_lock: the panic may have happened while the lock was held
+    let actions: Vec<String> = RECENT_ACTIONS
+        .try_lock()
+        .map(|a| a.iter().cloned().collect())
+        .unwrap_or_default();
+
+    let now = Utc::now();
+    let path = dir.join(format!(
+        "{}{}.{}",
+        REPORT_PREFIX,
+        now.format("%Y%m%d-%H%M%S"),
+        PENDING_EXTENSION
+    ));
+    fs::write(
+        &path,
+        format_report(message, &now.to_rfc3339(), &actions, backtrace),
+    )?;
+    Ok(path)
+}
+
+/// Format the contents of a crash report.
+fn format_report(
+    message: &str,
+    timestamp: &str,
+    actions: &[String],
+    backtrace: &impl std::fmt::Display,
+) -> String {
+    let actions = if actions.is_empty() {
+        "  (none)".to_string()
+    } else {
+        actions
+            .iter()
+            .map(|a| format!("  {}", a))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(
+        "Azure PIM crash report\n\
+         Version: {}\n\
+         Architecture: {}\n\
+         Time: {}\n\n\
+         {}\n\n\
+         Recent actions:\n{}\n\n\
+         Backtrace:\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::ARCH,
+        timestamp,
+        message,
+        actions,
+        backtrace
+    )
+}
+
+/// Find the most recent crash report not yet shown to the user.
+pub fn latest_pending_report() -> Option<PathBuf> {
+    let entries = fs::read_dir(log_directory()).ok()?;
+    entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| is_pending_report(p))
+        // Timestamped names sort chronologically
+        .max()
+}
+
+/// Mark all pending crash reports as seen so they aren't offered again.
+pub fn mark_reports_seen() {
+    let Ok(entries) = fs::read_dir(log_directory()) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        if is_pending_report(&path) {
+            if let Err(e) = fs::rename(&path, path.with_extension(SEEN_EXTENSION)) {
+                error!(
+                    "Failed to mark crash report {} as seen: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+    info!("Crash reports marked as seen");
+}
+
+/// Check whether a path is a crash report that hasn't been shown yet.
+fn is_pending_report(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    name.starts_with(REPORT_PREFIX)
+        && path.extension().and_then(|e| e.to_str()) == Some(PENDING_EXTENSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_pending_report() {
+        assert!(is_pending_report(Path::new(
+            "/logs/crash-20250101-120000.txt"
+        )));
+        assert!(!is_pending_report(Path::new(
+            "/logs/crash-20250101-120000.seen"
+        )));
+        assert!(!is_pending_report(Path::new("/logs/azurepim.log")));
+    }
+
+    #[test]
+    fn test_format_report() {
+        let actions = vec!["12:00:00 SignIn".to_string()];
+        let report = format_report("boom", "2025-01-01T12:00:00Z", &actions, &"frame 0");
+        assert!(report.contains(env!("CARGO_PKG_VERSION")));
+        assert!(report.contains("boom"));
+        assert!(report.contains("  12:00:00 SignIn"));
+        assert!(report.contains("Backtrace:\nframe 0"));
+    }
+}
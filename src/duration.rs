@@ -0,0 +1,260 @@
+//! Duration formatting for the menu, dialogs, notifications and VoiceOver.
+//!
+//! Every duration shown to the user goes through here, so token expiry,
+//! active roles and notifications read the same way: "1h 30m" where space
+//! is short, "1 hour 30 minutes" where it is read out. Units, plurals and
+//! the "left"/"ago" phrases follow the first supported language in the
+//! user's macOS language preferences, falling back to English.
+
+use chrono::Duration;
+use once_cell::sync::OnceCell;
+
+/// Language durations are formatted in, set once at startup.
+static CURRENT: OnceCell<Language> = OnceCell::new();
+
+/// Languages durations are formatted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    /// Norwegian (Bokmål; Nynorsk reads the same for durations).
+    Norwegian,
+    German,
+}
+
+impl Language {
+    /// The language for a BCP 47 code such as "nb-NO", if supported.
+    pub fn from_code(code: &str) -> Option<Self> {
+        let primary = code.split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Self::English),
+            "nb" | "nn" | "no" => Some(Self::Norwegian),
+            "de" => Some(Self::German),
+            _ => None,
+        }
+    }
+
+    /// Use the first supported language among the user's preferred languages.
+    ///
+    /// Only the first call counts; changing the language needs a restart, as
+    /// for other apps.
+    pub fn set_preferred(codes: &[String]) {
+        let language = codes
+            .iter()
+            .find_map(|code| Self::from_code(code))
+            .unwrap_or_default();
+        // Ignored if already set
+        let _ = CURRENT.set(language);
+    }
+
+    /// The language set at startup, English if none was.
+    pub fn current() -> Self {
+        CURRENT.get().copied().unwrap_or_default()
+    }
+}
+
+/// How much room a duration gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Abbreviated units for menu titles, e.g. "1h 30m".
+    Short,
+    /// Words for sentences and VoiceOver, e.g. "1 hour 30 minutes".
+    Long,
+}
+
+/// Format a duration in the user's language.
+pub fn format(duration: Duration, style: Style) -> String {
+    format_in(duration, style, Language::current())
+}
+
+/// Format a whole number of minutes, e.g. for durations offered in menus.
+pub fn format_minutes(minutes: u32) -> String {
+    format(Duration::minutes(minutes as i64), Style::Short)
+}
+
+/// Time left, e.g. "1h 30m left".
+pub fn left(duration: Duration) -> String {
+    left_in(duration, Language::current())
+}
+
+/// Time since, e.g. "40m ago".
+pub fn ago(duration: Duration) -> String {
+    ago_in(duration, Language::current())
+}
+
+/// Format a duration in `language`. Seconds are dropped; under a minute
+/// reads as "< 1m" or "less than a minute".
+pub fn format_in(duration: Duration, style: Style, language: Language) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 1 {
+        return match (style, language) {
+            (Style::Short, Language::German) => "< 1 Min.".to_string(),
+            (Style::Short, _) => "< 1m".to_string(),
+            (Style::Long, Language::English) => "less than a minute".to_string(),
+            (Style::Long, Language::Norwegian) => "under ett minutt".to_string(),
+            (Style::Long, Language::German) => "weniger als eine Minute".to_string(),
+        };
+    }
+
+    let hours_text = |n: i64| unit(n, Unit::Hour, style, language);
+    let minutes_text = |n: i64| unit(n, Unit::Minute, style, language);
+    match (minutes / 60, minutes % 60) {
+        (0, mins) => minutes_text(mins),
+        (hours, 0) => hours_text(hours),
+        (hours, mins) => format!("{} {}", hours_text(hours), minutes_text(mins)),
+    }
+}
+
+/// Time left in `language`.
+pub fn left_in(duration: Duration, language: Language) -> String {
+    let text = format_in(duration, Style::Short, language);
+    match language {
+        Language::English => format!("{} left", text),
+        Language::Norwegian => format!("{} igjen", text),
+        Language::German => format!("noch {}", text),
+    }
+}
+
+/// Time since in `language`.
+pub fn ago_in(duration: Duration, language: Language) -> String {
+    let text = format_in(duration, Style::Short, language);
+    match language {
+        Language::English => format!("{} ago", text),
+        Language::Norwegian => format!("for {} siden", text),
+        Language::German => format!("vor {}", text),
+    }
+}
+
+/// Read a duration typed as minutes ("90"), or hours and minutes ("2h",
+/// "1h 30m"). Returns `None` for anything else or a zero duration.
+pub fn parse_minutes(text: &str) -> Option<u32> {
+    let text = text.trim().to_ascii_lowercase();
+    if let Ok(minutes) = text.parse::<u32>() {
+        return (minutes > 0).then_some(minutes);
+    }
+    let (hours, rest) = match text.split_once('h') {
+        Some((hours, rest)) => (hours.trim().parse::<u32>().ok()?, rest.trim()),
+        None => (0, text.as_str()),
+    };
+    let minutes = match rest.strip_suffix('m') {
+        Some(minutes) => minutes.trim().parse::<u32>().ok()?,
+        None if rest.is_empty() => 0,
+        None => return None,
+    };
+    let total = hours.checked_mul(60)?.checked_add(minutes)?;
+    (total > 0).then_some(total)
+}
+
+#[derive(Clone, Copy)]
+enum Unit {
+    Hour,
+    Minute,
+}
+
+/// A count with its unit, pluralized for the long style.
+fn unit(n: i64, unit: Unit, style: Style, language: Language) -> String {
+    let one = n == 1;
+    match (style, language, unit) {
+        (Style::Short, Language::German, Unit::Hour) => format!("{} Std.", n),
+        (Style::Short, Language::German, Unit::Minute) => format!("{} Min.", n),
+        (Style::Short, Language::Norwegian, Unit::Hour) => format!("{}t", n),
+        (Style::Short, _, Unit::Hour) => format!("{}h", n),
+        (Style::Short, _, Unit::Minute) => format!("{}m", n),
+        (Style::Long, Language::English, Unit::Hour) => plural(n, one, "hour", "hours"),
+        (Style::Long, Language::English, Unit::Minute) => plural(n, one, "minute", "minutes"),
+        (Style::Long, Language::Norwegian, Unit::Hour) => plural(n, one, "time", "timer"),
+        (Style::Long, Language::Norwegian, Unit::Minute) => plural(n, one, "minutt", "minutter"),
+        (Style::Long, Language::German, Unit::Hour) => plural(n, one, "Stunde", "Stunden"),
+        (Style::Long, Language::German, Unit::Minute) => plural(n, one, "Minute", "Minuten"),
+    }
+}
+
+fn plural(n: i64, one: bool, singular: &str, plural: &str) -> String {
+    format!("{} {}", n, if one { singular } else { plural })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_durations() {
+        let short = |d| format_in(d, Style::Short, Language::English);
+        assert_eq!(short(Duration::seconds(30)), "< 1m");
+        assert_eq!(short(Duration::minutes(45)), "45m");
+        assert_eq!(short(Duration::hours(2)), "2h");
+        assert_eq!(short(Duration::minutes(90)), "1h 30m");
+        assert_eq!(short(Duration::seconds(90 * 60 + 59)), "1h 30m");
+        assert_eq!(
+            format_in(Duration::minutes(90), Style::Short, Language::Norwegian),
+            "1t 30m"
+        );
+        assert_eq!(
+            format_in(Duration::minutes(90), Style::Short, Language::German),
+            "1 Std. 30 Min."
+        );
+    }
+
+    #[test]
+    fn test_parse_minutes() {
+        assert_eq!(parse_minutes("90"), Some(90));
+        assert_eq!(parse_minutes(" 45m "), Some(45));
+        assert_eq!(parse_minutes("2h"), Some(120));
+        assert_eq!(parse_minutes("1h 30m"), Some(90));
+        assert_eq!(parse_minutes("1H30M"), Some(90));
+        assert_eq!(parse_minutes("0"), None);
+        assert_eq!(parse_minutes("0h 0m"), None);
+        assert_eq!(parse_minutes("m"), None);
+        assert_eq!(parse_minutes("1.5h"), None);
+        assert_eq!(parse_minutes("soon"), None);
+    }
+
+    #[test]
+    fn test_long_durations_pluralize() {
+        let long = |d, language| format_in(d, Style::Long, language);
+        assert_eq!(long(Duration::minutes(1), Language::English), "1 minute");
+        assert_eq!(
+            long(Duration::minutes(65), Language::English),
+            "1 hour 5 minutes"
+        );
+        assert_eq!(long(Duration::hours(2), Language::English), "2 hours");
+        assert_eq!(
+            long(Duration::seconds(5), Language::English),
+            "less than a minute"
+        );
+        assert_eq!(
+            long(Duration::minutes(121), Language::Norwegian),
+            "2 timer 1 minutt"
+        );
+        assert_eq!(
+            long(Duration::minutes(61), Language::German),
+            "1 Stunde 1 Minute"
+        );
+        assert_eq!(long(Duration::minutes(30), Language::German), "30 Minuten");
+    }
+
+    #[test]
+    fn test_left_and_ago() {
+        assert_eq!(
+            left_in(Duration::minutes(65), Language::English),
+            "1h 5m left"
+        );
+        assert_eq!(
+            left_in(Duration::minutes(65), Language::Norwegian),
+            "1t 5m igjen"
+        );
+        assert_eq!(ago_in(Duration::minutes(40), Language::English), "40m ago");
+        assert_eq!(
+            ago_in(Duration::minutes(40), Language::German),
+            "vor 40 Min."
+        );
+    }
+
+    #[test]
+    fn test_language_from_code() {
+        assert_eq!(Language::from_code("nb-NO"), Some(Language::Norwegian));
+        assert_eq!(Language::from_code("de_CH"), Some(Language::German));
+        assert_eq!(Language::from_code("EN"), Some(Language::English));
+        assert_eq!(Language::from_code("fr-FR"), None);
+    }
+}
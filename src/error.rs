@@ -4,6 +4,71 @@
 
 use thiserror::Error;
 
+use crate::auth::challenge::StepUp;
+use crate::pim::policy::PolicyRule;
+
+/// What the user can do about an error; decides how the error menu looks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Passes on its own, e.g. a network blip or a throttled request; retried automatically.
+    Transient,
+    /// The session can't go on; signing in again fixes it.
+    #[default]
+    NeedsReauth,
+    /// Only an administrator can fix it, e.g. a missing consent or redirect URI.
+    NeedsAdmin { code: &'static str },
+    /// A bug in the app.
+    Bug { code: &'static str },
+}
+
+impl ErrorClass {
+    /// Classify the first error in the chain this app knows. Unknown errors
+    /// need a new sign-in, as before errors were classified.
+    pub fn of(e: &anyhow::Error) -> Self {
+        e.chain()
+            .find_map(|cause| {
+                if let Some(e) = cause.downcast_ref::<AppError>() {
+                    Some(e.class())
+                } else if let Some(e) = cause.downcast_ref::<AuthError>() {
+                    Some(e.class())
+                } else if let Some(e) = cause.downcast_ref::<ApiError>() {
+                    Some(e.class())
+                } else if let Some(e) = cause.downcast_ref::<PimError>() {
+                    Some(e.class())
+                } else if cause.is::<KeychainError>() {
+                    Some(Self::NeedsReauth)
+                } else if cause.is::<AudienceError>() {
+                    Some(Self::Bug {
+                        code: "API-AUDIENCE",
+                    })
+                } else if cause.is::<AuthTransitionError>() {
+                    Some(Self::Bug { code: "APP-STATE" })
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Heading of the error menu.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Transient => "Temporarily Unavailable",
+            Self::NeedsReauth => "Authentication Failed",
+            Self::NeedsAdmin { .. } => "Setup Required",
+            Self::Bug { .. } => "Something Went Wrong",
+        }
+    }
+
+    /// Code to quote to IT or in a bug report, if the user can't fix the error.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            Self::NeedsAdmin { code } | Self::Bug { code } => Some(code),
+            Self::Transient | Self::NeedsReauth => None,
+        }
+    }
+}
+
 /// Top-level application error type.
 #[derive(Error, Debug)]
 #[allow(dead_code)]
@@ -37,6 +102,9 @@ pub enum AuthError {
     #[error("OAuth2 authorization failed: {0}")]
     OAuthFailed(String),
 
+    #[error("Redirect URI not registered for the app: {0}")]
+    RedirectUriMismatch(String),
+
     #[error("Invalid authorization code")]
     InvalidAuthCode,
 
@@ -57,6 +125,15 @@ pub enum AuthError {
 
     #[error("User cancelled authentication")]
     UserCancelled,
+
+    #[error("Azure AD unreachable: {0}")]
+    Network(String),
+
+    #[error("Azure AD temporarily unavailable: {0}")]
+    TemporarilyUnavailable(String),
+
+    #[error("Azure Service Management permission not granted: {0}")]
+    ArmPermissionMissing(String),
 }
 
 /// Keychain storage errors.
@@ -73,6 +150,76 @@ pub enum KeychainError {
 
     #[error("Token not found in keychain")]
     NotFound,
+
+    #[error("Keychain access was cancelled or denied by the user")]
+    UserCancelled,
+
+    #[error("Keychain access requires user interaction, which is not allowed right now")]
+    InteractionNotAllowed,
+}
+
+impl AuthError {
+    /// What the user can do about this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::Network(_) | Self::TemporarilyUnavailable(_) => ErrorClass::Transient,
+            Self::RedirectUriMismatch(_) => ErrorClass::NeedsAdmin {
+                code: "AUTH-REDIRECT-URI",
+            },
+            Self::ArmPermissionMissing(_) => ErrorClass::NeedsAdmin {
+                code: "AUTH-ARM-CONSENT",
+            },
+            Self::PkceGenerationFailed => ErrorClass::Bug { code: "AUTH-PKCE" },
+            Self::OAuthFailed(_)
+            | Self::InvalidAuthCode
+            | Self::TokenExchangeFailed(_)
+            | Self::TokenRefreshFailed(_)
+            | Self::StateValidationFailed
+            | Self::CallbackTimeout
+            | Self::UserCancelled => ErrorClass::NeedsReauth,
+        }
+    }
+}
+
+impl KeychainError {
+    /// Returns true if the user may grant access later, so credentials must not be discarded.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::UserCancelled | Self::InteractionNotAllowed)
+    }
+
+    /// Returns a user-friendly message for display in the UI.
+    pub fn user_message(&self) -> &str {
+        match self {
+            Self::StoreFailed(_) => "Failed to save credentials securely.",
+            Self::NotFound => "No saved session found.",
+            Self::UserCancelled => "Keychain access was denied. Allow access and try again.",
+            Self::InteractionNotAllowed => "Keychain is locked. Unlock your Mac and try again.",
+            _ => "Keychain error. Please try again.",
+        }
+    }
+}
+
+/// An access token was issued for a different API than the one it is sent to.
+///
+/// This is a bug in the app, not something the user can fix.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Access token audience mismatch: expected {expected}, got {actual}")]
+pub struct AudienceError {
+    pub expected: &'static str,
+    pub actual: String,
+}
+
+/// A role scope path that doesn't match any known ARM scope layout.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unrecognized scope: {0}")]
+pub struct ScopeParseError(pub String);
+
+/// An auth state change that isn't allowed from the current state.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid auth transition from {from} on {event}")]
+pub struct AuthTransitionError {
+    pub from: &'static str,
+    pub event: &'static str,
 }
 
 /// API-related errors.
@@ -92,6 +239,26 @@ pub enum ApiError {
 
     #[error("Rate limited (429): Too many requests")]
     RateLimited,
+
+    #[error(transparent)]
+    WrongAudience(#[from] AudienceError),
+}
+
+impl ApiError {
+    /// What the user can do about this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::GraphRequestFailed(_) | Self::RateLimited => ErrorClass::Transient,
+            Self::Unauthorized => ErrorClass::NeedsReauth,
+            Self::Forbidden => ErrorClass::NeedsAdmin {
+                code: "API-FORBIDDEN",
+            },
+            Self::ParseFailed(_) => ErrorClass::Bug { code: "API-PARSE" },
+            Self::WrongAudience(_) => ErrorClass::Bug {
+                code: "API-AUDIENCE",
+            },
+        }
+    }
 }
 
 /// PIM (Privileged Identity Management) errors.
@@ -110,6 +277,27 @@ pub enum PimError {
     #[error("Role is already active")]
     RoleAlreadyActive,
 
+    #[error("Role deactivation failed: {0}")]
+    DeactivationFailed(String),
+
+    #[error("Role activation was not provisioned: {0}")]
+    ProvisioningFailed(String),
+
+    #[error("Role activation is waiting for approval: {0}")]
+    ApprovalRequired(String),
+
+    #[error("Approval review failed: {0}")]
+    ReviewFailed(String),
+
+    #[error("Cancelling the request failed: {0}")]
+    CancelFailed(String),
+
+    #[error("Role policy not satisfied: {0}")]
+    PolicyViolation(PolicyRule),
+
+    #[error("Additional verification required: {0}")]
+    StepUpRequired(StepUp),
+
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
 
@@ -122,6 +310,9 @@ pub enum PimError {
     #[error("Forbidden (insufficient permissions)")]
     Forbidden,
 
+    #[error(transparent)]
+    WrongAudience(#[from] AudienceError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -135,26 +326,96 @@ impl PimError {
             Self::ActivationFailed(_) => "Failed to activate role. Please try again.",
             Self::RoleNotFound(_) => "Role not found. Try refreshing the role list.",
             Self::RoleAlreadyActive => "This role is already active.",
+            Self::DeactivationFailed(_) => {
+                "Failed to deactivate role. It stays active until it expires."
+            }
+            Self::ProvisioningFailed(_) => {
+                "Azure accepted the request but did not activate the role."
+            }
+            Self::ApprovalRequired(_) => "The request was sent to an approver.",
+            Self::ReviewFailed(_) => "Failed to submit the review. Please try again.",
+            Self::CancelFailed(_) => "Failed to cancel the scheduled activation. Please try again.",
+            Self::PolicyViolation(_) => "The role's activation policy was not met.",
+            Self::StepUpRequired(_) => "Azure requires you to verify your identity again.",
             Self::Network(_) => "Network error. Check your connection.",
             Self::InvalidResponse(_) => "Unexpected response from Azure. Please try again.",
             Self::Unauthorized => "Session expired. Please sign in again.",
             Self::Forbidden => "Insufficient permissions for this operation.",
+            Self::WrongAudience(_) => "Internal error: wrong access token. Please report this.",
             Self::Io(_) => "Failed to save settings.",
         }
     }
 
+    /// What the user can do about this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::PermissionDenied(_) => ErrorClass::NeedsAdmin {
+                code: "PIM-PERMISSION",
+            },
+            Self::Forbidden => ErrorClass::NeedsAdmin {
+                code: "PIM-FORBIDDEN",
+            },
+            Self::Unauthorized | Self::StepUpRequired(_) => ErrorClass::NeedsReauth,
+            Self::InvalidResponse(_) => ErrorClass::Bug {
+                code: "PIM-RESPONSE",
+            },
+            Self::WrongAudience(_) => ErrorClass::Bug {
+                code: "PIM-AUDIENCE",
+            },
+            Self::ActivationFailed(_)
+            | Self::RoleNotFound(_)
+            | Self::RoleAlreadyActive
+            | Self::DeactivationFailed(_)
+            | Self::ProvisioningFailed(_)
+            | Self::ApprovalRequired(_)
+            | Self::ReviewFailed(_)
+            | Self::CancelFailed(_)
+            | Self::PolicyViolation(_)
+            | Self::Network(_)
+            | Self::Io(_) => ErrorClass::Transient,
+        }
+    }
+
+    /// Whether Azure could not be reached at all, as opposed to an API error.
+    pub fn is_offline(&self) -> bool {
+        matches!(self, Self::Network(e) if e.is_connect() || e.is_timeout())
+    }
+
     /// Returns true if this error should trigger a sign-out.
     pub fn requires_sign_out(&self) -> bool {
         matches!(self, Self::Unauthorized)
     }
+
+    /// Whether Azure refused to extend an activation, so a back-to-back one may work instead.
+    pub fn is_extension_refused(&self) -> bool {
+        matches!(
+            self,
+            Self::PolicyViolation(_) | Self::ActivationFailed(_) | Self::RoleAlreadyActive
+        )
+    }
 }
 
 impl AppError {
+    /// What the user can do about this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Self::Auth(e) => e.class(),
+            Self::Keychain(_) => ErrorClass::NeedsReauth,
+            Self::Api(e) => e.class(),
+            Self::Pim(e) => e.class(),
+            Self::Config(_) => ErrorClass::NeedsAdmin { code: "APP-CONFIG" },
+            Self::Network(_) | Self::Io(_) => ErrorClass::Transient,
+        }
+    }
+
     /// Returns a user-friendly message for display in the UI.
     #[allow(dead_code)]
     pub fn user_message(&self) -> &str {
         match self {
             Self::Auth(AuthError::OAuthFailed(_)) => "Sign-in failed. Please try again.",
+            Self::Auth(AuthError::RedirectUriMismatch(_)) => {
+                "Redirect URI is not registered in the Azure AD app."
+            }
             Self::Auth(AuthError::TokenRefreshFailed(_)) => {
                 "Session expired. Please sign in again."
             }
@@ -163,8 +424,7 @@ impl AppError {
             }
             Self::Auth(AuthError::CallbackTimeout) => "Sign-in timed out. Please try again.",
             Self::Auth(AuthError::UserCancelled) => "Sign-in was cancelled.",
-            Self::Keychain(KeychainError::StoreFailed(_)) => "Failed to save credentials securely.",
-            Self::Keychain(KeychainError::NotFound) => "No saved session found.",
+            Self::Keychain(e) => e.user_message(),
             Self::Api(ApiError::Unauthorized) => "Authentication expired. Sign in again.",
             Self::Api(ApiError::Forbidden) => "Insufficient permissions for this operation.",
             Self::Api(ApiError::RateLimited) => "Too many requests. Please wait a moment.",
@@ -208,4 +468,34 @@ mod tests {
         let err = AppError::Api(ApiError::Forbidden);
         assert!(!err.requires_sign_out());
     }
+
+    #[test]
+    fn test_error_class() {
+        let class = |e: anyhow::Error| ErrorClass::of(&e);
+        assert_eq!(
+            class(AuthError::TemporarilyUnavailable("503".into()).into()),
+            ErrorClass::Transient
+        );
+        assert_eq!(
+            class(AuthError::TokenRefreshFailed("invalid_grant".into()).into()),
+            ErrorClass::NeedsReauth
+        );
+        assert_eq!(
+            class(PimError::PermissionDenied("no access".into()).into()),
+            ErrorClass::NeedsAdmin {
+                code: "PIM-PERMISSION"
+            }
+        );
+        assert_eq!(
+            class(AppError::Api(ApiError::ParseFailed("eof".into())).into()),
+            ErrorClass::Bug { code: "API-PARSE" }
+        );
+
+        // Context doesn't hide the cause; unknown errors need a new sign-in
+        let wrapped = anyhow::Error::from(AuthError::RedirectUriMismatch("uri".into()))
+            .context("Sign-in failed");
+        assert_eq!(class(wrapped).code(), Some("AUTH-REDIRECT-URI"));
+        assert_eq!(class(anyhow::anyhow!("boom")), ErrorClass::NeedsReauth);
+        assert_eq!(ErrorClass::NeedsReauth.code(), None);
+    }
 }
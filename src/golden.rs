@@ -0,0 +1,63 @@
+//! Golden-file checks for recorded Azure API payloads.
+//!
+//! `fixtures/` holds sanitized ARM and Graph responses. A test parses one
+//! into the struct the app deserializes it into and compares the
+//! pretty-printed result with the `.golden` file next to the payload, so a
+//! change in what is read from a response shows up as a diff. After an
+//! intended change, run the tests with `UPDATE_GOLDEN=1` and review the
+//! rewritten golden files.
+//!
+//! The same files hold text snapshots, such as the menu outlines in
+//! `fixtures/menu/`.
+//!
+//! Both the library's and the app's tests include this module, and each
+//! uses only part of it.
+
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+/// Path of a file in `fixtures/`, e.g. `arm/subscriptions.json`.
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name)
+}
+
+/// Contents of a fixture payload.
+pub fn fixture(name: &str) -> String {
+    let path = fixture_path(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Compare `actual` with the golden file of fixture `name`.
+pub fn assert_golden(name: &str, actual: &impl Debug) {
+    assert_golden_text(name, &format!("{:#?}\n", actual));
+}
+
+/// Compare the text `actual` with the golden file of fixture `name`.
+pub fn assert_golden_text(name: &str, actual: &str) {
+    let path = fixture_path(name).with_extension("golden");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {} (run with UPDATE_GOLDEN=1 to create it)",
+            path.display(),
+            e
+        )
+    });
+    assert_eq!(actual, expected, "{} differs from its golden file", name);
+}
+
+/// Parse fixture `name` as `T` and compare the result with its golden file.
+pub fn assert_parses_to_golden<T: DeserializeOwned + Debug>(name: &str) {
+    let parsed: T = serde_json::from_str(&fixture(name))
+        .unwrap_or_else(|e| panic!("{} doesn't parse: {}", name, e));
+    assert_golden(name, &parsed);
+}
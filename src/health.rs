@@ -0,0 +1,166 @@
+//! On-demand health checks for the Status submenu.
+//!
+//! Each check covers one integration the app depends on, so a "nothing
+//! works" report can be narrowed down from the menu without reading logs.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use tracing::info;
+
+use crate::auth::callback_server::CALLBACK_PORT;
+use crate::error::KeychainError;
+use crate::keychain;
+
+/// Timeout for each reachability check.
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoints probed for reachability. Any HTTP response counts as reachable.
+const GRAPH_URL: &str = "https://graph.microsoft.com/v1.0/$metadata";
+const ARM_URL: &str = "https://management.azure.com/";
+
+/// Result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    /// Whether the integration works.
+    pub ok: bool,
+    /// What was checked and what was found, e.g. "Graph reachable".
+    pub summary: String,
+}
+
+impl HealthCheck {
+    fn pass(summary: impl Into<String>) -> Self {
+        Self {
+            ok: true,
+            summary: summary.into(),
+        }
+    }
+
+    fn fail(summary: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            summary: summary.into(),
+        }
+    }
+
+    /// Menu text with a check or cross mark, e.g. "Graph reachable ✓".
+    pub fn menu_text(&self) -> String {
+        format!("{} {}", self.summary, if self.ok { "✓" } else { "✗" })
+    }
+}
+
+/// Results of the last run of all checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub checks: Vec<HealthCheck>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl HealthReport {
+    /// When the checks ran, e.g. "Checked at 14:32".
+    pub fn checked_at_text(&self) -> String {
+        format!(
+            "Checked at {}",
+            self.checked_at.with_timezone(&Local).format("%H:%M")
+        )
+    }
+}
+
+/// Run all checks.
+///
+/// `callback_server_running` is true while a sign-in holds the callback port,
+/// which is expected and not a failure.
+pub async fn run_checks(
+    token_expiry: Option<DateTime<Utc>>,
+    callback_server_running: bool,
+) -> HealthReport {
+    let http_client = reqwest::Client::builder()
+        .timeout(REACHABILITY_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let (graph, arm) = tokio::join!(
+        check_reachable(&http_client, "Graph", GRAPH_URL),
+        check_reachable(&http_client, "ARM", ARM_URL),
+    );
+
+    let checks = vec![
+        graph,
+        arm,
+        check_token(token_expiry, Utc::now()),
+        check_callback_port(CALLBACK_PORT, callback_server_running),
+        check_keychain(),
+    ];
+    let failed = checks.iter().filter(|c| !c.ok).count();
+    info!("Health checks done, {} of {} failed", failed, checks.len());
+
+    HealthReport {
+        checks,
+        checked_at: Utc::now(),
+    }
+}
+
+async fn check_reachable(http_client: &reqwest::Client, name: &str, url: &str) -> HealthCheck {
+    match http_client.get(url).send().await {
+        Ok(_) => HealthCheck::pass(format!("{} reachable", name)),
+        Err(e) if e.is_timeout() => HealthCheck::fail(format!("{} timed out", name)),
+        Err(_) => HealthCheck::fail(format!("{} unreachable", name)),
+    }
+}
+
+fn check_token(expiry: Option<DateTime<Utc>>, now: DateTime<Utc>) -> HealthCheck {
+    match expiry {
+        Some(expiry) if expiry > now => HealthCheck::pass(format!(
+            "Token valid until {}",
+            expiry.with_timezone(&Local).format("%H:%M")
+        )),
+        Some(_) => HealthCheck::fail("Token expired"),
+        None => HealthCheck::fail("No token"),
+    }
+}
+
+fn check_callback_port(port: u16, callback_server_running: bool) -> HealthCheck {
+    if callback_server_running {
+        return HealthCheck::pass(format!("Callback port {} in use by sign-in", port));
+    }
+    match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(_) => HealthCheck::pass(format!("Callback port {} free", port)),
+        Err(_) => HealthCheck::fail(format!("Callback port {} taken by another app", port)),
+    }
+}
+
+fn check_keychain() -> HealthCheck {
+    match keychain::get_token_expiry() {
+        Ok(_) | Err(KeychainError::NotFound) => HealthCheck::pass("Keychain accessible"),
+        Err(e) => HealthCheck::fail(format!("Keychain: {}", e.user_message())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_token() {
+        let now = Utc::now();
+        assert!(check_token(Some(now + chrono::Duration::minutes(5)), now).ok);
+        assert_eq!(
+            check_token(Some(now - chrono::Duration::minutes(5)), now),
+            HealthCheck::fail("Token expired")
+        );
+        assert_eq!(check_token(None, now).menu_text(), "No token ✗");
+    }
+
+    #[test]
+    fn test_check_callback_port() {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(
+            check_callback_port(port, false).menu_text(),
+            format!("Callback port {} taken by another app ✗", port)
+        );
+        assert!(check_callback_port(port, true).ok);
+
+        drop(listener);
+        assert!(check_callback_port(port, false).ok);
+    }
+}
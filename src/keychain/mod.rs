@@ -1,13 +1,19 @@
 //! macOS Keychain integration for secure token storage.
 //!
-//! Stores access tokens, refresh tokens, and user info in the macOS Keychain.
+//! Stores access tokens, refresh tokens, and user info in the macOS Keychain,
+//! or in a [`CredentialStore`] set with [`set_store`].
 
 pub mod secure;
 
+use std::collections::HashMap;
+use std::sync::Mutex;
+
 use crate::error::KeychainError;
+use once_cell::sync::OnceCell;
 use security_framework::passwords::{
     delete_generic_password, get_generic_password, set_generic_password,
 };
+use tracing::warn;
 use zeroize::Zeroizing;
 
 /// Keychain service identifier.
@@ -19,23 +25,94 @@ const ACCOUNT_REFRESH_TOKEN: &str = "azure_refresh_token";
 const ACCOUNT_USER_INFO: &str = "azure_user_info";
 const ACCOUNT_TOKEN_EXPIRY: &str = "azure_token_expiry";
 
+/// Storage for the items, by account name.
+pub trait CredentialStore: Send + Sync {
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), KeychainError>;
+    /// Returns [`KeychainError::NotFound`] for a missing item.
+    fn get(&self, account: &str) -> Result<Vec<u8>, KeychainError>;
+    /// Returns [`KeychainError::NotFound`] for a missing item.
+    fn delete(&self, account: &str) -> Result<(), KeychainError>;
+}
+
+/// The login Keychain.
+pub struct Keychain;
+
+impl CredentialStore for Keychain {
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), KeychainError> {
+        set_generic_password(SERVICE, account, secret)
+            .map_err(|e| map_error(e, KeychainError::StoreFailed))
+    }
+
+    fn get(&self, account: &str) -> Result<Vec<u8>, KeychainError> {
+        get_generic_password(SERVICE, account)
+            .map_err(|e| map_error(e, KeychainError::RetrieveFailed))
+    }
+
+    fn delete(&self, account: &str) -> Result<(), KeychainError> {
+        delete_generic_password(SERVICE, account)
+            .map_err(|e| map_error(e, KeychainError::DeleteFailed))
+    }
+}
+
+/// Items kept in memory for the life of the process, for tests (also of code
+/// embedding the crate).
+#[derive(Default)]
+pub struct MemoryStore {
+    items: Mutex<HashMap<String, Zeroizing<Vec<u8>>>>,
+}
+
+impl CredentialStore for MemoryStore {
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), KeychainError> {
+        self.items
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), Zeroizing::new(secret.to_vec()));
+        Ok(())
+    }
+
+    fn get(&self, account: &str) -> Result<Vec<u8>, KeychainError> {
+        self.items
+            .lock()
+            .unwrap()
+            .get(account)
+            .map(|secret| secret.to_vec())
+            .ok_or(KeychainError::NotFound)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), KeychainError> {
+        self.items
+            .lock()
+            .unwrap()
+            .remove(account)
+            .map(|_| ())
+            .ok_or(KeychainError::NotFound)
+    }
+}
+
+/// Store used by this process, the Keychain unless set earlier.
+static STORE: OnceCell<Box<dyn CredentialStore>> = OnceCell::new();
+
+/// Keep the items in `store`. Has no effect once the store is in use.
+pub fn set_store(store: Box<dyn CredentialStore>) {
+    if STORE.set(store).is_err() {
+        warn!("Credential store already in use, ignoring the new one");
+    }
+}
+
+fn store() -> &'static dyn CredentialStore {
+    STORE.get_or_init(|| Box::new(Keychain)).as_ref()
+}
+
 /// Store the access token in the Keychain.
 pub fn store_access_token(token: &str) -> Result<(), KeychainError> {
-    set_generic_password(SERVICE, ACCOUNT_ACCESS_TOKEN, token.as_bytes())
-        .map_err(|e| KeychainError::StoreFailed(e.to_string()))
+    store().set(ACCOUNT_ACCESS_TOKEN, token.as_bytes())
 }
 
 /// Retrieve the access token from the Keychain.
 ///
 /// Returns a `Zeroizing<String>` that will be securely cleared when dropped.
 pub fn get_access_token() -> Result<Zeroizing<String>, KeychainError> {
-    let bytes = get_generic_password(SERVICE, ACCOUNT_ACCESS_TOKEN).map_err(|e| {
-        if is_not_found_error(&e) {
-            KeychainError::NotFound
-        } else {
-            KeychainError::RetrieveFailed(e.to_string())
-        }
-    })?;
+    let bytes = store().get(ACCOUNT_ACCESS_TOKEN)?;
 
     let token =
         String::from_utf8(bytes).map_err(|e| KeychainError::RetrieveFailed(e.to_string()))?;
@@ -45,21 +122,14 @@ pub fn get_access_token() -> Result<Zeroizing<String>, KeychainError> {
 
 /// Store the refresh token in the Keychain.
 pub fn store_refresh_token(token: &str) -> Result<(), KeychainError> {
-    set_generic_password(SERVICE, ACCOUNT_REFRESH_TOKEN, token.as_bytes())
-        .map_err(|e| KeychainError::StoreFailed(e.to_string()))
+    store().set(ACCOUNT_REFRESH_TOKEN, token.as_bytes())
 }
 
 /// Retrieve the refresh token from the Keychain.
 ///
 /// Returns a `Zeroizing<String>` that will be securely cleared when dropped.
 pub fn get_refresh_token() -> Result<Zeroizing<String>, KeychainError> {
-    let bytes = get_generic_password(SERVICE, ACCOUNT_REFRESH_TOKEN).map_err(|e| {
-        if is_not_found_error(&e) {
-            KeychainError::NotFound
-        } else {
-            KeychainError::RetrieveFailed(e.to_string())
-        }
-    })?;
+    let bytes = store().get(ACCOUNT_REFRESH_TOKEN)?;
 
     let token =
         String::from_utf8(bytes).map_err(|e| KeychainError::RetrieveFailed(e.to_string()))?;
@@ -69,40 +139,26 @@ pub fn get_refresh_token() -> Result<Zeroizing<String>, KeychainError> {
 
 /// Store the token expiry timestamp (ISO 8601 format).
 pub fn store_token_expiry(expiry: &str) -> Result<(), KeychainError> {
-    set_generic_password(SERVICE, ACCOUNT_TOKEN_EXPIRY, expiry.as_bytes())
-        .map_err(|e| KeychainError::StoreFailed(e.to_string()))
+    store().set(ACCOUNT_TOKEN_EXPIRY, expiry.as_bytes())
 }
 
 /// Retrieve the token expiry timestamp.
 #[allow(dead_code)]
 pub fn get_token_expiry() -> Result<String, KeychainError> {
-    let bytes = get_generic_password(SERVICE, ACCOUNT_TOKEN_EXPIRY).map_err(|e| {
-        if is_not_found_error(&e) {
-            KeychainError::NotFound
-        } else {
-            KeychainError::RetrieveFailed(e.to_string())
-        }
-    })?;
+    let bytes = store().get(ACCOUNT_TOKEN_EXPIRY)?;
 
     String::from_utf8(bytes).map_err(|e| KeychainError::RetrieveFailed(e.to_string()))
 }
 
 /// Store user info JSON in the Keychain.
 pub fn store_user_info(json: &str) -> Result<(), KeychainError> {
-    set_generic_password(SERVICE, ACCOUNT_USER_INFO, json.as_bytes())
-        .map_err(|e| KeychainError::StoreFailed(e.to_string()))
+    store().set(ACCOUNT_USER_INFO, json.as_bytes())
 }
 
 /// Retrieve user info JSON from the Keychain.
 #[allow(dead_code)]
 pub fn get_user_info() -> Result<String, KeychainError> {
-    let bytes = get_generic_password(SERVICE, ACCOUNT_USER_INFO).map_err(|e| {
-        if is_not_found_error(&e) {
-            KeychainError::NotFound
-        } else {
-            KeychainError::RetrieveFailed(e.to_string())
-        }
-    })?;
+    let bytes = store().get(ACCOUNT_USER_INFO)?;
 
     String::from_utf8(bytes).map_err(|e| KeychainError::RetrieveFailed(e.to_string()))
 }
@@ -113,18 +169,17 @@ pub fn get_user_info() -> Result<String, KeychainError> {
 pub fn delete_all() -> Result<(), KeychainError> {
     // Delete each item, ignoring "not found" errors
     let results = [
-        delete_generic_password(SERVICE, ACCOUNT_ACCESS_TOKEN),
-        delete_generic_password(SERVICE, ACCOUNT_REFRESH_TOKEN),
-        delete_generic_password(SERVICE, ACCOUNT_USER_INFO),
-        delete_generic_password(SERVICE, ACCOUNT_TOKEN_EXPIRY),
+        store().delete(ACCOUNT_ACCESS_TOKEN),
+        store().delete(ACCOUNT_REFRESH_TOKEN),
+        store().delete(ACCOUNT_USER_INFO),
+        store().delete(ACCOUNT_TOKEN_EXPIRY),
     ];
 
     // Check if any deletion failed (other than "not found")
     for result in results {
-        if let Err(e) = result {
-            if !is_not_found_error(&e) {
-                return Err(KeychainError::DeleteFailed(e.to_string()));
-            }
+        match result {
+            Ok(()) | Err(KeychainError::NotFound) => {}
+            Err(e) => return Err(e),
         }
     }
 
@@ -137,16 +192,66 @@ pub fn has_tokens() -> bool {
     get_access_token().is_ok() || get_refresh_token().is_ok()
 }
 
-/// Helper to check if a security framework error is "item not found".
-fn is_not_found_error(error: &security_framework::base::Error) -> bool {
-    // errSecItemNotFound = -25300
-    error.code() == -25300
+/// errSecUserCanceled: the user cancelled the Keychain prompt.
+const ERR_SEC_USER_CANCELED: i32 = -128;
+/// errSecAuthFailed: the user denied access or entered a wrong password.
+const ERR_SEC_AUTH_FAILED: i32 = -25293;
+/// errSecItemNotFound: no matching item in the Keychain.
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+/// errSecInteractionNotAllowed: a prompt is needed but can't be shown (e.g. screen locked).
+const ERR_SEC_INTERACTION_NOT_ALLOWED: i32 = -25308;
+
+/// Map a security framework error to a `KeychainError`.
+///
+/// Errors without a dedicated variant are wrapped with `fallback`.
+fn map_error(
+    error: security_framework::base::Error,
+    fallback: fn(String) -> KeychainError,
+) -> KeychainError {
+    match error.code() {
+        ERR_SEC_ITEM_NOT_FOUND => KeychainError::NotFound,
+        ERR_SEC_USER_CANCELED | ERR_SEC_AUTH_FAILED => KeychainError::UserCancelled,
+        ERR_SEC_INTERACTION_NOT_ALLOWED => KeychainError::InteractionNotAllowed,
+        _ => fallback(error.to_string()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_map_error_user_interaction() {
+        use security_framework::base::Error;
+
+        let err = map_error(Error::from_code(-25293), KeychainError::RetrieveFailed);
+        assert!(matches!(err, KeychainError::UserCancelled));
+        assert!(err.is_retryable());
+
+        let err = map_error(Error::from_code(-128), KeychainError::RetrieveFailed);
+        assert!(matches!(err, KeychainError::UserCancelled));
+
+        let err = map_error(Error::from_code(-25308), KeychainError::StoreFailed);
+        assert!(matches!(err, KeychainError::InteractionNotAllowed));
+        assert!(err.is_retryable());
+
+        let err = map_error(Error::from_code(-25300), KeychainError::RetrieveFailed);
+        assert!(matches!(err, KeychainError::NotFound));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::default();
+        assert!(matches!(store.get("a"), Err(KeychainError::NotFound)));
+
+        store.set("a", b"secret").unwrap();
+        assert_eq!(store.get("a").unwrap(), b"secret");
+
+        store.delete("a").unwrap();
+        assert!(matches!(store.delete("a"), Err(KeychainError::NotFound)));
+    }
+
     // Note: These tests require Keychain access and may prompt for permission
 
     #[test]
@@ -0,0 +1,55 @@
+//! Azure PIM - sign-in and Privileged Identity Management for Azure.
+//!
+//! The core of the menu bar app, for tools that need just-in-time elevation
+//! without the app, such as CI jobs and bots. A minimal elevation:
+//!
+//! ```no_run
+//! use azurepim::config::RuntimeConfig;
+//! use azurepim::{ActivationRequest, PimClient, StaticToken, TokenProvider};
+//!
+//! # async fn elevate(user_id: String) -> anyhow::Result<()> {
+//! let tokens = StaticToken::new(std::env::var("ARM_TOKEN")?);
+//! let client = PimClient::new(&RuntimeConfig::default())?;
+//! let token = tokens.management_token().await?;
+//! let scan = client.get_all_eligible_roles(&token, &[user_id], &[]).await?;
+//! if let Some(role) = scan.roles.into_iter().find(|r| r.role_name == "Reader") {
+//!     let request = ActivationRequest {
+//!         eligible_role: role,
+//!         justification: "Deploy from CI".to_string(),
+//!         duration_minutes: 60,
+//!         ticket_number: None,
+//!         start_time: None,
+//!     };
+//!     client.activate_role(&token, request).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Stability
+//!
+//! The items re-exported here follow semver: [`PimClient`],
+//! [`OAuth2Client`], [`TokenProvider`] and its implementations, the models
+//! and the error types. The modules also serve the menu bar app; anything
+//! reached only through them may change in a minor release.
+
+#![deny(clippy::all)]
+
+pub mod api;
+pub mod auth;
+pub mod clock;
+pub mod config;
+pub mod duration;
+pub mod error;
+#[cfg(test)]
+mod golden;
+pub mod keychain;
+pub mod pim;
+
+pub use auth::oauth::{OAuth2Client, TokenResponse};
+pub use auth::{RefreshTokenProvider, StaticToken, TokenProvider};
+pub use clock::{Clock, SystemClock};
+pub use config::Config;
+pub use error::{ApiError, AuthError, KeychainError, PimError};
+pub use pim::client::EligibilityScan;
+pub use pim::{ActivationRequest, ActiveAssignment, EligibleRole, PimClient, Scope, Subscription};
@@ -0,0 +1,219 @@
+//! Throttling of repeated warnings within an operation.
+//!
+//! A failing tenant scan can log the same warning for every subscription and
+//! principal, on every refresh. Within an `operation` span, only the first
+//! warning per call site and `subscription` + `error` fields (or message, for
+//! warnings without them) is logged; repeats are counted and summarized in
+//! one line when the operation finishes.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::OnceCell;
+use tracing::callsite::Identifier;
+use tracing::field::{Field, Visit};
+use tracing::span::Id;
+use tracing::{Event, Level, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the spans warnings are throttled in.
+const OPERATION_SPAN: &str = "operation";
+
+/// Repeats of the installed layer, for operations to summarize.
+static INSTALLED: OnceCell<Repeats> = OnceCell::new();
+
+/// A warning logged once and then suppressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    /// Message of the warning that was logged.
+    pub message: String,
+    /// How many like it were not.
+    pub suppressed: usize,
+}
+
+/// Call site and what the warning is about.
+type WarningKey = (Identifier, String);
+
+/// Warnings seen in each open operation span.
+#[derive(Clone, Default)]
+pub struct Repeats(Arc<Mutex<HashMap<u64, HashMap<WarningKey, Repeat>>>>);
+
+impl Repeats {
+    /// Take the suppressed repeats of the operation in `span`, by message.
+    pub fn take(&self, span: &Span) -> Vec<Repeat> {
+        let Some(id) = span.id() else {
+            return Vec::new();
+        };
+        let Some(seen) = self
+            .0
+            .lock()
+            .ok()
+            .and_then(|mut ops| ops.remove(&id.into_u64()))
+        else {
+            return Vec::new();
+        };
+        let mut repeats: Vec<Repeat> = seen.into_values().filter(|r| r.suppressed > 0).collect();
+        repeats.sort_by(|a, b| a.message.cmp(&b.message));
+        repeats
+    }
+}
+
+/// A layer dropping warnings already logged in the same operation.
+#[derive(Default)]
+pub struct ThrottleLayer {
+    repeats: Repeats,
+}
+
+impl ThrottleLayer {
+    /// A layer whose repeats [`take_repeats`] reports, for the app's subscriber.
+    pub fn install() -> Self {
+        let layer = Self::default();
+        // Ignored if already set; only one subscriber is installed
+        let _ = INSTALLED.set(layer.repeats());
+        layer
+    }
+
+    /// The warnings this layer has suppressed.
+    pub fn repeats(&self) -> Repeats {
+        self.repeats.clone()
+    }
+}
+
+impl<S> Layer<S> for ThrottleLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        if *event.metadata().level() != Level::WARN {
+            return true;
+        }
+        let Some(operation) = ctx
+            .event_scope(event)
+            .and_then(|mut scope| scope.find(|span| span.name() == OPERATION_SPAN))
+        else {
+            return true;
+        };
+
+        let mut fields = WarningFields::default();
+        event.record(&mut fields);
+        let key = (event.metadata().callsite(), fields.subject());
+        let Ok(mut ops) = self.repeats.0.lock() else {
+            return true;
+        };
+        match ops.entry(operation.id().into_u64()).or_default().entry(key) {
+            Entry::Occupied(mut seen) => {
+                seen.get_mut().suppressed += 1;
+                false
+            }
+            Entry::Vacant(first) => {
+                first.insert(Repeat {
+                    message: fields.message,
+                    suppressed: 0,
+                });
+                true
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        // Span IDs are reused once closed
+        if let Ok(mut ops) = self.repeats.0.lock() {
+            ops.remove(&id.into_u64());
+        }
+    }
+}
+
+/// Take the warnings suppressed so far in the operation in `span`.
+pub fn take_repeats(span: &Span) -> Vec<Repeat> {
+    INSTALLED
+        .get()
+        .map(|repeats| repeats.take(span))
+        .unwrap_or_default()
+}
+
+/// The fields of a warning that tell repeats apart.
+#[derive(Default)]
+struct WarningFields {
+    message: String,
+    subscription: Option<String>,
+    error: Option<String>,
+}
+
+impl WarningFields {
+    /// What the warning is about: its subscription and error if it has them,
+    /// otherwise its whole message.
+    fn subject(&self) -> String {
+        match (&self.subscription, &self.error) {
+            (None, None) => self.message.clone(),
+            (subscription, error) => format!("{:?}|{:?}", subscription, error),
+        }
+    }
+}
+
+impl Visit for WarningFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let value = format!("{:?}", value);
+        match field.name() {
+            "message" => self.message = value,
+            "subscription" => self.subscription = Some(value),
+            "error" => self.error = Some(value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{info_span, warn};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_repeats_are_logged_once_per_operation() {
+        let layer = ThrottleLayer::default();
+        let repeats = layer.repeats();
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let operation = info_span!("operation");
+            operation.in_scope(|| {
+                for principal in ["user", "group-1", "group-2"] {
+                    warn!(
+                        subscription = "sub-1",
+                        error = "HTTP 500",
+                        "Scan failed for {}",
+                        principal
+                    );
+                }
+                warn!(
+                    subscription = "sub-2",
+                    error = "HTTP 500",
+                    "Scan failed for user"
+                );
+                for _ in 0..2 {
+                    warn!("Role is already active");
+                }
+            });
+            // Outside an operation nothing is throttled
+            warn!("Role is already active");
+
+            assert_eq!(
+                repeats.take(&operation),
+                [
+                    Repeat {
+                        message: "Role is already active".to_string(),
+                        suppressed: 1,
+                    },
+                    Repeat {
+                        message: "Scan failed for user".to_string(),
+                        suppressed: 2,
+                    },
+                ]
+            );
+            assert!(repeats.take(&operation).is_empty());
+        });
+    }
+}
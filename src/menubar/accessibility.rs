@@ -0,0 +1,198 @@
+//! VoiceOver descriptions and announcements.
+//!
+//! Menu titles are written for the eye: progress bars, glyphs, and "1h 5m"
+//! abbreviations. The descriptions here say the same thing in words, and
+//! state changes the user didn't trigger from the menu are announced.
+
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2_app_kit::{
+    NSAccessibilityAnnouncementKey, NSAccessibilityAnnouncementRequestedNotification,
+    NSAccessibilityPostNotificationWithUserInfo, NSAccessibilityPriorityKey,
+    NSAccessibilityPriorityLevel, NSApplication,
+};
+use objc2_foundation::{MainThreadMarker, NSDictionary, NSNumber, NSString};
+use tracing::debug;
+
+use crate::clock::Clock;
+use crate::duration::{self, Style};
+use crate::menubar::state::{AuthEvent, AuthState};
+use crate::pim::ActiveAssignment;
+
+/// Description of the status item, e.g. "Azure PIM, 2 active privileged
+/// roles, earliest expires in 12 minutes".
+pub fn status_description(
+    auth_state: &AuthState,
+    assignments: &[ActiveAssignment],
+    clock: &dyn Clock,
+) -> String {
+    let session = match auth_state {
+        AuthState::SignedOut => return "Azure PIM, signed out".to_string(),
+        AuthState::Authenticating => return "Azure PIM, signing in".to_string(),
+        AuthState::Error { .. } => return "Azure PIM, authentication failed".to_string(),
+        AuthState::Offline => "Azure PIM, offline",
+        AuthState::SignedIn => "Azure PIM",
+    };
+
+    let live: Vec<_> = assignments
+        .iter()
+        .filter(|a| !a.is_expired(clock))
+        .collect();
+    let Some(earliest) = live.iter().map(|a| a.time_remaining(clock)).min() else {
+        return format!("{}, no active privileged roles", session);
+    };
+    format!(
+        "{}, {} active privileged {}, {} in {}",
+        session,
+        live.len(),
+        if live.len() == 1 { "role" } else { "roles" },
+        if live.len() == 1 {
+            "expires"
+        } else {
+            "earliest expires"
+        },
+        duration::format(earliest, Style::Long)
+    )
+}
+
+/// Description of an active role's menu item, e.g. "Contributor on
+/// vipps-prod-001, 1 hour 5 minutes left".
+pub fn assignment_description(assignment: &ActiveAssignment, clock: &dyn Clock) -> String {
+    let place = format!(
+        "{} on {}",
+        assignment.role_name, assignment.subscription_name
+    );
+    if assignment.is_expired(clock) {
+        return format!("{}, expired", place);
+    }
+    format!(
+        "{}, {} left",
+        place,
+        duration::format(assignment.time_remaining(clock), Style::Long)
+    )
+}
+
+/// What to announce when `event` changes the auth state, if anything.
+pub fn auth_announcement(event: &AuthEvent) -> Option<String> {
+    match event {
+        AuthEvent::SignedOut => Some("Signed out of Azure".to_string()),
+        AuthEvent::Authenticating => None,
+        AuthEvent::SignedIn { user_info, .. } => {
+            Some(format!("Signed in as {}", user_info.display_name))
+        }
+        AuthEvent::WentOffline { .. } => {
+            Some("Azure unreachable, showing cached roles".to_string())
+        }
+        AuthEvent::BackOnline | AuthEvent::Recovered => Some("Reconnected to Azure".to_string()),
+        AuthEvent::Failed { message, .. } => Some(format!("Authentication failed: {}", message)),
+    }
+}
+
+/// What changed between two sets of active roles, if anything.
+pub fn assignment_changes(old: &[ActiveAssignment], new: &[ActiveAssignment]) -> Option<String> {
+    let describe = |a: &ActiveAssignment| format!("{} on {}", a.role_name, a.subscription_name);
+    let activated: Vec<_> = new
+        .iter()
+        .filter(|a| !old.iter().any(|o| o.id == a.id))
+        .map(describe)
+        .collect();
+    let ended: Vec<_> = old
+        .iter()
+        .filter(|o| !new.iter().any(|a| a.id == o.id))
+        .map(describe)
+        .collect();
+
+    let mut parts = Vec::new();
+    if !activated.is_empty() {
+        parts.push(format!("{} now active", activated.join(", ")));
+    }
+    if !ended.is_empty() {
+        parts.push(format!("{} no longer active", ended.join(", ")));
+    }
+    (!parts.is_empty()).then(|| parts.join(". "))
+}
+
+/// Ask VoiceOver to read `text`. Does nothing when VoiceOver is off.
+pub fn announce(mtm: MainThreadMarker, text: &str) {
+    debug!("Accessibility announcement: {}", text);
+    let app = NSApplication::sharedApplication(mtm);
+    let priority = NSNumber::new_isize(NSAccessibilityPriorityLevel::NSAccessibilityPriorityHigh.0);
+    let text = NSString::from_str(text);
+    unsafe {
+        let keys = [NSAccessibilityAnnouncementKey, NSAccessibilityPriorityKey];
+        // The user info values are untyped in the AppKit signature
+        let values: Vec<Retained<AnyObject>> = vec![Retained::cast(text), Retained::cast(priority)];
+        let user_info = NSDictionary::from_vec(&keys, values);
+        NSAccessibilityPostNotificationWithUserInfo(
+            &app,
+            NSAccessibilityAnnouncementRequestedNotification,
+            Some(&user_info),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use chrono::{Duration, Utc};
+
+    fn assignment(id: &str, role_name: &str, minutes_left: i64) -> ActiveAssignment {
+        let now = Utc::now();
+        ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: role_name.to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            start_time: now - Duration::minutes(10),
+            end_time: now + Duration::minutes(minutes_left),
+            justification: "Debugging".to_string(),
+            assignment_request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_status_description() {
+        let clock = MockClock::new(Utc::now());
+        let roles = [
+            assignment("a", "Contributor", 65),
+            assignment("b", "Reader", 12),
+        ];
+        assert_eq!(
+            status_description(&AuthState::SignedIn, &roles, &clock),
+            "Azure PIM, 2 active privileged roles, earliest expires in 12 minutes"
+        );
+        assert_eq!(
+            status_description(&AuthState::Offline, &roles[..1], &clock),
+            "Azure PIM, offline, 1 active privileged role, expires in 1 hour 5 minutes"
+        );
+        assert_eq!(
+            status_description(&AuthState::SignedIn, &[], &clock),
+            "Azure PIM, no active privileged roles"
+        );
+        assert_eq!(
+            status_description(&AuthState::SignedOut, &roles, &clock),
+            "Azure PIM, signed out"
+        );
+    }
+
+    #[test]
+    fn test_assignment_descriptions() {
+        let clock = MockClock::new(Utc::now());
+        assert_eq!(
+            assignment_description(&assignment("a", "Contributor", 120), &clock),
+            "Contributor on vipps-prod-001, 2 hours left"
+        );
+
+        let old = [assignment("a", "Contributor", 60)];
+        let new = [assignment("b", "Reader", 60)];
+        assert_eq!(assignment_changes(&old, &old), None);
+        assert_eq!(auth_announcement(&AuthEvent::Authenticating), None);
+        assert_eq!(
+            assignment_changes(&old, &new).as_deref(),
+            Some("Reader on vipps-prod-001 now active. Contributor on vipps-prod-001 no longer active")
+        );
+    }
+}
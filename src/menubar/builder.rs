@@ -1,19 +1,50 @@
-//! Menu bar and menu construction using AppKit.
-
-use crate::menubar::delegate::{send_activate_role, send_toggle_favorite, MenuActionTarget};
-use crate::menubar::state::{get_app_state, AuthState, PimState};
-use crate::pim::{EligibleRole, JustificationPreset, PimApiStatus};
+//! Menu bar rendering with AppKit.
+//!
+//! Shows a [`MenuModel`] in the status item's menu and on the status items;
+//! what is shown is decided by the model.
+
+use crate::clock::SystemClock;
+use crate::menubar::delegate::{
+    request_activation, send_check_activation, send_toggle_favorite, sound_menu_tag,
+    MenuActionTarget,
+};
+use crate::menubar::model::{
+    Color, Command, MenuEntry, MenuItem, MenuModel, RoleStatusItem, Setting, StatusText, Tint,
+};
+use crate::menubar::state::get_app_state;
+use crate::pim::{ActivationOptions, ReviewResult};
+use chrono::Timelike;
 use objc2::rc::Retained;
-use objc2::runtime::Sel;
-use objc2::sel;
+use objc2::runtime::{NSObjectProtocol, Sel};
+use objc2::{sel, ClassType};
 use objc2_app_kit::{
-    NSControlStateValueOff, NSControlStateValueOn, NSImage, NSMenu, NSMenuItem, NSStatusBar,
-    NSStatusItem, NSVariableStatusItemLength,
+    NSAccessibility, NSCellImagePosition, NSColor, NSControlStateValueOn,
+    NSForegroundColorAttributeName, NSImage, NSMenu, NSMenuItem, NSMenuItemBadge, NSStatusBar,
+    NSStatusBarButton, NSStatusItem, NSVariableStatusItemLength,
 };
-use objc2_foundation::{MainThreadMarker, NSString};
+use objc2_foundation::{MainThreadMarker, NSMutableAttributedString, NSRange, NSString};
 use once_cell::sync::OnceCell;
 use std::sync::Mutex;
-use tracing::info;
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// SF Symbol used for the idle status item icon.
+const STATUS_ICON: &str = "lock.shield";
+
+/// SF Symbol briefly shown after a successful activation.
+const SUCCESS_ICON: &str = "checkmark.shield";
+
+/// How long the success icon stays visible.
+const SUCCESS_FLASH_DURATION: Duration = Duration::from_millis(1500);
+
+/// SF Symbol flashed to draw attention to a critical event.
+const ALERT_ICON: &str = "exclamationmark.shield";
+
+/// Number of times the alert icon is shown when drawing attention.
+const ATTENTION_FLASHES: u32 = 4;
+
+/// How long each alert or idle icon stays visible while drawing attention.
+const ATTENTION_FLASH_INTERVAL: Duration = Duration::from_millis(400);
 
 /// Global menu bar instance.
 static MENU_BAR: OnceCell<Mutex<MenuBarInner>> = OnceCell::new();
@@ -33,10 +64,11 @@ pub fn get_menu_bar() -> Option<&'static Mutex<MenuBarInner>> {
 
 /// Menu bar wrapper that holds the status item and menu.
 pub struct MenuBarInner {
-    /// Retained to keep the status item alive (never read, but must not be dropped).
-    _status_item: Retained<NSStatusItem>,
+    status_item: Retained<NSStatusItem>,
     pub menu: Retained<NSMenu>,
     pub action_target: Retained<MenuActionTarget>,
+    /// Extra status items for active roles, keyed by assignment ID.
+    role_items: HashMap<String, Retained<NSStatusItem>>,
 }
 
 // SAFETY: MenuBarInner is only accessed from the main thread via MainThreadMarker checks.
@@ -61,13 +93,7 @@ impl MenuBarInner {
             // Set the menu bar icon using SF Symbol
             if let Some(button) = status_item.button(mtm) {
                 // Use "lock.shield" SF Symbol - represents identity/authentication
-                let symbol_name = NSString::from_str("lock.shield");
-
-                if let Some(image) = NSImage::imageWithSymbolName_variableValue(&symbol_name, 1.0) {
-                    // Set as template so it adapts to dark/light menu bar
-                    image.setTemplate(true);
-                    button.setImage(Some(&image));
-                } else {
+                if !set_status_icon(&button, STATUS_ICON) {
                     // Fallback to text if SF Symbol not available
                     let title = NSString::from_str("🔐");
                     button.setTitle(&title);
@@ -81,14 +107,29 @@ impl MenuBarInner {
             status_item.setMenu(Some(&menu));
 
             Self {
-                _status_item: status_item,
+                status_item,
                 menu,
                 action_target,
+                role_items: HashMap::new(),
             }
         }
     }
 }
 
+/// Set the status item button image to an SF Symbol. Returns false if unavailable.
+unsafe fn set_status_icon(button: &NSStatusBarButton, symbol: &str) -> bool {
+    let symbol_name = NSString::from_str(symbol);
+    match NSImage::imageWithSymbolName_variableValue(&symbol_name, 1.0) {
+        Some(image) => {
+            // Set as template so it adapts to dark/light menu bar
+            image.setTemplate(true);
+            button.setImage(Some(&image));
+            true
+        }
+        None => false,
+    }
+}
+
 /// Public menu bar API.
 pub struct MenuBar;
 
@@ -98,257 +139,171 @@ impl MenuBar {
         init_menu_bar(mtm)
     }
 
-    /// Build the signed-out menu.
-    pub fn build_signed_out_menu(mtm: MainThreadMarker) {
-        if let Some(menu_bar) = get_menu_bar() {
-            let inner = menu_bar.lock().unwrap();
-            let menu = &inner.menu;
-            let target = Some(&*inner.action_target);
+    /// Briefly swap the status item icon to indicate success.
+    pub fn flash_success(mtm: MainThreadMarker) {
+        if !Self::set_icon(mtm, SUCCESS_ICON) {
+            return;
+        }
 
-            // Clear existing items
-            unsafe {
-                menu.removeAllItems();
+        dispatch::Queue::main().exec_after(SUCCESS_FLASH_DURATION, || {
+            if let Some(mtm) = MainThreadMarker::new() {
+                Self::set_icon(mtm, STATUS_ICON);
             }
+        });
+    }
 
-            // Sign In item
-            let sign_in_item =
-                create_menu_item(mtm, "Sign In to Azure", Some(sel!(signIn:)), target);
-            menu.addItem(&sign_in_item);
+    /// Flash the status item icon to draw attention to a critical event.
+    pub fn flash_attention(mtm: MainThreadMarker) {
+        Self::attention_step(mtm, ATTENTION_FLASHES * 2);
+    }
 
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
+    /// Show the alert icon on even steps and the idle icon on odd ones, ending idle.
+    fn attention_step(mtm: MainThreadMarker, remaining: u32) {
+        let symbol = if remaining.is_multiple_of(2) {
+            ALERT_ICON
+        } else {
+            STATUS_ICON
+        };
+        if !Self::set_icon(mtm, symbol) || remaining <= 1 {
+            return;
+        }
 
-            // Quit item
-            let quit_item = create_menu_item(mtm, "Quit", Some(sel!(terminate:)), None);
-            unsafe {
-                quit_item.setKeyEquivalent(&NSString::from_str("q"));
+        dispatch::Queue::main().exec_after(ATTENTION_FLASH_INTERVAL, move || {
+            if let Some(mtm) = MainThreadMarker::new() {
+                Self::attention_step(mtm, remaining - 1);
             }
-            menu.addItem(&quit_item);
+        });
+    }
 
-            info!("Built signed-out menu");
+    /// Set the status item icon. Returns false if it could not be set.
+    fn set_icon(mtm: MainThreadMarker, symbol: &str) -> bool {
+        let Some(menu_bar) = get_menu_bar() else {
+            return false;
+        };
+        let Ok(inner) = menu_bar.lock() else {
+            return false;
+        };
+        match unsafe { inner.status_item.button(mtm) } {
+            Some(button) => unsafe { set_status_icon(&button, symbol) },
+            None => false,
         }
     }
 
-    /// Build the authenticating menu.
-    pub fn build_authenticating_menu(mtm: MainThreadMarker) {
-        if let Some(menu_bar) = get_menu_bar() {
-            let inner = menu_bar.lock().unwrap();
-            let menu = &inner.menu;
-            let target = Some(&*inner.action_target);
-
-            // Clear existing items
-            unsafe {
-                menu.removeAllItems();
-            }
-
-            // Status item (disabled)
-            let status_item = create_menu_item(mtm, "Signing in...", None, None);
-            unsafe {
-                status_item.setEnabled(false);
-            }
-            menu.addItem(&status_item);
-
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
-
-            // Cancel item
-            let cancel_item = create_menu_item(mtm, "Cancel", Some(sel!(cancelSignIn:)), target);
-            menu.addItem(&cancel_item);
-
-            // Quit item
-            let quit_item = create_menu_item(mtm, "Quit", Some(sel!(terminate:)), None);
-            unsafe {
-                quit_item.setKeyEquivalent(&NSString::from_str("q"));
+    /// Rebuild the menu based on current state.
+    ///
+    /// Skipped if nothing shown in the menu has changed, to avoid flicker.
+    pub fn rebuild_menu(mtm: MainThreadMarker) {
+        if let Some(state) = get_app_state() {
+            if !state.mark_menu_rendered(&SystemClock) {
+                debug!("Menu state unchanged, skipping rebuild");
+                return;
             }
-            menu.addItem(&quit_item);
-
-            info!("Built authenticating menu");
+            let model = MenuModel::from_app_state(&state, &SystemClock);
+            Self::render(mtm, &model);
+            info!("Built menu for {}", state.get_auth_state().name());
+            debug!("Menu:\n{}", model.outline());
         }
     }
 
-    /// Build the signed-in menu with user info.
-    pub fn build_signed_in_menu(mtm: MainThreadMarker) {
-        if let Some(menu_bar) = get_menu_bar() {
-            let inner = menu_bar.lock().unwrap();
-            let menu = &inner.menu;
-            let target = Some(&*inner.action_target);
-
-            // Clear existing items
-            unsafe {
-                menu.removeAllItems();
-            }
-
-            // Get user info from app state
-            let app_state = get_app_state();
-            let user_info = app_state.as_ref().and_then(|s| s.get_user_info());
-
-            // User name (disabled, bold-like appearance)
-            let name = user_info
-                .as_ref()
-                .map(|u| u.display_name.as_str())
-                .unwrap_or("Unknown User");
-            let name_item = create_menu_item(mtm, name, None, None);
-            unsafe {
-                name_item.setEnabled(false);
-            }
-            menu.addItem(&name_item);
-
-            // Email (disabled)
-            let email = user_info
-                .as_ref()
-                .map(|u| u.email.as_str())
-                .unwrap_or("No email");
-            let email_item = create_menu_item(mtm, email, None, None);
-            unsafe {
-                email_item.setEnabled(false);
-            }
-            menu.addItem(&email_item);
-
-            // Tenant (disabled)
-            let tenant = user_info
-                .as_ref()
-                .map(|u| u.tenant_name.as_str())
-                .unwrap_or("Unknown Tenant");
-            let tenant_item = create_menu_item(mtm, tenant, None, None);
-            unsafe {
-                tenant_item.setEnabled(false);
-            }
-            menu.addItem(&tenant_item);
-
-            // Token expiry (if enabled in settings)
-            if let Some(state) = app_state.as_ref() {
-                if state.get_settings().show_expiry {
-                    if let Some(expiry) = state.get_token_expiry() {
-                        let duration = expiry - chrono::Utc::now();
-                        let expiry_text = format!(
-                            "Expires in {}",
-                            crate::auth::token_manager::format_duration(duration)
-                        );
-                        let expiry_item = create_menu_item(mtm, &expiry_text, None, None);
-                        unsafe {
-                            expiry_item.setEnabled(false);
-                        }
-                        menu.addItem(&expiry_item);
-                    }
-                }
-            }
-
-            // PIM Section
-            if let Some(state) = app_state.as_ref() {
-                let pim_state = state.get_pim_state();
-                add_pim_section(mtm, menu, &pim_state, target);
-            }
-
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
-
-            // Copy Access Token
-            let copy_item =
-                create_menu_item(mtm, "Copy Access Token", Some(sel!(copyToken:)), target);
-            menu.addItem(&copy_item);
-
-            // Refresh Token
-            let refresh_item =
-                create_menu_item(mtm, "Refresh Token", Some(sel!(refreshToken:)), target);
-            menu.addItem(&refresh_item);
-
-            // Sign Out
-            let sign_out_item = create_menu_item(mtm, "Sign Out", Some(sel!(signOut:)), target);
-            menu.addItem(&sign_out_item);
-
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
-
-            // Settings submenu
-            let settings_menu = create_settings_submenu(mtm, target);
-            let settings_item = create_menu_item(mtm, "Settings", None, None);
-            settings_item.setSubmenu(Some(&settings_menu));
-            menu.addItem(&settings_item);
-
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
+    /// Show `model` in the menu and on the status items.
+    fn render(mtm: MainThreadMarker, model: &MenuModel) {
+        let Some(menu_bar) = get_menu_bar() else {
+            return;
+        };
+        let Ok(mut inner) = menu_bar.lock() else {
+            return;
+        };
 
-            // Quit
-            let quit_item = create_menu_item(mtm, "Quit", Some(sel!(terminate:)), None);
+        unsafe {
+            inner.menu.removeAllItems();
+        }
+        add_entries(
+            mtm,
+            &inner.menu,
+            &model.entries,
+            Some(&*inner.action_target),
+        );
+
+        if let Some(button) = unsafe { inner.status_item.button(mtm) } {
             unsafe {
-                quit_item.setKeyEquivalent(&NSString::from_str("q"));
+                render_countdown(&button, model.countdown.as_ref());
+                // Describe the session and active roles to VoiceOver
+                button.setAccessibilityLabel(Some(&NSString::from_str(&model.accessibility_label)));
             }
-            menu.addItem(&quit_item);
-
-            info!("Built signed-in menu");
         }
+        sync_role_items(mtm, &mut inner.role_items, &model.role_items);
     }
+}
 
-    /// Build the error menu.
-    pub fn build_error_menu(mtm: MainThreadMarker, error_message: &str) {
-        if let Some(menu_bar) = get_menu_bar() {
-            let inner = menu_bar.lock().unwrap();
-            let menu = &inner.menu;
-            let target = Some(&*inner.action_target);
-
-            // Clear existing items
-            unsafe {
-                menu.removeAllItems();
-            }
-
-            // Error status (disabled)
-            let error_item = create_menu_item(mtm, "Authentication Failed", None, None);
-            unsafe {
-                error_item.setEnabled(false);
-            }
-            menu.addItem(&error_item);
-
-            // Error message (disabled)
-            let msg_item = create_menu_item(mtm, error_message, None, None);
-            unsafe {
-                msg_item.setEnabled(false);
-            }
-            menu.addItem(&msg_item);
-
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
-
-            // Try Again
-            let retry_item = create_menu_item(mtm, "Try Again", Some(sel!(signIn:)), target);
-            menu.addItem(&retry_item);
-
-            // Sign Out
-            let sign_out_item = create_menu_item(mtm, "Sign Out", Some(sel!(signOut:)), target);
-            menu.addItem(&sign_out_item);
+/// Show the token countdown next to the status item icon, or just the icon.
+unsafe fn render_countdown(button: &NSStatusBarButton, countdown: Option<&StatusText>) {
+    if button.image().is_none() {
+        // The title is the fallback icon
+        return;
+    }
+    match countdown {
+        Some(countdown) => {
+            set_button_title(button, countdown);
+            button.setImagePosition(NSCellImagePosition::NSImageLeft);
+        }
+        None => {
+            button.setTitle(&NSString::from_str(""));
+            button.setImagePosition(NSCellImagePosition::NSImageOnly);
+        }
+    }
+}
 
-            // Separator
-            let separator = NSMenuItem::separatorItem(mtm);
-            menu.addItem(&separator);
+/// Set a status item title, colored if urgent.
+unsafe fn set_button_title(button: &NSStatusBarButton, title: &StatusText) {
+    match title.color {
+        Some(color) => button.setAttributedTitle(&colored_title(&title.text, &ns_color(color))),
+        None => button.setTitle(&NSString::from_str(&title.text)),
+    }
+}
 
-            // Quit
-            let quit_item = create_menu_item(mtm, "Quit", Some(sel!(terminate:)), None);
-            unsafe {
-                quit_item.setKeyEquivalent(&NSString::from_str("q"));
+/// Keep one compact status item per role in `items`, keyed by assignment ID.
+///
+/// Items are removed once their role expires or is deactivated.
+fn sync_role_items(
+    mtm: MainThreadMarker,
+    status_items: &mut HashMap<String, Retained<NSStatusItem>>,
+    items: &[RoleStatusItem],
+) {
+    unsafe {
+        let status_bar = NSStatusBar::systemStatusBar();
+        status_items.retain(|id, status_item| {
+            let live = items.iter().any(|item| &item.id == id);
+            if !live {
+                status_bar.removeStatusItem(status_item);
             }
-            menu.addItem(&quit_item);
-
-            info!("Built error menu");
+            live
+        });
+
+        for item in items {
+            let status_item = status_items
+                .entry(item.id.clone())
+                .or_insert_with(|| status_bar.statusItemWithLength(NSVariableStatusItemLength));
+            let Some(button) = status_item.button(mtm) else {
+                continue;
+            };
+            set_button_title(&button, &item.title);
+            button.setToolTip(Some(&NSString::from_str(&item.tooltip)));
+            button.setAccessibilityLabel(Some(&NSString::from_str(&item.accessibility_label)));
         }
     }
+}
 
-    /// Rebuild the menu based on current state.
-    pub fn rebuild_menu(mtm: MainThreadMarker) {
-        if let Some(state) = get_app_state() {
-            match state.get_auth_state() {
-                AuthState::SignedOut => Self::build_signed_out_menu(mtm),
-                AuthState::Authenticating => Self::build_authenticating_menu(mtm),
-                AuthState::SignedIn => Self::build_signed_in_menu(mtm),
-                AuthState::Error { message } => Self::build_error_menu(mtm, &message),
-                AuthState::Offline => Self::build_signed_in_menu(mtm),
-            }
-        }
+/// A title drawn entirely in `color`.
+fn colored_title(text: &str, color: &NSColor) -> Retained<NSMutableAttributedString> {
+    let mut title = NSMutableAttributedString::from_nsstring(&NSString::from_str(text));
+    unsafe {
+        title.addAttribute_value_range(
+            NSForegroundColorAttributeName,
+            color,
+            NSRange::new(0, text.encode_utf16().count()),
+        );
     }
+    title
 }
 
 /// Create a menu item with the given title, action, and optional target.
@@ -378,366 +333,427 @@ fn create_menu_item(
     item
 }
 
-/// Create the settings submenu.
-fn create_settings_submenu(
-    mtm: MainThreadMarker,
-    target: Option<&MenuActionTarget>,
-) -> Retained<NSMenu> {
-    let menu = NSMenu::new(mtm);
-
-    // Auto-launch toggle
-    let auto_launch_item = create_menu_item(
-        mtm,
-        "Auto-launch at login",
-        Some(sel!(toggleAutoLaunch:)),
-        target,
-    );
-    // Set checkmark based on current setting
-    if let Some(state) = get_app_state() {
-        let settings = state.get_settings();
-        unsafe {
-            auto_launch_item.setState(if settings.auto_launch {
-                NSControlStateValueOn
-            } else {
-                NSControlStateValueOff
-            });
-        }
-    }
-    menu.addItem(&auto_launch_item);
-
-    // Show expiry toggle
-    let show_expiry_item = create_menu_item(
-        mtm,
-        "Show expiry countdown",
-        Some(sel!(toggleShowExpiry:)),
-        target,
-    );
-    if let Some(state) = get_app_state() {
-        let settings = state.get_settings();
-        unsafe {
-            show_expiry_item.setState(if settings.show_expiry {
-                NSControlStateValueOn
-            } else {
-                NSControlStateValueOff
-            });
+/// Show a count on a menu item, using a native badge on macOS 14+.
+///
+/// Older systems don't support badges, so the count is appended to the title instead.
+fn set_count_badge(item: &NSMenuItem, title: &str, count: usize) {
+    unsafe {
+        if item.respondsToSelector(sel!(setBadge:)) {
+            let badge = NSMenuItemBadge::initWithCount(NSMenuItemBadge::alloc(), count as isize);
+            item.setBadge(Some(&badge));
+        } else {
+            item.setTitle(&NSString::from_str(&format!("{} ({})", title, count)));
         }
     }
-    menu.addItem(&show_expiry_item);
-
-    // Separator
-    let separator = NSMenuItem::separatorItem(mtm);
-    menu.addItem(&separator);
-
-    // Clear all data
-    let clear_item = create_menu_item(mtm, "Clear all data...", Some(sel!(clearData:)), target);
-    menu.addItem(&clear_item);
-
-    menu
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
-// PIM Menu Section
+// Menu Model Rendering
 // ─────────────────────────────────────────────────────────────────────────────
 
-/// Add the PIM section to the menu.
-fn add_pim_section(
+/// Add the items of `entries` to `menu`.
+fn add_entries(
     mtm: MainThreadMarker,
     menu: &NSMenu,
-    pim_state: &PimState,
+    entries: &[MenuEntry],
     target: Option<&MenuActionTarget>,
 ) {
-    // Separator before PIM section
-    let separator = NSMenuItem::separatorItem(mtm);
-    menu.addItem(&separator);
-
-    // Active Roles Section (if any)
-    if !pim_state.active_assignments.is_empty() {
-        let header_text = format!("Active Roles ({})", pim_state.active_assignments.len());
-        let header = create_menu_item(mtm, &header_text, None, None);
-        unsafe {
-            header.setEnabled(false);
+    for entry in entries {
+        match entry {
+            MenuEntry::Separator => menu.addItem(&NSMenuItem::separatorItem(mtm)),
+            MenuEntry::Item(item) => menu.addItem(&render_item(mtm, item, target)),
         }
-        menu.addItem(&header);
+    }
+}
 
-        for assignment in &pim_state.active_assignments {
-            let item_text = assignment.display_text_with_time();
-            let item = create_menu_item(mtm, &item_text, None, None);
-            unsafe {
-                item.setEnabled(false);
-            }
-            menu.addItem(&item);
+/// Create the AppKit item for a model item, with its submenu.
+fn render_item(
+    mtm: MainThreadMarker,
+    item: &MenuItem,
+    target: Option<&MenuActionTarget>,
+) -> Retained<NSMenuItem> {
+    let action = item.command.as_ref().map(command_selector);
+    let ns_item = create_menu_item(mtm, &item.title, action, target);
+    unsafe {
+        if let Some(tag) = item.command.as_ref().and_then(command_tag) {
+            ns_item.setTag(tag);
+        }
+        if !item.enabled {
+            ns_item.setEnabled(false);
+        }
+        if let Some(key) = item.key {
+            ns_item.setKeyEquivalent(&NSString::from_str(key));
         }
+        if let Some(tooltip) = &item.tooltip {
+            ns_item.setToolTip(Some(&NSString::from_str(tooltip)));
+        }
+        if let Some(label) = &item.accessibility_label {
+            ns_item.setAccessibilityLabel(Some(&NSString::from_str(label)));
+        }
+        if let Some(tint) = &item.tint {
+            ns_item.setAttributedTitle(Some(&tinted_title(&item.title, tint)));
+        }
+        if item.checked {
+            ns_item.setState(NSControlStateValueOn);
+        }
+    }
+    if let Some(count) = item.badge {
+        set_count_badge(&ns_item, &item.title, count);
+    }
 
-        // Separator after active roles
-        let separator = NSMenuItem::separatorItem(mtm);
-        menu.addItem(&separator);
+    if let Some(entries) = &item.submenu {
+        let submenu = NSMenu::new(mtm);
+        add_entries(mtm, &submenu, entries, target);
+        ns_item.setSubmenu(Some(&submenu));
     }
+    ns_item
+}
 
-    // Handle different API states
-    match &pim_state.api_status {
-        PimApiStatus::Loading => {
-            let loading_item = create_menu_item(mtm, "PIM Roles (loading...)", None, None);
-            unsafe {
-                loading_item.setEnabled(false);
+/// The delegate method a command is sent to.
+fn command_selector(command: &Command) -> Sel {
+    match command {
+        Command::SignIn => sel!(signIn:),
+        Command::CancelSignIn => sel!(cancelSignIn:),
+        Command::SignOut => sel!(signOut:),
+        Command::RetryRefresh => sel!(retryRefresh:),
+        Command::RefreshToken => sel!(refreshToken:),
+        Command::CopyToken => sel!(copyToken:),
+        Command::CopyTokenHeader => sel!(copyTokenHeader:),
+        Command::CopyTokenCurl(_) => sel!(copyTokenCurl:),
+        Command::CopyTokenCurlCustom => sel!(copyTokenCurlCustom:),
+        Command::CheckHealth => sel!(checkHealth:),
+        Command::RefreshRoles => sel!(refreshPimRoles:),
+        Command::RecheckSkippedSubscriptions => sel!(recheckSkippedSubscriptions:),
+        Command::OpenPimPortal => sel!(openPimPortal:),
+        Command::ShowPalette => sel!(showCommandPalette:),
+        Command::CopyArmConsentUrl => sel!(copyArmConsentUrl:),
+        Command::ActivatePlan(_) => sel!(activatePlan:),
+        Command::SavePlan => sel!(savePlan:),
+        Command::DeactivateAll => sel!(deactivateAllRoles:),
+        Command::Quit => sel!(terminate:),
+        Command::Activate { .. } => sel!(activateRole:),
+        Command::ToggleFavorite { .. } => sel!(toggleFavorite:),
+        Command::CheckActivation { .. } => sel!(checkActivation:),
+        Command::SetRoleDuration { .. } => sel!(setRoleDuration:),
+        Command::EditRoleLabel { .. } => sel!(editRoleLabel:),
+        Command::ExtendRole { .. } => sel!(extendRole:),
+        Command::DeactivateRole { .. } => sel!(deactivateRole:),
+        Command::SetReminder { .. } => sel!(setDeactivationReminder:),
+        Command::ScheduleDeactivation { .. } => sel!(scheduleDeactivation:),
+        Command::CancelScheduledDeactivation { .. } => sel!(cancelScheduledDeactivation:),
+        Command::ScheduleActivation { .. } => sel!(scheduleActivation:),
+        Command::CancelScheduledActivation { .. } => sel!(cancelScheduledActivation:),
+        Command::AddNote { .. } => sel!(addSessionNote:),
+        Command::CopyNotes { .. } => sel!(copySessionNotes:),
+        Command::ReviewApproval { .. } => sel!(reviewApproval:),
+        Command::Toggle(setting) => match setting {
+            Setting::AutoLaunch => sel!(toggleAutoLaunch:),
+            Setting::ShowExpiry => sel!(toggleShowExpiry:),
+            Setting::ProfileDetails => sel!(toggleProfileDetails:),
+            Setting::QuitWarning => sel!(toggleQuitWarning:),
+            Setting::MenuBarCountdown => sel!(toggleMenuBarCountdown:),
+            Setting::RefreshAtWarning => sel!(toggleRefreshAtWarning:),
+            Setting::RestoreSession => sel!(toggleRestoreSession:),
+            Setting::AutoLoadRoles => sel!(toggleAutoLoadRoles:),
+            Setting::RestoreNotification => sel!(toggleRestoreNotification:),
+            Setting::SuccessAnimation => sel!(toggleSuccessAnimation:),
+            Setting::CriticalFlash => sel!(toggleCriticalFlash:),
+            Setting::FocusBreakthrough => sel!(toggleFocusBreakthrough:),
+            Setting::RoleStatusItems => sel!(toggleRoleStatusItems:),
+            Setting::SkipGroupLookup => sel!(toggleSkipGroupLookup:),
+            Setting::KeyVaultToken => sel!(toggleKeyVaultToken:),
+            Setting::ExtensionAtExpiry => sel!(toggleExtensionAtExpiry:),
+            Setting::AdminView => sel!(toggleAdminView:),
+        },
+        Command::ChooseFavoritesFile => sel!(chooseFavoritesFile:),
+        Command::ToggleAdminViewSubscription { .. } => sel!(toggleAdminViewSubscription:),
+        Command::SetSkipDeniedSubscriptions(_) => sel!(setSkipDeniedSubscriptions:),
+        Command::SetRolePageSize(_) => sel!(setRolePageSize:),
+        Command::SetEndOfDaySummary(_) => sel!(setEndOfDaySummary:),
+        Command::SetTokenWarning(_) => sel!(setTokenWarning:),
+        Command::SetTokenCritical(_) => sel!(setTokenCritical:),
+        Command::SetNotificationSound { .. } => sel!(setNotificationSound:),
+        Command::ClearData => sel!(clearData:),
+    }
+}
+
+/// The tag telling the delegate what a command acts on.
+///
+/// Commands on a role or assignment get a hashed tag, with their data kept
+/// in the callback storage below.
+fn command_tag(command: &Command) -> Option<isize> {
+    let tag = match command {
+        Command::CopyTokenCurl(index) | Command::ActivatePlan(index) => *index as isize,
+        // The delegate reads the minutes back from the tag
+        Command::SetTokenWarning(minutes) | Command::SetTokenCritical(minutes) => *minutes as isize,
+        Command::SetSkipDeniedSubscriptions(hours) => *hours as isize,
+        Command::SetRolePageSize(size) => *size as isize,
+        // Minutes after midnight, negative for off
+        Command::SetEndOfDaySummary(time) => {
+            time.map_or(-1, |t| (t.num_seconds_from_midnight() / 60) as isize)
+        }
+        Command::SetNotificationSound { event, sound } => sound_menu_tag(*event, *sound),
+        Command::Activate {
+            role_key,
+            justification,
+        } => {
+            let tag = hash_tag((role_key, justification));
+            store_preset_callback(role_key, justification, tag);
+            tag
+        }
+        Command::ToggleFavorite { role_key } => {
+            let tag = hash_tag((role_key, "favorite"));
+            store_favorite_callback(role_key, tag);
+            tag
+        }
+        Command::CheckActivation { role_key } => {
+            let tag = hash_tag((role_key, "check"));
+            store_check_callback(role_key, tag);
+            tag
+        }
+        Command::SetRoleDuration { role_key, minutes } => {
+            let tag = hash_tag((role_key, minutes, "duration"));
+            if let Ok(mut callbacks) = get_duration_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), *minutes));
             }
-            menu.addItem(&loading_item);
+            tag
         }
-        PimApiStatus::PermissionDenied { message } => {
-            let error_item = create_menu_item(mtm, &format!("PIM: {}", message), None, None);
-            unsafe {
-                error_item.setEnabled(false);
+        Command::EditRoleLabel {
+            role_key,
+            role_text,
+        } => {
+            let tag = hash_tag((role_key, "label"));
+            if let Ok(mut callbacks) = get_label_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), role_text.clone()));
             }
-            menu.addItem(&error_item);
+            tag
         }
-        PimApiStatus::Unavailable { error } => {
-            let error_item = create_menu_item(mtm, &format!("PIM: {}", error), None, None);
-            unsafe {
-                error_item.setEnabled(false);
+        Command::ExtendRole { role_key, minutes } => {
+            let tag = hash_tag((role_key, minutes, "more-time"));
+            if let Ok(mut callbacks) = get_more_time_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), *minutes));
             }
-            menu.addItem(&error_item);
+            tag
         }
-        PimApiStatus::Unknown | PimApiStatus::Available => {
-            if pim_state.eligible_roles.is_empty() {
-                let empty_item = create_menu_item(mtm, "No eligible PIM roles", None, None);
-                unsafe {
-                    empty_item.setEnabled(false);
-                }
-                menu.addItem(&empty_item);
-            } else {
-                // ★ Favorites section (flat, at top for quick access)
-                let favorites = pim_state.favorite_roles();
-                if !favorites.is_empty() {
-                    let fav_header = create_menu_item(mtm, "★ Favorites", None, None);
-                    unsafe {
-                        fav_header.setEnabled(false);
-                    }
-                    menu.addItem(&fav_header);
-
-                    for role in favorites {
-                        let role_item = create_role_menu_item(mtm, role, true);
-                        menu.addItem(&role_item);
-                    }
-
-                    // Separator after favorites
-                    let separator = NSMenuItem::separatorItem(mtm);
-                    menu.addItem(&separator);
-                }
-
-                // Eligible Roles submenu (grouped by subscription)
-                let roles_by_sub = pim_state.roles_by_subscription();
-                if !roles_by_sub.is_empty() {
-                    let eligible_item = create_menu_item(mtm, "Eligible Roles", None, None);
-                    let eligible_submenu = create_eligible_roles_submenu(mtm, &roles_by_sub, pim_state);
-                    eligible_item.setSubmenu(Some(&eligible_submenu));
-                    menu.addItem(&eligible_item);
-                }
+        Command::DeactivateRole { role_key } => {
+            let tag = hash_tag((role_key, "deactivate"));
+            if let Ok(mut callbacks) = get_deactivate_callbacks().write() {
+                callbacks.insert(tag, role_key.clone());
             }
+            tag
         }
-    }
-
-    // Refresh Roles item
-    let refresh_item =
-        create_menu_item(mtm, "↻ Refresh Roles", Some(sel!(refreshPimRoles:)), target);
-    menu.addItem(&refresh_item);
+        Command::SetReminder { role_key, minutes } => {
+            let tag = hash_tag((role_key, minutes, "reminder"));
+            if let Ok(mut callbacks) = get_reminder_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), *minutes));
+            }
+            tag
+        }
+        Command::ScheduleDeactivation {
+            role_key,
+            role_text,
+        }
+        | Command::CancelScheduledDeactivation {
+            role_key,
+            role_text,
+        } => {
+            let tag = hash_tag((role_key, "schedule"));
+            if let Ok(mut callbacks) = get_schedule_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), role_text.clone()));
+            }
+            tag
+        }
+        Command::ScheduleActivation {
+            role_key,
+            role_text,
+        } => {
+            let tag = hash_tag((role_key, "schedule-activation"));
+            if let Ok(mut callbacks) = get_schedule_callbacks().write() {
+                callbacks.insert(tag, (role_key.clone(), role_text.clone()));
+            }
+            tag
+        }
+        Command::CancelScheduledActivation { request_id } => {
+            let tag = hash_tag((request_id, "cancel-scheduled"));
+            if let Ok(mut callbacks) = get_scheduled_activation_callbacks().write() {
+                callbacks.insert(tag, request_id.clone());
+            }
+            tag
+        }
+        Command::AddNote {
+            assignment_id,
+            role_text,
+        }
+        | Command::CopyNotes {
+            assignment_id,
+            role_text,
+        } => {
+            let tag = hash_tag((assignment_id, "notes"));
+            if let Ok(mut callbacks) = get_note_callbacks().write() {
+                callbacks.insert(tag, (assignment_id.clone(), role_text.clone()));
+            }
+            tag
+        }
+        Command::ReviewApproval { request_id, result } => {
+            let tag = hash_tag((request_id, result, "review"));
+            if let Ok(mut callbacks) = get_review_callbacks().write() {
+                callbacks.insert(tag, (request_id.clone(), *result));
+            }
+            tag
+        }
+        Command::ToggleAdminViewSubscription { subscription_id } => {
+            let tag = hash_tag((subscription_id, "admin-view"));
+            if let Ok(mut callbacks) = get_admin_view_callbacks().write() {
+                callbacks.insert(tag, subscription_id.clone());
+            }
+            tag
+        }
+        _ => return None,
+    };
+    Some(tag)
 }
 
-/// Create the "Eligible Roles" submenu with subscriptions as submenus.
-fn create_eligible_roles_submenu(
-    mtm: MainThreadMarker,
-    roles_by_subscription: &[(&str, Vec<&EligibleRole>)],
-    pim_state: &PimState,
-) -> Retained<NSMenu> {
-    let menu = NSMenu::new(mtm);
+/// A menu item tag derived from what the item acts on.
+fn hash_tag(value: impl std::hash::Hash) -> isize {
+    use std::hash::Hasher;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish() as isize
+}
 
-    for (subscription_name, roles) in roles_by_subscription {
-        if roles.is_empty() {
-            continue;
+/// The AppKit color for a model color.
+fn ns_color(color: Color) -> Retained<NSColor> {
+    unsafe {
+        match color {
+            Color::Green => NSColor::systemGreenColor(),
+            Color::Orange => NSColor::systemOrangeColor(),
+            Color::Red => NSColor::systemRedColor(),
         }
+    }
+}
 
-        // Subscription as a submenu item
-        let sub_item = create_menu_item(mtm, subscription_name, None, None);
-        let sub_menu = NSMenu::new(mtm);
+/// A title with the tinted part drawn in its color.
+fn tinted_title(title: &str, tint: &Tint) -> Retained<NSMutableAttributedString> {
+    let (color, tinted) = match tint {
+        Tint::Title(color) => return colored_title(title, &ns_color(*color)),
+        Tint::Suffix(color, suffix) => (*color, suffix.as_str()),
+    };
+    // NSRange is measured in UTF-16 code units
+    let length = tinted.encode_utf16().count();
+    let start = title.encode_utf16().count().saturating_sub(length);
+    let mut attributed = NSMutableAttributedString::from_nsstring(&NSString::from_str(title));
+    unsafe {
+        attributed.addAttribute_value_range(
+            NSForegroundColorAttributeName,
+            &ns_color(color),
+            NSRange::new(start, length),
+        );
+    }
+    attributed
+}
 
-        // Add roles within this subscription
-        for role in roles {
-            let is_favorite = pim_state.is_favorite(role);
-            let role_item = create_role_menu_item_short(mtm, role, is_favorite);
-            sub_menu.addItem(&role_item);
-        }
+// ─────────────────────────────────────────────────────────────────────────────
+// PIM Callback Storage (MVP approach)
+// ─────────────────────────────────────────────────────────────────────────────
 
-        sub_item.setSubmenu(Some(&sub_menu));
-        menu.addItem(&sub_item);
-    }
+use std::collections::HashMap;
+use std::sync::RwLock;
 
-    menu
-}
+/// Global storage for preset callbacks (role_key, justification) by tag.
+static PRESET_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, String)>>> = OnceCell::new();
 
-/// Create a menu item for a role (full display: "subscription - role").
-fn create_role_menu_item(
-    mtm: MainThreadMarker,
-    role: &EligibleRole,
-    is_favorite: bool,
-) -> Retained<NSMenuItem> {
-    let title = format!("  {} - {}", role.subscription_name, role.role_name);
-    let item = create_menu_item(mtm, &title, None, None);
+/// Global storage for favorite callbacks (role_key) by tag.
+static FAVORITE_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
 
-    // Create submenu with justification presets
-    let submenu = create_justification_submenu(mtm, role, is_favorite);
-    item.setSubmenu(Some(&submenu));
+/// Global storage for activation check callbacks (role_key) by tag.
+static CHECK_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
 
-    item
-}
+/// Role key and minutes of a "Duration" item, `None` for "Custom…".
+type DurationCallback = (String, Option<u32>);
 
-/// Create a menu item for a role (short display: just role name, used within subscription submenu).
-fn create_role_menu_item_short(
-    mtm: MainThreadMarker,
-    role: &EligibleRole,
-    is_favorite: bool,
-) -> Retained<NSMenuItem> {
-    let star = if is_favorite { "★ " } else { "" };
-    let title = format!("{}{}", star, role.role_name);
-    let item = create_menu_item(mtm, &title, None, None);
+/// Global storage for activation duration callbacks by tag.
+static DURATION_CALLBACKS: OnceCell<RwLock<HashMap<isize, DurationCallback>>> = OnceCell::new();
 
-    // Create submenu with justification presets
-    let submenu = create_justification_submenu(mtm, role, is_favorite);
-    item.setSubmenu(Some(&submenu));
+/// Global storage for role label callbacks (role_key, role text) by tag.
+static LABEL_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, String)>>> = OnceCell::new();
 
-    item
-}
+/// Global storage for "Need More Time" callbacks (role_key, minutes) by tag.
+static MORE_TIME_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, u32)>>> = OnceCell::new();
 
-/// Create the justification submenu for a role.
-fn create_justification_submenu(
-    mtm: MainThreadMarker,
-    role: &EligibleRole,
-    is_favorite: bool,
-) -> Retained<NSMenu> {
-    let menu = NSMenu::new(mtm);
-    let role_key = role.favorites_key();
-
-    // Add builtin justification presets
-    let presets = JustificationPreset::builtin_presets();
-    for preset in presets {
-        let preset_item = create_preset_menu_item(mtm, &preset, &role_key);
-        menu.addItem(&preset_item);
-    }
+/// Global storage for "Deactivate Now" callbacks (role_key) by tag.
+static DEACTIVATE_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
 
-    // Separator
-    let separator = NSMenuItem::separatorItem(mtm);
-    menu.addItem(&separator);
+/// Role key and reminder delay of a deactivation reminder item.
+type ReminderCallback = (String, Option<u32>);
 
-    // Favorite toggle
-    let favorite_text = if is_favorite {
-        "Remove from Favorites"
-    } else {
-        "Add to Favorites"
-    };
-    let favorite_item = create_favorite_toggle_item(mtm, favorite_text, &role_key);
-    menu.addItem(&favorite_item);
+/// Global storage for deactivation reminder callbacks by tag.
+static REMINDER_CALLBACKS: OnceCell<RwLock<HashMap<isize, ReminderCallback>>> = OnceCell::new();
 
-    menu
-}
+/// Global storage for scheduled activation and deactivation callbacks (role_key, role text) by tag.
+static SCHEDULE_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, String)>>> = OnceCell::new();
 
-/// Create a menu item for a justification preset.
-fn create_preset_menu_item(
-    mtm: MainThreadMarker,
-    preset: &JustificationPreset,
-    role_key: &str,
-) -> Retained<NSMenuItem> {
-    let ns_title = NSString::from_str(&preset.label);
-    let key_equiv = NSString::from_str("");
+/// Global storage for "Cancel Activation" callbacks (schedule request ID) by tag.
+static SCHEDULED_ACTIVATION_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
 
-    let item = unsafe {
-        NSMenuItem::initWithTitle_action_keyEquivalent(mtm.alloc(), &ns_title, None, &key_equiv)
-    };
+/// Global storage for session note callbacks (assignment ID, role text) by tag.
+static NOTE_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, String)>>> = OnceCell::new();
 
-    // Store role_key and justification for the callback
-    let role_key = role_key.to_string();
-    let justification = preset.justification.clone();
+/// Global storage for approval review callbacks (request ID, decision) by tag.
+static REVIEW_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, ReviewResult)>>> = OnceCell::new();
 
-    // Set up click handler using a block
-    unsafe {
-        // We need to use objc2's block support to create a callback
-        // For now, we'll set up a custom target/action mechanism
-        // The actual activation will happen via notification or custom delegate
-
-        // Create a custom action target that captures the role_key and justification
-        // Since we can't easily pass data through NSMenuItem actions, we'll use
-        // the representedObject pattern or a custom approach
-
-        // For simplicity in the MVP, we'll use a workaround:
-        // Store the action data in the menu item's tag or identifier
-        // and look it up when the action is triggered
-
-        // Alternative: Use objc2-block to create a proper block callback
-        // For now, we'll make this a simple clickable item that triggers activation
-
-        // Set a unique tag based on hash
-        let hash = {
-            use std::hash::{Hash, Hasher};
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            role_key.hash(&mut hasher);
-            justification.hash(&mut hasher);
-            hasher.finish() as isize
-        };
-        item.setTag(hash);
+/// Global storage for administrator view subscription callbacks (subscription ID) by tag.
+static ADMIN_VIEW_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
 
-        // Store the callback data globally and dispatch when clicked
-        // For the MVP, we'll trigger this via a notification mechanism
-        store_preset_callback(&role_key, &justification, hash);
-    }
+fn get_preset_callbacks() -> &'static RwLock<HashMap<isize, (String, String)>> {
+    PRESET_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-    item
+fn get_favorite_callbacks() -> &'static RwLock<HashMap<isize, String>> {
+    FAVORITE_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
 }
 
-/// Create a menu item for toggling favorite status.
-fn create_favorite_toggle_item(
-    mtm: MainThreadMarker,
-    title: &str,
-    role_key: &str,
-) -> Retained<NSMenuItem> {
-    let ns_title = NSString::from_str(title);
-    let key_equiv = NSString::from_str("");
+fn get_check_callbacks() -> &'static RwLock<HashMap<isize, String>> {
+    CHECK_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-    let item = unsafe {
-        NSMenuItem::initWithTitle_action_keyEquivalent(mtm.alloc(), &ns_title, None, &key_equiv)
-    };
+fn get_review_callbacks() -> &'static RwLock<HashMap<isize, (String, ReviewResult)>> {
+    REVIEW_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-    // Store callback data for favorite toggle
-    let role_key = role_key.to_string();
-    unsafe {
-        let hash = {
-            use std::hash::{Hash, Hasher};
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            role_key.hash(&mut hasher);
-            "favorite".hash(&mut hasher);
-            hasher.finish() as isize
-        };
-        item.setTag(hash);
-        store_favorite_callback(&role_key, hash);
-    }
+fn get_admin_view_callbacks() -> &'static RwLock<HashMap<isize, String>> {
+    ADMIN_VIEW_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-    item
+fn get_duration_callbacks() -> &'static RwLock<HashMap<isize, DurationCallback>> {
+    DURATION_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
 }
 
-// ─────────────────────────────────────────────────────────────────────────────
-// PIM Callback Storage (MVP approach)
-// ─────────────────────────────────────────────────────────────────────────────
+fn get_label_callbacks() -> &'static RwLock<HashMap<isize, (String, String)>> {
+    LABEL_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-use std::collections::HashMap;
-use std::sync::RwLock;
+fn get_more_time_callbacks() -> &'static RwLock<HashMap<isize, (String, u32)>> {
+    MORE_TIME_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-/// Global storage for preset callbacks (role_key, justification) by tag.
-static PRESET_CALLBACKS: OnceCell<RwLock<HashMap<isize, (String, String)>>> = OnceCell::new();
+fn get_deactivate_callbacks() -> &'static RwLock<HashMap<isize, String>> {
+    DEACTIVATE_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-/// Global storage for favorite callbacks (role_key) by tag.
-static FAVORITE_CALLBACKS: OnceCell<RwLock<HashMap<isize, String>>> = OnceCell::new();
+fn get_reminder_callbacks() -> &'static RwLock<HashMap<isize, ReminderCallback>> {
+    REMINDER_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
 
-fn get_preset_callbacks() -> &'static RwLock<HashMap<isize, (String, String)>> {
-    PRESET_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+fn get_schedule_callbacks() -> &'static RwLock<HashMap<isize, (String, String)>> {
+    SCHEDULE_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
 }
 
-fn get_favorite_callbacks() -> &'static RwLock<HashMap<isize, String>> {
-    FAVORITE_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+fn get_scheduled_activation_callbacks() -> &'static RwLock<HashMap<isize, String>> {
+    SCHEDULED_ACTIVATION_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn get_note_callbacks() -> &'static RwLock<HashMap<isize, (String, String)>> {
+    NOTE_CALLBACKS.get_or_init(|| RwLock::new(HashMap::new()))
 }
 
 fn store_preset_callback(role_key: &str, justification: &str, tag: isize) {
@@ -752,18 +768,25 @@ fn store_favorite_callback(role_key: &str, tag: isize) {
     }
 }
 
+fn store_check_callback(role_key: &str, tag: isize) {
+    if let Ok(mut callbacks) = get_check_callbacks().write() {
+        callbacks.insert(tag, role_key.to_string());
+    }
+}
+
 /// Look up and execute a preset callback by tag.
-#[allow(dead_code)]
-pub fn execute_preset_callback(tag: isize) {
-    if let Ok(callbacks) = get_preset_callbacks().read() {
-        if let Some((role_key, justification)) = callbacks.get(&tag) {
-            send_activate_role(role_key.clone(), justification.clone());
-        }
+pub fn execute_preset_callback(mtm: MainThreadMarker, tag: isize) {
+    let preset = get_preset_callbacks()
+        .read()
+        .ok()
+        .and_then(|callbacks| callbacks.get(&tag).cloned());
+    if let Some((role_key, justification)) = preset {
+        // The lock is released, since confirming runs a modal dialog
+        request_activation(mtm, role_key, justification, ActivationOptions::default());
     }
 }
 
 /// Look up and execute a favorite callback by tag.
-#[allow(dead_code)]
 pub fn execute_favorite_callback(tag: isize) {
     if let Ok(callbacks) = get_favorite_callbacks().read() {
         if let Some(role_key) = callbacks.get(&tag) {
@@ -771,3 +794,66 @@ pub fn execute_favorite_callback(tag: isize) {
         }
     }
 }
+
+/// Look up the role key and minutes of a "Duration" item by tag.
+pub fn duration_callback(tag: isize) -> Option<DurationCallback> {
+    get_duration_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the role key and role text of a "Set Label…" item by tag.
+pub fn label_callback(tag: isize) -> Option<(String, String)> {
+    get_label_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the role key and extra minutes of a "Need More Time" item by tag.
+pub fn more_time_callback(tag: isize) -> Option<(String, u32)> {
+    get_more_time_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the role key of a "Deactivate Now" item by tag.
+pub fn deactivate_callback(tag: isize) -> Option<String> {
+    get_deactivate_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the role key and reminder delay of a reminder item by tag.
+pub fn reminder_callback(tag: isize) -> Option<ReminderCallback> {
+    get_reminder_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the role key and role text of a scheduled activation or deactivation item by tag.
+pub fn schedule_callback(tag: isize) -> Option<(String, String)> {
+    get_schedule_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the schedule request ID of a "Cancel Activation" item by tag.
+pub fn scheduled_activation_callback(tag: isize) -> Option<String> {
+    get_scheduled_activation_callbacks()
+        .read()
+        .ok()?
+        .get(&tag)
+        .cloned()
+}
+
+/// Look up the assignment ID and role text of a session note item by tag.
+pub fn note_callback(tag: isize) -> Option<(String, String)> {
+    get_note_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the request ID and decision of an "Approve…" or "Deny…" item by tag.
+pub fn review_callback(tag: isize) -> Option<(String, ReviewResult)> {
+    get_review_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up the subscription ID of an administrator view subscription item by tag.
+pub fn admin_view_callback(tag: isize) -> Option<String> {
+    get_admin_view_callbacks().read().ok()?.get(&tag).cloned()
+}
+
+/// Look up and execute an activation check callback by tag.
+pub fn execute_check_callback(tag: isize) {
+    if let Ok(callbacks) = get_check_callbacks().read() {
+        if let Some(role_key) = callbacks.get(&tag) {
+            send_check_activation(role_key.clone());
+        }
+    }
+}
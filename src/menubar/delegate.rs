@@ -2,18 +2,31 @@
 //!
 //! Handles menu item clicks and dispatches to the appropriate callbacks.
 
+use chrono::{DateTime, Local, NaiveTime, Utc};
 use objc2::mutability::MainThreadOnly;
 use objc2::rc::Retained;
 use objc2::{declare_class, msg_send_id, ClassType, DeclaredClass};
-use objc2_app_kit::NSPasteboard;
+use objc2_app_kit::{NSMenuItem, NSPasteboard};
 use objc2_foundation::{MainThreadMarker, NSObject, NSObjectProtocol, NSString};
 use once_cell::sync::OnceCell;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info};
 
+use crate::auth::challenge::StepUp;
+use crate::auth::token_format::TokenFormat;
+use crate::error::KeychainError;
 use crate::keychain;
-use crate::menubar::state::{get_app_state, MenuCallbacks};
+use crate::menubar::palette::{self, Command};
+use crate::menubar::state::{get_app_state, AuthState, MenuCallbacks, PimState};
+use crate::menubar::{builder, dialogs, updates};
+use crate::notifications::{NotificationEvent, NotificationSound};
+use crate::pim::environment::{environment_of, Environment};
+use crate::pim::schedule;
+use crate::pim::{self, ActivationOptions, PimApiStatus, PlanDraft};
+use crate::settings::DataCategory;
 
 /// Global menu callbacks.
 #[allow(dead_code)]
@@ -34,7 +47,10 @@ pub fn get_menu_callbacks() -> Option<&'static Arc<MenuCallbacks>> {
 }
 
 /// Channel for sending menu actions to the Tokio runtime.
-static ACTION_SENDER: OnceCell<mpsc::Sender<MenuAction>> = OnceCell::new();
+static ACTION_SENDER: OnceCell<mpsc::Sender<ActionRequest>> = OnceCell::new();
+
+/// Correlation ID for the next action request.
+static NEXT_ACTION_ID: AtomicU64 = AtomicU64::new(1);
 
 /// Menu action types.
 #[derive(Debug, Clone)]
@@ -44,17 +60,57 @@ pub enum MenuAction {
     SignIn,
     SignOut,
     RefreshToken,
-    CopyToken,
+    /// Copy the access token, raw or wrapped in a header or curl command
+    CopyToken(TokenFormat),
+    CheckHealth,
+    RetryRefresh,
     ToggleAutoLaunch(bool),
     ToggleShowExpiry(bool),
-    ClearData,
+    ToggleProfileDetails(bool),
+    ToggleRestoreSession(bool),
+    ToggleAutoLoadRoles(bool),
+    ToggleRestoreNotification(bool),
+    SetNotificationSound {
+        event: NotificationEvent,
+        sound: NotificationSound,
+    },
+    ToggleSuccessAnimation(bool),
+    ToggleQuitWarning(bool),
+    ToggleCriticalFlash(bool),
+    ToggleFocusBreakthrough(bool),
+    ToggleRoleStatusItems(bool),
+    ToggleKeyVaultToken(bool),
+    ToggleExtensionAtExpiry(bool),
+    SetEndOfDaySummary(Option<NaiveTime>),
+    SetTokenWarningMinutes(u32),
+    SetTokenCriticalMinutes(u32),
+    ToggleMenuBarCountdown(bool),
+    ToggleRefreshAtWarning(bool),
+    /// Remove the stored data of the chosen categories
+    ClearData(Vec<DataCategory>),
     CancelSignIn,
+    /// Consent to scopes missing from the current grant
+    GrantConsent {
+        scopes: Vec<String>,
+    },
+    /// Sign in again with the verification an activation needs, then retry it
+    StepUpActivation {
+        step_up: StepUp,
+        role_key: String,
+        justification: String,
+        options: ActivationOptions,
+    },
 
     // PIM actions
     /// Activate a role with justification
     ActivateRole {
         role_key: String,
         justification: String,
+        options: ActivationOptions,
+    },
+    /// Check whether a role could be activated, without activating it
+    CheckActivation {
+        role_key: String,
     },
     /// Toggle favorite status for a role
     ToggleFavorite {
@@ -62,10 +118,180 @@ pub enum MenuAction {
     },
     /// Refresh PIM roles from Azure
     RefreshPimRoles,
+    /// Activate every role of an elevation plan
+    ActivatePlan {
+        name: String,
+    },
+    /// Save the active roles as an elevation plan
+    SavePlan {
+        draft: PlanDraft,
+    },
+    /// Deactivate a single active role
+    DeactivateRole {
+        role_key: String,
+    },
+    /// Deactivate every active role
+    DeactivateAll,
+    /// Keep an active role for `minutes` past its current end
+    ExtendRole {
+        role_key: String,
+        minutes: u32,
+    },
+    /// Remind to deactivate a role in `minutes`, or cancel the reminder for `None`
+    SetDeactivationReminder {
+        role_key: String,
+        minutes: Option<u32>,
+    },
+    /// Add a note to the elevation of an active assignment
+    AddSessionNote {
+        assignment_id: String,
+        text: String,
+    },
+    /// Deactivate a role at `at`, or cancel its scheduled deactivation for `None`
+    ScheduleDeactivation {
+        role_key: String,
+        at: Option<DateTime<Utc>>,
+    },
+    /// Cancel a scheduled activation before it starts
+    CancelScheduledActivation {
+        request_id: String,
+    },
+    /// Move favorites and presets to a custom file, or back to the default for `None`
+    SetFavoritesFile {
+        path: Option<PathBuf>,
+    },
+    /// Stop or resume reading group memberships
+    ToggleSkipGroupLookup(bool),
+    /// Approve or deny another user's request
+    ReviewApproval {
+        request_id: String,
+        result: pim::ReviewResult,
+        justification: String,
+    },
+    /// Show or hide everyone's active roles
+    ToggleAdminView(bool),
+    /// Add a subscription to the administrator view, or remove it if listed
+    ToggleAdminViewSubscription {
+        subscription_id: String,
+    },
+    /// Skip subscriptions without PIM access for this many hours
+    SetSkipDeniedSubscriptionsHours(u32),
+    /// Split a subscription's roles into submenus of this many, or never for 0
+    SetRolePageSize(u32),
+    /// Scan subscriptions skipped for lack of PIM access again
+    RecheckSkippedSubscriptions,
+    /// Activate a role without confirmation from now on
+    SkipActivationConfirmation {
+        role_key: String,
+    },
+    /// Show `label` next to a role, or remove its label if blank
+    SetRoleLabel {
+        role_key: String,
+        label: String,
+    },
+    /// Activate a role for `minutes` from now on
+    SetRoleDuration {
+        role_key: String,
+        minutes: u32,
+    },
+}
+
+impl MenuAction {
+    /// Short user-facing name, used when reporting the outcome.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::SignIn => "Sign In to Azure",
+            Self::SignOut => "Sign Out",
+            Self::RefreshToken => "Refresh Token",
+            Self::CopyToken(_) => "Copy Access Token",
+            Self::CheckHealth => "Run Checks",
+            Self::RetryRefresh => "Try Again Now",
+            Self::ToggleAutoLaunch(_) => "Auto-launch at login",
+            Self::ToggleShowExpiry(_) => "Show expiry countdown",
+            Self::ToggleProfileDetails(_) => "Show job title and office",
+            Self::ToggleRestoreSession(_) => "Restore session at launch",
+            Self::ToggleAutoLoadRoles(_) => "Load roles automatically",
+            Self::ToggleRestoreNotification(_) => "Notify when session is restored",
+            Self::SetNotificationSound { .. } => "Notification Sound",
+            Self::ToggleSuccessAnimation(_) => "Animate icon on success",
+            Self::ToggleQuitWarning(_) => "Warn about active roles on quit",
+            Self::ToggleCriticalFlash(_) => "Flash icon on critical events",
+            Self::ToggleFocusBreakthrough(_) => "Expiry warnings break through Focus",
+            Self::ToggleRoleStatusItems(_) => "Menu bar item per active role",
+            Self::ToggleKeyVaultToken(_) => "Offer Key Vault token after activation",
+            Self::ToggleExtensionAtExpiry(_) => "Offer to extend expiring roles",
+            Self::SetEndOfDaySummary(_) => "End-of-day summary",
+            Self::SetTokenWarningMinutes(_) => "Token warning threshold",
+            Self::SetTokenCriticalMinutes(_) => "Token critical threshold",
+            Self::ToggleMenuBarCountdown(_) => "Show countdown in menu bar",
+            Self::ToggleRefreshAtWarning(_) => "Refresh token at warning",
+            Self::ClearData(_) => "Clear all data",
+            Self::CancelSignIn => "Cancel sign-in",
+            Self::GrantConsent { .. } => "Grant permissions",
+            Self::StepUpActivation { .. } => "Verify identity",
+            Self::ActivateRole { .. } => "Activate Role",
+            Self::CheckActivation { .. } => "Check Activation",
+            Self::ToggleFavorite { .. } => "Toggle favorite",
+            Self::RefreshPimRoles => "Refresh Roles",
+            Self::ActivatePlan { .. } => "Activate Plan",
+            Self::SavePlan { .. } => "Save Plan",
+            Self::DeactivateRole { .. } => "Deactivate Role",
+            Self::DeactivateAll => "Deactivate All Roles",
+            Self::ExtendRole { .. } => "Need More Time",
+            Self::SetDeactivationReminder { .. } => "Remind Me to Deactivate",
+            Self::AddSessionNote { .. } => "Add Note",
+            Self::ScheduleDeactivation { .. } => "Deactivate At",
+            Self::CancelScheduledActivation { .. } => "Cancel Activation",
+            Self::SetFavoritesFile { .. } => "Favorites File",
+            Self::ToggleSkipGroupLookup(_) => "Skip group lookup",
+            Self::ReviewApproval { .. } => "Review Request",
+            Self::ToggleAdminView(_) => "Administrator view of active roles",
+            Self::ToggleAdminViewSubscription { .. } => "Administrator view subscriptions",
+            Self::SetSkipDeniedSubscriptionsHours(_) => "Skip subscriptions without PIM",
+            Self::SetRolePageSize(_) => "Split long role lists",
+            Self::RecheckSkippedSubscriptions => "Recheck skipped subscriptions",
+            Self::SkipActivationConfirmation { .. } => "Don't confirm activation",
+            Self::SetRoleDuration { .. } => "Activation duration",
+            Self::SetRoleLabel { .. } => "Set Label",
+        }
+    }
+}
+
+/// Result of handling an action, reported back to the UI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionOutcome {
+    /// Completed; any visible result is already reflected in the menu.
+    Done,
+    /// Completed with a confirmation to show the user.
+    Succeeded(String),
+    /// Failed with a reason to show the user.
+    Failed(String),
+}
+
+/// A menu action with a correlation ID and an optional reply channel.
+///
+/// Without a reply channel, the outcome is shown to the user by the
+/// background task that handles the action.
+#[derive(Debug)]
+pub struct ActionRequest {
+    pub id: u64,
+    pub action: MenuAction,
+    pub reply: Option<oneshot::Sender<ActionOutcome>>,
+}
+
+impl ActionRequest {
+    /// Create a request with a new correlation ID.
+    pub fn new(action: MenuAction, reply: Option<oneshot::Sender<ActionOutcome>>) -> Self {
+        Self {
+            id: NEXT_ACTION_ID.fetch_add(1, Ordering::Relaxed),
+            action,
+            reply,
+        }
+    }
 }
 
 /// Initialize the action channel.
-pub fn init_action_channel() -> mpsc::Receiver<MenuAction> {
+pub fn init_action_channel() -> mpsc::Receiver<ActionRequest> {
     let (tx, rx) = mpsc::channel(10);
     ACTION_SENDER
         .set(tx)
@@ -73,10 +299,28 @@ pub fn init_action_channel() -> mpsc::Receiver<MenuAction> {
     rx
 }
 
+/// Number of tag slots reserved per event in sound menu item tags.
+const SOUND_TAG_STRIDE: isize = 100;
+
+/// Encode a notification event and sound into a menu item tag.
+pub fn sound_menu_tag(event: NotificationEvent, sound: NotificationSound) -> isize {
+    let event_idx = NotificationEvent::ALL.iter().position(|e| *e == event);
+    let sound_idx = NotificationSound::ALL.iter().position(|s| *s == sound);
+    event_idx.unwrap_or(0) as isize * SOUND_TAG_STRIDE + sound_idx.unwrap_or(0) as isize
+}
+
+/// Decode a menu item tag created by [`sound_menu_tag`].
+fn decode_sound_menu_tag(tag: isize) -> Option<(NotificationEvent, NotificationSound)> {
+    let event = NotificationEvent::ALL.get((tag / SOUND_TAG_STRIDE) as usize)?;
+    let sound = NotificationSound::ALL.get((tag % SOUND_TAG_STRIDE) as usize)?;
+    Some((*event, *sound))
+}
+
 /// Send an action to be processed.
 fn send_action(action: MenuAction) {
+    crate::crash::record_action(format!("{:?}", action));
     if let Some(sender) = ACTION_SENDER.get() {
-        if let Err(e) = sender.try_send(action) {
+        if let Err(e) = sender.try_send(ActionRequest::new(action, None)) {
             error!("Failed to send menu action: {}", e);
         }
     }
@@ -84,8 +328,9 @@ fn send_action(action: MenuAction) {
 
 /// Send a PIM role activation action.
 ///
-/// This is called from the menu builder when a role's justification preset is clicked.
-pub fn send_activate_role(role_key: String, justification: String) {
+/// This is called from the menu builder when a role's justification preset is clicked,
+/// and when the user accepts a corrected retry after a policy failure.
+pub fn send_activate_role(role_key: String, justification: String, options: ActivationOptions) {
     info!(
         "Activating role {} with justification: {}",
         role_key, justification
@@ -93,6 +338,134 @@ pub fn send_activate_role(role_key: String, justification: String) {
     send_action(MenuAction::ActivateRole {
         role_key,
         justification,
+        options,
+    });
+}
+
+/// Confirm an activation with the user, unless they opted out for the role, then send it.
+///
+/// Called on the main thread when a role is activated from the menu or palette.
+pub fn request_activation(
+    mtm: MainThreadMarker,
+    role_key: String,
+    justification: String,
+    mut options: ActivationOptions,
+) {
+    let (pim_state, environments) = get_app_state()
+        .map(|s| (s.get_pim_state(), s.get_settings().environments))
+        .unwrap_or_default();
+    let role = pim_state
+        .eligible_roles
+        .iter()
+        .find(|r| r.favorites_key() == role_key);
+    // Roles in production are confirmed even if the user opted out
+    let in_prod = role.is_some_and(|r| {
+        environment_of(&r.subscription_name, &environments) == Some(Environment::Prod)
+    });
+    if let Some((role, covering)) =
+        role.and_then(|r| Some((r, r.covered_by(&pim_state.active_assignments)?)))
+    {
+        let covering_text = format!("{} on {}", covering.role_name, covering.subscription_name);
+        if !dialogs::confirm_redundant_activation(mtm, &role.display_text(), &covering_text) {
+            info!(
+                "Activation of {} skipped, covered by {}",
+                role_key, covering_text
+            );
+            return;
+        }
+    }
+    if let Some(role) =
+        role.filter(|_| in_prod || pim_state.settings.confirms_activation(&role_key))
+    {
+        let duration = pim_state.clamp_duration(
+            &role_key,
+            options
+                .duration_minutes
+                .unwrap_or(pim_state.settings.duration_for(&role_key)),
+        );
+        let Some(confirmation) =
+            dialogs::confirm_activation(mtm, role, duration, &justification, in_prod)
+        else {
+            info!("Activation of {} cancelled", role_key);
+            return;
+        };
+        if confirmation.skip_from_now_on {
+            send_action(MenuAction::SkipActivationConfirmation {
+                role_key: role_key.clone(),
+            });
+        }
+        if let Some(time) = confirmation.deactivate_at {
+            match options.start_at {
+                // A role that isn't active yet is requested to end then instead
+                Some(start_at) => {
+                    let start_at = start_at.with_timezone(&Local);
+                    options.duration_minutes =
+                        Some(schedule::scheduled_duration(&start_at, time, duration));
+                }
+                None => options.deactivate_at = schedule::next_occurrence(time, &Local::now()),
+            }
+        }
+    }
+    send_activate_role(role_key, justification, options);
+}
+
+/// Send an action to check a role's activation without activating it.
+///
+/// This is called from the menu builder when "Check Activation" is clicked.
+pub fn send_check_activation(role_key: String) {
+    info!("Checking activation of role {}", role_key);
+    send_action(MenuAction::CheckActivation { role_key });
+}
+
+/// Send an action to deactivate a single role.
+///
+/// This is called from "Deactivate Now" and when the user accepts a deactivation reminder.
+pub fn send_deactivate_role(role_key: String) {
+    info!("Deactivating role {}", role_key);
+    send_action(MenuAction::DeactivateRole { role_key });
+}
+
+/// Send an action to deactivate every active role.
+///
+/// This is called from "Deactivate All Roles" and the end-of-day summary.
+pub fn send_deactivate_all() {
+    info!("Deactivating all roles");
+    send_action(MenuAction::DeactivateAll);
+}
+
+/// Send an action to keep a role active for `minutes` past its current end.
+///
+/// This is called when the user accepts the offer to extend an expiring role.
+pub fn send_extend_role(role_key: String, minutes: u32) {
+    info!("Extending role {} by {} minutes", role_key, minutes);
+    send_action(MenuAction::ExtendRole { role_key, minutes });
+}
+
+/// Send an action to consent to additional scopes.
+///
+/// This is called when the user accepts the prompt for missing permissions.
+pub fn send_grant_consent(scopes: Vec<String>) {
+    info!("Requesting consent for scopes: {:?}", scopes);
+    send_action(MenuAction::GrantConsent { scopes });
+}
+
+/// Send an action to sign in with the verification an activation needs,
+/// then retry the activation.
+///
+/// This is called when an activation fails with an MFA or Authentication
+/// Context challenge.
+pub fn send_step_up_activation(
+    step_up: StepUp,
+    role_key: String,
+    justification: String,
+    options: ActivationOptions,
+) {
+    info!("Requesting {} to activate role {}", step_up, role_key);
+    send_action(MenuAction::StepUpActivation {
+        step_up,
+        role_key,
+        justification,
+        options,
     });
 }
 
@@ -104,6 +477,42 @@ pub fn send_toggle_favorite(role_key: String) {
     send_action(MenuAction::ToggleFavorite { role_key });
 }
 
+/// Run a command chosen in the palette.
+fn run_palette_command(mtm: MainThreadMarker, command: &Command, pim_state: &PimState) {
+    match command {
+        Command::Activate { role_key } => {
+            let Some(role) = pim_state
+                .eligible_roles
+                .iter()
+                .find(|r| r.favorites_key() == *role_key)
+            else {
+                return;
+            };
+            let presets = pim_state.settings.all_presets();
+            let choices: Vec<&str> = presets.iter().map(|p| p.justification.as_str()).collect();
+            if let Some(justification) =
+                dialogs::prompt_justification(mtm, &role.menu_title(), &choices)
+            {
+                request_activation(
+                    mtm,
+                    role_key.clone(),
+                    justification,
+                    ActivationOptions::default(),
+                );
+            }
+        }
+        Command::Deactivate { role_key } => send_deactivate_role(role_key.clone()),
+        Command::CopyToken(format) => send_action(MenuAction::CopyToken(format.clone())),
+        Command::RefreshToken => send_action(MenuAction::RefreshToken),
+        Command::RefreshRoles => send_action(MenuAction::RefreshPimRoles),
+        Command::OpenPortal => {
+            if let Err(e) = open::that(palette::PIM_PORTAL_URL) {
+                error!("Failed to open the Azure portal: {}", e);
+            }
+        }
+    }
+}
+
 // Define the MenuActionTarget class that receives menu item actions
 declare_class!(
     pub struct MenuActionTarget;
@@ -140,7 +549,45 @@ declare_class!(
         #[method(copyToken:)]
         fn copy_token(&self, _sender: &NSObject) {
             info!("Copy Token clicked");
-            send_action(MenuAction::CopyToken);
+            send_action(MenuAction::CopyToken(TokenFormat::Raw));
+        }
+
+        #[method(copyTokenHeader:)]
+        fn copy_token_header(&self, _sender: &NSObject) {
+            info!("Copy Authorization Header clicked");
+            send_action(MenuAction::CopyToken(TokenFormat::BearerHeader));
+        }
+
+        #[method(copyTokenCurl:)]
+        fn copy_token_curl(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Copy curl Snippet clicked");
+            if let Some(format) = TokenFormat::curl_endpoint(tag as usize) {
+                send_action(MenuAction::CopyToken(format));
+            }
+        }
+
+        #[method(copyTokenCurlCustom:)]
+        fn copy_token_curl_custom(&self, _sender: &NSObject) {
+            info!("Copy curl Snippet for custom endpoint clicked");
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+            if let Some(url) = dialogs::prompt_curl_url(mtm) {
+                send_action(MenuAction::CopyToken(TokenFormat::Curl { url }));
+            }
+        }
+
+        #[method(checkHealth:)]
+        fn check_health(&self, _sender: &NSObject) {
+            info!("Run Checks clicked");
+            send_action(MenuAction::CheckHealth);
+        }
+
+        #[method(retryRefresh:)]
+        fn retry_refresh(&self, _sender: &NSObject) {
+            info!("Try Again Now clicked");
+            send_action(MenuAction::RetryRefresh);
         }
 
         #[method(toggleAutoLaunch:)]
@@ -161,10 +608,207 @@ declare_class!(
             }
         }
 
+        #[method(toggleProfileDetails:)]
+        fn toggle_profile_details(&self, _sender: &NSObject) {
+            info!("Toggle Profile Details clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().show_profile_details;
+                send_action(MenuAction::ToggleProfileDetails(!current));
+            }
+        }
+
+        #[method(toggleQuitWarning:)]
+        fn toggle_quit_warning(&self, _sender: &NSObject) {
+            info!("Toggle Quit Warning clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().warn_active_roles_on_quit;
+                send_action(MenuAction::ToggleQuitWarning(!current));
+            }
+        }
+
+        #[method(toggleRestoreSession:)]
+        fn toggle_restore_session(&self, _sender: &NSObject) {
+            info!("Toggle Restore Session clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().restore_session_on_launch;
+                send_action(MenuAction::ToggleRestoreSession(!current));
+            }
+        }
+
+        #[method(toggleAutoLoadRoles:)]
+        fn toggle_auto_load_roles(&self, _sender: &NSObject) {
+            info!("Toggle Auto-Load Roles clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().auto_load_roles;
+                send_action(MenuAction::ToggleAutoLoadRoles(!current));
+            }
+        }
+
+        #[method(toggleRestoreNotification:)]
+        fn toggle_restore_notification(&self, _sender: &NSObject) {
+            info!("Toggle Restore Notification clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().notify_session_restored;
+                send_action(MenuAction::ToggleRestoreNotification(!current));
+            }
+        }
+
+        #[method(setNotificationSound:)]
+        fn set_notification_sound(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            if let Some((event, sound)) = decode_sound_menu_tag(tag) {
+                info!("Sound for {:?} set to {:?}", event, sound);
+                send_action(MenuAction::SetNotificationSound { event, sound });
+            }
+        }
+
+        #[method(toggleSuccessAnimation:)]
+        fn toggle_success_animation(&self, _sender: &NSObject) {
+            info!("Toggle Success Animation clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().animate_on_success;
+                send_action(MenuAction::ToggleSuccessAnimation(!current));
+            }
+        }
+
+        #[method(toggleCriticalFlash:)]
+        fn toggle_critical_flash(&self, _sender: &NSObject) {
+            info!("Toggle Critical Flash clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().flash_on_critical_events;
+                send_action(MenuAction::ToggleCriticalFlash(!current));
+            }
+        }
+
+        #[method(toggleFocusBreakthrough:)]
+        fn toggle_focus_breakthrough(&self, _sender: &NSObject) {
+            info!("Toggle Focus Breakthrough clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().time_sensitive_during_focus;
+                send_action(MenuAction::ToggleFocusBreakthrough(!current));
+            }
+        }
+
+        #[method(toggleRoleStatusItems:)]
+        fn toggle_role_status_items(&self, _sender: &NSObject) {
+            info!("Toggle Role Status Items clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().status_item_per_role;
+                send_action(MenuAction::ToggleRoleStatusItems(!current));
+            }
+        }
+
+        #[method(toggleKeyVaultToken:)]
+        fn toggle_key_vault_token(&self, _sender: &NSObject) {
+            info!("Toggle Key Vault Token clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().offer_key_vault_token;
+                send_action(MenuAction::ToggleKeyVaultToken(!current));
+            }
+        }
+
+        #[method(toggleExtensionAtExpiry:)]
+        fn toggle_extension_at_expiry(&self, _sender: &NSObject) {
+            info!("Toggle Extension At Expiry clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().offer_extension_at_expiry;
+                send_action(MenuAction::ToggleExtensionAtExpiry(!current));
+            }
+        }
+
+        #[method(toggleSkipGroupLookup:)]
+        fn toggle_skip_group_lookup(&self, _sender: &NSObject) {
+            info!("Toggle Skip Group Lookup clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().skip_group_lookup;
+                send_action(MenuAction::ToggleSkipGroupLookup(!current));
+            }
+        }
+
+        #[method(toggleAdminView:)]
+        fn toggle_admin_view(&self, _sender: &NSObject) {
+            info!("Toggle Admin View clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().admin_view;
+                send_action(MenuAction::ToggleAdminView(!current));
+            }
+        }
+
+        #[method(toggleAdminViewSubscription:)]
+        fn toggle_admin_view_subscription(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            if let Some(subscription_id) = builder::admin_view_callback(tag) {
+                info!("Admin view subscription {} toggled", subscription_id);
+                send_action(MenuAction::ToggleAdminViewSubscription { subscription_id });
+            }
+        }
+
+        #[method(setSkipDeniedSubscriptions:)]
+        fn set_skip_denied_subscriptions(&self, sender: &NSMenuItem) {
+            let hours = unsafe { sender.tag() };
+            info!("Skip subscriptions without PIM set to {} hours", hours);
+            send_action(MenuAction::SetSkipDeniedSubscriptionsHours(hours as u32));
+        }
+
+        #[method(setRolePageSize:)]
+        fn set_role_page_size(&self, sender: &NSMenuItem) {
+            let size = unsafe { sender.tag() };
+            info!("Split long role lists set to {} roles", size);
+            send_action(MenuAction::SetRolePageSize(size as u32));
+        }
+
+        #[method(setEndOfDaySummary:)]
+        fn set_end_of_day_summary(&self, sender: &NSMenuItem) {
+            let minutes = unsafe { sender.tag() };
+            // The tag is minutes after midnight, negative for off
+            let time = u32::try_from(minutes)
+                .ok()
+                .and_then(|m| NaiveTime::from_hms_opt(m / 60, m % 60, 0));
+            info!("End-of-day summary set to {:?}", time);
+            send_action(MenuAction::SetEndOfDaySummary(time));
+        }
+
+        #[method(setTokenWarning:)]
+        fn set_token_warning(&self, sender: &NSMenuItem) {
+            let minutes = unsafe { sender.tag() };
+            info!("Token warning threshold set to {} min", minutes);
+            send_action(MenuAction::SetTokenWarningMinutes(minutes as u32));
+        }
+
+        #[method(setTokenCritical:)]
+        fn set_token_critical(&self, sender: &NSMenuItem) {
+            let minutes = unsafe { sender.tag() };
+            info!("Token critical threshold set to {} min", minutes);
+            send_action(MenuAction::SetTokenCriticalMinutes(minutes as u32));
+        }
+
+        #[method(toggleMenuBarCountdown:)]
+        fn toggle_menu_bar_countdown(&self, _sender: &NSObject) {
+            info!("Toggle Menu Bar Countdown clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().show_menu_bar_countdown;
+                send_action(MenuAction::ToggleMenuBarCountdown(!current));
+            }
+        }
+
+        #[method(toggleRefreshAtWarning:)]
+        fn toggle_refresh_at_warning(&self, _sender: &NSObject) {
+            info!("Toggle Refresh At Warning clicked");
+            if let Some(state) = get_app_state() {
+                let current = state.get_settings().refresh_at_token_warning;
+                send_action(MenuAction::ToggleRefreshAtWarning(!current));
+            }
+        }
+
         #[method(clearData:)]
         fn clear_data(&self, _sender: &NSObject) {
             info!("Clear Data clicked");
-            send_action(MenuAction::ClearData);
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+            if let Some(categories) = dialogs::prompt_clear_data(mtm) {
+                send_action(MenuAction::ClearData(categories));
+            }
         }
 
         #[method(cancelSignIn:)]
@@ -178,6 +822,298 @@ declare_class!(
             info!("Refresh PIM Roles clicked");
             send_action(MenuAction::RefreshPimRoles);
         }
+
+        #[method(recheckSkippedSubscriptions:)]
+        fn recheck_skipped_subscriptions(&self, _sender: &NSObject) {
+            info!("Recheck Skipped Subscriptions clicked");
+            send_action(MenuAction::RecheckSkippedSubscriptions);
+        }
+
+        #[method(openPimPortal:)]
+        fn open_pim_portal(&self, _sender: &NSObject) {
+            info!("Open PIM Portal clicked");
+            if let Err(e) = open::that(palette::PIM_PORTAL_URL) {
+                error!("Failed to open the Azure portal: {}", e);
+            }
+        }
+
+        #[method(showCommandPalette:)]
+        fn show_command_palette(&self, _sender: &NSObject) {
+            info!("Command Palette clicked");
+            let (Some(mtm), Some(state)) = (MainThreadMarker::new(), get_app_state()) else {
+                return;
+            };
+            let pim_state = state.get_pim_state();
+            let offline = state.get_auth_state() == AuthState::Offline;
+            let entries = palette::palette_entries(&pim_state, offline);
+            let titles: Vec<&str> = entries.iter().map(|e| e.title.as_str()).collect();
+            let Some(query) = dialogs::prompt_command(mtm, &titles) else {
+                return;
+            };
+            match palette::best_match(&entries, &query) {
+                Some(entry) => {
+                    info!("Running palette command: {}", entry.title);
+                    run_palette_command(mtm, &entry.command, &pim_state);
+                }
+                None => updates::show_action_outcome(
+                    "Command Palette",
+                    &ActionOutcome::Failed(format!("No command matches \"{}\"", query)),
+                ),
+            }
+        }
+
+        #[method(chooseFavoritesFile:)]
+        fn choose_favorites_file(&self, _sender: &NSObject) {
+            info!("Choose Favorites File clicked");
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+            let current = pim::settings::get_custom_settings_path();
+            if let Some(path) = dialogs::prompt_favorites_file(mtm, current.as_deref()) {
+                send_action(MenuAction::SetFavoritesFile { path });
+            }
+        }
+
+        #[method(copyArmConsentUrl:)]
+        fn copy_arm_consent_url(&self, _sender: &NSObject) {
+            info!("Copy Admin Consent URL clicked");
+            let status = get_app_state().map(|s| s.get_pim_state().api_status);
+            if let Some(PimApiStatus::ArmPermissionMissing { consent_url }) = status {
+                copy_text_to_clipboard(&consent_url);
+                info!("Admin consent URL copied to clipboard");
+            }
+        }
+
+        #[method(activatePlan:)]
+        fn activate_plan(&self, sender: &NSMenuItem) {
+            let idx = unsafe { sender.tag() } as usize;
+            info!("Activate Plan clicked");
+            let plan = get_app_state()
+                .and_then(|s| s.get_pim_state().settings.elevation_plans.get(idx).cloned());
+            if let Some(plan) = plan {
+                send_action(MenuAction::ActivatePlan { name: plan.name });
+            }
+        }
+
+        #[method(deactivateAllRoles:)]
+        fn deactivate_all_roles(&self, _sender: &NSObject) {
+            info!("Deactivate All Roles clicked");
+            send_deactivate_all();
+        }
+
+        #[method(savePlan:)]
+        fn save_plan(&self, _sender: &NSObject) {
+            info!("Save Plan clicked");
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+            if let Some(draft) = dialogs::prompt_plan(mtm) {
+                send_action(MenuAction::SavePlan { draft });
+            }
+        }
+
+        #[method(extendRole:)]
+        fn extend_role(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Need More Time clicked");
+            if let Some((role_key, minutes)) = builder::more_time_callback(tag) {
+                send_action(MenuAction::ExtendRole { role_key, minutes });
+            }
+        }
+
+        #[method(deactivateRole:)]
+        fn deactivate_role(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Deactivate Now clicked");
+            if let Some(role_key) = builder::deactivate_callback(tag) {
+                send_deactivate_role(role_key);
+            }
+        }
+
+        #[method(setDeactivationReminder:)]
+        fn set_deactivation_reminder(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Deactivation reminder clicked");
+            if let Some((role_key, minutes)) = builder::reminder_callback(tag) {
+                send_action(MenuAction::SetDeactivationReminder { role_key, minutes });
+            }
+        }
+
+        #[method(addSessionNote:)]
+        fn add_session_note(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Add Note clicked");
+            let (Some(mtm), Some((assignment_id, role_text))) =
+                (MainThreadMarker::new(), builder::note_callback(tag))
+            else {
+                return;
+            };
+            if let Some(text) = dialogs::prompt_session_note(mtm, &role_text) {
+                send_action(MenuAction::AddSessionNote {
+                    assignment_id,
+                    text,
+                });
+            }
+        }
+
+        #[method(copySessionNotes:)]
+        fn copy_session_notes(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Copy Notes clicked");
+            let (Some((assignment_id, _)), Some(state)) =
+                (builder::note_callback(tag), get_app_state())
+            else {
+                return;
+            };
+            if let Some(writeup) = state.get_pim_state().session_notes.writeup(&assignment_id) {
+                copy_text_to_clipboard(&writeup);
+            }
+        }
+
+        #[method(scheduleDeactivation:)]
+        fn schedule_deactivation(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Deactivate At clicked");
+            let (Some(mtm), Some((role_key, role_text))) =
+                (MainThreadMarker::new(), builder::schedule_callback(tag))
+            else {
+                return;
+            };
+            let Some(time) = dialogs::prompt_deactivation_time(mtm, &role_text) else {
+                return;
+            };
+            if let Some(at) = schedule::next_occurrence(time, &Local::now()) {
+                send_action(MenuAction::ScheduleDeactivation {
+                    role_key,
+                    at: Some(at),
+                });
+            }
+        }
+
+        #[method(cancelScheduledDeactivation:)]
+        fn cancel_scheduled_deactivation(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Cancel Scheduled Deactivation clicked");
+            if let Some((role_key, _)) = builder::schedule_callback(tag) {
+                send_action(MenuAction::ScheduleDeactivation { role_key, at: None });
+            }
+        }
+
+        #[method(scheduleActivation:)]
+        fn schedule_activation(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Schedule Activation clicked");
+            let (Some(mtm), Some((role_key, role_text))) =
+                (MainThreadMarker::new(), builder::schedule_callback(tag))
+            else {
+                return;
+            };
+            let Some(start_at) = dialogs::prompt_activation_start(mtm, &role_text) else {
+                return;
+            };
+            let presets = get_app_state()
+                .map(|s| s.get_pim_state().settings.all_presets())
+                .unwrap_or_default();
+            let choices: Vec<&str> = presets.iter().map(|p| p.justification.as_str()).collect();
+            if let Some(justification) = dialogs::prompt_justification(mtm, &role_text, &choices) {
+                let options = ActivationOptions {
+                    start_at: Some(start_at),
+                    ..Default::default()
+                };
+                request_activation(mtm, role_key, justification, options);
+            }
+        }
+
+        #[method(cancelScheduledActivation:)]
+        fn cancel_scheduled_activation(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Cancel Activation clicked");
+            if let Some(request_id) = builder::scheduled_activation_callback(tag) {
+                send_action(MenuAction::CancelScheduledActivation { request_id });
+            }
+        }
+
+        #[method(activateRole:)]
+        fn activate_role(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Justification preset clicked");
+            if let Some(mtm) = MainThreadMarker::new() {
+                builder::execute_preset_callback(mtm, tag);
+            }
+        }
+
+        #[method(toggleFavorite:)]
+        fn toggle_favorite(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Toggle Favorite clicked");
+            builder::execute_favorite_callback(tag);
+        }
+
+        #[method(checkActivation:)]
+        fn check_activation(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Check Activation clicked");
+            builder::execute_check_callback(tag);
+        }
+
+        #[method(setRoleDuration:)]
+        fn set_role_duration(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Activation duration clicked");
+            let (Some(mtm), Some((role_key, minutes))) =
+                (MainThreadMarker::new(), builder::duration_callback(tag))
+            else {
+                return;
+            };
+            let Some(minutes) = minutes.or_else(|| dialogs::prompt_activation_duration(mtm)) else {
+                return;
+            };
+            send_action(MenuAction::SetRoleDuration { role_key, minutes });
+        }
+
+        #[method(reviewApproval:)]
+        fn review_approval(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Review request clicked");
+            let (Some(mtm), Some((request_id, result))) =
+                (MainThreadMarker::new(), builder::review_callback(tag))
+            else {
+                return;
+            };
+            let Some(request) = get_app_state().and_then(|s| {
+                s.get_pim_state()
+                    .approval_requests
+                    .into_iter()
+                    .find(|r| r.is(&request_id))
+            }) else {
+                return;
+            };
+            if let Some(justification) =
+                dialogs::prompt_review_justification(mtm, &request.menu_title(), result)
+            {
+                send_action(MenuAction::ReviewApproval {
+                    request_id,
+                    result,
+                    justification,
+                });
+            }
+        }
+
+        #[method(editRoleLabel:)]
+        fn edit_role_label(&self, sender: &NSMenuItem) {
+            let tag = unsafe { sender.tag() };
+            info!("Set Label clicked");
+            let (Some(mtm), Some((role_key, role_text))) =
+                (MainThreadMarker::new(), builder::label_callback(tag))
+            else {
+                return;
+            };
+            let current = get_app_state()
+                .and_then(|s| s.get_pim_settings().label_for(&role_key).map(str::to_string));
+            if let Some(label) = dialogs::prompt_role_label(mtm, &role_text, current.as_deref()) {
+                send_action(MenuAction::SetRoleLabel { role_key, label });
+            }
+        }
     }
 );
 
@@ -189,37 +1125,39 @@ impl MenuActionTarget {
     }
 }
 
-/// Copy the access token to the clipboard.
+/// Copy the access token to the clipboard in `format`.
 ///
-/// Schedules automatic clearing of the clipboard after 2 minutes.
-pub fn copy_token_to_clipboard(_mtm: MainThreadMarker) {
-    match keychain::get_access_token() {
-        Ok(token) => {
-            unsafe {
-                let pasteboard = NSPasteboard::generalPasteboard();
-                pasteboard.clearContents();
+/// Callers should follow up with [`schedule_clipboard_clear`].
+pub fn copy_token_to_clipboard(
+    _mtm: MainThreadMarker,
+    format: &TokenFormat,
+) -> Result<(), KeychainError> {
+    let token = keychain::get_access_token()?;
+    copy_text_to_clipboard(&format.render(&token));
 
-                let ns_token = NSString::from_str(&token);
+    info!("Access token copied to clipboard");
+    Ok(())
+}
 
-                // Use setString:forType: with the string type
-                // NSPasteboardTypeString is "public.utf8-plain-text"
-                let type_str = NSString::from_str("public.utf8-plain-text");
-                pasteboard.setString_forType(&ns_token, &type_str);
-            }
+/// Replace the clipboard contents with plain text. Must be called on the main thread.
+pub fn copy_text_to_clipboard(text: &str) {
+    unsafe {
+        let pasteboard = NSPasteboard::generalPasteboard();
+        pasteboard.clearContents();
 
-            info!("Access token copied to clipboard");
+        let ns_text = NSString::from_str(text);
 
-            // Schedule clipboard clear after 2 minutes
-            schedule_clipboard_clear();
-        }
-        Err(e) => {
-            error!("Failed to get access token: {}", e);
-        }
+        // Use setString:forType: with the string type
+        // NSPasteboardTypeString is "public.utf8-plain-text"
+        let type_str = NSString::from_str("public.utf8-plain-text");
+        pasteboard.setString_forType(&ns_text, &type_str);
     }
 }
 
 /// Schedule clearing the clipboard after 2 minutes.
-fn schedule_clipboard_clear() {
+///
+/// Must be called from within the Tokio runtime.
+pub fn schedule_clipboard_clear() {
     tokio::spawn(async {
         tokio::time::sleep(std::time::Duration::from_secs(120)).await;
 
@@ -243,4 +1181,22 @@ mod tests {
         let action = MenuAction::SignIn;
         assert!(matches!(action, MenuAction::SignIn));
     }
+
+    #[test]
+    fn test_action_requests_get_unique_ids() {
+        let first = ActionRequest::new(MenuAction::SignIn, None);
+        let second = ActionRequest::new(MenuAction::SignIn, None);
+        assert_ne!(first.id, second.id);
+    }
+
+    #[test]
+    fn test_sound_menu_tag_roundtrip() {
+        for event in NotificationEvent::ALL {
+            for sound in NotificationSound::ALL {
+                let tag = sound_menu_tag(event, sound);
+                assert_eq!(decode_sound_menu_tag(tag), Some((event, sound)));
+            }
+        }
+        assert_eq!(decode_sound_menu_tag(999), None);
+    }
 }
@@ -0,0 +1,1150 @@
+//! Modal dialogs shown from the menu bar.
+//!
+//! All functions here must be called on the main thread.
+
+use crate::auth::oauth::scope_display_name;
+use crate::duration::{format_minutes, parse_minutes};
+use crate::pim;
+use crate::pim::policy::{ActivationCheck, PolicyRemedy, PolicyRule};
+use crate::pim::schedule::{default_start, parse_time_of_day};
+use crate::settings::DataCategory;
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use objc2::runtime::NSObjectProtocol;
+use objc2::sel;
+use objc2_app_kit::{
+    NSAlert, NSAlertFirstButtonReturn, NSAlertSecondButtonReturn, NSAlertStyle, NSApplication,
+    NSButton, NSComboBox, NSControlStateValueOn, NSDatePicker, NSDatePickerElementFlags,
+    NSDatePickerStyle, NSPasteboard, NSTextField, NSView,
+};
+use objc2_foundation::{MainThreadMarker, NSDate, NSPoint, NSRect, NSSize, NSString};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+/// Maximum report length included in an email draft (mail clients truncate long URLs).
+const MAX_EMAILED_REPORT_LEN: usize = 4000;
+
+/// Longest activation PIM allows, in minutes.
+const MAX_ACTIVATION_MINUTES: u32 = 24 * 60;
+
+/// Build the step-by-step guidance for registering the redirect URI.
+pub fn redirect_uri_help_text(redirect_uri: &str) -> String {
+    format!(
+        "The Azure AD app registration does not allow this app's redirect URI (AADSTS50011).\n\n\
+         To fix it:\n\
+         1. Open the Azure Portal and go to Microsoft Entra ID → App registrations.\n\
+         2. Select the app registration used by azurepim.\n\
+         3. Open Authentication and click \"Add a platform\" → \"Mobile and desktop applications\".\n\
+         4. Add this redirect URI exactly:\n\n\
+         {}\n\n\
+         5. Save, then sign in again.",
+        redirect_uri
+    )
+}
+
+/// Show setup guidance when the app registration is missing the redirect URI.
+pub fn show_redirect_uri_help(mtm: MainThreadMarker, redirect_uri: &str) {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Redirect URI not registered"));
+        alert.setInformativeText(&NSString::from_str(&redirect_uri_help_text(redirect_uri)));
+        alert.addButtonWithTitle(&NSString::from_str("Copy Redirect URI"));
+        alert.addButtonWithTitle(&NSString::from_str("Close"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+
+    if response == NSAlertFirstButtonReturn {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard();
+            pasteboard.clearContents();
+            let type_str = NSString::from_str("public.utf8-plain-text");
+            pasteboard.setString_forType(&NSString::from_str(redirect_uri), &type_str);
+        }
+        info!("Redirect URI copied to clipboard");
+    }
+}
+
+/// Build the title, explanation, and retry button title for a failed policy rule.
+///
+/// There is no retry button if the rule can't be fixed from here.
+pub fn policy_remedy_text(
+    role_name: &str,
+    remedy: &PolicyRemedy,
+) -> (String, String, Option<String>) {
+    match remedy {
+        PolicyRemedy::MaxDuration(minutes) => {
+            let max = format_minutes(*minutes);
+            (
+                format!("Max duration for this role is {}", max),
+                format!(
+                    "{} can't be activated for longer than {}. Retry with {}?",
+                    role_name, max, max
+                ),
+                Some(format!("Retry with {}", max)),
+            )
+        }
+        PolicyRemedy::EnterJustification => (
+            "Justification required".to_string(),
+            format!(
+                "{} requires a justification. Enter one to retry.",
+                role_name
+            ),
+            Some("Retry".to_string()),
+        ),
+        PolicyRemedy::EnterDetailedJustification(min_length) => (
+            "Justification doesn't meet your team's standard".to_string(),
+            format!(
+                "{} needs a written justification of at least {} characters. Presets aren't accepted. Enter one to retry.",
+                role_name, min_length
+            ),
+            Some("Retry".to_string()),
+        ),
+        PolicyRemedy::EnterTicketNumber => (
+            "Ticket number required".to_string(),
+            format!(
+                "{} requires a ticket number. Enter one to retry.",
+                role_name
+            ),
+            Some("Retry".to_string()),
+        ),
+        PolicyRemedy::Explain(rule) => {
+            let hint = match rule {
+                PolicyRule::Mfa => {
+                    "\n\nSign out and sign in again with multi-factor authentication, then retry."
+                }
+                _ => "",
+            };
+            (
+                "Activation blocked by role policy".to_string(),
+                format!("{} could not be activated: {}.{}", role_name, rule, hint),
+                None,
+            )
+        }
+    }
+}
+
+/// Explain a failed policy rule and offer a corrected retry.
+///
+/// Returns `None` if the user cancels, otherwise the entered text (empty if
+/// the remedy needs no input).
+pub fn show_policy_remedy(
+    mtm: MainThreadMarker,
+    role_name: &str,
+    remedy: &PolicyRemedy,
+) -> Option<String> {
+    let (message, informative, retry_title) = policy_remedy_text(role_name, remedy);
+    let needs_input = matches!(
+        remedy,
+        PolicyRemedy::EnterJustification
+            | PolicyRemedy::EnterDetailedJustification(_)
+            | PolicyRemedy::EnterTicketNumber
+    );
+
+    let (response, input) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&message));
+        alert.setInformativeText(&NSString::from_str(&informative));
+
+        let input = needs_input.then(|| {
+            let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(280.0, 24.0));
+            let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+            alert.setAccessoryView(Some(&field));
+            field
+        });
+
+        match &retry_title {
+            Some(title) => {
+                alert.addButtonWithTitle(&NSString::from_str(title));
+                alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+            }
+            None => {
+                alert.addButtonWithTitle(&NSString::from_str("OK"));
+            }
+        }
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), input)
+    };
+
+    if retry_title.is_none() || response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    match input {
+        Some(field) => {
+            let text = unsafe { field.stringValue() }.to_string();
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        }
+        None => Some(String::new()),
+    }
+}
+
+/// Build the title and explanation of an activation check result.
+pub fn activation_check_text(
+    role_name: &str,
+    duration_minutes: u32,
+    check: &ActivationCheck,
+) -> (String, String) {
+    match check {
+        ActivationCheck::WouldSucceed => (
+            format!("{} can be activated", role_name),
+            format!(
+                "Activating for {} would succeed. Nothing was activated.",
+                format_minutes(duration_minutes)
+            ),
+        ),
+        ActivationCheck::AlreadyActive => (
+            format!("{} is already active", role_name),
+            "A new activation would be rejected until the current one ends.".to_string(),
+        ),
+        ActivationCheck::PolicyFailed(rules) => {
+            let rules: Vec<String> = rules.iter().map(|rule| format!("• {}", rule)).collect();
+            (
+                format!("{} would be blocked by its policy", role_name),
+                format!(
+                    "Activating for {} would fail these rules:\n{}",
+                    format_minutes(duration_minutes),
+                    rules.join("\n")
+                ),
+            )
+        }
+        ActivationCheck::Rejected(reason) => (
+            format!("{} can't be activated", role_name),
+            format!("Azure rejected the request: {}", reason),
+        ),
+    }
+}
+
+/// Report the result of checking an activation without activating.
+pub fn show_activation_check(
+    mtm: MainThreadMarker,
+    role_name: &str,
+    duration_minutes: u32,
+    check: &ActivationCheck,
+) {
+    let (message, informative) = activation_check_text(role_name, duration_minutes, check);
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&message));
+        alert.setInformativeText(&NSString::from_str(&informative));
+        alert.addButtonWithTitle(&NSString::from_str("OK"));
+
+        bring_app_to_front(mtm);
+        alert.runModal();
+    }
+}
+
+/// Ask where favorites and presets should be stored.
+///
+/// Returns `None` if the user cancels, otherwise the chosen file (`None` for
+/// the default location).
+pub fn prompt_favorites_file(
+    mtm: MainThreadMarker,
+    current: Option<&Path>,
+) -> Option<Option<PathBuf>> {
+    let (response, field) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Favorites File"));
+        alert.setInformativeText(&NSString::from_str(
+            "Store favorites and presets in a synced folder, such as iCloud Drive, \
+             to use them on all your Macs. Enter a file or folder path, or leave \
+             empty for the default location.",
+        ));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        if let Some(path) = current {
+            field.setStringValue(&NSString::from_str(&path.display().to_string()));
+        }
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Save"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), field)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    let input = unsafe { field.stringValue() }.to_string();
+    Some(pim::settings::parse_settings_location(&input))
+}
+
+/// Ask for the name, duration and justification of a plan of the active roles.
+///
+/// Asks again until the name is set and the duration, if any, reads as one
+/// PIM allows. Returns `None` if the user cancels.
+pub fn prompt_plan(mtm: MainThreadMarker) -> Option<pim::PlanDraft> {
+    const ROW_HEIGHT: f64 = 30.0;
+    let informative = "Activating the plan later activates all roles that are active now. \
+                       A plan with the same name is replaced. In the justification, {plan} \
+                       and {role} are replaced.";
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Save Active Roles as Plan"));
+        alert.setInformativeText(&NSString::from_str(informative));
+
+        let placeholders = [
+            "Name, e.g. Prod incident",
+            "Duration (optional), e.g. 2h",
+            "Justification (optional), e.g. {plan}: {role}",
+        ];
+        let height = ROW_HEIGHT * placeholders.len() as f64;
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(320.0, height));
+        let container = NSView::initWithFrame(mtm.alloc(), frame);
+        let fields: Vec<_> = placeholders
+            .iter()
+            .enumerate()
+            .map(|(row, placeholder)| {
+                // Rows from the top; AppKit's y axis points up
+                let y = height - ROW_HEIGHT * (row + 1) as f64;
+                let frame = NSRect::new(NSPoint::new(0.0, y), NSSize::new(320.0, 24.0));
+                let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+                field.setPlaceholderString(Some(&NSString::from_str(placeholder)));
+                container.addSubview(&field);
+                field
+            })
+            .collect();
+        alert.setAccessoryView(Some(&container));
+        alert.addButtonWithTitle(&NSString::from_str("Save"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let [name, duration, justification] =
+                [&fields[0], &fields[1], &fields[2]].map(|f| f.stringValue().to_string());
+            let (name, duration, justification) =
+                (name.trim(), duration.trim(), justification.trim());
+            let duration_minutes = if duration.is_empty() {
+                None
+            } else {
+                match parse_minutes(duration).filter(|&m| m <= MAX_ACTIVATION_MINUTES) {
+                    Some(minutes) => Some(minutes),
+                    None => {
+                        alert.setInformativeText(&NSString::from_str(&format!(
+                            "{}\n\n\"{}\" isn't a duration of up to {}. Use minutes or hours, e.g. 90 or 1h 30m.",
+                            informative,
+                            duration,
+                            format_minutes(MAX_ACTIVATION_MINUTES)
+                        )));
+                        continue;
+                    }
+                }
+            };
+            if name.is_empty() {
+                alert.setInformativeText(&NSString::from_str(&format!(
+                    "{}\n\nName the plan.",
+                    informative
+                )));
+                continue;
+            }
+            return Some(pim::PlanDraft {
+                name: name.to_string(),
+                duration_minutes,
+                justification: (!justification.is_empty()).then(|| justification.to_string()),
+            });
+        }
+    }
+}
+
+/// Build the title and summary of an activation confirmation.
+pub fn activation_confirmation_text(
+    role: &pim::EligibleRole,
+    duration_minutes: u32,
+    justification: &str,
+    in_prod: bool,
+) -> (String, String) {
+    let message = if in_prod {
+        format!("Activate {} in PRODUCTION?", role.role_name)
+    } else {
+        format!("Activate {}?", role.role_name)
+    };
+    (
+        message,
+        format!(
+            "{}\nFor {}\n\"{}\"",
+            role.scope.breadcrumb(&role.subscription_name),
+            format_minutes(duration_minutes),
+            justification
+        ),
+    )
+}
+
+/// What the user chose when confirming an activation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActivationConfirmation {
+    /// Skip this confirmation for the role from now on.
+    pub skip_from_now_on: bool,
+    /// Time of day to deactivate the role at, if one was entered.
+    pub deactivate_at: Option<NaiveTime>,
+}
+
+/// Explanation shown when a typed time of day can't be read.
+fn invalid_time_text(input: &str) -> String {
+    format!("\"{}\" isn't a time of day. Use HH:MM, e.g. 17:00.", input)
+}
+
+/// Ask before activating a role, showing where, how long, and why.
+///
+/// Roles in production get a critical alert that can't be turned off.
+///
+/// The user may also enter a time of day to deactivate the role at.
+/// Returns `None` if the user cancels.
+pub fn confirm_activation(
+    mtm: MainThreadMarker,
+    role: &pim::EligibleRole,
+    duration_minutes: u32,
+    justification: &str,
+    in_prod: bool,
+) -> Option<ActivationConfirmation> {
+    let (message, informative) =
+        activation_confirmation_text(role, duration_minutes, justification, in_prod);
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&message));
+        alert.setInformativeText(&NSString::from_str(&informative));
+        alert.addButtonWithTitle(&NSString::from_str("Activate"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+        let suppression = if in_prod {
+            alert.setAlertStyle(NSAlertStyle::Critical);
+            None
+        } else {
+            alert.setShowsSuppressionButton(true);
+            alert.suppressionButton()
+        };
+        if let Some(button) = &suppression {
+            button.setTitle(&NSString::from_str("Don't show again for this role"));
+        }
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(280.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        field.setPlaceholderString(Some(&NSString::from_str(
+            "Deactivate at (optional), e.g. 17:00",
+        )));
+        alert.setAccessoryView(Some(&field));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let input = field.stringValue().to_string();
+            let input = input.trim();
+            let deactivate_at = match parse_time_of_day(input) {
+                Some(time) => Some(time),
+                None if input.is_empty() => None,
+                None => {
+                    alert.setInformativeText(&NSString::from_str(&format!(
+                        "{}\n\n{}",
+                        informative,
+                        invalid_time_text(input)
+                    )));
+                    continue;
+                }
+            };
+            return Some(ActivationConfirmation {
+                skip_from_now_on: suppression
+                    .is_some_and(|button| button.state() == NSControlStateValueOn),
+                deactivate_at,
+            });
+        }
+    }
+}
+
+/// Ask for the URL a curl snippet should call with the access token.
+///
+/// Returns `None` if the user cancels or enters something that isn't an http(s) URL.
+pub fn prompt_curl_url(mtm: MainThreadMarker) -> Option<String> {
+    let (response, field) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Copy curl Snippet"));
+        alert.setInformativeText(&NSString::from_str(
+            "Enter the URL to call. The token is for Microsoft Graph, so other APIs will reject it.",
+        ));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        field.setPlaceholderString(Some(&NSString::from_str(
+            "https://graph.microsoft.com/v1.0/me",
+        )));
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Copy"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), field)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    let text = unsafe { field.stringValue() }.to_string();
+    let url = url::Url::parse(text.trim()).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+/// Ask for a note about what was done during an elevation.
+///
+/// Returns `None` if the user cancels or types nothing.
+pub fn prompt_session_note(mtm: MainThreadMarker, role_text: &str) -> Option<String> {
+    let (response, field) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&format!("Note for {}", role_text)));
+        alert.setInformativeText(&NSString::from_str(
+            "Record what you changed, e.g. for an incident writeup. Notes are kept on this Mac only.",
+        ));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Add Note"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), field)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    let text = unsafe { field.stringValue() }.to_string();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Ask for the reason to approve or deny another user's request.
+///
+/// Asks again until a reason is typed, since Azure requires one. Returns
+/// `None` if the user cancels.
+pub fn prompt_review_justification(
+    mtm: MainThreadMarker,
+    request_title: &str,
+    result: pim::ReviewResult,
+) -> Option<String> {
+    let verb = result.as_str();
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&format!("{} {}", verb, request_title)));
+        alert.setInformativeText(&NSString::from_str(
+            "The reason is recorded with the decision and shown to the requestor.",
+        ));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str(verb));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let text = field.stringValue().to_string();
+            let text = text.trim();
+            if !text.is_empty() {
+                return Some(text.to_string());
+            }
+            alert.setInformativeText(&NSString::from_str(
+                "Azure requires a reason for every decision.",
+            ));
+        }
+    }
+}
+
+/// Ask for the time of day to deactivate an active role at.
+///
+/// Asks again until the input reads as a time. Returns `None` if the user cancels.
+pub fn prompt_deactivation_time(mtm: MainThreadMarker, role_text: &str) -> Option<NaiveTime> {
+    let informative = "The role is deactivated at this time if it is still active, \
+                       even if you're away. Use HH:MM, e.g. 17:00.";
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&format!("Deactivate {} at", role_text)));
+        alert.setInformativeText(&NSString::from_str(informative));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(120.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        field.setPlaceholderString(Some(&NSString::from_str("17:00")));
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Schedule"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let input = field.stringValue().to_string();
+            match parse_time_of_day(&input) {
+                Some(time) => return Some(time),
+                None => {
+                    alert.setInformativeText(&NSString::from_str(&invalid_time_text(input.trim())))
+                }
+            }
+        }
+    }
+}
+
+/// Ask when a scheduled activation of a role should start, with a date picker.
+///
+/// Asks again until the picked time is still ahead. Returns `None` if the user cancels.
+pub fn prompt_activation_start(mtm: MainThreadMarker, role_text: &str) -> Option<DateTime<Utc>> {
+    let informative = "Azure activates the role at this time, e.g. for a maintenance window.";
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&format!("Activate {} at", role_text)));
+        alert.setInformativeText(&NSString::from_str(informative));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(200.0, 24.0));
+        let picker = NSDatePicker::initWithFrame(mtm.alloc(), frame);
+        picker.setDatePickerStyle(NSDatePickerStyle::TextFieldAndStepper);
+        picker.setDatePickerElements(
+            NSDatePickerElementFlags::NSDatePickerElementFlagYearMonthDay
+                | NSDatePickerElementFlags::NSDatePickerElementFlagHourMinute,
+        );
+        picker.setMinDate(Some(&NSDate::now()));
+        let start = default_start(&Local::now());
+        picker.setDateValue(&NSDate::dateWithTimeIntervalSince1970(
+            start.timestamp() as f64
+        ));
+        alert.setAccessoryView(Some(&picker));
+        alert.addButtonWithTitle(&NSString::from_str("Continue"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let picked = picker.dateValue().timeIntervalSince1970();
+            match DateTime::from_timestamp(picked as i64, 0).filter(|at| *at > Utc::now()) {
+                Some(at) => return Some(at),
+                None => alert.setInformativeText(&NSString::from_str(&format!(
+                    "{}\n\nThat time has passed. Pick a time ahead.",
+                    informative
+                ))),
+            }
+        }
+    }
+}
+
+/// Ask for a label to show next to a role, prefilled with its `current` one.
+///
+/// Returns `None` if the user cancels; an empty label removes it.
+pub fn prompt_role_label(
+    mtm: MainThreadMarker,
+    role_text: &str,
+    current: Option<&str>,
+) -> Option<String> {
+    let (response, field) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&format!("Label for {}", role_text)));
+        alert.setInformativeText(&NSString::from_str(
+            "Shown next to the role in the menu and the Command Palette, e.g. \"🔥 prod-payments\". \
+             Leave empty to remove the label.",
+        ));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(280.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        field.setStringValue(&NSString::from_str(current.unwrap_or_default()));
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Save"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), field)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    Some(unsafe { field.stringValue() }.to_string())
+}
+
+/// Ask for a custom activation duration.
+///
+/// Asks again until the input reads as a duration PIM allows. Returns `None`
+/// if the user cancels.
+pub fn prompt_activation_duration(mtm: MainThreadMarker) -> Option<u32> {
+    let informative = "Activate the role for this long from now on. The role's policy may \
+                       allow less. Use minutes or hours, e.g. 90 or 1h 30m.";
+    unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Activation Duration"));
+        alert.setInformativeText(&NSString::from_str(informative));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(120.0, 24.0));
+        let field = NSTextField::initWithFrame(mtm.alloc(), frame);
+        field.setPlaceholderString(Some(&NSString::from_str("1h 30m")));
+        alert.setAccessoryView(Some(&field));
+        alert.addButtonWithTitle(&NSString::from_str("Set"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        loop {
+            if alert.runModal() != NSAlertFirstButtonReturn {
+                return None;
+            }
+            let input = field.stringValue().to_string();
+            match parse_minutes(&input).filter(|&m| m <= MAX_ACTIVATION_MINUTES) {
+                Some(minutes) => return Some(minutes),
+                None => alert.setInformativeText(&NSString::from_str(&format!(
+                    "\"{}\" isn't a duration of up to {}. Use minutes or hours, e.g. 90 or 1h 30m.",
+                    input.trim(),
+                    format_minutes(MAX_ACTIVATION_MINUTES)
+                ))),
+            }
+        }
+    }
+}
+
+/// Ask for a palette command, offering `titles` for completion.
+///
+/// Returns `None` if the user cancels or types nothing.
+pub fn prompt_command(mtm: MainThreadMarker, titles: &[&str]) -> Option<String> {
+    prompt_with_choices(
+        mtm,
+        "Command Palette",
+        "Type part of a command or role, e.g. \"act contrib prod\" or \"copy token\".",
+        "Run",
+        titles,
+    )
+}
+
+/// Ask for the justification to activate a role with, offering the presets.
+pub fn prompt_justification(
+    mtm: MainThreadMarker,
+    role_title: &str,
+    presets: &[&str],
+) -> Option<String> {
+    prompt_with_choices(
+        mtm,
+        &format!("Activate {}", role_title),
+        "Why do you need this role? Pick a preset or type a justification.",
+        "Activate",
+        presets,
+    )
+}
+
+/// Run an alert with a combo box that completes from `choices` and accepts free text.
+fn prompt_with_choices(
+    mtm: MainThreadMarker,
+    message: &str,
+    informative: &str,
+    button: &str,
+    choices: &[&str],
+) -> Option<String> {
+    let (response, combo) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(message));
+        alert.setInformativeText(&NSString::from_str(informative));
+
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(360.0, 26.0));
+        let combo = NSComboBox::initWithFrame(mtm.alloc(), frame);
+        combo.setCompletes(true);
+        combo.setNumberOfVisibleItems(12);
+        for choice in choices {
+            combo.addItemWithObjectValue(&NSString::from_str(choice));
+        }
+        alert.setAccessoryView(Some(&combo));
+        alert.addButtonWithTitle(&NSString::from_str(button));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), combo)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    let text = unsafe { combo.stringValue() }.to_string();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Build the title and explanation of the prompt for missing permissions.
+pub fn consent_prompt_text(scopes: &[String]) -> (String, String) {
+    let names: Vec<_> = scopes.iter().map(|s| scope_display_name(s)).collect();
+    let noun = if scopes.len() == 1 {
+        "permission"
+    } else {
+        "permissions"
+    };
+    (
+        format!("Additional {} needed", noun),
+        format!(
+            "Azure PIM now needs {}. Grant access in your browser? You stay signed in.",
+            names.join(", ")
+        ),
+    )
+}
+
+/// Ask whether to consent to missing scopes. Returns true if the user agrees.
+pub fn confirm_consent(mtm: MainThreadMarker, scopes: &[String]) -> bool {
+    let (message, informative) = consent_prompt_text(scopes);
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&message));
+        alert.setInformativeText(&NSString::from_str(&informative));
+        alert.addButtonWithTitle(&NSString::from_str("Grant Access"));
+        alert.addButtonWithTitle(&NSString::from_str("Not Now"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertFirstButtonReturn
+}
+
+/// Remind the user to deactivate a role. Returns true to deactivate it now.
+pub fn confirm_deactivation_reminder(mtm: MainThreadMarker, role_text: &str) -> bool {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Done with your elevated role?"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "{} is still active. Deactivate it now if you no longer need it.",
+            role_text
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Deactivate"));
+        alert.addButtonWithTitle(&NSString::from_str("Keep"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertFirstButtonReturn
+}
+
+/// Warn that an active role already grants the role being activated.
+/// Returns true to activate it anyway.
+pub fn confirm_redundant_activation(
+    mtm: MainThreadMarker,
+    role_text: &str,
+    covering_text: &str,
+) -> bool {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("You already have this access"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "{} is active and includes {}. Activating it as well adds another \
+             approval and audit entry without granting anything new.",
+            covering_text, role_text
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Don't Activate"));
+        alert.addButtonWithTitle(&NSString::from_str("Activate Anyway"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertSecondButtonReturn
+}
+
+/// List the roles still active at the end of the day. Returns true to deactivate them all.
+pub fn confirm_end_of_day_deactivation(mtm: MainThreadMarker, roles: &[String]) -> bool {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Still elevated"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "These roles are still active:\n\n{}\n\nDeactivate them if you're done for the day.",
+            roles.join("\n")
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Deactivate All"));
+        alert.addButtonWithTitle(&NSString::from_str("Keep"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertFirstButtonReturn
+}
+
+/// Offer to extend a role that is about to expire. Returns true to extend it.
+pub fn confirm_extension(mtm: MainThreadMarker, role_text: &str, remaining: &str) -> bool {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Need more time?"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "{} expires in {}. Extend it to keep it active for another hour.",
+            role_text, remaining
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Extend by 1 Hour"));
+        alert.addButtonWithTitle(&NSString::from_str("Let It Expire"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertFirstButtonReturn
+}
+
+/// Offer to copy a Key Vault token after activating a Key Vault role.
+/// Returns true to copy it.
+pub fn confirm_copy_key_vault_token(mtm: MainThreadMarker, role_text: &str) -> bool {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Copy a Key Vault token?"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "{} is active. A token for Key Vault (vault.azure.net) is ready to \
+             read secrets, keys and certificates. New access can take a few \
+             minutes to reach the vault. The clipboard is cleared in 2 minutes.",
+            role_text
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Copy Token"));
+        alert.addButtonWithTitle(&NSString::from_str("Not Now"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+    response == NSAlertFirstButtonReturn
+}
+
+/// Ask which kinds of data to clear, all checked to start with.
+///
+/// Returns `None` if the user cancels or unchecks everything.
+pub fn prompt_clear_data(mtm: MainThreadMarker) -> Option<Vec<DataCategory>> {
+    const ROW_HEIGHT: f64 = 22.0;
+    let (response, checkboxes) = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Clear All Data"));
+        alert.setInformativeText(&NSString::from_str(
+            "Choose what to remove from this Mac. Roles stay active in Azure \
+             until they expire. To just switch accounts, use Sign Out, which \
+             keeps favorites, settings and cached roles.",
+        ));
+
+        let height = ROW_HEIGHT * DataCategory::ALL.len() as f64;
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(280.0, height));
+        let container = NSView::initWithFrame(mtm.alloc(), frame);
+        let checkboxes: Vec<(DataCategory, _)> = DataCategory::ALL
+            .iter()
+            .enumerate()
+            .map(|(row, &category)| {
+                let checkbox = NSButton::checkboxWithTitle_target_action(
+                    &NSString::from_str(category.label()),
+                    None,
+                    None,
+                    mtm,
+                );
+                // Rows from the top; AppKit's y axis points up
+                let y = height - ROW_HEIGHT * (row + 1) as f64;
+                checkbox.setFrame(NSRect::new(
+                    NSPoint::new(0.0, y),
+                    NSSize::new(280.0, ROW_HEIGHT),
+                ));
+                checkbox.setState(NSControlStateValueOn);
+                container.addSubview(&checkbox);
+                (category, checkbox)
+            })
+            .collect();
+        alert.setAccessoryView(Some(&container));
+        alert.addButtonWithTitle(&NSString::from_str("Clear"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        (alert.runModal(), checkboxes)
+    };
+
+    if response != NSAlertFirstButtonReturn {
+        return None;
+    }
+    let categories: Vec<DataCategory> = checkboxes
+        .into_iter()
+        .filter(|(_, checkbox)| unsafe { checkbox.state() } == NSControlStateValueOn)
+        .map(|(category, _)| category)
+        .collect();
+    (!categories.is_empty()).then_some(categories)
+}
+
+/// What to do about active roles when quitting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitChoice {
+    DeactivateAndQuit,
+    Quit,
+    Cancel,
+}
+
+/// Build the title of the quit warning.
+pub fn quit_warning_text(active_roles: usize) -> String {
+    let noun = if active_roles == 1 { "role" } else { "roles" };
+    format!("You still have {} active {}", active_roles, noun)
+}
+
+/// Warn that roles are still active and ask whether to deactivate them before quitting.
+pub fn confirm_quit_with_active_roles(mtm: MainThreadMarker, active_roles: usize) -> QuitChoice {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str(&quit_warning_text(active_roles)));
+        alert.setInformativeText(&NSString::from_str(
+            "Deactivate before quitting? Roles left active stay active in Azure until they expire.",
+        ));
+        alert.addButtonWithTitle(&NSString::from_str("Deactivate and Quit"));
+        alert.addButtonWithTitle(&NSString::from_str("Quit"));
+        alert.addButtonWithTitle(&NSString::from_str("Cancel"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+
+    if response == NSAlertFirstButtonReturn {
+        QuitChoice::DeactivateAndQuit
+    } else if response == NSAlertSecondButtonReturn {
+        QuitChoice::Quit
+    } else {
+        QuitChoice::Cancel
+    }
+}
+
+/// Offer to open or send a crash report from the previous run.
+pub fn show_crash_report_prompt(mtm: MainThreadMarker, report_path: &Path) {
+    let response = unsafe {
+        let alert = NSAlert::new(mtm);
+        alert.setMessageText(&NSString::from_str("Azure PIM quit unexpectedly"));
+        alert.setInformativeText(&NSString::from_str(&format!(
+            "A crash report was saved to:\n{}\n\nSending it helps fix the problem.",
+            report_path.display()
+        )));
+        alert.addButtonWithTitle(&NSString::from_str("Open Report"));
+        alert.addButtonWithTitle(&NSString::from_str("Send Report…"));
+        alert.addButtonWithTitle(&NSString::from_str("Ignore"));
+
+        bring_app_to_front(mtm);
+        alert.runModal()
+    };
+
+    let result = if response == NSAlertFirstButtonReturn {
+        open::that(report_path)
+    } else if response == NSAlertSecondButtonReturn {
+        let contents = std::fs::read_to_string(report_path).unwrap_or_default();
+        open::that(crash_report_mailto(&contents))
+    } else {
+        Ok(())
+    };
+
+    if let Err(e) = result {
+        error!("Failed to open crash report: {}", e);
+    }
+}
+
+/// Build a mailto: URL with the crash report as the message body.
+fn crash_report_mailto(report: &str) -> String {
+    let body: String = report.chars().take(MAX_EMAILED_REPORT_LEN).collect();
+    format!(
+        "mailto:?subject={}&body={}",
+        urlencoding::encode("Azure PIM crash report"),
+        urlencoding::encode(&body)
+    )
+}
+
+/// Activate the app so a modal dialog appears above other windows.
+///
+/// Menu bar apps are never frontmost on their own.
+fn bring_app_to_front(mtm: MainThreadMarker) {
+    let app = NSApplication::sharedApplication(mtm);
+    if app.respondsToSelector(sel!(activate)) {
+        unsafe { app.activate() };
+    } else {
+        #[allow(deprecated)]
+        app.activateIgnoringOtherApps(true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirect_uri_help_contains_uri() {
+        let text = redirect_uri_help_text("http://localhost:28491/callback");
+        assert!(text.contains("\n\nhttp://localhost:28491/callback\n\n"));
+        assert!(text.contains("AADSTS50011"));
+    }
+
+    #[test]
+    fn test_policy_remedy_text() {
+        let (message, _, retry) =
+            policy_remedy_text("Contributor", &PolicyRemedy::MaxDuration(120));
+        assert_eq!(message, "Max duration for this role is 2h");
+        assert_eq!(retry.as_deref(), Some("Retry with 2h"));
+
+        let (_, informative, retry) =
+            policy_remedy_text("Owner", &PolicyRemedy::Explain(PolicyRule::Mfa));
+        assert!(informative.contains("multi-factor"));
+        assert!(retry.is_none());
+    }
+
+    #[test]
+    fn test_activation_check_text() {
+        let (message, informative) =
+            activation_check_text("Contributor", 60, &ActivationCheck::WouldSucceed);
+        assert_eq!(message, "Contributor can be activated");
+        assert_eq!(
+            informative,
+            "Activating for 1h would succeed. Nothing was activated."
+        );
+
+        let check = ActivationCheck::PolicyFailed(vec![PolicyRule::Ticketing, PolicyRule::Mfa]);
+        let (message, informative) = activation_check_text("Owner", 90, &check);
+        assert_eq!(message, "Owner would be blocked by its policy");
+        assert_eq!(
+            informative,
+            "Activating for 1h 30m would fail these rules:\n\
+             • ticket number required\n\
+             • multi-factor authentication required"
+        );
+    }
+
+    #[test]
+    fn test_consent_prompt_text() {
+        let scopes = vec!["https://graph.microsoft.com/Directory.Read.All".to_string()];
+        let (message, informative) = consent_prompt_text(&scopes);
+        assert_eq!(message, "Additional permission needed");
+        assert!(informative.contains("needs Directory.Read.All."));
+    }
+
+    #[test]
+    fn test_activation_confirmation_text() {
+        let role = pim::EligibleRole {
+            id: "test-id".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id/resourceGroups/rg-web"
+                .parse()
+                .unwrap(),
+            principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        };
+        let (message, informative) = activation_confirmation_text(&role, 90, "INC-4711", false);
+        assert_eq!(message, "Activate Contributor?");
+        let (message, _) = activation_confirmation_text(&role, 90, "INC-4711", true);
+        assert_eq!(message, "Activate Contributor in PRODUCTION?");
+        assert_eq!(
+            informative,
+            "Tenant ▸ vipps-prod-001 ▸ rg-web\nFor 1h 30m\n\"INC-4711\""
+        );
+
+        let mut settings = pim::PimSettings::default();
+        assert!(settings.confirms_activation("key"));
+        settings.skip_activation_confirmation("key");
+        assert!(!settings.confirms_activation("key"));
+    }
+
+    #[test]
+    fn test_quit_warning_text() {
+        assert_eq!(quit_warning_text(1), "You still have 1 active role");
+        assert_eq!(quit_warning_text(2), "You still have 2 active roles");
+    }
+
+    #[test]
+    fn test_crash_report_mailto_truncates() {
+        let report = "x".repeat(MAX_EMAILED_REPORT_LEN + 100);
+        let url = crash_report_mailto(&report);
+        assert!(url.starts_with("mailto:?subject=Azure%20PIM%20crash%20report&body="));
+        assert_eq!(url.matches('x').count(), MAX_EMAILED_REPORT_LEN);
+    }
+}
@@ -2,7 +2,11 @@
 //!
 //! Provides the menu bar icon, dropdown menu, and state management.
 
+pub mod accessibility;
 pub mod builder;
 pub mod delegate;
+pub mod dialogs;
+pub mod model;
+pub mod palette;
 pub mod state;
 pub mod updates;
@@ -0,0 +1,2316 @@
+//! Menu contents as plain data.
+//!
+//! What the app shows for a given state is decided here, without AppKit: a
+//! [`MenuModel`] is built from the [`AppState`] and holds the menu entries
+//! and what the status items show, and the builder only renders it. Items
+//! carry a [`Command`] saying what choosing them does; the renderer maps it
+//! to a selector and tag. Other frontends can render the same model, e.g.
+//! as text with [`MenuModel::outline`].
+
+use crate::auth::graph::UserInfo;
+use crate::auth::token_format::CURL_ENDPOINTS;
+use crate::auth::token_manager::{
+    refresh_failures_text, retry_countdown_text, MAX_BACKGROUND_REFRESH_FAILURES,
+};
+use crate::clock::Clock;
+use crate::duration::{self, format_minutes, Style};
+use crate::error::ErrorClass;
+use crate::health::HealthReport;
+use crate::menubar::accessibility;
+use crate::menubar::state::{AppState, AuthState, PimState, Settings};
+use crate::notifications::{NotificationEvent, NotificationSound};
+use crate::pim::availability::{skip_period_label, SKIP_PERIOD_CHOICES};
+use crate::pim::environment::{environment_of, Environment, EnvironmentRule};
+use crate::pim::notes::SessionNote;
+use crate::pim::reminders::{reminder_choice_text, reminder_choices, reminder_due_text};
+use crate::pim::schedule::{
+    scheduled_activation_text, scheduled_deactivation_text, END_OF_DAY_CHOICES,
+};
+use crate::pim::{
+    ActiveAssignment, ApprovalRequest, EligibleRole, ExpiryUrgency, GroupLookup,
+    JustificationPreset, PimApiStatus, ReviewResult, ScheduledActivation,
+    ACTIVATION_DURATION_CHOICES, MORE_TIME_CHOICES,
+};
+use chrono::{DateTime, NaiveTime, Utc};
+
+/// Number of segments in the remaining-time bar of active roles.
+const PROGRESS_SEGMENTS: usize = 5;
+
+/// Suffix shown on a role until its activation request is provisioned.
+const ACTIVATING_SUFFIX: &str = "⏳ activating…";
+
+/// Suffix shown on a role with a request waiting for an approver.
+const PENDING_APPROVAL_SUFFIX: &str = "⌛ pending approval";
+
+/// Suffix shown on a role whose activations need an approver.
+const REQUIRES_APPROVAL_SUFFIX: &str = "✋ requires approval";
+
+/// Warning thresholds offered for the token countdown, in minutes.
+const TOKEN_WARNING_CHOICES: [u32; 4] = [10, 15, 30, 60];
+
+/// Critical thresholds offered for the token countdown, in minutes.
+const TOKEN_CRITICAL_CHOICES: [u32; 3] = [2, 5, 10];
+
+/// Choices for the most roles a subscription lists unsplit; 0 never splits.
+const ROLE_PAGE_SIZE_CHOICES: [u32; 4] = [0, 15, 25, 50];
+
+/// Tooltip of the note shown when group memberships can't be read.
+const GROUP_LOOKUP_TOOLTIP: &str = "Group memberships can't be read with the granted permissions, so roles granted through groups are missing. Ask an admin to grant Directory.Read.All, or turn on Settings → Skip group lookup.";
+
+/// Tooltip of "Sign Out", which unlike "Clear all data..." keeps everything but the session.
+const SIGN_OUT_TOOLTIP: &str =
+    "Removes your tokens and user info; favorites, settings and cached roles are kept";
+
+/// Everything the menu is built from.
+#[derive(Debug, Clone)]
+pub struct MenuInputs {
+    pub auth_state: AuthState,
+    pub user_info: Option<UserInfo>,
+    pub token_expiry: Option<DateTime<Utc>>,
+    pub settings: Settings,
+    pub pim_state: PimState,
+    pub health: Option<HealthReport>,
+    pub refresh_failures: u32,
+    pub error_retry_at: Option<DateTime<Utc>>,
+}
+
+impl MenuInputs {
+    /// Snapshot the parts of the app state the menu shows.
+    pub fn from_app_state(state: &AppState) -> Self {
+        Self {
+            auth_state: state.get_auth_state(),
+            user_info: state.get_user_info(),
+            token_expiry: state.get_token_expiry(),
+            settings: state.get_settings(),
+            pim_state: state.get_pim_state(),
+            health: state.get_health(),
+            refresh_failures: state.get_refresh_failures(),
+            error_retry_at: state.get_error_retry_at(),
+        }
+    }
+}
+
+/// Everything the menu bar shows: the menu and the status items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuModel {
+    pub entries: Vec<MenuEntry>,
+    /// Text next to the status item icon, if the token countdown is shown.
+    pub countdown: Option<StatusText>,
+    /// What VoiceOver reads for the status item.
+    pub accessibility_label: String,
+    /// Compact status items for active roles, if enabled.
+    pub role_items: Vec<RoleStatusItem>,
+}
+
+/// Text shown in the menu bar, colored if urgent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusText {
+    pub text: String,
+    pub color: Option<Color>,
+}
+
+/// The status item of one active role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleStatusItem {
+    /// Assignment ID, so the item is kept while the role stays active.
+    pub id: String,
+    pub title: StatusText,
+    pub tooltip: String,
+    pub accessibility_label: String,
+}
+
+impl MenuModel {
+    /// Build the model for the current app state.
+    pub fn from_app_state(state: &AppState, clock: &dyn Clock) -> Self {
+        Self::new(&MenuInputs::from_app_state(state), clock)
+    }
+
+    /// Build the model for `inputs`.
+    pub fn new(inputs: &MenuInputs, clock: &dyn Clock) -> Self {
+        let showing_session = matches!(inputs.auth_state, AuthState::SignedIn | AuthState::Offline);
+        let settings = &inputs.settings;
+
+        let countdown = inputs
+            .token_expiry
+            .filter(|_| settings.show_menu_bar_countdown && showing_session)
+            .map(|expiry| {
+                let remaining = expiry - clock.now();
+                StatusText {
+                    text: format!(" {}", duration::format(remaining, Style::Short)),
+                    color: Color::for_urgency(settings.token_urgency(remaining)),
+                }
+            });
+
+        let pim_state = &inputs.pim_state;
+        let threshold = pim_state.settings.expiry_warning_minutes as i64;
+        let role_items = pim_state
+            .active_assignments
+            .iter()
+            .filter(|_| settings.status_item_per_role && showing_session)
+            .filter(|a| !a.is_expired(clock))
+            .map(|assignment| RoleStatusItem {
+                id: assignment.id.clone(),
+                title: StatusText {
+                    text: assignment.compact_text(clock),
+                    color: Color::for_urgency(assignment.urgency(threshold, clock)),
+                },
+                tooltip: assignment.display_text_with_time(clock),
+                accessibility_label: accessibility::assignment_description(assignment, clock),
+            })
+            .collect();
+
+        Self {
+            entries: build(inputs, clock),
+            countdown,
+            accessibility_label: accessibility::status_description(
+                &inputs.auth_state,
+                &pim_state.active_assignments,
+                clock,
+            ),
+            role_items,
+        }
+    }
+
+    /// Plain-text outline of the menu bar, one line per item, nested by
+    /// indentation. Tooltips and accessibility labels are left out.
+    pub fn outline(&self) -> String {
+        let mut text = String::new();
+        if let Some(countdown) = &self.countdown {
+            text.push_str(&format!("[countdown]{}\n", status_text(countdown)));
+        }
+        for item in &self.role_items {
+            text.push_str(&format!("[role] {}\n", status_text(&item.title)));
+        }
+        write_outline(&mut text, &self.entries, 0);
+        text
+    }
+}
+
+fn status_text(status: &StatusText) -> String {
+    match status.color {
+        Some(color) => format!("{} {:?}", status.text, color),
+        None => status.text.clone(),
+    }
+}
+
+fn write_outline(text: &mut String, entries: &[MenuEntry], depth: usize) {
+    for entry in entries {
+        text.push_str(&"  ".repeat(depth));
+        let item = match entry {
+            MenuEntry::Separator => {
+                text.push_str("---\n");
+                continue;
+            }
+            MenuEntry::Item(item) => item,
+        };
+        if item.checked {
+            text.push_str("✓ ");
+        }
+        text.push_str(&item.title);
+        if let Some(count) = item.badge {
+            text.push_str(&format!(" ({})", count));
+        }
+        if let Some(key) = item.key {
+            text.push_str(&format!(" [⌘{}]", key.to_uppercase()));
+        }
+        if !item.enabled {
+            text.push_str(" [disabled]");
+        }
+        if let Some(tint) = &item.tint {
+            text.push_str(&format!(" {:?}", tint));
+        }
+        if let Some(command) = &item.command {
+            text.push_str(&format!(" → {:?}", command));
+        }
+        text.push('\n');
+        if let Some(entries) = &item.submenu {
+            write_outline(text, entries, depth + 1);
+        }
+    }
+}
+
+/// One line of a menu.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuEntry {
+    Item(Box<MenuItem>),
+    Separator,
+}
+
+/// A menu item and what it does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+    pub title: String,
+    /// What choosing the item does, if anything.
+    pub command: Option<Command>,
+    /// False for greyed-out information lines.
+    pub enabled: bool,
+    /// Shown with a checkmark, e.g. a setting that is on.
+    pub checked: bool,
+    /// Key equivalent, pressed with ⌘.
+    pub key: Option<&'static str>,
+    /// Count shown next to the title.
+    pub badge: Option<usize>,
+    pub tint: Option<Tint>,
+    pub tooltip: Option<String>,
+    /// What VoiceOver reads instead of the title.
+    pub accessibility_label: Option<String>,
+    pub submenu: Option<Vec<MenuEntry>>,
+}
+
+impl MenuItem {
+    /// An enabled item without a command, e.g. one holding a submenu.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            command: None,
+            enabled: true,
+            checked: false,
+            key: None,
+            badge: None,
+            tint: None,
+            tooltip: None,
+            accessibility_label: None,
+            submenu: None,
+        }
+    }
+
+    /// A greyed-out information line.
+    pub fn info(title: impl Into<String>) -> Self {
+        Self {
+            enabled: false,
+            ..Self::new(title)
+        }
+    }
+
+    /// An item running `command`.
+    pub fn command(title: impl Into<String>, command: Command) -> Self {
+        Self {
+            command: Some(command),
+            ..Self::new(title)
+        }
+    }
+
+    /// An item running `command`, checked if `checked`.
+    pub fn choice(title: impl Into<String>, command: Command, checked: bool) -> Self {
+        Self {
+            checked,
+            ..Self::command(title, command)
+        }
+    }
+
+    pub fn key(mut self, key: &'static str) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn badge(mut self, count: usize) -> Self {
+        self.badge = Some(count);
+        self
+    }
+
+    pub fn tint(mut self, tint: Tint) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    pub fn tooltip(mut self, tooltip: impl Into<String>) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    pub fn submenu(mut self, entries: Vec<MenuEntry>) -> Self {
+        self.submenu = Some(entries);
+        self
+    }
+}
+
+impl From<MenuItem> for MenuEntry {
+    fn from(item: MenuItem) -> Self {
+        Self::Item(Box::new(item))
+    }
+}
+
+/// Colors used to flag urgency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Orange,
+    Red,
+}
+
+impl Color {
+    /// Color for a warning or critical countdown; normal ones keep the default color.
+    pub fn for_urgency(urgency: ExpiryUrgency) -> Option<Self> {
+        match urgency {
+            ExpiryUrgency::Normal => None,
+            ExpiryUrgency::Warning => Some(Self::Orange),
+            ExpiryUrgency::Critical => Some(Self::Red),
+        }
+    }
+}
+
+/// Part of an item title drawn in a color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tint {
+    /// The whole title.
+    Title(Color),
+    /// The given text at the end of the title.
+    Suffix(Color, String),
+}
+
+/// What choosing a menu item does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    SignIn,
+    CancelSignIn,
+    SignOut,
+    RetryRefresh,
+    RefreshToken,
+    CopyToken,
+    CopyTokenHeader,
+    /// Copy a curl snippet against the endpoint at this index of [`CURL_ENDPOINTS`].
+    CopyTokenCurl(usize),
+    CopyTokenCurlCustom,
+    CheckHealth,
+    RefreshRoles,
+    RecheckSkippedSubscriptions,
+    OpenPimPortal,
+    ShowPalette,
+    CopyArmConsentUrl,
+    /// Activate the elevation plan at this index.
+    ActivatePlan(usize),
+    SavePlan,
+    DeactivateAll,
+    Quit,
+    Activate {
+        role_key: String,
+        justification: String,
+    },
+    CheckActivation {
+        role_key: String,
+    },
+    ToggleFavorite {
+        role_key: String,
+    },
+    /// Ask for a custom label shown next to the role.
+    EditRoleLabel {
+        role_key: String,
+        role_text: String,
+    },
+    /// Activate the role for `minutes` from now on, or ask for a duration for `None`.
+    SetRoleDuration {
+        role_key: String,
+        minutes: Option<u32>,
+    },
+    ExtendRole {
+        role_key: String,
+        minutes: u32,
+    },
+    DeactivateRole {
+        role_key: String,
+    },
+    /// Remind to deactivate in `minutes`, or cancel the reminder for `None`.
+    SetReminder {
+        role_key: String,
+        minutes: Option<u32>,
+    },
+    ScheduleDeactivation {
+        role_key: String,
+        role_text: String,
+    },
+    CancelScheduledDeactivation {
+        role_key: String,
+        role_text: String,
+    },
+    /// Ask for a start time and justification, then schedule an activation.
+    ScheduleActivation {
+        role_key: String,
+        role_text: String,
+    },
+    /// Cancel a scheduled activation before it starts.
+    CancelScheduledActivation {
+        request_id: String,
+    },
+    AddNote {
+        assignment_id: String,
+        role_text: String,
+    },
+    CopyNotes {
+        assignment_id: String,
+        role_text: String,
+    },
+    /// Approve or deny another user's request, asking for a reason.
+    ReviewApproval {
+        request_id: String,
+        result: ReviewResult,
+    },
+    /// Turn a setting on or off.
+    Toggle(Setting),
+    ChooseFavoritesFile,
+    /// Add a subscription to the administrator view, or remove it if listed.
+    ToggleAdminViewSubscription {
+        subscription_id: String,
+    },
+    /// Skip subscriptions without PIM access for this many hours.
+    SetSkipDeniedSubscriptions(u32),
+    /// Split a subscription's roles into submenus of this many, or never for 0.
+    SetRolePageSize(u32),
+    /// List the roles still active at this time of day, or never for `None`.
+    SetEndOfDaySummary(Option<NaiveTime>),
+    SetTokenWarning(u32),
+    SetTokenCritical(u32),
+    SetNotificationSound {
+        event: NotificationEvent,
+        sound: NotificationSound,
+    },
+    ClearData,
+}
+
+/// Settings turned on or off from the settings submenu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    AutoLaunch,
+    ShowExpiry,
+    ProfileDetails,
+    QuitWarning,
+    MenuBarCountdown,
+    RefreshAtWarning,
+    RestoreSession,
+    AutoLoadRoles,
+    RestoreNotification,
+    SuccessAnimation,
+    CriticalFlash,
+    FocusBreakthrough,
+    RoleStatusItems,
+    SkipGroupLookup,
+    KeyVaultToken,
+    ExtensionAtExpiry,
+    AdminView,
+}
+
+impl Setting {
+    /// Whether the setting is on.
+    pub fn is_on(self, settings: &Settings) -> bool {
+        match self {
+            Self::AutoLaunch => settings.auto_launch,
+            Self::ShowExpiry => settings.show_expiry,
+            Self::ProfileDetails => settings.show_profile_details,
+            Self::QuitWarning => settings.warn_active_roles_on_quit,
+            Self::MenuBarCountdown => settings.show_menu_bar_countdown,
+            Self::RefreshAtWarning => settings.refresh_at_token_warning,
+            Self::RestoreSession => settings.restore_session_on_launch,
+            Self::AutoLoadRoles => settings.auto_load_roles,
+            Self::RestoreNotification => settings.notify_session_restored,
+            Self::SuccessAnimation => settings.animate_on_success,
+            Self::CriticalFlash => settings.flash_on_critical_events,
+            Self::FocusBreakthrough => settings.time_sensitive_during_focus,
+            Self::RoleStatusItems => settings.status_item_per_role,
+            Self::SkipGroupLookup => settings.skip_group_lookup,
+            Self::KeyVaultToken => settings.offer_key_vault_token,
+            Self::ExtensionAtExpiry => settings.offer_extension_at_expiry,
+            Self::AdminView => settings.admin_view,
+        }
+    }
+}
+
+/// Build the menu for the current state.
+pub fn build(inputs: &MenuInputs, clock: &dyn Clock) -> Vec<MenuEntry> {
+    match &inputs.auth_state {
+        AuthState::SignedOut => signed_out(inputs),
+        AuthState::Authenticating => authenticating(),
+        AuthState::SignedIn => signed_in(inputs, false, clock),
+        AuthState::Error { message, class } => error(inputs, message, *class, clock),
+        AuthState::Offline => signed_in(inputs, true, clock),
+    }
+}
+
+fn signed_out(inputs: &MenuInputs) -> Vec<MenuEntry> {
+    vec![
+        MenuItem::command("Sign In to Azure", Command::SignIn)
+            .key("l")
+            .into(),
+        MenuEntry::Separator,
+        status_item(inputs.health.as_ref()).into(),
+        quit_item().into(),
+    ]
+}
+
+fn authenticating() -> Vec<MenuEntry> {
+    vec![
+        MenuItem::info("Signing in...").into(),
+        MenuEntry::Separator,
+        MenuItem::command("Cancel", Command::CancelSignIn).into(),
+        quit_item().into(),
+    ]
+}
+
+/// The signed-in menu; offline, the same menu with cached roles shown read-only.
+fn signed_in(inputs: &MenuInputs, offline: bool, clock: &dyn Clock) -> Vec<MenuEntry> {
+    let user_info = inputs.user_info.as_ref();
+    let mut entries = vec![
+        MenuItem::info(user_info.map_or("Unknown User", |u| u.display_name.as_str())).into(),
+        MenuItem::info(user_info.map_or("No email", |u| u.email.as_str())).into(),
+    ];
+
+    // Job title and office, if enabled in settings
+    if let Some(details) = user_info
+        .filter(|_| inputs.settings.show_profile_details)
+        .and_then(|u| u.profile_details())
+    {
+        entries.push(MenuItem::info(details).into());
+    }
+    entries.push(
+        MenuItem::info(user_info.map_or("Unknown Tenant", |u| u.tenant_name.as_str())).into(),
+    );
+
+    // Token expiry, if enabled in settings
+    if let Some(expiry) = inputs.token_expiry.filter(|_| inputs.settings.show_expiry) {
+        let remaining = expiry - clock.now();
+        let mut item = MenuItem::info(format!(
+            "Expires in {}",
+            duration::format(remaining, Style::Short)
+        ));
+        if let Some(color) = Color::for_urgency(inputs.settings.token_urgency(remaining)) {
+            item = item.tint(Tint::Title(color));
+        }
+        entries.push(item.into());
+    }
+
+    // Background refresh failures, with a retry once refresh has paused
+    if let Some(text) = refresh_failures_text(inputs.refresh_failures) {
+        entries.push(MenuItem::info(text).into());
+        if inputs.refresh_failures >= MAX_BACKGROUND_REFRESH_FAILURES {
+            entries.push(MenuItem::command("Try Again Now", Command::RetryRefresh).into());
+        }
+    }
+
+    let admin_view = inputs
+        .settings
+        .admin_view
+        .then_some(inputs.settings.admin_view_subscriptions.as_slice());
+    entries.extend(pim_section(
+        &inputs.pim_state,
+        &inputs.settings.environments,
+        admin_view,
+        inputs.settings.role_page_size,
+        offline,
+        clock,
+    ));
+
+    entries.extend([
+        MenuEntry::Separator,
+        MenuItem::command("Copy Access Token", Command::CopyToken)
+            .key("c")
+            .into(),
+        copy_token_as_item().into(),
+        MenuItem::command("Refresh Token", Command::RefreshToken)
+            .key("t")
+            .into(),
+        MenuItem::command("Sign Out", Command::SignOut)
+            .tooltip(SIGN_OUT_TOOLTIP)
+            .into(),
+        MenuEntry::Separator,
+        status_item(inputs.health.as_ref()).into(),
+        MenuItem::new("Settings")
+            .submenu(settings_entries(&inputs.settings))
+            .into(),
+        MenuEntry::Separator,
+        quit_item().into(),
+    ]);
+    entries
+}
+
+/// The error menu, offering what fixes an error of `class`.
+fn error(
+    inputs: &MenuInputs,
+    message: &str,
+    class: ErrorClass,
+    clock: &dyn Clock,
+) -> Vec<MenuEntry> {
+    let mut entries = vec![
+        MenuItem::info(class.title()).into(),
+        MenuItem::info(message).into(),
+        MenuEntry::Separator,
+    ];
+
+    if let Some(text) = error_code_text(class) {
+        entries.push(MenuItem::info(text).into());
+    }
+    match (class, inputs.error_retry_at) {
+        (ErrorClass::Transient, Some(retry_at)) => {
+            // Recoverable: the token refresh is retried without signing in
+            entries.extend([
+                MenuItem::info(retry_countdown_text(retry_at - clock.now())).into(),
+                MenuItem::command("Retry Now", Command::RetryRefresh).into(),
+                MenuItem::command("Sign In Again", Command::SignIn)
+                    .key("l")
+                    .into(),
+            ]);
+        }
+        _ => {
+            // A new sign-in is the fix, or the way to check that IT fixed it
+            let title = match class {
+                ErrorClass::NeedsReauth => "Sign In Again",
+                _ => "Try Again",
+            };
+            entries.push(MenuItem::command(title, Command::SignIn).key("l").into());
+        }
+    }
+
+    entries.extend([
+        MenuItem::command("Sign Out", Command::SignOut)
+            .tooltip(SIGN_OUT_TOOLTIP)
+            .into(),
+        MenuEntry::Separator,
+        status_item(inputs.health.as_ref()).into(),
+        MenuEntry::Separator,
+        quit_item().into(),
+    ]);
+    entries
+}
+
+/// What to tell the user about the error's code, if it has one.
+fn error_code_text(class: ErrorClass) -> Option<String> {
+    let code = class.code()?;
+    Some(match class {
+        ErrorClass::NeedsAdmin { .. } => format!("Contact IT with code {}", code),
+        _ => format!("Please report this with code {}", code),
+    })
+}
+
+/// A checked item turning `setting` on or off.
+fn toggle_item(title: &str, setting: Setting, settings: &Settings) -> MenuItem {
+    MenuItem::choice(title, Command::Toggle(setting), setting.is_on(settings))
+}
+
+/// The settings submenu.
+fn settings_entries(settings: &Settings) -> Vec<MenuEntry> {
+    // Favorites file location, checked when synced from a custom path
+    let mut favorites_file = MenuItem::choice(
+        "Favorites File...",
+        Command::ChooseFavoritesFile,
+        settings.favorites_file.is_some(),
+    );
+    if let Some(path) = &settings.favorites_file {
+        favorites_file = favorites_file.tooltip(path.display().to_string());
+    }
+
+    vec![
+        toggle_item("Auto-launch at login", Setting::AutoLaunch, settings).into(),
+        toggle_item("Show expiry countdown", Setting::ShowExpiry, settings).into(),
+        toggle_item(
+            "Show job title and office",
+            Setting::ProfileDetails,
+            settings,
+        )
+        .into(),
+        MenuItem::new("Token Expiry")
+            .submenu(token_expiry_entries(settings))
+            .into(),
+        toggle_item(
+            "Warn about active roles on quit",
+            Setting::QuitWarning,
+            settings,
+        )
+        .into(),
+        toggle_item(
+            "Offer to extend expiring roles",
+            Setting::ExtensionAtExpiry,
+            settings,
+        )
+        .tooltip("With the expiry warning, offer to keep the role active for another hour")
+        .into(),
+        end_of_day_item(settings).into(),
+        toggle_item(
+            "Offer Key Vault token after activation",
+            Setting::KeyVaultToken,
+            settings,
+        )
+        .tooltip(
+            "After activating a Key Vault data role, offer to copy a token for vault.azure.net",
+        )
+        .into(),
+        toggle_item(
+            "Administrator view of active roles",
+            Setting::AdminView,
+            settings,
+        )
+        .tooltip("List everyone holding an active role in subscriptions you choose. Needs read access to their role assignments.")
+        .into(),
+        favorites_file.into(),
+        MenuEntry::Separator,
+        MenuItem::new("Startup")
+            .submenu(vec![
+                toggle_item(
+                    "Restore session at launch",
+                    Setting::RestoreSession,
+                    settings,
+                )
+                .into(),
+                toggle_item("Load roles automatically", Setting::AutoLoadRoles, settings).into(),
+                toggle_item(
+                    "Notify when session is restored",
+                    Setting::RestoreNotification,
+                    settings,
+                )
+                .into(),
+            ])
+            .into(),
+        MenuItem::new("Sounds & Feedback")
+            .submenu(sounds_entries(settings))
+            .into(),
+        MenuEntry::Separator,
+        MenuItem::command("Clear all data...", Command::ClearData)
+            .tooltip("Choose what to remove: credentials, settings, caches, session notes or logs")
+            .into(),
+    ]
+}
+
+/// Token expiry thresholds and countdown options.
+fn token_expiry_entries(settings: &Settings) -> Vec<MenuEntry> {
+    vec![
+        threshold_item(
+            "Warn at",
+            &TOKEN_WARNING_CHOICES,
+            settings.token_warning_minutes,
+            Command::SetTokenWarning,
+        )
+        .into(),
+        threshold_item(
+            "Critical at",
+            &TOKEN_CRITICAL_CHOICES,
+            settings.token_critical_minutes,
+            Command::SetTokenCritical,
+        )
+        .into(),
+        MenuEntry::Separator,
+        toggle_item(
+            "Show countdown in menu bar",
+            Setting::MenuBarCountdown,
+            settings,
+        )
+        .into(),
+        toggle_item(
+            "Refresh token at warning",
+            Setting::RefreshAtWarning,
+            settings,
+        )
+        .into(),
+    ]
+}
+
+/// A submenu choosing a threshold among `choices`, with `current` checked.
+fn threshold_item(
+    title: &str,
+    choices: &[u32],
+    current: u32,
+    command: fn(u32) -> Command,
+) -> MenuItem {
+    let entries = choices
+        .iter()
+        .map(|&minutes| {
+            let title = format!("{} min", minutes);
+            MenuItem::choice(title, command(minutes), minutes == current).into()
+        })
+        .collect();
+    MenuItem::new(title).submenu(entries)
+}
+
+/// Notification sounds and feedback.
+fn sounds_entries(settings: &Settings) -> Vec<MenuEntry> {
+    let mut entries: Vec<MenuEntry> = NotificationEvent::ALL
+        .into_iter()
+        .map(|event| {
+            let current = settings.sounds.sound_for(event);
+            let sounds = NotificationSound::ALL
+                .into_iter()
+                .map(|sound| {
+                    let command = Command::SetNotificationSound { event, sound };
+                    MenuItem::choice(sound.label(), command, sound == current).into()
+                })
+                .collect();
+            MenuItem::new(event.label()).submenu(sounds).into()
+        })
+        .collect();
+
+    entries.extend([
+        MenuEntry::Separator,
+        toggle_item(
+            "Animate icon on success",
+            Setting::SuccessAnimation,
+            settings,
+        )
+        .into(),
+        toggle_item(
+            "Flash icon on critical events",
+            Setting::CriticalFlash,
+            settings,
+        )
+        .into(),
+        toggle_item(
+            "Expiry warnings break through Focus",
+            Setting::FocusBreakthrough,
+            settings,
+        )
+        .into(),
+        toggle_item(
+            "Menu bar item per active role",
+            Setting::RoleStatusItems,
+            settings,
+        )
+        .into(),
+        toggle_item("Skip group lookup", Setting::SkipGroupLookup, settings)
+            .tooltip(
+                "Don't read group memberships. Only roles you're eligible for directly are shown.",
+            )
+            .into(),
+        skip_period_item(settings).into(),
+        role_page_size_item(settings).into(),
+    ]);
+    entries
+}
+
+/// A submenu choosing how many roles a subscription lists before its roles
+/// are split into "A–F" style submenus.
+fn role_page_size_item(settings: &Settings) -> MenuItem {
+    let current = settings.role_page_size;
+    let mut choices = ROLE_PAGE_SIZE_CHOICES.to_vec();
+    if !choices.contains(&current) {
+        choices.push(current);
+        choices.sort();
+    }
+    let entries = choices
+        .into_iter()
+        .map(|size| {
+            let title = match size {
+                0 => "Never".to_string(),
+                size => format!("Over {} roles", size),
+            };
+            MenuItem::choice(title, Command::SetRolePageSize(size), size == current).into()
+        })
+        .collect();
+    MenuItem::new("Split long role lists")
+        .tooltip("Group a subscription's roles into submenus by name, e.g. A–F, once it has more than this many")
+        .submenu(entries)
+}
+
+/// A submenu choosing when to list the roles still active, or never.
+///
+/// A time set in settings.json that isn't among the choices is shown too.
+fn end_of_day_item(settings: &Settings) -> MenuItem {
+    let current = settings.end_of_day_summary;
+    let mut times: Vec<NaiveTime> = END_OF_DAY_CHOICES
+        .iter()
+        .filter_map(|&(hour, minute)| NaiveTime::from_hms_opt(hour, minute, 0))
+        .collect();
+    if let Some(time) = current.filter(|time| !times.contains(time)) {
+        times.push(time);
+        times.sort();
+    }
+    let mut entries =
+        vec![MenuItem::choice("Off", Command::SetEndOfDaySummary(None), current.is_none()).into()];
+    entries.extend(times.into_iter().map(|time| {
+        let command = Command::SetEndOfDaySummary(Some(time));
+        MenuItem::choice(
+            time.format("%H:%M").to_string(),
+            command,
+            current == Some(time),
+        )
+        .into()
+    }));
+    MenuItem::new("End-of-Day Summary")
+        .tooltip("At this time, list the roles still active and offer to deactivate them all")
+        .submenu(entries)
+}
+
+/// A submenu choosing how long subscriptions without PIM access are skipped.
+fn skip_period_item(settings: &Settings) -> MenuItem {
+    let current = settings.skip_denied_subscriptions_hours;
+    let entries = SKIP_PERIOD_CHOICES
+        .iter()
+        .map(|&hours| {
+            let command = Command::SetSkipDeniedSubscriptions(hours);
+            MenuItem::choice(skip_period_label(hours), command, hours == current).into()
+        })
+        .collect();
+    MenuItem::new("Skip subscriptions without PIM")
+        .tooltip("Leave out subscriptions that refused two scans in a row, then try them again.")
+        .submenu(entries)
+}
+
+fn quit_item() -> MenuItem {
+    MenuItem::command("Quit", Command::Quit).key("q")
+}
+
+/// The "Copy Token As" submenu: header and curl snippet variants.
+fn copy_token_as_item() -> MenuItem {
+    let mut entries = vec![
+        MenuItem::command("Authorization Header", Command::CopyTokenHeader).into(),
+        MenuEntry::Separator,
+    ];
+    for (index, (label, _)) in CURL_ENDPOINTS.iter().enumerate() {
+        entries.push(
+            MenuItem::command(format!("curl {}", label), Command::CopyTokenCurl(index)).into(),
+        );
+    }
+    entries.push(MenuItem::command("curl Custom Endpoint...", Command::CopyTokenCurlCustom).into());
+    MenuItem::new("Copy Token As").submenu(entries)
+}
+
+/// The "Status" submenu with the results of the last health checks.
+fn status_item(report: Option<&HealthReport>) -> MenuItem {
+    let mut entries = Vec::new();
+    match report {
+        Some(report) => {
+            for check in &report.checks {
+                let mut item = MenuItem::info(check.menu_text());
+                if !check.ok {
+                    item = item.tint(Tint::Title(Color::Red));
+                }
+                entries.push(item.into());
+            }
+            entries.push(MenuItem::info(report.checked_at_text()).into());
+        }
+        None => entries.push(MenuItem::info("Not checked yet").into()),
+    }
+    entries.push(MenuEntry::Separator);
+    entries.push(MenuItem::command("Run Checks", Command::CheckHealth).into());
+
+    let title = match report {
+        Some(report) if report.checks.iter().any(|c| !c.ok) => "Status ⚠",
+        _ => "Status",
+    };
+    MenuItem::new(title).submenu(entries)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// PIM Menu Section
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// The PIM section of the signed-in menu.
+///
+/// While offline, cached roles are shown read-only. `admin_view` holds the
+/// subscriptions of the administrator view, if it's on, and `role_page_size`
+/// the most roles a subscription's submenu lists before it is split.
+fn pim_section(
+    pim_state: &PimState,
+    environments: &[EnvironmentRule],
+    admin_view: Option<&[String]>,
+    role_page_size: u32,
+    offline: bool,
+    clock: &dyn Clock,
+) -> Vec<MenuEntry> {
+    let mut entries = vec![MenuEntry::Separator];
+
+    if offline {
+        entries.push(MenuItem::info(pim_state.offline_label()).into());
+    }
+
+    // Active roles, if any
+    if !pim_state.active_assignments.is_empty() {
+        entries.push(
+            MenuItem::info("Active Roles")
+                .badge(pim_state.active_assignments.len())
+                .into(),
+        );
+        let threshold = pim_state.settings.expiry_warning_minutes as i64;
+        for assignment in &pim_state.active_assignments {
+            entries.push(active_assignment_item(assignment, pim_state, threshold, clock).into());
+        }
+        if !offline {
+            entries.push(
+                MenuItem::command("Deactivate All Roles", Command::DeactivateAll)
+                    .tooltip("Ends every active role now, to get back to least privilege")
+                    .into(),
+            );
+        }
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Requests waiting on an approval, if any
+    let pending = &pim_state.pending_requests;
+    let awaiting_my_approval = pim_state.approvals.awaiting_my_approval;
+    if !pending.is_empty() {
+        entries.push(
+            MenuItem::info("Pending Approval")
+                .badge(pending.len())
+                .into(),
+        );
+        for request in pending {
+            let title = request_title(pim_state, &request.role_key);
+            entries.push(
+                MenuItem::info(format!("{}  {}", title, PENDING_APPROVAL_SUFFIX))
+                    .tooltip("Activates once an approver signs off on the request")
+                    .into(),
+            );
+        }
+    }
+
+    // Activations scheduled to start later, if any
+    let now = clock.now();
+    let scheduled: Vec<&ScheduledActivation> = pim_state
+        .scheduled_activations
+        .iter()
+        .filter(|s| s.start_time > now)
+        .collect();
+    if !scheduled.is_empty() {
+        entries.push(MenuItem::info("Scheduled").badge(scheduled.len()).into());
+        for activation in &scheduled {
+            entries.push(scheduled_activation_item(activation, pim_state, offline).into());
+        }
+    }
+
+    let to_review = &pim_state.approval_requests;
+    let awaiting_my_approval = awaiting_my_approval.max(to_review.len());
+    if awaiting_my_approval > 0 {
+        let item = if offline || to_review.is_empty() {
+            MenuItem::info("Awaiting Your Approval")
+        } else {
+            let mut requests: Vec<MenuEntry> = to_review
+                .iter()
+                .map(|r| approval_request_item(r).into())
+                .collect();
+            // Approvals at scopes the menu can't list are left to the portal
+            if awaiting_my_approval > to_review.len() {
+                requests.extend([
+                    MenuEntry::Separator,
+                    MenuItem::command("Review Others in Azure Portal", Command::OpenPimPortal)
+                        .into(),
+                ]);
+            }
+            MenuItem::new("Awaiting Your Approval").submenu(requests)
+        };
+        entries.push(item.badge(awaiting_my_approval).into());
+    }
+    if !pending.is_empty() || !scheduled.is_empty() || awaiting_my_approval > 0 {
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Everyone's active roles, for administrators
+    if let Some(chosen) = admin_view.filter(|_| !offline) {
+        entries.push(admin_view_item(pim_state, chosen, clock).into());
+        entries.push(MenuEntry::Separator);
+    }
+
+    // Offline, the cached roles are shown regardless of the API status
+    let api_status = if offline {
+        &PimApiStatus::Available
+    } else {
+        &pim_state.api_status
+    };
+    match api_status {
+        PimApiStatus::Loading => entries.push(MenuItem::info("PIM Roles (loading...)").into()),
+        PimApiStatus::PermissionDenied { .. } | PimApiStatus::ArmPermissionMissing { .. } => {
+            entries.extend(onboarding_entries(pim_state))
+        }
+        PimApiStatus::Unavailable { error } => {
+            entries.push(MenuItem::info(format!("PIM: {}", error)).into())
+        }
+        PimApiStatus::Available if !offline && pim_state.eligible_roles.is_empty() => {
+            entries.extend(onboarding_entries(pim_state))
+        }
+        PimApiStatus::Unknown | PimApiStatus::Available => entries.extend(roles_entries(
+            pim_state,
+            environments,
+            role_page_size,
+            offline,
+        )),
+    }
+
+    let skipped = &pim_state.skipped_subscriptions;
+    if !skipped.is_empty() {
+        let title = match skipped.len() {
+            1 => "Recheck 1 Skipped Subscription".to_string(),
+            n => format!("Recheck {} Skipped Subscriptions", n),
+        };
+        entries.push(
+            MenuItem::command(title, Command::RecheckSkippedSubscriptions)
+                .tooltip(format!("No PIM access in: {}", skipped.join(", ")))
+                .into(),
+        );
+    }
+    entries.push(
+        MenuItem::command("Refresh Roles", Command::RefreshRoles)
+            .key("r")
+            .into(),
+    );
+    entries.push(
+        MenuItem::command("Command Palette...", Command::ShowPalette)
+            .key("k")
+            .into(),
+    );
+    entries
+}
+
+/// Title of the role of one of the user's requests, with its label.
+fn request_title(pim_state: &PimState, role_key: &str) -> String {
+    pim_state
+        .eligible_roles
+        .iter()
+        .find(|r| r.favorites_key() == role_key)
+        .map_or_else(
+            || "Role no longer eligible".to_string(),
+            |r| pim_state.labeled_title(r.menu_title(), role_key),
+        )
+}
+
+/// A scheduled activation, with its start and a way to cancel it.
+fn scheduled_activation_item(
+    activation: &ScheduledActivation,
+    pim_state: &PimState,
+    offline: bool,
+) -> MenuItem {
+    let mut entries = vec![MenuItem::info(scheduled_activation_text(activation.start_time)).into()];
+    if !offline {
+        let command = Command::CancelScheduledActivation {
+            request_id: activation.id.clone(),
+        };
+        entries.extend([
+            MenuEntry::Separator,
+            MenuItem::command("Cancel Activation", command).into(),
+        ]);
+    }
+    MenuItem::new(request_title(pim_state, &activation.role_key)).submenu(entries)
+}
+
+/// A request to review, with its justification and the Approve and Deny actions.
+fn approval_request_item(request: &ApprovalRequest) -> MenuItem {
+    let justification = match request.justification.as_str() {
+        "" => "No justification given".to_string(),
+        text => format!("Justification: {}", text),
+    };
+    let review = |title: &str, result| {
+        let command = Command::ReviewApproval {
+            request_id: request.id.clone(),
+            result,
+        };
+        MenuItem::command(title, command).into()
+    };
+    let mut entries = vec![MenuItem::info(justification).into()];
+    if let Some(created_on) = request.created_on {
+        entries.push(
+            MenuItem::info(format!(
+                "Requested {}",
+                created_on.format("%Y-%m-%d %H:%M UTC")
+            ))
+            .into(),
+        );
+    }
+    entries.extend([
+        MenuEntry::Separator,
+        review("Approve…", ReviewResult::Approve),
+        review("Deny…", ReviewResult::Deny),
+    ]);
+    MenuItem::new(request.menu_title()).submenu(entries)
+}
+
+/// The "Who's Elevated" submenu of the administrator view: everyone's active
+/// roles in the `chosen` subscriptions, read-only, and the subscriptions to choose.
+fn admin_view_item(pim_state: &PimState, chosen: &[String], clock: &dyn Clock) -> MenuItem {
+    let mut entries: Vec<MenuEntry> = match &pim_state.tenant_assignments {
+        _ if chosen.is_empty() => vec![MenuItem::info("Choose subscriptions below").into()],
+        None => vec![MenuItem::info("Loading...").into()],
+        Some(assignments) if assignments.is_empty() => {
+            vec![MenuItem::info("Nobody is elevated").into()]
+        }
+        Some(assignments) => assignments
+            .iter()
+            .map(|assignment| {
+                let expires = assignment.end_time.format("%Y-%m-%d %H:%M UTC");
+                MenuItem::info(assignment.display_text_with_time(clock))
+                    .tooltip(format!("{}, until {}", assignment.principal_type, expires))
+                    .into()
+            })
+            .collect(),
+    };
+
+    // Known subscriptions, and chosen ones no longer listed by their ID
+    let mut subscriptions = pim_state.subscription_names.sorted();
+    for id in chosen {
+        if !subscriptions.iter().any(|(known, _)| known == id) {
+            subscriptions.push((id, id));
+        }
+    }
+    let choices: Vec<MenuEntry> = if subscriptions.is_empty() {
+        vec![MenuItem::info("Refresh roles to list subscriptions").into()]
+    } else {
+        subscriptions
+            .into_iter()
+            .map(|(id, name)| {
+                let command = Command::ToggleAdminViewSubscription {
+                    subscription_id: id.to_string(),
+                };
+                MenuItem::choice(name, command, chosen.iter().any(|c| c == id)).into()
+            })
+            .collect()
+    };
+    entries.push(MenuEntry::Separator);
+    entries.push(MenuItem::new("Subscriptions").submenu(choices).into());
+
+    let mut item = MenuItem::new("Who's Elevated");
+    match &pim_state.tenant_assignments {
+        Some(assignments) if !chosen.is_empty() && !assignments.is_empty() => {
+            item = item.badge(assignments.len())
+        }
+        _ => {}
+    }
+    item.submenu(entries)
+}
+
+/// Favorites, the "Eligible Roles" submenu and elevation plans.
+fn roles_entries(
+    pim_state: &PimState,
+    environments: &[EnvironmentRule],
+    role_page_size: u32,
+    offline: bool,
+) -> Vec<MenuEntry> {
+    let mut entries = Vec::new();
+    if let Some(text) = pim_state.group_lookup.menu_text() {
+        let mut item = MenuItem::info(text);
+        if pim_state.group_lookup == GroupLookup::Unavailable {
+            item = item.tooltip(GROUP_LOOKUP_TOOLTIP);
+        }
+        entries.push(item.into());
+    }
+
+    if pim_state.eligible_roles.is_empty() {
+        entries.push(MenuItem::info("No eligible PIM roles").into());
+        return entries;
+    }
+
+    // Favorites, flat at the top for quick access
+    let favorites = pim_state.favorite_roles();
+    if !favorites.is_empty() {
+        entries.push(MenuItem::info("Favorites").into());
+        for role in favorites {
+            let title = pim_state.labeled_title(role.menu_title(), &role.favorites_key());
+            let item = role_item(title, role, true, pim_state, offline);
+            entries.push(flag_environment(item, &role.subscription_name, environments).into());
+        }
+        entries.push(MenuEntry::Separator);
+    }
+
+    // The other roles, grouped by subscription
+    let roles_by_sub = pim_state.roles_by_subscription();
+    if !roles_by_sub.is_empty() {
+        let subscriptions = roles_by_sub
+            .iter()
+            .filter(|(_, roles)| !roles.is_empty())
+            .map(|(subscription_name, roles)| {
+                let role_entries = roles
+                    .iter()
+                    .map(|role| {
+                        let is_favorite = pim_state.is_favorite(role);
+                        // Marked at the end, so type-select still matches the role name
+                        let star = if is_favorite { "  ★" } else { "" };
+                        let title =
+                            pim_state.labeled_title(role.role_name.clone(), &role.favorites_key());
+                        let title = format!("{}{}", title, star);
+                        role_item(title, role, is_favorite, pim_state, offline).into()
+                    })
+                    .collect();
+                let role_entries = paginate_roles(roles, role_entries, role_page_size);
+                let item = MenuItem::new(*subscription_name).submenu(role_entries);
+                flag_environment(item, subscription_name, environments).into()
+            })
+            .collect();
+        entries.push(
+            MenuItem::new("Eligible Roles")
+                .badge(pim_state.eligible_roles.len())
+                .submenu(subscriptions)
+                .into(),
+        );
+    }
+
+    if !offline {
+        entries.extend(plans_entries(pim_state));
+    }
+    entries
+}
+
+/// Split the entries of a long role list, sorted by name, into submenus of at
+/// most `page_size` roles titled by the names they hold, e.g. "A–F".
+///
+/// A list no longer than `page_size`, or any list if it is 0, is kept flat.
+fn paginate_roles(
+    roles: &[&EligibleRole],
+    entries: Vec<MenuEntry>,
+    page_size: u32,
+) -> Vec<MenuEntry> {
+    let size = page_size as usize;
+    if size == 0 || entries.len() <= size {
+        return entries;
+    }
+    let names: Vec<&str> = roles.iter().map(|r| r.role_name.as_str()).collect();
+    let mut entries = entries.into_iter();
+    page_titles(&names, size)
+        .into_iter()
+        .map(|title| {
+            let page: Vec<MenuEntry> = entries.by_ref().take(size).collect();
+            MenuItem::new(title).badge(page.len()).submenu(page).into()
+        })
+        .collect()
+}
+
+/// Titles of the pages of `names`, by the first letters of their first and
+/// last names, with more letters where one letter doesn't tell pages apart.
+fn page_titles(names: &[&str], size: usize) -> Vec<String> {
+    let pages: Vec<&[&str]> = names.chunks(size).collect();
+    let titles_with = |letters: usize| -> Vec<String> {
+        pages
+            .iter()
+            .map(|page| {
+                let prefix = |name: &str| name.chars().take(letters).collect::<String>();
+                let first = prefix(page[0]);
+                let last = prefix(page[page.len() - 1]);
+                if first == last {
+                    first
+                } else {
+                    format!("{}–{}", first, last)
+                }
+            })
+            .collect()
+    };
+    (1..=3)
+        .chain([usize::MAX])
+        .map(titles_with)
+        .find(|titles| {
+            let distinct: std::collections::HashSet<&String> = titles.iter().collect();
+            distinct.len() == titles.len()
+        })
+        // Roles of the same name at different scopes
+        .unwrap_or_else(|| {
+            (1..=pages.len())
+                .map(|page| format!("Page {}", page))
+                .collect()
+        })
+}
+
+/// Mark an item for a subscription in production with a red label.
+fn flag_environment(
+    item: MenuItem,
+    subscription_name: &str,
+    environments: &[EnvironmentRule],
+) -> MenuItem {
+    if environment_of(subscription_name, environments) != Some(Environment::Prod) {
+        return item;
+    }
+    let label = Environment::Prod.label().to_string();
+    MenuItem {
+        title: format!("{}  {}", item.title, label),
+        ..item
+    }
+    .tint(Tint::Suffix(Color::Red, label))
+}
+
+/// The "Elevation Plans" submenu, or the progress of a plan being activated.
+fn plans_entries(pim_state: &PimState) -> Option<MenuEntry> {
+    if let Some(progress) = &pim_state.plan_progress {
+        return Some(MenuItem::info(progress.menu_text()).into());
+    }
+
+    let plans = &pim_state.settings.elevation_plans;
+    let can_save = !pim_state.active_assignments.is_empty();
+    if plans.is_empty() && !can_save {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    for (idx, plan) in plans.iter().enumerate() {
+        let roles: Vec<String> = plan
+            .role_keys
+            .iter()
+            .map(|key| {
+                pim_state
+                    .eligible_roles
+                    .iter()
+                    .find(|r| r.favorites_key() == *key)
+                    .map_or_else(|| key.clone(), |r| r.display_text())
+            })
+            .collect();
+        let duration = plan
+            .duration_minutes
+            .unwrap_or(pim_state.settings.default_duration_minutes);
+        entries.push(
+            MenuItem::command(plan.menu_text(), Command::ActivatePlan(idx))
+                .tooltip(format!(
+                    "{}\nFor {}",
+                    roles.join("\n"),
+                    format_minutes(duration)
+                ))
+                .into(),
+        );
+    }
+
+    if can_save {
+        if !plans.is_empty() {
+            entries.push(MenuEntry::Separator);
+        }
+        entries.push(MenuItem::command("Save Active Roles as Plan...", Command::SavePlan).into());
+    }
+    Some(MenuItem::new("Elevation Plans").submenu(entries).into())
+}
+
+/// A checklist for getting PIM roles into the menu, for when none can be shown.
+///
+/// Each step is checked once the app state shows it done: the Management
+/// API token was granted, a refresh went through, and eligible roles came back.
+fn onboarding_entries(pim_state: &PimState) -> Vec<MenuEntry> {
+    let permission_granted = !matches!(
+        pim_state.api_status,
+        PimApiStatus::PermissionDenied { .. } | PimApiStatus::ArmPermissionMissing { .. }
+    );
+    let refreshed = permission_granted && pim_state.roles_cached_at.is_some();
+    let has_roles = !pim_state.eligible_roles.is_empty();
+
+    let mut permission = MenuItem::new("1. Grant Azure Management permission");
+    permission = match &pim_state.api_status {
+        PimApiStatus::ArmPermissionMissing { .. } => permission.submenu(arm_permission_entries()),
+        PimApiStatus::PermissionDenied { message } => permission.tooltip(message.clone()),
+        _ => MenuItem::info(permission.title),
+    };
+    let steps = [
+        MenuItem {
+            checked: permission_granted,
+            ..permission
+        },
+        MenuItem::choice(
+            "2. Check your eligibilities in the Azure portal",
+            Command::OpenPimPortal,
+            has_roles,
+        )
+        .tooltip(
+            "PIM roles are assigned by an admin under Privileged Identity Management → My roles",
+        ),
+        MenuItem::choice("3. Refresh roles", Command::RefreshRoles, refreshed),
+    ];
+
+    let title = if permission_granted && refreshed {
+        "No eligible PIM roles yet"
+    } else {
+        "Set up PIM access"
+    };
+    let mut entries = vec![MenuItem::info(title).into()];
+    entries.extend(steps.into_iter().map(MenuEntry::from));
+    entries
+}
+
+/// Explain which app registration permission is missing and offer the admin consent URL.
+fn arm_permission_entries() -> Vec<MenuEntry> {
+    let lines = [
+        "Azure Management permission missing",
+        "Add to the app registration's API permissions:",
+        "  Azure Service Management → user_impersonation",
+    ];
+    let mut entries: Vec<MenuEntry> = lines
+        .into_iter()
+        .map(|line| MenuItem::info(line).into())
+        .collect();
+    entries.push(
+        MenuItem::command("Copy Admin Consent URL", Command::CopyArmConsentUrl)
+            .tooltip("Send this link to an Azure AD admin to grant the permission for your tenant")
+            .into(),
+    );
+    entries
+}
+
+/// An active assignment with a colored remaining-time bar.
+///
+/// Its submenu has when and why the role was activated, and what can be done with it.
+fn active_assignment_item(
+    assignment: &ActiveAssignment,
+    pim_state: &PimState,
+    threshold_minutes: i64,
+    clock: &dyn Clock,
+) -> MenuItem {
+    let role_key = assignment.role_key();
+    let role_text = format!(
+        "{} on {}",
+        assignment.role_name, assignment.subscription_name
+    );
+
+    let mut entries = vec![MenuItem::info(assignment.activated_text(clock)).into()];
+    entries.push(more_time_item(&role_key).into());
+    let command = Command::DeactivateRole {
+        role_key: role_key.clone(),
+    };
+    entries.push(MenuItem::command("Deactivate Now", command).into());
+    entries.extend(reminder_entries(
+        assignment,
+        pim_state.deactivation_reminders.due_at(&role_key),
+        clock,
+    ));
+    entries.extend(schedule_entries(
+        &role_key,
+        &role_text,
+        pim_state.scheduled_deactivations.due_at(&role_key),
+    ));
+    entries.extend(note_entries(
+        assignment,
+        &role_text,
+        pim_state.session_notes.notes_for(&assignment.id),
+    ));
+
+    let bar = assignment.progress_bar(PROGRESS_SEGMENTS, clock);
+    let color =
+        Color::for_urgency(assignment.urgency(threshold_minutes, clock)).unwrap_or(Color::Green);
+    MenuItem {
+        // Role name first so type-select matches it
+        title: format!(
+            "{}  {}",
+            pim_state.labeled_title(assignment.display_text_with_time(clock), &role_key),
+            bar
+        ),
+        // Read the time left in words instead of the bar
+        accessibility_label: Some(accessibility::assignment_description(assignment, clock)),
+        ..MenuItem::new("")
+    }
+    .tint(Tint::Suffix(color, bar))
+    .submenu(entries)
+}
+
+/// A "Need More Time" submenu that keeps the role active past its current end.
+fn more_time_item(role_key: &str) -> MenuItem {
+    let entries = MORE_TIME_CHOICES
+        .into_iter()
+        .map(|minutes| {
+            let command = Command::ExtendRole {
+                role_key: role_key.to_string(),
+                minutes,
+            };
+            MenuItem::command(format!("+{}", format_minutes(minutes)), command).into()
+        })
+        .collect();
+    MenuItem::new("Need More Time")
+        .tooltip("Extends the activation if the role's policy allows it, otherwise activates it again from when it ends")
+        .submenu(entries)
+}
+
+/// Items to schedule or cancel a reminder to deactivate the role.
+fn reminder_entries(
+    assignment: &ActiveAssignment,
+    reminder: Option<DateTime<Utc>>,
+    clock: &dyn Clock,
+) -> Vec<MenuEntry> {
+    let role_key = assignment.role_key();
+    let choices = reminder_choices(assignment.time_remaining(clock));
+    if choices.is_empty() && reminder.is_none() {
+        return vec![];
+    }
+    let mut entries = vec![MenuEntry::Separator];
+
+    if let Some(due_at) = reminder {
+        entries.push(MenuItem::info(reminder_due_text(due_at)).into());
+        let command = Command::SetReminder {
+            role_key: role_key.clone(),
+            minutes: None,
+        };
+        entries.push(MenuItem::command("Cancel Reminder", command).into());
+    }
+
+    if !choices.is_empty() {
+        let choice_entries = choices
+            .into_iter()
+            .map(|minutes| {
+                let command = Command::SetReminder {
+                    role_key: role_key.clone(),
+                    minutes: Some(minutes),
+                };
+                MenuItem::command(reminder_choice_text(minutes), command).into()
+            })
+            .collect();
+        entries.push(
+            MenuItem::new("Remind Me to Deactivate")
+                .submenu(choice_entries)
+                .into(),
+        );
+    }
+    entries
+}
+
+/// Items to deactivate the role at a time of day, or cancel that.
+fn schedule_entries(
+    role_key: &str,
+    role_text: &str,
+    deactivate_at: Option<DateTime<Utc>>,
+) -> Vec<MenuEntry> {
+    let role_key = role_key.to_string();
+    let role_text = role_text.to_string();
+    let mut entries = vec![MenuEntry::Separator];
+    match deactivate_at {
+        Some(at) => {
+            entries.push(MenuItem::info(scheduled_deactivation_text(at)).into());
+            let command = Command::CancelScheduledDeactivation {
+                role_key,
+                role_text,
+            };
+            entries.push(MenuItem::command("Cancel Scheduled Deactivation", command).into());
+        }
+        None => {
+            let command = Command::ScheduleDeactivation {
+                role_key,
+                role_text,
+            };
+            entries.push(MenuItem::command("Deactivate At...", command).into());
+        }
+    }
+    entries
+}
+
+/// The notes taken during the elevation and items to add or copy them.
+fn note_entries(
+    assignment: &ActiveAssignment,
+    role_text: &str,
+    notes: &[SessionNote],
+) -> Vec<MenuEntry> {
+    let mut entries = vec![MenuEntry::Separator];
+    for note in notes {
+        entries.push(
+            MenuItem::info(note.menu_text())
+                .tooltip(note.text.clone())
+                .into(),
+        );
+    }
+    let add = Command::AddNote {
+        assignment_id: assignment.id.clone(),
+        role_text: role_text.to_string(),
+    };
+    entries.push(MenuItem::command("Add Note...", add).into());
+    if !notes.is_empty() {
+        let copy = Command::CopyNotes {
+            assignment_id: assignment.id.clone(),
+            role_text: role_text.to_string(),
+        };
+        entries.push(MenuItem::command("Copy Notes", copy).into());
+    }
+    entries
+}
+
+/// An eligible role with its justification submenu.
+///
+/// While its activation is in flight or waiting for an approver the role is
+/// shown disabled without presets, so an activation can't be requested twice.
+fn role_item(
+    title: String,
+    role: &EligibleRole,
+    is_favorite: bool,
+    pim_state: &PimState,
+    offline: bool,
+) -> MenuItem {
+    if pim_state.is_activating(role) {
+        return MenuItem::info(format!("{}  {}", title, ACTIVATING_SUFFIX));
+    }
+    if pim_state.is_pending_approval(role) {
+        return MenuItem::info(format!("{}  {}", title, PENDING_APPROVAL_SUFFIX));
+    }
+    let title = if pim_state.requires_approval(role) {
+        format!("{}  {}", title, REQUIRES_APPROVAL_SUFFIX)
+    } else {
+        title
+    };
+    let role_key = role.favorites_key();
+    let duration = pim_state.duration_for(&role_key);
+    let max_duration = pim_state.max_duration(&role_key);
+    MenuItem::new(title)
+        .tooltip(role.tooltip_text())
+        .submenu(justification_entries(
+            role,
+            is_favorite,
+            duration,
+            max_duration,
+            offline,
+        ))
+}
+
+/// A "Duration" submenu choosing how long the role's presets activate it for.
+///
+/// Only durations up to the policy's maximum, if known, are offered.
+fn duration_item(role_key: &str, current: u32, max: Option<u32>) -> MenuItem {
+    let mut choices = ACTIVATION_DURATION_CHOICES.to_vec();
+    choices.extend(max);
+    choices.push(current);
+    choices.retain(|&minutes| max.is_none_or(|max| minutes <= max));
+    choices.sort();
+    choices.dedup();
+    let mut entries: Vec<MenuEntry> = Vec::new();
+    if let Some(max) = max {
+        entries.push(MenuItem::info(format!("Policy allows up to {}", format_minutes(max))).into());
+    }
+    entries.extend(choices.into_iter().map(|minutes| {
+        let command = Command::SetRoleDuration {
+            role_key: role_key.to_string(),
+            minutes: Some(minutes),
+        };
+        MenuItem::choice(format_minutes(minutes), command, minutes == current).into()
+    }));
+    let custom = Command::SetRoleDuration {
+        role_key: role_key.to_string(),
+        minutes: None,
+    };
+    entries.push(MenuItem::command("Custom…", custom).into());
+    MenuItem::new(format!("Duration: {}", format_minutes(current)))
+        .tooltip("How long the presets above activate this role for, remembered per role")
+        .submenu(entries)
+}
+
+/// Justification presets for a role, and what else can be done with it.
+///
+/// Offline, the presets are replaced by a note, since activation needs Azure.
+fn justification_entries(
+    role: &EligibleRole,
+    is_favorite: bool,
+    duration: u32,
+    max_duration: Option<u32>,
+    offline: bool,
+) -> Vec<MenuEntry> {
+    let role_key = role.favorites_key();
+    let mut entries = Vec::new();
+
+    if offline {
+        entries.push(MenuItem::info("Activation unavailable offline").into());
+    } else {
+        for preset in JustificationPreset::builtin_presets() {
+            let command = Command::Activate {
+                role_key: role_key.clone(),
+                justification: preset.justification,
+            };
+            entries.push(MenuItem::command(preset.label, command).into());
+        }
+        entries.push(duration_item(&role_key, duration, max_duration).into());
+    }
+    entries.push(MenuEntry::Separator);
+
+    // Dry run: validate the activation without elevating
+    if !offline {
+        let command = Command::CheckActivation {
+            role_key: role_key.clone(),
+        };
+        entries.push(MenuItem::command("Check Activation…", command).into());
+        let command = Command::ScheduleActivation {
+            role_key: role_key.clone(),
+            role_text: role.display_text(),
+        };
+        entries.push(
+            MenuItem::command("Schedule Activation…", command)
+                .tooltip("Starts the activation later, e.g. for a maintenance window")
+                .into(),
+        );
+    }
+
+    let favorite_text = if is_favorite {
+        "Remove from Favorites"
+    } else {
+        "Add to Favorites"
+    };
+    entries.push(
+        MenuItem::command(
+            favorite_text,
+            Command::ToggleFavorite {
+                role_key: role_key.clone(),
+            },
+        )
+        .into(),
+    );
+    let label = Command::EditRoleLabel {
+        role_key,
+        role_text: role.display_text(),
+    };
+    entries.push(MenuItem::command("Set Label…", label).into());
+
+    // Leaving a group only removes the role if no other grant remains
+    entries.push(MenuItem::info(format!("Eligible via: {}", role.eligible_via())).into());
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::golden::assert_golden_text;
+    use crate::pim::policy::RolePolicy;
+    use crate::pim::{
+        ApprovalCounts, ElevationPlan, PendingRequest, Subscription, TenantAssignment,
+    };
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()
+    }
+
+    fn role(role_name: &str, subscription_id: &str, subscription_name: &str) -> EligibleRole {
+        EligibleRole {
+            id: format!("{}-{}", role_name, subscription_id),
+            role_definition_id: format!("role-{}", role_name),
+            role_name: role_name.to_string(),
+            subscription_id: subscription_id.to_string(),
+            subscription_name: subscription_name.to_string(),
+            scope: format!("/subscriptions/{}", subscription_id)
+                .parse()
+                .unwrap(),
+            principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        }
+    }
+
+    fn active(role: &EligibleRole) -> ActiveAssignment {
+        ActiveAssignment {
+            id: "assignment-id".to_string(),
+            role_definition_id: role.role_definition_id.clone(),
+            role_name: role.role_name.clone(),
+            subscription_id: role.subscription_id.clone(),
+            subscription_name: role.subscription_name.clone(),
+            scope: role.scope.clone(),
+            start_time: now() - Duration::minutes(20),
+            end_time: now() + Duration::minutes(40),
+            justification: "INC-4711".to_string(),
+            assignment_request_id: None,
+        }
+    }
+
+    fn inputs(auth_state: AuthState) -> MenuInputs {
+        MenuInputs {
+            auth_state,
+            user_info: Some(UserInfo {
+                user_id: "user-id".to_string(),
+                display_name: "Kari Nordmann".to_string(),
+                email: "kari@example.com".to_string(),
+                tenant_id: "tenant-id".to_string(),
+                tenant_name: "Example".to_string(),
+                job_title: None,
+                office_location: None,
+            }),
+            token_expiry: Some(now() + Duration::minutes(50)),
+            settings: Settings::default(),
+            pim_state: PimState::default(),
+            health: None,
+            refresh_failures: 0,
+            error_retry_at: None,
+        }
+    }
+
+    /// Role sets the PIM section is snapshotted with.
+    fn role_sets() -> Vec<(&'static str, PimState)> {
+        let contributor = role("Contributor", "sub-1", "vipps-prod-001");
+        let reader = role("Reader", "sub-1", "vipps-prod-001");
+        let owner = role("Owner", "sub-2", "vipps-test-001");
+
+        let eligible = PimState {
+            eligible_roles: vec![contributor.clone(), reader.clone(), owner.clone()],
+            ..PimState::default()
+        };
+
+        let mut elevated = eligible.clone();
+        elevated.active_assignments = vec![active(&reader)];
+        elevated.settings.favorite_role_keys = vec![contributor.favorites_key()];
+        elevated
+            .settings
+            .set_label(&contributor.favorites_key(), "🔥 payments");
+        elevated
+            .settings
+            .set_label(&reader.favorites_key(), "audit");
+        elevated.activating.insert(owner.favorites_key());
+        elevated.approvals = ApprovalCounts {
+            my_pending: 1,
+            awaiting_my_approval: 2,
+        };
+        elevated.approval_requests = vec![ApprovalRequest {
+            id: "/req/9".to_string(),
+            approval_id: "/approvals/9".to_string(),
+            principal_name: "Ola Nordmann".to_string(),
+            role_name: "Owner".to_string(),
+            scope_name: "vipps-prod-001".to_string(),
+            justification: "INC-4712".to_string(),
+            created_on: Some(now() - Duration::minutes(5)),
+        }];
+        let access_admin = role("User Access Administrator", "sub-2", "vipps-test-001");
+        elevated.pending_requests = vec![PendingRequest {
+            id: "/req/1".to_string(),
+            role_key: access_admin.favorites_key(),
+        }];
+        elevated.eligible_roles.push(access_admin);
+        elevated.role_policies.insert(
+            contributor.favorites_key(),
+            RolePolicy {
+                max_minutes: Some(120),
+                approval_required: true,
+            },
+        );
+        elevated.settings.elevation_plans = vec![ElevationPlan {
+            name: "Prod incident".to_string(),
+            role_keys: vec![contributor.favorites_key(), reader.favorites_key()],
+            duration_minutes: None,
+            justification: "{plan}".to_string(),
+        }];
+        elevated.skipped_subscriptions = vec!["vipps-sandbox".to_string()];
+
+        vec![
+            ("no roles", PimState::default()),
+            ("eligible roles", eligible),
+            ("favorite, active and activating roles", elevated),
+        ]
+    }
+
+    fn api_statuses() -> Vec<PimApiStatus> {
+        vec![
+            PimApiStatus::Unknown,
+            PimApiStatus::Loading,
+            PimApiStatus::Available,
+            PimApiStatus::PermissionDenied {
+                message: "No PIM access".to_string(),
+            },
+            PimApiStatus::ArmPermissionMissing {
+                consent_url: "https://login.microsoftonline.com/consent".to_string(),
+            },
+            PimApiStatus::Unavailable {
+                error: "Service unavailable".to_string(),
+            },
+        ]
+    }
+
+    /// Outlines of the menus built for each of `cases`, under a heading each.
+    ///
+    /// The settings submenu is left out; it has a snapshot of its own.
+    fn outlines(cases: Vec<(String, MenuInputs)>) -> String {
+        let clock = MockClock::new(now());
+        cases
+            .into_iter()
+            .map(|(heading, inputs)| {
+                let mut model = MenuModel::new(&inputs, &clock);
+                for entry in &mut model.entries {
+                    if let MenuEntry::Item(item) = entry {
+                        if item.title == "Settings" {
+                            item.submenu = None;
+                        }
+                    }
+                }
+                format!("== {} ==\n{}", heading, model.outline())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn commands(entries: &[MenuEntry]) -> Vec<&Command> {
+        let mut commands = Vec::new();
+        for entry in entries {
+            let MenuEntry::Item(item) = entry else {
+                continue;
+            };
+            commands.extend(&item.command);
+            if let Some(entries) = &item.submenu {
+                commands.extend(self::commands(entries));
+            }
+        }
+        commands
+    }
+
+    #[test]
+    fn test_menu_snapshots_without_session() {
+        let mut transient = inputs(AuthState::Error {
+            message: "Network unreachable".to_string(),
+            class: ErrorClass::Transient,
+        });
+        transient.error_retry_at = Some(now() + Duration::minutes(2));
+        let error = |message: &str, class| {
+            inputs(AuthState::Error {
+                message: message.to_string(),
+                class,
+            })
+        };
+
+        let cases = vec![
+            ("signed out", inputs(AuthState::SignedOut)),
+            ("authenticating", inputs(AuthState::Authenticating)),
+            (
+                "error, needs sign-in",
+                error("Session expired", ErrorClass::NeedsReauth),
+            ),
+            ("error, transient with retry", transient),
+            (
+                "error, needs admin",
+                error(
+                    "Consent missing",
+                    ErrorClass::NeedsAdmin {
+                        code: "AUTH-ARM-CONSENT",
+                    },
+                ),
+            ),
+            (
+                "error, bug",
+                error("Unexpected state", ErrorClass::Bug { code: "APP-STATE" }),
+            ),
+        ];
+        let cases = cases
+            .into_iter()
+            .map(|(heading, inputs)| (heading.to_string(), inputs))
+            .collect();
+        assert_golden_text("menu/without_session", &outlines(cases));
+    }
+
+    #[test]
+    fn test_menu_snapshots_signed_in() {
+        let mut cases = Vec::new();
+        for api_status in api_statuses() {
+            for (roles, pim_state) in role_sets() {
+                let mut inputs = inputs(AuthState::SignedIn);
+                inputs.pim_state = PimState {
+                    api_status: api_status.clone(),
+                    ..pim_state
+                };
+                cases.push((format!("{:?}, {}", api_status, roles), inputs));
+            }
+        }
+        assert_golden_text("menu/signed_in", &outlines(cases));
+    }
+
+    #[test]
+    fn test_menu_snapshots_offline() {
+        let cases = role_sets()
+            .into_iter()
+            .map(|(roles, pim_state)| {
+                let mut inputs = inputs(AuthState::Offline);
+                inputs.pim_state = pim_state;
+                (roles.to_string(), inputs)
+            })
+            .collect();
+        assert_golden_text("menu/offline", &outlines(cases));
+    }
+
+    #[test]
+    fn test_menu_snapshots_admin_view() {
+        let admin_view = |chosen: &[&str], assignments: Option<Vec<TenantAssignment>>| {
+            let mut inputs = inputs(AuthState::SignedIn);
+            inputs.settings.admin_view = true;
+            inputs.settings.admin_view_subscriptions =
+                chosen.iter().map(|id| id.to_string()).collect();
+            let subscriptions =
+                [("sub-1", "vipps-prod-001"), ("sub-2", "vipps-test-001")].map(|(id, name)| {
+                    Subscription {
+                        subscription_id: id.to_string(),
+                        display_name: name.to_string(),
+                        state: "Enabled".to_string(),
+                    }
+                });
+            inputs
+                .pim_state
+                .subscription_names
+                .update(&subscriptions, now());
+            inputs.pim_state.tenant_assignments = assignments;
+            inputs
+        };
+        let owner = TenantAssignment {
+            id: "/a/1".to_string(),
+            principal_name: "Ola Nordmann".to_string(),
+            principal_type: "User".to_string(),
+            role_definition_id: "role-owner".to_string(),
+            role_name: "Owner".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-1".parse().unwrap(),
+            end_time: now() + Duration::minutes(25),
+        };
+        let mut offline = admin_view(&["sub-1"], Some(vec![owner.clone()]));
+        offline.auth_state = AuthState::Offline;
+
+        let cases = vec![
+            ("no subscriptions chosen", admin_view(&[], None)),
+            ("loading", admin_view(&["sub-1"], None)),
+            ("nobody elevated", admin_view(&["sub-1"], Some(vec![]))),
+            (
+                "elevated, chosen subscription gone",
+                admin_view(&["sub-1", "sub-9"], Some(vec![owner])),
+            ),
+            ("offline", offline),
+        ];
+        let cases = cases
+            .into_iter()
+            .map(|(heading, inputs)| (heading.to_string(), inputs))
+            .collect();
+        assert_golden_text("menu/admin_view", &outlines(cases));
+    }
+
+    #[test]
+    fn test_offline_menu_cannot_activate() {
+        let clock = MockClock::new(now());
+        for (_, pim_state) in role_sets() {
+            for api_status in api_statuses() {
+                let mut inputs = inputs(AuthState::Offline);
+                inputs.pim_state = PimState {
+                    api_status,
+                    ..pim_state.clone()
+                };
+                let menu = build(&inputs, &clock);
+                assert!(!commands(&menu).iter().any(|c| matches!(
+                    c,
+                    Command::Activate { .. }
+                        | Command::CheckActivation { .. }
+                        | Command::ScheduleActivation { .. }
+                        | Command::CancelScheduledActivation { .. }
+                        | Command::ActivatePlan(_)
+                )));
+            }
+        }
+    }
+
+    #[test]
+    fn test_scheduled_activations_can_be_cancelled() {
+        let clock = MockClock::new(now());
+        let owner = role("Owner", "sub-2", "vipps-test-001");
+        let scheduled = |id: &str, hours| ScheduledActivation {
+            id: id.to_string(),
+            role_key: owner.favorites_key(),
+            start_time: now() + Duration::hours(hours),
+        };
+        let mut inputs = inputs(AuthState::SignedIn);
+        inputs.pim_state = PimState {
+            eligible_roles: vec![owner.clone()],
+            api_status: PimApiStatus::Available,
+            // One started since the last poll
+            scheduled_activations: vec![scheduled("/req/1", -1), scheduled("/req/2", 10)],
+            ..PimState::default()
+        };
+
+        let menu = build(&inputs, &clock);
+        let section = menu
+            .iter()
+            .position(|e| matches!(e, MenuEntry::Item(item) if item.title == "Scheduled"))
+            .expect("Scheduled section");
+        let MenuEntry::Item(item) = &menu[section + 1] else {
+            panic!("no scheduled activation after the heading");
+        };
+        assert_eq!(item.title, "Owner - vipps-test-001");
+        let cancels: Vec<&Command> = commands(&menu)
+            .into_iter()
+            .filter(|c| matches!(c, Command::CancelScheduledActivation { .. }))
+            .collect();
+        assert_eq!(
+            cancels,
+            [&Command::CancelScheduledActivation {
+                request_id: "/req/2".to_string()
+            }]
+        );
+
+        inputs.pim_state.scheduled_activations.truncate(1);
+        let menu = build(&inputs, &clock);
+        assert!(!menu
+            .iter()
+            .any(|e| matches!(e, MenuEntry::Item(item) if item.title == "Scheduled")));
+    }
+
+    #[test]
+    fn test_long_role_lists_are_split() {
+        let names = [
+            "AcrPull",
+            "Backup Reader",
+            "Billing Reader",
+            "Contributor",
+            "Cost Management Reader",
+            "Data Factory Contributor",
+            "DNS Zone Contributor",
+            "Key Vault Reader",
+            "Monitoring Reader",
+            "Owner",
+            "Reader",
+        ];
+        let roles: Vec<EligibleRole> = names.iter().map(|n| role(n, "sub-1", "dev")).collect();
+        let roles: Vec<&EligibleRole> = roles.iter().collect();
+        let entries = || -> Vec<MenuEntry> {
+            roles
+                .iter()
+                .map(|r| MenuItem::info(r.role_name.clone()).into())
+                .collect()
+        };
+
+        assert_eq!(paginate_roles(&roles, entries(), 0).len(), names.len());
+        assert_eq!(paginate_roles(&roles, entries(), 11).len(), names.len());
+        let pages = paginate_roles(&roles, entries(), 4);
+        let pages: Vec<(&str, usize)> = pages
+            .iter()
+            .map(|entry| match entry {
+                MenuEntry::Item(item) => (
+                    item.title.as_str(),
+                    item.submenu.as_ref().map_or(0, Vec::len),
+                ),
+                MenuEntry::Separator => panic!("separator between pages"),
+            })
+            .collect();
+        assert_eq!(pages, [("A–C", 4), ("C–K", 4), ("M–R", 3)]);
+
+        let same_letter = [
+            "Contributor",
+            "Cost Reader",
+            "Custom Deployer",
+            "Custom Reader",
+        ];
+        assert_eq!(page_titles(&same_letter, 2), ["Co", "Cu"]);
+        assert_eq!(
+            page_titles(&["Reader", "Reader", "Reader"], 2),
+            ["Page 1", "Page 2"]
+        );
+    }
+
+    #[test]
+    fn test_menu_flags_urgency() {
+        let clock = MockClock::new(now());
+        let mut inputs = inputs(AuthState::SignedIn);
+        inputs.token_expiry = Some(now() + Duration::minutes(3));
+        let menu = build(&inputs, &clock);
+        let expiry = menu.iter().find_map(|entry| match entry {
+            MenuEntry::Item(item) if item.title.starts_with("Expires in") => Some(item),
+            _ => None,
+        });
+        assert_eq!(expiry.unwrap().tint, Some(Tint::Title(Color::Red)));
+    }
+
+    #[test]
+    fn test_menu_snapshots_settings() {
+        let mut inputs = inputs(AuthState::SignedIn);
+        inputs.settings.favorites_file = Some("/Users/kari/favorites.json".into());
+        let model = MenuModel::new(&inputs, &MockClock::new(now()));
+        let settings = model
+            .entries
+            .into_iter()
+            .find_map(|entry| match entry {
+                MenuEntry::Item(item) if item.title == "Settings" => item.submenu,
+                _ => None,
+            })
+            .unwrap();
+        let model = MenuModel {
+            entries: settings,
+            countdown: None,
+            accessibility_label: String::new(),
+            role_items: vec![],
+        };
+        assert_golden_text("menu/settings", &model.outline());
+    }
+
+    #[test]
+    fn test_status_items() {
+        let clock = MockClock::new(now());
+        let reader = role("Reader", "sub-1", "vipps-prod-001");
+        let mut inputs = inputs(AuthState::SignedIn);
+        inputs.pim_state.active_assignments = vec![active(&reader)];
+        inputs.settings.show_menu_bar_countdown = true;
+        inputs.settings.status_item_per_role = true;
+
+        let model = MenuModel::new(&inputs, &clock);
+        assert_eq!(
+            model.countdown,
+            Some(StatusText {
+                text: " 50m".to_string(),
+                color: None,
+            })
+        );
+        assert_eq!(model.role_items.len(), 1);
+        assert_eq!(model.role_items[0].title.text, "Reader 40m");
+        assert!(model
+            .outline()
+            .starts_with("[countdown] 50m\n[role] Reader 40m\n"));
+
+        // Only shown with a session
+        inputs.auth_state = AuthState::SignedOut;
+        let model = MenuModel::new(&inputs, &clock);
+        assert_eq!(model.countdown, None);
+        assert!(model.role_items.is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+//! Command palette: every menu command and role, reachable by typing.
+//!
+//! The palette lists commands as text ("Activate Contributor - vipps-prod-001",
+//! "Copy Access Token", ...) and runs the one that best matches what was typed.
+//! Matching is fuzzy: the query's characters must appear in order, and matches
+//! at word starts or in runs score higher.
+
+use crate::auth::token_format::{TokenFormat, CURL_ENDPOINTS};
+use crate::menubar::state::PimState;
+
+/// Azure portal page listing the user's PIM roles.
+pub const PIM_PORTAL_URL: &str =
+    "https://portal.azure.com/#view/Microsoft_Azure_PIMCommon/ActivationMenuBlade/~/azurerbac";
+
+/// A command the palette can run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Activate an eligible role, asking for a justification.
+    Activate {
+        role_key: String,
+    },
+    /// Deactivate an active role.
+    Deactivate {
+        role_key: String,
+    },
+    /// Copy the access token, raw or as a header or curl command.
+    CopyToken(TokenFormat),
+    RefreshToken,
+    RefreshRoles,
+    /// Open the PIM page of the Azure portal.
+    OpenPortal,
+}
+
+/// A palette row: the text shown and matched, and the command it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub title: String,
+    pub command: Command,
+}
+
+impl PaletteEntry {
+    fn new(title: impl Into<String>, command: Command) -> Self {
+        Self {
+            title: title.into(),
+            command,
+        }
+    }
+}
+
+/// Commands available for the current roles. Role commands need Azure, so
+/// they are left out while offline.
+pub fn palette_entries(pim_state: &PimState, offline: bool) -> Vec<PaletteEntry> {
+    let mut entries = Vec::new();
+    if !offline {
+        for assignment in &pim_state.active_assignments {
+            entries.push(PaletteEntry::new(
+                pim_state.labeled_title(
+                    format!(
+                        "Deactivate {} - {}",
+                        assignment.role_name, assignment.subscription_name
+                    ),
+                    &assignment.role_key(),
+                ),
+                Command::Deactivate {
+                    role_key: assignment.role_key(),
+                },
+            ));
+        }
+        for role in &pim_state.eligible_roles {
+            let role_key = role.favorites_key();
+            let active = pim_state
+                .active_assignments
+                .iter()
+                .any(|a| a.role_key() == role_key);
+            if !active {
+                entries.push(PaletteEntry::new(
+                    pim_state.labeled_title(format!("Activate {}", role.menu_title()), &role_key),
+                    Command::Activate { role_key },
+                ));
+            }
+        }
+        entries.push(PaletteEntry::new("Refresh Roles", Command::RefreshRoles));
+    }
+    entries.push(PaletteEntry::new(
+        "Copy Access Token",
+        Command::CopyToken(TokenFormat::Raw),
+    ));
+    entries.push(PaletteEntry::new(
+        "Copy Authorization Header",
+        Command::CopyToken(TokenFormat::BearerHeader),
+    ));
+    for (index, (label, _)) in CURL_ENDPOINTS.iter().enumerate() {
+        if let Some(format) = TokenFormat::curl_endpoint(index) {
+            entries.push(PaletteEntry::new(
+                format!("Copy curl Snippet for {}", label),
+                Command::CopyToken(format),
+            ));
+        }
+    }
+    entries.push(PaletteEntry::new("Refresh Token", Command::RefreshToken));
+    entries.push(PaletteEntry::new("Open Azure Portal", Command::OpenPortal));
+    entries
+}
+
+/// Score how well `query` fuzzy-matches `text`, or `None` if it doesn't.
+///
+/// Every query character must appear in `text` in order, ignoring case and
+/// spaces in the query. Characters at word starts and right after the
+/// previous match score extra.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = next + text[next..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 2;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// The entry that best matches `query`. An exact title wins; ties go to the
+/// shorter title, then to the earlier entry.
+pub fn best_match<'a>(entries: &'a [PaletteEntry], query: &str) -> Option<&'a PaletteEntry> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+    if let Some(exact) = entries.iter().find(|e| e.title.eq_ignore_ascii_case(query)) {
+        return Some(exact);
+    }
+    entries
+        .iter()
+        .filter_map(|e| fuzzy_score(query, &e.title).map(|score| (score, e)))
+        .min_by_key(|(score, e)| (std::cmp::Reverse(*score), e.title.len()))
+        .map(|(_, e)| e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pim::{ActiveAssignment, EligibleRole};
+    use chrono::{Duration, Utc};
+
+    fn role(role_name: &str, subscription_id: &str, subscription_name: &str) -> EligibleRole {
+        EligibleRole {
+            id: format!("{}-{}", role_name, subscription_id),
+            role_definition_id: format!("role-{}", role_name),
+            role_name: role_name.to_string(),
+            subscription_id: subscription_id.to_string(),
+            subscription_name: subscription_name.to_string(),
+            scope: format!("/subscriptions/{}", subscription_id)
+                .parse()
+                .unwrap(),
+            principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        }
+    }
+
+    fn active(role: &EligibleRole) -> ActiveAssignment {
+        ActiveAssignment {
+            id: "assignment-id".to_string(),
+            role_definition_id: role.role_definition_id.clone(),
+            role_name: role.role_name.clone(),
+            subscription_id: role.subscription_id.clone(),
+            subscription_name: role.subscription_name.clone(),
+            scope: role.scope.clone(),
+            start_time: Utc::now(),
+            end_time: Utc::now() + Duration::hours(1),
+            justification: "Debugging".to_string(),
+            assignment_request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_palette_entries() {
+        let contributor = role("Contributor", "sub-1", "vipps-prod-001");
+        let reader = role("Reader", "sub-2", "vipps-test-001");
+        let mut pim_state = PimState {
+            active_assignments: vec![active(&reader)],
+            eligible_roles: vec![contributor.clone(), reader],
+            ..PimState::default()
+        };
+        pim_state
+            .settings
+            .set_label(&contributor.favorites_key(), "payments");
+
+        let titles: Vec<String> = palette_entries(&pim_state, false)
+            .into_iter()
+            .map(|e| e.title)
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                "Deactivate Reader - vipps-test-001",
+                "Activate Contributor - vipps-prod-001  (payments)",
+                "Refresh Roles",
+                "Copy Access Token",
+                "Copy Authorization Header",
+                "Copy curl Snippet for Graph /me",
+                "Copy curl Snippet for Graph /me/memberOf",
+                "Copy curl Snippet for Graph /organization",
+                "Refresh Token",
+                "Open Azure Portal",
+            ]
+        );
+
+        // Offline, only commands that work without Azure remain
+        assert_eq!(palette_entries(&pim_state, true).len(), 7);
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        assert!(fuzzy_score("cat", "Copy Access Token").is_some());
+        assert_eq!(fuzzy_score("xyz", "Copy Access Token"), None);
+        // Word starts beat scattered letters
+        assert!(fuzzy_score("rt", "Refresh Token") > fuzzy_score("rt", "Contributor"));
+
+        let entries = vec![
+            PaletteEntry::new(
+                "Activate Contributor - vipps-prod-001",
+                Command::RefreshRoles,
+            ),
+            PaletteEntry::new("Activate Reader - vipps-prod-001", Command::RefreshRoles),
+            PaletteEntry::new("Copy Access Token", Command::CopyToken(TokenFormat::Raw)),
+            PaletteEntry::new("Open Azure Portal", Command::OpenPortal),
+        ];
+        assert_eq!(
+            best_match(&entries, "act reader").map(|e| e.title.as_str()),
+            Some("Activate Reader - vipps-prod-001")
+        );
+        assert_eq!(
+            best_match(&entries, "portal").map(|e| &e.command),
+            Some(&Command::OpenPortal)
+        );
+        assert_eq!(
+            best_match(&entries, "copy access token").map(|e| &e.command),
+            Some(&Command::CopyToken(TokenFormat::Raw))
+        );
+        assert_eq!(best_match(&entries, "  "), None);
+    }
+}
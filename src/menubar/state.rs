@@ -1,10 +1,25 @@
 //! Application state management for the menu bar.
 
 use crate::auth::graph::UserInfo;
-use crate::pim::{ActiveAssignment, EligibleRole, PimApiStatus, PimSettings};
-use chrono::{DateTime, Duration, Utc};
+use crate::auth::token_manager::retry_countdown_text;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{AuthTransitionError, ErrorClass};
+use crate::health::HealthReport;
+use crate::notifications::SoundSettings;
+use crate::pim::environment::{self, EnvironmentRule};
+use crate::pim::policy::RolePolicy;
+use crate::pim::{
+    ActiveAssignment, ApprovalCounts, ApprovalRequest, DeactivationReminders, EligibleRole,
+    ExpiryUrgency, GroupLookup, GroupNames, NotesStore, PendingRequest, PimApiStatus, PimSettings,
+    PlanProgress, ScheduledActivation, ScheduledDeactivations, SubscriptionNames, TenantAssignment,
+};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone, Utc};
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
 
 /// Global application state.
 pub static APP_STATE: OnceCell<Arc<AppState>> = OnceCell::new();
@@ -36,6 +51,14 @@ pub struct AppState {
     pub settings: Mutex<Settings>,
     /// PIM state.
     pub pim_state: Mutex<PimState>,
+    /// Results of the last health checks, if any were run.
+    pub health: Mutex<Option<HealthReport>>,
+    /// Consecutive background token refresh failures.
+    pub refresh_failures: Mutex<u32>,
+    /// When a recoverable error is silently retried next, if one is scheduled.
+    pub error_retry_at: Mutex<Option<DateTime<Utc>>>,
+    /// State the menu was last rendered from.
+    rendered: Mutex<Option<MenuSnapshot>>,
 }
 
 impl AppState {
@@ -47,6 +70,10 @@ impl AppState {
             token_expiry: Mutex::new(None),
             settings: Mutex::new(Settings::default()),
             pim_state: Mutex::new(PimState::default()),
+            health: Mutex::new(None),
+            refresh_failures: Mutex::new(0),
+            error_retry_at: Mutex::new(None),
+            rendered: Mutex::new(None),
         }
     }
 
@@ -55,11 +82,64 @@ impl AppState {
         self.auth_state.lock().unwrap().clone()
     }
 
-    /// Set the authentication state.
+    /// Set the authentication state, bypassing transition checks.
+    ///
+    /// Use [`AppState::transition`] for state changes at runtime.
     pub fn set_auth_state(&self, state: AuthState) {
         *self.auth_state.lock().unwrap() = state;
     }
 
+    /// Move the auth state on `event`, along with the user info, token
+    /// expiry, and error retry it carries.
+    ///
+    /// Refused transitions are logged and leave the state unchanged.
+    pub fn transition(&self, event: AuthEvent) -> Result<(), AuthTransitionError> {
+        let mut auth_state = self.auth_state.lock().unwrap();
+        let mut user_info = self.user_info.lock().unwrap();
+        let has_user_info = user_info.is_some()
+            || matches!(
+                &event,
+                AuthEvent::SignedIn { .. } | AuthEvent::WentOffline { user_info: Some(_) }
+            );
+
+        let next = match auth_state.on(&event, has_user_info) {
+            Ok(next) => next,
+            Err(e) => {
+                warn!("{}", e);
+                return Err(e);
+            }
+        };
+        info!(
+            "Auth state {} -> {} on {}",
+            auth_state.name(),
+            next.name(),
+            event.name()
+        );
+
+        let mut retry_at = None;
+        match event {
+            AuthEvent::SignedOut => {
+                *user_info = None;
+                self.set_token_expiry(None);
+            }
+            AuthEvent::SignedIn {
+                user_info: info,
+                expires_at,
+            } => {
+                *user_info = Some(info);
+                self.set_token_expiry(Some(expires_at));
+            }
+            AuthEvent::WentOffline {
+                user_info: Some(info),
+            } => *user_info = Some(info),
+            AuthEvent::Failed { retry_at: at, .. } => retry_at = at,
+            _ => {}
+        }
+        self.set_error_retry_at(retry_at);
+        *auth_state = next;
+        Ok(())
+    }
+
     /// Get the cached user info.
     pub fn get_user_info(&self) -> Option<UserInfo> {
         self.user_info.lock().unwrap().clone()
@@ -98,6 +178,36 @@ impl AppState {
         self.set_token_expiry(None);
     }
 
+    /// Get the results of the last health checks.
+    pub fn get_health(&self) -> Option<HealthReport> {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Set the results of the last health checks.
+    pub fn set_health(&self, report: HealthReport) {
+        *self.health.lock().unwrap() = Some(report);
+    }
+
+    /// Get the number of consecutive background token refresh failures.
+    pub fn get_refresh_failures(&self) -> u32 {
+        *self.refresh_failures.lock().unwrap()
+    }
+
+    /// Set the number of consecutive background token refresh failures.
+    pub fn set_refresh_failures(&self, count: u32) {
+        *self.refresh_failures.lock().unwrap() = count;
+    }
+
+    /// Get when a recoverable error is silently retried next.
+    pub fn get_error_retry_at(&self) -> Option<DateTime<Utc>> {
+        *self.error_retry_at.lock().unwrap()
+    }
+
+    /// Set when a recoverable error is silently retried next.
+    pub fn set_error_retry_at(&self, at: Option<DateTime<Utc>>) {
+        *self.error_retry_at.lock().unwrap() = at;
+    }
+
     /// Get the PIM state.
     pub fn get_pim_state(&self) -> PimState {
         self.pim_state.lock().unwrap().clone()
@@ -108,20 +218,45 @@ impl AppState {
         *self.pim_state.lock().unwrap() = state;
     }
 
-    /// Update PIM eligible roles.
-    pub fn set_pim_eligible_roles(&self, roles: Vec<EligibleRole>) {
+    /// Update PIM eligible roles and the names of the groups they were queried for.
+    pub fn set_pim_eligible_roles(&self, mut roles: Vec<EligibleRole>, group_names: GroupNames) {
         let mut pim = self.pim_state.lock().unwrap();
+        pim.subscription_names.rename_roles(&mut roles);
         pim.eligible_roles = roles;
+        pim.group_names = group_names;
         pim.roles_cached_at = Some(Utc::now());
         pim.api_status = PimApiStatus::Available;
     }
 
+    /// Show eligible roles cached by a previous run, keeping their fetch time.
+    pub fn restore_pim_eligible_roles(
+        &self,
+        mut roles: Vec<EligibleRole>,
+        cached_at: DateTime<Utc>,
+    ) {
+        let mut pim = self.pim_state.lock().unwrap();
+        pim.subscription_names.rename_roles(&mut roles);
+        pim.eligible_roles = roles;
+        pim.roles_cached_at = Some(cached_at);
+        pim.api_status = PimApiStatus::Available;
+    }
+
     /// Update PIM active assignments.
-    pub fn set_pim_active_assignments(&self, assignments: Vec<ActiveAssignment>) {
+    pub fn set_pim_active_assignments(&self, mut assignments: Vec<ActiveAssignment>) {
         let mut pim = self.pim_state.lock().unwrap();
+        pim.subscription_names.rename_assignments(&mut assignments);
         pim.active_assignments = assignments;
     }
 
+    /// Update subscription names and show them on the current roles and assignments.
+    pub fn set_subscription_names(&self, names: SubscriptionNames) {
+        let mut pim = self.pim_state.lock().unwrap();
+        let pim = &mut *pim;
+        names.rename_roles(&mut pim.eligible_roles);
+        names.rename_assignments(&mut pim.active_assignments);
+        pim.subscription_names = names;
+    }
+
     /// Get PIM settings.
     #[allow(dead_code)] // Full PIM integration pending
     pub fn get_pim_settings(&self) -> PimSettings {
@@ -129,17 +264,46 @@ impl AppState {
     }
 
     /// Update PIM settings.
-    #[allow(dead_code)] // Full PIM integration pending
     pub fn set_pim_settings(&self, settings: PimSettings) {
         self.pim_state.lock().unwrap().settings = settings;
     }
 
-    /// Get active role count for badge.
-    #[allow(dead_code)] // Full PIM integration pending
+    /// Get active role count.
     pub fn get_active_role_count(&self) -> usize {
         self.pim_state.lock().unwrap().active_assignments.len()
     }
 
+    /// Record the state the menu is about to be rendered from.
+    ///
+    /// Returns false if nothing shown in the menu has changed since the last
+    /// render, so the rebuild can be skipped.
+    pub fn mark_menu_rendered(&self, clock: &dyn Clock) -> bool {
+        let mut pim_state = self.get_pim_state();
+        // Not shown in the menu; changes on every fetch
+        pim_state.roles_cached_at = None;
+
+        let snapshot = MenuSnapshot {
+            auth_state: self.get_auth_state(),
+            user_info: self.get_user_info(),
+            token_expiry: self.get_token_expiry(),
+            settings: self.get_settings(),
+            pim_state,
+            health: self.get_health(),
+            refresh_failures: self.get_refresh_failures(),
+            retry_countdown: self
+                .get_error_retry_at()
+                .map(|at| retry_countdown_text(at - clock.now())),
+            minute: clock.now().timestamp() / 60,
+        };
+
+        let mut rendered = self.rendered.lock().unwrap();
+        if rendered.as_ref() == Some(&snapshot) {
+            return false;
+        }
+        *rendered = Some(snapshot);
+        true
+    }
+
     /// Check if any role is expiring soon.
     #[allow(dead_code)] // Full PIM integration pending
     pub fn has_expiring_roles(&self) -> bool {
@@ -147,7 +311,7 @@ impl AppState {
         let threshold = pim.settings.expiry_warning_minutes as i64;
         pim.active_assignments
             .iter()
-            .any(|a| a.is_expiring_soon(threshold))
+            .any(|a| a.is_expiring_soon(threshold, &SystemClock))
     }
 }
 
@@ -157,6 +321,22 @@ impl Default for AppState {
     }
 }
 
+/// Everything the menu displays, compared to skip redundant rebuilds.
+#[derive(Debug, Clone, PartialEq)]
+struct MenuSnapshot {
+    auth_state: AuthState,
+    user_info: Option<UserInfo>,
+    token_expiry: Option<DateTime<Utc>>,
+    settings: Settings,
+    pim_state: PimState,
+    health: Option<HealthReport>,
+    refresh_failures: u32,
+    /// The retry countdown changes without any state changing.
+    retry_countdown: Option<String>,
+    /// Countdowns are shown in whole minutes, so a new minute needs a rebuild.
+    minute: i64,
+}
+
 /// Authentication state enum.
 #[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
@@ -167,13 +347,94 @@ pub enum AuthState {
     Authenticating,
     /// Successfully signed in.
     SignedIn,
-    /// Error occurred.
-    Error { message: String },
+    /// Error occurred; `class` decides what the error menu offers.
+    Error { message: String, class: ErrorClass },
     /// Offline (no network).
     Offline,
 }
 
+/// Something that moves the auth state. See [`AuthState::on`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthEvent {
+    /// Signed out, or no session could be restored.
+    SignedOut,
+    /// A browser sign-in or a session restore started.
+    Authenticating,
+    /// Signed in or restored as `user_info`.
+    SignedIn {
+        user_info: UserInfo,
+        expires_at: DateTime<Utc>,
+    },
+    /// Azure AD became unreachable; `user_info` is set when restoring offline.
+    WentOffline { user_info: Option<UserInfo> },
+    /// Azure AD is reachable again.
+    BackOnline,
+    /// Something failed, retried silently at `retry_at` if recoverable.
+    Failed {
+        message: String,
+        retry_at: Option<DateTime<Utc>>,
+        class: ErrorClass,
+    },
+    /// A silent retry recovered from an error.
+    Recovered,
+}
+
+impl AuthEvent {
+    /// Name used when tracing transitions.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SignedOut => "signed-out",
+            Self::Authenticating => "authenticating",
+            Self::SignedIn { .. } => "signed-in",
+            Self::WentOffline { .. } => "went-offline",
+            Self::BackOnline => "back-online",
+            Self::Failed { .. } => "failed",
+            Self::Recovered => "recovered",
+        }
+    }
+}
+
 impl AuthState {
+    /// The state after `event`, or an error if the transition isn't allowed.
+    ///
+    /// `has_user_info` is whether user info is known once the event applies;
+    /// states that show the user's session require it. A sign-in completing
+    /// after the user signed out is refused, so a late callback or restore
+    /// can't sign them back in.
+    pub fn on(&self, event: &AuthEvent, has_user_info: bool) -> Result<Self, AuthTransitionError> {
+        let next = match (self, event) {
+            (_, AuthEvent::SignedOut) => Some(Self::SignedOut),
+            (_, AuthEvent::Authenticating) => Some(Self::Authenticating),
+            (Self::SignedOut, AuthEvent::SignedIn { .. }) => None,
+            (_, AuthEvent::SignedIn { .. }) => Some(Self::SignedIn),
+            (_, AuthEvent::WentOffline { .. }) if has_user_info => Some(Self::Offline),
+            (Self::Offline | Self::SignedIn, AuthEvent::BackOnline) => Some(Self::SignedIn),
+            (_, AuthEvent::Failed { message, class, .. }) => Some(Self::Error {
+                message: message.clone(),
+                class: *class,
+            }),
+            (Self::Error { .. } | Self::SignedIn, AuthEvent::Recovered) if has_user_info => {
+                Some(Self::SignedIn)
+            }
+            _ => None,
+        };
+        next.ok_or(AuthTransitionError {
+            from: self.name(),
+            event: event.name(),
+        })
+    }
+
+    /// Name used when tracing transitions.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SignedOut => "signed-out",
+            Self::Authenticating => "authenticating",
+            Self::SignedIn => "signed-in",
+            Self::Error { .. } => "error",
+            Self::Offline => "offline",
+        }
+    }
+
     /// Check if signed in.
     #[allow(dead_code)]
     pub fn is_signed_in(&self) -> bool {
@@ -190,19 +451,71 @@ impl AuthState {
     #[allow(dead_code)]
     pub fn error_message(&self) -> Option<&str> {
         match self {
-            AuthState::Error { message } => Some(message),
+            AuthState::Error { message, .. } => Some(message),
             _ => None,
         }
     }
 }
 
-/// Application settings.
-#[derive(Debug, Clone)]
+/// Application settings - persisted locally.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     /// Auto-launch at login.
     pub auto_launch: bool,
     /// Show token expiry countdown in menu.
     pub show_expiry: bool,
+    /// Show job title and office from the Graph profile under the user's name.
+    pub show_profile_details: bool,
+    /// Attempt to restore the previous session from the Keychain at launch.
+    pub restore_session_on_launch: bool,
+    /// Load PIM roles automatically after sign-in and session restore.
+    #[serde(alias = "prefetch_roles_on_launch")]
+    pub auto_load_roles: bool,
+    /// Show a notification once the session has been restored at launch.
+    pub notify_session_restored: bool,
+    /// Sound played for each notification event.
+    pub sounds: SoundSettings,
+    /// Briefly animate the status item after a successful activation.
+    pub animate_on_success: bool,
+    /// Ask before quitting while roles are still active.
+    pub warn_active_roles_on_quit: bool,
+    /// Flash the status item when an activation fails or a role expires.
+    pub flash_on_critical_events: bool,
+    /// Minutes before token expiry at which the countdown turns to a warning.
+    pub token_warning_minutes: u32,
+    /// Minutes before token expiry at which the countdown turns critical.
+    pub token_critical_minutes: u32,
+    /// Show the token countdown next to the status item icon.
+    pub show_menu_bar_countdown: bool,
+    /// Refresh the token once it reaches the warning threshold.
+    pub refresh_at_token_warning: bool,
+    /// Send expiry warnings as time-sensitive, so they may break through a Focus.
+    pub time_sensitive_during_focus: bool,
+    /// Show an extra status item with the remaining minutes of each active role.
+    pub status_item_per_role: bool,
+    /// Custom location of the favorites and presets file, e.g. in a synced folder.
+    pub favorites_file: Option<PathBuf>,
+    /// Require a written justification of at least this many characters for every activation.
+    pub justification_min_length: Option<u32>,
+    /// Don't read group memberships; only roles eligible to the user directly are shown.
+    pub skip_group_lookup: bool,
+    /// After activating a Key Vault data role, offer to copy a Key Vault access token.
+    pub offer_key_vault_token: bool,
+    /// When a role is about to expire, offer to extend it.
+    pub offer_extension_at_expiry: bool,
+    /// Local time to list roles still active and offer to deactivate them; `None` for never.
+    pub end_of_day_summary: Option<NaiveTime>,
+    /// Subscription name patterns and their environments; roles in production are flagged.
+    pub environments: Vec<EnvironmentRule>,
+    /// Hours to leave subscriptions without PIM access out of role scans; 0 scans them every time.
+    pub skip_denied_subscriptions_hours: u32,
+    /// Most roles a subscription's submenu lists before they are split by name; 0 never splits.
+    pub role_page_size: u32,
+    /// Show everyone's active roles in the chosen subscriptions, for PIM administrators.
+    pub admin_view: bool,
+    /// IDs of the subscriptions the administrator view lists.
+    pub admin_view_subscriptions: Vec<String>,
 }
 
 impl Default for Settings {
@@ -210,12 +523,50 @@ impl Default for Settings {
         Self {
             auto_launch: true,
             show_expiry: true,
+            show_profile_details: false,
+            restore_session_on_launch: true,
+            auto_load_roles: true,
+            notify_session_restored: false,
+            sounds: SoundSettings::default(),
+            animate_on_success: false,
+            warn_active_roles_on_quit: true,
+            flash_on_critical_events: false,
+            token_warning_minutes: 15,
+            token_critical_minutes: 5,
+            show_menu_bar_countdown: false,
+            refresh_at_token_warning: false,
+            time_sensitive_during_focus: false,
+            status_item_per_role: false,
+            favorites_file: None,
+            justification_min_length: None,
+            skip_group_lookup: false,
+            offer_key_vault_token: true,
+            offer_extension_at_expiry: true,
+            end_of_day_summary: None,
+            environments: environment::default_rules(),
+            skip_denied_subscriptions_hours: 24,
+            role_page_size: 25,
+            admin_view: false,
+            admin_view_subscriptions: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// How close the token is to expiring, given the time it has left.
+    pub fn token_urgency(&self, remaining: Duration) -> ExpiryUrgency {
+        if remaining <= Duration::minutes(self.token_critical_minutes as i64) {
+            ExpiryUrgency::Critical
+        } else if remaining <= Duration::minutes(self.token_warning_minutes as i64) {
+            ExpiryUrgency::Warning
+        } else {
+            ExpiryUrgency::Normal
         }
     }
 }
 
 /// PIM (Privileged Identity Management) state.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PimState {
     /// Cached eligible roles (refreshed on demand, cached for 1 hour).
     pub eligible_roles: Vec<EligibleRole>,
@@ -227,6 +578,38 @@ pub struct PimState {
     pub settings: PimSettings,
     /// Current PIM API status.
     pub api_status: PimApiStatus,
+    /// Keys of roles with an activation request in flight.
+    pub activating: HashSet<String>,
+    /// Requests waiting on an approval, from the approvals worker.
+    pub approvals: ApprovalCounts,
+    /// The user's activation requests waiting for an approver.
+    pub pending_requests: Vec<PendingRequest>,
+    /// Other users' requests waiting for the user to review, from the approvals worker.
+    pub approval_requests: Vec<ApprovalRequest>,
+    /// The user's activations scheduled to start later, soonest first.
+    pub scheduled_activations: Vec<ScheduledActivation>,
+    /// Activation rules of the eligible roles whose policy could be read, by
+    /// role key, from the PIM actor.
+    pub role_policies: HashMap<String, RolePolicy>,
+    /// Elevation plan being activated, if any.
+    pub plan_progress: Option<PlanProgress>,
+    /// Reminders to deactivate roles, from the PIM actor.
+    pub deactivation_reminders: DeactivationReminders,
+    /// Roles to deactivate at a set time, from the PIM actor.
+    pub scheduled_deactivations: ScheduledDeactivations,
+    /// Notes taken during elevations, from the PIM actor.
+    pub session_notes: NotesStore,
+    /// Display names of the user's groups from the last role refresh.
+    pub group_names: GroupNames,
+    /// Current subscription names, shown in place of the cached ones.
+    pub subscription_names: SubscriptionNames,
+    /// Whether group-based roles were included in the last refresh.
+    pub group_lookup: GroupLookup,
+    /// Names of the subscriptions left out of role scans for lack of PIM access.
+    pub skipped_subscriptions: Vec<String>,
+    /// Everyone's active roles in the administrator view's subscriptions;
+    /// `None` until first listed.
+    pub tenant_assignments: Option<Vec<TenantAssignment>>,
 }
 
 impl Default for PimState {
@@ -237,6 +620,21 @@ impl Default for PimState {
             roles_cached_at: None,
             settings: PimSettings::default(),
             api_status: PimApiStatus::Unknown,
+            activating: HashSet::new(),
+            approvals: ApprovalCounts::default(),
+            pending_requests: Vec::new(),
+            approval_requests: Vec::new(),
+            scheduled_activations: Vec::new(),
+            role_policies: HashMap::new(),
+            plan_progress: None,
+            deactivation_reminders: DeactivationReminders::default(),
+            scheduled_deactivations: ScheduledDeactivations::default(),
+            session_notes: NotesStore::default(),
+            group_names: GroupNames::new(),
+            subscription_names: SubscriptionNames::default(),
+            group_lookup: GroupLookup::default(),
+            skipped_subscriptions: Vec::new(),
+            tenant_assignments: None,
         }
     }
 }
@@ -269,8 +667,16 @@ impl PimState {
             .contains(&role.favorites_key())
     }
 
+    /// A role's title with its custom label, if any, after it, so type-select
+    /// still matches the role name.
+    pub fn labeled_title(&self, title: String, role_key: &str) -> String {
+        match self.settings.label_for(role_key) {
+            Some(label) => format!("{}  ({})", title, label),
+            None => title,
+        }
+    }
+
     /// Check if cache is still valid (within 1 hour).
-    #[allow(dead_code)] // Full PIM integration pending
     pub fn is_cache_valid(&self) -> bool {
         match self.roles_cached_at {
             Some(cached_at) => {
@@ -281,6 +687,11 @@ impl PimState {
         }
     }
 
+    /// Header shown while offline, e.g. "Offline — data from 10:32".
+    pub fn offline_label(&self) -> String {
+        offline_text(self.roles_cached_at.map(|t| t.with_timezone(&Local)))
+    }
+
     /// Get active role count for badge.
     #[allow(dead_code)] // Full PIM integration pending
     pub fn active_role_count(&self) -> usize {
@@ -293,7 +704,43 @@ impl PimState {
         let threshold = self.settings.expiry_warning_minutes as i64;
         self.active_assignments
             .iter()
-            .any(|a| a.is_expiring_soon(threshold))
+            .any(|a| a.is_expiring_soon(threshold, &SystemClock))
+    }
+
+    /// Check if an activation is in flight for a role.
+    pub fn is_activating(&self, role: &EligibleRole) -> bool {
+        self.activating.contains(&role.favorites_key())
+    }
+
+    /// Check if a role's activations need an approver.
+    pub fn requires_approval(&self, role: &EligibleRole) -> bool {
+        self.role_policies
+            .get(&role.favorites_key())
+            .is_some_and(|policy| policy.approval_required)
+    }
+
+    /// The longest activation the role's policy allows, in minutes, if known.
+    pub fn max_duration(&self, role_key: &str) -> Option<u32> {
+        self.role_policies.get(role_key)?.max_minutes
+    }
+
+    /// The longest of `minutes` the role's policy allows.
+    pub fn clamp_duration(&self, role_key: &str, minutes: u32) -> u32 {
+        self.role_policies
+            .get(role_key)
+            .map_or(minutes, |policy| policy.clamp(minutes))
+    }
+
+    /// Minutes to activate the role for: its remembered duration, cut to
+    /// what its policy allows.
+    pub fn duration_for(&self, role_key: &str) -> u32 {
+        self.clamp_duration(role_key, self.settings.duration_for(role_key))
+    }
+
+    /// Check if a request for a role is waiting for an approver.
+    pub fn is_pending_approval(&self, role: &EligibleRole) -> bool {
+        let key = role.favorites_key();
+        self.pending_requests.iter().any(|r| r.role_key == key)
     }
 
     /// Toggle favorite status for a role.
@@ -303,10 +750,14 @@ impl PimState {
 
     /// Get favorite roles only.
     pub fn favorite_roles(&self) -> Vec<&EligibleRole> {
-        self.eligible_roles
+        let mut favorites: Vec<_> = self
+            .eligible_roles
             .iter()
             .filter(|role| self.is_favorite(role))
-            .collect()
+            .collect();
+        // Sorted by menu title, so type-select moves through them in order
+        favorites.sort_by_key(|role| role.menu_title());
+        favorites
     }
 
     /// Get non-favorite roles grouped by subscription name.
@@ -381,9 +832,21 @@ impl std::fmt::Debug for MenuCallbacks {
     }
 }
 
+/// Offline header for data cached at `cached_at`, in that time's zone.
+fn offline_text<Tz: TimeZone>(cached_at: Option<DateTime<Tz>>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match cached_at {
+        Some(cached_at) => format!("Offline — data from {}", cached_at.format("%H:%M")),
+        None => "Offline — no cached data".to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
 
     #[test]
     fn test_auth_state() {
@@ -395,10 +858,77 @@ mod tests {
 
         let state = AuthState::Error {
             message: "test".into(),
+            class: ErrorClass::NeedsReauth,
         };
         assert_eq!(state.error_message(), Some("test"));
     }
 
+    #[test]
+    fn test_auth_transitions() {
+        let user_info = UserInfo {
+            display_name: "Test User".into(),
+            email: "test@example.com".into(),
+            tenant_name: "Test".into(),
+            tenant_id: "tenant-id".into(),
+            user_id: "user-id".into(),
+            job_title: None,
+            office_location: None,
+        };
+        let signed_in = AuthEvent::SignedIn {
+            user_info: user_info.clone(),
+            expires_at: Utc::now(),
+        };
+        let failed = AuthEvent::Failed {
+            message: "boom".into(),
+            retry_at: None,
+            class: ErrorClass::NeedsReauth,
+        };
+
+        // A late sign-in can't undo a sign-out
+        assert!(AuthState::SignedOut.on(&signed_in, true).is_err());
+        assert_eq!(
+            AuthState::Authenticating.on(&signed_in, true),
+            Ok(AuthState::SignedIn)
+        );
+
+        // Offline and recovered states need user info to show
+        let offline = AuthEvent::WentOffline { user_info: None };
+        assert!(AuthState::Authenticating.on(&offline, false).is_err());
+        assert_eq!(
+            AuthState::SignedIn.on(&offline, true),
+            Ok(AuthState::Offline)
+        );
+        let error = AuthState::SignedIn.on(&failed, true).unwrap();
+        assert_eq!(error.error_message(), Some("boom"));
+        assert!(error.on(&AuthEvent::Recovered, false).is_err());
+        assert_eq!(
+            error.on(&AuthEvent::Recovered, true),
+            Ok(AuthState::SignedIn)
+        );
+
+        assert_eq!(
+            AuthState::Error {
+                message: "boom".into(),
+                class: ErrorClass::NeedsReauth,
+            }
+            .on(&AuthEvent::BackOnline, true),
+            Err(AuthTransitionError {
+                from: "error",
+                event: "back-online"
+            })
+        );
+
+        // Refused transitions leave the app state alone
+        let app_state = AppState::new();
+        assert!(app_state.transition(signed_in.clone()).is_err());
+        app_state.transition(AuthEvent::Authenticating).unwrap();
+        app_state.transition(signed_in).unwrap();
+        assert_eq!(app_state.get_user_info(), Some(user_info));
+        app_state.transition(AuthEvent::SignedOut).unwrap();
+        assert_eq!(app_state.get_user_info(), None);
+        assert_eq!(app_state.get_token_expiry(), None);
+    }
+
     #[test]
     fn test_app_state() {
         let app_state = AppState::new();
@@ -411,4 +941,93 @@ mod tests {
         app_state.clear();
         assert!(!app_state.get_auth_state().is_signed_in());
     }
+
+    #[test]
+    fn test_menu_rebuild_only_when_changed() {
+        let app_state = AppState::new();
+        let clock = MockClock::new(Utc::now());
+
+        assert!(app_state.mark_menu_rendered(&clock));
+        assert!(!app_state.mark_menu_rendered(&clock));
+
+        // Identical data does not trigger a rebuild
+        app_state.set_pim_active_assignments(vec![]);
+        assert!(!app_state.mark_menu_rendered(&clock));
+
+        app_state.set_auth_state(AuthState::SignedIn);
+        assert!(app_state.mark_menu_rendered(&clock));
+
+        // Countdowns need refreshing once a minute
+        clock.advance(Duration::minutes(1));
+        assert!(app_state.mark_menu_rendered(&clock));
+    }
+
+    #[test]
+    fn test_role_activating() {
+        let role = EligibleRole {
+            id: "eligibility-id".into(),
+            role_definition_id: "role-def-id".into(),
+            role_name: "Contributor".into(),
+            subscription_id: "sub-id".into(),
+            subscription_name: "Test Sub".into(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            principal_id: "user-id".into(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        };
+        let mut pim_state = PimState::default();
+        assert!(!pim_state.is_activating(&role));
+
+        pim_state.activating.insert(role.favorites_key());
+        assert!(pim_state.is_activating(&role));
+    }
+
+    #[test]
+    fn test_duration_clamped_to_policy() {
+        let role_key = "/subscriptions/sub-id|contributor";
+        let mut pim_state = PimState::default();
+        pim_state.settings.set_duration(role_key, 240);
+        assert_eq!(pim_state.duration_for(role_key), 240);
+
+        pim_state.role_policies.insert(
+            role_key.to_string(),
+            RolePolicy {
+                max_minutes: Some(60),
+                approval_required: true,
+            },
+        );
+        assert_eq!(pim_state.duration_for(role_key), 60);
+        assert_eq!(pim_state.clamp_duration(role_key, 30), 30);
+        assert_eq!(pim_state.max_duration(role_key), Some(60));
+        assert_eq!(pim_state.duration_for("/subscriptions/other|reader"), 60);
+    }
+
+    #[test]
+    fn test_offline_text() {
+        let cached_at = Utc.with_ymd_and_hms(2024, 5, 1, 10, 32, 0).unwrap();
+        assert_eq!(offline_text(Some(cached_at)), "Offline — data from 10:32");
+        assert_eq!(offline_text::<Utc>(None), "Offline — no cached data");
+    }
+
+    #[test]
+    fn test_token_urgency() {
+        let settings = Settings::default();
+        assert_eq!(
+            settings.token_urgency(Duration::minutes(45)),
+            ExpiryUrgency::Normal
+        );
+        assert_eq!(
+            settings.token_urgency(Duration::minutes(15)),
+            ExpiryUrgency::Warning
+        );
+        assert_eq!(
+            settings.token_urgency(Duration::minutes(3)),
+            ExpiryUrgency::Critical
+        );
+        assert_eq!(
+            settings.token_urgency(Duration::minutes(-1)),
+            ExpiryUrgency::Critical
+        );
+    }
 }
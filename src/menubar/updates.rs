@@ -3,24 +3,54 @@
 //! These functions ensure all AppKit operations run on the main thread.
 
 use crate::auth::graph::UserInfo;
+use crate::crash;
+use crate::error::ErrorClass;
+use crate::health::HealthReport;
+use crate::menubar::accessibility;
 use crate::menubar::builder::MenuBar;
-use crate::menubar::state::{get_app_state, AuthState, Settings};
-use crate::pim::{ActiveAssignment, EligibleRole, PimApiStatus};
+use crate::menubar::delegate::{
+    copy_text_to_clipboard, send_activate_role, send_deactivate_all, send_deactivate_role,
+    send_extend_role, send_grant_consent, ActionOutcome,
+};
+use crate::menubar::dialogs;
+use crate::menubar::state::{get_app_state, AuthEvent, AuthState, Settings};
+use crate::notifications;
+use crate::pim::policy::{ActivationCheck, PolicyRemedy, RolePolicy};
+use crate::pim::{
+    ActivationOptions, ActiveAssignment, ApprovalCounts, ApprovalRequest, DeactivationReminders,
+    EligibleRole, GroupLookup, GroupNames, NotesStore, PendingRequest, PimApiStatus, PimSettings,
+    PlanProgress, ScheduledActivation, ScheduledDeactivations, SubscriptionNames, TenantAssignment,
+    EXPIRY_EXTENSION_MINUTES,
+};
 use chrono::{DateTime, Utc};
 use dispatch::Queue;
+use objc2_app_kit::NSApplication;
 use objc2_foundation::MainThreadMarker;
+use std::collections::HashMap;
+use tokio::sync::oneshot;
 use tracing::info;
 
+/// Apply `event` to the auth state, announcing it to VoiceOver if the state changed.
+fn apply_auth_event(mtm: MainThreadMarker, event: AuthEvent) {
+    let Some(state) = get_app_state() else {
+        return;
+    };
+    let before = state.get_auth_state();
+    let announcement = accessibility::auth_announcement(&event);
+    if state.transition(event).is_err() || state.get_auth_state() == before {
+        return;
+    }
+    if let Some(text) = announcement {
+        accessibility::announce(mtm, &text);
+    }
+}
+
 /// Update the UI to reflect the signed-out state.
 pub fn update_signed_out() {
     dispatch_to_main(|| {
         if let Some(mtm) = MainThreadMarker::new() {
-            if let Some(state) = get_app_state() {
-                state.set_auth_state(AuthState::SignedOut);
-                state.set_user_info(None);
-                state.set_token_expiry(None);
-            }
-            MenuBar::build_signed_out_menu(mtm);
+            apply_auth_event(mtm, AuthEvent::SignedOut);
+            MenuBar::rebuild_menu(mtm);
             info!("UI updated: signed out");
         }
     });
@@ -30,10 +60,8 @@ pub fn update_signed_out() {
 pub fn update_authenticating() {
     dispatch_to_main(|| {
         if let Some(mtm) = MainThreadMarker::new() {
-            if let Some(state) = get_app_state() {
-                state.set_auth_state(AuthState::Authenticating);
-            }
-            MenuBar::build_authenticating_menu(mtm);
+            apply_auth_event(mtm, AuthEvent::Authenticating);
+            MenuBar::rebuild_menu(mtm);
             info!("UI updated: authenticating");
         }
     });
@@ -43,32 +71,185 @@ pub fn update_authenticating() {
 pub fn update_signed_in(user_info: UserInfo, expires_at: DateTime<Utc>) {
     dispatch_to_main(move || {
         if let Some(mtm) = MainThreadMarker::new() {
-            if let Some(state) = get_app_state() {
-                state.set_auth_state(AuthState::SignedIn);
-                state.set_user_info(Some(user_info));
-                state.set_token_expiry(Some(expires_at));
-            }
-            MenuBar::build_signed_in_menu(mtm);
+            apply_auth_event(
+                mtm,
+                AuthEvent::SignedIn {
+                    user_info,
+                    expires_at,
+                },
+            );
+            MenuBar::rebuild_menu(mtm);
             info!("UI updated: signed in");
         }
     });
 }
 
-/// Update the UI to reflect an error state.
-pub fn update_error(message: String) {
+/// Update the UI to reflect that Azure is unreachable.
+///
+/// The cached roles stay visible read-only; `user_info` is set when restoring
+/// a session from the Keychain without a network.
+pub fn update_offline(user_info: Option<UserInfo>) {
     dispatch_to_main(move || {
         if let Some(mtm) = MainThreadMarker::new() {
-            if let Some(state) = get_app_state() {
-                state.set_auth_state(AuthState::Error {
-                    message: message.clone(),
-                });
+            apply_auth_event(mtm, AuthEvent::WentOffline { user_info });
+            MenuBar::rebuild_menu(mtm);
+            info!("UI updated: offline");
+        }
+    });
+}
+
+/// Update the UI after connectivity returned while offline.
+pub fn update_back_online() {
+    dispatch_to_main(|| {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if get_app_state().is_some_and(|s| s.get_auth_state() == AuthState::Offline) {
+                apply_auth_event(mtm, AuthEvent::BackOnline);
             }
-            MenuBar::build_error_menu(mtm, &message);
+            MenuBar::rebuild_menu(mtm);
+            info!("UI updated: back online");
+        }
+    });
+}
+
+/// Update the UI to reflect an error state, with the menu `class` calls for.
+pub fn update_error(message: String, class: ErrorClass) {
+    update_error_with_retry(message, None, class);
+}
+
+/// Update the UI to reflect an error that is retried silently at `retry_at`.
+pub fn update_recoverable_error(message: String, retry_at: DateTime<Utc>) {
+    update_error_with_retry(message, Some(retry_at), ErrorClass::Transient);
+}
+
+fn update_error_with_retry(message: String, retry_at: Option<DateTime<Utc>>, class: ErrorClass) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            apply_auth_event(
+                mtm,
+                AuthEvent::Failed {
+                    message: message.clone(),
+                    retry_at,
+                    class,
+                },
+            );
+            MenuBar::rebuild_menu(mtm);
             info!("UI updated: error - {}", message);
         }
     });
 }
 
+/// Update the UI after a silent retry recovered from an error.
+pub fn update_error_recovered() {
+    dispatch_to_main(|| {
+        if let Some(mtm) = MainThreadMarker::new() {
+            let in_error = get_app_state()
+                .is_some_and(|s| matches!(s.get_auth_state(), AuthState::Error { .. }));
+            if in_error {
+                apply_auth_event(mtm, AuthEvent::Recovered);
+            }
+            MenuBar::rebuild_menu(mtm);
+            info!("UI updated: recovered from error");
+        }
+    });
+}
+
+/// Show setup guidance for a redirect URI missing from the app registration.
+pub fn show_redirect_uri_help(redirect_uri: String) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            dialogs::show_redirect_uri_help(mtm, &redirect_uri);
+        }
+    });
+}
+
+/// Offer a corrected retry after an activation failed the role's policy.
+pub fn offer_policy_retry(
+    role_key: String,
+    role_name: String,
+    mut justification: String,
+    mut options: ActivationOptions,
+    remedy: PolicyRemedy,
+) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            let Some(input) = dialogs::show_policy_remedy(mtm, &role_name, &remedy) else {
+                return;
+            };
+            match remedy {
+                PolicyRemedy::MaxDuration(minutes) => options.duration_minutes = Some(minutes),
+                PolicyRemedy::EnterJustification | PolicyRemedy::EnterDetailedJustification(_) => {
+                    justification = input
+                }
+                PolicyRemedy::EnterTicketNumber => options.ticket_number = Some(input),
+                PolicyRemedy::Explain(_) => return,
+            }
+            info!("Retrying activation of {} after policy failure", role_name);
+            send_activate_role(role_key, justification, options);
+        }
+    });
+}
+
+/// Show the result of checking a role's activation without activating it.
+pub fn show_activation_check(role_name: String, duration_minutes: u32, check: ActivationCheck) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            dialogs::show_activation_check(mtm, &role_name, duration_minutes, &check);
+        }
+    });
+}
+
+/// Ask the user to consent to scopes missing from the current grant.
+pub fn offer_consent(scopes: Vec<String>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if dialogs::confirm_consent(mtm, &scopes) {
+                send_grant_consent(scopes);
+            } else {
+                info!("Consent for {:?} postponed", scopes);
+            }
+        }
+    });
+}
+
+/// Let a quit deferred by `applicationShouldTerminate:` proceed.
+pub fn finish_termination() {
+    dispatch_to_main(|| {
+        if let Some(mtm) = MainThreadMarker::new() {
+            info!("Shutdown finished, terminating");
+            unsafe {
+                NSApplication::sharedApplication(mtm).replyToApplicationShouldTerminate(true)
+            };
+        }
+    });
+}
+
+/// Show the result of a menu action that isn't otherwise visible in the menu.
+///
+/// The menu is closed by the time an action completes, so outcomes are
+/// delivered as notifications.
+pub fn show_action_outcome(label: &str, outcome: &ActionOutcome) {
+    match outcome {
+        ActionOutcome::Done => {}
+        ActionOutcome::Succeeded(message) => notifications::notify(label, message),
+        ActionOutcome::Failed(reason) => {
+            notifications::notify(&format!("{} failed", label), reason)
+        }
+    }
+}
+
+/// Offer the most recent unseen crash report to the user, if any.
+pub fn offer_pending_crash_report() {
+    dispatch_to_main(|| {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(report) = crash::latest_pending_report() {
+                info!("Found crash report from previous run: {}", report.display());
+                dialogs::show_crash_report_prompt(mtm, &report);
+                crash::mark_reports_seen();
+            }
+        }
+    });
+}
+
 /// Update the token expiry time (e.g., after refresh).
 pub fn update_token_expiry(expires_at: DateTime<Utc>) {
     dispatch_to_main(move || {
@@ -83,6 +264,46 @@ pub fn update_token_expiry(expires_at: DateTime<Utc>) {
     });
 }
 
+/// Show the results of the health checks in the Status submenu.
+pub fn update_health(report: HealthReport) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                state.set_health(report);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update the menu header after the user's profile changed.
+pub fn update_user_info(user_info: UserInfo) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                // A sign-out may have raced the profile refresh
+                if state.get_user_info().is_some() {
+                    state.set_user_info(Some(user_info));
+                }
+            }
+            MenuBar::rebuild_menu(mtm);
+            info!("UI updated: user info");
+        }
+    });
+}
+
+/// Update the count of consecutive background token refresh failures.
+pub fn update_refresh_failures(count: u32) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                state.set_refresh_failures(count);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
 /// Update settings and rebuild menu.
 pub fn update_settings(settings: Settings) {
     dispatch_to_main(move || {
@@ -112,11 +333,11 @@ pub fn rebuild_menu() {
 
 /// Update the UI with new eligible roles.
 #[allow(dead_code)]
-pub fn update_pim_eligible_roles(roles: Vec<EligibleRole>) {
+pub fn update_pim_eligible_roles(roles: Vec<EligibleRole>, group_names: GroupNames) {
     dispatch_to_main(move || {
         if let Some(mtm) = MainThreadMarker::new() {
             if let Some(state) = get_app_state() {
-                state.set_pim_eligible_roles(roles);
+                state.set_pim_eligible_roles(roles, group_names);
             }
             MenuBar::rebuild_menu(mtm);
             info!("PIM eligible roles updated");
@@ -124,12 +345,42 @@ pub fn update_pim_eligible_roles(roles: Vec<EligibleRole>) {
     });
 }
 
+/// Show whether group-based roles are included.
+pub fn update_pim_group_lookup(lookup: GroupLookup) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.group_lookup = lookup;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update subscription names and show them on the current roles.
+pub fn update_subscription_names(names: SubscriptionNames) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                state.set_subscription_names(names);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
 /// Update the UI with active role assignments.
 #[allow(dead_code)]
 pub fn update_pim_active_assignments(assignments: Vec<ActiveAssignment>) {
     dispatch_to_main(move || {
         if let Some(mtm) = MainThreadMarker::new() {
             if let Some(state) = get_app_state() {
+                let old = state.get_pim_state().active_assignments;
+                if let Some(text) = accessibility::assignment_changes(&old, &assignments) {
+                    accessibility::announce(mtm, &text);
+                }
                 state.set_pim_active_assignments(assignments);
             }
             MenuBar::rebuild_menu(mtm);
@@ -138,17 +389,37 @@ pub fn update_pim_active_assignments(assignments: Vec<ActiveAssignment>) {
     });
 }
 
+/// Update the UI with favorites and presets reloaded from disk.
+pub fn update_pim_settings(settings: PimSettings) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                state.set_pim_settings(settings);
+            }
+            MenuBar::rebuild_menu(mtm);
+            info!("PIM settings updated");
+        }
+    });
+}
+
 /// Update the UI after a role has been activated.
-#[allow(dead_code)]
 pub fn update_pim_role_activated(assignment: ActiveAssignment) {
     dispatch_to_main(move || {
         if let Some(mtm) = MainThreadMarker::new() {
+            let mut animate = false;
             if let Some(state) = get_app_state() {
                 let mut pim_state = state.get_pim_state();
+                pim_state
+                    .active_assignments
+                    .retain(|a| a.role_key() != assignment.role_key());
                 pim_state.active_assignments.push(assignment.clone());
                 state.set_pim_state(pim_state);
+                animate = state.get_settings().animate_on_success;
             }
             MenuBar::rebuild_menu(mtm);
+            if animate {
+                MenuBar::flash_success(mtm);
+            }
             info!(
                 "PIM role activated: {} on {}",
                 assignment.role_name, assignment.subscription_name
@@ -157,6 +428,286 @@ pub fn update_pim_role_activated(assignment: ActiveAssignment) {
     });
 }
 
+/// Update the progress of an elevation plan being activated.
+pub fn update_pim_plan_progress(progress: Option<PlanProgress>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.plan_progress = progress;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update the pending reminders to deactivate roles.
+pub fn update_pim_deactivation_reminders(reminders: DeactivationReminders) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.deactivation_reminders = reminders;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update the roles scheduled for deactivation.
+pub fn update_pim_scheduled_deactivations(scheduled: ScheduledDeactivations) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.scheduled_deactivations = scheduled;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update the notes taken during elevations.
+pub fn update_pim_session_notes(notes: NotesStore) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.session_notes = notes;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Update the subscriptions left out of role scans.
+pub fn update_pim_skipped_subscriptions(names: Vec<String>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.skipped_subscriptions = names;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Remind the user to deactivate a role, offering to do it now.
+pub fn show_deactivation_reminder(role_key: String, role_text: String) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if dialogs::confirm_deactivation_reminder(mtm, &role_text) {
+                send_deactivate_role(role_key);
+            } else {
+                info!("Keeping {} active after reminder", role_key);
+            }
+        }
+    });
+}
+
+/// List the roles still active at the end of the day, offering to deactivate them all.
+pub fn show_end_of_day_summary(roles: Vec<String>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if dialogs::confirm_end_of_day_deactivation(mtm, &roles) {
+                send_deactivate_all();
+            } else {
+                info!(
+                    "Keeping {} roles active after end-of-day summary",
+                    roles.len()
+                );
+            }
+        }
+    });
+}
+
+/// Offer to extend a role that is about to expire by [`EXPIRY_EXTENSION_MINUTES`].
+pub fn offer_extension(role_key: String, role_text: String, remaining: String) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if dialogs::confirm_extension(mtm, &role_text, &remaining) {
+                send_extend_role(role_key, EXPIRY_EXTENSION_MINUTES);
+            } else {
+                info!("Letting {} expire", role_key);
+            }
+        }
+    });
+}
+
+/// Offer to copy a Key Vault token after activating `role_text`.
+///
+/// Returns true if it was copied, so the caller can clear the clipboard later.
+pub async fn offer_key_vault_token(role_text: String, token: String) -> bool {
+    let (tx, rx) = oneshot::channel();
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            let copy = dialogs::confirm_copy_key_vault_token(mtm, &role_text);
+            if copy {
+                copy_text_to_clipboard(&token);
+                info!("Key Vault token copied to clipboard");
+            }
+            let _ = tx.send(copy);
+        }
+    });
+    rx.await.unwrap_or(false)
+}
+
+/// Update the counts of requests waiting on an approval.
+pub fn update_pim_approvals(counts: ApprovalCounts) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.approvals = counts;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Replace everyone's active roles shown in the administrator view.
+pub fn update_pim_tenant_assignments(assignments: Option<Vec<TenantAssignment>>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.tenant_assignments = assignments;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Replace the user's requests waiting for an approver.
+pub fn update_pim_pending_requests(requests: Vec<PendingRequest>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.pending_requests = requests;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Replace the requests waiting for the user to review.
+pub fn update_pim_approval_requests(requests: Vec<ApprovalRequest>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.approval_requests = requests;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Show a request just sent for approval, before the approvals worker lists it.
+pub fn add_pim_pending_request(request: PendingRequest) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                if !pim_state.pending_requests.iter().any(|r| r.is(&request.id)) {
+                    pim_state.pending_requests.push(request);
+                }
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Replace the user's scheduled activations.
+pub fn update_pim_scheduled_activations(activations: Vec<ScheduledActivation>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.scheduled_activations = activations;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Show an activation just scheduled, before the approvals worker lists it.
+pub fn add_pim_scheduled_activation(activation: ScheduledActivation) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                let scheduled = &mut pim_state.scheduled_activations;
+                if !scheduled.iter().any(|s| s.is(&activation.id)) {
+                    scheduled.push(activation);
+                    scheduled.sort_by_key(|s| s.start_time);
+                }
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Set the activation rules of the eligible roles.
+pub fn update_pim_role_policies(policies: HashMap<String, RolePolicy>) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.role_policies = policies;
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
+/// Flash the status item for a critical event, if enabled in settings.
+pub fn request_attention() {
+    dispatch_to_main(|| {
+        if let Some(mtm) = MainThreadMarker::new() {
+            let enabled =
+                get_app_state().is_some_and(|s| s.get_settings().flash_on_critical_events);
+            if enabled {
+                info!("Requesting attention for a critical event");
+                MenuBar::flash_attention(mtm);
+            }
+        }
+    });
+}
+
+/// Mark a role as activating, or clear the mark once the request has finished.
+pub fn update_pim_activating(role_key: String, activating: bool) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                if activating {
+                    pim_state.activating.insert(role_key);
+                } else {
+                    pim_state.activating.remove(&role_key);
+                }
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+        }
+    });
+}
+
 /// Update the UI to show PIM loading state.
 #[allow(dead_code)]
 pub fn update_pim_loading() {
@@ -209,6 +760,21 @@ pub fn update_pim_permission_denied(message: String) {
     });
 }
 
+/// Update the UI to show that the Azure Service Management permission is missing.
+pub fn update_pim_arm_permission_missing(consent_url: String) {
+    dispatch_to_main(move || {
+        if let Some(mtm) = MainThreadMarker::new() {
+            if let Some(state) = get_app_state() {
+                let mut pim_state = state.get_pim_state();
+                pim_state.api_status = PimApiStatus::ArmPermissionMissing { consent_url };
+                state.set_pim_state(pim_state);
+            }
+            MenuBar::rebuild_menu(mtm);
+            info!("PIM unavailable: Azure Service Management permission missing");
+        }
+    });
+}
+
 /// Helper to dispatch a closure to the main thread.
 fn dispatch_to_main<F>(f: F)
 where
@@ -0,0 +1,701 @@
+//! User notifications via the macOS Notification Center.
+//!
+//! Delivery goes through a [`NotificationBackend`], chosen once per process:
+//! UNUserNotificationCenter when running from a signed app bundle,
+//! NSUserNotification from an unsigned one, `terminal-notifier` when running
+//! unbundled (e.g. from `cargo run`, where Notification Center drops
+//! notifications), and a no-op for command-line mode. Callers may invoke
+//! these functions from any thread.
+//!
+//! Each notification has an interruption level. UNUserNotificationCenter
+//! passes it on as `UNNotificationInterruptionLevel` (macOS 12 and later):
+//! passive ones go straight to Notification Center without a banner or
+//! sound, and only time-sensitive ones may break through a Focus, if the
+//! user allows it for the app. Expiry warnings are time-sensitive only when
+//! the user opts in; otherwise they are active like everything else. The
+//! other backends have no levels and only keep passive notifications silent.
+//! Time-sensitive delivery also needs the
+//! `com.apple.developer.usernotifications.time-sensitive` entitlement in the
+//! app's signature.
+
+use block2::RcBlock;
+use dispatch::Queue;
+use objc2::rc::Retained;
+use objc2::runtime::{AnyClass, Bool, NSObject, NSObjectProtocol};
+use objc2::{msg_send, msg_send_id, sel};
+// NSUserNotification is deprecated, but still delivers for unsigned bundles
+#[allow(deprecated)]
+use objc2_foundation::{
+    NSError, NSString, NSUserNotification, NSUserNotificationCenter,
+    NSUserNotificationDefaultSoundName,
+};
+use once_cell::sync::OnceCell;
+use security_framework::os::macos::code_signing::{Flags, SecCode, SecRequirement};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Once;
+use tracing::{debug, info, warn};
+
+use crate::menubar::state::get_app_state;
+
+/// Events that can trigger a notification with a configurable sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationEvent {
+    /// An active role is about to expire.
+    ExpiryWarning,
+    /// A role was activated successfully.
+    ActivationSucceeded,
+    /// A role activation failed.
+    ActivationFailed,
+}
+
+impl NotificationEvent {
+    /// All events, in menu order.
+    pub const ALL: [NotificationEvent; 3] = [
+        Self::ExpiryWarning,
+        Self::ActivationSucceeded,
+        Self::ActivationFailed,
+    ];
+
+    /// The event for the outcome of a role activation.
+    pub fn for_activation(succeeded: bool) -> Self {
+        if succeeded {
+            Self::ActivationSucceeded
+        } else {
+            Self::ActivationFailed
+        }
+    }
+
+    /// How strongly the event may interrupt the user.
+    pub fn interruption_level(&self) -> InterruptionLevel {
+        match self {
+            Self::ExpiryWarning => InterruptionLevel::TimeSensitive,
+            Self::ActivationSucceeded | Self::ActivationFailed => InterruptionLevel::Active,
+        }
+    }
+
+    /// Label shown in the settings menu.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ExpiryWarning => "Expiry Warning",
+            Self::ActivationSucceeded => "Activation Succeeded",
+            Self::ActivationFailed => "Activation Failed",
+        }
+    }
+}
+
+/// How strongly a notification may interrupt the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptionLevel {
+    /// Routine information, e.g. a completed refresh. No banner or sound.
+    Passive,
+    /// The result of something the user did. Held back during Focus.
+    Active,
+    /// Needs attention soon, e.g. an expiring role. May break through Focus.
+    TimeSensitive,
+}
+
+impl InterruptionLevel {
+    /// The matching `UNNotificationInterruptionLevel` value.
+    fn un_value(self) -> usize {
+        match self {
+            Self::Passive => 0,
+            Self::Active => 1,
+            Self::TimeSensitive => 2,
+        }
+    }
+}
+
+/// Sound played with a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationSound {
+    /// Silent notification.
+    None,
+    /// The system default notification sound.
+    Default,
+    Basso,
+    Glass,
+    Hero,
+    Ping,
+    Sosumi,
+}
+
+impl NotificationSound {
+    /// All sounds, in menu order.
+    pub const ALL: [NotificationSound; 7] = [
+        Self::None,
+        Self::Default,
+        Self::Basso,
+        Self::Glass,
+        Self::Hero,
+        Self::Ping,
+        Self::Sosumi,
+    ];
+
+    /// Label shown in the settings menu.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::None => "None",
+            Self::Default => "Default",
+            Self::Basso => "Basso",
+            Self::Glass => "Glass",
+            Self::Hero => "Hero",
+            Self::Ping => "Ping",
+            Self::Sosumi => "Sosumi",
+        }
+    }
+}
+
+/// Per-event sound preferences.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub expiry_warning: NotificationSound,
+    pub activation_succeeded: NotificationSound,
+    pub activation_failed: NotificationSound,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            expiry_warning: NotificationSound::Default,
+            activation_succeeded: NotificationSound::None,
+            activation_failed: NotificationSound::Default,
+        }
+    }
+}
+
+impl SoundSettings {
+    /// Get the sound configured for an event.
+    pub fn sound_for(&self, event: NotificationEvent) -> NotificationSound {
+        match event {
+            NotificationEvent::ExpiryWarning => self.expiry_warning,
+            NotificationEvent::ActivationSucceeded => self.activation_succeeded,
+            NotificationEvent::ActivationFailed => self.activation_failed,
+        }
+    }
+
+    /// Set the sound for an event.
+    pub fn set_sound_for(&mut self, event: NotificationEvent, sound: NotificationSound) {
+        match event {
+            NotificationEvent::ExpiryWarning => self.expiry_warning = sound,
+            NotificationEvent::ActivationSucceeded => self.activation_succeeded = sound,
+            NotificationEvent::ActivationFailed => self.activation_failed = sound,
+        }
+    }
+}
+
+/// Deliver a passive notification with the given title and body.
+pub fn notify(title: &str, body: &str) {
+    notify_at(
+        InterruptionLevel::Passive,
+        NotificationSound::None,
+        title,
+        body,
+    );
+}
+
+/// Deliver a notification for an event, using the sound configured in settings.
+pub fn notify_event(event: NotificationEvent, title: &str, body: &str) {
+    let sound = get_app_state()
+        .map(|s| s.get_settings().sounds)
+        .unwrap_or_default()
+        .sound_for(event);
+    notify_at(event.interruption_level(), sound, title, body);
+}
+
+/// Deliver a notification at `level`, or at a lower one if the user hasn't
+/// opted in to time-sensitive notifications.
+fn notify_at(level: InterruptionLevel, sound: NotificationSound, title: &str, body: &str) {
+    let settings = get_app_state()
+        .map(|s| s.get_settings())
+        .unwrap_or_default();
+    let level = effective_level(level, settings.time_sensitive_during_focus);
+    post(level, title, body, sound_for_level(level, sound));
+}
+
+/// The level to deliver at: time-sensitive only if the user opted in to
+/// breaking through Focus.
+fn effective_level(level: InterruptionLevel, time_sensitive_allowed: bool) -> InterruptionLevel {
+    match level {
+        InterruptionLevel::TimeSensitive if !time_sensitive_allowed => InterruptionLevel::Active,
+        level => level,
+    }
+}
+
+/// The sound to play for a notification at `level`. Passive ones are silent.
+fn sound_for_level(level: InterruptionLevel, sound: NotificationSound) -> NotificationSound {
+    match level {
+        InterruptionLevel::Passive => NotificationSound::None,
+        _ => sound,
+    }
+}
+
+/// A notification ready for delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+    /// How strongly it may interrupt; backends without levels may ignore it.
+    pub level: InterruptionLevel,
+    /// Sound to play, already adjusted for the interruption level.
+    pub sound: NotificationSound,
+}
+
+/// Delivers notifications to the user.
+pub trait NotificationBackend: Send + Sync {
+    /// Short name for logs and diagnostics.
+    fn name(&self) -> &'static str;
+
+    /// Deliver a notification. May be called from any thread.
+    fn deliver(&self, notification: Notification);
+}
+
+// Loads UNUserNotificationCenter and friends, which are looked up by name
+#[link(name = "UserNotifications", kind = "framework")]
+extern "C" {}
+
+/// UNAuthorizationOptionSound | UNAuthorizationOptionAlert.
+const AUTHORIZATION_OPTIONS: usize = (1 << 1) | (1 << 2);
+
+/// macOS Notification Center via UNUserNotificationCenter. Requires running
+/// from a signed app bundle.
+///
+/// Permission is asked for on the first delivery; macOS prompts only once.
+pub struct UserNotificationCenterBackend {
+    authorization: Once,
+}
+
+impl UserNotificationCenterBackend {
+    pub fn new() -> Self {
+        Self {
+            authorization: Once::new(),
+        }
+    }
+}
+
+impl Default for UserNotificationCenterBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NotificationBackend for UserNotificationCenterBackend {
+    fn name(&self) -> &'static str {
+        "Notification Center"
+    }
+
+    fn deliver(&self, notification: Notification) {
+        self.authorization
+            .call_once(|| Queue::main().exec_async(request_authorization));
+        Queue::main().exec_async(move || {
+            deliver_to_user_notification_center(&notification);
+        });
+    }
+}
+
+/// macOS Notification Center via the deprecated NSUserNotification, for app
+/// bundles that aren't signed.
+pub struct LegacyNotificationCenterBackend;
+
+impl NotificationBackend for LegacyNotificationCenterBackend {
+    fn name(&self) -> &'static str {
+        "Notification Center (NSUserNotification)"
+    }
+
+    fn deliver(&self, notification: Notification) {
+        Queue::main().exec_async(move || {
+            deliver_to_notification_center(&notification);
+        });
+    }
+}
+
+/// The `terminal-notifier` command-line tool, for unbundled builds.
+pub struct TerminalNotifierBackend {
+    program: PathBuf,
+}
+
+impl NotificationBackend for TerminalNotifierBackend {
+    fn name(&self) -> &'static str {
+        "terminal-notifier"
+    }
+
+    fn deliver(&self, notification: Notification) {
+        let program = self.program.clone();
+        // Wait on a separate thread so the process is reaped without blocking the caller
+        std::thread::spawn(move || {
+            match Command::new(&program)
+                .args(terminal_notifier_args(&notification))
+                .output()
+            {
+                Ok(output) if output.status.success() => {
+                    debug!("Notification delivered: {}", notification.title);
+                }
+                Ok(output) => warn!("terminal-notifier failed: {}", output.status),
+                Err(e) => warn!("Failed to run terminal-notifier: {}", e),
+            }
+        });
+    }
+}
+
+/// Drops notifications, for command-line mode.
+pub struct NoopBackend;
+
+impl NotificationBackend for NoopBackend {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn deliver(&self, notification: Notification) {
+        debug!("Notification dropped: {}", notification.title);
+    }
+}
+
+/// Backend used by this process, chosen on first use unless set earlier.
+static BACKEND: OnceCell<Box<dyn NotificationBackend>> = OnceCell::new();
+
+/// Use `backend` for all notifications. Has no effect once a backend is in use.
+pub fn set_backend(backend: Box<dyn NotificationBackend>) {
+    let name = backend.name();
+    if BACKEND.set(backend).is_err() {
+        warn!("Notification backend already chosen, ignoring {}", name);
+    }
+}
+
+/// Choose the backend that can reach the user from this process.
+pub fn default_backend() -> Box<dyn NotificationBackend> {
+    let bundled = std::env::current_exe().is_ok_and(|exe| is_in_app_bundle(&exe));
+    if bundled && is_signed() {
+        return Box::new(UserNotificationCenterBackend::new());
+    }
+    if !bundled {
+        if let Some(program) = find_in_path("terminal-notifier") {
+            return Box::new(TerminalNotifierBackend { program });
+        }
+    }
+    Box::new(LegacyNotificationCenterBackend)
+}
+
+fn backend() -> &'static dyn NotificationBackend {
+    BACKEND
+        .get_or_init(|| {
+            let backend = default_backend();
+            info!("Delivering notifications via {}", backend.name());
+            backend
+        })
+        .as_ref()
+}
+
+/// Whether an executable runs from inside a `.app` bundle.
+fn is_in_app_bundle(exe: &Path) -> bool {
+    exe.parent()
+        .filter(|dir| dir.ends_with("Contents/MacOS"))
+        .and_then(|dir| dir.parent()?.parent())
+        .and_then(|bundle| bundle.extension())
+        .is_some_and(|ext| ext == "app")
+}
+
+/// Whether this process is signed with an Apple-issued certificate, which
+/// UNUserNotificationCenter requires. Ad-hoc signatures don't count.
+fn is_signed() -> bool {
+    let Ok(requirement) = "anchor apple generic".parse::<SecRequirement>() else {
+        return false;
+    };
+    SecCode::for_self(Flags::NONE)
+        .and_then(|code| code.check_validity(Flags::NONE, &requirement))
+        .is_ok()
+}
+
+/// Find an executable in `PATH`.
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Arguments for delivering a notification with `terminal-notifier`.
+fn terminal_notifier_args(notification: &Notification) -> Vec<String> {
+    let mut args = vec![
+        "-title".to_string(),
+        notification.title.clone(),
+        "-message".to_string(),
+        notification.body.clone(),
+        "-group".to_string(),
+        "de.malvik.azurepim".to_string(),
+    ];
+    match notification.sound {
+        NotificationSound::None => {}
+        NotificationSound::Default => args.extend(["-sound".to_string(), "default".to_string()]),
+        named => args.extend(["-sound".to_string(), named.label().to_string()]),
+    }
+    args
+}
+
+/// Hand a notification to the backend.
+fn post(level: InterruptionLevel, title: &str, body: &str, sound: NotificationSound) {
+    backend().deliver(Notification {
+        title: title.to_string(),
+        body: body.to_string(),
+        level,
+        sound,
+    });
+}
+
+/// The shared UNUserNotificationCenter, if UserNotifications.framework is loaded.
+fn user_notification_center() -> Option<Retained<NSObject>> {
+    let class = AnyClass::get("UNUserNotificationCenter")?;
+    Some(unsafe { msg_send_id![class, currentNotificationCenter] })
+}
+
+/// Ask for permission to show alerts and play sounds.
+fn request_authorization() {
+    let Some(center) = user_notification_center() else {
+        warn!("UNUserNotificationCenter unavailable");
+        return;
+    };
+    let handler = RcBlock::new(|granted: Bool, error: *mut NSError| {
+        if granted.as_bool() {
+            debug!("Notifications allowed");
+        } else {
+            // SAFETY: the error is either null or valid for the duration of the call
+            let reason = unsafe { error.as_ref() }
+                .map(|e| e.localizedDescription().to_string())
+                .unwrap_or_else(|| "turned off in System Settings".to_string());
+            warn!("Notifications not allowed: {}", reason);
+        }
+    });
+    unsafe {
+        let _: () = msg_send![
+            &*center,
+            requestAuthorizationWithOptions: AUTHORIZATION_OPTIONS,
+            completionHandler: &*handler
+        ];
+    }
+}
+
+/// Deliver a notification through UNUserNotificationCenter (must be called
+/// on the main thread).
+fn deliver_to_user_notification_center(notification: &Notification) {
+    let Some(center) = user_notification_center() else {
+        warn!(
+            "UNUserNotificationCenter unavailable, dropping: {}",
+            notification.title
+        );
+        return;
+    };
+    let (Some(content_class), Some(request_class), Some(sound_class)) = (
+        AnyClass::get("UNMutableNotificationContent"),
+        AnyClass::get("UNNotificationRequest"),
+        AnyClass::get("UNNotificationSound"),
+    ) else {
+        warn!(
+            "UserNotifications classes missing, dropping: {}",
+            notification.title
+        );
+        return;
+    };
+
+    let Notification {
+        title,
+        body,
+        level,
+        sound,
+    } = notification;
+    let title_text = title.clone();
+    let handler = RcBlock::new(move |error: *mut NSError| {
+        // SAFETY: the error is either null or valid for the duration of the call
+        match unsafe { error.as_ref() } {
+            Some(e) => warn!(
+                "Notification not delivered ({}): {}",
+                title_text,
+                e.localizedDescription()
+            ),
+            None => debug!("Notification delivered: {}", title_text),
+        }
+    });
+    unsafe {
+        let content: Retained<NSObject> = msg_send_id![content_class, new];
+        let _: () = msg_send![&*content, setTitle: &*NSString::from_str(title)];
+        let _: () = msg_send![&*content, setBody: &*NSString::from_str(body)];
+        let _: () =
+            msg_send![&*content, setThreadIdentifier: &*NSString::from_str("de.malvik.azurepim")];
+        // Interruption levels arrived in macOS 12; older versions treat all as active
+        if content.respondsToSelector(sel!(setInterruptionLevel:)) {
+            let _: () = msg_send![&*content, setInterruptionLevel: level.un_value()];
+        }
+
+        let sound: Option<Retained<NSObject>> = match sound {
+            NotificationSound::None => None,
+            NotificationSound::Default => Some(msg_send_id![sound_class, defaultSound]),
+            // System sounds are found by file name in /System/Library/Sounds
+            named => {
+                let file = NSString::from_str(&format!("{}.aiff", named.label()));
+                Some(msg_send_id![sound_class, soundNamed: &*file])
+            }
+        };
+        if let Some(sound) = &sound {
+            let _: () = msg_send![&*content, setSound: &**sound];
+        }
+
+        let identifier = NSString::from_str(&uuid::Uuid::new_v4().to_string());
+        let request: Retained<NSObject> = msg_send_id![
+            request_class,
+            requestWithIdentifier: &*identifier,
+            content: &*content,
+            trigger: None::<&NSObject>
+        ];
+        let _: () = msg_send![
+            &*center,
+            addNotificationRequest: &*request,
+            withCompletionHandler: &*handler
+        ];
+    }
+}
+
+/// Deliver a notification to Notification Center via NSUserNotification
+/// (must be called on the main thread).
+#[allow(deprecated)]
+fn deliver_to_notification_center(notification: &Notification) {
+    let Notification {
+        title, body, sound, ..
+    } = notification;
+    unsafe {
+        let notification = NSUserNotification::new();
+        notification.setTitle(Some(&NSString::from_str(title)));
+        notification.setInformativeText(Some(&NSString::from_str(body)));
+
+        match sound {
+            NotificationSound::None => {}
+            NotificationSound::Default => {
+                notification.setSoundName(Some(NSUserNotificationDefaultSoundName));
+            }
+            named => {
+                notification.setSoundName(Some(&NSString::from_str(named.label())));
+            }
+        }
+
+        let center = NSUserNotificationCenter::defaultUserNotificationCenter();
+        center.deliverNotification(&notification);
+    }
+
+    debug!("Notification delivered: {}", title);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sound_settings_per_event() {
+        let mut sounds = SoundSettings::default();
+        assert_eq!(
+            sounds.sound_for(NotificationEvent::ExpiryWarning),
+            NotificationSound::Default
+        );
+
+        sounds.set_sound_for(NotificationEvent::ExpiryWarning, NotificationSound::None);
+        assert_eq!(
+            sounds.sound_for(NotificationEvent::ExpiryWarning),
+            NotificationSound::None
+        );
+        assert_eq!(
+            sounds.sound_for(NotificationEvent::ActivationFailed),
+            NotificationSound::Default
+        );
+    }
+
+    #[test]
+    fn test_activation_outcome_uses_its_sound() {
+        let mut sounds = SoundSettings::default();
+        sounds.set_sound_for(
+            NotificationEvent::ActivationSucceeded,
+            NotificationSound::Glass,
+        );
+        sounds.set_sound_for(
+            NotificationEvent::ActivationFailed,
+            NotificationSound::Basso,
+        );
+
+        assert_eq!(
+            sounds.sound_for(NotificationEvent::for_activation(true)),
+            NotificationSound::Glass
+        );
+        assert_eq!(
+            sounds.sound_for(NotificationEvent::for_activation(false)),
+            NotificationSound::Basso
+        );
+    }
+
+    #[test]
+    fn test_interruption_levels() {
+        let sound = NotificationSound::Glass;
+        assert_eq!(
+            sound_for_level(InterruptionLevel::Passive, sound),
+            NotificationSound::None
+        );
+        assert_eq!(sound_for_level(InterruptionLevel::Active, sound), sound);
+        assert_eq!(
+            sound_for_level(InterruptionLevel::TimeSensitive, sound),
+            sound
+        );
+
+        // Nothing breaks through Focus unless the user opted in
+        assert_eq!(
+            effective_level(InterruptionLevel::TimeSensitive, false),
+            InterruptionLevel::Active
+        );
+        assert_eq!(
+            effective_level(InterruptionLevel::TimeSensitive, true),
+            InterruptionLevel::TimeSensitive
+        );
+        assert_eq!(
+            effective_level(InterruptionLevel::Passive, true),
+            InterruptionLevel::Passive
+        );
+    }
+
+    #[test]
+    fn test_is_in_app_bundle() {
+        assert!(is_in_app_bundle(Path::new(
+            "/Applications/Azure PIM.app/Contents/MacOS/azurepim"
+        )));
+        assert!(!is_in_app_bundle(Path::new(
+            "/Users/me/azurepim/target/debug/azurepim"
+        )));
+        assert!(!is_in_app_bundle(Path::new("/opt/Contents/MacOS/azurepim")));
+    }
+
+    #[test]
+    fn test_terminal_notifier_args() {
+        let mut notification = Notification {
+            title: "Role expiring".to_string(),
+            body: "Owner expires in 5 min".to_string(),
+            level: InterruptionLevel::TimeSensitive,
+            sound: NotificationSound::None,
+        };
+        assert_eq!(
+            terminal_notifier_args(&notification),
+            vec![
+                "-title",
+                "Role expiring",
+                "-message",
+                "Owner expires in 5 min",
+                "-group",
+                "de.malvik.azurepim"
+            ]
+        );
+
+        notification.sound = NotificationSound::Glass;
+        assert!(terminal_notifier_args(&notification)
+            .ends_with(&["-sound".to_string(), "Glass".to_string()]));
+    }
+
+    #[test]
+    fn test_sound_serialization() {
+        let json = serde_json::to_string(&NotificationSound::Sosumi).unwrap();
+        assert_eq!(json, "\"sosumi\"");
+    }
+}
@@ -0,0 +1,174 @@
+//! Tracing spans around user-visible operations.
+//!
+//! Sign-in, session restore, token refresh, the PIM role scan and role
+//! activation each run in an `operation` span carrying the tenant and, for
+//! the scan, the number of subscriptions scanned. Every operation ends with
+//! one summary info line giving its outcome and duration, so the log can be
+//! filtered and aggregated per operation. Warnings repeated within an
+//! operation are logged once, with a count of the repeats before the summary.
+
+use std::time::{Duration, Instant};
+
+use tracing::{field, info, info_span, warn, Span};
+
+use crate::log_throttle;
+use crate::menubar::state::get_app_state;
+
+/// Operations the user waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    SignIn,
+    Restore,
+    Refresh,
+    PimScan,
+    Activation,
+}
+
+impl OperationKind {
+    /// Name logged in the span's `op` field.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SignIn => "sign-in",
+            Self::Restore => "restore",
+            Self::Refresh => "refresh",
+            Self::PimScan => "pim-scan",
+            Self::Activation => "activation",
+        }
+    }
+}
+
+/// How an operation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+    /// Azure AD or ARM couldn't be reached.
+    Offline,
+    /// The user gave up, e.g. closed the sign-in page.
+    Cancelled,
+}
+
+impl Outcome {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Offline => "offline",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A running operation: its span and when it started.
+#[derive(Debug)]
+pub struct Operation {
+    kind: OperationKind,
+    span: Span,
+    started: Instant,
+}
+
+impl Operation {
+    /// Start an operation, in the tenant of the signed-in user if there is one.
+    pub fn start(kind: OperationKind) -> Self {
+        let span = info_span!(
+            "operation",
+            op = kind.name(),
+            tenant = field::Empty,
+            subscriptions = field::Empty,
+            duration_ms = field::Empty,
+            outcome = field::Empty,
+        );
+        let operation = Self {
+            kind,
+            span,
+            started: Instant::now(),
+        };
+        if let Some(user_info) = get_app_state().and_then(|s| s.get_user_info()) {
+            operation.record_tenant(&user_info.tenant_id);
+        }
+        operation
+    }
+
+    /// The span, to instrument the operation's futures with.
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+
+    /// Record the tenant once it is known, e.g. after signing in.
+    pub fn record_tenant(&self, tenant_id: &str) {
+        self.span.record("tenant", tenant_id);
+    }
+
+    /// End the operation with a summary line. Returns how long it took.
+    pub fn finish(self, outcome: Outcome) -> Duration {
+        let elapsed = self.started.elapsed();
+        let duration_ms = elapsed.as_millis() as u64;
+        self.span.record("duration_ms", duration_ms);
+        self.span.record("outcome", outcome.name());
+        self.span.in_scope(|| {
+            for repeat in log_throttle::take_repeats(&self.span) {
+                warn!(
+                    "Suppressed {} repeats of: {}",
+                    repeat.suppressed, repeat.message
+                );
+            }
+            info!(
+                "{} {} in {}ms",
+                self.kind.name(),
+                outcome.name(),
+                duration_ms
+            )
+        });
+        elapsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Log output captured in memory.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_operation_summary_line() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let operation = Operation::start(OperationKind::PimScan);
+            operation.record_tenant("tenant-id");
+            operation.span().in_scope(|| {
+                // As recorded by the PIM client's scan
+                Span::current().record("subscriptions", 12);
+                info!("Scanning");
+            });
+            operation.finish(Outcome::Offline);
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let summary = output.lines().last().unwrap();
+        assert!(summary.contains("op=\"pim-scan\""), "{}", summary);
+        assert!(summary.contains("tenant=\"tenant-id\""), "{}", summary);
+        assert!(summary.contains("subscriptions=12"), "{}", summary);
+        assert!(summary.contains("pim-scan offline in "), "{}", summary);
+    }
+}
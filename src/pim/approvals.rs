@@ -0,0 +1,441 @@
+//! Pending activation requests and approvals.
+//!
+//! Roles that require approval leave a `PendingApproval` schedule request
+//! until an approver acts on it. The approvals worker polls both sides:
+//! the user's own pending requests and the requests waiting for them to
+//! approve. Approvers review a request on the in-progress stage of its
+//! `roleAssignmentApprovals` resource.
+//!
+//! The same list of the user's own requests holds their scheduled
+//! activations: accepted requests whose start is still ahead.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use super::models::role_key;
+
+/// Schedule request status while waiting for an approver.
+const PENDING_APPROVAL: &str = "PendingApproval";
+
+/// Schedule request statuses of an accepted activation that may not have started.
+const SCHEDULED_STATUSES: [&str; 5] = [
+    "Granted",
+    "PendingScheduleCreation",
+    "ScheduleCreated",
+    "PendingProvisioning",
+    "Provisioned",
+];
+
+/// Schedule request type of the user's own activation.
+const SELF_ACTIVATE: &str = "SelfActivate";
+
+/// Approval stage status while waiting for a review.
+const STAGE_IN_PROGRESS: &str = "InProgress";
+
+/// Counts of requests waiting on an approval.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApprovalCounts {
+    /// The user's own activation requests waiting for approval.
+    pub my_pending: usize,
+    /// Other users' requests waiting for the user to approve.
+    pub awaiting_my_approval: usize,
+}
+
+impl ApprovalCounts {
+    /// Whether anything is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.my_pending == 0 && self.awaiting_my_approval == 0
+    }
+}
+
+/// One of the user's activation requests waiting for approval.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRequest {
+    /// Resource ID of the schedule request.
+    pub id: String,
+    /// Key of the requested role, as in `EligibleRole::favorites_key`.
+    pub role_key: String,
+}
+
+impl PendingRequest {
+    /// Whether this is the request with resource ID `id`; ARM IDs are case-insensitive.
+    pub fn is(&self, id: &str) -> bool {
+        self.id.eq_ignore_ascii_case(id)
+    }
+}
+
+/// One of the user's activations scheduled to start later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledActivation {
+    /// Resource ID of the schedule request, to cancel it.
+    pub id: String,
+    /// Key of the requested role, as in `EligibleRole::favorites_key`.
+    pub role_key: String,
+    pub start_time: DateTime<Utc>,
+}
+
+impl ScheduledActivation {
+    /// Whether this is the request with resource ID `id`; ARM IDs are case-insensitive.
+    pub fn is(&self, id: &str) -> bool {
+        self.id.eq_ignore_ascii_case(id)
+    }
+}
+
+/// The user's own requests from a `roleAssignmentScheduleRequests` list
+/// filtered with `asRequestor()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MyRequests {
+    pub pending: Vec<PendingRequest>,
+    pub scheduled: Vec<ScheduledActivation>,
+}
+
+/// Another user's activation request waiting for the user to approve or deny it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalRequest {
+    /// Resource ID of the schedule request.
+    pub id: String,
+    /// Resource ID of the approval to review.
+    pub approval_id: String,
+    /// Display name of the user or group the role is requested for, or its ID.
+    pub principal_name: String,
+    pub role_name: String,
+    /// Display name of the scope the role is requested at.
+    pub scope_name: String,
+    pub justification: String,
+    pub created_on: Option<DateTime<Utc>>,
+}
+
+impl ApprovalRequest {
+    /// Whether this is the request with resource ID `id`; ARM IDs are case-insensitive.
+    pub fn is(&self, id: &str) -> bool {
+        self.id.eq_ignore_ascii_case(id)
+    }
+
+    /// Menu text, e.g. "Ola Nordmann: Owner - contoso-prod".
+    pub fn menu_title(&self) -> String {
+        format!(
+            "{}: {} - {}",
+            self.principal_name, self.role_name, self.scope_name
+        )
+    }
+}
+
+/// An approver's decision on a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReviewResult {
+    Approve,
+    Deny,
+}
+
+impl ReviewResult {
+    /// The `reviewResult` value ARM expects.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Approve => "Approve",
+            Self::Deny => "Deny",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequestListResponse {
+    value: Vec<ScheduleRequestItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequestItem {
+    id: String,
+    properties: ScheduleRequestProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequestProperties {
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    scope: String,
+    #[serde(rename = "roleDefinitionId", default)]
+    role_definition_id: String,
+    #[serde(rename = "principalId", default)]
+    principal_id: String,
+    #[serde(rename = "approvalId")]
+    approval_id: Option<String>,
+    justification: Option<String>,
+    #[serde(rename = "createdOn")]
+    created_on: Option<DateTime<Utc>>,
+    #[serde(rename = "requestType", default)]
+    request_type: String,
+    #[serde(rename = "scheduleInfo")]
+    schedule_info: Option<ScheduleInfo>,
+    #[serde(rename = "expandedProperties")]
+    expanded_properties: Option<ExpandedProperties>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleInfo {
+    #[serde(rename = "startDateTime")]
+    start_date_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExpandedProperties {
+    principal: ExpandedObject,
+    #[serde(rename = "roleDefinition")]
+    role_definition: ExpandedObject,
+    scope: ExpandedObject,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExpandedObject {
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+}
+
+impl ExpandedObject {
+    /// The display name, or `fallback` if ARM left it out.
+    fn name_or(self, fallback: &str) -> String {
+        self.display_name
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| fallback.to_string())
+    }
+}
+
+/// Get the pending requests from a `roleAssignmentScheduleRequests` list response.
+pub fn parse_pending_requests(body: &str) -> Option<Vec<PendingRequest>> {
+    let response: ScheduleRequestListResponse = serde_json::from_str(body).ok()?;
+    Some(
+        response
+            .value
+            .into_iter()
+            .filter(|item| item.properties.status == PENDING_APPROVAL)
+            .map(|item| PendingRequest {
+                role_key: role_key(&item.properties.scope, &item.properties.role_definition_id),
+                id: item.id,
+            })
+            .collect(),
+    )
+}
+
+/// Get the activations starting after `now` from a `roleAssignmentScheduleRequests`
+/// list response filtered with `asRequestor()`, soonest first.
+pub fn parse_scheduled_activations(
+    body: &str,
+    now: DateTime<Utc>,
+) -> Option<Vec<ScheduledActivation>> {
+    let response: ScheduleRequestListResponse = serde_json::from_str(body).ok()?;
+    let mut scheduled: Vec<ScheduledActivation> = response
+        .value
+        .into_iter()
+        .filter(|item| {
+            item.properties.request_type == SELF_ACTIVATE
+                && SCHEDULED_STATUSES.contains(&item.properties.status.as_str())
+        })
+        .filter_map(|item| {
+            let start_time = item.properties.schedule_info?.start_date_time?;
+            (start_time > now).then(|| ScheduledActivation {
+                role_key: role_key(&item.properties.scope, &item.properties.role_definition_id),
+                id: item.id,
+                start_time,
+            })
+        })
+        .collect();
+    scheduled.sort_by_key(|s| s.start_time);
+    Some(scheduled)
+}
+
+/// Get the requests to review from a `roleAssignmentScheduleRequests`
+/// list response filtered with `asApprover()`.
+pub fn parse_approval_requests(body: &str) -> Option<Vec<ApprovalRequest>> {
+    let response: ScheduleRequestListResponse = serde_json::from_str(body).ok()?;
+    Some(
+        response
+            .value
+            .into_iter()
+            .filter(|item| item.properties.status == PENDING_APPROVAL)
+            .filter_map(|item| {
+                let properties = item.properties;
+                let approval_id = approval_resource_id(&properties.approval_id?);
+                let expanded = properties.expanded_properties.unwrap_or_default();
+                Some(ApprovalRequest {
+                    id: item.id,
+                    approval_id,
+                    principal_name: expanded.principal.name_or(&properties.principal_id),
+                    role_name: expanded.role_definition.name_or("Unknown Role"),
+                    scope_name: expanded.scope.name_or(&properties.scope),
+                    justification: properties.justification.unwrap_or_default(),
+                    created_on: properties.created_on,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// The resource ID of an approval; some responses carry only its GUID.
+fn approval_resource_id(approval_id: &str) -> String {
+    if approval_id.starts_with('/') {
+        approval_id.to_string()
+    } else {
+        format!(
+            "/providers/Microsoft.Authorization/roleAssignmentApprovals/{}",
+            approval_id
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalResponse {
+    properties: ApprovalProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalProperties {
+    #[serde(default)]
+    stages: Vec<ApprovalStage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalStage {
+    id: String,
+    properties: ApprovalStageProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalStageProperties {
+    #[serde(default)]
+    status: String,
+    #[serde(rename = "assignedToMe")]
+    assigned_to_me: Option<bool>,
+}
+
+/// Get the resource ID of the stage waiting for the user's review from a
+/// `roleAssignmentApprovals` response, if any.
+pub fn parse_stage_awaiting_me(body: &str) -> Option<String> {
+    let response: ApprovalResponse = serde_json::from_str(body).ok()?;
+    response
+        .properties
+        .stages
+        .into_iter()
+        .find(|stage| {
+            stage.properties.status == STAGE_IN_PROGRESS
+                && stage.properties.assigned_to_me != Some(false)
+        })
+        .map(|stage| stage.id)
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalListResponse {
+    value: Vec<serde_json::Value>,
+}
+
+/// Count approvals in a `roleAssignmentApprovals` list response.
+pub fn parse_approval_count(body: &str) -> Option<usize> {
+    let response: ApprovalListResponse = serde_json::from_str(body).ok()?;
+    Some(response.value.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_parse_pending_requests() {
+        let body = r#"{"value":[
+            {"id":"/req/1","properties":{"status":"PendingApproval","scope":"/subscriptions/SUB-1","roleDefinitionId":"/subscriptions/sub-1/providers/Microsoft.Authorization/roleDefinitions/ACDD72A7"}},
+            {"id":"/req/2","properties":{"status":"Provisioned"}}
+        ]}"#;
+        let pending = parse_pending_requests(body).unwrap();
+        assert_eq!(
+            pending,
+            vec![PendingRequest {
+                id: "/req/1".to_string(),
+                role_key: "/subscriptions/sub-1|acdd72a7".to_string(),
+            }]
+        );
+        assert!(pending[0].is("/REQ/1"));
+        assert_eq!(parse_pending_requests("not json"), None);
+    }
+
+    #[test]
+    fn test_parse_scheduled_activations() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let body = r#"{"value":[
+            {"id":"/req/1","properties":{"status":"ScheduleCreated","requestType":"SelfActivate","scope":"/subscriptions/sub-1","roleDefinitionId":"/providers/Microsoft.Authorization/roleDefinitions/r1","scheduleInfo":{"startDateTime":"2024-03-02T06:00:00Z"}}},
+            {"id":"/req/2","properties":{"status":"Granted","requestType":"SelfActivate","scope":"/subscriptions/sub-2","roleDefinitionId":"/providers/Microsoft.Authorization/roleDefinitions/r2","scheduleInfo":{"startDateTime":"2024-03-01T22:00:00Z"}}},
+            {"id":"/req/3","properties":{"status":"Provisioned","requestType":"SelfActivate","scheduleInfo":{"startDateTime":"2024-03-01T11:00:00Z"}}},
+            {"id":"/req/4","properties":{"status":"Canceled","requestType":"SelfActivate","scheduleInfo":{"startDateTime":"2024-03-01T22:00:00Z"}}},
+            {"id":"/req/5","properties":{"status":"Provisioned","requestType":"SelfDeactivate","scheduleInfo":{"startDateTime":"2024-03-01T22:00:00Z"}}},
+            {"id":"/req/6","properties":{"status":"PendingApproval","requestType":"SelfActivate","scheduleInfo":{"startDateTime":"2024-03-01T22:00:00Z"}}}
+        ]}"#;
+        let scheduled = parse_scheduled_activations(body, now).unwrap();
+        assert_eq!(
+            scheduled,
+            vec![
+                ScheduledActivation {
+                    id: "/req/2".to_string(),
+                    role_key: "/subscriptions/sub-2|r2".to_string(),
+                    start_time: now + Duration::hours(10),
+                },
+                ScheduledActivation {
+                    id: "/req/1".to_string(),
+                    role_key: "/subscriptions/sub-1|r1".to_string(),
+                    start_time: now + Duration::hours(18),
+                },
+            ]
+        );
+        assert!(scheduled[0].is("/REQ/2"));
+        assert_eq!(parse_scheduled_activations("not json", now), None);
+    }
+
+    #[test]
+    fn test_parse_approval_requests() {
+        let body = r#"{"value":[
+            {"id":"/req/1","properties":{"status":"PendingApproval","scope":"/subscriptions/sub-1",
+             "principalId":"p1","approvalId":"/providers/Microsoft.Authorization/roleAssignmentApprovals/a1",
+             "justification":"INC-4711","createdOn":"2024-03-01T11:50:00Z",
+             "expandedProperties":{"principal":{"displayName":"Ola Nordmann"},
+              "roleDefinition":{"displayName":"Owner"},"scope":{"displayName":"vipps-prod-001"}}}},
+            {"id":"/req/2","properties":{"status":"PendingApproval","scope":"/subscriptions/sub-2",
+             "principalId":"p2","approvalId":"a2"}},
+            {"id":"/req/3","properties":{"status":"Provisioned","approvalId":"a3"}}
+        ]}"#;
+        let requests = parse_approval_requests(body).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].menu_title(),
+            "Ola Nordmann: Owner - vipps-prod-001"
+        );
+        assert_eq!(requests[0].justification, "INC-4711");
+        // Without expanded properties the IDs stand in for names
+        assert_eq!(
+            requests[1].menu_title(),
+            "p2: Unknown Role - /subscriptions/sub-2"
+        );
+        assert_eq!(
+            requests[1].approval_id,
+            "/providers/Microsoft.Authorization/roleAssignmentApprovals/a2"
+        );
+    }
+
+    #[test]
+    fn test_parse_stage_awaiting_me() {
+        let body = r#"{"properties":{"stages":[
+            {"id":"/approvals/a1/stages/s1","properties":{"status":"Completed","assignedToMe":true}},
+            {"id":"/approvals/a1/stages/s2","properties":{"status":"InProgress","assignedToMe":false}},
+            {"id":"/approvals/a1/stages/s3","properties":{"status":"InProgress","assignedToMe":true}}
+        ]}}"#;
+        assert_eq!(
+            parse_stage_awaiting_me(body),
+            Some("/approvals/a1/stages/s3".to_string())
+        );
+        assert_eq!(parse_stage_awaiting_me(r#"{"properties":{}}"#), None);
+    }
+
+    #[test]
+    fn test_parse_approval_count() {
+        let body = r#"{"value":[{"id":"a"},{"id":"b"}]}"#;
+        assert_eq!(parse_approval_count(body), Some(2));
+    }
+}
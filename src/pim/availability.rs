@@ -0,0 +1,177 @@
+//! Subscriptions without PIM access.
+//!
+//! Some subscriptions answer every eligibility query with 403, e.g. ones the
+//! user can list but has no PIM rights in. Once a subscription has been
+//! refused `DENIALS_BEFORE_SKIP` scans in a row, scans skip it for the period
+//! chosen in Settings and then try it again. The memory is saved across
+//! restarts and can be reset from the menu.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::models::Subscription;
+
+/// Scans in a row a subscription must refuse before it is skipped.
+const DENIALS_BEFORE_SKIP: u32 = 2;
+
+/// Choices for how long to skip subscriptions without PIM access, in hours; 0 never skips.
+pub const SKIP_PERIOD_CHOICES: [u32; 4] = [0, 24, 7 * 24, 30 * 24];
+
+/// Menu text for a skip period, e.g. "1 week".
+pub fn skip_period_label(hours: u32) -> String {
+    match hours {
+        0 => "Never".to_string(),
+        h if h % (7 * 24) == 0 => plural(h / (7 * 24), "week"),
+        h if h % 24 == 0 => plural(h / 24, "day"),
+        h => plural(h, "hour"),
+    }
+}
+
+fn plural(n: u32, unit: &str) -> String {
+    if n == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", n, unit)
+    }
+}
+
+/// Refused scans of one subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Denial {
+    /// Subscription name when last refused, for the refresh report and menu.
+    name: String,
+    /// Scans in a row that were refused.
+    count: u32,
+    last_denied: DateTime<Utc>,
+}
+
+/// Subscriptions that refused eligibility scans, keyed by subscription ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PimAvailability {
+    denied: HashMap<String, Denial>,
+}
+
+impl PimAvailability {
+    /// Record a scan: `denied` refused every query, the rest of `scanned`
+    /// answered. Returns true if anything changed.
+    pub fn record_scan(
+        &mut self,
+        scanned: &[String],
+        denied: &[Subscription],
+        now: DateTime<Utc>,
+    ) -> bool {
+        let count = self.denied.len();
+        self.denied.retain(|id, _| {
+            !scanned.contains(id) || denied.iter().any(|s| s.subscription_id == *id)
+        });
+        let mut changed = self.denied.len() != count;
+        for subscription in denied {
+            let denial = self
+                .denied
+                .entry(subscription.subscription_id.clone())
+                .or_insert_with(|| Denial {
+                    name: subscription.display_name.clone(),
+                    count: 0,
+                    last_denied: now,
+                });
+            denial.name = subscription.display_name.clone();
+            denial.count += 1;
+            denial.last_denied = now;
+            changed = true;
+        }
+        changed
+    }
+
+    /// IDs of the subscriptions to leave out of a scan at `now`, for `period`
+    /// after they were last refused.
+    pub fn skipped_ids(&self, now: DateTime<Utc>, period: Duration) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .denied
+            .iter()
+            .filter(|(_, denial)| is_skipped(denial, now, period))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Names of the subscriptions skipped at `now`, sorted.
+    pub fn skipped_names(&self, now: DateTime<Utc>, period: Duration) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .denied
+            .values()
+            .filter(|denial| is_skipped(denial, now, period))
+            .map(|denial| denial.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Forget all refusals, so the next scan tries every subscription.
+    pub fn clear(&mut self) -> bool {
+        let changed = !self.denied.is_empty();
+        self.denied.clear();
+        changed
+    }
+}
+
+fn is_skipped(denial: &Denial, now: DateTime<Utc>, period: Duration) -> bool {
+    denial.count >= DENIALS_BEFORE_SKIP && now - denial.last_denied < period
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(id: &str, name: &str) -> Subscription {
+        Subscription {
+            subscription_id: id.to_string(),
+            display_name: name.to_string(),
+            state: "Enabled".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_skip_after_repeated_denials() {
+        let now = Utc::now();
+        let day = Duration::hours(24);
+        let scanned = vec!["a".to_string(), "b".to_string()];
+        let denied = vec![subscription("a", "sandbox")];
+        let mut availability = PimAvailability::default();
+
+        // One refusal could be a hiccup
+        assert!(availability.record_scan(&scanned, &denied, now));
+        assert!(availability.skipped_ids(now, day).is_empty());
+
+        availability.record_scan(&scanned, &denied, now);
+        assert_eq!(availability.skipped_ids(now, day), vec!["a"]);
+        assert_eq!(availability.skipped_names(now, day), vec!["sandbox"]);
+
+        // Tried again once the period is over, or never skipped with no period
+        assert!(availability
+            .skipped_ids(now + Duration::hours(25), day)
+            .is_empty());
+        assert!(availability.skipped_ids(now, Duration::zero()).is_empty());
+
+        // Answering resets the count; not being scanned keeps it
+        assert!(!availability.record_scan(&["b".to_string()], &[], now));
+        assert!(availability.record_scan(&scanned, &[], now));
+        assert_eq!(availability, PimAvailability::default());
+
+        availability.record_scan(&scanned, &denied, now);
+        assert!(availability.clear());
+        assert!(!availability.clear());
+    }
+
+    #[test]
+    fn test_skip_period_labels() {
+        let labels: Vec<String> = SKIP_PERIOD_CHOICES
+            .iter()
+            .map(|&hours| skip_period_label(hours))
+            .collect();
+        assert_eq!(labels, vec!["Never", "1 day", "1 week", "30 days"]);
+        assert_eq!(skip_period_label(2), "2 hours");
+    }
+}
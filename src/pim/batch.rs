@@ -0,0 +1,105 @@
+//! ARM batch requests.
+//!
+//! The `/batch` endpoint wraps up to 20 requests in one HTTP call. Each
+//! wrapped request gets its own status code and body, so one failing
+//! subscription doesn't fail the batch.
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum requests ARM accepts in one batch.
+pub const MAX_BATCH_SIZE: usize = 20;
+
+/// Body of a batch request.
+#[derive(Debug, Serialize)]
+pub struct BatchRequest {
+    requests: Vec<BatchItem>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchItem {
+    /// Echoed back in the response, used to match responses to requests.
+    name: String,
+    #[serde(rename = "httpMethod")]
+    http_method: &'static str,
+    url: String,
+}
+
+impl BatchRequest {
+    /// Batch GET requests for `urls`, named by their index.
+    pub fn get(urls: &[String]) -> Self {
+        Self {
+            requests: urls
+                .iter()
+                .enumerate()
+                .map(|(idx, url)| BatchItem {
+                    name: idx.to_string(),
+                    http_method: "GET",
+                    url: url.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Response to a single request in a batch.
+#[derive(Debug, Deserialize)]
+pub struct BatchItemResponse {
+    /// Index of the request in the batch.
+    #[serde(deserialize_with = "index_from_name")]
+    pub name: usize,
+    #[serde(rename = "httpStatusCode")]
+    pub http_status_code: u16,
+    #[serde(default)]
+    pub content: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    #[serde(default)]
+    responses: Vec<BatchItemResponse>,
+}
+
+fn index_from_name<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(serde::de::Error::custom)
+}
+
+/// Parse a batch response body, ordered by request index.
+pub fn parse_batch_response(body: &str) -> Option<Vec<BatchItemResponse>> {
+    let response: BatchResponse = serde_json::from_str(body).ok()?;
+    let mut responses = response.responses;
+    responses.sort_by_key(|r| r.name);
+    Some(responses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_request_body() {
+        let urls = vec!["/a?x=1".to_string(), "/b".to_string()];
+        let body = serde_json::to_value(BatchRequest::get(&urls)).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"requests": [
+                {"name": "0", "httpMethod": "GET", "url": "/a?x=1"},
+                {"name": "1", "httpMethod": "GET", "url": "/b"}
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_parse_batch_response() {
+        let body = r#"{"responses":[
+            {"name":"1","httpStatusCode":403,"content":{"error":{}}},
+            {"name":"0","httpStatusCode":200,"content":{"value":[]}}
+        ]}"#;
+        let responses = parse_batch_response(body).unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].name, 0);
+        assert_eq!(responses[0].http_status_code, 200);
+        assert_eq!(responses[1].http_status_code, 403);
+        assert!(parse_batch_response("nope").is_none());
+    }
+}
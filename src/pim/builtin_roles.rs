@@ -0,0 +1,212 @@
+//! Names of well-known Azure built-in roles.
+//!
+//! Built-in role definition GUIDs are the same in every tenant, so common
+//! roles resolve without an API call. Custom roles still need a lookup.
+
+/// Built-in role definition GUIDs and their names.
+const BUILTIN_ROLES: &[(&str, &str)] = &[
+    ("8e3af657-a8ff-443c-a75c-2fe8c4bcb635", "Owner"),
+    ("b24988ac-6180-42a0-ab88-20f7382dd24c", "Contributor"),
+    ("acdd72a7-3385-48ef-bd42-f606fba81ae7", "Reader"),
+    (
+        "18d7d88d-d35e-4fb5-a5c3-7773c20a72d9",
+        "User Access Administrator",
+    ),
+    (
+        "f58310d9-a9f6-439a-9e8d-f62e7b41a168",
+        "Role Based Access Control Administrator",
+    ),
+    ("fa23ad8b-c56e-40d8-ac0c-ce449e1d2c64", "Billing Reader"),
+    (
+        "72fafb9e-0641-4937-9268-a91bfd8191a3",
+        "Cost Management Reader",
+    ),
+    (
+        "434105ed-43f6-45c7-a02f-909b2ba83430",
+        "Cost Management Contributor",
+    ),
+    ("43d0d8ad-25c7-4714-9337-8ba259a9fe05", "Monitoring Reader"),
+    (
+        "749f88d5-cbae-40b8-bcfc-e573ddc772fa",
+        "Monitoring Contributor",
+    ),
+    (
+        "73c42c96-874c-492b-b04d-ab87d138a893",
+        "Log Analytics Reader",
+    ),
+    (
+        "92aaf0da-9dab-42b6-94a3-d43ce8d16293",
+        "Log Analytics Contributor",
+    ),
+    ("39bc4728-0917-49c7-9d2c-d95423bc2eb4", "Security Reader"),
+    ("fb1c8493-542b-48eb-b624-b4c8fea62acd", "Security Admin"),
+    (
+        "36243c78-bf99-498c-9df9-86d9f8d28608",
+        "Resource Policy Contributor",
+    ),
+    (
+        "cfd33db0-3dd1-45e3-aa9d-cdbdf3b6f24e",
+        "Support Request Contributor",
+    ),
+    ("4a9ae827-6dc8-4573-8ac7-8239d42aa03f", "Tag Contributor"),
+    (
+        "9980e02c-c2be-4d73-94e8-173b1dc7cf3c",
+        "Virtual Machine Contributor",
+    ),
+    (
+        "4d97b98b-1d4f-4787-a291-c67834d212e7",
+        "Network Contributor",
+    ),
+    (
+        "17d1049b-9a84-46fb-8f53-869881c3d3ab",
+        "Storage Account Contributor",
+    ),
+    (
+        "b7e6dc6d-f1e8-4753-8033-0f276bb0955b",
+        "Storage Blob Data Owner",
+    ),
+    (
+        "ba92f5b4-2d11-453d-a403-e96b0029c9fe",
+        "Storage Blob Data Contributor",
+    ),
+    (
+        "2a2b9908-6ea1-4ae2-8e65-a410df84e7d1",
+        "Storage Blob Data Reader",
+    ),
+    (
+        "00482a5a-887f-4fb3-b363-3b7fe8e74483",
+        "Key Vault Administrator",
+    ),
+    ("21090545-7ca7-4776-b22c-e363652d74d2", "Key Vault Reader"),
+    (
+        "b86a8fe4-44ce-4948-aee5-eccb2c155cd7",
+        "Key Vault Secrets Officer",
+    ),
+    (
+        "4633458b-17de-408a-b874-0445c86b69e6",
+        "Key Vault Secrets User",
+    ),
+    (
+        "0ab0b1a8-8aac-4efd-b8c2-3ee1fb270be8",
+        "Azure Kubernetes Service Cluster Admin Role",
+    ),
+    (
+        "4abbcc35-e782-43d8-92c5-2d3f1bd2253f",
+        "Azure Kubernetes Service Cluster User Role",
+    ),
+    ("7f951dda-4ed3-4680-a7ca-43fe172d538d", "AcrPull"),
+    ("8311e382-0749-4cb8-b61a-304f252e45ec", "AcrPush"),
+    (
+        "de139f84-1756-47ae-9be6-808fbbe84772",
+        "Website Contributor",
+    ),
+    ("9b7fa17d-e63e-47b0-bb0a-15c516ac86ec", "SQL DB Contributor"),
+    (
+        "6d8ee4ec-f05a-4a1d-8b00-a9b17e38b437",
+        "SQL Server Contributor",
+    ),
+    (
+        "f1a07417-d97a-45cb-824c-7a7467783830",
+        "Managed Identity Operator",
+    ),
+    (
+        "e40ec5ca-96e0-45a2-b4ff-59039f2c2b59",
+        "Managed Identity Contributor",
+    ),
+];
+
+/// Key Vault roles that manage vaults without reaching their contents.
+const KEY_VAULT_MANAGEMENT_ROLES: [&str; 2] = [
+    "Key Vault Contributor",
+    "Key Vault Data Access Administrator",
+];
+
+/// Whether a role, by name, grants access to Key Vault secrets, keys or certificates.
+pub fn is_key_vault_data_role(role_name: &str) -> bool {
+    role_name.starts_with("Key Vault ") && !KEY_VAULT_MANAGEMENT_ROLES.contains(&role_name)
+}
+
+/// Built-in roles and the built-in roles whose permissions they include.
+///
+/// Only control plane roles: Owner doesn't include data actions such as
+/// reading Key Vault secrets.
+const INCLUDED_ROLES: &[(&str, &[&str])] = &[
+    (
+        "Owner",
+        &[
+            "Contributor",
+            "Reader",
+            "User Access Administrator",
+            "Role Based Access Control Administrator",
+        ],
+    ),
+    ("Contributor", &["Reader"]),
+    (
+        "User Access Administrator",
+        &["Role Based Access Control Administrator"],
+    ),
+];
+
+/// Whether the role named `broader` grants everything the role named `narrower` does.
+pub fn role_includes(broader: &str, narrower: &str) -> bool {
+    broader == narrower
+        || INCLUDED_ROLES
+            .iter()
+            .any(|(role, included)| *role == broader && included.contains(&narrower))
+}
+
+/// Name of a built-in role, given its definition ID (full path or bare GUID).
+pub fn builtin_role_name(role_definition_id: &str) -> Option<&'static str> {
+    let guid = role_definition_id
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(role_definition_id);
+    BUILTIN_ROLES
+        .iter()
+        .find(|(id, _)| id.eq_ignore_ascii_case(guid))
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_role_name() {
+        assert_eq!(
+            builtin_role_name(
+                "/subscriptions/sub-id/providers/Microsoft.Authorization/roleDefinitions/b24988ac-6180-42a0-ab88-20f7382dd24c"
+            ),
+            Some("Contributor")
+        );
+        assert_eq!(
+            builtin_role_name("8E3AF657-A8FF-443C-A75C-2FE8C4BCB635"),
+            Some("Owner")
+        );
+        assert_eq!(
+            builtin_role_name("/providers/Microsoft.Authorization/roleDefinitions/custom-role"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_role_includes() {
+        assert!(role_includes("Owner", "Reader"));
+        assert!(role_includes("Contributor", "Reader"));
+        assert!(role_includes(
+            "Key Vault Secrets User",
+            "Key Vault Secrets User"
+        ));
+        assert!(!role_includes("Reader", "Contributor"));
+        assert!(!role_includes("Owner", "Key Vault Secrets User"));
+    }
+
+    #[test]
+    fn test_key_vault_data_roles() {
+        assert!(is_key_vault_data_role("Key Vault Secrets User"));
+        assert!(is_key_vault_data_role("Key Vault Administrator"));
+        assert!(!is_key_vault_data_role("Key Vault Contributor"));
+        assert!(!is_key_vault_data_role("Contributor"));
+    }
+}
@@ -1,8 +1,11 @@
 //! PIM role cache with time-to-live (TTL) support.
 
+use std::sync::Arc;
+
 use chrono::{DateTime, Duration, Utc};
 
 use super::models::EligibleRole;
+use crate::clock::{Clock, SystemClock};
 
 /// Cache TTL in hours.
 const CACHE_TTL_HOURS: i64 = 1;
@@ -15,10 +18,18 @@ struct CachedData<T> {
 }
 
 /// PIM role cache with 1-hour TTL.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct PimCache {
     /// Cached eligible roles.
     eligible_roles: Option<CachedData<Vec<EligibleRole>>>,
+    /// Time source for TTL checks.
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for PimCache {
+    fn default() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
 }
 
 impl PimCache {
@@ -27,6 +38,14 @@ impl PimCache {
         Self::default()
     }
 
+    /// Create a new empty cache using the given time source.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            eligible_roles: None,
+            clock,
+        }
+    }
+
     /// Get cached eligible roles if still valid.
     pub fn get_eligible_roles(&self) -> Option<&Vec<EligibleRole>> {
         self.eligible_roles.as_ref().and_then(|cached| {
@@ -42,7 +61,7 @@ impl PimCache {
     pub fn set_eligible_roles(&mut self, roles: Vec<EligibleRole>) {
         self.eligible_roles = Some(CachedData {
             data: roles,
-            cached_at: Utc::now(),
+            cached_at: self.clock.now(),
         });
     }
 
@@ -58,7 +77,7 @@ impl PimCache {
 
     /// Check if the cache is still valid.
     pub fn is_valid(&self, cached_at: &DateTime<Utc>) -> bool {
-        Utc::now() - *cached_at < Duration::hours(CACHE_TTL_HOURS)
+        self.clock.now() - *cached_at < Duration::hours(CACHE_TTL_HOURS)
     }
 
     /// Check if cache needs refresh.
@@ -73,6 +92,7 @@ impl PimCache {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
 
     fn make_test_role() -> EligibleRole {
         EligibleRole {
@@ -81,8 +101,11 @@ mod tests {
             role_name: "Contributor".to_string(),
             subscription_id: "sub-id".to_string(),
             subscription_name: "Test Sub".to_string(),
-            scope: "/subscriptions/sub-id".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
             principal_id: "principal".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
         }
     }
 
@@ -106,6 +129,21 @@ mod tests {
         assert!(!cache.needs_refresh());
     }
 
+    #[test]
+    fn test_cache_expires_after_ttl() {
+        let clock = Arc::new(MockClock::new(Utc::now()));
+        let mut cache = PimCache::with_clock(clock.clone());
+        cache.set_eligible_roles(vec![make_test_role()]);
+
+        clock.advance(Duration::hours(CACHE_TTL_HOURS) - Duration::seconds(1));
+        assert!(cache.get_eligible_roles().is_some());
+        assert!(!cache.needs_refresh());
+
+        clock.advance(Duration::seconds(1));
+        assert!(cache.get_eligible_roles().is_none());
+        assert!(cache.needs_refresh());
+    }
+
     #[test]
     fn test_cache_invalidate() {
         let mut cache = PimCache::new();
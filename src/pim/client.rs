@@ -2,28 +2,55 @@
 //!
 //! Uses the Azure Resource Management API to interact with PIM.
 
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration as StdDuration;
 
 use chrono::Utc;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info, warn};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn, Instrument, Span};
 use uuid::Uuid;
 
-use super::models::{ActivationRequest, ActiveAssignment, EligibleRole, Subscription};
+use super::approvals::{self, ApprovalRequest, MyRequests, ReviewResult};
+use super::batch;
+use super::builtin_roles::builtin_role_name;
+use super::compat::{self, ApiVersionProblem};
+use super::models::{
+    merge_duplicate_grants, ActivationRequest, ActiveAssignment, EligibleRole, Subscription,
+    TenantAssignment,
+};
+use super::policy::{self, ActivationCheck, PolicyRule, RolePolicy};
+use super::scope::Scope;
+use crate::auth::audience::{check_audience, Audience};
+use crate::auth::challenge::{self, StepUp};
+use crate::config::{ApiVersions, RuntimeConfig};
 use crate::error::PimError;
 
 /// Azure Management API base URL.
 const MANAGEMENT_BASE_URL: &str = "https://management.azure.com";
 
-/// API version for PIM operations.
-const API_VERSION_PIM: &str = "2020-10-01";
+/// Ticket system name sent with ticket numbers.
+const TICKET_SYSTEM: &str = "azurepim";
 
-/// API version for subscription operations.
-const API_VERSION_SUBS: &str = "2022-12-01";
+/// Schedule request types for activating a role and extending an activation.
+const REQUEST_SELF_ACTIVATE: &str = "SelfActivate";
+const REQUEST_SELF_EXTEND: &str = "SelfExtend";
 
-/// API version for role definitions.
-const API_VERSION_ROLES: &str = "2022-04-01";
+/// Maximum polls of a batch that ARM completes asynchronously.
+const BATCH_POLL_LIMIT: usize = 30;
+
+/// Upper bound for the `Retry-After` delay between batch polls, in seconds.
+const BATCH_MAX_RETRY_AFTER_SECS: u64 = 5;
+
+/// Maximum polls of an activation request that is not provisioned yet.
+const PROVISIONING_POLL_LIMIT: usize = 30;
+
+/// Delay between polls of an activation request.
+const PROVISIONING_POLL_INTERVAL: StdDuration = StdDuration::from_secs(2);
 
 /// HTTP request timeout.
 const HTTP_TIMEOUT: StdDuration = StdDuration::from_secs(30);
@@ -31,21 +58,64 @@ const HTTP_TIMEOUT: StdDuration = StdDuration::from_secs(30);
 /// HTTP connection timeout.
 const HTTP_CONNECT_TIMEOUT: StdDuration = StdDuration::from_secs(10);
 
+/// Eligible roles found by a scan.
+#[derive(Debug, Clone, Default)]
+pub struct EligibilityScan {
+    pub roles: Vec<EligibleRole>,
+    /// IDs of the subscriptions scanned.
+    pub scanned: Vec<String>,
+    /// Subscriptions that refused every query with 403.
+    pub denied: Vec<Subscription>,
+}
+
+/// Results of querying every subscription, in subscription order.
+struct SubscriptionScan<'a, T> {
+    found: Vec<(&'a Subscription, Vec<T>)>,
+    /// Subscriptions that refused every query with 403.
+    denied: Vec<&'a Subscription>,
+}
+
+/// An activation request Azure accepted.
+#[derive(Debug, Clone)]
+pub enum SubmittedActivation {
+    /// Provisioned right away, or scheduled to start later.
+    Provisioned(ActiveAssignment),
+    /// Still being provisioned; see [`PimClient::wait_for_provisioning`].
+    Pending(ActiveAssignment),
+}
+
 /// Azure PIM API client.
+///
+/// Cheap to clone; clones share the connection pool.
+#[derive(Clone)]
 pub struct PimClient {
     http_client: Client,
+    api_versions: Arc<ApiVersions>,
+    /// Maximum subscription requests in flight during a scan.
+    max_concurrent_requests: usize,
 }
 
 impl PimClient {
     /// Create a new PIM client.
-    pub fn new() -> Result<Self, PimError> {
+    pub fn new(runtime: &RuntimeConfig) -> Result<Self, PimError> {
         let http_client = Client::builder()
             .timeout(HTTP_TIMEOUT)
             .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .pool_max_idle_per_host(runtime.pool_max_idle_per_host)
             .build()
             .map_err(PimError::Network)?;
 
-        Ok(Self { http_client })
+        Ok(Self {
+            http_client,
+            api_versions: Arc::new(ApiVersions::default()),
+            max_concurrent_requests: runtime.max_concurrent_requests.max(1),
+        })
+    }
+
+    /// Call the Management APIs with these versions instead of the defaults.
+    pub fn with_api_versions(mut self, versions: ApiVersions) -> Self {
+        self.api_versions = Arc::new(versions);
+        self
     }
 
     /// List all accessible subscriptions.
@@ -53,9 +123,10 @@ impl PimClient {
         &self,
         access_token: &str,
     ) -> Result<Vec<Subscription>, PimError> {
+        check_audience(access_token, Audience::Management)?;
         let url = format!(
             "{}/subscriptions?api-version={}",
-            MANAGEMENT_BASE_URL, API_VERSION_SUBS
+            MANAGEMENT_BASE_URL, self.api_versions.subscriptions
         );
 
         debug!("Fetching subscriptions from {}", url);
@@ -108,8 +179,9 @@ impl PimClient {
         principal_id: &str,
     ) -> Result<Vec<EligibleRole>, PimError> {
         let url = format!(
-            "{}/subscriptions/{}/providers/Microsoft.Authorization/roleEligibilityScheduleInstances?api-version={}&$filter=principalId eq '{}'",
-            MANAGEMENT_BASE_URL, subscription_id, API_VERSION_PIM, principal_id
+            "{}{}",
+            MANAGEMENT_BASE_URL,
+            eligibility_path(&self.api_versions, subscription_id, principal_id)
         );
 
         debug!(
@@ -132,82 +204,296 @@ impl PimClient {
                     .json()
                     .await
                     .map_err(|e| PimError::InvalidResponse(e.to_string()))?;
-
-                // Need to resolve role names from role definition IDs
-                let mut roles = Vec::new();
-                for item in body.value {
-                    let role_name = self
-                        .get_role_name(access_token, &item.properties.role_definition_id)
-                        .await
-                        .unwrap_or_else(|_| "Unknown Role".to_string());
-
-                    roles.push(EligibleRole {
-                        id: item.id,
-                        role_definition_id: item.properties.role_definition_id,
-                        role_name,
-                        subscription_id: subscription_id.to_string(),
-                        subscription_name: String::new(), // Will be filled by caller
-                        scope: item.properties.scope,
-                        principal_id: item.properties.principal_id,
-                    });
-                }
-
-                Ok(roles)
+                Ok(self
+                    .eligible_roles_from_list(access_token, subscription_id, body)
+                    .await)
             }
             401 => Err(PimError::Unauthorized),
-            403 => {
-                // User may not have PIM access to this subscription
-                debug!(
-                    "No PIM access to subscription {}, skipping",
-                    subscription_id
-                );
-                Ok(vec![])
-            }
+            // User may not have PIM access to this subscription
+            403 => Err(PimError::Forbidden),
             _ => {
                 let body = response.text().await.unwrap_or_default();
                 warn!(
-                    "Failed to get eligible roles for {}: HTTP {} - {}",
-                    subscription_id, status, body
+                    subscription = %subscription_id,
+                    error = %status,
+                    "Failed to get eligible roles: {}",
+                    body
                 );
                 Ok(vec![])
             }
         }
     }
 
-    /// Get role definition name from role definition ID.
-    async fn get_role_name(
+    /// Turn an eligibility list into roles, resolving role names.
+    async fn eligible_roles_from_list(
         &self,
         access_token: &str,
-        role_definition_id: &str,
-    ) -> Result<String, PimError> {
+        subscription_id: &str,
+        body: RoleEligibilityListResponse,
+    ) -> Vec<EligibleRole> {
+        let mut roles = Vec::new();
+        for item in body.value {
+            let scope: Scope = match item.properties.scope.parse() {
+                Ok(scope) => scope,
+                Err(e) => {
+                    warn!(
+                        subscription = %subscription_id,
+                        error = %e,
+                        "Skipping eligibility {}",
+                        item.id
+                    );
+                    continue;
+                }
+            };
+            let role_name = self
+                .get_role_name(access_token, &item.properties.role_definition_id, &scope)
+                .await
+                .unwrap_or_else(|_| "Unknown Role".to_string());
+
+            roles.push(EligibleRole {
+                id: item.id,
+                role_definition_id: item.properties.role_definition_id,
+                role_name,
+                subscription_id: subscription_id.to_string(),
+                subscription_name: String::new(), // Will be filled by caller
+                scope,
+                principal_id: item.properties.principal_id,
+                end_time: item.properties.end_date_time,
+                granted_via: item
+                    .properties
+                    .expanded_properties
+                    .and_then(|expanded| expanded.principal)
+                    .filter(|principal| principal.principal_type == "Group")
+                    .map(|principal| principal.display_name),
+                other_grants: Vec::new(),
+            });
+        }
+        roles
+    }
+
+    /// Get eligible roles for every subscription and principal via ARM batch requests.
+    ///
+    /// Queries are sent 20 to a batch, at most `max_concurrent_requests`
+    /// batches at a time. Results are returned in subscription order.
+    async fn batch_eligible_roles<'a>(
+        &self,
+        access_token: &str,
+        subscriptions: &'a [Subscription],
+        principal_ids: &[String],
+    ) -> Result<SubscriptionScan<'a, EligibleRole>, PimError> {
+        let queries: Vec<(usize, String)> = subscriptions
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, sub)| {
+                principal_ids.iter().map(move |principal_id| {
+                    (
+                        idx,
+                        eligibility_path(&self.api_versions, &sub.subscription_id, principal_id),
+                    )
+                })
+            })
+            .collect();
+        info!(
+            "Querying {} subscription/principal pairs in {} batches",
+            queries.len(),
+            queries.len().div_ceil(batch::MAX_BATCH_SIZE)
+        );
+
+        let limit = Arc::new(Semaphore::new(self.max_concurrent_requests));
+        let mut tasks = JoinSet::new();
+        for chunk in queries.chunks(batch::MAX_BATCH_SIZE) {
+            let client = self.clone();
+            let token = access_token.to_string();
+            let chunk = chunk.to_vec();
+            let subscription_ids: Vec<String> = chunk
+                .iter()
+                .map(|(idx, _)| subscriptions[*idx].subscription_id.clone())
+                .collect();
+            let limit = Arc::clone(&limit);
+            let fetch_chunk = async move {
+                let _permit = limit.acquire_owned().await;
+                let urls: Vec<String> = chunk.iter().map(|(_, url)| url.clone()).collect();
+                let responses = client.batch_get(&token, &urls).await?;
+
+                let mut found = Vec::new();
+                let mut refused = Vec::new();
+                for response in responses {
+                    let (Some((idx, _)), Some(subscription_id)) = (
+                        chunk.get(response.name),
+                        subscription_ids.get(response.name),
+                    ) else {
+                        continue;
+                    };
+                    match response.http_status_code {
+                        200 => match serde_json::from_value(response.content) {
+                            Ok(body) => {
+                                let roles = client
+                                    .eligible_roles_from_list(&token, subscription_id, body)
+                                    .await;
+                                found.push((*idx, roles));
+                            }
+                            Err(e) => warn!(
+                                subscription = %subscription_id,
+                                error = %e,
+                                "Invalid eligible roles response"
+                            ),
+                        },
+                        401 => return Err(PimError::Unauthorized),
+                        403 => {
+                            debug!("No PIM access to subscription {}", subscription_id);
+                            refused.push(*idx);
+                        }
+                        status => warn!(
+                            subscription = %subscription_id,
+                            error = status,
+                            "Failed to get eligible roles"
+                        ),
+                    }
+                }
+                Ok((found, refused))
+            };
+            // Warnings count toward the scan's operation
+            tasks.spawn(fetch_chunk.in_current_span());
+        }
+
+        let mut roles_by_sub: Vec<Vec<EligibleRole>> = vec![Vec::new(); subscriptions.len()];
+        let mut answered = vec![false; subscriptions.len()];
+        let mut refused = vec![false; subscriptions.len()];
+        while let Some(joined) = tasks.join_next().await {
+            let (found, refused_in_chunk) =
+                joined.map_err(|e| PimError::InvalidResponse(e.to_string()))??;
+            for (idx, roles) in found {
+                roles_by_sub[idx].extend(roles);
+                answered[idx] = true;
+            }
+            for idx in refused_in_chunk {
+                refused[idx] = true;
+            }
+        }
+
+        Ok(SubscriptionScan {
+            found: subscriptions.iter().zip(roles_by_sub).collect(),
+            denied: denied_subscriptions(subscriptions, &answered, &refused),
+        })
+    }
+
+    /// Send GET requests for `urls` (relative to ARM) as one batch.
+    ///
+    /// Returns one response per URL, in order. Batches ARM completes
+    /// asynchronously are polled until done.
+    async fn batch_get(
+        &self,
+        access_token: &str,
+        urls: &[String],
+    ) -> Result<Vec<batch::BatchItemResponse>, PimError> {
         let url = format!(
-            "{}{}?api-version={}",
-            MANAGEMENT_BASE_URL, role_definition_id, API_VERSION_ROLES
+            "{}/batch?api-version={}",
+            MANAGEMENT_BASE_URL, self.api_versions.batch
         );
 
-        let response = self
+        let mut response = self
             .http_client
-            .get(&url)
+            .post(&url)
             .bearer_auth(access_token)
+            .json(&batch::BatchRequest::get(urls))
             .send()
             .await
             .map_err(PimError::Network)?;
 
-        if response.status().is_success() {
-            let body: RoleDefinitionResponse = response
-                .json()
+        for _ in 0..BATCH_POLL_LIMIT {
+            if response.status().as_u16() != 202 {
+                break;
+            }
+            let Some(location) = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+            else {
+                return Err(PimError::InvalidResponse(
+                    "Batch accepted without a Location header".to_string(),
+                ));
+            };
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(1)
+                .min(BATCH_MAX_RETRY_AFTER_SECS);
+            tokio::time::sleep(StdDuration::from_secs(retry_after)).await;
+
+            response = self
+                .http_client
+                .get(&location)
+                .bearer_auth(access_token)
+                .send()
+                .await
+                .map_err(PimError::Network)?;
+        }
+
+        let status = response.status();
+        match status.as_u16() {
+            200 => {
+                let body = response.text().await.map_err(PimError::Network)?;
+                batch::parse_batch_response(&body)
+                    .ok_or_else(|| PimError::InvalidResponse("Invalid batch response".to_string()))
+            }
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => Err(PimError::InvalidResponse(format!(
+                "Batch failed: HTTP {}",
+                status
+            ))),
+        }
+    }
+
+    /// Get role definition name from role definition ID.
+    ///
+    /// Built-in roles resolve from a compiled-in table. Custom definitions
+    /// can live at a management group even when the ID is subscription-relative,
+    /// so the lookup falls back to the assignment scope and then the tenant
+    /// root before giving up.
+    async fn get_role_name(
+        &self,
+        access_token: &str,
+        role_definition_id: &str,
+        scope: &Scope,
+    ) -> Result<String, PimError> {
+        if let Some(name) = builtin_role_name(role_definition_id) {
+            return Ok(name.to_string());
+        }
+
+        for url in role_definition_urls(&self.api_versions, role_definition_id, scope) {
+            let response = self
+                .http_client
+                .get(&url)
+                .bearer_auth(access_token)
+                .send()
                 .await
-                .map_err(|e| PimError::InvalidResponse(e.to_string()))?;
-            Ok(body.properties.role_name)
-        } else {
-            Err(PimError::InvalidResponse(format!(
-                "Failed to get role definition: {}",
-                role_definition_id
-            )))
+                .map_err(PimError::Network)?;
+
+            let status = response.status();
+            if status.is_success() {
+                let body: RoleDefinitionResponse = response
+                    .json()
+                    .await
+                    .map_err(|e| PimError::InvalidResponse(e.to_string()))?;
+                return Ok(body.properties.role_name);
+            }
+            if status.as_u16() == 401 {
+                return Err(PimError::Unauthorized);
+            }
+            debug!("Role definition not found at {}: HTTP {}", url, status);
         }
+
+        Err(PimError::InvalidResponse(format!(
+            "Failed to get role definition: {}",
+            role_definition_id
+        )))
     }
 
-    /// Get all eligible roles across all subscriptions.
+    /// Get all eligible roles across all subscriptions but those in `skip`.
     ///
     /// `principal_ids` should include the user's object ID plus all group IDs
     /// the user is a member of, to find roles assigned via group membership.
@@ -215,64 +501,58 @@ impl PimClient {
         &self,
         access_token: &str,
         principal_ids: &[String],
-    ) -> Result<Vec<EligibleRole>, PimError> {
+        skip: &[String],
+    ) -> Result<EligibilityScan, PimError> {
         if principal_ids.is_empty() {
             return Err(PimError::InvalidResponse("No principal IDs provided".to_string()));
         }
 
         info!("Checking eligible roles for {} principal IDs (user + groups)", principal_ids.len());
 
-        let subscriptions = self.list_subscriptions(access_token).await?;
-        let total_subs = subscriptions.len();
-
-        let mut all_roles = Vec::new();
-        let mut seen_role_ids = std::collections::HashSet::new();
-
-        for (idx, sub) in subscriptions.iter().enumerate() {
-            // Log progress every 10 subscriptions
-            if idx % 10 == 0 {
-                info!(
-                    "Checking subscription {}/{}: {}",
-                    idx + 1,
-                    total_subs,
-                    sub.display_name
-                );
+        let mut subscriptions = self.list_subscriptions(access_token).await?;
+        subscriptions.retain(|sub| !skip.contains(&sub.subscription_id));
+        // Counted in the caller's `operation` span, if there is one
+        Span::current().record("subscriptions", subscriptions.len());
+        let scan = match self
+            .batch_eligible_roles(access_token, &subscriptions, principal_ids)
+            .await
+        {
+            Ok(results) => results,
+            Err(PimError::Unauthorized) => return Err(PimError::Unauthorized),
+            Err(e) if e.is_offline() => return Err(e),
+            Err(e) => {
+                warn!("Batch eligibility query failed, querying one by one: {}", e);
+                let fetch = |client: PimClient, token: String, sub_id: String, principal_id: String| async move {
+                    client
+                        .get_eligible_roles_for_subscription(&token, &sub_id, &principal_id)
+                        .await
+                };
+                self.scan_subscriptions(access_token, &subscriptions, principal_ids, fetch)
+                    .await?
             }
+        };
 
-            // Query for each principal ID (user + groups)
-            for principal_id in principal_ids {
-                match self
-                    .get_eligible_roles_for_subscription(
-                        access_token,
-                        &sub.subscription_id,
-                        principal_id,
-                    )
-                    .await
-                {
-                    Ok(mut roles) => {
-                        // Fill in subscription names and deduplicate
-                        for role in &mut roles {
-                            role.subscription_name = sub.display_name.clone();
-                            // Deduplicate by role ID (same role might appear for multiple groups)
-                            if seen_role_ids.insert(role.id.clone()) {
-                                all_roles.push(role.clone());
-                            }
-                        }
-                    }
-                    Err(PimError::Unauthorized) => return Err(PimError::Unauthorized),
-                    Err(e) => {
-                        warn!(
-                            "Error fetching roles for subscription {} (principal {}): {}",
-                            sub.display_name, principal_id, e
-                        );
-                        // Continue with other subscriptions/principals
-                    }
-                }
+        let mut all_roles = Vec::new();
+        for (sub, roles) in scan.found {
+            // Fill in subscription names
+            for mut role in roles {
+                role.subscription_name = role.scope.display_name(&sub.display_name);
+                all_roles.push(role);
             }
         }
+        // The same role can be granted to the user and several groups; keep
+        // one per role, listing every principal it is granted to
+        let all_roles = merge_duplicate_grants(all_roles);
 
         info!("Found {} total eligible roles (deduplicated)", all_roles.len());
-        Ok(all_roles)
+        Ok(EligibilityScan {
+            roles: all_roles,
+            scanned: subscriptions
+                .iter()
+                .map(|sub| sub.subscription_id.clone())
+                .collect(),
+            denied: scan.denied.into_iter().cloned().collect(),
+        })
     }
 
     /// Get active role assignments for all subscriptions.
@@ -289,42 +569,150 @@ impl PimClient {
         }
 
         let subscriptions = self.list_subscriptions(access_token).await?;
+        let results = self
+            .get_active_assignments_in(access_token, &subscriptions, principal_ids)
+            .await?;
+        let all_assignments: Vec<_> = results
+            .into_iter()
+            .flat_map(|(_, assignments)| assignments)
+            .collect();
+
+        info!("Found {} active assignments (deduplicated)", all_assignments.len());
+        Ok(all_assignments)
+    }
 
-        let mut all_assignments = Vec::new();
+    /// Get active role assignments in only the given subscriptions.
+    ///
+    /// Returns each subscription's ID with its assignments, in subscription
+    /// order. Subscriptions that could not be queried are left out.
+    pub async fn get_active_assignments_in(
+        &self,
+        access_token: &str,
+        subscriptions: &[Subscription],
+        principal_ids: &[String],
+    ) -> Result<Vec<(String, Vec<ActiveAssignment>)>, PimError> {
+        if principal_ids.is_empty() {
+            return Err(PimError::InvalidResponse("No principal IDs provided".to_string()));
+        }
+
+        let fetch = |client: PimClient, token: String, sub_id: String, principal_id: String| async move {
+            client
+                .get_active_assignments_for_subscription(&token, &sub_id, &principal_id)
+                .await
+        };
+        let scan = self
+            .scan_subscriptions(access_token, subscriptions, principal_ids, fetch)
+            .await?;
+
+        // One entry per subscription, even with several principals
+        let mut by_subscription: Vec<(String, Vec<ActiveAssignment>)> = Vec::new();
         let mut seen_assignment_ids = std::collections::HashSet::new();
+        for (sub, assignments) in scan.found {
+            // Fill in subscription names and deduplicate
+            let assignments: Vec<_> = assignments
+                .into_iter()
+                .filter(|assignment| seen_assignment_ids.insert(assignment.id.clone()))
+                .map(|mut assignment| {
+                    assignment.subscription_name = assignment.scope.display_name(&sub.display_name);
+                    assignment
+                })
+                .collect();
+            match by_subscription.last_mut() {
+                Some((id, existing)) if *id == sub.subscription_id => existing.extend(assignments),
+                _ => by_subscription.push((sub.subscription_id.clone(), assignments)),
+            }
+        }
+        Ok(by_subscription)
+    }
+
+    /// Run `fetch` for every subscription and principal ID, at most
+    /// `max_concurrent_requests` at a time.
+    ///
+    /// Results are returned in subscription order. Failures for a single
+    /// subscription are logged and skipped; an expired token aborts the scan.
+    async fn scan_subscriptions<'a, T, F, Fut>(
+        &self,
+        access_token: &str,
+        subscriptions: &'a [Subscription],
+        principal_ids: &[String],
+        fetch: F,
+    ) -> Result<SubscriptionScan<'a, T>, PimError>
+    where
+        T: Send + 'static,
+        F: Fn(PimClient, String, String, String) -> Fut,
+        Fut: Future<Output = Result<Vec<T>, PimError>> + Send + 'static,
+    {
+        info!(
+            "Scanning {} subscriptions with up to {} concurrent requests",
+            subscriptions.len(),
+            self.max_concurrent_requests
+        );
 
-        for sub in &subscriptions {
+        let limit = Arc::new(Semaphore::new(self.max_concurrent_requests));
+        let mut tasks = JoinSet::new();
+        for (idx, sub) in subscriptions.iter().enumerate() {
+            // Query for each principal ID (user + groups)
             for principal_id in principal_ids {
-                match self
-                    .get_active_assignments_for_subscription(
-                        access_token,
-                        &sub.subscription_id,
-                        principal_id,
-                    )
-                    .await
-                {
-                    Ok(mut assignments) => {
-                        // Fill in subscription names and deduplicate
-                        for assignment in &mut assignments {
-                            assignment.subscription_name = sub.display_name.clone();
-                            if seen_assignment_ids.insert(assignment.id.clone()) {
-                                all_assignments.push(assignment.clone());
-                            }
-                        }
-                    }
-                    Err(PimError::Unauthorized) => return Err(PimError::Unauthorized),
-                    Err(e) => {
-                        warn!(
-                            "Error fetching active assignments for subscription {} (principal {}): {}",
-                            sub.display_name, principal_id, e
-                        );
+                let request = fetch(
+                    self.clone(),
+                    access_token.to_string(),
+                    sub.subscription_id.clone(),
+                    principal_id.clone(),
+                );
+                let limit = Arc::clone(&limit);
+                let principal_id = principal_id.clone();
+                tasks.spawn(
+                    async move {
+                        let _permit = limit.acquire_owned().await;
+                        (idx, principal_id, request.await)
                     }
+                    .in_current_span(),
+                );
+            }
+        }
+
+        let mut results = Vec::new();
+        let mut answered = vec![false; subscriptions.len()];
+        let mut refused = vec![false; subscriptions.len()];
+        while let Some(joined) = tasks.join_next().await {
+            let (idx, principal_id, result) = match joined {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!("Subscription scan task failed: {}", e);
+                    continue;
+                }
+            };
+            let sub = &subscriptions[idx];
+            match result {
+                Ok(items) => {
+                    answered[idx] = true;
+                    results.push((idx, sub, items));
+                }
+                Err(PimError::Unauthorized) => return Err(PimError::Unauthorized),
+                Err(PimError::Forbidden) => {
+                    debug!("No PIM access to subscription {}", sub.display_name);
+                    refused[idx] = true;
+                }
+                Err(e) => {
+                    warn!(
+                        subscription = %sub.display_name,
+                        error = %e,
+                        "Error scanning subscription for principal {}",
+                        principal_id
+                    );
+                    // Continue with other subscriptions/principals
                 }
             }
         }
 
-        info!("Found {} active assignments (deduplicated)", all_assignments.len());
-        Ok(all_assignments)
+        results.sort_by_key(|(idx, _, _)| *idx);
+        Ok(SubscriptionScan {
+            found: results
+                .into_iter()
+                .map(|(_, sub, items)| (sub, items))
+                .collect(),
+            denied: denied_subscriptions(subscriptions, &answered, &refused),
+        })
     }
 
     /// Get active assignments for a single subscription.
@@ -336,7 +724,7 @@ impl PimClient {
     ) -> Result<Vec<ActiveAssignment>, PimError> {
         let url = format!(
             "{}/subscriptions/{}/providers/Microsoft.Authorization/roleAssignmentScheduleInstances?api-version={}&$filter=principalId eq '{}'",
-            MANAGEMENT_BASE_URL, subscription_id, API_VERSION_PIM, principal_id
+            MANAGEMENT_BASE_URL, subscription_id, self.api_versions.pim, principal_id
         );
 
         let response = self
@@ -362,21 +750,45 @@ impl PimClient {
                         item.properties.start_date_time,
                         item.properties.end_date_time,
                     ) {
+                        let scope: Scope = match item.properties.scope.parse() {
+                            Ok(scope) => scope,
+                            Err(e) => {
+                                warn!("Skipping assignment {}: {}", item.id, e);
+                                continue;
+                            }
+                        };
                         let role_name = self
-                            .get_role_name(access_token, &item.properties.role_definition_id)
+                            .get_role_name(
+                                access_token,
+                                &item.properties.role_definition_id,
+                                &scope,
+                            )
                             .await
                             .unwrap_or_else(|_| "Unknown Role".to_string());
 
+                        // The list response has no justification; it lives on the request
+                        let justification = match &item.properties.role_assignment_schedule_id {
+                            Some(schedule_id) => self
+                                .get_justification(access_token, schedule_id)
+                                .await
+                                .unwrap_or_else(|e| {
+                                    debug!("No justification for {}: {}", schedule_id, e);
+                                    None
+                                })
+                                .unwrap_or_default(),
+                            None => String::new(),
+                        };
+
                         assignments.push(ActiveAssignment {
                             id: item.id,
                             role_definition_id: item.properties.role_definition_id,
                             role_name,
                             subscription_id: subscription_id.to_string(),
                             subscription_name: String::new(),
-                            scope: item.properties.scope,
+                            scope,
                             start_time: start,
                             end_time: end,
-                            justification: String::new(), // Not available in list response
+                            justification,
                             assignment_request_id: item.properties.role_assignment_schedule_id,
                         });
                     }
@@ -390,118 +802,939 @@ impl PimClient {
         }
     }
 
-    /// Activate a PIM role.
-    pub async fn activate_role(
+    /// Get the roles activated by anyone in `subscriptions`, soonest to expire first.
+    ///
+    /// For PIM administrators; reading others' assignments needs
+    /// `roleAssignmentScheduleInstances/read` on the subscription. Subscriptions
+    /// that refuse or fail the query are logged and left out.
+    pub async fn get_tenant_assignments(
         &self,
         access_token: &str,
-        request: ActivationRequest,
-    ) -> Result<ActiveAssignment, PimError> {
-        let request_id = Uuid::new_v4().to_string();
-        let url = format!(
-            "{}{}/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/{}?api-version={}",
-            MANAGEMENT_BASE_URL,
-            request.eligible_role.scope,
-            request_id,
-            API_VERSION_PIM
-        );
-
-        let start_time = Utc::now();
-        let duration = format!("PT{}M", request.duration_minutes);
-
-        let body = ActivationRequestBody {
-            properties: ActivationProperties {
-                principal_id: request.eligible_role.principal_id.clone(),
-                role_definition_id: request.eligible_role.role_definition_id.clone(),
-                request_type: "SelfActivate".to_string(),
-                justification: request.justification.clone(),
-                linked_role_eligibility_schedule_id: Some(request.eligible_role.id.clone()),
-                schedule_info: ScheduleInfo {
-                    start_date_time: start_time.to_rfc3339(),
-                    expiration: Expiration {
-                        expiration_type: "AfterDuration".to_string(),
-                        duration,
-                    },
-                },
-            },
-        };
+        subscriptions: &[Subscription],
+    ) -> Result<Vec<TenantAssignment>, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let limit = Arc::new(Semaphore::new(self.max_concurrent_requests));
+        let mut tasks = JoinSet::new();
+        for subscription in subscriptions {
+            let client = self.clone();
+            let token = access_token.to_string();
+            let subscription = subscription.clone();
+            let limit = Arc::clone(&limit);
+            let fetch_assignments = async move {
+                let _permit = limit.acquire_owned().await;
+                let result = client
+                    .get_tenant_assignments_for_subscription(&token, &subscription)
+                    .await;
+                (subscription, result)
+            };
+            tasks.spawn(fetch_assignments.in_current_span());
+        }
 
-        info!(
-            "Activating role {} on {} for {} minutes",
-            request.eligible_role.role_name,
-            request.eligible_role.subscription_name,
-            request.duration_minutes
+        let mut assignments: Vec<TenantAssignment> = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let (subscription, result) = match joined {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!("Tenant assignment task failed: {}", e);
+                    continue;
+                }
+            };
+            match result {
+                Ok(found) => {
+                    // Assignments above a subscription show up in each one below it
+                    for assignment in found {
+                        if !assignments.iter().any(|a| a.id == assignment.id) {
+                            assignments.push(assignment);
+                        }
+                    }
+                }
+                Err(PimError::Unauthorized) => return Err(PimError::Unauthorized),
+                Err(e) => warn!(
+                    subscription = %subscription.display_name,
+                    error = %e,
+                    "Failed to list active assignments"
+                ),
+            }
+        }
+        assignments.sort_by(|a, b| {
+            a.end_time
+                .cmp(&b.end_time)
+                .then_with(|| a.principal_name.cmp(&b.principal_name))
+        });
+        Ok(assignments)
+    }
+
+    async fn get_tenant_assignments_for_subscription(
+        &self,
+        access_token: &str,
+        subscription: &Subscription,
+    ) -> Result<Vec<TenantAssignment>, PimError> {
+        let url = format!(
+            "{}/subscriptions/{}/providers/Microsoft.Authorization/roleAssignmentScheduleInstances?api-version={}",
+            MANAGEMENT_BASE_URL, subscription.subscription_id, self.api_versions.pim
         );
+        let body = self.get_text(access_token, &url).await?;
+        let list: RoleAssignmentListResponse =
+            serde_json::from_str(&body).map_err(|e| PimError::InvalidResponse(e.to_string()))?;
+
+        let mut assignments = tenant_assignments_from_list(list, &subscription.display_name);
+        // Role names come with the list only if ARM expanded its properties
+        for assignment in assignments.iter_mut().filter(|a| a.role_name.is_empty()) {
+            assignment.role_name = self
+                .get_role_name(
+                    access_token,
+                    &assignment.role_definition_id,
+                    &assignment.scope,
+                )
+                .await
+                .unwrap_or_else(|_| "Unknown Role".to_string());
+        }
+        Ok(assignments)
+    }
+
+    /// Count the user's own activation requests still waiting for approval at `scopes`.
+    ///
+    /// Requests visible from more than one scope are counted once.
+    pub async fn count_my_pending_requests(
+        &self,
+        access_token: &str,
+        scopes: &[Scope],
+    ) -> Result<usize, PimError> {
+        Ok(self
+            .list_my_requests(access_token, scopes)
+            .await?
+            .pending
+            .len())
+    }
+
+    /// List the user's own activation requests at `scopes` still waiting for
+    /// approval or scheduled to start later.
+    ///
+    /// Requests visible from more than one scope are listed once.
+    pub async fn list_my_requests(
+        &self,
+        access_token: &str,
+        scopes: &[Scope],
+    ) -> Result<MyRequests, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let now = Utc::now();
+        let mut mine = MyRequests::default();
+        for scope in scopes {
+            let url = scope.url(
+                MANAGEMENT_BASE_URL,
+                &format!(
+                    "/providers/Microsoft.Authorization/roleAssignmentScheduleRequests?api-version={}&$filter=asRequestor()",
+                    self.api_versions.pim
+                ),
+            );
+            let body = self.get_text(access_token, &url).await?;
+            let invalid = || PimError::InvalidResponse("Invalid schedule request list".to_string());
+            let pending = approvals::parse_pending_requests(&body).ok_or_else(invalid)?;
+            let scheduled =
+                approvals::parse_scheduled_activations(&body, now).ok_or_else(invalid)?;
+            for request in pending {
+                if !mine.pending.iter().any(|p| p.is(&request.id)) {
+                    mine.pending.push(request);
+                }
+            }
+            for activation in scheduled {
+                if !mine.scheduled.iter().any(|s| s.is(&activation.id)) {
+                    mine.scheduled.push(activation);
+                }
+            }
+        }
+        mine.scheduled.sort_by_key(|s| s.start_time);
+        Ok(mine)
+    }
 
+    /// Cancel one of the user's schedule requests, such as an activation
+    /// scheduled to start later.
+    pub async fn cancel_schedule_request(
+        &self,
+        access_token: &str,
+        request_id: &str,
+    ) -> Result<(), PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let url = format!(
+            "{}{}/cancel?api-version={}",
+            MANAGEMENT_BASE_URL, request_id, self.api_versions.pim
+        );
+        info!("Cancelling schedule request {}", request_id);
         let response = self
             .http_client
-            .put(&url)
+            .post(&url)
             .bearer_auth(access_token)
-            .json(&body)
+            // ARM wants a length even without a body
+            .header(reqwest::header::CONTENT_LENGTH, 0)
             .send()
             .await
             .map_err(PimError::Network)?;
 
         let status = response.status();
         match status.as_u16() {
-            200 | 201 => {
-                let response_body: ActivationResponseBody = response
-                    .json()
-                    .await
-                    .map_err(|e| PimError::InvalidResponse(e.to_string()))?;
-
-                let end_time =
-                    start_time + chrono::Duration::minutes(request.duration_minutes as i64);
-
-                info!(
-                    "Successfully activated role {} until {}",
-                    request.eligible_role.role_name, end_time
-                );
-
-                Ok(ActiveAssignment {
-                    id: response_body.id,
-                    role_definition_id: request.eligible_role.role_definition_id,
-                    role_name: request.eligible_role.role_name,
-                    subscription_id: request.eligible_role.subscription_id,
-                    subscription_name: request.eligible_role.subscription_name,
-                    scope: request.eligible_role.scope,
-                    start_time,
-                    end_time,
-                    justification: request.justification,
-                    assignment_request_id: Some(request_id),
-                })
-            }
-            400 => {
-                let body = response.text().await.unwrap_or_default();
-                error!("Bad request for role activation: {}", body);
-                Err(PimError::ActivationFailed("Bad request".to_string()))
-            }
+            200 | 204 => Ok(()),
             401 => Err(PimError::Unauthorized),
             403 => Err(PimError::Forbidden),
-            409 => {
-                warn!("Role is already active");
-                Err(PimError::RoleAlreadyActive)
-            }
             _ => {
                 let body = response.text().await.unwrap_or_default();
-                error!("Role activation failed: HTTP {} - {}", status, body);
-                Err(PimError::ActivationFailed(format!("HTTP {}", status)))
+                error!(
+                    "Cancelling schedule request failed: HTTP {} - {}",
+                    status, body
+                );
+                Err(PimError::CancelFailed(format!("HTTP {}", status)))
             }
         }
     }
-}
-
-// --- API Response Types ---
 
-#[derive(Debug, Deserialize)]
-struct SubscriptionListResponse {
-    value: Vec<SubscriptionItem>,
-}
+    /// List other users' activation requests at `scopes` waiting for the user to review.
+    ///
+    /// Requests visible from more than one scope are listed once.
+    pub async fn list_requests_awaiting_my_approval(
+        &self,
+        access_token: &str,
+        scopes: &[Scope],
+    ) -> Result<Vec<ApprovalRequest>, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let mut awaiting: Vec<ApprovalRequest> = Vec::new();
+        for scope in scopes {
+            let url = scope.url(
+                MANAGEMENT_BASE_URL,
+                &format!(
+                    "/providers/Microsoft.Authorization/roleAssignmentScheduleRequests?api-version={}&$filter=asApprover()",
+                    self.api_versions.pim
+                ),
+            );
+            let body = match self.get_text(access_token, &url).await {
+                Ok(body) => body,
+                // Requests the user may review are readable to them
+                Err(PimError::Forbidden) => {
+                    debug!("Not allowed to list requests to review at {}", scope);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let requests = approvals::parse_approval_requests(&body).ok_or_else(|| {
+                PimError::InvalidResponse("Invalid schedule request list".to_string())
+            })?;
+            for request in requests {
+                if !awaiting.iter().any(|a| a.is(&request.id)) {
+                    awaiting.push(request);
+                }
+            }
+        }
+        Ok(awaiting)
+    }
 
-#[derive(Debug, Deserialize)]
-struct SubscriptionItem {
-    #[serde(rename = "subscriptionId")]
-    subscription_id: String,
-    #[serde(rename = "displayName")]
+    /// Approve or deny a request on the stage of its approval waiting for the user.
+    ///
+    /// ARM requires a `justification` for either decision.
+    pub async fn review_approval(
+        &self,
+        access_token: &str,
+        request: &ApprovalRequest,
+        result: ReviewResult,
+        justification: &str,
+    ) -> Result<(), PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let url = format!(
+            "{}{}?api-version={}",
+            MANAGEMENT_BASE_URL, request.approval_id, self.api_versions.approvals
+        );
+        let body = self.get_text(access_token, &url).await?;
+        let Some(stage_id) = approvals::parse_stage_awaiting_me(&body) else {
+            return Err(PimError::ReviewFailed(
+                "the request is no longer waiting for your review".to_string(),
+            ));
+        };
+
+        info!(
+            "Submitting {} for {}",
+            result.as_str(),
+            request.menu_title()
+        );
+        let url = format!(
+            "{}{}?api-version={}",
+            MANAGEMENT_BASE_URL, stage_id, self.api_versions.approvals
+        );
+        let response = self
+            .http_client
+            .put(&url)
+            .bearer_auth(access_token)
+            .json(&serde_json::json!({
+                "properties": {
+                    "reviewResult": result.as_str(),
+                    "justification": justification,
+                }
+            }))
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200 | 201 => Ok(()),
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => {
+                let body = response.text().await.unwrap_or_default();
+                error!("Approval review failed: HTTP {} - {}", status, body);
+                Err(PimError::ReviewFailed(format!("HTTP {}", status)))
+            }
+        }
+    }
+
+    /// Count requests waiting for the user to approve.
+    pub async fn count_awaiting_my_approval(&self, access_token: &str) -> Result<usize, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let url = format!(
+            "{}/providers/Microsoft.Authorization/roleAssignmentApprovals?api-version={}&$filter=asApprover()",
+            MANAGEMENT_BASE_URL, self.api_versions.approvals
+        );
+        let body = self.get_text(access_token, &url).await?;
+        approvals::parse_approval_count(&body)
+            .ok_or_else(|| PimError::InvalidResponse("Invalid approval list".to_string()))
+    }
+
+    /// GET a Management API URL and return the response body.
+    /// Try each configured API version once, returning those ARM rejects.
+    ///
+    /// Requests that fail for other reasons are not reported; the calls
+    /// using the version report them.
+    pub async fn check_api_versions(
+        &self,
+        access_token: &str,
+    ) -> Result<Vec<ApiVersionProblem>, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let mut problems = Vec::new();
+        for (name, version) in self.api_versions.entries() {
+            let request = match name {
+                "subscriptions" => self
+                    .http_client
+                    .get(format!("{}/subscriptions", MANAGEMENT_BASE_URL)),
+                "batch" => self
+                    .http_client
+                    .post(format!("{}/batch", MANAGEMENT_BASE_URL))
+                    .json(&serde_json::json!({ "requests": [] })),
+                "approvals" => self.http_client.get(format!(
+                    "{}/providers/Microsoft.Authorization/roleAssignmentApprovals?$filter=asApprover()",
+                    MANAGEMENT_BASE_URL
+                )),
+                "role_definitions" => self.http_client.get(format!(
+                    "{}/providers/Microsoft.Authorization/roleDefinitions?$filter=roleName eq 'Reader'",
+                    MANAGEMENT_BASE_URL
+                )),
+                "pim" => self.http_client.get(format!(
+                    "{}/providers/Microsoft.Authorization/roleEligibilityScheduleInstances?$filter=asTarget()",
+                    MANAGEMENT_BASE_URL
+                )),
+                _ => continue,
+            };
+            let response = match request
+                .query(&[("api-version", version)])
+                .bearer_auth(access_token)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    debug!("API version check of {} failed: {}", name, e);
+                    continue;
+                }
+            };
+            if response.status().as_u16() != 400 {
+                continue;
+            }
+            let body = response.text().await.unwrap_or_default();
+            if let Some(suggested) = compat::rejected_api_version(&body) {
+                problems.push(ApiVersionProblem {
+                    name,
+                    version: version.to_string(),
+                    suggested,
+                });
+            }
+        }
+        Ok(problems)
+    }
+
+    async fn get_text(&self, access_token: &str, url: &str) -> Result<String, PimError> {
+        let response = self
+            .http_client
+            .get(url)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200 => response.text().await.map_err(PimError::Network),
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => Err(PimError::InvalidResponse(format!("HTTP {}", status))),
+        }
+    }
+
+    /// Get the justification of the request that created an assignment schedule.
+    ///
+    /// Follows the schedule to its `roleAssignmentScheduleRequestId` and reads
+    /// the justification from that request.
+    async fn get_justification(
+        &self,
+        access_token: &str,
+        schedule_id: &str,
+    ) -> Result<Option<String>, PimError> {
+        let schedule: ScheduleResponse = self.get_resource(access_token, schedule_id).await?;
+        let Some(request_id) = schedule.properties.role_assignment_schedule_request_id else {
+            return Ok(None);
+        };
+
+        let request: ScheduleRequestResponse = self.get_resource(access_token, &request_id).await?;
+        Ok(request
+            .properties
+            .justification
+            .filter(|justification| !justification.is_empty()))
+    }
+
+    /// GET a Management API resource by its full resource ID.
+    async fn get_resource<T: serde::de::DeserializeOwned>(
+        &self,
+        access_token: &str,
+        resource_id: &str,
+    ) -> Result<T, PimError> {
+        let url = format!(
+            "{}{}?api-version={}",
+            MANAGEMENT_BASE_URL, resource_id, self.api_versions.pim
+        );
+
+        let response = self
+            .http_client
+            .get(&url)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200 => response
+                .json()
+                .await
+                .map_err(|e| PimError::InvalidResponse(e.to_string())),
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => Err(PimError::InvalidResponse(format!("HTTP {}", status))),
+        }
+    }
+
+    /// Check whether an activation would succeed, without activating.
+    ///
+    /// Uses the schedule request `validate` action, which runs the same
+    /// policy checks as a real request but creates nothing.
+    pub async fn validate_activation(
+        &self,
+        access_token: &str,
+        request: &ActivationRequest,
+    ) -> Result<ActivationCheck, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let url = validation_url(
+            &self.api_versions,
+            &request.eligible_role.scope,
+            &Uuid::new_v4().to_string(),
+        );
+        let body = activation_body(request, Utc::now(), REQUEST_SELF_ACTIVATE);
+
+        info!(
+            "Validating activation of {} on {}",
+            request.eligible_role.role_name, request.eligible_role.subscription_name
+        );
+
+        let response = self
+            .http_client
+            .post(&url)
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status().as_u16();
+        match status {
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => {
+                let body = response.text().await.unwrap_or_default();
+                debug!("Activation validation returned HTTP {}: {}", status, body);
+                Ok(policy::parse_activation_check(status, &body))
+            }
+        }
+    }
+
+    /// Activate a PIM role, from `request.start_time` or now, and wait until
+    /// Azure has provisioned it.
+    pub async fn activate_role(
+        &self,
+        access_token: &str,
+        request: ActivationRequest,
+    ) -> Result<ActiveAssignment, PimError> {
+        let submitted = self.submit_activation(access_token, request).await?;
+        self.provisioned(access_token, submitted).await
+    }
+
+    /// Request activation of a PIM role without waiting for Azure to provision it.
+    pub async fn submit_activation(
+        &self,
+        access_token: &str,
+        request: ActivationRequest,
+    ) -> Result<SubmittedActivation, PimError> {
+        self.submit_schedule_request(access_token, request, REQUEST_SELF_ACTIVATE)
+            .await
+    }
+
+    /// Extend an activation so it lasts `request.duration_minutes` from `request.start_time`.
+    ///
+    /// Most role policies refuse this; callers fall back to a back-to-back
+    /// activation (see [`PimError::is_extension_refused`]).
+    pub async fn extend_role(
+        &self,
+        access_token: &str,
+        request: ActivationRequest,
+    ) -> Result<ActiveAssignment, PimError> {
+        let submitted = self
+            .submit_schedule_request(access_token, request, REQUEST_SELF_EXTEND)
+            .await?;
+        self.provisioned(access_token, submitted).await
+    }
+
+    /// The assignment of a submitted request, once Azure has provisioned it.
+    async fn provisioned(
+        &self,
+        access_token: &str,
+        submitted: SubmittedActivation,
+    ) -> Result<ActiveAssignment, PimError> {
+        match submitted {
+            SubmittedActivation::Provisioned(assignment) => Ok(assignment),
+            SubmittedActivation::Pending(assignment) => {
+                self.wait_for_provisioning(access_token, &assignment)
+                    .await?;
+                Ok(assignment)
+            }
+        }
+    }
+
+    async fn submit_schedule_request(
+        &self,
+        access_token: &str,
+        request: ActivationRequest,
+        request_type: &str,
+    ) -> Result<SubmittedActivation, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let request_id = Uuid::new_v4().to_string();
+        let url = activation_url(
+            &self.api_versions,
+            &request.eligible_role.scope,
+            &request_id,
+        );
+
+        let start_time = request.start_time.unwrap_or_else(Utc::now);
+        let body = activation_body(&request, start_time, request_type);
+
+        info!(
+            "{} role {} on {} for {} minutes from {}",
+            request_type,
+            request.eligible_role.role_name,
+            request.eligible_role.subscription_name,
+            request.duration_minutes,
+            start_time
+        );
+
+        let response = self
+            .http_client
+            .put(&url)
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200 | 201 => {
+                let response_body: ActivationResponseBody = response
+                    .json()
+                    .await
+                    .map_err(|e| PimError::InvalidResponse(e.to_string()))?;
+                let status = response_body
+                    .properties
+                    .and_then(|p| p.status)
+                    .unwrap_or_default();
+                let provisioned = check_provisioning(&status, &response_body.id)?;
+
+                let end_time =
+                    start_time + chrono::Duration::minutes(request.duration_minutes as i64);
+
+                if start_time > Utc::now() {
+                    info!(
+                        "Scheduled role {} from {} until {}",
+                        request.eligible_role.role_name, start_time, end_time
+                    );
+                } else {
+                    info!(
+                        "Requested role {} until {}",
+                        request.eligible_role.role_name, end_time
+                    );
+                }
+
+                let assignment = ActiveAssignment {
+                    id: response_body.id,
+                    role_definition_id: request.eligible_role.role_definition_id,
+                    role_name: request.eligible_role.role_name,
+                    subscription_id: request.eligible_role.subscription_id,
+                    subscription_name: request.eligible_role.subscription_name,
+                    scope: request.eligible_role.scope,
+                    start_time,
+                    end_time,
+                    justification: request.justification,
+                    assignment_request_id: Some(request_id),
+                };
+                Ok(if provisioned {
+                    SubmittedActivation::Provisioned(assignment)
+                } else {
+                    SubmittedActivation::Pending(assignment)
+                })
+            }
+            400 => {
+                let body = response.text().await.unwrap_or_default();
+                error!("Bad request for role activation: {}", body);
+                if let Some(claims) = challenge::claims_from_arm_error(&body) {
+                    return Err(PimError::StepUpRequired(StepUp::Claims(claims)));
+                }
+                match policy::parse_failed_rules(&body).into_iter().next() {
+                    Some(PolicyRule::Mfa) => Err(PimError::StepUpRequired(StepUp::Mfa)),
+                    Some(rule) => Err(PimError::PolicyViolation(rule)),
+                    None => Err(PimError::ActivationFailed("Bad request".to_string())),
+                }
+            }
+            401 => {
+                // Conditional Access asks for more claims in the challenge header
+                let claims = response
+                    .headers()
+                    .get(reqwest::header::WWW_AUTHENTICATE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(challenge::claims_from_www_authenticate);
+                match claims {
+                    Some(claims) => Err(PimError::StepUpRequired(StepUp::Claims(claims))),
+                    None => Err(PimError::Unauthorized),
+                }
+            }
+            403 => Err(PimError::Forbidden),
+            409 => {
+                warn!("Role is already active");
+                Err(PimError::RoleAlreadyActive)
+            }
+            _ => {
+                let body = response.text().await.unwrap_or_default();
+                error!("Role activation failed: HTTP {} - {}", status, body);
+                Err(PimError::ActivationFailed(format!("HTTP {}", status)))
+            }
+        }
+    }
+
+    /// Poll the schedule request of a pending activation until Azure has
+    /// provisioned it.
+    ///
+    /// A request is usually still `PendingProvisioning` right after the PUT.
+    /// Denied and failed requests return [`PimError::ProvisioningFailed`] with
+    /// the status as the reason, and requests waiting for an approver
+    /// [`PimError::ApprovalRequired`]. Requests still pending after the last
+    /// poll are left to the assignment poll.
+    pub async fn wait_for_provisioning(
+        &self,
+        access_token: &str,
+        assignment: &ActiveAssignment,
+    ) -> Result<(), PimError> {
+        let mut status = String::new();
+        for _ in 0..PROVISIONING_POLL_LIMIT {
+            tokio::time::sleep(PROVISIONING_POLL_INTERVAL).await;
+            let request: ScheduleRequestResponse =
+                self.get_resource(access_token, &assignment.id).await?;
+            status = request.properties.status.unwrap_or_default();
+            if check_provisioning(&status, &assignment.id)? {
+                return Ok(());
+            }
+        }
+        warn!("Activation request still {} after polling", status);
+        Ok(())
+    }
+
+    /// Deactivate an active role assignment before it expires.
+    pub async fn deactivate_role(
+        &self,
+        access_token: &str,
+        assignment: &ActiveAssignment,
+        principal_id: &str,
+    ) -> Result<(), PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let url = activation_url(
+            &self.api_versions,
+            &assignment.scope,
+            &Uuid::new_v4().to_string(),
+        );
+        let body = DeactivationRequestBody {
+            properties: DeactivationProperties {
+                principal_id: principal_id.to_string(),
+                role_definition_id: assignment.role_definition_id.clone(),
+                request_type: "SelfDeactivate".to_string(),
+            },
+        };
+
+        info!(
+            "Deactivating role {} on {}",
+            assignment.role_name, assignment.subscription_name
+        );
+
+        let response = self
+            .http_client
+            .put(&url)
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        let status = response.status();
+        match status.as_u16() {
+            200 | 201 => Ok(()),
+            401 => Err(PimError::Unauthorized),
+            403 => Err(PimError::Forbidden),
+            _ => {
+                let body = response.text().await.unwrap_or_default();
+                error!("Role deactivation failed: HTTP {} - {}", status, body);
+                Err(PimError::DeactivationFailed(format!("HTTP {}", status)))
+            }
+        }
+    }
+
+    /// Get the maximum activation duration in minutes allowed by a role's policy at `scope`.
+    pub async fn get_max_activation_minutes(
+        &self,
+        access_token: &str,
+        scope: &Scope,
+        role_definition_id: &str,
+    ) -> Result<Option<u32>, PimError> {
+        let body = self
+            .get_role_policy(access_token, scope, role_definition_id)
+            .await?;
+        Ok(body.as_deref().and_then(policy::parse_max_duration))
+    }
+
+    /// Read the activation rules of `roles`: their maximum duration and
+    /// whether each activation needs an approver.
+    ///
+    /// Reads each role's policy, a few at a time. Returns the role keys whose
+    /// policy could be read, with its rules.
+    pub async fn get_role_policies(
+        &self,
+        access_token: &str,
+        roles: &[EligibleRole],
+    ) -> Result<HashMap<String, RolePolicy>, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let limit = Arc::new(Semaphore::new(self.max_concurrent_requests));
+        let mut tasks = JoinSet::new();
+        for role in roles {
+            let client = self.clone();
+            let token = access_token.to_string();
+            let role = role.clone();
+            let limit = Arc::clone(&limit);
+            let fetch_policy = async move {
+                let _permit = limit.acquire_owned().await;
+                let body = client
+                    .get_role_policy(&token, &role.scope, &role.role_definition_id)
+                    .await?;
+                let rules = body.as_deref().and_then(policy::parse_role_policy);
+                Ok::<_, PimError>((role.favorites_key(), rules))
+            };
+            tasks.spawn(fetch_policy.in_current_span());
+        }
+
+        let mut policies = HashMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(Ok((role_key, Some(rules)))) => {
+                    policies.insert(role_key, rules);
+                }
+                Ok(Ok((role_key, None))) => debug!("No activation rules for {}", role_key),
+                Ok(Err(PimError::Unauthorized)) => return Err(PimError::Unauthorized),
+                Ok(Err(e)) => warn!("Failed to fetch role policy: {}", e),
+                Err(e) => warn!("Role policy task failed: {}", e),
+            }
+        }
+        Ok(policies)
+    }
+
+    /// Get the `roleManagementPolicyAssignments` of a role at `scope`, or
+    /// `None` if ARM refused them.
+    async fn get_role_policy(
+        &self,
+        access_token: &str,
+        scope: &Scope,
+        role_definition_id: &str,
+    ) -> Result<Option<String>, PimError> {
+        check_audience(access_token, Audience::Management)?;
+        let filter = format!("roleDefinitionId eq '{}'", role_definition_id);
+        let url = scope.url(
+            MANAGEMENT_BASE_URL,
+            &format!(
+                "/providers/Microsoft.Authorization/roleManagementPolicyAssignments?api-version={}&$filter={}",
+                self.api_versions.pim,
+                urlencoding::encode(&filter)
+            ),
+        );
+
+        let response = self
+            .http_client
+            .get(&url)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(PimError::Network)?;
+
+        match response.status().as_u16() {
+            200 => Ok(Some(response.text().await.map_err(PimError::Network)?)),
+            401 => Err(PimError::Unauthorized),
+            status => {
+                warn!("Failed to fetch role policy: HTTP {}", status);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Subscriptions that refused a query and answered none, e.g. because the
+/// user has no PIM access there but a group they are in has.
+fn denied_subscriptions<'a>(
+    subscriptions: &'a [Subscription],
+    answered: &[bool],
+    refused: &[bool],
+) -> Vec<&'a Subscription> {
+    subscriptions
+        .iter()
+        .zip(answered.iter().zip(refused))
+        .filter(|(_, (&answered, &refused))| refused && !answered)
+        .map(|(sub, _)| sub)
+        .collect()
+}
+
+/// Path (relative to ARM) listing a principal's role eligibilities in a subscription.
+///
+/// The filter is encoded here, since batch requests send the path as is.
+fn eligibility_path(versions: &ApiVersions, subscription_id: &str, principal_id: &str) -> String {
+    let filter = format!("principalId eq '{}'", principal_id);
+    format!(
+        "/subscriptions/{}/providers/Microsoft.Authorization/roleEligibilityScheduleInstances?api-version={}&$filter={}",
+        subscription_id,
+        versions.pim,
+        urlencoding::encode(&filter)
+    )
+}
+
+/// Build the URLs to try when resolving a role definition, most specific first.
+///
+/// The ID's own scope comes first, then the scope the role is assigned at,
+/// then the tenant root (where built-in definitions always resolve).
+fn role_definition_urls(
+    versions: &ApiVersions,
+    role_definition_id: &str,
+    scope: &Scope,
+) -> Vec<String> {
+    let definition = role_definition_id
+        .rsplit('/')
+        .next()
+        .unwrap_or(role_definition_id);
+    let path = format!(
+        "/providers/Microsoft.Authorization/roleDefinitions/{}?api-version={}",
+        definition, versions.role_definitions
+    );
+
+    let mut urls = vec![format!(
+        "{}{}?api-version={}",
+        MANAGEMENT_BASE_URL, role_definition_id, versions.role_definitions
+    )];
+    for url in [
+        scope.url(MANAGEMENT_BASE_URL, &path),
+        Scope::Tenant.url(MANAGEMENT_BASE_URL, &path),
+    ] {
+        if !urls.iter().any(|u| u.eq_ignore_ascii_case(&url)) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Build the role assignment schedule request URL for activating a role at `scope`.
+fn activation_url(versions: &ApiVersions, scope: &Scope, request_id: &str) -> String {
+    scope.url(
+        MANAGEMENT_BASE_URL,
+        &format!(
+            "/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/{}?api-version={}",
+            request_id, versions.pim
+        ),
+    )
+}
+
+/// Build the validation URL for a role assignment schedule request at `scope`.
+fn validation_url(versions: &ApiVersions, scope: &Scope, request_id: &str) -> String {
+    scope.url(
+        MANAGEMENT_BASE_URL,
+        &format!(
+            "/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/{}/validate?api-version={}",
+            request_id, versions.pim
+        ),
+    )
+}
+
+/// Build the schedule request body for activating a role from `start_time`.
+fn activation_body(
+    request: &ActivationRequest,
+    start_time: chrono::DateTime<Utc>,
+    request_type: &str,
+) -> ActivationRequestBody {
+    ActivationRequestBody {
+        properties: ActivationProperties {
+            principal_id: request.eligible_role.principal_id.clone(),
+            role_definition_id: request.eligible_role.role_definition_id.clone(),
+            request_type: request_type.to_string(),
+            justification: request.justification.clone(),
+            linked_role_eligibility_schedule_id: Some(request.eligible_role.id.clone()),
+            ticket_info: request
+                .ticket_number
+                .clone()
+                .map(|ticket_number| TicketInfo {
+                    ticket_number,
+                    ticket_system: TICKET_SYSTEM.to_string(),
+                }),
+            schedule_info: ScheduleInfo {
+                start_date_time: start_time.to_rfc3339(),
+                expiration: Expiration {
+                    expiration_type: "AfterDuration".to_string(),
+                    duration: format!("PT{}M", request.duration_minutes),
+                },
+            },
+        },
+    }
+}
+
+// --- API Response Types ---
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionListResponse {
+    value: Vec<SubscriptionItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionItem {
+    #[serde(rename = "subscriptionId")]
+    subscription_id: String,
+    #[serde(rename = "displayName")]
     display_name: String,
     state: String,
 }
@@ -524,6 +1757,23 @@ struct RoleEligibilityProperties {
     #[serde(rename = "principalId")]
     principal_id: String,
     scope: String,
+    #[serde(rename = "endDateTime")]
+    end_date_time: Option<chrono::DateTime<Utc>>,
+    #[serde(rename = "expandedProperties")]
+    expanded_properties: Option<EligibilityExpandedProperties>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EligibilityExpandedProperties {
+    principal: Option<ExpandedPrincipal>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExpandedPrincipal {
+    #[serde(rename = "displayName", default)]
+    display_name: String,
+    #[serde(rename = "type", default)]
+    principal_type: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -553,6 +1803,15 @@ struct RoleAssignmentProperties {
     #[serde(rename = "roleDefinitionId")]
     role_definition_id: String,
     scope: String,
+    #[serde(rename = "principalId")]
+    principal_id: Option<String>,
+    #[serde(rename = "principalType")]
+    principal_type: Option<String>,
+    /// "Activated" for PIM activations, "Assigned" for permanent assignments.
+    #[serde(rename = "assignmentType")]
+    assignment_type: Option<String>,
+    #[serde(rename = "expandedProperties")]
+    expanded_properties: Option<ExpandedProperties>,
     #[serde(rename = "startDateTime")]
     start_date_time: Option<chrono::DateTime<Utc>>,
     #[serde(rename = "endDateTime")]
@@ -561,6 +1820,152 @@ struct RoleAssignmentProperties {
     role_assignment_schedule_id: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ExpandedProperties {
+    principal: Option<ExpandedObject>,
+    #[serde(rename = "roleDefinition")]
+    role_definition: Option<ExpandedObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExpandedObject {
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+}
+
+/// The activated assignments in a `roleAssignmentScheduleInstances` list of
+/// `subscription_name`, with empty role names where ARM didn't expand them.
+fn tenant_assignments_from_list(
+    list: RoleAssignmentListResponse,
+    subscription_name: &str,
+) -> Vec<TenantAssignment> {
+    list.value
+        .into_iter()
+        .filter(|item| item.properties.assignment_type.as_deref() == Some("Activated"))
+        .filter_map(|item| {
+            let properties = item.properties;
+            let end_time = properties.end_date_time?;
+            let scope: Scope = match properties.scope.parse() {
+                Ok(scope) => scope,
+                Err(e) => {
+                    warn!("Skipping assignment {}: {}", item.id, e);
+                    return None;
+                }
+            };
+            let expanded = properties.expanded_properties;
+            let display_name = |object: Option<ExpandedObject>| {
+                object
+                    .and_then(|o| o.display_name)
+                    .filter(|name| !name.is_empty())
+            };
+            let (principal, role) = match expanded {
+                Some(expanded) => (
+                    display_name(expanded.principal),
+                    display_name(expanded.role_definition),
+                ),
+                None => (None, None),
+            };
+            Some(TenantAssignment {
+                id: item.id,
+                principal_name: principal
+                    .or(properties.principal_id)
+                    .unwrap_or_else(|| "Unknown principal".to_string()),
+                principal_type: properties.principal_type.unwrap_or_default(),
+                role_definition_id: properties.role_definition_id,
+                role_name: role.unwrap_or_default(),
+                subscription_name: scope.display_name(subscription_name),
+                scope,
+                end_time,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleResponse {
+    properties: ScheduleProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleProperties {
+    #[serde(rename = "roleAssignmentScheduleRequestId")]
+    role_assignment_schedule_request_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequestResponse {
+    properties: ScheduleRequestProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleRequestProperties {
+    justification: Option<String>,
+    status: Option<String>,
+}
+
+/// Where a schedule request is, by its `status`.
+#[derive(Debug, PartialEq, Eq)]
+enum ProvisioningState {
+    Provisioned,
+    Pending,
+    AwaitingApproval,
+    /// Denied or failed, with the reason to show.
+    Failed(String),
+}
+
+fn provisioning_state(status: &str) -> ProvisioningState {
+    match status {
+        // No status is read as provisioned, as before polling; a request
+        // with a later start only gets a schedule for now
+        "" | "Provisioned" | "ScheduleCreated" => ProvisioningState::Provisioned,
+        "PendingApproval" | "PendingApprovalProvisioning" | "PendingAdminDecision" => {
+            ProvisioningState::AwaitingApproval
+        }
+        "Denied"
+        | "AdminDenied"
+        | "Failed"
+        | "FailedAsResourceIsLocked"
+        | "Canceled"
+        | "Revoked"
+        | "TimedOut"
+        | "Invalid" => ProvisioningState::Failed(describe_status(status)),
+        _ => ProvisioningState::Pending,
+    }
+}
+
+/// Whether a schedule request in `status` is provisioned.
+///
+/// Requests that are denied, failed or waiting for an approver are errors.
+fn check_provisioning(status: &str, request_id: &str) -> Result<bool, PimError> {
+    match provisioning_state(status) {
+        ProvisioningState::Provisioned => Ok(true),
+        ProvisioningState::AwaitingApproval => {
+            info!("Activation request is {}, waiting for approval", status);
+            Err(PimError::ApprovalRequired(request_id.to_string()))
+        }
+        ProvisioningState::Failed(reason) => {
+            error!("Activation request ended as {}", status);
+            Err(PimError::ProvisioningFailed(reason))
+        }
+        ProvisioningState::Pending => {
+            debug!("Activation request is {}", status);
+            Ok(false)
+        }
+    }
+}
+
+/// A status like "FailedAsResourceIsLocked" as "failed as resource is locked".
+fn describe_status(status: &str) -> String {
+    let mut text = String::new();
+    for c in status.chars() {
+        if c.is_uppercase() && !text.is_empty() {
+            text.push(' ');
+        }
+        text.extend(c.to_lowercase());
+    }
+    text
+}
+
 // --- Request Body Types ---
 
 #[derive(Debug, Serialize)]
@@ -580,10 +1985,21 @@ struct ActivationProperties {
     #[serde(rename = "linkedRoleEligibilityScheduleId")]
     #[serde(skip_serializing_if = "Option::is_none")]
     linked_role_eligibility_schedule_id: Option<String>,
+    #[serde(rename = "ticketInfo")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ticket_info: Option<TicketInfo>,
     #[serde(rename = "scheduleInfo")]
     schedule_info: ScheduleInfo,
 }
 
+#[derive(Debug, Serialize)]
+struct TicketInfo {
+    #[serde(rename = "ticketNumber")]
+    ticket_number: String,
+    #[serde(rename = "ticketSystem")]
+    ticket_system: String,
+}
+
 #[derive(Debug, Serialize)]
 struct ScheduleInfo {
     #[serde(rename = "startDateTime")]
@@ -598,7 +2014,192 @@ struct Expiration {
     duration: String,
 }
 
+#[derive(Debug, Serialize)]
+struct DeactivationRequestBody {
+    properties: DeactivationProperties,
+}
+
+#[derive(Debug, Serialize)]
+struct DeactivationProperties {
+    #[serde(rename = "principalId")]
+    principal_id: String,
+    #[serde(rename = "roleDefinitionId")]
+    role_definition_id: String,
+    #[serde(rename = "requestType")]
+    request_type: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct ActivationResponseBody {
     id: String,
+    properties: Option<ActivationResponseProperties>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActivationResponseProperties {
+    status: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_parses_to_golden, fixture};
+
+    #[test]
+    fn test_golden_arm_responses() {
+        assert_parses_to_golden::<SubscriptionListResponse>("arm/subscriptions.json");
+        // nextLink is not followed yet, so only the first page of eligibilities is read
+        assert_parses_to_golden::<RoleEligibilityListResponse>(
+            "arm/role_eligibility_schedule_instances.json",
+        );
+        assert_parses_to_golden::<RoleDefinitionResponse>("arm/role_definition.json");
+        assert_parses_to_golden::<RoleAssignmentListResponse>(
+            "arm/role_assignment_schedule_instances.json",
+        );
+        assert_parses_to_golden::<ScheduleResponse>("arm/role_assignment_schedule.json");
+        assert_parses_to_golden::<ScheduleRequestResponse>(
+            "arm/role_assignment_schedule_request.json",
+        );
+        assert_parses_to_golden::<ActivationResponseBody>("arm/activation_response.json");
+    }
+
+    #[test]
+    fn test_tenant_assignments_from_list() {
+        let list: RoleAssignmentListResponse =
+            serde_json::from_str(&fixture("arm/role_assignment_schedule_instances.json")).unwrap();
+        let assignments = tenant_assignments_from_list(list, "contoso-prod-001");
+        // The permanent assignment is left out
+        assert_eq!(assignments.len(), 1);
+        let assignment = &assignments[0];
+        assert_eq!(assignment.principal_name, "Alex Wilber");
+        assert_eq!(assignment.principal_type, "User");
+        assert_eq!(assignment.role_name, "Contributor");
+        assert_eq!(assignment.subscription_name, "contoso-prod-001");
+
+        let unexpanded = r#"{"value":[{"id":"/a/1","properties":{
+            "roleDefinitionId":"/providers/Microsoft.Authorization/roleDefinitions/r1",
+            "scope":"/subscriptions/sub-1","principalId":"p1","assignmentType":"Activated",
+            "endDateTime":"2024-03-01T13:00:00Z"}}]}"#;
+        let assignments =
+            tenant_assignments_from_list(serde_json::from_str(unexpanded).unwrap(), "dev");
+        assert_eq!(assignments[0].principal_name, "p1");
+        assert_eq!(assignments[0].role_name, "");
+    }
+
+    #[test]
+    fn test_provisioning_state() {
+        assert_eq!(
+            provisioning_state("Provisioned"),
+            ProvisioningState::Provisioned
+        );
+        for status in [
+            "Accepted",
+            "PendingEvaluation",
+            "Granted",
+            "PendingProvisioning",
+        ] {
+            assert_eq!(provisioning_state(status), ProvisioningState::Pending);
+        }
+        assert_eq!(
+            provisioning_state("PendingApproval"),
+            ProvisioningState::AwaitingApproval
+        );
+        assert_eq!(
+            provisioning_state("FailedAsResourceIsLocked"),
+            ProvisioningState::Failed("failed as resource is locked".to_string())
+        );
+        assert_eq!(
+            provisioning_state("Denied"),
+            ProvisioningState::Failed("denied".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_provisioning() {
+        assert!(check_provisioning("Provisioned", "req-1").unwrap());
+        assert!(!check_provisioning("PendingProvisioning", "req-1").unwrap());
+        assert!(matches!(
+            check_provisioning("PendingApproval", "req-1"),
+            Err(PimError::ApprovalRequired(id)) if id == "req-1"
+        ));
+        assert!(matches!(
+            check_provisioning("Denied", "req-1"),
+            Err(PimError::ProvisioningFailed(reason)) if reason == "denied"
+        ));
+    }
+
+    #[test]
+    fn test_activation_url_subscription_scope() {
+        assert_eq!(
+            activation_url(&ApiVersions::default(), &"/subscriptions/sub-id".parse().unwrap(), "req-id"),
+            "https://management.azure.com/subscriptions/sub-id/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/req-id?api-version=2020-10-01"
+        );
+    }
+
+    #[test]
+    fn test_eligibility_path_encodes_filter() {
+        assert_eq!(
+            eligibility_path(&ApiVersions::default(), "sub-id", "user-1"),
+            "/subscriptions/sub-id/providers/Microsoft.Authorization/roleEligibilityScheduleInstances?api-version=2020-10-01&$filter=principalId%20eq%20%27user-1%27"
+        );
+    }
+
+    #[test]
+    fn test_validation_url() {
+        assert_eq!(
+            validation_url(&ApiVersions::default(), &"/subscriptions/sub-id".parse().unwrap(), "req-id"),
+            "https://management.azure.com/subscriptions/sub-id/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/req-id/validate?api-version=2020-10-01"
+        );
+    }
+
+    #[test]
+    fn test_activation_url_root_scope() {
+        assert_eq!(
+            activation_url(&ApiVersions::default(), &Scope::Tenant, "req-id"),
+            "https://management.azure.com/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/req-id?api-version=2020-10-01"
+        );
+    }
+
+    #[test]
+    fn test_role_definition_urls_fall_back_to_assignment_scope() {
+        let urls = role_definition_urls(
+            &ApiVersions::default(),
+            "/subscriptions/sub-id/providers/Microsoft.Authorization/roleDefinitions/def-id",
+            &"/providers/Microsoft.Management/managementGroups/platform"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            urls,
+            vec![
+                "https://management.azure.com/subscriptions/sub-id/providers/Microsoft.Authorization/roleDefinitions/def-id?api-version=2022-04-01",
+                "https://management.azure.com/providers/Microsoft.Management/managementGroups/platform/providers/Microsoft.Authorization/roleDefinitions/def-id?api-version=2022-04-01",
+                "https://management.azure.com/providers/Microsoft.Authorization/roleDefinitions/def-id?api-version=2022-04-01",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_role_definition_urls_deduplicate() {
+        let urls = role_definition_urls(
+            &ApiVersions::default(),
+            "/providers/Microsoft.Authorization/roleDefinitions/def-id",
+            &Scope::Tenant,
+        );
+        assert_eq!(urls.len(), 1);
+    }
+
+    #[test]
+    fn test_activation_url_management_group_scope() {
+        assert_eq!(
+            activation_url(
+                &ApiVersions::default(),
+                &"/providers/Microsoft.Management/managementGroups/platform"
+                    .parse()
+                    .unwrap(),
+                "req-id"
+            ),
+            "https://management.azure.com/providers/Microsoft.Management/managementGroups/platform/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/req-id?api-version=2020-10-01"
+        );
+    }
 }
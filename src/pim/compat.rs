@@ -0,0 +1,111 @@
+//! API version compatibility.
+//!
+//! ARM answers a retired `api-version` with 400 `InvalidApiVersionParameter`,
+//! listing the versions it supports. At startup each configured version is
+//! tried once, so a retired one is reported with a version to set instead of
+//! every call failing with "Unexpected response from Azure".
+
+use super::policy::ErrorResponse;
+
+/// Error code of a request with an unsupported `api-version`.
+pub const INVALID_API_VERSION: &str = "InvalidApiVersionParameter";
+
+/// A configured API version ARM no longer accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiVersionProblem {
+    /// Name of the version in config.toml, e.g. "pim".
+    pub name: &'static str,
+    pub version: String,
+    /// The newest version ARM listed as supported, if it listed any.
+    pub suggested: Option<String>,
+}
+
+impl ApiVersionProblem {
+    /// What to do about it, for the log and a notification.
+    pub fn advice(&self) -> String {
+        let fix = match &self.suggested {
+            Some(version) => format!(
+                "Set AZURE_PIM_API_VERSIONS=\"{}={}\" or update the app.",
+                self.name, version
+            ),
+            None => "Update the app.".to_string(),
+        };
+        format!(
+            "Azure no longer accepts api-version {} for {}. {}",
+            self.version, self.name, fix
+        )
+    }
+}
+
+/// Check an ARM error response for a rejected `api-version`.
+///
+/// Returns `None` if the body is another error, otherwise the newest version
+/// listed as supported, preferring stable over preview versions.
+pub fn rejected_api_version(body: &str) -> Option<Option<String>> {
+    let response = serde_json::from_str::<ErrorResponse>(body).ok()?;
+    if response.error.code != INVALID_API_VERSION {
+        return None;
+    }
+    Some(newest_supported_version(&response.error.message))
+}
+
+/// The newest version in a message like "... The supported versions are
+/// '2022-04-01,2020-10-01-preview'. ...".
+fn newest_supported_version(message: &str) -> Option<String> {
+    let start = message.find("supported versions are '")? + "supported versions are '".len();
+    let list = &message[start..];
+    let list = &list[..list.find('\'')?];
+    let versions: Vec<&str> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect();
+    // Dates sort as text
+    versions
+        .iter()
+        .filter(|v| !v.contains("preview"))
+        .max()
+        .or_else(|| versions.iter().max())
+        .map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejected_api_version() {
+        let body = r#"{"error":{"code":"InvalidApiVersionParameter","message":"The api-version '2019-01-01' is invalid. The supported versions are '2022-04-01-preview,2022-04-01,2020-10-01,2024-02-01-preview'."}}"#;
+        assert_eq!(
+            rejected_api_version(body),
+            Some(Some("2022-04-01".to_string()))
+        );
+
+        let only_preview = r#"{"error":{"code":"InvalidApiVersionParameter","message":"The supported versions are '2021-01-01-preview,2023-01-01-preview'."}}"#;
+        assert_eq!(
+            rejected_api_version(only_preview),
+            Some(Some("2023-01-01-preview".to_string()))
+        );
+
+        let unlisted = r#"{"error":{"code":"InvalidApiVersionParameter","message":"Invalid."}}"#;
+        assert_eq!(rejected_api_version(unlisted), Some(None));
+
+        let other = r#"{"error":{"code":"InvalidScope","message":"Bad scope."}}"#;
+        assert_eq!(rejected_api_version(other), None);
+        assert_eq!(rejected_api_version("not json"), None);
+    }
+
+    #[test]
+    fn test_advice() {
+        let problem = ApiVersionProblem {
+            name: "pim",
+            version: "2020-10-01".to_string(),
+            suggested: Some("2022-04-01".to_string()),
+        };
+        assert_eq!(
+            problem.advice(),
+            "Azure no longer accepts api-version 2020-10-01 for pim. \
+             Set AZURE_PIM_API_VERSIONS=\"pim=2022-04-01\" or update the app."
+        );
+    }
+}
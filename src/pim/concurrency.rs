@@ -0,0 +1,138 @@
+//! Concurrency and ordering of activations requested together.
+//!
+//! ARM handles parallel writes at the same scope poorly, so when several
+//! roles are activated or deactivated at once (an elevation plan, "Deactivate
+//! All Roles"), requests at the same scope are sent one after another and at most
+//! [`MAX_CONCURRENT_ACTIVATIONS`] requests are in flight. Results carry the
+//! index of their request, so callers can report them in the order the roles
+//! were requested rather than the order they finished in.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, Semaphore};
+
+use super::models::{ActivationRequest, ActiveAssignment};
+use crate::api::PimApi;
+use crate::error::PimError;
+
+/// Most activation requests in flight at once.
+pub const MAX_CONCURRENT_ACTIVATIONS: usize = 3;
+
+/// Result of the request at an index.
+pub type IndexedResult = (usize, Result<ActiveAssignment, PimError>);
+
+/// Result of the deactivation at an index.
+pub type IndexedDeactivation = (usize, Result<(), PimError>);
+
+/// Indices of `scopes` grouped by equal scope. Groups are in order of their
+/// first index, and indices within a group are ascending.
+pub fn group_by_scope<T: PartialEq>(scopes: &[T]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (index, scope) in scopes.iter().enumerate() {
+        match groups.iter_mut().find(|group| scopes[group[0]] == *scope) {
+            Some(group) => group.push(index),
+            None => groups.push(vec![index]),
+        }
+    }
+    groups
+}
+
+/// Submit `requests`, same-scope requests one at a time and at most
+/// [`MAX_CONCURRENT_ACTIVATIONS`] overall. Must be called within the Tokio runtime.
+///
+/// Results arrive as requests finish; the channel closes after the last one.
+pub fn activate_all(
+    client: Arc<dyn PimApi>,
+    access_token: String,
+    requests: Vec<ActivationRequest>,
+) -> mpsc::UnboundedReceiver<IndexedResult> {
+    let scopes: Vec<_> = requests
+        .iter()
+        .map(|r| r.eligible_role.scope.clone())
+        .collect();
+    submit_by_scope(&scopes, requests, move |request| {
+        let client = Arc::clone(&client);
+        let access_token = access_token.clone();
+        async move { client.activate_role(&access_token, request).await }
+    })
+}
+
+/// Deactivate `assignments` of `principal_id`, with the same ordering and
+/// limits as [`activate_all`]. Must be called within the Tokio runtime.
+pub fn deactivate_all(
+    client: Arc<dyn PimApi>,
+    access_token: String,
+    principal_id: String,
+    assignments: Vec<ActiveAssignment>,
+) -> mpsc::UnboundedReceiver<IndexedDeactivation> {
+    let scopes: Vec<_> = assignments.iter().map(|a| a.scope.clone()).collect();
+    submit_by_scope(&scopes, assignments, move |assignment| {
+        let client = Arc::clone(&client);
+        let access_token = access_token.clone();
+        let principal_id = principal_id.clone();
+        async move {
+            client
+                .deactivate_role(&access_token, &assignment, &principal_id)
+                .await
+        }
+    })
+}
+
+/// Run `submit` on each item, one scope group per task, sending results by index.
+fn submit_by_scope<S, T, R, F, Fut>(
+    scopes: &[S],
+    items: Vec<T>,
+    submit: F,
+) -> mpsc::UnboundedReceiver<(usize, R)>
+where
+    S: PartialEq,
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_ACTIVATIONS));
+    let submit = Arc::new(submit);
+    let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+
+    for group in group_by_scope(scopes) {
+        let queue: Vec<(usize, T)> = group
+            .into_iter()
+            .filter_map(|index| Some((index, items[index].take()?)))
+            .collect();
+        let submit = Arc::clone(&submit);
+        let permits = Arc::clone(&permits);
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            for (index, item) in queue {
+                let result = match permits.acquire().await {
+                    Ok(_permit) => submit(item).await,
+                    // The semaphore is never closed
+                    Err(_) => return,
+                };
+                if tx.send((index, result)).is_err() {
+                    // Nobody waits for the rest
+                    return;
+                }
+            }
+        });
+    }
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_scope() {
+        let scopes = ["sub-a", "sub-b", "sub-a", "sub-c", "sub-b", "sub-a"];
+        assert_eq!(
+            group_by_scope(&scopes),
+            vec![vec![0, 2, 5], vec![1, 4], vec![3]]
+        );
+        assert!(group_by_scope::<&str>(&[]).is_empty());
+    }
+}
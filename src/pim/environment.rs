@@ -0,0 +1,120 @@
+//! Environments of subscriptions, from their names.
+//!
+//! Subscriptions named alike ("vipps-prod-001", "vipps-test-001") are easy to
+//! mix up. Name patterns in settings.json map subscriptions to an environment;
+//! roles in production are flagged in the menu and always confirmed before
+//! activation. The first matching pattern wins, so "*nonprod*" can come
+//! before "*prod*".
+
+use serde::{Deserialize, Serialize};
+
+/// Environment a subscription belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Prod,
+    Test,
+    Dev,
+}
+
+impl Environment {
+    /// Label shown next to roles, e.g. "PROD".
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Prod => "PROD",
+            Self::Test => "TEST",
+            Self::Dev => "DEV",
+        }
+    }
+}
+
+/// Subscriptions whose name matches `pattern` belong to `environment`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentRule {
+    /// Case-insensitive name pattern; `*` matches any text.
+    pub pattern: String,
+    pub environment: Environment,
+}
+
+impl EnvironmentRule {
+    fn new(pattern: &str, environment: Environment) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            environment,
+        }
+    }
+}
+
+/// Patterns used until settings.json lists its own.
+pub fn default_rules() -> Vec<EnvironmentRule> {
+    vec![
+        EnvironmentRule::new("*nonprod*", Environment::Test),
+        EnvironmentRule::new("*prod*", Environment::Prod),
+        EnvironmentRule::new("*test*", Environment::Test),
+        EnvironmentRule::new("*dev*", Environment::Dev),
+    ]
+}
+
+/// The environment of the first rule matching `subscription_name`.
+pub fn environment_of(subscription_name: &str, rules: &[EnvironmentRule]) -> Option<Environment> {
+    rules
+        .iter()
+        .find(|rule| glob_matches(&rule.pattern, subscription_name))
+        .map(|rule| rule.environment)
+}
+
+/// Whether `text` matches `pattern`, ignoring case; `*` matches any text.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = parts.next().and_then(|first| text.strip_prefix(first)) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    // Without a `*`, the whole text must match
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*prod*", "vipps-PROD-001"));
+        assert!(glob_matches("vipps-*-001", "vipps-prod-001"));
+        assert!(glob_matches("vipps-prod-001", "Vipps-Prod-001"));
+        assert!(!glob_matches("vipps-prod", "vipps-prod-001"));
+        assert!(!glob_matches("*prod", "vipps-prod-001"));
+        assert!(!glob_matches("*a*a*", "a"));
+    }
+
+    #[test]
+    fn test_environment_of() {
+        let rules = default_rules();
+        assert_eq!(
+            environment_of("vipps-prod-001", &rules),
+            Some(Environment::Prod)
+        );
+        assert_eq!(
+            environment_of("vipps-nonprod-001", &rules),
+            Some(Environment::Test)
+        );
+        assert_eq!(
+            environment_of("vipps-dev-001", &rules),
+            Some(Environment::Dev)
+        );
+        assert_eq!(environment_of("vipps-shared", &rules), None);
+        assert_eq!(environment_of("vipps-prod-001", &[]), None);
+    }
+}
@@ -4,21 +4,57 @@
 //! - Fetching eligible PIM roles across Azure subscriptions
 //! - Activating roles with justification
 //! - Managing active role assignments
-//! - Caching and persistence of favorites/settings
+//! - Tracking requests pending approval
+//! - Caching and persistence of favorites/settings and active assignments
 
 // Allow dead code and unused imports in PIM module - full integration pending
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
+pub mod approvals;
+pub mod availability;
+pub mod batch;
+pub mod builtin_roles;
 pub mod cache;
 pub mod client;
+pub mod compat;
+pub mod concurrency;
+pub mod environment;
 pub mod models;
+pub mod notes;
+pub mod policy;
+pub mod reminders;
+pub mod schedule;
+pub mod scope;
 pub mod settings;
+pub mod store;
+pub mod subscriptions;
 
+pub use approvals::{
+    ApprovalCounts, ApprovalRequest, MyRequests, PendingRequest, ReviewResult, ScheduledActivation,
+};
+pub use availability::PimAvailability;
 pub use cache::PimCache;
-pub use client::PimClient;
+pub use client::{PimClient, SubmittedActivation};
 pub use models::{
-    ActivationRequest, ActiveAssignment, EligibleRole, JustificationPreset, PimApiStatus,
-    PimSettings, Subscription,
+    active_subscriptions, eligibility_report, eligible_subscriptions, label_group_grants,
+    merge_duplicate_grants, reconcile_polled_assignments, ActivationOptions, ActivationRequest,
+    ActiveAssignment, ElevationPlan, EligibleRole, ExpiryUrgency, Grant, GroupLookup, GroupNames,
+    JustificationPreset, PimApiStatus, PimSettings, PlanDraft, PlanProgress, Subscription,
+    TenantAssignment, ACTIVATION_DURATION_CHOICES, EXPIRY_EXTENSION_MINUTES, MORE_TIME_CHOICES,
+};
+pub use notes::NotesStore;
+pub use reminders::DeactivationReminders;
+pub use schedule::ScheduledDeactivations;
+pub use scope::Scope;
+pub use settings::{delete_pim_settings, load_pim_settings, save_pim_settings};
+pub use store::{
+    account_caches_exist, data_dir, delete_active_assignments, delete_cache_owner,
+    delete_pim_availability, delete_roles_cache, delete_scheduled_deactivations,
+    delete_session_notes, delete_subscription_names, load_active_assignments, load_cache_owner,
+    load_pim_availability, load_roles_cache, load_scheduled_deactivations, load_session_notes,
+    load_subscription_names, save_active_assignments, save_cache_owner, save_pim_availability,
+    save_roles_cache, save_scheduled_deactivations, save_session_notes, save_subscription_names,
+    set_data_dir, CacheOwner,
 };
-pub use settings::{load_pim_settings, save_pim_settings};
+pub use subscriptions::SubscriptionNames;
@@ -1,10 +1,40 @@
 //! PIM data models for Azure Privileged Identity Management.
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::builtin_roles::role_includes;
+use super::scope::Scope;
+use crate::auth::challenge::StepUp;
+use crate::clock::Clock;
+use crate::duration::{self, Style};
+
+/// Display names of the user's groups, keyed by group object ID.
+pub type GroupNames = HashMap<String, String>;
+
+/// Extra minutes offered when an active role needs more time.
+pub const MORE_TIME_CHOICES: [u32; 3] = [20, 30, 60];
+
+/// Activation durations offered per role, in minutes.
+pub const ACTIVATION_DURATION_CHOICES: [u32; 5] = [30, 60, 120, 240, 480];
+
+/// Longest custom role label kept, in characters.
+pub const MAX_ROLE_LABEL_CHARS: usize = 40;
+
+/// Extra minutes offered when a role is about to expire.
+pub const EXPIRY_EXTENSION_MINUTES: u32 = 60;
+
+/// Justification template of plans saved without one.
+const PLAN_JUSTIFICATION: &str = "{plan}";
+
+/// How long after its start an assignment missing from a poll is kept, since
+/// ARM may not list a fresh activation yet.
+const POLL_GRACE_MINUTES: i64 = 5;
+
 /// Represents an Azure subscription-level role the user is eligible for.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EligibleRole {
     /// Unique identifier for this role assignment eligibility.
     /// Format: /subscriptions/{sub}/providers/.../roleEligibilityScheduleInstances/{id}
@@ -23,11 +53,32 @@ pub struct EligibleRole {
     /// Subscription display name.
     pub subscription_name: String,
 
-    /// Full scope path (e.g., "/subscriptions/{id}").
-    pub scope: String,
+    /// Scope the role is eligible at (e.g., "/subscriptions/{id}").
+    pub scope: Scope,
 
     /// Principal ID (user's Azure AD object ID).
     pub principal_id: String,
+
+    /// When the eligibility ends, or `None` if it is permanent.
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// Display name of the group the eligibility is granted through, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granted_via: Option<String>,
+
+    /// Other principals the same role is eligible through, e.g. a second group.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_grants: Vec<Grant>,
+}
+
+/// A principal an eligibility is granted to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grant {
+    pub principal_id: String,
+    /// Display name of the group, or `None` for a direct assignment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granted_via: Option<String>,
 }
 
 impl EligibleRole {
@@ -36,14 +87,189 @@ impl EligibleRole {
         format!("{} - {}", self.subscription_name, self.role_name)
     }
 
+    /// Menu title with the role name first, so type-select matches it:
+    /// "role_name - subscription_name".
+    pub fn menu_title(&self) -> String {
+        format!("{} - {}", self.role_name, self.subscription_name)
+    }
+
+    /// Role metadata shown on hover: scope, definition, eligibility end, and granting principals.
+    pub fn tooltip_text(&self) -> String {
+        let eligible_until = match self.end_time {
+            Some(end) => end.format("%Y-%m-%d %H:%M UTC").to_string(),
+            None => "Permanent".to_string(),
+        };
+        format!(
+            "Scope: {} ({})\nRole definition: {}\nEligible until: {}\nEligible via: {}",
+            self.scope,
+            self.scope.level(),
+            self.role_definition_id,
+            eligible_until,
+            self.eligible_via()
+        )
+    }
+
+    /// Every principal the role is eligible through, starting with the one activations use.
+    pub fn grants(&self) -> impl Iterator<Item = Grant> + '_ {
+        std::iter::once(Grant {
+            principal_id: self.principal_id.clone(),
+            granted_via: self.granted_via.clone(),
+        })
+        .chain(self.other_grants.iter().cloned())
+    }
+
+    /// Whether the role is eligible through more than one principal.
+    pub fn has_several_grants(&self) -> bool {
+        !self.other_grants.is_empty()
+    }
+
+    /// Who the role is granted to, e.g. "you, Group-Ops, Group-OnCall".
+    pub fn eligible_via(&self) -> String {
+        let names: Vec<String> = self
+            .grants()
+            .map(|grant| grant.granted_via.unwrap_or_else(|| "you".to_string()))
+            .collect();
+        names.join(", ")
+    }
+
+    /// Record that the role is also eligible through the principal of `other`,
+    /// the same role found under another principal.
+    ///
+    /// The eligibility lasts as long as the longest of its grants.
+    fn merge_grant(&mut self, other: EligibleRole) {
+        self.end_time = match (self.end_time, other.end_time) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            _ => None,
+        };
+        for grant in other.grants() {
+            if !self.grants().any(|g| g.principal_id == grant.principal_id) {
+                self.other_grants.push(grant);
+            }
+        }
+    }
+
     /// Unique key for favorites storage (stable identifier).
+    ///
+    /// Fully qualified by scope, so roles at management groups and in
+    /// subscriptions of different tenants don't collide.
     pub fn favorites_key(&self) -> String {
-        format!("{}:{}", self.subscription_id, self.role_definition_id)
+        role_key(&self.scope.to_string(), &self.role_definition_id)
+    }
+
+    /// Another active role that already grants this one: the same or a broader
+    /// built-in role at this scope or above it.
+    pub fn covered_by<'a>(&self, active: &'a [ActiveAssignment]) -> Option<&'a ActiveAssignment> {
+        let key = self.favorites_key();
+        active.iter().find(|a| {
+            a.role_key() != key
+                && a.scope.contains(&self.scope)
+                && role_includes(&a.role_name, &self.role_name)
+        })
     }
 }
 
+/// Merge roles found under several principals (the user and groups, or
+/// more than one group) into one role listing all of its grants.
+///
+/// Roles are the same if they share a role key; the first one found is kept.
+pub fn merge_duplicate_grants(roles: Vec<EligibleRole>) -> Vec<EligibleRole> {
+    let mut merged: Vec<EligibleRole> = Vec::with_capacity(roles.len());
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for role in roles {
+        match index.get(&role.favorites_key()) {
+            Some(&idx) => merged[idx].merge_grant(role),
+            None => {
+                index.insert(role.favorites_key(), merged.len());
+                merged.push(role);
+            }
+        }
+    }
+    merged
+}
+
+/// Name the granting groups of roles whose eligibility ARM returned without them.
+pub fn label_group_grants(roles: &mut [EligibleRole], group_names: &GroupNames) {
+    for role in roles.iter_mut() {
+        if role.granted_via.is_none() {
+            role.granted_via = group_names.get(&role.principal_id).cloned();
+        }
+        for grant in role
+            .other_grants
+            .iter_mut()
+            .filter(|g| g.granted_via.is_none())
+        {
+            grant.granted_via = group_names.get(&grant.principal_id).cloned();
+        }
+    }
+}
+
+/// Summary of where eligible roles come from, for the refresh log.
+///
+/// Each grant is counted, so a role eligible through several principals
+/// counts once per principal and is listed at the end, e.g. "12 eligible
+/// roles: 5 direct, 4 via Platform Admins, 4 via DBAs; Contributor - prod
+/// via you, DBAs".
+pub fn eligibility_report(roles: &[EligibleRole]) -> String {
+    let mut direct = 0;
+    let mut via_groups: BTreeMap<String, usize> = BTreeMap::new();
+    for grant in roles.iter().flat_map(EligibleRole::grants) {
+        match grant.granted_via {
+            Some(group) => *via_groups.entry(group).or_default() += 1,
+            None => direct += 1,
+        }
+    }
+
+    let mut report = format!("{} eligible roles: {} direct", roles.len(), direct);
+    for (group, count) in via_groups {
+        report.push_str(&format!(", {} via {}", count, group));
+    }
+    let duplicates: Vec<String> = roles
+        .iter()
+        .filter(|r| r.has_several_grants())
+        .map(|r| format!("{} via {}", r.menu_title(), r.eligible_via()))
+        .collect();
+    if !duplicates.is_empty() {
+        report.push_str(&format!("; {}", duplicates.join("; ")));
+    }
+    report
+}
+
+/// Build a role key from a scope and a role definition ID: `{scope}|{role definition GUID}`.
+///
+/// ARM IDs are case-insensitive, and the same definition can be referenced
+/// by a subscription-relative or tenant-level path, so both parts are normalized.
+pub(super) fn role_key(scope: &str, role_definition_id: &str) -> String {
+    let definition = role_definition_id
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(role_definition_id);
+    format!(
+        "{}|{}",
+        scope.trim_end_matches('/').to_ascii_lowercase(),
+        definition.to_ascii_lowercase()
+    )
+}
+
+/// Convert a legacy `subscription_id:role_definition_id` key to a role key.
+///
+/// Returns `None` for keys that are already migrated or can't be converted.
+fn migrate_legacy_role_key(key: &str) -> Option<String> {
+    if key.contains('|') {
+        return None;
+    }
+    let (subscription_id, role_definition_id) = key.split_once(':')?;
+    if subscription_id.is_empty() || role_definition_id.is_empty() {
+        return None;
+    }
+    Some(role_key(
+        &format!("/subscriptions/{}", subscription_id),
+        role_definition_id,
+    ))
+}
+
 /// Represents a currently active PIM role assignment.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ActiveAssignment {
     /// Assignment schedule instance ID.
     pub id: String,
@@ -60,8 +286,8 @@ pub struct ActiveAssignment {
     /// Subscription name.
     pub subscription_name: String,
 
-    /// Full scope.
-    pub scope: String,
+    /// Scope the role is active at.
+    pub scope: Scope,
 
     /// When the activation started.
     pub start_time: DateTime<Utc>,
@@ -79,25 +305,56 @@ pub struct ActiveAssignment {
 
 impl ActiveAssignment {
     /// Display text with time remaining.
-    pub fn display_text_with_time(&self) -> String {
-        let remaining = self.time_remaining();
-        let minutes = remaining.num_minutes();
-        let time_str = if minutes >= 60 {
-            format!("{} hr {} min left", minutes / 60, minutes % 60)
-        } else if minutes > 0 {
-            format!("{} min left", minutes)
-        } else {
+    pub fn display_text_with_time(&self, clock: &dyn Clock) -> String {
+        let time_str = if self.is_expired(clock) {
             "expired".to_string()
+        } else {
+            duration::left(self.time_remaining(clock))
         };
         format!(
             "{} - {}    {}",
-            self.subscription_name, self.role_name, time_str
+            self.role_name, self.subscription_name, time_str
         )
     }
 
+    /// Role name and time left for a compact status item, e.g. "Contributor 30m".
+    pub fn compact_text(&self, clock: &dyn Clock) -> String {
+        format!(
+            "{} {}",
+            self.role_name,
+            duration::format(self.time_remaining(clock), Style::Short)
+        )
+    }
+
+    /// When the role was activated and why, e.g. "Activated 40m ago: INC-1234".
+    pub fn activated_text(&self, clock: &dyn Clock) -> String {
+        let since = clock.now() - self.start_time;
+        let ago = if since.num_minutes() > 0 {
+            format!("Activated {}", duration::ago(since))
+        } else {
+            "Activated just now".to_string()
+        };
+        if self.justification.is_empty() {
+            ago
+        } else {
+            format!("{}: {}", ago, self.justification)
+        }
+    }
+
+    /// Minutes from the start of the activation to `extra_minutes` past its current end.
+    pub fn extended_duration_minutes(&self, extra_minutes: u32) -> u32 {
+        let current = (self.end_time - self.start_time).num_minutes().max(0);
+        current as u32 + extra_minutes
+    }
+
+    /// Key of the eligible role this assignment activates (see [`EligibleRole::favorites_key`]).
+    pub fn role_key(&self) -> String {
+        role_key(&self.scope.to_string(), &self.role_definition_id)
+    }
+
     /// Get time remaining until expiry.
-    pub fn time_remaining(&self) -> Duration {
-        let now = Utc::now();
+    pub fn time_remaining(&self, clock: &dyn Clock) -> Duration {
+        let now = clock.now();
         if self.end_time > now {
             self.end_time - now
         } else {
@@ -106,16 +363,56 @@ impl ActiveAssignment {
     }
 
     /// Check if expiring soon (within threshold minutes).
-    pub fn is_expiring_soon(&self, threshold_minutes: i64) -> bool {
-        self.time_remaining().num_minutes() <= threshold_minutes
+    pub fn is_expiring_soon(&self, threshold_minutes: i64, clock: &dyn Clock) -> bool {
+        self.time_remaining(clock).num_minutes() <= threshold_minutes
     }
 
     /// Check if this assignment has expired.
-    pub fn is_expired(&self) -> bool {
-        self.end_time <= Utc::now()
+    pub fn is_expired(&self, clock: &dyn Clock) -> bool {
+        self.end_time <= clock.now()
+    }
+
+    /// Fraction of the activation window remaining (0.0 to 1.0).
+    pub fn remaining_fraction(&self, clock: &dyn Clock) -> f64 {
+        let total = (self.end_time - self.start_time).num_seconds();
+        if total <= 0 {
+            return 0.0;
+        }
+        let remaining = self.time_remaining(clock).num_seconds() as f64 / total as f64;
+        remaining.clamp(0.0, 1.0)
+    }
+
+    /// Remaining time as a bar of filled and empty segments (e.g., "▰▰▰▱▱").
+    pub fn progress_bar(&self, segments: usize, clock: &dyn Clock) -> String {
+        // Round up so any time left shows at least one filled segment
+        let filled = (self.remaining_fraction(clock) * segments as f64).ceil() as usize;
+        let filled = filled.min(segments);
+        format!("{}{}", "▰".repeat(filled), "▱".repeat(segments - filled))
+    }
+
+    /// How close this assignment is to expiring.
+    pub fn urgency(&self, threshold_minutes: i64, clock: &dyn Clock) -> ExpiryUrgency {
+        if self.is_expiring_soon(threshold_minutes, clock) {
+            ExpiryUrgency::Critical
+        } else if self.remaining_fraction(clock) <= 0.5 {
+            ExpiryUrgency::Warning
+        } else {
+            ExpiryUrgency::Normal
+        }
     }
 }
 
+/// How close an active assignment or the access token is to expiring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryUrgency {
+    /// More than half of the activation window remains.
+    Normal,
+    /// Half or less of the activation window remains.
+    Warning,
+    /// Within the expiry warning threshold.
+    Critical,
+}
+
 /// Justification preset for quick activation.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct JustificationPreset {
@@ -153,7 +450,7 @@ impl JustificationPreset {
 }
 
 /// User's PIM preferences - persisted locally.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PimSettings {
     /// Default activation duration in minutes (default: 60).
     pub default_duration_minutes: u32,
@@ -167,8 +464,24 @@ pub struct PimSettings {
     /// Custom justification presets (user-defined).
     pub custom_presets: Vec<JustificationPreset>,
 
-    /// Favorite role keys (subscription_id:role_definition_id format).
+    /// Favorite role keys (`{scope}|{role definition GUID}` format).
     pub favorite_role_keys: Vec<String>,
+
+    /// Named bundles of roles activated together.
+    #[serde(default)]
+    pub elevation_plans: Vec<ElevationPlan>,
+
+    /// Roles activated without a confirmation (role key format, as favorites).
+    #[serde(default)]
+    pub unconfirmed_role_keys: Vec<String>,
+
+    /// Last-used activation duration in minutes, by role key.
+    #[serde(default)]
+    pub role_durations: BTreeMap<String, u32>,
+
+    /// Custom labels shown next to roles, e.g. "🔥 prod-payments", by role key.
+    #[serde(default)]
+    pub role_labels: BTreeMap<String, String>,
 }
 
 impl Default for PimSettings {
@@ -179,6 +492,10 @@ impl Default for PimSettings {
             show_all_eligible: true,
             custom_presets: vec![],
             favorite_role_keys: vec![],
+            elevation_plans: vec![],
+            unconfirmed_role_keys: vec![],
+            role_durations: BTreeMap::new(),
+            role_labels: BTreeMap::new(),
         }
     }
 }
@@ -196,6 +513,29 @@ impl PimSettings {
         self.favorite_role_keys.contains(&role_key.to_string())
     }
 
+    /// Migrate legacy `subscription_id:role_definition_id` favorites to role keys.
+    ///
+    /// Legacy keys that can't be converted are dropped. Returns true if anything changed.
+    pub fn migrate_favorite_keys(&mut self) -> bool {
+        let mut changed = false;
+        let mut keys: Vec<String> = Vec::with_capacity(self.favorite_role_keys.len());
+        for key in &self.favorite_role_keys {
+            let migrated = if key.contains('|') {
+                Some(key.clone())
+            } else {
+                changed = true;
+                migrate_legacy_role_key(key)
+            };
+            if let Some(migrated) = migrated {
+                if !keys.contains(&migrated) {
+                    keys.push(migrated);
+                }
+            }
+        }
+        self.favorite_role_keys = keys;
+        changed
+    }
+
     /// Toggle favorite status for a role key.
     pub fn toggle_favorite(&mut self, role_key: &str) {
         if self.is_favorite(role_key) {
@@ -204,6 +544,177 @@ impl PimSettings {
             self.favorite_role_keys.push(role_key.to_string());
         }
     }
+
+    /// Whether activating the role asks for confirmation first.
+    pub fn confirms_activation(&self, role_key: &str) -> bool {
+        !self.unconfirmed_role_keys.iter().any(|k| k == role_key)
+    }
+
+    /// Stop asking for confirmation before activating the role.
+    pub fn skip_activation_confirmation(&mut self, role_key: &str) {
+        if self.confirms_activation(role_key) {
+            self.unconfirmed_role_keys.push(role_key.to_string());
+        }
+    }
+
+    /// Minutes to activate the role for: its last-used duration, or the default.
+    pub fn duration_for(&self, role_key: &str) -> u32 {
+        self.role_durations
+            .get(role_key)
+            .copied()
+            .unwrap_or(self.default_duration_minutes)
+    }
+
+    /// Remember the duration to activate the role for from now on.
+    pub fn set_duration(&mut self, role_key: &str, minutes: u32) {
+        self.role_durations.insert(role_key.to_string(), minutes);
+    }
+
+    /// The role's custom label, if it has one.
+    pub fn label_for(&self, role_key: &str) -> Option<&str> {
+        self.role_labels.get(role_key).map(String::as_str)
+    }
+
+    /// Label the role, or remove its label if `label` is blank. Long labels
+    /// are cut to [`MAX_ROLE_LABEL_CHARS`].
+    pub fn set_label(&mut self, role_key: &str, label: &str) {
+        let label: String = label.trim().chars().take(MAX_ROLE_LABEL_CHARS).collect();
+        if label.is_empty() {
+            self.role_labels.remove(role_key);
+        } else {
+            self.role_labels.insert(role_key.to_string(), label);
+        }
+    }
+
+    /// Find an elevation plan by name.
+    pub fn plan(&self, name: &str) -> Option<&ElevationPlan> {
+        self.elevation_plans.iter().find(|p| p.name == name)
+    }
+
+    /// Add a plan, replacing any plan with the same name.
+    pub fn save_plan(&mut self, plan: ElevationPlan) {
+        match self
+            .elevation_plans
+            .iter_mut()
+            .find(|p| p.name == plan.name)
+        {
+            Some(existing) => *existing = plan,
+            None => self.elevation_plans.push(plan),
+        }
+    }
+}
+
+/// A named bundle of roles activated together, e.g. "Prod incident".
+///
+/// Plans are stored with favorites, so a synced favorites file shares them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElevationPlan {
+    /// Display name in menu.
+    pub name: String,
+
+    /// Keys of the roles to activate.
+    pub role_keys: Vec<String>,
+
+    /// Duration in minutes, or the default duration if unset.
+    #[serde(default)]
+    pub duration_minutes: Option<u32>,
+
+    /// Justification sent for each role; `{plan}` and `{role}` are replaced.
+    pub justification: String,
+}
+
+impl ElevationPlan {
+    /// Justification for one role of the plan.
+    pub fn justification_for(&self, role_name: &str) -> String {
+        self.justification
+            .replace("{plan}", &self.name)
+            .replace("{role}", role_name)
+    }
+
+    /// Options each role of the plan is activated with.
+    pub fn activation_options(&self) -> ActivationOptions {
+        ActivationOptions {
+            duration_minutes: self.duration_minutes,
+            ..Default::default()
+        }
+    }
+
+    /// Menu text, e.g. "Prod incident (2 roles)".
+    pub fn menu_text(&self) -> String {
+        let noun = if self.role_keys.len() == 1 {
+            "role"
+        } else {
+            "roles"
+        };
+        format!("{} ({} {})", self.name, self.role_keys.len(), noun)
+    }
+}
+
+/// What the user entered when saving the active roles as a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanDraft {
+    pub name: String,
+    /// Duration in minutes, or the default duration if unset.
+    pub duration_minutes: Option<u32>,
+    /// Justification template, or the plan name if unset.
+    pub justification: Option<String>,
+}
+
+impl PlanDraft {
+    /// The plan activating `role_keys` with these details.
+    pub fn into_plan(self, role_keys: Vec<String>) -> ElevationPlan {
+        ElevationPlan {
+            name: self.name,
+            role_keys,
+            duration_minutes: self.duration_minutes,
+            justification: self
+                .justification
+                .unwrap_or_else(|| PLAN_JUSTIFICATION.to_string()),
+        }
+    }
+}
+
+/// Whether roles granted through group membership could be looked up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupLookup {
+    /// Group memberships were read, so group-based roles are included.
+    #[default]
+    Complete,
+    /// The granted permissions don't allow reading group memberships.
+    Unavailable,
+    /// Turned off in settings; no group calls are made.
+    Skipped,
+}
+
+impl GroupLookup {
+    /// Menu note for reduced-permissions mode, if group-based roles are missing.
+    pub fn menu_text(self) -> Option<&'static str> {
+        match self {
+            Self::Complete => None,
+            Self::Unavailable => {
+                Some("Group-based eligibilities unavailable — request Directory.Read.All")
+            }
+            Self::Skipped => Some("Group-based eligibilities off"),
+        }
+    }
+}
+
+/// Progress of an elevation plan being activated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanProgress {
+    /// Name of the plan.
+    pub name: String,
+    /// Roles processed so far, activated or not.
+    pub done: usize,
+    /// Roles in the plan.
+    pub total: usize,
+}
+
+impl PlanProgress {
+    /// Menu text, e.g. "Activating Prod incident (1/2)...".
+    pub fn menu_text(&self) -> String {
+        format!("Activating {} ({}/{})...", self.name, self.done, self.total)
+    }
 }
 
 /// PIM API availability status.
@@ -216,6 +727,8 @@ pub enum PimApiStatus {
     Available,
     /// Permission denied (needs admin consent or role assignment).
     PermissionDenied { message: String },
+    /// The app registration lacks the Azure Service Management permission.
+    ArmPermissionMissing { consent_url: String },
     /// API unreachable or other error.
     Unavailable { error: String },
     /// Currently loading data.
@@ -233,6 +746,62 @@ pub struct ActivationRequest {
 
     /// Requested duration in minutes.
     pub duration_minutes: u32,
+
+    /// Ticket number, for roles whose policy requires one.
+    pub ticket_number: Option<String>,
+
+    /// Start of the activation window; `None` starts now.
+    pub start_time: Option<DateTime<Utc>>,
+}
+
+/// Overrides applied when retrying an activation that failed the role's policy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivationOptions {
+    /// Duration in minutes instead of the default.
+    pub duration_minutes: Option<u32>,
+
+    /// Ticket number to attach to the request.
+    pub ticket_number: Option<String>,
+
+    /// Deactivate the role at this time if it is still active.
+    pub deactivate_at: Option<DateTime<Utc>>,
+
+    /// Start the activation at this time instead of now.
+    pub start_at: Option<DateTime<Utc>>,
+
+    /// Verification completed before this retry; its claims are requested
+    /// with the token, and another challenge fails instead of asking again.
+    pub step_up: Option<StepUp>,
+}
+
+/// An activated role held by anyone in a subscription, for the administrator view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TenantAssignment {
+    /// Assignment schedule instance ID.
+    pub id: String,
+    /// Display name of the user, group or app holding the role, or its ID if unknown.
+    pub principal_name: String,
+    /// "User", "Group" or "ServicePrincipal".
+    pub principal_type: String,
+    pub role_definition_id: String,
+    pub role_name: String,
+    pub subscription_name: String,
+    pub scope: Scope,
+    /// When the activation expires.
+    pub end_time: DateTime<Utc>,
+}
+
+impl TenantAssignment {
+    /// Menu text, e.g. "Alice Smith: Owner - contoso-prod    40m left".
+    pub fn display_text_with_time(&self, clock: &dyn Clock) -> String {
+        format!(
+            "{}: {} - {}    {}",
+            self.principal_name,
+            self.role_name,
+            self.subscription_name,
+            duration::left(self.end_time - clock.now())
+        )
+    }
 }
 
 /// Azure subscription info.
@@ -248,9 +817,133 @@ pub struct Subscription {
     pub state: String,
 }
 
+/// Subscriptions with known active assignments, for polling only those.
+pub fn active_subscriptions(assignments: &[ActiveAssignment]) -> Vec<Subscription> {
+    collect_subscriptions(
+        assignments
+            .iter()
+            .map(|a| (&a.subscription_id, &a.subscription_name, &a.scope)),
+    )
+}
+
+/// Subscriptions of eligible roles, where roles may be activated outside this app.
+pub fn eligible_subscriptions(roles: &[EligibleRole]) -> Vec<Subscription> {
+    collect_subscriptions(
+        roles
+            .iter()
+            .map(|r| (&r.subscription_id, &r.subscription_name, &r.scope)),
+    )
+}
+
+/// One subscription per ID from (subscription ID, name, scope) triples.
+fn collect_subscriptions<'a>(
+    scopes: impl Iterator<Item = (&'a String, &'a String, &'a Scope)>,
+) -> Vec<Subscription> {
+    let mut subscriptions: Vec<Subscription> = Vec::new();
+    for (subscription_id, subscription_name, scope) in scopes {
+        // Scopes above the subscription are named after the scope instead
+        let name = match scope {
+            Scope::Tenant | Scope::ManagementGroup { .. } => None,
+            _ => Some(subscription_name),
+        };
+        match subscriptions
+            .iter_mut()
+            .find(|s| s.subscription_id == *subscription_id)
+        {
+            Some(sub) => {
+                if let Some(name) = name {
+                    sub.display_name = name.clone();
+                }
+            }
+            None => subscriptions.push(Subscription {
+                subscription_id: subscription_id.clone(),
+                display_name: name.unwrap_or(subscription_id).clone(),
+                state: "Enabled".to_string(),
+            }),
+        }
+    }
+    subscriptions
+}
+
+/// Active assignments after a poll, and how they changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolledAssignments {
+    pub assignments: Vec<ActiveAssignment>,
+    /// Assignments that had time left but are gone from Azure: revoked by
+    /// an admin or deactivated in the portal.
+    pub revoked: Vec<ActiveAssignment>,
+    /// Assignments activated outside this app.
+    pub added: Vec<ActiveAssignment>,
+}
+
+/// Whether `a` and `b` are the same activation.
+///
+/// An activation made in the app has its schedule request's ID, while the
+/// poll lists schedule instances, so the role key is compared as well.
+fn same_activation(a: &ActiveAssignment, b: &ActiveAssignment) -> bool {
+    a.id.eq_ignore_ascii_case(&b.id) || a.role_key() == b.role_key()
+}
+
+/// Reconcile `current` with polled assignments, keeping assignments in
+/// subscriptions the poll didn't cover and ones activated moments ago.
+///
+/// A polled assignment that was already known keeps its ID, so notes and
+/// schedules stored under it still apply.
+pub fn reconcile_polled_assignments(
+    current: Vec<ActiveAssignment>,
+    polled: Vec<(String, Vec<ActiveAssignment>)>,
+    now: DateTime<Utc>,
+) -> PolledAssignments {
+    let listed = |assignment: &ActiveAssignment| {
+        polled
+            .iter()
+            .flat_map(|(_, assignments)| assignments)
+            .any(|p| same_activation(assignment, p))
+    };
+
+    let mut result = PolledAssignments::default();
+    let mut known = Vec::new();
+    for assignment in current {
+        let covered = polled
+            .iter()
+            .any(|(id, _)| *id == assignment.subscription_id);
+        let fresh = now - assignment.start_time < Duration::minutes(POLL_GRACE_MINUTES);
+        if !covered {
+            result.assignments.push(assignment);
+        } else if listed(&assignment) {
+            known.push(assignment);
+        } else if fresh {
+            result.assignments.push(assignment);
+        } else if assignment.end_time > now {
+            result.revoked.push(assignment);
+        }
+    }
+    for (_, assignments) in polled {
+        for mut assignment in assignments {
+            if result
+                .assignments
+                .iter()
+                .any(|a| same_activation(a, &assignment))
+            {
+                continue;
+            }
+            match known.iter().find(|k| same_activation(k, &assignment)) {
+                Some(known) => {
+                    assignment.id = known.id.clone();
+                    assignment.assignment_request_id = known.assignment_request_id.clone();
+                }
+                None => result.added.push(assignment.clone()),
+            }
+            result.assignments.push(assignment);
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
 
     #[test]
     fn test_eligible_role_display_text() {
@@ -260,34 +953,344 @@ mod tests {
             role_name: "Contributor".to_string(),
             subscription_id: "sub-id".to_string(),
             subscription_name: "vipps-prod-001".to_string(),
-            scope: "/subscriptions/sub-id".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
             principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
         };
 
         assert_eq!(role.display_text(), "vipps-prod-001 - Contributor");
-        assert_eq!(role.favorites_key(), "sub-id:role-def-id");
+        assert_eq!(role.menu_title(), "Contributor - vipps-prod-001");
+        assert_eq!(role.favorites_key(), "/subscriptions/sub-id|role-def-id");
+    }
+
+    #[test]
+    fn test_eligible_role_tooltip_text() {
+        let mut role = EligibleRole {
+            id: "test-id".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            principal_id: "group-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        };
+        assert_eq!(
+            role.tooltip_text(),
+            "Scope: /subscriptions/sub-id (Subscription)\nRole definition: role-def-id\n\
+             Eligible until: Permanent\nEligible via: you"
+        );
+
+        role.end_time = Some("2025-03-01T12:30:00Z".parse().unwrap());
+        role.granted_via = Some("Platform Admins".to_string());
+        assert!(role
+            .tooltip_text()
+            .ends_with("Eligible until: 2025-03-01 12:30 UTC\nEligible via: Platform Admins"));
+    }
+
+    #[test]
+    fn test_group_grants() {
+        let role = |principal_id: &str, granted_via: Option<&str>| EligibleRole {
+            id: "test-id".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            principal_id: principal_id.to_string(),
+            end_time: None,
+            granted_via: granted_via.map(str::to_string),
+            other_grants: Vec::new(),
+        };
+        let mut roles = vec![
+            role("user-id", None),
+            role("group-1", None),
+            role("group-2", Some("DBAs")),
+            role("group-1", None),
+        ];
+        let group_names = GroupNames::from([
+            ("group-1".to_string(), "Platform Admins".to_string()),
+            ("group-2".to_string(), "Renamed DBAs".to_string()),
+        ]);
+
+        label_group_grants(&mut roles, &group_names);
+        assert_eq!(roles[0].granted_via, None);
+        assert_eq!(roles[1].granted_via.as_deref(), Some("Platform Admins"));
+        // Names expanded by ARM are kept
+        assert_eq!(roles[2].granted_via.as_deref(), Some("DBAs"));
+        assert_eq!(
+            eligibility_report(&roles),
+            "4 eligible roles: 1 direct, 1 via DBAs, 2 via Platform Admins"
+        );
+    }
+
+    #[test]
+    fn test_merge_duplicate_grants() {
+        let role = |scope: &str, principal_id: &str, end_time: Option<&str>| EligibleRole {
+            id: format!("{}-{}", scope, principal_id),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: scope.parse().unwrap(),
+            principal_id: principal_id.to_string(),
+            end_time: end_time.map(|t| t.parse().unwrap()),
+            granted_via: None,
+            other_grants: Vec::new(),
+        };
+        let roles = merge_duplicate_grants(vec![
+            role(
+                "/subscriptions/sub-id",
+                "group-1",
+                Some("2025-03-01T12:00:00Z"),
+            ),
+            role(
+                "/subscriptions/sub-id",
+                "user-id",
+                Some("2025-06-01T12:00:00Z"),
+            ),
+            // The same eligibility seen again under another query
+            role(
+                "/subscriptions/sub-id",
+                "group-1",
+                Some("2025-03-01T12:00:00Z"),
+            ),
+            role("/subscriptions/sub-id/resourceGroups/rg", "group-2", None),
+        ]);
+        assert_eq!(roles.len(), 2);
+        assert_eq!(roles[0].principal_id, "group-1");
+        assert_eq!(roles[0].other_grants.len(), 1);
+        // Eligible as long as the longest grant lasts
+        assert_eq!(
+            roles[0].end_time,
+            Some("2025-06-01T12:00:00Z".parse().unwrap())
+        );
+        assert!(!roles[1].has_several_grants());
+
+        let mut roles = roles;
+        let group_names = GroupNames::from([
+            ("group-1".to_string(), "Group-Ops".to_string()),
+            ("group-2".to_string(), "Group-OnCall".to_string()),
+        ]);
+        label_group_grants(&mut roles, &group_names);
+        assert_eq!(roles[0].eligible_via(), "Group-Ops, you");
+        assert_eq!(
+            eligibility_report(&roles),
+            "2 eligible roles: 1 direct, 1 via Group-OnCall, 1 via Group-Ops; \
+             Contributor - vipps-prod-001 via Group-Ops, you"
+        );
+    }
+
+    #[test]
+    fn test_poll_active_subscriptions() {
+        let assignment = |id: &str, sub: &str, scope: &str, name: &str| ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: sub.to_string(),
+            subscription_name: name.to_string(),
+            scope: scope.parse().unwrap(),
+            start_time: Utc::now() - Duration::minutes(10),
+            end_time: Utc::now() + Duration::hours(1),
+            justification: String::new(),
+            assignment_request_id: None,
+        };
+        let current = vec![
+            assignment(
+                "a",
+                "sub-1",
+                "/providers/Microsoft.Management/managementGroups/mg",
+                "Management Group: mg",
+            ),
+            assignment("b", "sub-1", "/subscriptions/sub-1", "prod"),
+            assignment("c", "sub-2", "/subscriptions/sub-2", "dev"),
+        ];
+
+        let subscriptions = active_subscriptions(&current);
+        assert_eq!(subscriptions.len(), 2);
+        assert_eq!(subscriptions[0].display_name, "prod");
+        assert_eq!(subscriptions[1].subscription_id, "sub-2");
+
+        // sub-1 was polled and "a" ended; sub-2 failed and is kept as it was
+        let polled = vec![(
+            "sub-1".to_string(),
+            vec![assignment("b", "sub-1", "/subscriptions/sub-1", "prod")],
+        )];
+        let merged = reconcile_polled_assignments(current, polled, Utc::now());
+        let ids: Vec<_> = merged.assignments.iter().map(|a| a.id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+    }
+
+    #[test]
+    fn test_reconcile_polled_assignments() {
+        let now = Utc::now();
+        let assignment = |id: &str, started_ago: i64, ends_in: i64| ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: format!("role-{}", id),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-1".to_string(),
+            subscription_name: "prod".to_string(),
+            scope: "/subscriptions/sub-1".parse().unwrap(),
+            start_time: now - Duration::minutes(started_ago),
+            end_time: now + Duration::minutes(ends_in),
+            justification: String::new(),
+            assignment_request_id: None,
+        };
+        let current = vec![
+            assignment("kept", 30, 30),
+            assignment("revoked", 30, 30),
+            // Just activated; ARM doesn't list it yet
+            assignment("fresh", 1, 60),
+            // Ran out since the last poll
+            assignment("expired", 60, 0),
+        ];
+        let polled = vec![(
+            "sub-1".to_string(),
+            vec![assignment("kept", 30, 30), assignment("portal", 2, 58)],
+        )];
+
+        let result = reconcile_polled_assignments(current, polled, now);
+        let ids = |list: &[ActiveAssignment]| -> Vec<String> {
+            list.iter().map(|a| a.id.clone()).collect()
+        };
+        assert_eq!(ids(&result.assignments), vec!["fresh", "kept", "portal"]);
+        assert_eq!(ids(&result.revoked), vec!["revoked"]);
+        assert_eq!(ids(&result.added), vec!["portal"]);
+
+        let roles = vec![EligibleRole {
+            id: "eligibility".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Reader".to_string(),
+            subscription_id: "sub-3".to_string(),
+            subscription_name: "staging".to_string(),
+            scope: "/subscriptions/sub-3".parse().unwrap(),
+            principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        }];
+        let subscriptions = eligible_subscriptions(&roles);
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].display_name, "staging");
+    }
+
+    #[test]
+    fn test_reconcile_matches_request_to_instance() {
+        let now = Utc::now();
+        let assignment = |id: &str, started_ago: i64| ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: "/providers/Microsoft.Authorization/roleDefinitions/owner"
+                .to_string(),
+            role_name: "Owner".to_string(),
+            subscription_id: "sub-1".to_string(),
+            subscription_name: "prod".to_string(),
+            scope: "/subscriptions/sub-1".parse().unwrap(),
+            start_time: now - Duration::minutes(started_ago),
+            end_time: now + Duration::minutes(60),
+            justification: "INC-4711".to_string(),
+            assignment_request_id: Some(format!("{}-request", id)),
+        };
+        let request_id = "/subscriptions/sub-1/providers/Microsoft.Authorization/roleAssignmentScheduleRequests/r1";
+        let instance_id = "/subscriptions/sub-1/providers/Microsoft.Authorization/roleAssignmentScheduleInstances/i1";
+
+        // Activated in the app, listed by the poll under its instance ID
+        for started_ago in [1, 30] {
+            let polled = vec![(
+                "sub-1".to_string(),
+                vec![assignment(instance_id, started_ago)],
+            )];
+            let result = reconcile_polled_assignments(
+                vec![assignment(request_id, started_ago)],
+                polled,
+                now,
+            );
+            assert_eq!(result.assignments.len(), 1);
+            assert_eq!(result.assignments[0].id, request_id);
+            assert!(result.added.is_empty());
+            assert!(result.revoked.is_empty());
+        }
     }
 
     #[test]
     fn test_active_assignment_time_remaining() {
         let now = Utc::now();
+        let clock = MockClock::new(now);
         let assignment = ActiveAssignment {
             id: "test-id".to_string(),
             role_definition_id: "role-def-id".to_string(),
             role_name: "Contributor".to_string(),
             subscription_id: "sub-id".to_string(),
             subscription_name: "vipps-prod-001".to_string(),
-            scope: "/subscriptions/sub-id".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
             start_time: now - Duration::minutes(30),
             end_time: now + Duration::minutes(30),
             justification: "Testing".to_string(),
             assignment_request_id: None,
         };
 
-        assert!(!assignment.is_expired());
-        assert!(assignment.time_remaining().num_minutes() > 0);
-        assert!(assignment.is_expiring_soon(35));
-        assert!(!assignment.is_expiring_soon(25));
+        assert!(!assignment.is_expired(&clock));
+        assert_eq!(assignment.time_remaining(&clock), Duration::minutes(30));
+        assert!(assignment.is_expiring_soon(35, &clock));
+        assert!(!assignment.is_expiring_soon(25, &clock));
+        assert!(assignment.is_expiring_soon(30, &clock));
+        assert_eq!(
+            assignment.display_text_with_time(&clock),
+            "Contributor - vipps-prod-001    30m left"
+        );
+        assert_eq!(
+            assignment.activated_text(&clock),
+            "Activated 30m ago: Testing"
+        );
+        assert_eq!(assignment.compact_text(&clock), "Contributor 30m");
+        assert_eq!(assignment.role_key(), "/subscriptions/sub-id|role-def-id");
+        assert_eq!(assignment.extended_duration_minutes(20), 80);
+
+        // Expires exactly at end_time
+        clock.advance(Duration::minutes(30) - Duration::seconds(1));
+        assert!(!assignment.is_expired(&clock));
+        clock.advance(Duration::seconds(1));
+        assert!(assignment.is_expired(&clock));
+        assert_eq!(assignment.time_remaining(&clock), Duration::zero());
+    }
+
+    #[test]
+    fn test_active_assignment_progress() {
+        let now = Utc::now();
+        let clock = MockClock::new(now);
+        let mut assignment = ActiveAssignment {
+            id: "test-id".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            start_time: now - Duration::minutes(15),
+            end_time: now + Duration::minutes(45),
+            justification: "Testing".to_string(),
+            assignment_request_id: None,
+        };
+
+        assert_eq!(assignment.progress_bar(4, &clock), "▰▰▰▱");
+        assert_eq!(assignment.urgency(5, &clock), ExpiryUrgency::Normal);
+
+        assignment.start_time = now - Duration::minutes(45);
+        assignment.end_time = now + Duration::minutes(15);
+        assert_eq!(assignment.progress_bar(4, &clock), "▰▱▱▱");
+        assert_eq!(assignment.urgency(5, &clock), ExpiryUrgency::Warning);
+        assert_eq!(assignment.urgency(20, &clock), ExpiryUrgency::Critical);
+
+        // Exactly half the window left
+        assignment.start_time = now - Duration::minutes(30);
+        assignment.end_time = now + Duration::minutes(30);
+        assert_eq!(assignment.progress_bar(4, &clock), "▰▰▱▱");
+        assert_eq!(assignment.urgency(5, &clock), ExpiryUrgency::Warning);
+
+        assignment.end_time = now - Duration::minutes(1);
+        assert_eq!(assignment.progress_bar(4, &clock), "▱▱▱▱");
     }
 
     #[test]
@@ -304,6 +1307,133 @@ mod tests {
         assert!(!settings.is_favorite(key));
     }
 
+    #[test]
+    fn test_role_durations() {
+        let mut settings = PimSettings {
+            default_duration_minutes: 90,
+            ..PimSettings::default()
+        };
+        let key = "/subscriptions/a|owner";
+        assert_eq!(settings.duration_for(key), 90);
+
+        settings.set_duration(key, 240);
+        assert_eq!(settings.duration_for(key), 240);
+        assert_eq!(settings.duration_for("/subscriptions/b|owner"), 90);
+    }
+
+    #[test]
+    fn test_role_labels() {
+        let mut settings = PimSettings::default();
+        let key = "/subscriptions/a|owner";
+        assert_eq!(settings.label_for(key), None);
+
+        settings.set_label(key, "  🔥 prod-payments ");
+        assert_eq!(settings.label_for(key), Some("🔥 prod-payments"));
+
+        settings.set_label(key, &"x".repeat(100));
+        assert_eq!(
+            settings.label_for(key).map(|l| l.chars().count()),
+            Some(MAX_ROLE_LABEL_CHARS)
+        );
+
+        settings.set_label(key, " ");
+        assert_eq!(settings.label_for(key), None);
+    }
+
+    #[test]
+    fn test_role_key_is_scope_qualified() {
+        let definition = "/providers/Microsoft.Management/managementGroups/mg/providers/Microsoft.Authorization/roleDefinitions/B24988AC";
+        assert_eq!(
+            role_key(
+                "/providers/Microsoft.Management/managementGroups/MG/",
+                definition
+            ),
+            "/providers/microsoft.management/managementgroups/mg|b24988ac"
+        );
+        assert_ne!(
+            role_key("/subscriptions/a", definition),
+            role_key("/subscriptions/b", definition)
+        );
+    }
+
+    #[test]
+    fn test_migrate_favorite_keys() {
+        let mut settings = PimSettings {
+            favorite_role_keys: vec![
+                "sub-a:/subscriptions/sub-a/providers/Microsoft.Authorization/roleDefinitions/def-1"
+                    .to_string(),
+                "/subscriptions/sub-b|def-2".to_string(),
+                ":def-3".to_string(),
+            ],
+            ..PimSettings::default()
+        };
+
+        assert!(settings.migrate_favorite_keys());
+        assert_eq!(
+            settings.favorite_role_keys,
+            vec!["/subscriptions/sub-a|def-1", "/subscriptions/sub-b|def-2"]
+        );
+        assert!(!settings.migrate_favorite_keys());
+    }
+
+    #[test]
+    fn test_elevation_plans() {
+        let plan = ElevationPlan {
+            name: "Prod incident".to_string(),
+            role_keys: vec!["/subscriptions/a|owner".to_string()],
+            duration_minutes: Some(120),
+            justification: "{plan}: {role}".to_string(),
+        };
+        assert_eq!(plan.justification_for("Owner"), "Prod incident: Owner");
+        assert_eq!(plan.menu_text(), "Prod incident (1 role)");
+
+        let mut settings = PimSettings::default();
+        settings.save_plan(plan.clone());
+        settings.save_plan(ElevationPlan {
+            duration_minutes: None,
+            ..plan
+        });
+        assert_eq!(settings.elevation_plans.len(), 1);
+        assert_eq!(
+            settings.plan("Prod incident").unwrap().duration_minutes,
+            None
+        );
+
+        // Settings saved before plans existed still load
+        let json = r#"{"default_duration_minutes":60,"expiry_warning_minutes":5,
+            "show_all_eligible":true,"custom_presets":[],"favorite_role_keys":[]}"#;
+        let loaded: PimSettings = serde_json::from_str(json).unwrap();
+        assert!(loaded.elevation_plans.is_empty());
+    }
+
+    #[test]
+    fn test_plan_draft_carries_into_activation() {
+        let keys = vec!["/subscriptions/a|owner".to_string()];
+        let mut settings = PimSettings::default();
+        settings.save_plan(
+            PlanDraft {
+                name: "Prod incident".to_string(),
+                duration_minutes: Some(90),
+                justification: Some("INC-42 {role}".to_string()),
+            }
+            .into_plan(keys.clone()),
+        );
+        let plan = settings.plan("Prod incident").unwrap();
+        assert_eq!(plan.role_keys, keys);
+        assert_eq!(plan.activation_options().duration_minutes, Some(90));
+        assert_eq!(plan.justification_for("Owner"), "INC-42 Owner");
+
+        // Without details, the default duration and the plan name
+        let plan = PlanDraft {
+            name: "Deploy".to_string(),
+            duration_minutes: None,
+            justification: None,
+        }
+        .into_plan(keys);
+        assert_eq!(plan.activation_options(), ActivationOptions::default());
+        assert_eq!(plan.justification_for("Owner"), "Deploy");
+    }
+
     #[test]
     fn test_justification_presets() {
         let presets = JustificationPreset::builtin_presets();
@@ -0,0 +1,187 @@
+//! Notes taken during an elevation.
+//!
+//! While a role is active the user can jot down what they changed. Notes are
+//! kept per assignment ID, together with the role, scope and justification,
+//! so they still make sense for an incident writeup after the role expired.
+//! They are stored locally and dropped after `NOTES_RETENTION_DAYS`.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::models::ActiveAssignment;
+
+/// How long notes are kept after the elevation started.
+const NOTES_RETENTION_DAYS: i64 = 90;
+
+/// Longest note text shown in the menu before it is cut off.
+const MENU_NOTE_CHARS: usize = 60;
+
+/// A note with the time it was written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionNote {
+    pub written_at: DateTime<Utc>,
+    pub text: String,
+}
+
+impl SessionNote {
+    /// Menu text, e.g. "14:32  Restarted the app service".
+    pub fn menu_text(&self) -> String {
+        let text: String = if self.text.chars().count() > MENU_NOTE_CHARS {
+            let cut: String = self.text.chars().take(MENU_NOTE_CHARS - 1).collect();
+            format!("{}…", cut.trim_end())
+        } else {
+            self.text.clone()
+        };
+        format!(
+            "{}  {}",
+            self.written_at.with_timezone(&Local).format("%H:%M"),
+            text.replace('\n', " ")
+        )
+    }
+}
+
+/// The notes of one elevation and what was elevated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionNotes {
+    pub role_name: String,
+    pub subscription_name: String,
+    pub scope: String,
+    pub justification: String,
+    pub start_time: DateTime<Utc>,
+    pub notes: Vec<SessionNote>,
+}
+
+/// Session notes of all elevations, keyed by assignment ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotesStore {
+    sessions: HashMap<String, SessionNotes>,
+}
+
+impl NotesStore {
+    /// Add a note to the elevation of `assignment`. Blank notes are ignored.
+    pub fn add(&mut self, assignment: &ActiveAssignment, text: &str, now: DateTime<Utc>) -> bool {
+        let text = text.trim();
+        if text.is_empty() {
+            return false;
+        }
+        self.sessions
+            .entry(assignment.id.clone())
+            .or_insert_with(|| SessionNotes {
+                role_name: assignment.role_name.clone(),
+                subscription_name: assignment.subscription_name.clone(),
+                scope: assignment.scope.to_string(),
+                justification: assignment.justification.clone(),
+                start_time: assignment.start_time,
+                notes: Vec::new(),
+            })
+            .notes
+            .push(SessionNote {
+                written_at: now,
+                text: text.to_string(),
+            });
+        true
+    }
+
+    /// Notes of an assignment, oldest first.
+    pub fn notes_for(&self, assignment_id: &str) -> &[SessionNote] {
+        self.sessions
+            .get(assignment_id)
+            .map_or(&[], |session| session.notes.as_slice())
+    }
+
+    /// Notes of an assignment as plain text for an incident writeup.
+    pub fn writeup(&self, assignment_id: &str) -> Option<String> {
+        let session = self.sessions.get(assignment_id)?;
+        let mut text = format!(
+            "{} on {} ({})\nActivated {}: \"{}\"\n",
+            session.role_name,
+            session.subscription_name,
+            session.scope,
+            session
+                .start_time
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+            session.justification
+        );
+        for note in &session.notes {
+            text.push_str(&format!(
+                "- {} {}\n",
+                note.written_at.with_timezone(&Local).format("%H:%M"),
+                note.text
+            ));
+        }
+        Some(text)
+    }
+
+    /// Drop notes of elevations that started too long ago. Returns true if any were dropped.
+    pub fn prune(&mut self, now: DateTime<Utc>) -> bool {
+        let count = self.sessions.len();
+        let cutoff = now - Duration::days(NOTES_RETENTION_DAYS);
+        self.sessions
+            .retain(|_, session| session.start_time >= cutoff);
+        self.sessions.len() != count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assignment(id: &str, start_time: DateTime<Utc>) -> ActiveAssignment {
+        ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "vipps-prod-001".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            start_time,
+            end_time: start_time + Duration::hours(1),
+            justification: "INC-4711".to_string(),
+            assignment_request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_notes_per_assignment() {
+        let now = Utc::now();
+        let mut store = NotesStore::default();
+        assert!(store.add(&assignment("a", now), "Restarted app service", now));
+        assert!(store.add(&assignment("a", now), " Scaled out to 3 ", now));
+        assert!(!store.add(&assignment("a", now), "  ", now));
+
+        let notes = store.notes_for("a");
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[1].text, "Scaled out to 3");
+        assert!(store.notes_for("b").is_empty());
+
+        let writeup = store.writeup("a").unwrap();
+        assert!(writeup.starts_with("Contributor on vipps-prod-001 (/subscriptions/sub-id)\n"));
+        assert!(writeup.contains("\"INC-4711\""));
+        assert!(writeup.ends_with(" Scaled out to 3\n"));
+        assert_eq!(store.writeup("b"), None);
+    }
+
+    #[test]
+    fn test_notes_menu_text_and_retention() {
+        let now = Utc::now();
+        let note = SessionNote {
+            written_at: now,
+            text: "x".repeat(100),
+        };
+        assert!(note.menu_text().ends_with('…'));
+        assert_eq!(
+            note.menu_text().chars().count(),
+            "14:32  ".len() + MENU_NOTE_CHARS
+        );
+
+        let mut store = NotesStore::default();
+        store.add(&assignment("old", now - Duration::days(91)), "Old", now);
+        store.add(&assignment("new", now), "New", now);
+        assert!(store.prune(now));
+        assert!(store.notes_for("old").is_empty());
+        assert!(!store.prune(now));
+    }
+}
@@ -0,0 +1,460 @@
+//! Role management policy rules and validation errors.
+//!
+//! Activation requests that break a role's policy fail with
+//! `RoleAssignmentRequestPolicyValidationFailed` and the names of the failed
+//! rules. Parsing them lets the UI offer a targeted fix instead of a generic
+//! error.
+
+use serde::Deserialize;
+
+use super::models::JustificationPreset;
+
+/// Error code returned when an activation request breaks the role's policy.
+const POLICY_VALIDATION_FAILED: &str = "RoleAssignmentRequestPolicyValidationFailed";
+
+/// Error code returned when the role is already active at the scope.
+const ROLE_ASSIGNMENT_EXISTS: &str = "RoleAssignmentExists";
+
+/// Policy rule holding the maximum activation duration for end users.
+const END_USER_EXPIRATION_RULE: &str = "Expiration_EndUser_Assignment";
+
+/// Policy rule holding whether end user activations need an approver.
+const END_USER_APPROVAL_RULE: &str = "Approval_EndUser_Assignment";
+
+/// A role management policy rule that an activation request failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyRule {
+    /// The requested duration exceeds the role's maximum.
+    Expiration,
+    /// A justification is required (or was rejected).
+    Justification,
+    /// A ticket number is required.
+    Ticketing,
+    /// Multi-factor authentication is required.
+    Mfa,
+    /// Any other rule, by name.
+    Other(String),
+}
+
+impl PolicyRule {
+    /// Map a rule name from the API error to a rule.
+    fn from_name(name: &str) -> Self {
+        match name {
+            "ExpirationRule" => Self::Expiration,
+            "JustificationRule" => Self::Justification,
+            "TicketingRule" => Self::Ticketing,
+            "MfaRule" | "AuthenticationContextRule" => Self::Mfa,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expiration => write!(f, "maximum duration exceeded"),
+            Self::Justification => write!(f, "justification required"),
+            Self::Ticketing => write!(f, "ticket number required"),
+            Self::Mfa => write!(f, "multi-factor authentication required"),
+            Self::Other(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// How the user can fix a failed policy rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyRemedy {
+    /// Retry with the role's maximum duration in minutes.
+    MaxDuration(u32),
+    /// Retry with a justification entered by the user.
+    EnterJustification,
+    /// Retry with a ticket number entered by the user.
+    EnterTicketNumber,
+    /// Retry with a written justification of at least this many characters.
+    EnterDetailedJustification(u32),
+    /// No corrected retry is possible; explain the rule.
+    Explain(PolicyRule),
+}
+
+/// Result of validating an activation request without submitting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivationCheck {
+    /// The activation would succeed.
+    WouldSucceed,
+    /// The role is already active at this scope.
+    AlreadyActive,
+    /// The request breaks the role's policy.
+    PolicyFailed(Vec<PolicyRule>),
+    /// Azure rejected the request for another reason.
+    Rejected(String),
+}
+
+/// An ARM error response body.
+#[derive(Debug, Deserialize)]
+pub(super) struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ErrorBody {
+    pub code: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Parse the failed policy rules from an activation error response body.
+///
+/// Returns an empty list if the error is not a policy validation failure.
+/// The message has the form `The following policy rules failed: ["ExpirationRule"]`.
+pub fn parse_failed_rules(body: &str) -> Vec<PolicyRule> {
+    let Ok(response) = serde_json::from_str::<ErrorResponse>(body) else {
+        return vec![];
+    };
+    if response.error.code != POLICY_VALIDATION_FAILED {
+        return vec![];
+    }
+
+    let message = &response.error.message;
+    let Some(list) = message.find('[').and_then(|start| {
+        message[start..]
+            .find(']')
+            .map(|end| &message[start + 1..start + end])
+    }) else {
+        return vec![];
+    };
+
+    list.split(',')
+        .map(|name| name.trim().trim_matches('"'))
+        .filter(|name| !name.is_empty())
+        .map(PolicyRule::from_name)
+        .collect()
+}
+
+/// Interpret the response to a validation request.
+///
+/// Authorization failures are not a verdict on the activation and must be
+/// handled before calling this.
+pub fn parse_activation_check(status: u16, body: &str) -> ActivationCheck {
+    if (200..300).contains(&status) {
+        return ActivationCheck::WouldSucceed;
+    }
+    if status == 409 {
+        return ActivationCheck::AlreadyActive;
+    }
+
+    let rules = parse_failed_rules(body);
+    if !rules.is_empty() {
+        return ActivationCheck::PolicyFailed(rules);
+    }
+    match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(response) if response.error.code == ROLE_ASSIGNMENT_EXISTS => {
+            ActivationCheck::AlreadyActive
+        }
+        Ok(response) if !response.error.message.is_empty() => {
+            ActivationCheck::Rejected(response.error.message)
+        }
+        _ => ActivationCheck::Rejected(format!("HTTP {}", status)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PolicyAssignmentListResponse {
+    value: Vec<PolicyAssignment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PolicyAssignment {
+    properties: PolicyAssignmentProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct PolicyAssignmentProperties {
+    #[serde(rename = "effectiveRules", default)]
+    effective_rules: Vec<EffectiveRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EffectiveRule {
+    id: String,
+    #[serde(rename = "maximumDuration")]
+    maximum_duration: Option<String>,
+    setting: Option<ApprovalSetting>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalSetting {
+    #[serde(rename = "isApprovalRequired", default)]
+    is_approval_required: bool,
+}
+
+/// Get the maximum end-user activation duration in minutes from a
+/// `roleManagementPolicyAssignments` list response.
+pub fn parse_max_duration(body: &str) -> Option<u32> {
+    let response: PolicyAssignmentListResponse = serde_json::from_str(body).ok()?;
+    response
+        .value
+        .iter()
+        .flat_map(|a| &a.properties.effective_rules)
+        .find(|rule| rule.id == END_USER_EXPIRATION_RULE)
+        .and_then(|rule| rule.maximum_duration.as_deref())
+        .and_then(parse_iso_duration_minutes)
+}
+
+/// Get whether activations need an approver from a
+/// `roleManagementPolicyAssignments` list response, if it has the rule.
+pub fn parse_approval_required(body: &str) -> Option<bool> {
+    let response: PolicyAssignmentListResponse = serde_json::from_str(body).ok()?;
+    response
+        .value
+        .iter()
+        .flat_map(|a| &a.properties.effective_rules)
+        .find(|rule| rule.id == END_USER_APPROVAL_RULE)
+        .map(|rule| {
+            rule.setting
+                .as_ref()
+                .is_some_and(|setting| setting.is_approval_required)
+        })
+}
+
+/// What a role's management policy says about activating it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RolePolicy {
+    /// Longest an activation may last, in minutes, if the policy limits it.
+    pub max_minutes: Option<u32>,
+    /// Whether each activation needs an approver.
+    pub approval_required: bool,
+}
+
+impl RolePolicy {
+    /// The longest of `minutes` the policy allows.
+    pub fn clamp(&self, minutes: u32) -> u32 {
+        self.max_minutes.map_or(minutes, |max| minutes.min(max))
+    }
+}
+
+/// Get the activation rules from a `roleManagementPolicyAssignments` list
+/// response, if it has either rule.
+pub fn parse_role_policy(body: &str) -> Option<RolePolicy> {
+    let max_minutes = parse_max_duration(body);
+    let approval_required = parse_approval_required(body);
+    if max_minutes.is_none() && approval_required.is_none() {
+        return None;
+    }
+    Some(RolePolicy {
+        max_minutes,
+        approval_required: approval_required.unwrap_or(false),
+    })
+}
+
+/// Parse an ISO 8601 duration such as `PT8H`, `PT90M` or `P1D` into minutes.
+///
+/// `None` if unreadable or too long to count in minutes.
+fn parse_iso_duration_minutes(duration: &str) -> Option<u32> {
+    let rest = duration.strip_prefix('P')?;
+    let (days, time) = match rest.split_once('T') {
+        Some((days, time)) => (days, time),
+        None => (rest, ""),
+    };
+
+    let mut minutes = 0u32;
+    for (part, units) in [
+        (days, &[('D', 24 * 60)][..]),
+        (time, &[('H', 60), ('M', 1)][..]),
+    ] {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+            } else {
+                let (_, factor) = units.iter().find(|(unit, _)| *unit == c)?;
+                let part = number.parse::<u32>().ok()?.checked_mul(*factor)?;
+                minutes = minutes.checked_add(part)?;
+                number.clear();
+            }
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+
+    (minutes > 0).then_some(minutes)
+}
+
+/// The app's own justification standard, stricter than Azure policy.
+///
+/// Set by the user in settings or by the organization through a managed
+/// profile; the stricter of the two applies to every activation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JustificationStandard {
+    /// Minimum justification length in characters.
+    pub min_length: u32,
+}
+
+impl JustificationStandard {
+    /// The stricter of the user's and the organization's minimum length, if any is set.
+    pub fn effective(user: Option<u32>, managed: Option<u32>) -> Option<Self> {
+        user.max(managed).map(|min_length| Self { min_length })
+    }
+
+    /// Whether a justification is written out and long enough.
+    ///
+    /// Preset texts are rejected whatever their length.
+    pub fn accepts(&self, justification: &str, presets: &[JustificationPreset]) -> bool {
+        let justification = justification.trim();
+        let is_preset = presets.iter().any(|p| {
+            p.justification.trim().eq_ignore_ascii_case(justification)
+                || p.label.trim().eq_ignore_ascii_case(justification)
+        });
+        !is_preset && justification.chars().count() >= self.min_length as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_golden, fixture};
+
+    #[test]
+    fn test_golden_error_envelopes_and_policies() {
+        for name in [
+            "arm/error_policy_validation.json",
+            "arm/error_role_assignment_exists.json",
+            "arm/error_authorization_failed.json",
+        ] {
+            let body = fixture(name);
+            let parsed = (
+                parse_failed_rules(&body),
+                parse_activation_check(400, &body),
+            );
+            assert_golden(name, &parsed);
+        }
+
+        let name = "arm/role_management_policy_assignments.json";
+        let body = fixture(name);
+        assert_golden(
+            name,
+            &(parse_max_duration(&body), parse_approval_required(&body)),
+        );
+    }
+
+    #[test]
+    fn test_parse_failed_rules() {
+        let body = r#"{"error":{"code":"RoleAssignmentRequestPolicyValidationFailed","message":"The following policy rules failed: [\"ExpirationRule\",\"TicketingRule\"]"}}"#;
+        assert_eq!(
+            parse_failed_rules(body),
+            vec![PolicyRule::Expiration, PolicyRule::Ticketing]
+        );
+
+        let other = r#"{"error":{"code":"InvalidResourceType","message":"[\"ExpirationRule\"]"}}"#;
+        assert!(parse_failed_rules(other).is_empty());
+        assert!(parse_failed_rules("Bad request").is_empty());
+    }
+
+    #[test]
+    fn test_parse_activation_check() {
+        assert_eq!(
+            parse_activation_check(200, ""),
+            ActivationCheck::WouldSucceed
+        );
+        assert_eq!(
+            parse_activation_check(409, ""),
+            ActivationCheck::AlreadyActive
+        );
+        assert_eq!(
+            parse_activation_check(
+                400,
+                r#"{"error":{"code":"RoleAssignmentRequestPolicyValidationFailed","message":"The following policy rules failed: [\"TicketingRule\"]"}}"#
+            ),
+            ActivationCheck::PolicyFailed(vec![PolicyRule::Ticketing])
+        );
+        assert_eq!(
+            parse_activation_check(
+                400,
+                r#"{"error":{"code":"RoleAssignmentExists","message":"The Role assignment already exists."}}"#
+            ),
+            ActivationCheck::AlreadyActive
+        );
+        assert_eq!(
+            parse_activation_check(
+                400,
+                r#"{"error":{"code":"InvalidScope","message":"Bad scope."}}"#
+            ),
+            ActivationCheck::Rejected("Bad scope.".to_string())
+        );
+        assert_eq!(
+            parse_activation_check(500, "oops"),
+            ActivationCheck::Rejected("HTTP 500".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_max_duration() {
+        let body = r#"{"value":[{"properties":{"effectiveRules":[
+            {"id":"Expiration_Admin_Eligibility","maximumDuration":"P365D"},
+            {"id":"Expiration_EndUser_Assignment","maximumDuration":"PT2H"}
+        ]}}]}"#;
+        assert_eq!(parse_max_duration(body), Some(120));
+        assert_eq!(parse_max_duration(r#"{"value":[]}"#), None);
+    }
+
+    #[test]
+    fn test_parse_approval_required() {
+        let body = r#"{"value":[{"properties":{"effectiveRules":[
+            {"id":"Approval_Admin_Assignment","setting":{"isApprovalRequired":false}},
+            {"id":"Approval_EndUser_Assignment","setting":{"isApprovalRequired":true}}
+        ]}}]}"#;
+        assert_eq!(parse_approval_required(body), Some(true));
+        let without_setting = r#"{"value":[{"properties":{"effectiveRules":[
+            {"id":"Approval_EndUser_Assignment"}
+        ]}}]}"#;
+        assert_eq!(parse_approval_required(without_setting), Some(false));
+        assert_eq!(parse_approval_required(r#"{"value":[]}"#), None);
+    }
+
+    #[test]
+    fn test_parse_role_policy() {
+        let body = r#"{"value":[{"properties":{"effectiveRules":[
+            {"id":"Expiration_EndUser_Assignment","maximumDuration":"PT1H"}
+        ]}}]}"#;
+        let policy = parse_role_policy(body).unwrap();
+        assert_eq!(
+            policy,
+            RolePolicy {
+                max_minutes: Some(60),
+                approval_required: false,
+            }
+        );
+        assert_eq!(policy.clamp(240), 60);
+        assert_eq!(policy.clamp(30), 30);
+        assert_eq!(RolePolicy::default().clamp(240), 240);
+        assert_eq!(parse_role_policy(r#"{"value":[]}"#), None);
+    }
+
+    #[test]
+    fn test_parse_iso_duration_minutes() {
+        assert_eq!(parse_iso_duration_minutes("PT8H"), Some(480));
+        assert_eq!(parse_iso_duration_minutes("PT1H30M"), Some(90));
+        assert_eq!(parse_iso_duration_minutes("P1D"), Some(1440));
+        assert_eq!(parse_iso_duration_minutes("PT"), None);
+        assert_eq!(parse_iso_duration_minutes("8H"), None);
+        // Overflowing durations are unreadable, not wrapped
+        assert_eq!(parse_iso_duration_minutes("P99999999D"), None);
+        assert_eq!(parse_iso_duration_minutes("P2982616DT5H"), None);
+    }
+
+    #[test]
+    fn test_justification_standard() {
+        assert_eq!(JustificationStandard::effective(None, None), None);
+        let standard = JustificationStandard::effective(Some(10), Some(20)).unwrap();
+        assert_eq!(standard.min_length, 20);
+
+        let presets = JustificationPreset::builtin_presets();
+        assert!(!standard.accepts("Debugging", &presets));
+        assert!(!standard.accepts("too short", &presets));
+        assert!(standard.accepts("INC-4711: restore failed backup job", &presets));
+
+        // Long presets are still presets
+        let standard = JustificationStandard { min_length: 5 };
+        assert!(!standard.accepts(" incident investigation ", &presets));
+    }
+}
@@ -0,0 +1,128 @@
+//! Reminders to deactivate a role once the work is done.
+//!
+//! A reminder fires at a time the user picks after activating, independent
+//! of when the role actually expires. Reminders are kept in memory only; a
+//! restart forgets them.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, Utc};
+
+use crate::duration::format_minutes;
+
+/// Reminder delays offered in the menu, in minutes.
+pub const REMINDER_CHOICES: [u32; 4] = [15, 30, 60, 120];
+
+/// Pending deactivation reminders, keyed by role key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeactivationReminders {
+    due: HashMap<String, DateTime<Utc>>,
+}
+
+impl DeactivationReminders {
+    /// Remind about `role_key` in `minutes`, replacing any earlier reminder.
+    pub fn set(&mut self, role_key: &str, minutes: u32, now: DateTime<Utc>) {
+        self.due.insert(
+            role_key.to_string(),
+            now + Duration::minutes(minutes as i64),
+        );
+    }
+
+    /// Drop the reminder for `role_key`. Returns true if there was one.
+    pub fn cancel(&mut self, role_key: &str) -> bool {
+        self.due.remove(role_key).is_some()
+    }
+
+    /// When the reminder for `role_key` fires, if one is set.
+    pub fn due_at(&self, role_key: &str) -> Option<DateTime<Utc>> {
+        self.due.get(role_key).copied()
+    }
+
+    /// Drop reminders for roles no longer active. Returns true if any were dropped.
+    pub fn retain_active(&mut self, active_keys: &[String]) -> bool {
+        let count = self.due.len();
+        self.due.retain(|key, _| active_keys.contains(key));
+        self.due.len() != count
+    }
+
+    /// Remove and return the role keys whose reminders are due.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let mut due: Vec<String> = self
+            .due
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.sort();
+        for key in &due {
+            self.due.remove(key);
+        }
+        due
+    }
+}
+
+/// Reminder delays that fall before the role expires.
+pub fn reminder_choices(time_remaining: Duration) -> Vec<u32> {
+    REMINDER_CHOICES
+        .into_iter()
+        .filter(|minutes| (*minutes as i64) < time_remaining.num_minutes())
+        .collect()
+}
+
+/// Menu text for a reminder delay, e.g. "In 30m".
+pub fn reminder_choice_text(minutes: u32) -> String {
+    format!("In {}", format_minutes(minutes))
+}
+
+/// Menu text for a pending reminder, e.g. "Reminder at 14:32".
+pub fn reminder_due_text(due_at: DateTime<Utc>) -> String {
+    format!(
+        "Reminder at {}",
+        due_at.with_timezone(&Local).format("%H:%M")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminders_fire_once() {
+        let now = Utc::now();
+        let mut reminders = DeactivationReminders::default();
+        reminders.set("a", 15, now);
+        reminders.set("b", 60, now);
+        assert_eq!(reminders.due_at("a"), Some(now + Duration::minutes(15)));
+
+        assert!(reminders.take_due(now).is_empty());
+        assert_eq!(reminders.take_due(now + Duration::minutes(15)), vec!["a"]);
+        assert!(reminders.take_due(now + Duration::minutes(15)).is_empty());
+
+        // Setting again replaces the earlier reminder
+        reminders.set("b", 15, now);
+        assert_eq!(reminders.take_due(now + Duration::minutes(20)), vec!["b"]);
+    }
+
+    #[test]
+    fn test_reminders_cancel_and_retain() {
+        let now = Utc::now();
+        let mut reminders = DeactivationReminders::default();
+        reminders.set("a", 15, now);
+        reminders.set("b", 15, now);
+
+        assert!(reminders.cancel("a"));
+        assert!(!reminders.cancel("a"));
+        assert!(!reminders.retain_active(&["b".to_string()]));
+        assert!(reminders.retain_active(&[]));
+        assert_eq!(reminders, DeactivationReminders::default());
+    }
+
+    #[test]
+    fn test_reminder_choices() {
+        assert_eq!(reminder_choices(Duration::minutes(480)), REMINDER_CHOICES);
+        assert_eq!(reminder_choices(Duration::minutes(45)), vec![15, 30]);
+        assert!(reminder_choices(Duration::minutes(15)).is_empty());
+        assert_eq!(reminder_choice_text(30), "In 30m");
+        assert_eq!(reminder_choice_text(120), "In 2h");
+    }
+}
@@ -0,0 +1,234 @@
+//! Deactivations scheduled for a time of day.
+//!
+//! Unlike reminders, a scheduled deactivation doesn't ask: at the chosen
+//! time the role is deactivated if it is still active, so elevated access
+//! doesn't linger after hours. Schedules are saved, so they survive a
+//! restart; one that came due while the app wasn't running fires at launch.
+//!
+//! Activations can also be scheduled to start later, e.g. for tonight's
+//! maintenance window: Azure holds the request until its start time, and it
+//! can be cancelled until then.
+//!
+//! The optional end-of-day summary is a backstop for roles nobody scheduled:
+//! at a set time it lists the roles still active and offers to deactivate
+//! them all.
+
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime, Days, DurationRound, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+use serde::{Deserialize, Serialize};
+
+/// Pending scheduled deactivations, keyed by role key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledDeactivations {
+    due: HashMap<String, DateTime<Utc>>,
+}
+
+impl ScheduledDeactivations {
+    /// Deactivate `role_key` at `at`, replacing any earlier schedule.
+    pub fn schedule(&mut self, role_key: &str, at: DateTime<Utc>) {
+        self.due.insert(role_key.to_string(), at);
+    }
+
+    /// Drop the schedule for `role_key`. Returns true if there was one.
+    pub fn cancel(&mut self, role_key: &str) -> bool {
+        self.due.remove(role_key).is_some()
+    }
+
+    /// When `role_key` is deactivated, if scheduled.
+    pub fn due_at(&self, role_key: &str) -> Option<DateTime<Utc>> {
+        self.due.get(role_key).copied()
+    }
+
+    /// Drop schedules for roles no longer active. Returns true if any were dropped.
+    pub fn retain_active(&mut self, active_keys: &[String]) -> bool {
+        let count = self.due.len();
+        self.due.retain(|key, _| active_keys.contains(key));
+        self.due.len() != count
+    }
+
+    /// Remove and return the role keys that are due for deactivation.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let mut due: Vec<String> = self
+            .due
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        due.sort();
+        for key in &due {
+            self.due.remove(key);
+        }
+        due
+    }
+}
+
+/// Parse a time of day typed by the user: "17:00", "17.30", "1730" or "17".
+pub fn parse_time_of_day(text: &str) -> Option<NaiveTime> {
+    let text = text.trim();
+    if !text.is_ascii() {
+        return None;
+    }
+    let (hours, minutes) = match text.split_once([':', '.']) {
+        Some((hours, minutes)) => (hours, minutes),
+        None if text.len() > 2 => text.split_at(text.len() - 2),
+        None => (text, "0"),
+    };
+    NaiveTime::from_hms_opt(hours.parse().ok()?, minutes.parse().ok()?, 0)
+}
+
+/// The next time the clock shows `time` after `now`: today, or tomorrow if
+/// that has passed.
+pub fn next_occurrence<Tz: TimeZone>(time: NaiveTime, now: &DateTime<Tz>) -> Option<DateTime<Utc>> {
+    let today = now.date_naive().and_time(time);
+    let at = now.timezone().from_local_datetime(&today).earliest()?;
+    if at > *now {
+        return Some(at.with_timezone(&Utc));
+    }
+    let tomorrow = today.checked_add_days(Days::new(1))?;
+    let at = now.timezone().from_local_datetime(&tomorrow).earliest()?;
+    Some(at.with_timezone(&Utc))
+}
+
+/// Start offered for a scheduled activation: the next full hour after `now`.
+pub fn default_start<Tz: TimeZone>(now: &DateTime<Tz>) -> DateTime<Utc> {
+    let hour = now
+        .clone()
+        .duration_trunc(TimeDelta::hours(1))
+        .unwrap_or_else(|_| now.clone());
+    (hour + TimeDelta::hours(1)).with_timezone(&Utc)
+}
+
+/// Duration of an activation scheduled at `start` that should end the next
+/// time the clock shows `end`, at most `duration_minutes`.
+///
+/// A scheduled role isn't active yet, so it can't get a scheduled
+/// deactivation; it is requested to end at that time instead.
+pub fn scheduled_duration<Tz: TimeZone>(
+    start: &DateTime<Tz>,
+    end: NaiveTime,
+    duration_minutes: u32,
+) -> u32 {
+    next_occurrence(end, start)
+        .map(|end| (end - start.with_timezone(&Utc)).num_minutes())
+        .and_then(|minutes| u32::try_from(minutes).ok())
+        .map_or(duration_minutes, |minutes| minutes.min(duration_minutes))
+}
+
+/// Menu text for a scheduled activation, e.g. "Starts Sat 2 Mar 22:00".
+pub fn scheduled_activation_text(at: DateTime<Utc>) -> String {
+    format!(
+        "Starts {}",
+        at.with_timezone(&Local).format("%a %-d %b %H:%M")
+    )
+}
+
+/// Times offered for the end-of-day summary, as (hour, minute).
+pub const END_OF_DAY_CHOICES: [(u32, u32); 4] = [(16, 0), (17, 0), (17, 30), (18, 0)];
+
+/// Whether the daily summary at `time` is due at `now`: the time passed
+/// today less than an hour ago and the summary wasn't sent today. A summary
+/// missed by more than an hour, e.g. while asleep, waits for the next day.
+pub fn summary_due<Tz: TimeZone>(
+    time: NaiveTime,
+    now: &DateTime<Tz>,
+    sent_on: Option<NaiveDate>,
+) -> bool {
+    let since = now.time() - time;
+    sent_on != Some(now.date_naive()) && since >= TimeDelta::zero() && since < TimeDelta::hours(1)
+}
+
+/// Menu text for a scheduled deactivation, e.g. "Deactivates at 17:00".
+pub fn scheduled_deactivation_text(at: DateTime<Utc>) -> String {
+    format!(
+        "Deactivates at {}",
+        at.with_timezone(&Local).format("%H:%M")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_parse_time_of_day() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0);
+        assert_eq!(parse_time_of_day("17:00"), time(17, 0));
+        assert_eq!(parse_time_of_day(" 9.30 "), time(9, 30));
+        assert_eq!(parse_time_of_day("1745"), time(17, 45));
+        assert_eq!(parse_time_of_day("17"), time(17, 0));
+        assert_eq!(parse_time_of_day("25:00"), None);
+        assert_eq!(parse_time_of_day("5pm"), None);
+        assert_eq!(parse_time_of_day("é5"), None);
+        assert_eq!(parse_time_of_day(""), None);
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let five = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(next_occurrence(five, &now), Some(now + Duration::hours(5)));
+        // A time already passed today means tomorrow
+        assert_eq!(next_occurrence(nine, &now), Some(now + Duration::hours(21)));
+    }
+
+    #[test]
+    fn test_default_start() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 20, 30).unwrap();
+        assert_eq!(
+            default_start(&now),
+            Utc.with_ymd_and_hms(2024, 3, 1, 13, 0, 0).unwrap()
+        );
+        let late = Utc.with_ymd_and_hms(2024, 3, 1, 23, 0, 0).unwrap();
+        assert_eq!(
+            default_start(&late),
+            Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scheduled_duration() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 22, 0, 0).unwrap();
+        let end = |h| NaiveTime::from_hms_opt(h, 30, 0).unwrap();
+        // Ends at the next 23:30 after the start
+        assert_eq!(scheduled_duration(&start, end(23), 480), 90);
+        // 01:30 is the next morning
+        assert_eq!(scheduled_duration(&start, end(1), 480), 210);
+        // Never longer than the role would be active anyway
+        assert_eq!(scheduled_duration(&start, end(8), 480), 480);
+    }
+
+    #[test]
+    fn test_summary_due_once_a_day() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 3, 1, h, m, 0).unwrap();
+        let time = NaiveTime::from_hms_opt(17, 30, 0).unwrap();
+        assert!(!summary_due(time, &at(17, 29), None));
+        assert!(summary_due(time, &at(17, 30), None));
+        assert!(summary_due(time, &at(18, 29), None));
+        assert!(!summary_due(time, &at(18, 30), None));
+        assert!(!summary_due(time, &at(17, 45), Some(at(0, 0).date_naive())));
+        let yesterday = at(0, 0).date_naive().pred_opt();
+        assert!(summary_due(time, &at(17, 45), yesterday));
+    }
+
+    #[test]
+    fn test_scheduled_deactivations_fire_once() {
+        let now = Utc::now();
+        let mut scheduled = ScheduledDeactivations::default();
+        scheduled.schedule("a", now + Duration::minutes(5));
+        scheduled.schedule("b", now + Duration::hours(1));
+        assert_eq!(scheduled.due_at("a"), Some(now + Duration::minutes(5)));
+
+        assert!(scheduled.take_due(now).is_empty());
+        assert_eq!(scheduled.take_due(now + Duration::minutes(5)), vec!["a"]);
+        assert!(scheduled.take_due(now + Duration::minutes(5)).is_empty());
+
+        assert!(!scheduled.retain_active(&["b".to_string()]));
+        assert!(scheduled.cancel("b"));
+        assert_eq!(scheduled, ScheduledDeactivations::default());
+    }
+}
@@ -0,0 +1,390 @@
+//! Azure RBAC scope model.
+//!
+//! Role eligibilities can be granted at the tenant root ("/"), a management
+//! group, a subscription, or below. Scopes are parsed once into [`Scope`] and
+//! written back in canonical form, so grouping, keys, and ARM URLs all agree
+//! on the same path.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ScopeParseError;
+
+/// Management API path segment for management groups.
+const MANAGEMENT_GROUP_PREFIX: &str = "/providers/Microsoft.Management/managementGroups/";
+
+/// The level at which a role is scoped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Scope {
+    /// Tenant root ("/").
+    Tenant,
+    /// A management group.
+    ManagementGroup { name: String },
+    /// A subscription.
+    Subscription { subscription_id: String },
+    /// A resource group within a subscription.
+    ResourceGroup {
+        subscription_id: String,
+        resource_group: String,
+    },
+    /// An individual resource within a subscription.
+    Resource {
+        subscription_id: String,
+        /// Path below the subscription, e.g. `resourceGroups/rg/providers/Microsoft.KeyVault/vaults/kv`.
+        path: String,
+    },
+}
+
+impl Scope {
+    /// Subscription the scope belongs to, if it is at or below one.
+    pub fn subscription_id(&self) -> Option<&str> {
+        match self {
+            Self::Tenant | Self::ManagementGroup { .. } => None,
+            Self::Subscription { subscription_id }
+            | Self::ResourceGroup {
+                subscription_id, ..
+            }
+            | Self::Resource {
+                subscription_id, ..
+            } => Some(subscription_id),
+        }
+    }
+
+    /// Whether the scope sits above any single subscription.
+    pub fn is_above_subscription(&self) -> bool {
+        matches!(self, Self::Tenant | Self::ManagementGroup { .. })
+    }
+
+    /// Whether a role assigned at this scope also applies at `other`.
+    ///
+    /// Which subscriptions a management group holds isn't known here, so a
+    /// management group only contains itself.
+    pub fn contains(&self, other: &Scope) -> bool {
+        if *self == Self::Tenant {
+            return true;
+        }
+        let outer = self.to_string().to_lowercase();
+        let inner = other.to_string().to_lowercase();
+        inner == outer || inner.starts_with(&format!("{}/", outer))
+    }
+
+    /// Name of the scope's level, e.g. "Resource group".
+    pub fn level(&self) -> &'static str {
+        match self {
+            Self::Tenant => "Tenant",
+            Self::ManagementGroup { .. } => "Management group",
+            Self::Subscription { .. } => "Subscription",
+            Self::ResourceGroup { .. } => "Resource group",
+            Self::Resource { .. } => "Resource",
+        }
+    }
+
+    /// Label used to group roles in the menu.
+    ///
+    /// Subscription-level and narrower scopes use the subscription name.
+    pub fn display_name(&self, subscription_name: &str) -> String {
+        match self {
+            Self::Tenant => "Tenant Root".to_string(),
+            Self::ManagementGroup { name } => format!("Management Group: {}", name),
+            _ => subscription_name.to_string(),
+        }
+    }
+
+    /// Path from the tenant root, e.g. "Tenant ▸ vipps-prod-001 ▸ rg-web".
+    ///
+    /// A subscription's management group isn't part of its scope, so it only
+    /// appears for roles scoped to the management group itself.
+    pub fn breadcrumb(&self, subscription_name: &str) -> String {
+        let mut parts = vec!["Tenant"];
+        match self {
+            Self::Tenant => {}
+            Self::ManagementGroup { name } => parts.push(name),
+            Self::Subscription { .. } => parts.push(subscription_name),
+            Self::ResourceGroup { resource_group, .. } => {
+                parts.extend([subscription_name, resource_group.as_str()])
+            }
+            Self::Resource { path, .. } => {
+                parts.push(subscription_name);
+                let segments: Vec<&str> = path.split('/').collect();
+                if let [group_key, group, ..] = segments[..] {
+                    if group_key.eq_ignore_ascii_case("resourceGroups") {
+                        parts.push(group);
+                    }
+                }
+                parts.extend(segments.last());
+            }
+        }
+        parts.join(" ▸ ")
+    }
+
+    /// Build a management API URL for a path under this scope.
+    ///
+    /// `path` must start with "/". The tenant root is written as an empty
+    /// prefix so the result never contains a double slash.
+    pub fn url(&self, base_url: &str, path: &str) -> String {
+        let scope = self.to_string();
+        format!("{}{}{}", base_url, scope.trim_end_matches('/'), path)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tenant => write!(f, "/"),
+            Self::ManagementGroup { name } => write!(f, "{}{}", MANAGEMENT_GROUP_PREFIX, name),
+            Self::Subscription { subscription_id } => {
+                write!(f, "/subscriptions/{}", subscription_id)
+            }
+            Self::ResourceGroup {
+                subscription_id,
+                resource_group,
+            } => write!(
+                f,
+                "/subscriptions/{}/resourceGroups/{}",
+                subscription_id, resource_group
+            ),
+            Self::Resource {
+                subscription_id,
+                path,
+            } => write!(f, "/subscriptions/{}/{}", subscription_id, path),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ScopeParseError;
+
+    /// Parse an ARM scope path. Prefix segments are matched case-insensitively.
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        let invalid = || ScopeParseError(scope.to_string());
+
+        let trimmed = scope.trim_end_matches('/');
+        if trimmed.is_empty() {
+            return if scope.starts_with('/') {
+                Ok(Self::Tenant)
+            } else {
+                Err(invalid())
+            };
+        }
+
+        if let Some(name) = strip_prefix_ignore_case(trimmed, MANAGEMENT_GROUP_PREFIX) {
+            if name.is_empty() || name.contains('/') {
+                return Err(invalid());
+            }
+            return Ok(Self::ManagementGroup {
+                name: name.to_string(),
+            });
+        }
+
+        let segments: Vec<&str> = trimmed
+            .strip_prefix('/')
+            .ok_or_else(invalid)?
+            .split('/')
+            .collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            return Err(invalid());
+        }
+
+        match segments.as_slice() {
+            [subs, id] if subs.eq_ignore_ascii_case("subscriptions") => Ok(Self::Subscription {
+                subscription_id: id.to_string(),
+            }),
+            [subs, id, rgs, rg]
+                if subs.eq_ignore_ascii_case("subscriptions")
+                    && rgs.eq_ignore_ascii_case("resourceGroups") =>
+            {
+                Ok(Self::ResourceGroup {
+                    subscription_id: id.to_string(),
+                    resource_group: rg.to_string(),
+                })
+            }
+            [subs, id, rest @ ..] if subs.eq_ignore_ascii_case("subscriptions") => {
+                Ok(Self::Resource {
+                    subscription_id: id.to_string(),
+                    path: rest.join("/"),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = ScopeParseError;
+
+    fn try_from(scope: String) -> Result<Self, Self::Error> {
+        scope.parse()
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+/// Strip an ASCII prefix case-insensitively (ARM paths are case-insensitive).
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(scope: &str) -> Scope {
+        scope.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(parse("/"), Scope::Tenant);
+        assert_eq!(
+            parse("/providers/Microsoft.Management/managementGroups/platform"),
+            Scope::ManagementGroup {
+                name: "platform".to_string()
+            }
+        );
+        assert_eq!(
+            parse("/subscriptions/sub-id"),
+            Scope::Subscription {
+                subscription_id: "sub-id".to_string()
+            }
+        );
+        assert_eq!(
+            parse("/subscriptions/sub-id/resourceGroups/rg"),
+            Scope::ResourceGroup {
+                subscription_id: "sub-id".to_string(),
+                resource_group: "rg".to_string()
+            }
+        );
+        assert_eq!(
+            parse("/subscriptions/sub-id/resourceGroups/rg/providers/Microsoft.KeyVault/vaults/kv"),
+            Scope::Resource {
+                subscription_id: "sub-id".to_string(),
+                path: "resourceGroups/rg/providers/Microsoft.KeyVault/vaults/kv".to_string()
+            }
+        );
+        assert!(parse("/").is_above_subscription());
+        assert!(!parse("/subscriptions/sub-id").is_above_subscription());
+        assert_eq!(
+            parse("/subscriptions/sub-id/resourceGroups/rg").subscription_id(),
+            Some("sub-id")
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_layouts() {
+        for scope in [
+            "",
+            "subscriptions/sub-id",
+            "/providers/Other/thing",
+            "/subscriptions",
+            "/subscriptions//resourceGroups/rg",
+            "/providers/Microsoft.Management/managementGroups/",
+        ] {
+            assert!(scope.parse::<Scope>().is_err(), "{}", scope);
+        }
+    }
+
+    #[test]
+    fn test_scope_round_trip() {
+        for scope in [
+            "/",
+            "/providers/Microsoft.Management/managementGroups/platform",
+            "/subscriptions/sub-id",
+            "/subscriptions/sub-id/resourceGroups/rg",
+            "/subscriptions/sub-id/resourceGroups/rg/providers/Microsoft.KeyVault/vaults/kv",
+            "/subscriptions/sub-id/providers/Microsoft.Security/pricings/default",
+        ] {
+            assert_eq!(parse(scope).to_string(), scope);
+        }
+
+        // Prefixes are written in canonical case, trailing slashes dropped
+        assert_eq!(
+            parse("/SUBSCRIPTIONS/sub-id/RESOURCEGROUPS/rg/").to_string(),
+            "/subscriptions/sub-id/resourceGroups/rg"
+        );
+
+        let json = serde_json::to_string(&parse("/subscriptions/sub-id")).unwrap();
+        assert_eq!(json, "\"/subscriptions/sub-id\"");
+        assert_eq!(
+            serde_json::from_str::<Scope>(&json).unwrap(),
+            parse("/subscriptions/sub-id")
+        );
+        assert!(serde_json::from_str::<Scope>("\"/bogus\"").is_err());
+    }
+
+    #[test]
+    fn test_scope_url() {
+        assert_eq!(
+            Scope::Tenant.url("https://arm", "/providers/x"),
+            "https://arm/providers/x"
+        );
+        assert_eq!(
+            parse("/subscriptions/sub-id").url("https://arm", "/providers/x"),
+            "https://arm/subscriptions/sub-id/providers/x"
+        );
+    }
+
+    #[test]
+    fn test_scope_display_name() {
+        assert_eq!(Scope::Tenant.display_name("prod"), "Tenant Root");
+        assert_eq!(
+            parse("/providers/Microsoft.Management/managementGroups/platform").display_name("prod"),
+            "Management Group: platform"
+        );
+        assert_eq!(parse("/subscriptions/sub-id").display_name("prod"), "prod");
+        assert_eq!(
+            parse("/subscriptions/sub-id/resourceGroups/rg").level(),
+            "Resource group"
+        );
+    }
+
+    #[test]
+    fn test_scope_contains() {
+        let sub = parse("/subscriptions/sub-1");
+        let rg = parse("/subscriptions/sub-1/resourceGroups/rg-web");
+        let vault = parse(
+            "/subscriptions/sub-1/resourceGroups/RG-WEB/providers/Microsoft.KeyVault/vaults/kv",
+        );
+        let group = parse("/providers/Microsoft.Management/managementGroups/platform");
+        assert!(sub.contains(&rg) && sub.contains(&vault) && rg.contains(&vault));
+        assert!(rg.contains(&rg));
+        assert!(!rg.contains(&sub));
+        assert!(!parse("/subscriptions/sub-10").contains(&parse("/subscriptions/sub-1")));
+        assert!(!parse("/subscriptions/sub-1/resourceGroups/rg").contains(&rg));
+        assert!(parse("/").contains(&group) && parse("/").contains(&vault));
+        assert!(!group.contains(&sub));
+    }
+
+    #[test]
+    fn test_breadcrumb() {
+        assert_eq!(parse("/").breadcrumb("unused"), "Tenant");
+        assert_eq!(
+            parse("/providers/Microsoft.Management/managementGroups/platform").breadcrumb("unused"),
+            "Tenant ▸ platform"
+        );
+        assert_eq!(
+            parse("/subscriptions/sub-1/resourceGroups/rg-web").breadcrumb("vipps-prod-001"),
+            "Tenant ▸ vipps-prod-001 ▸ rg-web"
+        );
+        assert_eq!(
+            parse(
+                "/subscriptions/sub-1/resourceGroups/rg-web/providers/Microsoft.KeyVault/vaults/kv"
+            )
+            .breadcrumb("vipps-prod-001"),
+            "Tenant ▸ vipps-prod-001 ▸ rg-web ▸ kv"
+        );
+    }
+}
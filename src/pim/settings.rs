@@ -1,22 +1,98 @@
 //! PIM settings persistence using local JSON storage.
+//!
+//! Favorites and presets can live at a user-chosen path, e.g. in iCloud Drive
+//! or a synced corporate folder, so they follow the user across machines.
+//! [`SettingsWatcher`] picks up changes synced in from other machines.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
 
-use directories::ProjectDirs;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
 
 use super::models::PimSettings;
+use super::store::data_dir;
 use crate::error::PimError;
 
 /// Settings file name.
 const SETTINGS_FILE: &str = "pim_settings.json";
 
+/// User-chosen settings file, replacing the default location when set.
+static CUSTOM_SETTINGS_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
 /// Get the path to the PIM settings file.
 ///
-/// Returns `~/Library/Application Support/de.malvik.azurepim/pim_settings.json` on macOS.
+/// Returns the custom path if one is set, otherwise
+/// `~/Library/Application Support/de.malvik.azurepim/pim_settings.json` on macOS.
 pub fn get_settings_path() -> Option<PathBuf> {
-    ProjectDirs::from("de", "malvik", "azurepim").map(|dirs| dirs.config_dir().join(SETTINGS_FILE))
+    if let Some(path) = get_custom_settings_path() {
+        return Some(path);
+    }
+    data_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+/// Get the user-chosen settings file, if any.
+pub fn get_custom_settings_path() -> Option<PathBuf> {
+    CUSTOM_SETTINGS_PATH
+        .read()
+        .ok()
+        .and_then(|path| path.clone())
+}
+
+/// Use a custom settings file, or the default location for `None`.
+pub fn set_custom_settings_path(path: Option<PathBuf>) {
+    if let Ok(mut current) = CUSTOM_SETTINGS_PATH.write() {
+        *current = path;
+    }
+}
+
+/// Turn a user-entered location into a settings file path.
+///
+/// Expands a leading `~/`, and a directory gets the default file name.
+/// Returns `None` for empty input, meaning the default location.
+pub fn parse_settings_location(input: &str) -> Option<PathBuf> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    let path = match input.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(input)),
+        None => PathBuf::from(input),
+    };
+    Some(if path.is_dir() {
+        path.join(SETTINGS_FILE)
+    } else {
+        path
+    })
+}
+
+/// Detects changes to the settings file made outside this app.
+///
+/// Polls the modification time, which works for files replaced by sync
+/// clients as well as edited in place.
+#[derive(Debug, Default)]
+pub struct SettingsWatcher {
+    modified: Option<SystemTime>,
+}
+
+impl SettingsWatcher {
+    /// Whether `path` changed since the last poll. A missing file is not a change.
+    pub fn poll(&mut self, path: &Path) -> bool {
+        let Some(modified) = fs::metadata(path).and_then(|m| m.modified()).ok() else {
+            return false;
+        };
+        let changed = self.modified.is_some_and(|seen| seen != modified);
+        self.modified = Some(modified);
+        changed
+    }
+
+    /// Forget the last seen state, e.g. after switching files.
+    pub fn reset(&mut self) {
+        self.modified = None;
+    }
 }
 
 /// Load PIM settings from disk.
@@ -37,9 +113,15 @@ pub fn load_pim_settings() -> PimSettings {
     }
 
     match fs::read_to_string(&path) {
-        Ok(content) => match serde_json::from_str(&content) {
-            Ok(settings) => {
+        Ok(content) => match serde_json::from_str::<PimSettings>(&content) {
+            Ok(mut settings) => {
                 debug!("Loaded PIM settings from {:?}", path);
+                if settings.migrate_favorite_keys() {
+                    info!("Migrated favorites to scope-qualified role keys");
+                    if let Err(e) = save_pim_settings(&settings) {
+                        error!("Failed to save migrated PIM settings: {}", e);
+                    }
+                }
                 settings
             }
             Err(e) => {
@@ -81,6 +163,21 @@ pub fn save_pim_settings(settings: &PimSettings) -> Result<(), PimError> {
     Ok(())
 }
 
+/// Stop using a custom settings file and delete the one at the default location.
+///
+/// A custom file is left alone, since other Macs may still sync it.
+pub fn delete_pim_settings() -> Result<(), PimError> {
+    set_custom_settings_path(None);
+    let Some(path) = get_settings_path() else {
+        return Ok(());
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(PimError::Io(e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +228,58 @@ mod tests {
         // Cleanup
         let _ = fs::remove_file(&test_file);
     }
+
+    #[test]
+    fn test_parse_settings_location() {
+        assert_eq!(parse_settings_location("  "), None);
+        assert_eq!(
+            parse_settings_location("/tmp/none/favorites.json"),
+            Some(PathBuf::from("/tmp/none/favorites.json"))
+        );
+
+        // A folder gets the default file name
+        let temp_dir = env::temp_dir();
+        assert_eq!(
+            parse_settings_location(temp_dir.to_str().unwrap()),
+            Some(temp_dir.join(SETTINGS_FILE))
+        );
+
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(
+                parse_settings_location("~/missing/pim.json"),
+                Some(home.join("missing/pim.json"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_settings_watcher() {
+        let temp_dir = env::temp_dir().join("azurepim_watch_test");
+        let _ = fs::create_dir_all(&temp_dir);
+        let test_file = temp_dir.join("pim_settings.json");
+        let _ = fs::remove_file(&test_file);
+
+        let mut watcher = SettingsWatcher::default();
+        assert!(!watcher.poll(&test_file));
+
+        // The first sighting is the baseline, not a change
+        fs::write(&test_file, "{}").unwrap();
+        assert!(!watcher.poll(&test_file));
+        assert!(!watcher.poll(&test_file));
+
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        fs::File::options()
+            .write(true)
+            .open(&test_file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(watcher.poll(&test_file));
+        assert!(!watcher.poll(&test_file));
+
+        watcher.reset();
+        assert!(!watcher.poll(&test_file));
+
+        let _ = fs::remove_file(&test_file);
+    }
 }
@@ -0,0 +1,401 @@
+//! Persistence of active role assignments and the eligible roles cache.
+//!
+//! Activations outlive the app, so the last known assignments are saved and
+//! shown again at launch until the API confirms them. Eligible roles are
+//! cached so the menu is populated before the first refresh completes.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use directories::ProjectDirs;
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
+
+use super::availability::PimAvailability;
+use super::models::{ActiveAssignment, EligibleRole};
+use super::notes::NotesStore;
+use super::schedule::ScheduledDeactivations;
+use super::subscriptions::SubscriptionNames;
+use crate::clock::Clock;
+use crate::error::PimError;
+
+/// Active assignments file name.
+const ASSIGNMENTS_FILE: &str = "active_assignments.json";
+
+/// Eligible roles cache file name.
+const ROLES_CACHE_FILE: &str = "eligible_roles.json";
+
+/// Subscription names file name.
+const SUBSCRIPTION_NAMES_FILE: &str = "subscription_names.json";
+
+/// Scheduled deactivations file name.
+const SCHEDULED_DEACTIVATIONS_FILE: &str = "scheduled_deactivations.json";
+
+/// Elevation session notes file name.
+const SESSION_NOTES_FILE: &str = "session_notes.json";
+
+/// Subscriptions without PIM access file name.
+const AVAILABILITY_FILE: &str = "pim_availability.json";
+
+/// Owner of the cached roles and assignments file name.
+const CACHE_OWNER_FILE: &str = "cache_owner.json";
+
+/// Files holding an account's roles, assignments and schedules.
+const ACCOUNT_CACHE_FILES: [&str; 5] = [
+    ASSIGNMENTS_FILE,
+    ROLES_CACHE_FILE,
+    SUBSCRIPTION_NAMES_FILE,
+    SCHEDULED_DEACTIVATIONS_FILE,
+    AVAILABILITY_FILE,
+];
+
+/// Eligible roles with the time they were fetched.
+#[derive(Debug, Serialize, Deserialize)]
+struct RolesCache {
+    cached_at: DateTime<Utc>,
+    roles: Vec<EligibleRole>,
+}
+
+/// The account whose roles, assignments and schedules are cached.
+///
+/// Saved next to the caches rather than in the Keychain, so it survives
+/// Sign Out and the next sign-in can tell whether the caches are its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheOwner {
+    pub user_id: String,
+    pub tenant_id: String,
+}
+
+impl CacheOwner {
+    /// Whether `other` is the same account in the same tenant.
+    pub fn is_same_account(&self, other: &CacheOwner) -> bool {
+        self.user_id == other.user_id && self.tenant_id.eq_ignore_ascii_case(&other.tenant_id)
+    }
+    /// Whether this account, signing in, keeps the caches recorded for `recorded`.
+    ///
+    /// Caches without a recorded owner may have been written for another
+    /// account by an older build, so they are kept only if there are none,
+    /// e.g. right after Clear All Data.
+    pub fn keeps_caches_of(&self, recorded: Option<&CacheOwner>, caches_exist: bool) -> bool {
+        match recorded {
+            Some(recorded) => recorded.is_same_account(self),
+            None => !caches_exist,
+        }
+    }
+}
+
+/// Directory replacing the app's config directory when set.
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Keep the app's files in `dir`, e.g. a temporary directory in tests. Has
+/// no effect once a directory was set.
+pub fn set_data_dir(dir: PathBuf) {
+    if let Err(dir) = DATA_DIR.set(dir) {
+        warn!("Data directory already set, ignoring {}", dir.display());
+    }
+}
+
+/// Get the app's config directory.
+///
+/// Returns `~/Library/Application Support/de.malvik.azurepim` on macOS,
+/// unless [`set_data_dir`] chose another.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = DATA_DIR.get() {
+        return Some(dir.clone());
+    }
+    ProjectDirs::from("de", "malvik", "azurepim").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Get the path to a file in the app's config directory.
+fn data_path(file: &str) -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(file))
+}
+
+/// Get the path to the active assignments file.
+///
+/// Returns `~/Library/Application Support/de.malvik.azurepim/active_assignments.json` on macOS.
+pub fn get_assignments_path() -> Option<PathBuf> {
+    data_path(ASSIGNMENTS_FILE)
+}
+
+/// Get the path to the eligible roles cache file.
+pub fn get_roles_cache_path() -> Option<PathBuf> {
+    data_path(ROLES_CACHE_FILE)
+}
+
+/// Load saved active assignments, dropping any that have expired.
+///
+/// Returns an empty list if the file doesn't exist or is corrupted.
+pub fn load_active_assignments(clock: &dyn Clock) -> Vec<ActiveAssignment> {
+    let assignments: Vec<ActiveAssignment> = read_json(ASSIGNMENTS_FILE).unwrap_or_default();
+    let assignments = prune_expired(assignments, clock);
+    debug!("Loaded {} active assignments", assignments.len());
+    assignments
+}
+
+/// Save active assignments to disk.
+pub fn save_active_assignments(assignments: &[ActiveAssignment]) -> Result<(), PimError> {
+    write_json(ASSIGNMENTS_FILE, &assignments)?;
+    debug!("Saved {} active assignments", assignments.len());
+    Ok(())
+}
+
+/// Delete saved active assignments (e.g., by "Clear all data").
+pub fn delete_active_assignments() -> Result<(), PimError> {
+    delete(ASSIGNMENTS_FILE)
+}
+
+/// Load cached eligible roles and when they were fetched.
+pub fn load_roles_cache() -> Option<(Vec<EligibleRole>, DateTime<Utc>)> {
+    let cache: RolesCache = read_json(ROLES_CACHE_FILE)?;
+    debug!("Loaded {} cached eligible roles", cache.roles.len());
+    Some((cache.roles, cache.cached_at))
+}
+
+/// Save eligible roles so they can be shown at the next launch.
+pub fn save_roles_cache(roles: &[EligibleRole], cached_at: DateTime<Utc>) -> Result<(), PimError> {
+    let cache = RolesCache {
+        cached_at,
+        roles: roles.to_vec(),
+    };
+    write_json(ROLES_CACHE_FILE, &cache)?;
+    debug!("Saved {} eligible roles to cache", roles.len());
+    Ok(())
+}
+
+/// Delete cached eligible roles (e.g., by "Clear all data").
+pub fn delete_roles_cache() -> Result<(), PimError> {
+    delete(ROLES_CACHE_FILE)
+}
+
+/// Load subscription names saved by a previous run.
+pub fn load_subscription_names() -> SubscriptionNames {
+    read_json(SUBSCRIPTION_NAMES_FILE).unwrap_or_default()
+}
+
+/// Save subscription names so cached roles show current names at the next launch.
+pub fn save_subscription_names(names: &SubscriptionNames) -> Result<(), PimError> {
+    write_json(SUBSCRIPTION_NAMES_FILE, names)
+}
+
+/// Delete saved subscription names (e.g., by "Clear all data").
+pub fn delete_subscription_names() -> Result<(), PimError> {
+    delete(SUBSCRIPTION_NAMES_FILE)
+}
+
+/// Load deactivations scheduled by a previous run.
+pub fn load_scheduled_deactivations() -> ScheduledDeactivations {
+    read_json(SCHEDULED_DEACTIVATIONS_FILE).unwrap_or_default()
+}
+
+/// Save scheduled deactivations so they fire even after a restart.
+pub fn save_scheduled_deactivations(scheduled: &ScheduledDeactivations) -> Result<(), PimError> {
+    write_json(SCHEDULED_DEACTIVATIONS_FILE, scheduled)
+}
+
+/// Delete scheduled deactivations (e.g., by "Clear all data").
+pub fn delete_scheduled_deactivations() -> Result<(), PimError> {
+    delete(SCHEDULED_DEACTIVATIONS_FILE)
+}
+
+/// Load notes taken during elevations.
+pub fn load_session_notes() -> NotesStore {
+    read_json(SESSION_NOTES_FILE).unwrap_or_default()
+}
+
+/// Save notes taken during elevations.
+pub fn save_session_notes(notes: &NotesStore) -> Result<(), PimError> {
+    write_json(SESSION_NOTES_FILE, notes)
+}
+
+/// Delete notes taken during elevations.
+pub fn delete_session_notes() -> Result<(), PimError> {
+    delete(SESSION_NOTES_FILE)
+}
+
+/// Load the subscriptions that refused earlier scans.
+pub fn load_pim_availability() -> PimAvailability {
+    read_json(AVAILABILITY_FILE).unwrap_or_default()
+}
+
+/// Save the subscriptions that refused scans.
+pub fn save_pim_availability(availability: &PimAvailability) -> Result<(), PimError> {
+    write_json(AVAILABILITY_FILE, availability)
+}
+
+/// Delete the subscriptions that refused scans (e.g., by "Clear all data").
+pub fn delete_pim_availability() -> Result<(), PimError> {
+    delete(AVAILABILITY_FILE)
+}
+
+/// Load the account the caches were saved for, if one was recorded.
+pub fn load_cache_owner() -> Option<CacheOwner> {
+    read_json(CACHE_OWNER_FILE)
+}
+
+/// Record the account the caches are saved for.
+pub fn save_cache_owner(owner: &CacheOwner) -> Result<(), PimError> {
+    write_json(CACHE_OWNER_FILE, owner)
+}
+
+/// Delete the recorded cache owner (e.g., by "Clear all data").
+pub fn delete_cache_owner() -> Result<(), PimError> {
+    delete(CACHE_OWNER_FILE)
+}
+
+/// Whether any of an account's cached roles, assignments or schedules are saved.
+pub fn account_caches_exist() -> bool {
+    ACCOUNT_CACHE_FILES
+        .iter()
+        .filter_map(|file| data_path(file))
+        .any(|path| path.exists())
+}
+
+/// Read a JSON file from the config directory.
+///
+/// Returns `None` if the file doesn't exist or is corrupted.
+fn read_json<T: DeserializeOwned>(file: &str) -> Option<T> {
+    let path = data_path(file)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            error!("Failed to read {:?}: {}", path, e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("Failed to parse {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Write a value as JSON to a file in the config directory.
+fn write_json<T: Serialize>(file: &str, value: &T) -> Result<(), PimError> {
+    let path = data_path(file).ok_or_else(|| {
+        PimError::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Could not determine config directory",
+        ))
+    })?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(PimError::Io)?;
+    }
+
+    let content = serde_json::to_string_pretty(value).map_err(|e| {
+        PimError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            e.to_string(),
+        ))
+    })?;
+
+    fs::write(&path, content).map_err(PimError::Io)
+}
+
+/// Delete a file from the config directory, if it exists.
+fn delete(file: &str) -> Result<(), PimError> {
+    let Some(path) = data_path(file) else {
+        return Ok(());
+    };
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(PimError::Io(e)),
+    }
+}
+
+/// Remove assignments that have expired.
+pub fn prune_expired(
+    assignments: Vec<ActiveAssignment>,
+    clock: &dyn Clock,
+) -> Vec<ActiveAssignment> {
+    assignments
+        .into_iter()
+        .filter(|a| !a.is_expired(clock))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use chrono::Duration;
+
+    fn make_assignment(id: &str, end_in_minutes: i64) -> ActiveAssignment {
+        let now = Utc::now();
+        ActiveAssignment {
+            id: id.to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: "Test Sub".to_string(),
+            scope: "/subscriptions/sub-id".parse().unwrap(),
+            start_time: now - Duration::minutes(10),
+            end_time: now + Duration::minutes(end_in_minutes),
+            justification: "Testing".to_string(),
+            assignment_request_id: None,
+        }
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let clock = MockClock::new(Utc::now());
+        let assignments = vec![make_assignment("live", 30), make_assignment("gone", -1)];
+
+        let pruned = prune_expired(assignments, &clock);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].id, "live");
+
+        clock.advance(Duration::minutes(31));
+        assert!(prune_expired(pruned, &clock).is_empty());
+    }
+
+    #[test]
+    fn test_assignments_roundtrip() {
+        let assignments = vec![make_assignment("live", 30)];
+        let content = serde_json::to_string_pretty(&assignments).unwrap();
+        let loaded: Vec<ActiveAssignment> = serde_json::from_str(&content).unwrap();
+        assert_eq!(loaded, assignments);
+    }
+
+    #[test]
+    fn test_same_account_keeps_caches_after_sign_out() {
+        let owner = CacheOwner {
+            user_id: "user-1".to_string(),
+            tenant_id: "tenant-a".to_string(),
+        };
+        // Recorded at sign-in; Sign Out only clears the Keychain and leaves the file
+        let saved = serde_json::to_string_pretty(&owner).unwrap();
+        let recorded: CacheOwner = serde_json::from_str(&saved).unwrap();
+
+        let same = CacheOwner {
+            tenant_id: "TENANT-A".to_string(),
+            ..owner.clone()
+        };
+        let other_user = CacheOwner {
+            user_id: "user-2".to_string(),
+            ..owner.clone()
+        };
+        let other_tenant = CacheOwner {
+            tenant_id: "tenant-b".to_string(),
+            ..owner.clone()
+        };
+
+        assert!(same.keeps_caches_of(Some(&recorded), true));
+        assert!(!other_user.keeps_caches_of(Some(&recorded), true));
+        assert!(!other_tenant.keeps_caches_of(Some(&recorded), true));
+        // Nothing recorded and nothing cached, e.g. right after Clear All Data
+        assert!(other_user.keeps_caches_of(None, false));
+        // Caches without an owner, e.g. written by an older build
+        assert!(!same.keeps_caches_of(None, true));
+        assert!(!other_user.keeps_caches_of(None, true));
+    }
+}
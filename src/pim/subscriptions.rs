@@ -0,0 +1,151 @@
+//! Subscription display names by subscription ID.
+//!
+//! Roles and assignments carry the subscription name they were fetched with,
+//! and the cached ones shown at launch can predate a rename. Names are kept
+//! here by ID, refreshed at most hourly, and applied whenever roles or
+//! assignments enter the app state, so the menu shows the current name.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::models::{ActiveAssignment, EligibleRole, Subscription};
+
+/// How long fetched names are trusted before they're refreshed.
+const NAMES_TTL_MINUTES: i64 = 60;
+
+/// Known subscription names and when they were fetched.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubscriptionNames {
+    names: HashMap<String, String>,
+    fetched_at: Option<DateTime<Utc>>,
+}
+
+impl SubscriptionNames {
+    /// Replace the names with a fresh subscription list. Returns true if any name changed.
+    pub fn update(&mut self, subscriptions: &[Subscription], now: DateTime<Utc>) -> bool {
+        let names: HashMap<String, String> = subscriptions
+            .iter()
+            .map(|s| (s.subscription_id.clone(), s.display_name.clone()))
+            .collect();
+        self.fetched_at = Some(now);
+        if names == self.names {
+            return false;
+        }
+        self.names = names;
+        true
+    }
+
+    /// The current name of a subscription, if known.
+    pub fn get(&self, subscription_id: &str) -> Option<&str> {
+        self.names.get(subscription_id).map(String::as_str)
+    }
+
+    /// All subscriptions as (ID, name), by name.
+    pub fn sorted(&self) -> Vec<(&str, &str)> {
+        let mut subscriptions: Vec<(&str, &str)> = self
+            .names
+            .iter()
+            .map(|(id, name)| (id.as_str(), name.as_str()))
+            .collect();
+        subscriptions.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+        subscriptions
+    }
+
+    /// Whether the names should be fetched again.
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.fetched_at
+            .is_none_or(|at| now - at >= Duration::minutes(NAMES_TTL_MINUTES))
+    }
+
+    /// Show the current subscription names on roles. Returns true if any changed.
+    pub fn rename_roles(&self, roles: &mut [EligibleRole]) -> bool {
+        let mut changed = false;
+        for role in roles {
+            if let Some(name) = self.get(&role.subscription_id) {
+                let name = role.scope.display_name(name);
+                if role.subscription_name != name {
+                    role.subscription_name = name;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Show the current subscription names on assignments. Returns true if any changed.
+    pub fn rename_assignments(&self, assignments: &mut [ActiveAssignment]) -> bool {
+        let mut changed = false;
+        for assignment in assignments {
+            if let Some(name) = self.get(&assignment.subscription_id) {
+                let name = assignment.scope.display_name(name);
+                if assignment.subscription_name != name {
+                    assignment.subscription_name = name;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(id: &str, name: &str) -> Subscription {
+        Subscription {
+            subscription_id: id.to_string(),
+            display_name: name.to_string(),
+            state: "Enabled".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_names_refresh_hourly() {
+        let now = Utc::now();
+        let mut names = SubscriptionNames::default();
+        assert!(names.is_stale(now));
+
+        assert!(names.update(&[subscription("sub-1", "prod")], now));
+        assert!(!names.update(&[subscription("sub-1", "prod")], now));
+        assert_eq!(names.get("sub-1"), Some("prod"));
+        names.update(
+            &[subscription("sub-1", "prod"), subscription("sub-2", "dev")],
+            now,
+        );
+        assert_eq!(names.sorted(), [("sub-2", "dev"), ("sub-1", "prod")]);
+        assert!(!names.is_stale(now + Duration::minutes(59)));
+        assert!(names.is_stale(now + Duration::minutes(60)));
+    }
+
+    #[test]
+    fn test_rename_roles() {
+        let mut names = SubscriptionNames::default();
+        names.update(&[subscription("sub-id", "prod-renamed")], Utc::now());
+
+        let role = |scope: &str, name: &str| EligibleRole {
+            id: "test-id".to_string(),
+            role_definition_id: "role-def-id".to_string(),
+            role_name: "Contributor".to_string(),
+            subscription_id: "sub-id".to_string(),
+            subscription_name: name.to_string(),
+            scope: scope.parse().unwrap(),
+            principal_id: "principal-id".to_string(),
+            end_time: None,
+            granted_via: None,
+            other_grants: Vec::new(),
+        };
+        let mut roles = vec![
+            role("/subscriptions/sub-id/resourceGroups/rg", "prod"),
+            role("/", "Tenant Root"),
+        ];
+
+        assert!(names.rename_roles(&mut roles));
+        assert_eq!(roles[0].subscription_name, "prod-renamed");
+        // Names of scopes above the subscription don't come from it
+        assert_eq!(roles[1].subscription_name, "Tenant Root");
+        assert!(!names.rename_roles(&mut roles));
+    }
+}
@@ -1,7 +1,145 @@
 //! Application settings management including auto-launch at login.
 
-use anyhow::Result;
-use tracing::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use objc2_foundation::{NSString, NSUserDefaults};
+use tracing::{debug, error, info, warn};
+
+use crate::menubar::state::Settings;
+use crate::pim::data_dir;
+
+/// Settings file name.
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Managed preference key for the organization's minimum justification length.
+const MANAGED_JUSTIFICATION_MIN_LENGTH: &str = "JustificationMinLength";
+
+/// Managed preference keys for the fleet monitoring heartbeat.
+const MANAGED_HEARTBEAT_FILE: &str = "HeartbeatFile";
+const MANAGED_HEARTBEAT_URL: &str = "HeartbeatURL";
+const MANAGED_HEARTBEAT_INTERVAL: &str = "HeartbeatIntervalSeconds";
+
+/// Get the path to the application settings file.
+///
+/// Returns `~/Library/Application Support/de.malvik.azurepim/settings.json` on macOS.
+pub fn get_settings_path() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+/// Load application settings from disk.
+///
+/// Returns default settings if file doesn't exist or is corrupted.
+pub fn load_settings() -> Settings {
+    let path = match get_settings_path() {
+        Some(p) => p,
+        None => {
+            warn!("Could not determine config directory, using default settings");
+            return Settings::default();
+        }
+    };
+
+    if !path.exists() {
+        debug!("Settings file does not exist, using defaults");
+        return Settings::default();
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(settings) => {
+                debug!("Loaded settings from {:?}", path);
+                settings
+            }
+            Err(e) => {
+                error!("Failed to parse settings: {}, using defaults", e);
+                Settings::default()
+            }
+        },
+        Err(e) => {
+            error!("Failed to read settings file: {}, using defaults", e);
+            Settings::default()
+        }
+    }
+}
+
+/// Save application settings to disk.
+pub fn save_settings(settings: &Settings) -> Result<()> {
+    let path = get_settings_path().context("Could not determine config directory")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create config directory")?;
+    }
+
+    let content = serde_json::to_string_pretty(settings).context("Failed to serialize settings")?;
+    fs::write(&path, content).context("Failed to write settings file")?;
+
+    debug!("Saved settings to {:?}", path);
+    Ok(())
+}
+
+/// Minimum justification length set by a configuration profile (MDM), if any.
+///
+/// Only forced values count, so a plain `defaults write` can't stand in for
+/// the organization.
+pub fn managed_justification_min_length() -> Option<u32> {
+    let key = NSString::from_str(MANAGED_JUSTIFICATION_MIN_LENGTH);
+    unsafe {
+        let defaults = NSUserDefaults::standardUserDefaults();
+        if !defaults.objectIsForcedForKey(&key) {
+            return None;
+        }
+        u32::try_from(defaults.integerForKey(&key)).ok()
+    }
+}
+
+/// Heartbeat file, URL and interval in seconds set by a configuration profile (MDM).
+///
+/// As for the justification length, only forced values count. Returns `None`
+/// when neither a file nor a URL is configured.
+pub fn managed_heartbeat() -> Option<(Option<PathBuf>, Option<String>, Option<u64>)> {
+    unsafe {
+        let defaults = NSUserDefaults::standardUserDefaults();
+        let forced_string = |name: &str| {
+            let key = NSString::from_str(name);
+            if !defaults.objectIsForcedForKey(&key) {
+                return None;
+            }
+            defaults
+                .stringForKey(&key)
+                .map(|value| value.to_string())
+                .filter(|value| !value.trim().is_empty())
+        };
+        let file = forced_string(MANAGED_HEARTBEAT_FILE).map(PathBuf::from);
+        let url = forced_string(MANAGED_HEARTBEAT_URL);
+        if file.is_none() && url.is_none() {
+            return None;
+        }
+
+        let key = NSString::from_str(MANAGED_HEARTBEAT_INTERVAL);
+        let interval = if defaults.objectIsForcedForKey(&key) {
+            u64::try_from(defaults.integerForKey(&key)).ok()
+        } else {
+            None
+        };
+        Some((file, url, interval))
+    }
+}
+
+/// The user's preferred languages in order, as BCP 47 codes (e.g. "nb-NO").
+pub fn preferred_languages() -> Vec<String> {
+    let key = NSString::from_str("AppleLanguages");
+    unsafe {
+        NSUserDefaults::standardUserDefaults()
+            .stringArrayForKey(&key)
+            .map(|languages| {
+                (0..languages.count())
+                    .map(|i| languages.objectAtIndex(i).to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
 
 /// Check if the app is set to auto-launch at login.
 #[allow(dead_code)]
@@ -60,6 +198,68 @@ pub fn init_log_directory() -> Result<()> {
     Ok(())
 }
 
+/// Kinds of stored data "Clear All Data" can remove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataCategory {
+    /// Tokens and user info in the Keychain; removing them signs out.
+    Credentials,
+    /// App settings, favorites, presets and elevation plans.
+    Settings,
+    /// Cached roles and subscription names, saved active assignments and
+    /// scheduled deactivations.
+    Caches,
+    /// Notes taken during elevations.
+    SessionNotes,
+    /// Log files and crash reports.
+    Logs,
+}
+
+impl DataCategory {
+    /// All categories, in the order offered.
+    pub const ALL: [Self; 5] = [
+        Self::Credentials,
+        Self::Settings,
+        Self::Caches,
+        Self::SessionNotes,
+        Self::Logs,
+    ];
+
+    /// Checkbox title in the Clear All Data dialog.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Credentials => "Sign-in credentials",
+            Self::Settings => "Settings, favorites and plans",
+            Self::Caches => "Cached roles and assignments",
+            Self::SessionNotes => "Session notes",
+            Self::Logs => "Logs and crash reports",
+        }
+    }
+}
+
+/// Remove the files in the log directory, keeping the directory itself.
+pub fn clear_logs() -> Result<usize> {
+    remove_files_in(&log_directory())
+}
+
+/// Remove the files directly in `dir`. Returns how many were removed.
+fn remove_files_in(dir: &Path) -> Result<usize> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).context("Failed to read log directory"),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() {
+            fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            removed += 1;
+        }
+    }
+    info!("Removed {} files from {:?}", removed, dir);
+    Ok(removed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,4 +269,36 @@ mod tests {
         let path = log_directory();
         assert!(path.to_string_lossy().contains("azurepim"));
     }
+
+    #[test]
+    fn test_remove_files_in_keeps_directories() {
+        let dir = std::env::temp_dir().join(format!("azurepim-logs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("azurepim.log"), "log").unwrap();
+        fs::write(dir.join("crash-1.txt"), "crash").unwrap();
+
+        assert_eq!(remove_files_in(&dir).unwrap(), 2);
+        assert!(dir.join("nested").is_dir());
+        assert_eq!(remove_files_in(&dir.join("missing")).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_settings_missing_fields_use_defaults() {
+        // Settings written by older versions lack the startup options
+        let settings: Settings =
+            serde_json::from_str(r#"{"auto_launch": false, "show_expiry": true}"#).unwrap();
+        assert!(!settings.auto_launch);
+        assert!(settings.restore_session_on_launch);
+        assert!(settings.auto_load_roles);
+        assert!(!settings.notify_session_restored);
+    }
+
+    #[test]
+    fn test_settings_prefetch_roles_alias() {
+        // Older versions stored the auto-load option as a launch-only setting
+        let settings: Settings =
+            serde_json::from_str(r#"{"prefetch_roles_on_launch": false}"#).unwrap();
+        assert!(!settings.auto_load_roles);
+    }
 }